@@ -0,0 +1 @@
+ALTER TABLE "Charts" ADD COLUMN "duration" INTEGER;
@@ -0,0 +1,2 @@
+ALTER TABLE "Scores" ADD COLUMN "hard_random" BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE "Scores" ADD COLUMN "random_seed" INTEGER;
@@ -34,6 +34,9 @@ pub struct ChartEntry {
     pub preview_length: i64,
     pub lwt: i64,
     pub custom_offset: i64,
+    pub radar: Option<String>,
+    /// In milliseconds
+    pub duration: Option<i64>,
 }
 
 pub struct ChallengeEntry {
@@ -75,6 +78,9 @@ pub struct ScoreEntry {
     pub gauge_opt: i64,
     pub mirror: bool,
     pub random: bool,
+    pub hard_random: bool,
+    /// Seed of the random lanes, unsigned 32 bit
+    pub random_seed: Option<i64>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -91,6 +97,8 @@ pub enum SortColumn {
     Effector,
     Date,
     Score,
+    /// Time of the newest score
+    LastPlayed,
 }
 
 impl LocalSongsDb {
@@ -138,7 +146,9 @@ impl LocalSongsDb {
             preview_offset,
             preview_length,
             lwt,
-            custom_offset
+            custom_offset,
+            radar,
+            duration
          FROM Charts"
         )
         .fetch_all(&self.sqlite_pool)
@@ -176,7 +186,9 @@ impl LocalSongsDb {
             preview_offset,
             preview_length,
             lwt,
-            custom_offset
+            custom_offset,
+            radar,
+            duration
          FROM Charts WHERE rowid = ?",
             id
         )
@@ -193,7 +205,7 @@ impl LocalSongsDb {
     ) -> std::result::Result<Vec<i64>, sqlx::Error> {
         let base_query = "SELECT DISTINCT folderId FROM Charts";
         let mut query_builder = sqlx::query_builder::QueryBuilder::new(base_query);
-        if let (SortColumn::Score, _) = order {
+        if let (SortColumn::Score | SortColumn::LastPlayed, _) = order {
             query_builder.push(" LEFT JOIN Scores on Charts.hash = Scores.chart_hash");
         }
         let mut binds = vec![];
@@ -205,6 +217,12 @@ impl LocalSongsDb {
                     query_builder.push(" AND")
                 };
 
+                if let Some(effector) = term.strip_prefix("effector:") {
+                    query_builder.push(" effector LIKE ?");
+                    binds.push(format!("%{effector}%"));
+                    continue;
+                }
+
                 query_builder.push(
                     " (artist LIKE ?
 					 OR title LIKE ?
@@ -250,6 +268,7 @@ impl LocalSongsDb {
             SortColumn::Effector => "effector COLLATE NOCASE",
             SortColumn::Date => "lwt",
             SortColumn::Score => "Scores.score",
+            SortColumn::LastPlayed => "Scores.timestamp",
         });
 
         match order.1 {
@@ -293,12 +312,14 @@ impl LocalSongsDb {
             gauge_opt,
             mirror,
             random,
+            hard_random,
+            random_seed,
         }: ScoreEntry,
     ) -> std::result::Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
         query!("
             INSERT INTO
-			Scores(score,crit,near,early,late,combo,miss,gauge,auto_flags,replay,timestamp,chart_hash,user_name,user_id,local_score,window_perfect,window_good,window_hold,window_miss,window_slam,gauge_type,gauge_opt,mirror,random)
-			VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)",
+			Scores(score,crit,near,early,late,combo,miss,gauge,auto_flags,replay,timestamp,chart_hash,user_name,user_id,local_score,window_perfect,window_good,window_hold,window_miss,window_slam,gauge_type,gauge_opt,mirror,random,hard_random,random_seed)
+			VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)",
             score,
             crit,
             near,
@@ -323,6 +344,8 @@ impl LocalSongsDb {
             gauge_opt,
             mirror,
             random,
+            hard_random,
+            random_seed,
         ).execute(&self.sqlite_pool).await
     }
 
@@ -353,7 +376,9 @@ impl LocalSongsDb {
         preview_offset,
         preview_length,
         lwt,
-        custom_offset
+        custom_offset,
+        radar,
+        duration
      FROM Charts WHERE folderid = ? ORDER BY diff_index DESC",
             id
         )
@@ -385,13 +410,15 @@ impl LocalSongsDb {
             lwt,
             rowid: _,
             custom_offset: _,
+            radar,
+            duration,
         }: ChartEntry,
     ) -> std::result::Result<i64, sqlx::Error> {
         query_scalar!(
             "INSERT INTO Charts(
 			folderid,path,title,artist,title_translit,artist_translit,jacket_path,effector,illustrator,
-			diff_name,diff_shortname,bpm,diff_index,level,hash,preview_file,preview_offset,preview_length,lwt,custom_offset)
-			VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,0) RETURNING rowid",
+			diff_name,diff_shortname,bpm,diff_index,level,hash,preview_file,preview_offset,preview_length,lwt,custom_offset,radar,duration)
+			VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,0,?,?) RETURNING rowid",
             folderid,
             path,
             title,
@@ -410,7 +437,9 @@ impl LocalSongsDb {
             preview_file,
             preview_offset,
             preview_length,
-            lwt
+            lwt,
+            radar,
+            duration
         )
         .fetch_one(&self.sqlite_pool)
         .await
@@ -481,11 +510,13 @@ impl LocalSongsDb {
             lwt,
             rowid: _,
             custom_offset: _,
+            radar,
+            duration,
         }: ChartEntry,
         id: i32,
     ) -> std::result::Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
         query!("UPDATE Charts SET path=?,title=?,artist=?,title_translit=?,artist_translit=?,jacket_path=?,effector=?,illustrator=?,
-			diff_name=?,diff_shortname=?,bpm=?,diff_index=?,level=?,hash=?,preview_file=?,preview_offset=?,preview_length=?,lwt=? WHERE rowid=?",
+			diff_name=?,diff_shortname=?,bpm=?,diff_index=?,level=?,hash=?,preview_file=?,preview_offset=?,preview_length=?,lwt=?,radar=?,duration=? WHERE rowid=?",
             path,
             title,
             artist,
@@ -504,6 +535,8 @@ impl LocalSongsDb {
             preview_offset,
             preview_length,
             lwt,
+            radar,
+            duration,
             id
 
         ).execute(&self.sqlite_pool).await
@@ -596,7 +629,9 @@ impl LocalSongsDb {
         gauge_type,
         gauge_opt,
         mirror,
-        random
+        random,
+        hard_random,
+        random_seed
         FROM Scores WHERE chart_hash=?",
             chart_hash
         )
@@ -610,6 +645,34 @@ impl LocalSongsDb {
             .await
     }
 
+    /// Path and hash of every chart
+    pub async fn get_all_chart_files(&self) -> sqlx::Result<Vec<(String, String)>> {
+        sqlx::query_as("SELECT path, hash FROM Charts")
+            .fetch_all(&self.sqlite_pool)
+            .await
+    }
+
+    /// Paths of all charts with the given hash, copies of a chart in different folders share it
+    pub async fn get_hash_paths(&self, hash: &str) -> sqlx::Result<Vec<String>> {
+        sqlx::query_scalar("SELECT path FROM Charts WHERE hash=?")
+            .bind(hash)
+            .fetch_all(&self.sqlite_pool)
+            .await
+    }
+
+    /// Removes a single copy of a chart, unlike `remove_hash` which removes all of them
+    pub async fn remove_chart_file(
+        &self,
+        path: &str,
+        hash: &str,
+    ) -> sqlx::Result<SqliteQueryResult> {
+        sqlx::query("DELETE FROM Charts WHERE path=? AND hash=?")
+            .bind(path)
+            .bind(hash)
+            .execute(&self.sqlite_pool)
+            .await
+    }
+
     pub async fn get_all_scores(
         &self,
     ) -> std::result::Result<std::vec::Vec<ScoreEntry>, sqlx::Error> {
@@ -640,7 +703,9 @@ impl LocalSongsDb {
         gauge_type,
         gauge_opt,
         mirror,
-        random
+        random,
+        hard_random,
+        random_seed
         FROM Scores",
         )
         .fetch_all(&self.sqlite_pool)
@@ -690,6 +755,17 @@ impl LocalSongsDb {
         }
     }
 
+    pub async fn has_chart_stats(&self, hash: &str) -> sqlx::Result<bool> {
+        let count: i64 = sqlx::query(
+            "SELECT COUNT(*) FROM Charts WHERE hash=? AND radar IS NOT NULL AND duration IS NOT NULL",
+        )
+        .bind(hash)
+        .fetch_one(&self.sqlite_pool)
+        .await?
+        .try_get(0)?;
+        Ok(count > 0)
+    }
+
     pub async fn get_hash_id(&self, hash: &str) -> std::result::Result<Option<i64>, sqlx::Error> {
         query_scalar!("SELECT rowid FROM Charts WHERE hash=?", hash)
             .fetch_optional(&self.sqlite_pool)
@@ -701,4 +777,45 @@ impl LocalSongsDb {
             .execute(&self.sqlite_pool)
             .await
     }
+
+    /// Marks every chart to be imported again by the next scan. The rows are kept so practice
+    /// setups still point at their charts.
+    pub async fn clear_chart_stats(&self) -> sqlx::Result<SqliteQueryResult> {
+        sqlx::query("UPDATE Charts SET radar=NULL, duration=NULL")
+            .execute(&self.sqlite_pool)
+            .await
+    }
+
+    pub async fn get_chart_id(&self, path: &str, hash: &str) -> sqlx::Result<Option<i64>> {
+        sqlx::query_scalar("SELECT rowid FROM Charts WHERE path=? AND hash=?")
+            .bind(path)
+            .bind(hash)
+            .fetch_optional(&self.sqlite_pool)
+            .await
+    }
+
+    /// Hashes of the charts that have scores but aren't in the database, with the number of
+    /// scores, the best score and the time of the newest score of each
+    pub async fn get_orphaned_scores(&self) -> sqlx::Result<Vec<(String, i64, i64, i64)>> {
+        sqlx::query_as(
+            "SELECT chart_hash, COUNT(*), MAX(score), MAX(timestamp) FROM Scores
+            WHERE chart_hash NOT IN (SELECT hash FROM Charts) GROUP BY chart_hash",
+        )
+        .fetch_all(&self.sqlite_pool)
+        .await
+    }
+
+    /// Removes the scores of a chart unless the chart is in the database
+    pub async fn remove_orphaned_scores(&self, hash: &str) -> sqlx::Result<SqliteQueryResult> {
+        sqlx::query(
+            "DELETE FROM Scores WHERE chart_hash=? AND chart_hash NOT IN (SELECT hash FROM Charts)",
+        )
+        .bind(hash)
+        .execute(&self.sqlite_pool)
+        .await
+    }
+
+    pub async fn vacuum(&self) -> sqlx::Result<SqliteQueryResult> {
+        sqlx::query("VACUUM").execute(&self.sqlite_pool).await
+    }
 }
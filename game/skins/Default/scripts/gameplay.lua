@@ -376,8 +376,9 @@ function render(deltaTime)
     if portrait then yshift = draw_banner(deltaTime) end
 
     gfx.Translate(0, yshift - 150 * math.max(introTimer - 1, 0))
-    draw_song_info(deltaTime)
-    draw_score(deltaTime)
+    -- gameplay.hud holds the HUD elements the player chose to show
+    if gameplay.hud.songInfo then draw_song_info(deltaTime) end
+    if gameplay.hud.score then draw_score(deltaTime) end
 
     
     if prevGaugeType ~= nil then
@@ -398,7 +399,7 @@ function render(deltaTime)
             awayGauge.value = 0.0
             gfx.Save()
             gfx.Translate(v * gauge_info.width, 0)
-            draw_gauge(awayGauge)
+            if gameplay.hud.gauge then draw_gauge(awayGauge) end
             gfx.Restore()
             gfx.Translate((1-v) * gauge_info.width, 0)
         else
@@ -408,15 +409,16 @@ function render(deltaTime)
     else
         prevGaugeType = gameplay.gauge.type
     end
-    draw_gauge(gameplay.gauge)
+    if gameplay.hud.gauge then draw_gauge(gameplay.gauge) end
     gfx.Restore()
 
 
-    if earlatePos ~= "off" then
+    if earlatePos ~= "off" and gameplay.hud.hitDeltas then
         draw_earlate(deltaTime)
     end
-    draw_combo(deltaTime)
+    if gameplay.hud.combo then draw_combo(deltaTime) end
     draw_alerts(deltaTime)
+    draw_countdown()
     
     if gameplay.practice_setup ~= nil then
         draw_practice(deltaTime);
@@ -665,10 +667,14 @@ function render_crit_overlay(deltaTime)
             end
 
             -- If the timer is active, flash based on a sin wave
+            -- With reduced flashing the button stays lit instead
             local timer = consoleAnimTimers[i]
             if timer ~= 0 then
                 local image = consoleAnimImages[i]
-                local alpha = (math.sin(timer) * 0.5 + 0.5) * 0.5 + 0.25
+                local alpha = 0.5
+                if not gameplay.reduceFlashing then
+                    alpha = (math.sin(timer) * 0.5 + 0.5) * 0.5 + 0.25
+                end
                 FillColor(255, 255, 255, alpha * 255);
                 DrawRect(image, io_x, io_y, io_w, io_h)
             end
@@ -684,21 +690,23 @@ function render_crit_overlay(deltaTime)
     local cursorHeight = cursorWidth * (ch / cw)
 
     -- draw each laser cursor
-    for i = 1, 2 do
-        local cursor = gameplay.critLine.cursors[i]
-        local pos, skew = cursor.pos, cursor.skew
-
-        -- Add a kinda-perspective effect with a horizontal skew
-        gfx.SkewX(skew)
-
-        -- Draw the colored background with the appropriate laser color
-        FillLaserColor(i, cursor.alpha * 255)
-        DrawRect(laserCursor, pos - cursorWidth / 2, -cursorHeight / 2, cursorWidth, cursorHeight)
-        -- Draw the uncolored overlay on top of the color
-        FillColor(255, 255, 255, cursor.alpha * 255)
-        DrawRect(laserCursorOverlay, pos - cursorWidth / 2, -cursorHeight / 2, cursorWidth, cursorHeight)
-        -- Un-skew
-        gfx.SkewX(-skew)
+    if gameplay.hud.critCursors then
+        for i = 1, 2 do
+            local cursor = gameplay.critLine.cursors[i]
+            local pos, skew = cursor.pos, cursor.skew
+
+            -- Add a kinda-perspective effect with a horizontal skew
+            gfx.SkewX(skew)
+
+            -- Draw the colored background with the appropriate laser color
+            FillLaserColor(i, cursor.alpha * 255)
+            DrawRect(laserCursor, pos - cursorWidth / 2, -cursorHeight / 2, cursorWidth, cursorHeight)
+            -- Draw the uncolored overlay on top of the color
+            FillColor(255, 255, 255, cursor.alpha * 255)
+            DrawRect(laserCursorOverlay, pos - cursorWidth / 2, -cursorHeight / 2, cursorWidth, cursorHeight)
+            -- Un-skew
+            gfx.SkewX(-skew)
+        end
     end
 
     -- We're done, reset graphics stuffs
@@ -1067,6 +1075,16 @@ function change_earlatepos()
     game.SetSkinSetting("earlate_position", earlatePos)
 end
 
+-- -------------------------------------------------------------------------- --
+-- render_lane_cover (optional):                                              --
+-- Called after the track overlay with the screen y of the hidden and sudden  --
+--  cutoffs, use it to draw lane covers over the track.                       --
+-- function render_lane_cover(deltaTime, hiddenCutoffY, suddenCutoffY) end    --
+-- -------------------------------------------------------------------------- --
+-- render_console (optional):                                                 --
+-- Called after render with the held buttons in BT-A to D, FX-L, FX-R order   --
+--  and the laser positions, as { buttons = {...}, lasers = { l, r } }.       --
+-- function render_console(deltaTime, states) end                             --
 -- -------------------------------------------------------------------------- --
 -- render_intro:                                                              --
 local bta_last = false
@@ -1135,6 +1153,23 @@ function near_hit(wasLate) --for updating early/late display
 end
 -- -------------------------------------------------------------------------- --
 -- laser_alert:                                                               --
+local countdownBeats = 0
+
+function countdown(beatsRemaining) --beats left until the song audio starts
+    countdownBeats = beatsRemaining
+end
+
+function draw_countdown()
+    if countdownBeats <= 0 then return end
+    gfx.Save()
+    gfx.BeginPath()
+    FillColor(255,255,255)
+    gfx.TextAlign(gfx.TEXT_ALIGN_CENTER + gfx.TEXT_ALIGN_MIDDLE)
+    gfx.FontSize(120)
+    gfx.Text(tostring(countdownBeats), desw / 2, desh / 2)
+    gfx.Restore()
+end
+
 function laser_alert(isRight) --for starting laser alert animations
     if isRight and alertTimers[2] < -1.5 then
         alertTimers[2] = 1.5
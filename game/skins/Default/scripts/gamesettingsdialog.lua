@@ -221,7 +221,54 @@ function render(deltaTime, visible)
 
 
     gfx.Restore() --draw current tab end
+
+    if SettingsDiag.confirm ~= nil then
+        draw_confirm(SettingsDiag.confirm)
+    end
+
     prevTab = SettingsDiag.currentTab
     prevVis = visible
 
-end
\ No newline at end of file
+end
+
+-- Prompt shown before destructive actions, BT-A/B or FX-L selects confirm, BT-C/D or FX-R cancel
+function draw_confirm(confirm)
+    local width = 400
+    local height = 140
+
+    gfx.BeginPath()
+    gfx.Rect(-diagWidth/2, -diagHeight/2, diagWidth, diagHeight)
+    gfx.FillColor(0, 0, 0, 160)
+    gfx.Fill()
+
+    gfx.BeginPath()
+    gfx.Rect(-width/2, -height/2, width, height)
+    gfx.FillColor(30, 30, 30)
+    gfx.StrokeColor(0, 127, 255)
+    gfx.StrokeWidth(2)
+    gfx.Fill()
+    gfx.Stroke()
+
+    gfx.TextAlign(gfx.TEXT_ALIGN_CENTER + gfx.TEXT_ALIGN_TOP)
+    gfx.FontSize(25)
+    gfx.FillColor(255, 255, 255)
+    gfx.Text(confirm.message, 0, -height/2 + 15)
+
+    local labels = {confirm.confirmLabel, confirm.cancelLabel}
+    for i, label in ipairs(labels) do
+        local selected = (i == 1) == confirm.confirmSelected
+        local x = (i == 1) and -width/4 or width/4
+        gfx.BeginPath()
+        gfx.Rect(x - 70, height/2 - 45, 140, 35)
+        gfx.FillColor(0, 64, 128)
+        if selected then
+            gfx.StrokeColor(255, 127, 0)
+        else
+            gfx.StrokeColor(0, 127, 255)
+        end
+        gfx.Fill()
+        gfx.Stroke()
+        gfx.FillColor(255, 255, 255)
+        gfx.Text(label, x, height/2 - 42)
+    end
+end
@@ -43,6 +43,7 @@ local highScores = nil
 local highestScore = 0
 
 local hasHitStat = false
+local hasHitGraph = false
 local hitHistogram = {}
 local hitMinDelta = 0
 local hitMaxDelta = 0
@@ -221,7 +222,7 @@ result_set = function()
             newScore.score = string.format("%08d", s.score)
             newScore.badge = s.badge
             newScore.badgeDesc = getScoreBadgeDesc(s)
-            newScore.subtext = s.name
+            newScore.subtext = s.name or s.playerName
 
             if highestScore < s.score then
                 highestScore = s.score
@@ -288,6 +289,7 @@ result_set = function()
     end
 
     hasHitStat = result.noteHitStats ~= nil and #result.noteHitStats > 0
+    hasHitGraph = result.hitGraph ~= nil and #result.hitGraph.buckets > 0
 
     hitWindowPerfect = NORMAL_HIT_WINDOW_PERFECT
     hitWindowGood = NORMAL_HIT_WINDOW_GOOD
@@ -590,8 +592,60 @@ draw_hit_graph_lines = function(x, y, w, h)
     gfx.Stroke()
 end
 
+-- Downsampled graph, used when the game doesn't send every hit
+draw_hit_graph_buckets = function(x, y, w, h, xfocus, xscale)
+    if not hasHitGraph or hitDeltaScale == 0.0 then
+        return
+    end
+
+    if xfocus == nil then xfocus = 0 end
+    if xscale == nil then xscale = 1 end
+
+    draw_hit_graph_lines(x, y, w, h)
+
+    local buckets = result.hitGraph.buckets
+    local bucketW = w / #buckets
+
+    for i = 1, #buckets do
+        local bucket = buckets[i]
+        local bucketX = ((i - 0.5)*bucketW - xfocus)*xscale + xfocus
+
+        if 0 <= bucketX then
+            if bucketX > w then break end
+
+            if bucket.hits > 0 then
+                local minY = math.max(0, math.min(h, h/2 + bucket.minDelta * hitDeltaScale))
+                local maxY = math.max(0, math.min(h, h/2 + bucket.maxDelta * hitDeltaScale))
+                local meanY = math.max(0, math.min(h, h/2 + bucket.meanDelta * hitDeltaScale))
+
+                gfx.BeginPath()
+                gfx.FillColor(255, 150, 0, 96)
+                gfx.Rect(x+bucketX-1, y+minY, 2, math.max(1, maxY-minY))
+                gfx.Fill()
+
+                gfx.BeginPath()
+                gfx.FillColor(255, 150, 0, 220)
+                gfx.Rect(x+bucketX-1.5, y+meanY-1.5, 3, 3)
+                gfx.Fill()
+            end
+
+            if bucket.misses > 0 then
+                gfx.BeginPath()
+                gfx.FillColor(255, 0, 0, 160)
+                gfx.Rect(x+bucketX-1, y+h-2*bucket.misses, 2, 2*bucket.misses)
+                gfx.Fill()
+            end
+        end
+    end
+end
+
 draw_hit_graph = function(x, y, w, h, xfocus, xscale)
-    if not hasHitStat or hitDeltaScale == 0.0 then
+    if not hasHitStat then
+        draw_hit_graph_buckets(x, y, w, h, xfocus, xscale)
+        return
+    end
+
+    if hitDeltaScale == 0.0 then
         return
     end
 
@@ -1226,6 +1280,15 @@ draw_guide = function(x, y, w, h, full)
     gfx.BeginPath()
     gfx.FillColor(255, 255, 255, 96)
     gfx.Text(string.format("%s, %s", fxLText, fxRText), x+5, y+h)
+
+    if retry_available then
+        local retryText = "BT-A: select retry"
+        if retry_selected then
+            gfx.FillColor(255, 255, 255, 255)
+            retryText = "Start: retry"
+        end
+        gfx.Text(retryText, x+5, y+h-22)
+    end
 end
 
 draw_icons = function(x, y, w, h)
@@ -0,0 +1,60 @@
+resx,resy = game.GetResolution()
+local selection = 1
+local yoff = 0
+local badges = { "Played", "Clear", "Hard clear", "Full combo", "Perfect" }
+
+-- leaderboard = { title, scores = { { playerName, score, badge, gauge, timestamp, date, source } }, selected }
+
+function render(deltaTime, shown)
+    if not shown or not leaderboard then
+        return
+    end
+    gfx.Save()
+    gfx.ResetTransform()
+    resx,resy = game.GetResolution();
+    gfx.FillColor(0,0,0,200)
+    gfx.FastRect(0,0,resx,resy)
+    gfx.LoadSkinFont("NotoSans-Regular.ttf");
+
+    gfx.BeginPath();
+    gfx.TextAlign(gfx.TEXT_ALIGN_CENTER + gfx.TEXT_ALIGN_TOP);
+    gfx.FontSize(50);
+    gfx.FillColor(255,255,255,255)
+    gfx.Text(leaderboard.title, resx / 2, 20);
+
+    if #leaderboard.scores == 0 then
+        gfx.TextAlign(gfx.TEXT_ALIGN_CENTER + gfx.TEXT_ALIGN_MIDDLE);
+        gfx.FontSize(40);
+        gfx.Text("No scores", resx / 2, resy / 2);
+        gfx.Restore()
+        return
+    end
+
+    gfx.FontSize(30);
+    for i,s in ipairs(leaderboard.scores) do
+        local ypos = resy/2 + 50 * (i - selection - yoff)
+        if ypos > 80 and ypos < resy then
+            if i == selection then
+                gfx.FillColor(255,255,255,255)
+            else
+                gfx.FillColor(255,255,255,128)
+            end
+            gfx.TextAlign(gfx.TEXT_ALIGN_LEFT + gfx.TEXT_ALIGN_MIDDLE);
+            gfx.Text(string.format("#%d", i), resx * 0.05, ypos);
+            gfx.Text(s.playerName, resx * 0.12, ypos);
+            gfx.Text(string.format("%08d", s.score), resx * 0.35, ypos);
+            gfx.Text(badges[s.badge] or "", resx * 0.5, ypos);
+            gfx.Text(string.format("%.1f%%", s.gauge * 100), resx * 0.63, ypos);
+            gfx.Text(s.date, resx * 0.72, ypos);
+            gfx.TextAlign(gfx.TEXT_ALIGN_RIGHT + gfx.TEXT_ALIGN_MIDDLE);
+            gfx.Text(s.source, resx * 0.97, ypos);
+        end
+    end
+    gfx.Restore()
+    yoff = yoff * 0.7
+end
+
+function set_selection(index)
+    yoff = yoff + selection - index
+    selection = index
+end
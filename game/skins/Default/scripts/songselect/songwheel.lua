@@ -21,6 +21,11 @@ local effector       = 0
 local searchText     = gfx.CreateLabel("", 5, 0)
 local searchIndex    = 1
 local jacketFallback = gfx.CreateSkinImage("song_select/loading.png", 0)
+local jacketSong     = nil
+local jacketShown    = nil
+local jacketPrevious = nil
+local jacketFade     = 1
+local frameTime      = 0
 local showGuide      = game.GetSkinSetting("show_guide")
 local legendTable    = {
   { ["labelSingleLine"] = gfx.CreateLabel("DIFFICULTY SELECT", 16, 0), ["labelMultiLine"] = gfx.CreateLabel("DIFFICULTY\nSELECT", 16, 0), ["image"] = gfx.CreateSkinImage("legend/knob-left.png", 0) },
@@ -467,9 +472,30 @@ draw_selected = function(song, x, y, w, h)
     songCache[song.id][selectedDiff] = gfx.LoadImageJob(diff.jacketPath, jacketFallback, 200, 200)
   end
 
-  if songCache[song.id][selectedDiff] then
+  if jacketSong ~= song.id then
+    jacketSong = song.id
+    jacketShown = nil
+    jacketPrevious = nil
+  end
+  -- Keep the previous jacket up until the one of the selected difficulty is decoded, then fade over
+  local image = songCache[song.id][selectedDiff]
+  if image ~= jacketShown and (diff.jacketLoaded or image ~= jacketFallback) then
+    jacketPrevious = jacketShown
+    jacketShown = image
+    jacketFade = 0
+  end
+  jacketFade = math.min(jacketFade + frameTime * 4, 1)
+
+  local imageYPos = y + yMargin + yPadding
+  if jacketPrevious and jacketFade < 1 then
     gfx.BeginPath()
-    gfx.ImageRect(imageXPos, y + yMargin + yPadding, imageSize, imageSize, songCache[song.id][selectedDiff], 1, 0)
+    gfx.ImageRect(imageXPos, imageYPos, imageSize, imageSize, jacketPrevious, 1, 0)
+  end
+  gfx.BeginPath()
+  if jacketShown then
+    gfx.ImageRect(imageXPos, imageYPos, imageSize, imageSize, jacketShown, jacketFade, 0)
+  else
+    gfx.ImageRect(imageXPos, imageYPos, imageSize, imageSize, jacketFallback, 1, 0)
   end
   -- difficulty should take up 1/6 of height, full width, and be centered
   if aspectRatio == "PortraitWidescreen" then
@@ -617,6 +643,7 @@ draw_search = function(x, y, w, h)
 end
 
 render = function(deltaTime)
+  frameTime = deltaTime
   timer = (timer + deltaTime)
   timer = timer % 2
   resx, resy = game.GetResolution();
@@ -671,6 +698,13 @@ render = function(deltaTime)
     local forceText = string.format("Force: %.2f", totalForce)
     gfx.Text(forceText, 0, fullY)
   end
+  if songwheel.versus then
+    gfx.BeginPath()
+    gfx.FillColor(255, 128, 0)
+    gfx.FontSize(20);
+    gfx.TextAlign(gfx.TEXT_ALIGN_RIGHT + gfx.TEXT_ALIGN_BOTTOM)
+    gfx.Text("Versus", fullX, fullY)
+  end
   gfx.LoadSkinFont("NotoSans-Regular.ttf");
   gfx.ResetTransform()
   gfx.ForceRender()
@@ -757,3 +791,20 @@ songs_changed = function(withAll)
     end
   end
 end
+
+-- Defining these gets songwheel.songs updated in place instead of replaced on every change.
+-- Labels and jackets are created the first time a song is drawn, so added songs need nothing.
+songs_added = function(startIndex, songs)
+end
+
+-- Called before the songs are removed from songwheel.songs
+songs_removed = function(indices)
+  for _, i in ipairs(indices) do
+    local song = songwheel.songs[i]
+    if song then songCache[song.id] = nil end
+  end
+end
+
+-- Called after a song got a new score, the cached labels don't show scores
+song_updated = function(index, song)
+end
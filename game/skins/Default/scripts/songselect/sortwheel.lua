@@ -23,10 +23,15 @@ function render(deltaTime, shown)
         if not sortLabels[i] then
            sortLabels[i] = gfx.CreateLabel(f, 40, 0)
         end
+        local alpha = 128
         if i == selection then
-            gfx.FillColor(255,255,255,255)
+            alpha = 255
+        end
+        -- The radio entry is after the sorts
+        if i == #sorts and radio then
+            gfx.FillColor(255,200,0,alpha)
         else
-            gfx.FillColor(255,255,255,128)
+            gfx.FillColor(255,255,255,alpha)
         end
         local xpos = resx - 100 + ((i - selection - yoff) ^ 2) * 1
         local ypos = resy/2 + 50  * (i - selection - yoff)
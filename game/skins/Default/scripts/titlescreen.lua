@@ -19,13 +19,7 @@ local buttons = nil
 local resx, resy = game.GetResolution();
 
 view_update = function()
-    if package.config:sub(1, 1) == '\\' then --windows
-        updateUrl, updateVersion = game.UpdateAvailable()
-        os.execute("start " .. updateUrl)
-    else --unix
-        --TODO: Mac solution
-        os.execute("xdg-open " .. updateUrl)
-    end
+    Menu.OpenUrl(updateUrl)
 end
 
 mouse_clipped = function(x, y, w, h)
@@ -189,6 +183,13 @@ render = function(deltaTime)
     end
     gfx.TextAlign(gfx.TEXT_ALIGN_CENTER + gfx.TEXT_ALIGN_MIDDLE);
     gfx.DrawLabel(label, resx / 2, resy / 2 - 200, resx - 40);
+    if game_version then
+        gfx.BeginPath()
+        gfx.TextAlign(gfx.TEXT_ALIGN_BOTTOM + gfx.TEXT_ALIGN_RIGHT)
+        gfx.FontSize(20)
+        gfx.FillColor(255, 255, 255, 128)
+        gfx.Text(game_version, resx - 5, resy - 5)
+    end
     updateUrl, updateVersion = game.UpdateAvailable()
     if updateUrl then
         gfx.BeginPath()
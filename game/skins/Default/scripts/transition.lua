@@ -7,6 +7,9 @@ function render(deltaTime)
     render_bars(transitionTimer)
     transitionTimer = transitionTimer + deltaTime * 2
     transitionTimer = math.min(transitionTimer,1)
+    if transitionTimer >= 1 then
+        render_progress(progress or 0)
+    end
     return transitionTimer >= 1
 end
 
@@ -35,6 +38,18 @@ function render_bars(progress)
     end
 end
 
+function render_progress(progress)
+    local width = resx / 2
+    gfx.BeginPath()
+    gfx.Rect((resx - width) / 2, resy * 0.8, width, 8)
+    gfx.FillColor(0, 0, 0, 128)
+    gfx.Fill()
+    gfx.BeginPath()
+    gfx.Rect((resx - width) / 2, resy * 0.8, width * progress, 8)
+    gfx.FillColor(255, 255, 255)
+    gfx.Fill()
+end
+
 function reset()
     transitionTimer = 0
     resx, resy = game.GetResolution()
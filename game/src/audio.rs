@@ -1,6 +1,221 @@
-use cpal::Sample as CpalSample;
+use std::{
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use cpal::{
+    traits::{DeviceTrait, HostTrait},
+    Sample as CpalSample,
+};
+use kson_rodio_sources::resample::resample;
+use log::{info, warn};
 use rodio::{cpal, Sample, Source};
 
+use crate::InnerRuscMixer;
+
+static MIXER_SAMPLE_RATE: AtomicU32 = AtomicU32::new(44100);
+static RESAMPLED_SOURCES: AtomicUsize = AtomicUsize::new(0);
+static LIVE_SOURCES: AtomicUsize = AtomicUsize::new(0);
+
+/// Native sample rate of the default output device, mixing at this rate keeps the output stream
+/// from resampling again
+pub fn output_sample_rate() -> u32 {
+    cpal::default_host()
+        .default_output_device()
+        .and_then(|device| device.default_output_config().ok())
+        .map_or(44100, |config| config.sample_rate().0)
+}
+
+pub fn set_mixer_sample_rate(sample_rate: u32) {
+    MIXER_SAMPLE_RATE.store(sample_rate, Ordering::Relaxed);
+}
+
+pub fn mixer_sample_rate() -> u32 {
+    MIXER_SAMPLE_RATE.load(Ordering::Relaxed)
+}
+
+/// Number of sources added at a different sample rate than the mixer
+pub fn resampled_sources() -> usize {
+    RESAMPLED_SOURCES.load(Ordering::Relaxed)
+}
+
+/// Number of sources added with `add_resampled` that have not finished yet
+pub fn live_sources() -> usize {
+    LIVE_SOURCES.load(Ordering::Relaxed)
+}
+
+/// Counts a source as live until it returns `None`, after which it stays finished
+struct CountedSource<S> {
+    source: S,
+    finished: bool,
+}
+
+impl<S> CountedSource<S> {
+    fn new(source: S) -> Self {
+        LIVE_SOURCES.fetch_add(1, Ordering::Relaxed);
+        Self {
+            source,
+            finished: false,
+        }
+    }
+
+    fn finish(&mut self) {
+        if !self.finished {
+            self.finished = true;
+            LIVE_SOURCES.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<S> Drop for CountedSource<S> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for CountedSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let sample = self.source.next();
+        if sample.is_none() {
+            self.finish();
+        }
+        sample
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.finished {
+            (0, Some(0))
+        } else {
+            self.source.size_hint()
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for CountedSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.finished {
+            Some(0)
+        } else {
+            self.source.current_frame_len()
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+/// Sink all game audio is played through. Without an output device it is backed by a thread
+/// that pulls samples at playback speed, so sources still advance and finish as they would when
+/// heard
+pub struct AudioService {
+    sink: rodio::Sink,
+    null: bool,
+}
+
+impl AudioService {
+    /// Opens the default output device, falls back to a null sink if there is none or `no_audio`
+    /// is set. The stream has to be kept alive for as long as audio is played.
+    pub fn open(no_audio: bool) -> (Self, Option<rodio::OutputStream>) {
+        if no_audio {
+            info!("Audio disabled");
+            return (Self::null(), None);
+        }
+
+        let opened = rodio::OutputStream::try_default()
+            .map_err(anyhow::Error::from)
+            .and_then(|(stream, handle)| Ok((stream, rodio::Sink::try_new(&handle)?)));
+
+        match opened {
+            Ok((stream, sink)) => (Self { sink, null: false }, Some(stream)),
+            Err(e) => {
+                warn!("Could not open audio output, continuing without sound: {e}");
+                (Self::null(), None)
+            }
+        }
+    }
+
+    pub fn null() -> Self {
+        let (sink, output) = rodio::Sink::new_idle();
+        drain_in_real_time(output);
+        Self { sink, null: true }
+    }
+
+    /// No audio is heard
+    pub fn is_null(&self) -> bool {
+        self.null
+    }
+
+    pub fn sink(&self) -> &rodio::Sink {
+        &self.sink
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.sink.set_volume(volume);
+    }
+}
+
+fn drain_in_real_time<S: Source<Item = f32> + Send + 'static>(mut output: S) {
+    const TICK: Duration = Duration::from_millis(10);
+    let spawned = std::thread::Builder::new()
+        .name("Null audio".into())
+        .spawn(move || {
+            let mut next = Instant::now();
+            loop {
+                let samples =
+                    output.sample_rate() as u128 * output.channels() as u128 * TICK.as_millis()
+                        / 1000;
+                for _ in 0..samples {
+                    if output.next().is_none() {
+                        return;
+                    }
+                }
+                next += TICK;
+                std::thread::sleep(next.saturating_duration_since(Instant::now()));
+            }
+        });
+
+    if let Err(e) = spawned {
+        warn!("Failed to start null audio thread: {e}");
+    }
+}
+
+pub trait MixerExt {
+    /// Adds a source resampled to the rate of the mixer
+    fn add_resampled<S: Source<Item = f32> + Send + 'static>(&self, source: S);
+}
+
+impl MixerExt for InnerRuscMixer {
+    fn add_resampled<S: Source<Item = f32> + Send + 'static>(&self, source: S) {
+        let sample_rate = mixer_sample_rate();
+        let source = CountedSource::new(source);
+        if source.sample_rate() == sample_rate {
+            self.add(source);
+        } else {
+            RESAMPLED_SOURCES.fetch_add(1, Ordering::Relaxed);
+            log::debug!(
+                "Resampling source from {} Hz to {sample_rate} Hz",
+                source.sample_rate()
+            );
+            self.add(resample(source, sample_rate));
+        }
+    }
+}
+
 pub struct ChartAudio {
     /// twice the length of the song, second half is effected
     samples: Vec<f32>,
@@ -78,3 +293,44 @@ impl Iterator for ChartAudio {
         res
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rodio::Source;
+
+    use super::{live_sources, mixer_sample_rate, AudioService, MixerExt};
+
+    #[test]
+    fn null_sink_plays_in_real_time() {
+        let audio = AudioService::null();
+        assert!(audio.is_null());
+
+        let start = Instant::now();
+        audio.sink().append(
+            rodio::source::Zero::<f32>::new(2, 44100).take_duration(Duration::from_millis(100)),
+        );
+        audio.sink().sleep_until_end();
+        assert!(start.elapsed() >= Duration::from_millis(80));
+    }
+
+    #[test]
+    fn finished_sources_leave_the_mixer() {
+        let sample_rate = mixer_sample_rate();
+        let (controller, mut mixer) = rodio::dynamic_mixer::mixer::<f32>(2, sample_rate);
+        let baseline = live_sources();
+
+        for _ in 0..100 {
+            controller.add_resampled(
+                rodio::source::Zero::<f32>::new(2, sample_rate)
+                    .take_duration(Duration::from_millis(10)),
+            );
+        }
+        assert_eq!(live_sources(), baseline + 100);
+
+        // A second of output is plenty for every source to run out
+        mixer.by_ref().take(sample_rate as usize * 2).for_each(drop);
+        assert_eq!(live_sources(), baseline);
+    }
+}
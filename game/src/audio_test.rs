@@ -19,7 +19,11 @@ use kson_rodio_sources::{
     takeable_source::TakeableSource,
 };
 
-use crate::{scene::Scene, InnerRuscMixer, RuscMixer};
+use crate::{
+    audio::{mixer_sample_rate, MixerExt},
+    scene::Scene,
+    InnerRuscMixer, RuscMixer,
+};
 
 pub struct AudioTest {
     mixer: RuscMixer,
@@ -32,8 +36,9 @@ pub struct AudioTest {
 
 impl AudioTest {
     pub fn new(services: ServiceProvider) -> Self {
-        let (inner_mixer, mixer_source) = dynamic_mixer::mixer(2, 44100);
-        inner_mixer.add(rodio::source::Zero::new(2, 44100));
+        let sample_rate = mixer_sample_rate();
+        let (inner_mixer, mixer_source) = dynamic_mixer::mixer(2, sample_rate);
+        inner_mixer.add(rodio::source::Zero::new(2, sample_rate));
         let [a, b, c] = [channel(), channel(), channel()];
 
         let mixer_source = biquad(mixer_source, Default::default(), Some(a.1));
@@ -44,7 +49,7 @@ impl AudioTest {
 
         services
             .get_required::<InnerRuscMixer>()
-            .add(owned_source(mixer_source, &master_owner));
+            .add_resampled(owned_source(mixer_source, &master_owner));
         let mixer = inner_mixer;
 
         let source = if let Ok(a) = std::fs::File::open("sound_test.wav") {
@@ -55,7 +60,7 @@ impl AudioTest {
         .map(TakeableSource::new);
 
         let source = if let Some((source, real_source)) = source {
-            mixer.add(source.convert_samples());
+            mixer.add_resampled(source.convert_samples());
             Some(real_source)
         } else {
             None
@@ -199,7 +204,7 @@ impl Scene for AudioTest {
                 };
 
             self.mixer
-                .add(owned_source(self.apply_effects(source), &self.source_owner))
+                .add_resampled(owned_source(self.apply_effects(source), &self.source_owner))
         }
 
         Ok(())
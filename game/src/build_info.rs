@@ -0,0 +1,125 @@
+use std::{borrow::Cow, fmt::Display};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// Revision of the API skins and companion clients see. Bump it whenever behaviour visible to
+/// Lua or the companion protocol changes so they can check for it.
+pub const API_REVISION: u32 = 1;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Commit the game was built from, `unknown` outside of a git checkout
+pub const GIT_HASH: &str = env!("RUSC_GIT_HASH");
+
+/// Semver version with the commit as build metadata, e.g. `0.1.0+1a2b3c4`
+pub fn version() -> String {
+    format!("{VERSION}+{GIT_HASH}")
+}
+
+/// Optional parts of the game compiled into this build
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Features {
+    pub api_revision: u32,
+    pub video: bool,
+    pub system_fonts: bool,
+    pub folder_provider: bool,
+    pub portable: bool,
+}
+
+impl Features {
+    pub fn current() -> Self {
+        Self {
+            api_revision: API_REVISION,
+            video: cfg!(feature = "video"),
+            system_fonts: cfg!(feature = "system-fonts"),
+            folder_provider: cfg!(feature = "folder-provider"),
+            portable: cfg!(feature = "portable"),
+        }
+    }
+
+    fn enabled(&self) -> Vec<&'static str> {
+        [
+            ("video", self.video),
+            ("system-fonts", self.system_fonts),
+            ("folder-provider", self.folder_provider),
+            ("portable", self.portable),
+        ]
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    pub version: Cow<'static, str>,
+    pub git_hash: Cow<'static, str>,
+    pub platform: Cow<'static, str>,
+    pub features: Features,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: VERSION.into(),
+            git_hash: GIT_HASH.into(),
+            platform: std::env::consts::OS.into(),
+            features: Features::current(),
+        }
+    }
+}
+
+impl Display for BuildInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let features = self.features.enabled();
+        write!(
+            f,
+            "rusc {}+{} ({}), API revision {}, features: {}",
+            self.version,
+            self.git_hash,
+            self.platform,
+            self.features.api_revision,
+            if features.is_empty() {
+                "none".to_string()
+            } else {
+                features.join(", ")
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuildInfo, Features};
+
+    #[test]
+    fn build_info_summary() {
+        let mut info = BuildInfo {
+            version: "1.2.3".into(),
+            git_hash: "abc1234".into(),
+            platform: "linux".into(),
+            features: Features {
+                api_revision: 4,
+                video: false,
+                system_fonts: false,
+                folder_provider: false,
+                portable: false,
+            },
+        };
+        assert_eq!(
+            info.to_string(),
+            "rusc 1.2.3+abc1234 (linux), API revision 4, features: none"
+        );
+
+        info.features.video = true;
+        info.features.portable = true;
+        assert_eq!(
+            info.to_string(),
+            "rusc 1.2.3+abc1234 (linux), API revision 4, features: video, portable"
+        );
+    }
+}
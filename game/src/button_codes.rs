@@ -1,7 +1,6 @@
 use std::{
     borrow::BorrowMut,
     collections::HashMap,
-    ops::{AddAssign, SubAssign},
     sync::mpsc::{channel, Receiver, Sender},
     time::{Duration, SystemTime},
 };
@@ -16,15 +15,18 @@ use kson::{BtLane, Side};
 pub struct RuscFilter {
     button_map: HashMap<u32, Button>,
     axis_map: HashMap<u32, (Axis, f32)>,
-    offset: Duration,
-    offset_neg: bool,
-    offset_rx: Receiver<i32>,
+    global_offset: f64,
+    device_offsets: DeviceOffsets,
+    offset_rx: Receiver<(f64, DeviceOffsets)>,
 }
 
 pub struct CustomBindingFilter;
 
 impl RuscFilter {
-    pub fn new(offset: i32) -> (Self, Sender<i32>) {
+    pub fn new(
+        global_offset: f64,
+        device_offsets: DeviceOffsets,
+    ) -> (Self, Sender<(f64, DeviceOffsets)>) {
         let (offset_tx, offset_rx) = channel();
         (
             Self {
@@ -42,8 +44,8 @@ impl RuscFilter {
                     (1 << 16, (Axis::LeftStickX, 1.0)),
                     (1 << 16 | 1, (Axis::RightStickX, -1.0)),
                 ]),
-                offset: Duration::from_millis(offset.unsigned_abs() as _),
-                offset_neg: offset < 0,
+                global_offset,
+                device_offsets,
                 offset_rx,
             },
             offset_tx,
@@ -51,9 +53,9 @@ impl RuscFilter {
     }
 
     pub fn update(&mut self) {
-        while let Ok(new_offset) = self.offset_rx.try_recv() {
-            self.offset = Duration::from_millis(new_offset.unsigned_abs() as _);
-            self.offset_neg = new_offset < 0;
+        while let Ok((global_offset, device_offsets)) = self.offset_rx.try_recv() {
+            self.global_offset = global_offset;
+            self.device_offsets = device_offsets;
         }
     }
 }
@@ -62,12 +64,13 @@ impl FilterFn for RuscFilter {
     fn filter(&self, ev: Option<gilrs::Event>, gilrs: &mut gilrs::Gilrs) -> Option<gilrs::Event> {
         match ev {
             Some(mut ev) => {
-                let source = gilrs.gamepad(ev.id).mapping_source();
-                if self.offset_neg {
-                    ev.time.add_assign(self.offset);
-                } else {
-                    ev.time.sub_assign(self.offset);
-                }
+                let gamepad = gilrs.gamepad(ev.id);
+                let source = gamepad.mapping_source();
+                let device = InputDevice::Controller(uuid::Uuid::from_bytes(gamepad.uuid()));
+                ev.time = offset_timestamp(
+                    ev.time,
+                    self.device_offsets.total(self.global_offset, device),
+                );
                 match source {
                     gilrs::MappingSource::SdlMappings => Some(ev),
                     _ => {
@@ -261,11 +264,53 @@ impl From<UscButton> for u8 {
     }
 }
 
+/// Non-gameplay actions that can be bound to button chords
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Hash, PartialEq, Eq)]
+pub enum ActionKind {
+    Restart,
+    Screenshot,
+    ToggleFps,
+    OpenSettings,
+    /// Only in practice mode
+    ToggleAutoplay,
+    /// Only in cab mode, the chord also closes it
+    ServiceMenu,
+}
+
+impl ActionKind {
+    pub const ALL: [Self; 6] = [
+        Self::Restart,
+        Self::Screenshot,
+        Self::ToggleFps,
+        Self::OpenSettings,
+        Self::ToggleAutoplay,
+        Self::ServiceMenu,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ActionKind::Restart => "Restart",
+            ActionKind::Screenshot => "Screenshot",
+            ActionKind::ToggleFps => "Toggle FPS",
+            ActionKind::OpenSettings => "Open settings",
+            ActionKind::ToggleAutoplay => "Toggle autoplay (practice)",
+            ActionKind::ServiceMenu => "Service menu (cab mode)",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum UscInputEvent {
+    /// Timestamped knob movement, only meant for judging lasers in gameplay which needs
+    /// sub-frame timing. Every other scene reads the knobs from the [`LaserState`] passed to
+    /// [`crate::scene::Scene::tick`], which has all of them added up.
     Laser(LaserState, SystemTime),
     Button(UscButton, ElementState, SystemTime),
     ClientEvent(ClientEvent),
+    /// Input from the player 2 controller in versus mode
+    Player2(Box<UscInputEvent>),
+    /// A chord bound to the action was held, sent in addition to the presses of its buttons
+    Action(ActionKind),
 }
 
 impl From<Button> for UscButton {
@@ -363,13 +408,157 @@ impl LaserState {
         self.0.delta = 0.0;
         self.1.delta = 0.0;
     }
+
+    /// Adds the movement of `other`, to collect the laser events of every input method between
+    /// two ticks into one state. Positions follow the deltas since the mouse has no absolute
+    /// position.
+    pub fn accumulate(&mut self, other: &LaserState) {
+        for (axis, other) in [(&mut self.0, other.0), (&mut self.1, other.1)] {
+            axis.delta += other.delta;
+            axis.pos = (axis.pos + other.delta + std::f32::consts::PI)
+                .rem_euclid(std::f32::consts::TAU)
+                - std::f32::consts::PI;
+        }
+    }
+}
+
+/// Moves an input timestamp `offset_ms` earlier, or later when the offset is negative
+pub fn offset_timestamp(time: SystemTime, offset_ms: f64) -> SystemTime {
+    if !offset_ms.is_finite() {
+        return time;
+    }
+
+    let offset = Duration::from_secs_f64(offset_ms.abs() / 1000.0);
+    if offset_ms < 0.0 {
+        time.checked_add(offset)
+    } else {
+        time.checked_sub(offset)
+    }
+    .unwrap_or(time)
 }
 
-use crate::{companion_interface::ClientEvent, config::GameConfig};
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+use crate::{
+    companion_interface::ClientEvent,
+    config::{DeviceOffsets, GameConfig, InputDevice},
+};
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
 pub struct CustomControlleMap {
     pub buttons: HashMap<Button, Code>,
     pub axis: HashMap<Axis, Code>, //TODO: Direction?
 }
 
 pub type CustomBindings = HashMap<uuid::Uuid, CustomControlleMap>;
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use kson::Side;
+
+    use super::{offset_timestamp, LaserState};
+    use crate::{
+        config::{DeviceOffsets, InputDevice},
+        songselect::KNOB_NAV_THRESHOLD,
+    };
+
+    /// Song select navigation steps from the states passed to each tick
+    fn navigate(ticks: impl IntoIterator<Item = LaserState>) -> (i32, f32) {
+        let mut advance = 0.0;
+        let mut steps = 0;
+        let mut total = 0.0;
+        for state in ticks {
+            let delta = state.get_axis(Side::Right).delta;
+            total += delta;
+            advance += delta;
+            let tick_steps = (advance / KNOB_NAV_THRESHOLD).trunc() as i32;
+            advance -= tick_steps as f32 * KNOB_NAV_THRESHOLD;
+            steps += tick_steps;
+        }
+        (steps, total)
+    }
+
+    /// Collects `events` into one state per tick, `per_tick` events at a time
+    fn ticks(events: Vec<LaserState>, per_tick: usize) -> Vec<LaserState> {
+        let mut knob_state = LaserState::default();
+        events
+            .chunks(per_tick)
+            .map(|chunk| {
+                chunk.iter().for_each(|e| knob_state.accumulate(e));
+                let tick = knob_state;
+                knob_state.zero_deltas();
+                tick
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_rotation_from_every_input() {
+        const STEP: f32 = 2.0 / 240.0;
+        const EVENTS: usize = 100;
+
+        // Absolute positions from a controller, crossing the point where the axis wraps around
+        let mut controller = LaserState::default();
+        controller.update(Side::Right, 0.9);
+        let controller_events = (1..=EVENTS)
+            .map(|i| {
+                controller.zero_deltas();
+                let pos = (0.9 + i as f32 * STEP + 1.0).rem_euclid(2.0) - 1.0;
+                controller.update(Side::Right, pos);
+                controller
+            })
+            .collect();
+
+        // Keyboard and mouse events only have the movement since the last one
+        let relative_events = || {
+            (0..EVENTS)
+                .map(|_| {
+                    let mut state = LaserState::default();
+                    state.update(Side::Right, STEP);
+                    state
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let (steps, total) = navigate(ticks(controller_events, 1));
+        assert_eq!(steps, 2);
+        for (keyboard_or_mouse, per_tick) in [(relative_events(), 3), (relative_events(), EVENTS)] {
+            let (other_steps, other_total) = navigate(ticks(keyboard_or_mouse, per_tick));
+            assert_eq!(other_steps, steps);
+            assert!(
+                (other_total - total).abs() < 1e-4,
+                "{other_total} != {total}"
+            );
+        }
+    }
+
+    #[test]
+    fn device_offsets_compose() {
+        let uuid = uuid::Uuid::from_u128(1);
+        let mut offsets = DeviceOffsets::default();
+        *offsets.get_mut(InputDevice::Controller(uuid)) = 3.0;
+
+        let total = offsets.total(-10.0, InputDevice::Controller(uuid));
+        assert_eq!(total, -7.0);
+        assert_eq!(offsets.total(-10.0, InputDevice::Keyboard), -10.0);
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        assert_eq!(
+            offset_timestamp(time, total),
+            time + Duration::from_millis(7)
+        );
+    }
+
+    #[test]
+    fn negative_and_fractional_offsets() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        assert_eq!(
+            offset_timestamp(time, -2.5),
+            time + Duration::from_micros(2500)
+        );
+        assert_eq!(
+            offset_timestamp(time, 0.25),
+            time - Duration::from_micros(250)
+        );
+        assert_eq!(offset_timestamp(time, f64::NAN), time);
+    }
+}
@@ -1,9 +1,14 @@
 use std::borrow::Cow;
-use std::sync::{atomic::AtomicBool, Arc};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use crate::build_info::BuildInfo;
 use crate::button_codes::UscButton;
 use crate::config::GameConfig;
 use crate::help::button_click_event;
+use crate::play_stats::PlayStats;
 use crate::{button_codes::UscInputEvent, song_provider, worker_service::WorkerService};
 use futures::StreamExt;
 use futures_util::SinkExt;
@@ -15,12 +20,24 @@ use specta::{ts, Type};
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use tokio::net::TcpStream;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Judgement batches kept for the companion server, the oldest are dropped past this
+const JUDGEMENT_QUEUE_LEN: usize = 32;
+/// Time between play snapshots sent to judgement subscribers
+const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(250);
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Type)]
 #[serde(tag = "variant")]
 pub enum GameState {
     None,
-    TitleScreen,
+    /// First message sent to every new connection
+    Handshake {
+        build: BuildInfo,
+    },
+    TitleScreen {
+        session_stats: PlayStats,
+    },
     SongSelect {
         search_string: Cow<'static, str>,
         level_filter: u8,
@@ -28,24 +45,130 @@ pub enum GameState {
         sort_index: usize,
         filters: Vec<song_provider::SongFilterType>,
         sorts: Vec<song_provider::SongSort>,
+        radio: bool,
+    },
+    /// Judgements of one frame, only sent to connections subscribed to judgements
+    Judgements {
+        events: Vec<JudgementEvent>,
+    },
+    /// Sent to connections subscribed to judgements a few times a second while playing
+    PlaySnapshot {
+        score: u32,
+        /// Between 0 and 1
+        gauge: f32,
+        combo: u32,
+        /// Judgement batches dropped since the last snapshot because the server fell behind
+        dropped_batches: u32,
+    },
+    /// Sent while a chart is played
+    InGame {
+        chart_hash: String,
+        modifiers: PlayModifierInfo,
+    },
+    /// Sent on the result screen, for the shown player in multiplayer
+    Results {
+        chart_hash: String,
+        score: u32,
+        modifiers: PlayModifierInfo,
     },
 }
 
+/// Lane modifiers of a play, the chart hash and `seed` reproduce the lanes of a random play
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Type)]
+pub struct PlayModifierInfo {
+    pub mirror: bool,
+    pub random: bool,
+    pub hard_random: bool,
+    /// Only on random plays
+    pub seed: Option<u32>,
+}
+
+impl From<kson::LaneModifiers> for PlayModifierInfo {
+    fn from(value: kson::LaneModifiers) -> Self {
+        let random = value.random != kson::RandomMode::Off;
+        Self {
+            mirror: value.mirror,
+            random,
+            hard_random: value.random == kson::RandomMode::HardRandom,
+            seed: random.then_some(value.seed),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Type)]
+pub enum JudgementRating {
+    Crit,
+    Near,
+    Miss,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Type)]
+pub struct JudgementEvent {
+    /// 0-3 for BT A-D, 4-5 for FX L/R, 6-7 for the left and right laser
+    pub lane: u8,
+    pub rating: JudgementRating,
+    /// Milliseconds the note was hit early by, negative when late and 0 for ticks without timing
+    pub delta: f64,
+    /// Combo after the judgement
+    pub combo: u32,
+}
+
+/// Sent to subscribers as [`GameState::PlaySnapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlayState {
+    pub score: u32,
+    pub gauge: f32,
+    pub combo: u32,
+}
+
+/// Judgements waiting for the companion server, bounded so slow clients can't hold up the game
+#[derive(Debug, Default)]
+struct JudgementQueue {
+    batches: VecDeque<Vec<JudgementEvent>>,
+    /// Batches dropped since the last snapshot
+    dropped: u32,
+}
+
+impl JudgementQueue {
+    fn push(&mut self, batch: Vec<JudgementEvent>) {
+        if batch.is_empty() {
+            return;
+        }
+        if self.batches.len() == JUDGEMENT_QUEUE_LEN {
+            self.batches.pop_front();
+            self.dropped += 1;
+        }
+        self.batches.push_back(batch);
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Type)]
 #[serde(tag = "variant", content = "v")]
 pub enum ClientEvent {
     Invalid(Cow<'static, str>),
     Start,
+    StartDemo,
     Back,
     SetSearch(Cow<'static, str>),
     SetLevelFilter(u8),
     SetSongFilterType(song_provider::SongFilterType),
     SetSongSort(song_provider::SongSort),
+    /// Turns the song select radio on or off
+    SetRadio(bool),
+    /// Opts this connection in or out of the judgement stream, handled by the server itself
+    Subscribe {
+        judgements: bool,
+    },
 }
 
 pub struct CompanionServer {
     event_bus: tokio::sync::broadcast::Sender<GameState>,
     pub active: Arc<AtomicBool>,
+    /// Connections subscribed to judgements
+    judgement_subscribers: Arc<AtomicUsize>,
+    judgements: JudgementQueue,
+    play_state: Option<PlayState>,
+    last_snapshot: Instant,
     _listener: poll_promise::Promise<()>,
 }
 
@@ -54,9 +177,12 @@ async fn accept_connection(
     stream: TcpStream,
     event_proxy: winit::event_loop::EventLoopProxy<UscInputEvent>,
     new_events: tokio::sync::broadcast::Receiver<GameState>,
+    judgement_subscribers: Arc<AtomicUsize>,
 ) {
     use tokio_tungstenite::tungstenite::Error;
-    if let Err(e) = handle_connection(peer, stream, event_proxy, new_events).await {
+    if let Err(e) =
+        handle_connection(peer, stream, event_proxy, new_events, judgement_subscribers).await
+    {
         match e {
             Error::ConnectionClosed | Error::Protocol(_) | Error::Utf8 => (),
             err => error!("Error processing connection: {}", err),
@@ -69,6 +195,7 @@ async fn handle_connection(
     stream: TcpStream,
     event_proxy: winit::event_loop::EventLoopProxy<UscInputEvent>,
     mut new_events: tokio::sync::broadcast::Receiver<GameState>,
+    judgement_subscribers: Arc<AtomicUsize>,
 ) -> tokio_tungstenite::tungstenite::Result<()> {
     let ws_stream = tokio_tungstenite::accept_async(stream)
         .await
@@ -77,8 +204,32 @@ async fn handle_connection(
     info!("New WebSocket connection: {}", peer);
 
     let (mut tx, mut rx) = ws_stream.split();
+    let handshake = GameState::Handshake {
+        build: BuildInfo::current(),
+    };
+    tx.send(tokio_tungstenite::tungstenite::Message::Text(
+        serde_json::to_string(&handshake).expect("Failed to serialize GameState"),
+    ))
+    .await?;
+
+    let subscribed = AtomicBool::new(false);
+
     let a = async {
-        while let Ok(e) = new_events.recv().await {
+        loop {
+            let e = match new_events.recv().await {
+                Ok(e) => e,
+                // Skipped states are replaced by newer ones
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            if matches!(
+                e,
+                GameState::Judgements { .. } | GameState::PlaySnapshot { .. }
+            ) && !subscribed.load(Ordering::Relaxed)
+            {
+                continue;
+            }
+
             let res = tx
                 .send(tokio_tungstenite::tungstenite::Message::Text(
                     serde_json::to_string(&e).expect("Failed to serialize GameState"),
@@ -102,6 +253,16 @@ async fn handle_connection(
             }
 
             let events = match e {
+                ClientEvent::Subscribe { judgements } => {
+                    if subscribed.swap(judgements, Ordering::Relaxed) != judgements {
+                        if judgements {
+                            judgement_subscribers.fetch_add(1, Ordering::Relaxed);
+                        } else {
+                            judgement_subscribers.fetch_sub(1, Ordering::Relaxed);
+                        }
+                    }
+                    continue;
+                }
                 ClientEvent::Start => button_click_event(UscButton::Start),
                 ClientEvent::Back => button_click_event(UscButton::Back),
                 e => vec![UscInputEvent::ClientEvent(e)],
@@ -118,14 +279,20 @@ async fn handle_connection(
     };
 
     tokio::join!(a, b);
+    if subscribed.load(Ordering::Relaxed) {
+        judgement_subscribers.fetch_sub(1, Ordering::Relaxed);
+    }
 
     Ok(())
 }
 
 impl CompanionServer {
     pub fn new(event_proxy: winit::event_loop::EventLoopProxy<UscInputEvent>) -> Self {
-        let (event_bus, _) = tokio::sync::broadcast::channel(8);
+        // Judgement batches are sent every frame with notes, so a few frames are buffered
+        let (event_bus, _) = tokio::sync::broadcast::channel(64);
         let client_bus = event_bus.clone();
+        let judgement_subscribers = Arc::new(AtomicUsize::new(0));
+        let subscribers = judgement_subscribers.clone();
 
         let _listener = if let Some(addr) = GameConfig::get().companion_address.as_ref() {
             let addr = addr.clone();
@@ -147,6 +314,7 @@ impl CompanionServer {
                             stream,
                             event_proxy.clone(),
                             client_bus.subscribe(),
+                            subscribers.clone(),
                         ));
                     }
                 }
@@ -158,6 +326,10 @@ impl CompanionServer {
         Self {
             event_bus,
             active: Arc::new(AtomicBool::new(false)),
+            judgement_subscribers,
+            judgements: JudgementQueue::default(),
+            play_state: None,
+            last_snapshot: Instant::now(),
             _listener,
         }
     }
@@ -165,14 +337,44 @@ impl CompanionServer {
     pub fn send_state(&self, state: GameState) {
         _ = self.event_bus.send(state);
     }
+
+    /// Whether any connection wants judgements, nothing needs to be collected otherwise
+    pub fn wants_judgements(&self) -> bool {
+        self.judgement_subscribers.load(Ordering::Relaxed) > 0
+    }
+
+    /// Queues the judgements of a frame, sent on the next update
+    pub fn push_judgements(&mut self, batch: Vec<JudgementEvent>, state: PlayState) {
+        self.judgements.push(batch);
+        self.play_state = Some(state);
+    }
+
+    /// Stops the snapshots once gameplay ends
+    pub fn clear_play_state(&mut self) {
+        self.play_state = None;
+    }
 }
 
 impl WorkerService for CompanionServer {
     fn update(&mut self) {
-        self.active.store(
-            self.event_bus.receiver_count() > 0,
-            std::sync::atomic::Ordering::Relaxed,
-        );
+        self.active
+            .store(self.event_bus.receiver_count() > 0, Ordering::Relaxed);
+
+        for events in self.judgements.batches.drain(..) {
+            _ = self.event_bus.send(GameState::Judgements { events });
+        }
+
+        if let Some(state) = self.play_state {
+            if self.last_snapshot.elapsed() >= SNAPSHOT_INTERVAL {
+                self.last_snapshot = Instant::now();
+                _ = self.event_bus.send(GameState::PlaySnapshot {
+                    score: state.score,
+                    gauge: state.gauge,
+                    combo: state.combo,
+                    dropped_batches: std::mem::take(&mut self.judgements.dropped),
+                });
+            }
+        }
     }
 }
 
@@ -198,3 +400,30 @@ pub fn print_ts(p: &str) {
         .export_by_default(Some(true));
     _ = specta::export::ts_with_cfg(p, &config);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{JudgementEvent, JudgementQueue, JudgementRating, JUDGEMENT_QUEUE_LEN};
+
+    #[test]
+    fn queue_drops_oldest() {
+        let batch = |combo| {
+            vec![JudgementEvent {
+                lane: 0,
+                rating: JudgementRating::Crit,
+                delta: 0.0,
+                combo,
+            }]
+        };
+        let mut queue = JudgementQueue::default();
+        queue.push(vec![]);
+        assert!(queue.batches.is_empty());
+
+        for combo in 0..JUDGEMENT_QUEUE_LEN as u32 + 3 {
+            queue.push(batch(combo));
+        }
+        assert_eq!(queue.batches.len(), JUDGEMENT_QUEUE_LEN);
+        assert_eq!(queue.dropped, 3);
+        assert_eq!(queue.batches[0][0].combo, 3);
+    }
+}
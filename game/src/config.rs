@@ -1,23 +1,36 @@
 use std::{
-    collections::HashMap, fmt::Display, fs::File, io::Read, path::PathBuf, sync::RwLock,
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::RwLock,
     time::Duration,
 };
 
 use clap::Parser;
 use game_loop::winit::keyboard::PhysicalKey;
-use log::{error, info};
+use log::{error, info, warn};
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
 
 use crate::{
-    button_codes::{CustomBindings, UscButton},
+    button_codes::{ActionKind, CustomBindings, UscButton},
+    display_rotation::DisplayRotation,
     game::{self, HitWindow},
     skin_settings::{SkinSettingEntry, SkinSettingValue},
     song_provider,
 };
 use serde_with::serde_as;
 
+mod bundle;
+mod overrides;
+mod storage;
+use bundle::{ImportConflict, SettingsBundle};
+pub use overrides::Overrides;
+pub use storage::write_atomic;
+
 #[derive(Debug, Default, Parser, Clone)]
 pub struct Args {
     pub chart: Option<String>,
@@ -31,10 +44,22 @@ pub struct Args {
     pub notitle: bool,
     #[arg(long)]
     pub camera_test: bool,
+    /// Open and close `chart` this many times, then check that no resources were leaked
+    #[arg(long, requires = "chart")]
+    pub soak: Option<u32>,
     #[arg(long)]
     pub settings: bool,
+    /// Reload the playing chart when its file changes and list problems found in it
+    #[arg(long)]
+    pub charting: bool,
     #[arg(long)]
     pub companion_schema: Option<PathBuf>,
+    /// Write the local song library to this .json or .csv file and exit without opening a window
+    #[arg(long)]
+    pub export_library: Option<PathBuf>,
+    /// Play audio through a null sink instead of the output device
+    #[arg(long)]
+    pub no_audio: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
@@ -79,8 +104,19 @@ impl Display for ScoreScreenshot {
 pub struct GameConfig {
     #[serde(skip_serializing, skip_deserializing)]
     config_file: PathBuf,
+    /// Format version of the config file, older files are upgraded when loaded
+    pub config_version: u32,
+    /// Problem with the config file found on startup, to be shown to the user
+    #[serde(skip_serializing, skip_deserializing)]
+    pub load_warning: Option<String>,
     pub songs_path: PathBuf,
+    pub library: LibrarySettings,
+    /// Song providers by name in order of preference, see `song_provider::register_provider`
+    pub providers: Vec<String>,
     pub skin: String,
+    /// Keep skin scripts from running programs, writing files and reading outside of the skin
+    /// and songs folders, disable only for skin development
+    pub lua_sandbox: bool,
     pub laser_hues: [f32; 2],
     pub mappings: Vec<String>,
     pub mouse_knobs: bool,
@@ -88,7 +124,9 @@ pub struct GameConfig {
     pub mod_speed: f64,
     pub keyboard_buttons: bool,
     pub keyboard_knobs: bool,
-    pub global_offset: i32,
+    /// Input offset in milliseconds, applied to every device
+    pub global_offset: f64,
+    pub device_offsets: DeviceOffsets,
     pub button_offset: i32,
     pub laser_offset: i32,
     #[serde(skip_serializing, skip_deserializing)]
@@ -99,8 +137,13 @@ pub struct GameConfig {
     pub game_folder: PathBuf,
     #[serde(skip_serializing, skip_deserializing)]
     pub args: Args,
+    /// Values pinned by `Override.cfg`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub overrides: Overrides,
     pub keybinds: Vec<Keybinds>,
     pub controller_binds: CustomBindings,
+    /// Controller used by player 2 in versus mode
+    pub versus_controller: Option<uuid::Uuid>,
     pub song_select: SongSelectSettings,
     pub graphics: GraphicsSettings,
     #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
@@ -108,16 +151,86 @@ pub struct GameConfig {
     pub distant_button_scale: f32,
     pub master_volume: f32,
     pub hit_window: game::HitWindow,
+    /// Gameplay HUD elements to show
+    pub hud: game::HudVisibility,
+    /// Part of the end of holds that may be released early
+    pub hold_grace: game::hold::HoldGrace,
+    /// Minimum time between the start of a chart and its first note, judgement input is ignored
+    /// before it
+    #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
+    pub lead_in: Duration,
     pub score_display: ScoreDisplayMode,
     pub fallback_gauge: bool,
     pub start_gauge: game::gauge::GaugeType,
+    /// Drain level of the blastive gauge, 0.5 to 10 in 0.5 steps
+    pub blastive_level: f32,
+    pub modifiers: game::modifiers::PlayModifiers,
+    /// Scores played with mirror or random count as best scores
+    pub modified_best_scores: bool,
     pub slam_volume: f32,
+    /// Play each slam sample at a slightly different pitch
+    pub slam_pitch_variance: bool,
+    /// Add a short noise sweep following the direction of slams
+    pub slam_tail: bool,
+    /// Play menu navigation sounds for skins that don't play their own
+    pub nav_sounds: bool,
     pub companion_address: Option<String>,
+    pub lighting: LightingSettings,
     pub score_screenshots: ScoreScreenshot,
     pub screenshot_path: PathBuf,
+    pub input_thread: bool,
+    /// Pass every hit to result screen scripts, not only the downsampled hit graph
+    pub full_hit_stats: bool,
+    /// JSON endpoint queried by the "check for updates" main menu action
+    pub update_url: Option<String>,
+    /// Same as the `--charting` launch argument
+    pub charting_aid: bool,
+    pub quick_retry: QuickRetry,
+    pub chords: Vec<ChordBinding>,
+    pub cab_mode: CabMode,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputDevice {
+    Keyboard,
+    Mouse,
+    Controller(uuid::Uuid),
+}
+
+/// Offsets of individual input devices in milliseconds, layered on top of the global offset
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct DeviceOffsets {
+    pub keyboard: f64,
+    /// Only applies to mouse knobs
+    pub mouse: f64,
+    pub controllers: HashMap<uuid::Uuid, f64>,
+}
+
+impl DeviceOffsets {
+    pub fn get(&self, device: InputDevice) -> f64 {
+        match device {
+            InputDevice::Keyboard => self.keyboard,
+            InputDevice::Mouse => self.mouse,
+            InputDevice::Controller(uuid) => self.controllers.get(&uuid).copied().unwrap_or(0.0),
+        }
+    }
+
+    pub fn get_mut(&mut self, device: InputDevice) -> &mut f64 {
+        match device {
+            InputDevice::Keyboard => &mut self.keyboard,
+            InputDevice::Mouse => &mut self.mouse,
+            InputDevice::Controller(uuid) => self.controllers.entry(uuid).or_default(),
+        }
+    }
+
+    /// Offset of `device` combined with the `global` offset
+    pub fn total(&self, global: f64, device: InputDevice) -> f64 {
+        global + self.get(device)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub enum Fullscreen {
     Windowed {
         pos: PhysicalPosition<i32>,
@@ -132,6 +245,29 @@ pub enum Fullscreen {
     },
 }
 
+impl Fullscreen {
+    /// Whether both show the window the same way, the position and size of a window can differ
+    pub fn same_mode(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Fullscreen::Windowed { .. }, Fullscreen::Windowed { .. }) => true,
+            (Fullscreen::Borderless { monitor: a }, Fullscreen::Borderless { monitor: b }) => {
+                a == b
+            }
+            (
+                Fullscreen::Exclusive {
+                    monitor: a,
+                    resolution: ra,
+                },
+                Fullscreen::Exclusive {
+                    monitor: b,
+                    resolution: rb,
+                },
+            ) => a == b && ra == rb,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(default)]
 pub struct GraphicsSettings {
@@ -140,7 +276,61 @@ pub struct GraphicsSettings {
     pub anti_alias: u8,
     pub target_fps: u32,
     pub show_fps: bool,
+    /// Graph of the last frame times, shown without the debug UI
+    pub show_frame_graph: bool,
     pub disable_bg: bool,
+    /// Memory budget for images that are no longer in use
+    pub image_cache_mb: u32,
+    /// Disk budget for downscaled jackets, trimmed on startup
+    pub thumbnail_cache_mb: u32,
+    pub rotation: DisplayRotation,
+    /// Width of the skin's track overlay canvas relative to the shorter side of the window
+    pub track_overlay_resolution: f32,
+    /// Scale of the settings and debug UI on top of the display scale, skins are unaffected
+    pub ui_scale: f32,
+    pub ui_theme: UiTheme,
+    /// Tones down pulsing and flashing effects, skins can read it from Lua to do the same
+    pub reduce_flashing: bool,
+}
+
+/// Colors of the settings and debug UI
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UiTheme {
+    /// Follows the dark or light preference of the OS
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl Display for UiTheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            UiTheme::System => "System",
+            UiTheme::Dark => "Dark",
+            UiTheme::Light => "Light",
+        })
+    }
+}
+
+impl UiTheme {
+    /// `system` is the theme reported by the window, dark is used when it isn't known
+    pub fn is_dark(self, system: Option<winit::window::Theme>) -> bool {
+        match self {
+            UiTheme::System => system != Some(winit::window::Theme::Light),
+            UiTheme::Dark => true,
+            UiTheme::Light => false,
+        }
+    }
+}
+
+impl GraphicsSettings {
+    pub const UI_SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.5..=3.0;
+
+    pub fn ui_scale(&self) -> f32 {
+        self.ui_scale
+            .clamp(*Self::UI_SCALE_RANGE.start(), *Self::UI_SCALE_RANGE.end())
+    }
 }
 
 impl Default for GraphicsSettings {
@@ -154,7 +344,15 @@ impl Default for GraphicsSettings {
             anti_alias: 4,
             target_fps: 300,
             show_fps: false,
+            show_frame_graph: false,
             disable_bg: false,
+            image_cache_mb: 512,
+            thumbnail_cache_mb: 256,
+            rotation: DisplayRotation::None,
+            track_overlay_resolution: 0.5,
+            ui_scale: 1.0,
+            ui_theme: UiTheme::System,
+            reduce_flashing: false,
         }
     }
 }
@@ -165,6 +363,125 @@ pub struct SongSelectSettings {
     pub sorting: song_provider::SongSort,
     pub filter: song_provider::SongFilter,
     pub last_played: song_provider::SongDiffId,
+    /// Timestamp of the newest score the song select has received
+    #[serde(skip)]
+    pub last_score_seen: i32,
+    pub knob_acceleration: KnobAcceleration,
+}
+
+/// Song wheel scrolling speed up when the knob is turned fast
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct KnobAcceleration {
+    /// Knob speeds in rotations per second from which every step moves that many songs
+    pub steps: Vec<(f32, u32)>,
+    /// Knob speed from which every step jumps to the next group instead, 0 to disable
+    pub group_speed: f32,
+    /// Time for the speed to fall to a third once the knob slows down
+    pub decay_ms: f32,
+    /// Holding BT-D makes every step jump to the next group
+    pub group_button: bool,
+}
+
+/// Cabinets without a keyboard. Players can't close the game, the service menu chord opens a
+/// menu to exit or restart it.
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct CabMode {
+    pub enabled: bool,
+    /// Time without input on the result screen before going back to the title screen, zero
+    /// stays on it
+    #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
+    pub idle_timeout: Duration,
+}
+
+impl Default for CabMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Restarting a chart without going back to song select
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct QuickRetry {
+    pub enabled: bool,
+    /// Buttons held together during gameplay to restart, long notes of these buttons don't count
+    pub buttons: Vec<UscButton>,
+    #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
+    pub hold: Duration,
+}
+
+impl Default for QuickRetry {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            buttons: vec![
+                UscButton::FX(kson::Side::Left),
+                UscButton::FX(kson::Side::Right),
+            ],
+            hold: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Song library scanning
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct LibrarySettings {
+    /// List songs whose charts are all copies of charts in another song folder
+    pub show_duplicates: bool,
+    /// Rescan the songs folder this often, for folders where changes aren't picked up otherwise
+    #[serde_as(as = "Option<DurationSeconds<u64>>")]
+    pub rescan_interval: Option<Duration>,
+    /// Charts parsed at the same time while scanning, one less than the number of cores if unset
+    pub scan_threads: Option<usize>,
+}
+
+impl LibrarySettings {
+    pub fn scan_threads(&self) -> usize {
+        self.scan_threads
+            .unwrap_or_else(|| num_cpus::get().saturating_sub(1))
+            .max(1)
+    }
+}
+
+/// Controller and cabinet lights, see `lighting::LightingService`
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct LightingSettings {
+    pub enabled: bool,
+    /// Address the light state is sent to as JSON datagrams, can be a broadcast address
+    pub udp_address: Option<String>,
+    /// Show the light state in a window
+    pub debug_view: bool,
+}
+
+/// Buttons held together to trigger an action, the buttons still work as usual
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ChordBinding {
+    pub buttons: Vec<UscButton>,
+    #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
+    pub hold: Duration,
+    pub action: ActionKind,
+}
+
+impl Default for KnobAcceleration {
+    fn default() -> Self {
+        Self {
+            steps: vec![(1.0, 2), (2.0, 5), (3.0, 10)],
+            group_speed: 0.0,
+            decay_ms: 300.0,
+            group_button: false,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
@@ -217,6 +534,32 @@ impl Keybinds {
             _ => None,
         }
     }
+
+    /// The key bound to `button`, None if the button can't be bound to a key
+    pub fn key(&self, button: UscButton) -> Option<PhysicalKey> {
+        let mut keybinds = *self;
+        keybinds.key_mut(button).copied()
+    }
+
+    pub fn key_mut(&mut self, button: UscButton) -> Option<&mut PhysicalKey> {
+        use kson::{BtLane, Side};
+        match button {
+            UscButton::BT(BtLane::A) => Some(&mut self.bt_a),
+            UscButton::BT(BtLane::B) => Some(&mut self.bt_b),
+            UscButton::BT(BtLane::C) => Some(&mut self.bt_c),
+            UscButton::BT(BtLane::D) => Some(&mut self.bt_d),
+            UscButton::FX(Side::Left) => Some(&mut self.fx_l),
+            UscButton::FX(Side::Right) => Some(&mut self.fx_r),
+            UscButton::Start => Some(&mut self.start),
+            UscButton::Back => Some(&mut self.back),
+            UscButton::Refresh => Some(&mut self.refresh),
+            UscButton::Laser(Side::Left, Side::Left) => Some(&mut self.laser_l.0),
+            UscButton::Laser(Side::Left, Side::Right) => Some(&mut self.laser_l.1),
+            UscButton::Laser(Side::Right, Side::Left) => Some(&mut self.laser_r.0),
+            UscButton::Laser(Side::Right, Side::Right) => Some(&mut self.laser_r.1),
+            UscButton::Other(_) => None,
+        }
+    }
 }
 
 impl Default for Keybinds {
@@ -248,14 +591,20 @@ impl Default for GameConfig {
     fn default() -> Self {
         Self {
             config_file: PathBuf::from_iter([".", "Main.cfg"]),
+            config_version: storage::CONFIG_VERSION,
+            load_warning: None,
             songs_path: PathBuf::from_iter([".", "songs"]),
+            library: LibrarySettings::default(),
+            providers: vec!["files".into()],
             skin: "Default".into(),
+            lua_sandbox: true,
             skin_settings: HashMap::new(),
             skin_definition: vec![],
             mod_speed: 400.0,
             laser_hues: [200.0, 330.0],
             game_folder: crate::default_game_dir(),
             args: Default::default(),
+            overrides: Overrides::default(),
             mappings: vec![
             String::from("03000000d01600006d0a000000000000,Pocket Voltex Rev4,a:b1,b:b2,y:b3,x:b4,leftshoulder:b5,rightshoulder:b6,start:b0,leftx:a0,rightx:a1"),
             String::from("03000000cf1c00001410000000000000,F2 eAcloud,a:b1,b:b2,x:b4,y:b3,start:b0,leftshoulder:b5,rightshoulder:b6,leftx:a0,rightx:a1"),
@@ -266,23 +615,42 @@ impl Default for GameConfig {
             keyboard_buttons: false,
             keybinds: vec![Keybinds::default()],
             keyboard_knobs: false,
-            global_offset: 0,
+            global_offset: 0.0,
+            device_offsets: DeviceOffsets::default(),
             button_offset: 0,
             laser_offset: 0,
             controller_binds: HashMap::new(),
+            versus_controller: None,
             song_select: SongSelectSettings::default(),
             graphics: GraphicsSettings::default(),
             distant_button_scale: 2.0,
             master_volume: 0.8,
             hit_window: HitWindow::NORMAL,
+            hud: Default::default(),
+            hold_grace: Default::default(),
+            lead_in: Duration::from_secs(3),
             score_display: ScoreDisplayMode::default(),
             fallback_gauge: false,
             start_gauge: game::gauge::GaugeType::Normal,
+            blastive_level: game::gauge::BLASTIVE_HARD_LEVEL,
+            modifiers: Default::default(),
+            modified_best_scores: false,
             slam_volume: 0.75,
+            slam_pitch_variance: true,
+            slam_tail: false,
+            nav_sounds: false,
             laser_input_delay: Duration::from_millis(50),
             companion_address: Some("127.0.0.1:9002".to_string()),
+            lighting: LightingSettings::default(),
             score_screenshots: ScoreScreenshot::default(),
             screenshot_path: PathBuf::from_iter([".", "screenshots"]),
+            input_thread: true,
+            full_hit_stats: false,
+            update_url: None,
+            charting_aid: false,
+            quick_retry: QuickRetry::default(),
+            chords: vec![],
+            cab_mode: CabMode::default(),
         }
     }
 }
@@ -303,6 +671,11 @@ impl GameConfig {
             .expect("Tried to get GameConfig before initializing")
     }
 
+    /// Total input offset of `device` in milliseconds
+    pub fn input_offset(&self, device: InputDevice) -> f64 {
+        self.device_offsets.total(self.global_offset, device)
+    }
+
     pub fn skin_path(&self) -> PathBuf {
         let mut skin_path = self.game_folder.clone();
         skin_path.push("skins");
@@ -334,6 +707,7 @@ impl GameConfig {
                     label: _,
                     name,
                     values: _,
+                    quick: _,
                 } => (name, SkinSettingValue::Text(default.clone())),
                 SkinSettingEntry::Text {
                     default,
@@ -350,6 +724,7 @@ impl GameConfig {
                     default,
                     label: _,
                     name,
+                    quick: _,
                 } => (name, SkinSettingValue::Bool(*default)),
                 SkinSettingEntry::Float {
                     default,
@@ -357,6 +732,7 @@ impl GameConfig {
                     name,
                     min: _,
                     max: _,
+                    quick: _,
                 } => (name, SkinSettingValue::Float(*default)),
                 SkinSettingEntry::Integer {
                     default,
@@ -364,6 +740,7 @@ impl GameConfig {
                     name,
                     min: _,
                     max: _,
+                    quick: _,
                 } => (name, SkinSettingValue::Integer(*default)),
                 _ => continue,
             };
@@ -387,65 +764,148 @@ impl GameConfig {
         Ok(())
     }
 
+    /// Config as written to `Main.cfg`, pinned values are replaced by the ones they override
+    pub fn to_table(&self) -> anyhow::Result<toml::Table> {
+        let toml::Value::Table(mut table) = toml::Value::try_from(self)? else {
+            anyhow::bail!("Config is not a table");
+        };
+        self.overrides.unpin(&mut table);
+        Ok(table)
+    }
+
+    /// Config read from `table`, keeping the state that isn't saved
+    fn with_values(&self, table: toml::Table) -> anyhow::Result<GameConfig> {
+        let mut config: GameConfig = toml::Value::Table(table).try_into()?;
+        config.config_file.clone_from(&self.config_file);
+        config.load_warning.clone_from(&self.load_warning);
+        config.skin_definition.clone_from(&self.skin_definition);
+        config.skin_settings.clone_from(&self.skin_settings);
+        config.game_folder.clone_from(&self.game_folder);
+        config.args = self.args.clone();
+        config.overrides = self.overrides.clone();
+        Ok(config)
+    }
+
+    /// Replaces the settings pinned by `Override.cfg`
+    pub fn apply_overrides(&mut self) -> anyhow::Result<()> {
+        if self.overrides.is_empty() {
+            return Ok(());
+        }
+
+        let mut overrides = self.overrides.clone();
+        let toml::Value::Table(mut table) = toml::Value::try_from(&*self)? else {
+            anyhow::bail!("Config is not a table");
+        };
+        overrides.pin(&mut table);
+        *self = self.with_values(table)?;
+        self.overrides = overrides;
+        Ok(())
+    }
+
+    /// Writes the portable part of the settings to a JSON file at `path`
+    pub fn export_settings(&self, path: &Path) -> anyhow::Result<()> {
+        let bundle = SettingsBundle::export(self)?;
+        write_atomic(path, serde_json::to_string_pretty(&bundle)?.as_bytes())
+    }
+
+    /// Merges settings exported with [`GameConfig::export_settings`] into these
+    pub fn import_settings(&mut self, path: &Path) -> anyhow::Result<Vec<ImportConflict>> {
+        let bundle: SettingsBundle = serde_json::from_reader(File::open(path)?)?;
+        let imported_skin_settings = bundle.skin_settings.clone();
+        let (mut merged, conflicts) = bundle.merge(self)?;
+        if merged.skin != self.skin {
+            if let Err(e) = merged.init_skin_settings() {
+                log::warn!("{e}");
+            }
+            merged.skin_settings.extend(imported_skin_settings);
+        }
+
+        *self = merged;
+        Ok(conflicts)
+    }
+
     pub fn init(mut path: PathBuf, args: Args) {
         info!("Loading game config from: {:?}", &path);
-        let file_content =
-            std::fs::read_to_string(&path).map(|str| toml::from_str::<GameConfig>(&str));
-
-        let instance_result = match file_content {
-            Ok(Ok(mut config)) => {
+        let (config, load_warning) = storage::load(&path);
+        let overrides = Overrides::load(&path.with_file_name("Override.cfg")).unwrap_or_else(|e| {
+            error!("Could not read config overrides: {e}");
+            Overrides::default()
+        });
+
+        let instance_result = match config {
+            Some(mut config) => {
                 config.args = args;
+                config.load_warning = load_warning;
                 config.config_file.clone_from(&path);
                 path.pop();
                 config.game_folder = path;
                 INSTANCE.set(RwLock::new(config))
             }
-            Ok(Err(e)) => {
-                error!("{}", e);
-                INSTANCE.set(RwLock::new(GameConfig {
-                    config_file: path,
-                    songs_path: PathBuf::from_iter([".", "songs"]),
-                    skin: "Default".into(),
-                    args,
-                    ..Default::default()
-                }))
-            }
-            Err(e) => {
-                error!("{}", e);
-                INSTANCE.set(RwLock::new(GameConfig {
-                    config_file: path,
-                    songs_path: PathBuf::from_iter([".", "songs"]),
-                    skin: "Default".into(),
-                    args,
-                    ..Default::default()
-                }))
-            }
+            None => INSTANCE.set(RwLock::new(GameConfig {
+                config_file: path,
+                songs_path: PathBuf::from_iter([".", "songs"]),
+                skin: "Default".into(),
+                args,
+                load_warning,
+                ..Default::default()
+            })),
         };
 
         instance_result.expect("Config already initialized");
 
+        {
+            let mut config = GameConfig::get_mut();
+            config.overrides = overrides;
+            if let Err(e) = config.apply_overrides() {
+                error!("Could not apply config overrides: {e}");
+            }
+        }
+
         if let Err(err) = GameConfig::get_mut().init_skin_settings() {
             log::warn!("{}", err)
         };
+
+        let mut config = GameConfig::get_mut();
+        if config.disable_unusable_cab_mode() {
+            let warning = "Cab mode was turned off, bind a service menu chord to leave it first";
+            config.load_warning = Some(match config.load_warning.take() {
+                Some(load_warning) => format!("{load_warning}\n{warning}"),
+                None => warning.to_string(),
+            });
+        }
+    }
+
+    /// Whether a chord opens the service menu, the only way to close the game in cab mode
+    pub fn has_service_chord(&self) -> bool {
+        self.chords
+            .iter()
+            .any(|c| c.action == ActionKind::ServiceMenu && !c.buttons.is_empty())
+    }
+
+    /// Turns cab mode off when there is no way to leave it, returns whether it was turned off
+    pub fn disable_unusable_cab_mode(&mut self) -> bool {
+        if self.cab_mode.enabled && !self.has_service_chord() {
+            warn!("No service menu chord is bound, turning cab mode off");
+            self.cab_mode.enabled = false;
+            return true;
+        }
+        false
     }
 
     pub fn save(&self) {
         info!("Saving config");
 
-        if let Err(e) = toml::to_string_pretty(self)
-            .map_err(|e| anyhow::anyhow!(e))
-            .and_then(|data| {
-                std::fs::write(&self.config_file, data).map_err(|e| anyhow::anyhow!(e))
-            })
+        if let Err(e) = self
+            .to_table()
+            .and_then(|table| Ok(toml::to_string_pretty(&table)?))
+            .and_then(|data| storage::write_atomic(&self.config_file, data.as_bytes()))
         {
             error!("Could not save config: {e}")
         }
 
         if let Err(e) = toml::to_string_pretty(&self.skin_settings)
             .map_err(|e| anyhow::anyhow!(e))
-            .and_then(|data| {
-                std::fs::write(self.skin_config_path(), data).map_err(|e| anyhow::anyhow!(e))
-            })
+            .and_then(|data| storage::write_atomic(&self.skin_config_path(), data.as_bytes()))
         {
             error!("Could not save skin config: {e}")
         }
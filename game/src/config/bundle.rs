@@ -0,0 +1,222 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use super::{
+    overrides::{lookup, merge_tables},
+    storage, GameConfig,
+};
+use crate::{button_codes::CustomBindings, skin_settings::SkinSettingValue};
+
+/// Paths that only make sense on the machine they were set on, left out of exports and only
+/// imported if they aren't set yet
+const MACHINE_PATHS: [&str; 2] = ["songs_path", "screenshot_path"];
+
+/// Settings copied between machines as a single JSON file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsBundle {
+    /// Main config without machine specific paths and controller binds
+    pub config: Table,
+    pub controller_binds: CustomBindings,
+    /// Settings of the skin selected in `config`
+    pub skin_settings: HashMap<String, SkinSettingValue>,
+}
+
+/// Part of a bundle that was not imported as is
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportConflict {
+    /// A local path was kept
+    PathKept(String),
+    /// Binds of this controller were replaced
+    BindsReplaced(uuid::Uuid),
+    /// The setting is pinned by `Override.cfg`
+    Pinned(String),
+}
+
+impl Display for ImportConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportConflict::PathKept(key) => write!(f, "Kept the local {key}"),
+            ImportConflict::BindsReplaced(uuid) => {
+                write!(f, "Replaced the binds of controller {uuid}")
+            }
+            ImportConflict::Pinned(key) => write!(f, "{key} is pinned by Override.cfg"),
+        }
+    }
+}
+
+impl SettingsBundle {
+    pub fn export(config: &GameConfig) -> anyhow::Result<Self> {
+        let mut table = config.to_table()?;
+        for key in MACHINE_PATHS {
+            table.remove(key);
+        }
+        table.remove("controller_binds");
+
+        Ok(Self {
+            config: table,
+            controller_binds: config.controller_binds.clone(),
+            skin_settings: config.skin_settings.clone(),
+        })
+    }
+
+    /// Merges the bundle into `current`. Controller binds are added to the existing ones, local
+    /// paths and pinned values are kept.
+    pub fn merge(
+        mut self,
+        current: &GameConfig,
+    ) -> anyhow::Result<(GameConfig, Vec<ImportConflict>)> {
+        let mut conflicts = vec![];
+        storage::migrate(&mut self.config)?;
+        self.config.remove("controller_binds");
+
+        let mut table = current.to_table()?;
+        for key in MACHINE_PATHS {
+            let Some(path) = self.config.remove(key) else {
+                continue;
+            };
+            let local_set = table
+                .get(key)
+                .and_then(Value::as_str)
+                .is_some_and(|p| !p.is_empty());
+            if !local_set {
+                table.insert(key.into(), path);
+            } else if table.get(key) != Some(&path) {
+                conflicts.push(ImportConflict::PathKept(key.into()));
+            }
+        }
+
+        for key in current.overrides.pinned_keys() {
+            if lookup(&self.config, &key)
+                .is_some_and(|v| Some(v) != current.overrides.pinned_value(&key))
+            {
+                conflicts.push(ImportConflict::Pinned(key));
+            }
+        }
+
+        merge_tables(&mut table, &self.config);
+        let mut merged = current.with_values(table)?;
+        merged.apply_overrides()?;
+
+        for (uuid, binds) in self.controller_binds {
+            if merged
+                .controller_binds
+                .get(&uuid)
+                .is_some_and(|existing| *existing != binds)
+            {
+                conflicts.push(ImportConflict::BindsReplaced(uuid));
+            }
+            merged.controller_binds.insert(uuid, binds);
+        }
+
+        if merged.skin != current.skin {
+            merged.skin_settings.clear();
+        }
+        merged.skin_settings.extend(self.skin_settings);
+
+        Ok((merged, conflicts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{ImportConflict, SettingsBundle};
+    use crate::{
+        button_codes::CustomControlleMap,
+        config::{overrides::Overrides, GameConfig},
+        skin_settings::SkinSettingValue,
+    };
+
+    fn round_trip(bundle: SettingsBundle) -> SettingsBundle {
+        serde_json::from_str(&serde_json::to_string(&bundle).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn export_import_round_trip() {
+        let cab_a = uuid::Uuid::from_u128(1);
+        let cab_b = uuid::Uuid::from_u128(2);
+
+        let mut exported = GameConfig {
+            songs_path: PathBuf::from("/songs"),
+            master_volume: 0.3,
+            mod_speed: 750.0,
+            ..Default::default()
+        };
+        exported.graphics.vsync = true;
+        exported
+            .controller_binds
+            .insert(cab_a, CustomControlleMap::default());
+        exported
+            .skin_settings
+            .insert("hue".into(), SkinSettingValue::Integer(3));
+
+        let bundle = round_trip(SettingsBundle::export(&exported).unwrap());
+        assert!(!bundle.config.contains_key("songs_path"));
+        assert!(!bundle.config.contains_key("controller_binds"));
+
+        let mut local = GameConfig {
+            songs_path: PathBuf::from("/local/songs"),
+            ..Default::default()
+        };
+        local
+            .controller_binds
+            .insert(cab_b, CustomControlleMap::default());
+
+        let (merged, conflicts) = bundle.merge(&local).unwrap();
+        assert_eq!(conflicts, vec![]);
+        assert_eq!(merged.master_volume, 0.3);
+        assert_eq!(merged.mod_speed, 750.0);
+        assert!(merged.graphics.vsync);
+        assert_eq!(merged.songs_path, PathBuf::from("/local/songs"));
+        assert!(merged.controller_binds.contains_key(&cab_a));
+        assert!(merged.controller_binds.contains_key(&cab_b));
+        assert!(matches!(
+            merged.skin_settings.get("hue"),
+            Some(SkinSettingValue::Integer(3))
+        ));
+
+        // Importing the same bundle again changes nothing
+        let bundle = round_trip(SettingsBundle::export(&merged).unwrap());
+        let (again, conflicts) = bundle.merge(&merged).unwrap();
+        assert_eq!(conflicts, vec![]);
+        assert_eq!(again.to_table().unwrap(), merged.to_table().unwrap());
+    }
+
+    #[test]
+    fn import_conflicts() {
+        let exported = GameConfig {
+            master_volume: 1.0,
+            ..Default::default()
+        };
+        let mut bundle = SettingsBundle::export(&exported).unwrap();
+        bundle
+            .config
+            .insert("songs_path".into(), "/other/songs".into());
+
+        let mut local = GameConfig {
+            songs_path: PathBuf::from("/local/songs"),
+            overrides: Overrides::parse("master_volume = 0.5").unwrap(),
+            ..Default::default()
+        };
+        local.apply_overrides().unwrap();
+
+        let (merged, conflicts) = bundle.clone().merge(&local).unwrap();
+        assert_eq!(
+            conflicts,
+            vec![
+                ImportConflict::PathKept("songs_path".into()),
+                ImportConflict::Pinned("master_volume".into()),
+            ]
+        );
+        assert_eq!(merged.songs_path, PathBuf::from("/local/songs"));
+        assert_eq!(merged.master_volume, 0.5);
+
+        // Paths that aren't set yet are imported
+        local.songs_path = PathBuf::new();
+        let (merged, _) = bundle.merge(&local).unwrap();
+        assert_eq!(merged.songs_path, PathBuf::from("/other/songs"));
+    }
+}
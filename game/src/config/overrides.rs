@@ -0,0 +1,192 @@
+use std::path::Path;
+
+use log::info;
+use toml::{Table, Value};
+
+/// Settings sections that can't be changed are listed under this key of `Override.cfg`
+const LOCKED_SECTIONS: &str = "locked_sections";
+
+/// Values pinned by the read-only `Override.cfg` next to the main config, for cabinet operators.
+///
+/// It uses the same format as `Main.cfg`, every value set in it replaces the one of the main
+/// config and can't be changed in the settings.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pinned: Table,
+    /// Values of the main config the pinned values replaced, written back when saving
+    original: Table,
+    locked_sections: Vec<String>,
+}
+
+/// Recursively replaces the values of `target` with the ones set in `source`
+pub fn merge_tables(target: &mut Table, source: &Table) {
+    for (key, value) in source {
+        match (target.get_mut(key), value) {
+            (Some(Value::Table(target)), Value::Table(source)) => merge_tables(target, source),
+            _ => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Value at a dotted `key` such as `graphics.vsync`
+pub fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let mut parts = key.split('.');
+    let mut value = table.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_table()?.get(part)?;
+    }
+    Some(value)
+}
+
+/// Dotted keys of all values in `table` that aren't tables themselves
+fn leaf_keys(table: &Table, prefix: &str, keys: &mut Vec<String>) {
+    for (key, value) in table {
+        let key = format!("{prefix}{key}");
+        match value {
+            Value::Table(table) => leaf_keys(table, &format!("{key}."), keys),
+            _ => keys.push(key),
+        }
+    }
+}
+
+/// The parts of `table` that `shape` has values for
+fn pick(table: &Table, shape: &Table) -> Table {
+    shape
+        .iter()
+        .filter_map(|(key, shape)| {
+            let value = table.get(key)?;
+            match (value, shape) {
+                (Value::Table(value), Value::Table(shape)) => {
+                    Some((key.clone(), Value::Table(pick(value, shape))))
+                }
+                _ => Some((key.clone(), value.clone())),
+            }
+        })
+        .collect()
+}
+
+impl Overrides {
+    pub fn parse(data: &str) -> anyhow::Result<Self> {
+        let mut pinned: Table = data.parse()?;
+        let locked_sections = match pinned.remove(LOCKED_SECTIONS) {
+            Some(sections) => sections.try_into()?,
+            None => vec![],
+        };
+
+        Ok(Self {
+            pinned,
+            original: Table::new(),
+            locked_sections,
+        })
+    }
+
+    /// Reads the overrides at `path`, there are none if the file doesn't exist
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(data) => {
+                info!("Loading config overrides from: {:?}", path);
+                Self::parse(&data)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pinned.is_empty() && self.locked_sections.is_empty()
+    }
+
+    /// Whether the setting at a dotted `key` such as `graphics.vsync` is pinned, either directly
+    /// or as part of a pinned table
+    pub fn is_pinned(&self, key: &str) -> bool {
+        let mut table = &self.pinned;
+        for part in key.split('.') {
+            match table.get(part) {
+                Some(Value::Table(inner)) => table = inner,
+                Some(_) => return true,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    pub fn pinned_keys(&self) -> Vec<String> {
+        let mut keys = vec![];
+        leaf_keys(&self.pinned, "", &mut keys);
+        keys
+    }
+
+    pub fn pinned_value(&self, key: &str) -> Option<&Value> {
+        lookup(&self.pinned, key)
+    }
+
+    pub fn is_section_locked(&self, name: &str) -> bool {
+        self.locked_sections
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(name))
+    }
+
+    /// Pins the values of `config`, the replaced values are kept the first time so they can be
+    /// saved again
+    pub fn pin(&mut self, config: &mut Table) {
+        if self.original.is_empty() {
+            self.original = pick(config, &self.pinned);
+        }
+        merge_tables(config, &self.pinned);
+    }
+
+    /// Puts the values the pinned ones replaced back into `config`
+    pub fn unpin(&self, config: &mut Table) {
+        merge_tables(config, &self.original);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::{Table, Value};
+
+    use super::Overrides;
+
+    #[test]
+    fn pin_and_unpin() {
+        let mut overrides = Overrides::parse(
+            "master_volume = 0.5\nlocked_sections = [\"Graphics\"]\n[graphics]\nvsync = true",
+        )
+        .unwrap();
+        assert!(overrides.is_pinned("master_volume"));
+        assert!(overrides.is_pinned("graphics.vsync"));
+        assert!(!overrides.is_pinned("graphics.show_fps"));
+        assert!(!overrides.is_pinned("slam_volume"));
+        assert!(overrides.is_section_locked("graphics"));
+        assert!(!overrides.is_section_locked("Audio"));
+        assert_eq!(
+            overrides.pinned_keys(),
+            vec!["graphics.vsync".to_string(), "master_volume".to_string()]
+        );
+
+        let mut config: Table =
+            "master_volume = 1.0\nslam_volume = 0.8\n[graphics]\nvsync = false\nshow_fps = true"
+                .parse()
+                .unwrap();
+        overrides.pin(&mut config);
+        assert_eq!(config.get("master_volume"), Some(&Value::Float(0.5)));
+        assert_eq!(config["graphics"].get("vsync"), Some(&Value::Boolean(true)));
+        assert_eq!(
+            config["graphics"].get("show_fps"),
+            Some(&Value::Boolean(true))
+        );
+
+        // Pinning again keeps the values from the main config
+        overrides.pin(&mut config);
+        config.insert("slam_volume".into(), Value::Float(0.2));
+        overrides.unpin(&mut config);
+        assert_eq!(config.get("master_volume"), Some(&Value::Float(1.0)));
+        assert_eq!(
+            config["graphics"].get("vsync"),
+            Some(&Value::Boolean(false))
+        );
+        assert_eq!(config.get("slam_volume"), Some(&Value::Float(0.2)));
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    ffi::OsString,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use log::{error, info, warn};
+use toml::{Table, Value};
+
+use super::GameConfig;
+
+/// Version of new configs, bump it and add a migration when a field is renamed or changes type
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Migration at index `n` upgrades a config from version `n` to `n + 1`
+const MIGRATIONS: [fn(&mut Table); CONFIG_VERSION as usize] = [v0_to_v1];
+
+/// Offsets were whole milliseconds before device offsets were added
+fn v0_to_v1(config: &mut Table) {
+    if let Some(Value::Integer(offset)) = config.get("global_offset") {
+        let offset = *offset as f64;
+        config.insert("global_offset".into(), Value::Float(offset));
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+/// Upgrades a config table written by an older version
+pub fn migrate(config: &mut Table) -> anyhow::Result<()> {
+    let version = match config.get("config_version") {
+        None => 0,
+        Some(Value::Integer(v)) if *v >= 0 => *v as usize,
+        Some(v) => anyhow::bail!("Invalid config version: {v}"),
+    };
+
+    if version > CONFIG_VERSION as usize {
+        warn!("Config is from a newer version ({version}), unknown settings will be lost");
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        info!("Upgrading config from version {from}");
+        migration(config);
+    }
+
+    config.insert(
+        "config_version".into(),
+        Value::Integer(CONFIG_VERSION as i64),
+    );
+    Ok(())
+}
+
+pub fn parse(data: &str) -> anyhow::Result<GameConfig> {
+    let mut config: Table = data.parse()?;
+    migrate(&mut config)?;
+    Ok(Value::Table(config).try_into()?)
+}
+
+/// Reads the config at `path`, returns a message for the user if it exists but couldn't be used.
+///
+/// A broken config is renamed to `<name>.broken-<timestamp>` so it isn't overwritten by the
+/// next save, and the backup of the previous save is loaded instead if there is one.
+pub fn load(path: &Path) -> (Option<GameConfig>, Option<String>) {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            info!("No config at {}, using defaults", path.display());
+            return (None, None);
+        }
+        Err(e) => {
+            error!("Could not read config: {e}");
+            return (None, Some(format!("Could not read settings: {e}")));
+        }
+    };
+
+    let e = match parse(&data) {
+        Ok(config) => return (Some(config), None),
+        Err(e) => e,
+    };
+    error!("Could not parse config: {e}");
+
+    let broken = with_suffix(
+        path,
+        &format!(
+            ".broken-{}",
+            chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")
+        ),
+    );
+    let kept = match std::fs::rename(path, &broken) {
+        Ok(()) => format!("it was saved as {}", broken.display()),
+        Err(e) => {
+            error!("Could not keep broken config: {e}");
+            "it could not be kept".to_string()
+        }
+    };
+
+    let backup = std::fs::read_to_string(backup_path(path))
+        .map_err(anyhow::Error::from)
+        .and_then(|data| parse(&data));
+    match backup {
+        Ok(config) => (
+            Some(config),
+            Some(format!(
+                "Settings were broken and restored from the last backup, {kept}"
+            )),
+        ),
+        Err(_) => (
+            None,
+            Some(format!("Settings were broken and have been reset, {kept}")),
+        ),
+    }
+}
+
+/// Replaces the file at `path` so a crash leaves either the old or the new contents, the old
+/// file is kept as `<name>.bak`
+pub fn write_atomic(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let tmp = with_suffix(path, ".tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+
+    if path.exists() {
+        std::fs::copy(path, backup_path(path))?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::{Table, Value};
+
+    use super::{load, migrate, parse, write_atomic, CONFIG_VERSION};
+
+    #[test]
+    fn migrate_v0() {
+        let mut config: Table = "global_offset = 12\nmod_speed = 500.0".parse().unwrap();
+        migrate(&mut config).unwrap();
+        assert_eq!(
+            config.get("config_version"),
+            Some(&Value::Integer(CONFIG_VERSION as i64))
+        );
+        assert_eq!(config.get("global_offset"), Some(&Value::Float(12.0)));
+
+        let config = parse("global_offset = -7\nmod_speed = 500.0").unwrap();
+        assert_eq!(config.global_offset, -7.0);
+        assert_eq!(config.mod_speed, 500.0);
+        assert_eq!(config.config_version, CONFIG_VERSION);
+    }
+
+    #[test]
+    fn broken_config() {
+        let dir = std::env::temp_dir().join(format!("rusc_config_{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Main.cfg");
+        let broken_files = || {
+            std::fs::read_dir(&dir)
+                .unwrap()
+                .filter(|e| {
+                    let name = e.as_ref().unwrap().file_name();
+                    name.to_string_lossy().starts_with("Main.cfg.broken-")
+                })
+                .count()
+        };
+
+        std::fs::write(&path, "mod_speed = [").unwrap();
+        let (config, warning) = load(&path);
+        assert!(warning.unwrap().contains("reset"));
+        assert!(config.is_none());
+        assert!(!path.exists());
+        assert_eq!(broken_files(), 1);
+
+        // The previous save is used once there is one
+        write_atomic(&path, b"mod_speed = 500.0").unwrap();
+        write_atomic(&path, b"mod_speed = 600.0").unwrap();
+        std::fs::write(&path, "mod_speed = [").unwrap();
+        let (config, warning) = load(&path);
+        assert!(warning.unwrap().contains("backup"));
+        assert_eq!(config.unwrap().mod_speed, 500.0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
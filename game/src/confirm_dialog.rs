@@ -0,0 +1,210 @@
+use kson::{BtLane, Side};
+use tealr::mlu::mlua::{IntoLua, Lua, Value};
+
+use crate::{button_codes::UscButton, songselect::KNOB_NAV_THRESHOLD};
+
+type Callback<S> = Box<dyn FnOnce(&mut S, bool)>;
+
+/// Yes/no prompt shown before destructive actions, works with the mouse in egui screens and with
+/// the controller everywhere.
+///
+/// The owner `S` keeps the dialog in an `Option` and passes it every button press while it's
+/// open, so nothing beneath reacts to them. The callback gets the owner and the choice once the
+/// dialog closes.
+pub struct ConfirmDialog<S> {
+    message: String,
+    confirm_label: String,
+    cancel_label: String,
+    /// Starts on cancel so a stray press doesn't confirm
+    confirm_selected: bool,
+    knob_advance: f32,
+    callback: Callback<S>,
+}
+
+impl<S> ConfirmDialog<S> {
+    pub fn new(
+        message: impl Into<String>,
+        confirm_label: impl Into<String>,
+        cancel_label: impl Into<String>,
+        callback: impl FnOnce(&mut S, bool) + 'static,
+    ) -> Self {
+        Self {
+            message: message.into(),
+            confirm_label: confirm_label.into(),
+            cancel_label: cancel_label.into(),
+            confirm_selected: false,
+            knob_advance: 0.0,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Moves the selection or makes a choice, returns the choice once one is made
+    pub fn on_button_pressed(&mut self, button: UscButton) -> Option<bool> {
+        match button {
+            UscButton::Start => Some(self.confirm_selected),
+            UscButton::Back => Some(false),
+            UscButton::BT(BtLane::A | BtLane::B) | UscButton::FX(Side::Left) => {
+                self.confirm_selected = true;
+                None
+            }
+            UscButton::BT(BtLane::C | BtLane::D) | UscButton::FX(Side::Right) => {
+                self.confirm_selected = false;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Moves the selection with a knob, turning left selects confirm
+    pub fn on_knob(&mut self, delta: f32) {
+        self.knob_advance += delta;
+        if self.knob_advance.abs() >= KNOB_NAV_THRESHOLD {
+            self.confirm_selected = self.knob_advance < 0.0;
+            self.knob_advance = 0.0;
+        }
+    }
+
+    /// Draws the dialog over everything else and blocks clicks on what's beneath, returns the
+    /// choice once one is made
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<bool> {
+        let screen = ctx.screen_rect();
+        egui::Area::new(egui::Id::new("confirm_dialog_backdrop"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(screen.min)
+            .show(ctx, |ui| {
+                ui.allocate_rect(screen, egui::Sense::click());
+                ui.painter()
+                    .rect_filled(screen, 0.0, egui::Color32::from_black_alpha(160));
+            });
+
+        let mut choice = None;
+        // Above the foreground backdrop, egui has no modal windows
+        egui::Area::new(egui::Id::new("confirm_dialog"))
+            .order(egui::Order::Tooltip)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                egui::Frame::window(ui.style()).show(ui, |ui| {
+                    ui.label(&self.message);
+                    ui.horizontal(|ui| {
+                        let confirm =
+                            egui::Button::new(&self.confirm_label).selected(self.confirm_selected);
+                        let cancel =
+                            egui::Button::new(&self.cancel_label).selected(!self.confirm_selected);
+                        if ui.add(confirm).clicked() {
+                            choice = Some(true);
+                        }
+                        if ui.add(cancel).clicked() {
+                            choice = Some(false);
+                        }
+                    });
+                });
+            });
+
+        choice
+    }
+
+    fn finish(self, owner: &mut S, choice: bool) {
+        (self.callback)(owner, choice)
+    }
+
+    /// Passes a button press to the dialog `slot` of `owner` holds, running the callback once a
+    /// choice is made. Returns whether a dialog was open and took the press.
+    pub fn route_button(
+        owner: &mut S,
+        slot: fn(&mut S) -> &mut Option<Self>,
+        button: UscButton,
+    ) -> bool {
+        let Some(dialog) = slot(owner) else {
+            return false;
+        };
+
+        if let Some(choice) = dialog.on_button_pressed(button) {
+            if let Some(dialog) = slot(owner).take() {
+                dialog.finish(owner, choice);
+            }
+        }
+        true
+    }
+
+    /// Shows the dialog `slot` of `owner` holds if there is one, running the callback once a
+    /// choice is made
+    pub fn show_in(owner: &mut S, slot: fn(&mut S) -> &mut Option<Self>, ctx: &egui::Context) {
+        let Some(dialog) = slot(owner) else {
+            return;
+        };
+
+        if let Some(choice) = dialog.show(ctx) {
+            if let Some(dialog) = slot(owner).take() {
+                dialog.finish(owner, choice);
+            }
+        }
+    }
+}
+
+impl<'lua, S> IntoLua<'lua> for &ConfirmDialog<S> {
+    fn into_lua(self, lua: &'lua Lua) -> tealr::mlu::mlua::Result<Value<'lua>> {
+        let table = lua.create_table()?;
+        table.set("message", self.message.as_str())?;
+        table.set("confirmLabel", self.confirm_label.as_str())?;
+        table.set("cancelLabel", self.cancel_label.as_str())?;
+        table.set("confirmSelected", self.confirm_selected)?;
+        Ok(Value::Table(table))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kson::{BtLane, Side};
+
+    use super::ConfirmDialog;
+    use crate::button_codes::UscButton;
+
+    #[derive(Default)]
+    struct Owner {
+        dialog: Option<ConfirmDialog<Owner>>,
+        choice: Option<bool>,
+    }
+
+    impl Owner {
+        fn open(&mut self) {
+            self.dialog = Some(ConfirmDialog::new(
+                "Sure?",
+                "Yes",
+                "No",
+                |o: &mut Owner, c| o.choice = Some(c),
+            ));
+        }
+
+        fn press(&mut self, button: UscButton) -> bool {
+            ConfirmDialog::route_button(self, |o| &mut o.dialog, button)
+        }
+    }
+
+    #[test]
+    fn controller_navigation() {
+        let mut owner = Owner::default();
+        assert!(!owner.press(UscButton::Start));
+
+        // Cancel is selected by default
+        owner.open();
+        assert!(owner.press(UscButton::Start));
+        assert_eq!(owner.choice, Some(false));
+        assert!(owner.dialog.is_none());
+
+        owner.open();
+        assert!(owner.press(UscButton::FX(Side::Left)));
+        assert!(owner.press(UscButton::BT(BtLane::D)));
+        owner
+            .dialog
+            .as_mut()
+            .unwrap()
+            .on_knob(-std::f32::consts::PI);
+        assert!(owner.press(UscButton::Start));
+        assert_eq!(owner.choice, Some(true));
+
+        owner.open();
+        owner.choice = None;
+        assert!(owner.press(UscButton::Back));
+        assert_eq!(owner.choice, Some(false));
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    rc::Rc,
+    sync::{mpsc::Sender, Arc},
+};
+
+use di::{RefMut, ServiceProvider};
+use game_loop::winit::window::Window;
+use tealr::mlu::mlua::Lua;
+
+use crate::{
+    config::GameConfig,
+    game_main::{AutoPlay, ControlMessage},
+    main_menu::MainMenuButton,
+    scene::{Scene, SceneData},
+    settings_screen::SettingsScreen,
+    song_provider::{adjacent_song, closest_difficulty, SongDiffId, SongProvider},
+    songselect::Song,
+    transition::Transition,
+    vg_ui::Vgfx,
+    LuaArena, Scenes,
+};
+
+/// Transition script used to change to the target of a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionKind {
+    /// `transition.lua`
+    Default,
+    /// `songtransition.lua`
+    Song,
+}
+
+/// Change to the scene stack requested by a [`ControlMessage`]
+pub enum SceneCommand {
+    None,
+    Transition {
+        kind: TransitionKind,
+        target: ControlMessage,
+        /// Suspend the top scene while the transition plays
+        suspend_top: bool,
+    },
+    Push(Box<dyn Scene>),
+    /// Replaces the result screen with the last played chart, without a transition
+    Retry {
+        song: Arc<Song>,
+        diff_idx: usize,
+        retries: u32,
+    },
+    /// Starts the song next to the one just played, see [`ControlMessage::AdjacentSong`]
+    AdjacentSong {
+        song: Arc<Song>,
+        diff_idx: usize,
+        step: i32,
+    },
+    OpenSettings,
+    Clear,
+    /// Handled by the owner of the window, doesn't touch the scene stack
+    ApplySettings,
+}
+
+impl ControlMessage {
+    pub fn into_command(self) -> SceneCommand {
+        match self {
+            ControlMessage::None => SceneCommand::None,
+            ControlMessage::MainMenu(button) => match button {
+                MainMenuButton::Start => SceneCommand::Transition {
+                    kind: TransitionKind::Default,
+                    target: ControlMessage::MainMenu(MainMenuButton::Start),
+                    suspend_top: true,
+                },
+                MainMenuButton::Exit => SceneCommand::Clear,
+                MainMenuButton::Options => SceneCommand::OpenSettings,
+                _ => SceneCommand::None,
+            },
+            song @ ControlMessage::Song { .. } => SceneCommand::Transition {
+                kind: TransitionKind::Song,
+                target: song,
+                suspend_top: false,
+            },
+            ControlMessage::TransitionComplete(scene) => SceneCommand::Push(scene),
+            result @ (ControlMessage::Result(_) | ControlMessage::VersusResult(_)) => {
+                SceneCommand::Transition {
+                    kind: TransitionKind::Default,
+                    target: result,
+                    suspend_top: false,
+                }
+            }
+            ControlMessage::Retry {
+                song,
+                diff_idx,
+                retries,
+            } => SceneCommand::Retry {
+                song,
+                diff_idx,
+                retries,
+            },
+            ControlMessage::AdjacentSong {
+                song,
+                diff_idx,
+                step,
+            } => SceneCommand::AdjacentSong {
+                song,
+                diff_idx,
+                step,
+            },
+            ControlMessage::ApplySettings => SceneCommand::ApplySettings,
+        }
+    }
+}
+
+/// Scene stack operations used by [`SceneCommand::apply`]
+pub trait SceneStack {
+    fn suspend_top(&mut self);
+    fn clear(&mut self);
+    fn push_immediate(&mut self, scene: Box<dyn Scene>);
+    fn push_with_transition(&mut self, kind: TransitionKind, target: ControlMessage);
+    fn open_settings(&mut self);
+    fn retry(&mut self, song: Arc<Song>, diff_idx: usize, retries: u32);
+    fn adjacent_song(&mut self, song: Arc<Song>, diff_idx: usize, step: i32);
+}
+
+impl SceneCommand {
+    pub fn apply(self, stack: &mut impl SceneStack) {
+        match self {
+            SceneCommand::None | SceneCommand::ApplySettings => {}
+            SceneCommand::Transition {
+                kind,
+                target,
+                suspend_top,
+            } => {
+                if suspend_top {
+                    stack.suspend_top();
+                }
+                stack.push_with_transition(kind, target);
+            }
+            SceneCommand::Push(scene) => stack.push_immediate(scene),
+            SceneCommand::Retry {
+                song,
+                diff_idx,
+                retries,
+            } => stack.retry(song, diff_idx, retries),
+            SceneCommand::AdjacentSong {
+                song,
+                diff_idx,
+                step,
+            } => stack.adjacent_song(song, diff_idx, step),
+            SceneCommand::OpenSettings => stack.open_settings(),
+            SceneCommand::Clear => stack.clear(),
+        }
+    }
+}
+
+/// Applies scene commands to the scenes of the game
+pub struct ControlDispatcher<'a> {
+    pub scenes: &'a mut Scenes,
+    pub lua_arena: &'a RefMut<LuaArena>,
+    pub transition_lua: &'a Rc<Lua>,
+    pub transition_song_lua: &'a Rc<Lua>,
+    pub control_tx: &'a Sender<ControlMessage>,
+    pub vgfx: &'a RefMut<Vgfx>,
+    pub viewport: three_d::Viewport,
+    pub service_provider: &'a ServiceProvider,
+    pub window: &'a Window,
+}
+
+impl SceneStack for ControlDispatcher<'_> {
+    fn suspend_top(&mut self) {
+        self.scenes.suspend_top();
+    }
+
+    fn clear(&mut self) {
+        self.scenes.clear();
+    }
+
+    fn push_immediate(&mut self, scene: Box<dyn Scene>) {
+        self.scenes.loaded.push(scene);
+    }
+
+    fn push_with_transition(&mut self, kind: TransitionKind, target: ControlMessage) {
+        if let Ok(_arena) = self.lua_arena.read() {
+            let transition_lua = match kind {
+                TransitionKind::Default => self.transition_lua.clone(),
+                TransitionKind::Song => self.transition_song_lua.clone(),
+            };
+            self.scenes.transition = Transition::new(
+                transition_lua,
+                target,
+                self.control_tx.clone(),
+                self.vgfx.clone(),
+                self.viewport,
+                self.service_provider.create_scope(),
+            )
+            .ok()
+        }
+    }
+
+    fn open_settings(&mut self) {
+        self.push_immediate(Box::new(SettingsScreen::new(
+            self.service_provider.create_scope(),
+            self.control_tx.clone(),
+            self.window,
+        )))
+    }
+
+    fn retry(&mut self, song: Arc<Song>, diff_idx: usize, retries: u32) {
+        let last_played = self
+            .service_provider
+            .get_required_mut::<crate::game_data::GameData>()
+            .write()
+            .expect("Lock error")
+            .last_played
+            .take()
+            .filter(|data| data.is_chart(&song, diff_idx));
+
+        let Some(data) = last_played else {
+            log::warn!("No loaded chart to retry {}", song.title);
+            return;
+        };
+
+        match Box::new(data.with_retries(retries)).make_scene(self.service_provider.create_scope())
+        {
+            Ok(game) => self.push_immediate(game),
+            Err(e) => log::error!("Could not restart chart: {e}"),
+        }
+    }
+
+    fn adjacent_song(&mut self, song: Arc<Song>, diff_idx: usize, step: i32) {
+        let provider: RefMut<dyn SongProvider> = self.service_provider.get_required();
+        let provider = provider.read().expect("Lock error");
+
+        // The order is queried again since songs may have been filtered or removed while playing
+        let (songs, order) = provider.get_all();
+        let difficulty = song
+            .difficulties
+            .read()
+            .expect("Lock error")
+            .get(diff_idx)
+            .map(|d| d.difficulty)
+            .unwrap_or_default();
+        let target = adjacent_song(&order, &song.id, step)
+            .and_then(|id| songs.into_iter().find(|s| s.id == *id))
+            .and_then(|next| Some((closest_difficulty(&next, difficulty)?, next)));
+
+        let Some((diff, next)) = target else {
+            log::warn!("No song next to {} in the song list", song.title);
+            return;
+        };
+
+        let song_diff = SongDiffId::SongDiff(
+            next.id.clone(),
+            next.difficulties.read().expect("Lock error")[diff]
+                .id
+                .clone(),
+        );
+        let loaded = provider.load_song(&song_diff);
+        drop(provider);
+        match loaded {
+            Ok(loader) => {
+                GameConfig::get_mut().song_select.last_played = song_diff;
+                self.push_with_transition(
+                    TransitionKind::Song,
+                    ControlMessage::Song {
+                        song: next,
+                        diff,
+                        loader,
+                        autoplay: AutoPlay::None,
+                        versus: false,
+                    },
+                );
+            }
+            Err(e) => log::warn!("Failed to load song: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{SceneStack, TransitionKind};
+    use crate::{
+        game_main::ControlMessage, main_menu::MainMenuButton, scene::Scene, songselect::Song,
+    };
+
+    #[derive(Debug, PartialEq)]
+    enum Op {
+        SuspendTop,
+        Clear,
+        Push(String),
+        Transition(TransitionKind, &'static str),
+        OpenSettings,
+        Retry(usize, u32),
+        AdjacentSong(usize, i32),
+    }
+
+    #[derive(Default)]
+    struct FakeScenes(Vec<Op>);
+
+    impl SceneStack for FakeScenes {
+        fn suspend_top(&mut self) {
+            self.0.push(Op::SuspendTop);
+        }
+
+        fn clear(&mut self) {
+            self.0.push(Op::Clear);
+        }
+
+        fn push_immediate(&mut self, scene: Box<dyn Scene>) {
+            self.0.push(Op::Push(scene.name().to_string()));
+        }
+
+        fn push_with_transition(&mut self, kind: TransitionKind, target: ControlMessage) {
+            let target = match target {
+                ControlMessage::MainMenu(MainMenuButton::Start) => "start",
+                ControlMessage::Song { .. } => "song",
+                ControlMessage::VersusResult(_) => "versus result",
+                _ => "other",
+            };
+            self.0.push(Op::Transition(kind, target));
+        }
+
+        fn open_settings(&mut self) {
+            self.0.push(Op::OpenSettings);
+        }
+
+        fn retry(&mut self, _song: Arc<Song>, diff_idx: usize, retries: u32) {
+            self.0.push(Op::Retry(diff_idx, retries));
+        }
+
+        fn adjacent_song(&mut self, _song: Arc<Song>, diff_idx: usize, step: i32) {
+            self.0.push(Op::AdjacentSong(diff_idx, step));
+        }
+    }
+
+    struct Named;
+
+    impl Scene for Named {
+        fn render_ui(&mut self, _dt: f64) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn is_suspended(&self) -> bool {
+            false
+        }
+
+        fn debug_ui(&mut self, _ctx: &egui::Context) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn closed(&self) -> bool {
+            false
+        }
+
+        fn name(&self) -> &str {
+            "Named"
+        }
+    }
+
+    fn ops(msg: ControlMessage) -> Vec<Op> {
+        let mut scenes = FakeScenes::default();
+        msg.into_command().apply(&mut scenes);
+        scenes.0
+    }
+
+    #[test]
+    fn message_stack_changes() {
+        assert_eq!(
+            ops(ControlMessage::MainMenu(MainMenuButton::Start)),
+            [
+                Op::SuspendTop,
+                Op::Transition(TransitionKind::Default, "start")
+            ]
+        );
+        assert_eq!(
+            ops(ControlMessage::MainMenu(MainMenuButton::Exit)),
+            [Op::Clear]
+        );
+        assert_eq!(
+            ops(ControlMessage::MainMenu(MainMenuButton::Options)),
+            [Op::OpenSettings]
+        );
+        assert!(ops(ControlMessage::MainMenu(MainMenuButton::Downloads)).is_empty());
+        assert_eq!(
+            ops(ControlMessage::Song {
+                song: Default::default(),
+                diff: 0,
+                loader: Box::new(|_| anyhow::bail!("No chart")),
+                autoplay: crate::game_main::AutoPlay::None,
+                versus: false,
+            }),
+            [Op::Transition(TransitionKind::Song, "song")]
+        );
+        assert_eq!(
+            ops(ControlMessage::VersusResult(vec![])),
+            [Op::Transition(TransitionKind::Default, "versus result")]
+        );
+        assert_eq!(
+            ops(ControlMessage::TransitionComplete(Box::new(Named))),
+            [Op::Push("Named".to_string())]
+        );
+        assert_eq!(
+            ops(ControlMessage::Retry {
+                song: Default::default(),
+                diff_idx: 2,
+                retries: 1,
+            }),
+            [Op::Retry(2, 1)]
+        );
+        assert_eq!(
+            ops(ControlMessage::AdjacentSong {
+                song: Default::default(),
+                diff_idx: 1,
+                step: -1,
+            }),
+            [Op::AdjacentSong(1, -1)]
+        );
+        assert!(ops(ControlMessage::ApplySettings).is_empty());
+        assert!(ops(ControlMessage::None).is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use three_d::Viewport;
+
+/// How the picture is rotated on the framebuffer, for monitors that are physically rotated while
+/// the desktop stays in landscape.
+///
+/// Skins are laid out in the rotated (logical) space, the window and video modes stay in
+/// physical pixels.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayRotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl Display for DisplayRotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DisplayRotation::None => "None",
+            DisplayRotation::Clockwise90 => "90°",
+            DisplayRotation::Clockwise180 => "180°",
+            DisplayRotation::Clockwise270 => "270°",
+        })
+    }
+}
+
+impl DisplayRotation {
+    pub const ALL: [Self; 4] = [
+        Self::None,
+        Self::Clockwise90,
+        Self::Clockwise180,
+        Self::Clockwise270,
+    ];
+
+    pub fn swaps_axes(self) -> bool {
+        matches!(self, Self::Clockwise90 | Self::Clockwise270)
+    }
+
+    /// Size of the skin coordinate space for a framebuffer of `size`
+    pub fn logical_size(self, (width, height): (u32, u32)) -> (u32, u32) {
+        if self.swaps_axes() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    pub fn logical_viewport(self, viewport: Viewport) -> Viewport {
+        let (width, height) = self.logical_size((viewport.width, viewport.height));
+        Viewport {
+            width,
+            height,
+            ..viewport
+        }
+    }
+
+    /// Angle in radians the camera has to be rolled by around its view direction
+    pub fn camera_roll(self) -> f32 {
+        match self {
+            Self::None => 0.0,
+            Self::Clockwise90 => -std::f32::consts::FRAC_PI_2,
+            Self::Clockwise180 => std::f32::consts::PI,
+            Self::Clockwise270 => std::f32::consts::FRAC_PI_2,
+        }
+    }
+
+    /// Translation and then rotation in radians that map logical canvas coordinates to a
+    /// physical canvas of `size`
+    pub fn canvas_transform(self, (width, height): (f32, f32)) -> ((f32, f32), f32) {
+        use std::f32::consts::{FRAC_PI_2, PI};
+        match self {
+            Self::None => ((0.0, 0.0), 0.0),
+            Self::Clockwise90 => ((width, 0.0), FRAC_PI_2),
+            Self::Clockwise180 => ((width, height), PI),
+            Self::Clockwise270 => ((0.0, height), -FRAC_PI_2),
+        }
+    }
+
+    /// Maps a logical position to the physical canvas of `size`
+    pub fn to_physical(self, (x, y): (f64, f64), (width, height): (f64, f64)) -> (f64, f64) {
+        match self {
+            Self::None => (x, y),
+            Self::Clockwise90 => (width - y, x),
+            Self::Clockwise180 => (width - x, height - y),
+            Self::Clockwise270 => (y, height - x),
+        }
+    }
+
+    /// Maps a position on the physical canvas of `size`, like the mouse cursor, to logical
+    /// coordinates
+    pub fn to_logical(self, (x, y): (f64, f64), (width, height): (f64, f64)) -> (f64, f64) {
+        match self {
+            Self::None => (x, y),
+            Self::Clockwise90 => (y, width - x),
+            Self::Clockwise180 => (width - x, height - y),
+            Self::Clockwise270 => (height - y, x),
+        }
+    }
+
+    /// Part of the framebuffer viewport `physical` that the `logical` area of its logical
+    /// viewport ends up in
+    pub fn physical_viewport(self, logical: Viewport, physical: Viewport) -> Viewport {
+        let size = (physical.width as f64, physical.height as f64);
+        let (_, logical_height) = self.logical_size((physical.width, physical.height));
+        // Viewports start at the bottom left, canvas coordinates at the top left
+        let left = (logical.x - physical.x) as f64;
+        let top = logical_height as f64 - (logical.y - physical.y) as f64 - logical.height as f64;
+
+        let (ax, ay) = self.to_physical((left, top), size);
+        let (bx, by) = self.to_physical(
+            (left + logical.width as f64, top + logical.height as f64),
+            size,
+        );
+        let (width, height) = self.logical_size((logical.width, logical.height));
+        let bottom = physical.height as f64 - ay.max(by);
+
+        Viewport {
+            x: physical.x + ax.min(bx).round() as i32,
+            y: physical.y + bottom.round() as i32,
+            width,
+            height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use three_d::Viewport;
+
+    use super::DisplayRotation;
+
+    const SIZE: (f64, f64) = (1280.0, 720.0);
+
+    #[test]
+    fn round_trip() {
+        for rotation in DisplayRotation::ALL {
+            for pos in [(0.0, 0.0), (100.0, 50.0), (1280.0, 720.0)] {
+                let physical = rotation.to_physical(pos, SIZE);
+                assert_eq!(rotation.to_logical(physical, SIZE), pos, "{rotation}");
+            }
+        }
+    }
+
+    #[test]
+    fn canvas_transform_matches() {
+        for rotation in DisplayRotation::ALL {
+            let ((tx, ty), angle) = rotation.canvas_transform((SIZE.0 as f32, SIZE.1 as f32));
+            let (x, y) = (100.0_f32, 50.0_f32);
+            let rotated = (
+                x * angle.cos() - y * angle.sin() + tx,
+                x * angle.sin() + y * angle.cos() + ty,
+            );
+            let expected = rotation.to_physical((x as f64, y as f64), SIZE);
+            assert!((rotated.0 as f64 - expected.0).abs() < 1e-3, "{rotation}");
+            assert!((rotated.1 as f64 - expected.1).abs() < 1e-3, "{rotation}");
+        }
+    }
+
+    #[test]
+    fn portrait_on_landscape() {
+        let rotation = DisplayRotation::Clockwise90;
+        let physical = Viewport::new_at_origo(1280, 720);
+        let logical = rotation.logical_viewport(physical);
+        assert_eq!((logical.width, logical.height), (720, 1280));
+        assert_eq!(rotation.physical_viewport(logical, physical), physical);
+
+        // The left half of the portrait screen is the top half of the framebuffer
+        let left_half = Viewport {
+            width: 360,
+            ..logical
+        };
+        assert_eq!(
+            rotation.physical_viewport(left_half, physical),
+            Viewport {
+                x: 0,
+                y: 360,
+                width: 1280,
+                height: 360
+            }
+        );
+    }
+}
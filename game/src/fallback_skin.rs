@@ -0,0 +1,236 @@
+use std::{collections::BTreeSet, fmt::Display, path::Path, sync::Mutex};
+
+use log::warn;
+
+/// Scripts the game can't run without, relative to the skin's `scripts` folder
+pub const REQUIRED_SCRIPTS: &[&str] = &[
+    "titlescreen.lua",
+    "songselect/songwheel.lua",
+    "songselect/background.lua",
+    "songselect/filterwheel.lua",
+    "songselect/sortwheel.lua",
+    "gameplay.lua",
+    "gamesettingsdialog.lua",
+    "result.lua",
+    "transition.lua",
+    "songtransition.lua",
+];
+
+/// Textures loaded directly by the gameplay scene, relative to the skin's `textures` folder
+pub const REQUIRED_TEXTURES: &[&str] = &[
+    "button.png",
+    "buttonhold.png",
+    "fxbutton.png",
+    "fxbuttonhold.png",
+    "track.png",
+    "laser_l.png",
+    "laser_r.png",
+    "scorehit.png",
+    "hitcolors.png",
+];
+
+/// Loaded before any of the fallback scripts
+pub const HELPERS: &str = include_str!("static_assets/fallback_skin/helpers.lua");
+
+const SCRIPTS: &[(&str, &str)] = &[
+    (
+        "titlescreen.lua",
+        include_str!("static_assets/fallback_skin/titlescreen.lua"),
+    ),
+    (
+        "songselect/songwheel.lua",
+        include_str!("static_assets/fallback_skin/songselect/songwheel.lua"),
+    ),
+    (
+        "songselect/background.lua",
+        include_str!("static_assets/fallback_skin/songselect/background.lua"),
+    ),
+    (
+        "songselect/filterwheel.lua",
+        include_str!("static_assets/fallback_skin/songselect/filterwheel.lua"),
+    ),
+    (
+        "songselect/sortwheel.lua",
+        include_str!("static_assets/fallback_skin/songselect/sortwheel.lua"),
+    ),
+    (
+        "gameplay.lua",
+        include_str!("static_assets/fallback_skin/gameplay.lua"),
+    ),
+    (
+        "gamesettingsdialog.lua",
+        include_str!("static_assets/fallback_skin/gamesettingsdialog.lua"),
+    ),
+    (
+        "result.lua",
+        include_str!("static_assets/fallback_skin/result.lua"),
+    ),
+    (
+        "transition.lua",
+        include_str!("static_assets/fallback_skin/transition.lua"),
+    ),
+    (
+        "songtransition.lua",
+        include_str!("static_assets/fallback_skin/songtransition.lua"),
+    ),
+];
+
+const TEXTURES: &[(&str, &[u8])] = &[
+    (
+        "button.png",
+        include_bytes!("static_assets/fallback_skin/textures/button.png"),
+    ),
+    (
+        "buttonhold.png",
+        include_bytes!("static_assets/fallback_skin/textures/buttonhold.png"),
+    ),
+    (
+        "fxbutton.png",
+        include_bytes!("static_assets/fallback_skin/textures/fxbutton.png"),
+    ),
+    (
+        "fxbuttonhold.png",
+        include_bytes!("static_assets/fallback_skin/textures/fxbuttonhold.png"),
+    ),
+    (
+        "track.png",
+        include_bytes!("static_assets/fallback_skin/textures/track.png"),
+    ),
+    (
+        "laser_l.png",
+        include_bytes!("static_assets/fallback_skin/textures/laser_l.png"),
+    ),
+    (
+        "laser_r.png",
+        include_bytes!("static_assets/fallback_skin/textures/laser_r.png"),
+    ),
+    (
+        "scorehit.png",
+        include_bytes!("static_assets/fallback_skin/textures/scorehit.png"),
+    ),
+    (
+        "hitcolors.png",
+        include_bytes!("static_assets/fallback_skin/textures/hitcolors.png"),
+    ),
+];
+
+/// Built-in version of a skin script
+pub fn script(name: &str) -> Option<&'static str> {
+    SCRIPTS
+        .iter()
+        .find(|(script, _)| *script == name)
+        .map(|(_, code)| *code)
+}
+
+/// Built-in version of a texture the game loads from the `textures` folder of the skin, if the
+/// skin doesn't have it
+pub fn texture(path: &Path) -> Option<&'static [u8]> {
+    if path.is_file() || path.parent()?.file_name()? != "textures" {
+        return None;
+    }
+    let name = path.file_name()?.to_str()?;
+    let (_, data) = TEXTURES.iter().find(|(texture, _)| *texture == name)?;
+    warn!("{} not found, using fallback", path.display());
+    report_missing(&format!("textures/{name}"));
+    Some(data)
+}
+
+struct Notice {
+    missing: BTreeSet<String>,
+    dismissed: bool,
+}
+
+static NOTICE: Mutex<Notice> = Mutex::new(Notice {
+    missing: BTreeSet::new(),
+    dismissed: false,
+});
+
+/// Records that the fallback was used for `name`, the notice is shown again if it's a new file
+pub fn report_missing(name: &str) {
+    let mut notice = NOTICE.lock().expect("Lock error");
+    if notice.missing.insert(name.to_string()) {
+        notice.dismissed = false;
+    }
+}
+
+/// Files that had to be replaced by the fallback skin, if the user hasn't dismissed the notice
+pub fn pending_notice() -> Option<Vec<String>> {
+    let notice = NOTICE.lock().expect("Lock error");
+    if notice.dismissed || notice.missing.is_empty() {
+        None
+    } else {
+        Some(notice.missing.iter().cloned().collect())
+    }
+}
+
+pub fn dismiss_notice() {
+    NOTICE.lock().expect("Lock error").dismissed = true;
+}
+
+/// Required files missing from a skin folder
+#[derive(Debug, Default, Clone)]
+pub struct SkinReport {
+    pub missing_scripts: Vec<&'static str>,
+    pub missing_textures: Vec<&'static str>,
+}
+
+impl SkinReport {
+    pub fn check(skin_path: &Path) -> Self {
+        let missing = |folder: &str, files: &[&'static str]| {
+            files
+                .iter()
+                .copied()
+                .filter(|file| !skin_path.join(folder).join(file).is_file())
+                .collect()
+        };
+
+        Self {
+            missing_scripts: missing("scripts", REQUIRED_SCRIPTS),
+            missing_textures: missing("textures", REQUIRED_TEXTURES),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.missing_scripts.is_empty() && self.missing_textures.is_empty()
+    }
+}
+
+impl Display for SkinReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.missing_scripts.is_empty() {
+            writeln!(f, "Missing scripts: {}", self.missing_scripts.join(", "))?;
+        }
+        if !self.missing_textures.is_empty() {
+            writeln!(f, "Missing textures: {}", self.missing_textures.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{script, texture, SkinReport, REQUIRED_SCRIPTS, REQUIRED_TEXTURES};
+
+    #[test]
+    fn required_files_have_fallbacks() {
+        for name in REQUIRED_SCRIPTS {
+            assert!(script(name).is_some(), "No fallback for {name}");
+        }
+        let textures = std::env::temp_dir()
+            .join("rusc_missing_skin")
+            .join("textures");
+        for name in REQUIRED_TEXTURES {
+            let data = texture(&textures.join(name));
+            let data = data.unwrap_or_else(|| panic!("No fallback for {name}"));
+            assert!(image::load_from_memory(data).is_ok(), "Broken {name}");
+        }
+    }
+
+    #[test]
+    fn report_missing_skin() {
+        let report = SkinReport::check(&std::env::temp_dir().join("rusc_missing_skin"));
+        assert!(!report.is_ok());
+        assert_eq!(report.missing_scripts, REQUIRED_SCRIPTS);
+        assert_eq!(report.missing_textures, REQUIRED_TEXTURES);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+
+use femtovg::{Canvas, Color, Paint, Path, Renderer};
+
+/// Frame times at or under this are drawn green
+const GOOD_MS: f32 = 1000.0 / 60.0;
+/// Frame times at or under this are drawn yellow, longer ones red
+const SLOW_MS: f32 = 1000.0 / 30.0;
+/// Frame time shown at the top of the graph, longer frames are cut off
+const MAX_MS: f32 = 50.0;
+
+/// Times of the last frames, drawn as a bar graph with one pixel per frame
+#[derive(Debug, Default)]
+pub struct FrameGraph {
+    times: VecDeque<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameLevel {
+    Good,
+    Slow,
+    Bad,
+}
+
+impl FrameLevel {
+    fn of(ms: f32) -> Self {
+        if ms <= GOOD_MS {
+            Self::Good
+        } else if ms <= SLOW_MS {
+            Self::Slow
+        } else {
+            Self::Bad
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            FrameLevel::Good => Color::rgb(64, 200, 64),
+            FrameLevel::Slow => Color::rgb(230, 200, 40),
+            FrameLevel::Bad => Color::rgb(230, 50, 50),
+        }
+    }
+}
+
+impl FrameGraph {
+    pub const LEN: usize = 240;
+    pub const HEIGHT: f32 = 60.0;
+
+    pub fn push(&mut self, ms: f64) {
+        if self.times.len() == Self::LEN {
+            self.times.pop_front();
+        }
+        self.times.push_back(ms as f32);
+    }
+
+    /// Draws the graph with its bottom left corner at `(x, y)`
+    pub fn render<T: Renderer>(&self, canvas: &mut Canvas<T>, x: f32, y: f32) {
+        let mut background = Path::new();
+        background.rect(x, y - Self::HEIGHT, Self::LEN as f32, Self::HEIGHT);
+        canvas.fill_path(&background, &Paint::color(Color::rgba(0, 0, 0, 160)));
+
+        // One path per color keeps the draw calls down to three
+        for level in [FrameLevel::Good, FrameLevel::Slow, FrameLevel::Bad] {
+            let mut bars = Path::new();
+            for (i, ms) in self.times.iter().enumerate() {
+                if FrameLevel::of(*ms) != level {
+                    continue;
+                }
+                let height = (ms / MAX_MS).min(1.0) * Self::HEIGHT;
+                bars.rect(x + i as f32, y - height, 1.0, height);
+            }
+            canvas.fill_path(&bars, &Paint::color(level.color()));
+        }
+
+        let mut target = Path::new();
+        let target_y = y - GOOD_MS / MAX_MS * Self::HEIGHT;
+        target.move_to(x, target_y);
+        target.line_to(x + Self::LEN as f32, target_y);
+        canvas.stroke_path(
+            &target,
+            &Paint::color(Color::rgba(255, 255, 255, 96)).with_line_width(1.0),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameGraph, FrameLevel};
+
+    #[test]
+    fn frame_graph() {
+        let mut graph = FrameGraph::default();
+        for i in 0..FrameGraph::LEN + 10 {
+            graph.push(i as f64);
+        }
+        assert_eq!(graph.times.len(), FrameGraph::LEN);
+        assert_eq!(graph.times[0], 10.0);
+
+        assert_eq!(FrameLevel::of(8.0), FrameLevel::Good);
+        assert_eq!(FrameLevel::of(20.0), FrameLevel::Slow);
+        assert_eq!(FrameLevel::of(40.0), FrameLevel::Bad);
+    }
+}
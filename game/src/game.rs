@@ -1,12 +1,20 @@
 use crate::{
-    button_codes::{UscButton, UscInputEvent},
+    audio::MixerExt,
+    button_codes::{ActionKind, UscButton, UscInputEvent},
+    companion_interface::{CompanionServer, GameState, JudgementEvent, JudgementRating, PlayState},
     config::{GameConfig, ScoreDisplayMode},
-    game_main::AutoPlay,
-    input_state::InputState,
+    display_rotation::DisplayRotation,
+    fallback_skin,
+    game_main::{AutoPlay, GameResult},
+    input_state::{ButtonChord, InputState},
+    lighting::{LaserLight, LightState, LightingService, Rgb},
     log_result,
     lua_service::LuaProvider,
+    play_stats::{PlayRecord, StatsTracker},
+    results::calculate_clear_mark,
     scene::{Scene, SceneData},
     shaded_mesh::ShadedMesh,
+    song_provider::load_swap_audio,
     songselect::Song,
     vg_ui::Vgfx,
     ControlMessage,
@@ -17,11 +25,10 @@ use di::{RefMut, ServiceProvider};
 use egui::epaint::Hsva;
 use egui_plot::{Line, PlotPoints};
 use image::GenericImageView;
-use itertools::Itertools;
 use kson::{
     effects::AudioEffect,
     score_ticks::{PlacedScoreTick, ScoreTick, ScoreTickSummary, ScoreTicker},
-    Chart, Graph, Side,
+    BtLane, Chart, Graph, LaneModifiers, Side,
 };
 use kson_music_playback::GetBiQuadState;
 use kson_rodio_sources::{
@@ -31,16 +38,16 @@ use kson_rodio_sources::{
 
 use log::{info, warn};
 use puffin::{profile_function, profile_scope};
-use rodio::{dynamic_mixer::DynamicMixerController, source::Buffered, Decoder, Source};
+use rodio::{dynamic_mixer::DynamicMixerController, source::Buffered, Source};
 use std::{
     cmp::Ordering,
-    collections::{BTreeMap, VecDeque},
+    collections::{BTreeMap, HashMap, VecDeque},
     f32::consts::SQRT_2,
     ops::Sub,
     path::PathBuf,
     rc::Rc,
     sync::{mpsc::Sender, Arc},
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 use tealr::mlu::mlua::{Function, Lua, LuaSerdeExt};
 use three_d::{vec2, vec3, Blend, Camera, Mat4, Matrix4, Vec3, Vec4, Viewport, Zero};
@@ -53,12 +60,70 @@ use camera::*;
 mod background;
 use background::GameBackground;
 mod lua_data;
-pub use lua_data::HitWindow;
 pub(crate) use lua_data::LuaGameState;
+pub use lua_data::{HitWindow, HudVisibility};
 pub mod graphics;
+pub mod hold;
+use hold::{HoldButton, HoldGrace, HoldTiming};
+mod track_overlay;
+use track_overlay::TrackOverlay;
+mod video_background;
+use video_background::VideoBackground;
+mod scroll_speed;
+pub mod scrubber;
+use scrubber::{ChartScrubber, ScrubberAction};
+mod chart_reload;
+use chart_reload::ChartWatcher;
+mod chip_matching;
+use chip_matching::ChipJudgement;
+mod versus;
+pub use versus::VersusData;
+mod slam_sound;
+use slam_sound::SlamSound;
+pub mod modifiers;
+pub mod track_layout;
+use track_layout::TrackLayout;
 
 const LASER_THRESHOLD: f64 = 1.0 / 12.0;
-const LEADIN: Duration = Duration::from_secs(3);
+/// Judgements listed in the debug judgement log
+const JUDGEMENT_LOG_LEN: usize = 50;
+/// Track positions around which objects fade in and out, given to the track shaders and the
+/// skin's `render_lane_cover`
+const HIDDEN_CUTOFF: f32 = 0.0;
+const HIDDEN_FADE_WINDOW: f32 = 100.0;
+const SUDDEN_CUTOFF: f32 = 10.0;
+const SUDDEN_FADE_WINDOW: f32 = 1000.0;
+/// Results are shown after this even if the skin's `render_outro` never finishes
+const OUTRO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Glow and texture state of active holds and lasers at `time_ms`. The glow normally flashes
+/// ten times a second, with reduced flashing it slowly breathes and the texture stays the same.
+fn hold_glow(time_ms: f64, reduce_flashing: bool) -> (f32, i32) {
+    if reduce_flashing {
+        let glow = (time_ms.rem_euclid(1000.0) / 500.0 - 1.0).abs() * 0.2 + 0.65;
+        (glow as f32, 2)
+    } else {
+        let glow = ((time_ms as f32 % 100.0) / 50.0 - 1.0).abs() * 0.5 + 0.5;
+        (glow, (time_ms / 50.0).rem_euclid(2.0) as i32 + 2)
+    }
+}
+
+/// Hash of the played difficulty, it seeds the random lanes together with the play's seed
+fn chart_hash(song: &Song, diff_idx: usize) -> String {
+    song.difficulties.read().expect("Lock error")[diff_idx]
+        .hash
+        .clone()
+        .unwrap_or_default()
+}
+
+/// Skin animation between the end of a play and the result screen
+struct Outro {
+    started: Instant,
+    /// [`crate::results::ClearMark`] of the play as passed to `render_outro`, 1 is failed and 5 is perfect
+    clear_state: u8,
+    /// Set once `render_outro` returns true
+    done: bool,
+}
 
 pub struct Game {
     view: ChartView,
@@ -75,8 +140,16 @@ pub struct Game {
     camera: ChartCamera,
     lua_game_state: lua_data::LuaGameState,
     hit_window: HitWindow,
+    hold_grace: HoldGrace,
+    /// Presses of the BT and FX buttons for judging holds
+    hold_buttons: [HoldButton; 6],
     lua: Rc<Lua>,
     intro_done: bool,
+    /// Held behind the transition until it reveals the game, the intro, clock and audio only
+    /// start after the first resume
+    suspended: bool,
+    /// Set on the first tick while shown, suspending after that ends the play
+    shown: bool,
     song: Arc<Song>,
     diff_idx: usize,
     control_tx: Option<Sender<ControlMessage>>,
@@ -111,22 +184,93 @@ pub struct Game {
     mixer: Arc<DynamicMixerController<f32>>,
     biquad_control: BiquadController,
     source_owner: owned_source::Marker,
-    slam_sample: Option<Buffered<Decoder<std::fs::File>>>,
+    slam_sound: SlamSound,
     slam_marker: owned_source::Marker,
     background: Option<GameBackground>,
     foreground: Option<GameBackground>,
+    video_background: Option<VideoBackground>,
     service_provider: ServiceProvider,
     sync_delta: VecDeque<f64>,
     laser_effects: BTreeMap<u32, AudioEffect>,
     default_laser_effect: AudioEffect,
     autoplay: AutoPlay,
-    slam_volume: f32,
-    chip_h: f32,
     laser_buffer: [VecDeque<(SystemTime, f64)>; 2],
     laser_input_delay: Duration,
     laser_offset: f64,
     button_offset: f64,
     global_offset: f64,
+    scrubber: Option<ChartScrubber>,
+    chart_watcher: Option<ChartWatcher>,
+    practice_loop: Option<(u32, u32)>,
+    player: u8,
+    /// False for player 2 in versus mode, the song and effects are played by player 1
+    play_audio: bool,
+    lead_in: Duration,
+    /// Chart time of the first note in milliseconds
+    first_note_ms: f64,
+    /// Beats remaining last sent to the `countdown` lua callback
+    countdown: Option<u32>,
+    /// Whether the skin defines `render_track_overlay`
+    has_track_overlay: bool,
+    track_overlay: Option<TrackOverlay>,
+    /// Times the chart was restarted without going back to song select
+    retries: u32,
+    /// Already applied to `chart`
+    modifiers: LaneModifiers,
+    /// Set when the chart ends or the gauge fails, judgement stops until the results are shown
+    outro: Option<Outro>,
+    /// A companion client is subscribed to judgements, checked every frame
+    collect_judgements: bool,
+    /// Judgements of the current frame for the companion server
+    judgement_batch: Vec<JudgementEvent>,
+    /// `None` when quick retry is disabled
+    quick_retry: Option<crate::config::QuickRetry>,
+    retry_chord: ButtonChord,
+    /// Set once the current play was sent to the [`StatsTracker`]
+    play_recorded: bool,
+    /// Tempo changes of the chart for skins, computed once per chart
+    bpm_changes: Vec<lua_data::BpmChange>,
+}
+
+/// Meshes drawn on the track, sorted by [`ShadedMesh::draw_order`] before drawing
+#[derive(Debug, Clone, Copy)]
+enum TrackObject {
+    Track,
+    FxHold,
+    BtHold,
+    FxChip,
+    BtChip,
+    LaneBeams,
+    /// `[side][part]` of `laser_shaders`
+    Laser(usize, usize),
+}
+
+impl TrackObject {
+    const ALL: [Self; 10] = [
+        Self::Track,
+        Self::FxHold,
+        Self::BtHold,
+        Self::FxChip,
+        Self::BtChip,
+        Self::LaneBeams,
+        Self::Laser(0, 0),
+        Self::Laser(0, 1),
+        Self::Laser(1, 0),
+        Self::Laser(1, 1),
+    ];
+
+    /// Draws lasers over notes and notes over the track
+    fn default_priority(self) -> i32 {
+        match self {
+            Self::Track => 0,
+            Self::FxHold => 10,
+            Self::BtHold => 20,
+            Self::FxChip => 30,
+            Self::BtChip => 40,
+            Self::LaneBeams => 50,
+            Self::Laser(side, part) => 60 + (side * 2 + part) as i32,
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -143,17 +287,26 @@ pub enum HitRating {
     Crit {
         tick: PlacedScoreTick,
         delta: f64,
+        /// Chart time of the judgement in milliseconds, without offsets or lead-in
         time: f64,
+        /// When the judged input happened, `None` for notes judged without an input
+        input_time: Option<SystemTime>,
     },
     Good {
         tick: PlacedScoreTick,
         delta: f64,
+        /// Chart time of the judgement in milliseconds, without offsets or lead-in
         time: f64,
+        /// When the judged input happened, `None` for notes judged without an input
+        input_time: Option<SystemTime>,
     },
     Miss {
         tick: PlacedScoreTick,
         delta: f64,
+        /// Chart time of the judgement in milliseconds, without offsets or lead-in
         time: f64,
+        /// When the judged input happened, `None` for notes judged without an input
+        input_time: Option<SystemTime>,
     },
 }
 
@@ -213,6 +366,15 @@ impl HitRating {
         }
     }
 
+    pub fn tick(self) -> Option<PlacedScoreTick> {
+        match self {
+            HitRating::None => None,
+            HitRating::Crit { tick, .. }
+            | HitRating::Good { tick, .. }
+            | HitRating::Miss { tick, .. } => Some(tick),
+        }
+    }
+
     pub fn time(self) -> f64 {
         match self {
             HitRating::None => f64::NAN,
@@ -222,6 +384,26 @@ impl HitRating {
         }
     }
 
+    /// Event for companion clients subscribed to judgements, `combo` is the combo after it
+    pub fn companion_event(self, combo: u32) -> Option<JudgementEvent> {
+        let (rating, tick, delta) = match self {
+            HitRating::None => return None,
+            HitRating::Crit { tick, delta, .. } => (JudgementRating::Crit, tick, delta),
+            HitRating::Good { tick, delta, .. } => (JudgementRating::Near, tick, delta),
+            HitRating::Miss { tick, delta, .. } => (JudgementRating::Miss, tick, delta),
+        };
+        let lane = match tick.tick {
+            ScoreTick::Chip { lane } | ScoreTick::Hold { lane, .. } => lane,
+            ScoreTick::Laser { lane, .. } | ScoreTick::Slam { lane, .. } => 6 + lane,
+        };
+        Some(JudgementEvent {
+            lane: lane as u8,
+            rating,
+            delta: if delta.is_finite() { delta } else { 0.0 },
+            combo,
+        })
+    }
+
     pub fn for_stats(self) -> bool {
         match self {
             HitRating::None => false,
@@ -249,20 +431,14 @@ impl HitRating {
 
 impl From<&Gauge> for lua_data::LuaGauge {
     fn from(value: &Gauge) -> Self {
-        match value {
-            Gauge::Normal { value, .. } => lua_data::LuaGauge {
-                gauge_type: 0,
-                options: 0,
-                value: *value,
-                name: "Normal".into(),
+        match value.spec() {
+            Some(spec) => lua_data::LuaGauge {
+                gauge_type: spec.gauge_type as i32,
+                options: spec.option(),
+                value: value.value(),
+                name: spec.name(),
             },
-            Gauge::Hard { value, .. } => lua_data::LuaGauge {
-                gauge_type: 1,
-                options: 0,
-                value: *value,
-                name: "Hard".into(),
-            },
-            Gauge::None => lua_data::LuaGauge {
+            None => lua_data::LuaGauge {
                 gauge_type: 0,
                 options: 0,
                 value: 0.0,
@@ -283,8 +459,12 @@ pub struct GameData {
     diff_idx: usize,
     chart: kson::Chart,
     skin_folder: PathBuf,
-    audio: std::boxed::Box<(dyn rodio::source::Source<Item = f32> + std::marker::Send + 'static)>,
+    audio: Buffered<Box<dyn Source<Item = f32> + Send>>,
+    /// Decoded audio of the `AudioSwap` effects by file name
+    swap_audio: HashMap<String, Arc<[f32]>>,
     autoplay: AutoPlay,
+    chart_path: Option<PathBuf>,
+    retries: u32,
 }
 
 impl GameData {
@@ -305,10 +485,58 @@ impl GameData {
             skin_folder,
             diff_idx,
             song,
-            audio: Box::new(audio),
+            audio: audio.buffered(),
+            swap_audio: HashMap::new(),
             autoplay,
+            chart_path: None,
+            retries: 0,
         })
     }
+
+    /// File the chart was loaded from, watched for changes when the charting aid is enabled
+    pub fn with_chart_path(mut self, chart_path: Option<PathBuf>) -> Self {
+        self.chart_path = chart_path;
+        self
+    }
+
+    /// Decodes the audio the `AudioSwap` effects of the chart switch to from the folder of the
+    /// chart file, has to come after [`GameData::with_chart_path`]
+    pub fn with_swap_audio(mut self) -> Self {
+        if let Some(chart_path) = &self.chart_path {
+            self.swap_audio = load_swap_audio(
+                &self.chart,
+                chart_path,
+                self.audio.channels(),
+                self.audio.sample_rate(),
+            );
+        }
+        self
+    }
+
+    /// Whether this plays `diff_idx` of `song`
+    pub fn is_chart(&self, song: &Song, diff_idx: usize) -> bool {
+        self.song.id == song.id && self.diff_idx == diff_idx
+    }
+
+    /// Copy for playing the chart again, the decoded audio is shared
+    fn retry_copy(&self) -> Self {
+        Self {
+            song: self.song.clone(),
+            diff_idx: self.diff_idx,
+            chart: self.chart.clone(),
+            skin_folder: self.skin_folder.clone(),
+            audio: self.audio.clone(),
+            swap_audio: self.swap_audio.clone(),
+            autoplay: self.autoplay,
+            chart_path: self.chart_path.clone(),
+            retries: self.retries,
+        }
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
 }
 
 impl SceneData for GameData {
@@ -316,16 +544,33 @@ impl SceneData for GameData {
         self: Box<Self>,
         service_provider: ServiceProvider,
     ) -> anyhow::Result<Box<dyn Scene>> {
+        // Kept for retrying from the result screen without loading the chart again
+        let retry = self.retry_copy();
+        let game_data = service_provider.get_required_mut::<crate::game_data::GameData>();
+        let game = self.into_game(service_provider)?;
+        game_data.write().expect("Lock error").last_played = Some(retry);
+        Ok(Box::new(game))
+    }
+}
+
+impl GameData {
+    fn into_game(self, service_provider: ServiceProvider) -> anyhow::Result<Game> {
         let Self {
-            chart,
+            mut chart,
             skin_folder,
             diff_idx,
             song,
             audio,
+            swap_audio,
             autoplay,
-        } = *self;
+            chart_path,
+            retries,
+        } = self;
         profile_function!();
 
+        let modifiers = GameConfig::get().modifiers.roll();
+        chart.apply_modifiers(&chart_hash(&song, diff_idx), &modifiers);
+
         let context = service_provider
             .get_required::<three_d::Context>()
             .as_ref()
@@ -363,11 +608,6 @@ impl SceneData for GameData {
             true,
         )?;
 
-        fx_long_shader.set_data_mesh(&graphics::xy_rect(
-            vec3(0.0, 0.5, 0.0),
-            vec2(2.0 / 6.0, 1.0),
-        ));
-
         let mut bt_long_shader = ShadedMesh::new(&context, "holdbutton", &shader_folder)
             .expect("Failed to load shader:")
             .with_transform(Matrix4::from_translation(vec3(-0.5, 0.0, 0.0)));
@@ -379,11 +619,6 @@ impl SceneData for GameData {
             true,
         )?;
 
-        bt_long_shader.set_data_mesh(&graphics::xy_rect(
-            vec3(0.0, 0.5, 0.0),
-            vec2(1.0 / 6.0, 1.0),
-        ));
-
         let mut fx_chip_shader = ShadedMesh::new(&context, "button", &shader_folder)
             .expect("Failed to load shader:")
             .with_transform(Matrix4::from_translation(vec3(-0.5, 0.0, 0.0)));
@@ -393,13 +628,6 @@ impl SceneData for GameData {
             (false, false),
             true,
         )?;
-        let fx_height = 1.0;
-
-        fx_chip_shader.set_data_mesh(&graphics::xy_rect(
-            vec3(0.0, fx_height / 2.0, 0.0),
-            vec2(2.0 / 6.0, fx_height),
-        ));
-
         let mut bt_chip_shader = ShadedMesh::new(&context, "button", &shader_folder)
             .expect("Failed to load shader:")
             .with_transform(Matrix4::from_translation(vec3(-0.5, 0.0, 0.0)));
@@ -412,18 +640,34 @@ impl SceneData for GameData {
             true,
         )?;
 
+        let layout = TrackLayout::from_skin(
+            &GameConfig::get().skin_settings,
+            bt_tex.height as f32 / bt_tex.width as f32,
+        );
+        let fx_height = 1.0;
+
+        fx_long_shader.set_data_mesh(&graphics::xy_rect(
+            vec3(0.0, 0.5, 0.0),
+            vec2(layout.fx_width, 1.0),
+        ));
+        bt_long_shader.set_data_mesh(&graphics::xy_rect(
+            vec3(0.0, 0.5, 0.0),
+            vec2(layout.lane_width, 1.0),
+        ));
+        fx_chip_shader.set_data_mesh(&graphics::xy_rect(
+            vec3(0.0, fx_height / 2.0, 0.0),
+            vec2(layout.fx_width, fx_height),
+        ));
         bt_chip_shader.set_data_mesh(&graphics::xy_rect(
             vec3(0.0, bt_height / 2.0, 0.0),
-            vec2(1.0 / 6.0, bt_height),
+            vec2(layout.lane_width, bt_height),
         ));
 
-        let chip_h = (1.0 / 6.0) * (bt_tex.height as f32 / bt_tex.width as f32);
-
         let mut track_shader =
             ShadedMesh::new(&context, "track", &shader_folder).expect("Failed to load shader:");
         track_shader.set_data_mesh(&graphics::xy_rect(
             Vec3::zero(),
-            vec2(1.0, ChartView::TRACK_LENGTH * 2.0),
+            vec2(layout.track_width(), ChartView::TRACK_LENGTH * 2.0),
         ));
 
         let laser_colors: [three_d::Vector4<f32>; 2] = [
@@ -435,8 +679,8 @@ impl SceneData for GameData {
                 .into(),
         ];
 
-        track_shader.set_param("lCol", laser_colors[0]);
-        track_shader.set_param("rCol", laser_colors[1]);
+        track_shader.set_param_if_declared("lCol", laser_colors[0]);
+        track_shader.set_param_if_declared("rCol", laser_colors[1]);
 
         track_shader.use_texture(
             "mainTex",
@@ -480,11 +724,15 @@ impl SceneData for GameData {
             true,
         )?;
 
-        let beam_colors: Vec<_> = image::open(texture_folder.with_file_name("hitcolors.png"))
-            .expect("Failed to load hitcolors.png")
-            .pixels()
-            .map(|x| x.2)
-            .collect();
+        let hitcolors = texture_folder.with_file_name("hitcolors.png");
+        let beam_colors: Vec<_> = match fallback_skin::texture(&hitcolors) {
+            Some(data) => image::load_from_memory(data),
+            None => image::open(hitcolors),
+        }
+        .expect("Failed to load hitcolors.png")
+        .pixels()
+        .map(|x| x.2)
+        .collect();
 
         laser_left.set_blend(Blend::ADD);
         laser_left_active.set_blend(Blend::ADD);
@@ -494,15 +742,15 @@ impl SceneData for GameData {
         let mut playback = kson_music_playback::AudioPlayback::new();
         let (biquad_control, _) = std::sync::mpsc::channel();
         playback
-            .open(audio, "Game", None)
+            .open_buffered(audio, "Game", None)
             .expect("Failed to load audio");
+        for (name, samples) in swap_audio {
+            playback.add_swap_audio(name, samples);
+        }
         playback.build_effects(&chart);
         playback.stop();
         let laser_effects = chart.laser_effect_queue();
 
-        //TODO: No need to set leadin if first tick is beyond the leadin time.
-        playback.set_leadin(LEADIN);
-
         let bg = chart
             .bg
             .legacy
@@ -567,7 +815,27 @@ impl SceneData for GameData {
             })
             .flatten();
 
-        Ok(Box::new(Game::new(
+        let jacket_path = song.difficulties.read().expect("Lock error")[diff_idx]
+            .jacket_path
+            .clone();
+        let video_background = bg_enabled
+            .then(|| video_background::chart_video(&chart, &jacket_path))
+            .flatten()
+            .and_then(|(path, offset)| {
+                VideoBackground::new(&context, &path, offset)
+                    .inspect_err(|e| {
+                        log::warn!("Failed to load video background: {e} \n {:?}", &path)
+                    })
+                    .ok()
+            });
+
+        let config = GameConfig::get();
+        let chart_watcher = chart_path
+            .filter(|_| config.charting_aid || config.args.charting)
+            .map(|path| ChartWatcher::new(path, &chart));
+        drop(config);
+
+        let mut game = Game::new(
             chart,
             &skin_folder,
             &context,
@@ -589,12 +857,17 @@ impl SceneData for GameData {
             biquad_control,
             background,
             foreground,
+            video_background,
             service_provider,
             laser_effects,
             autoplay,
-            chip_h,
+            layout,
             laser_colors,
-        )?))
+        )?;
+        game.chart_watcher = chart_watcher;
+        game.retries = retries;
+        game.modifiers = modifiers;
+        Ok(game)
     }
 }
 
@@ -620,14 +893,16 @@ impl Game {
         biquad_control: BiquadController,
         background: Option<GameBackground>,
         foreground: Option<GameBackground>,
+        video_background: Option<VideoBackground>,
         service_provider: ServiceProvider,
         laser_effects: BTreeMap<u32, AudioEffect>,
         autoplay: AutoPlay,
-        chip_h: f32,
+        layout: TrackLayout,
         laser_colors: [three_d::Vector4<f32>; 2],
     ) -> Result<Self> {
-        let mut view = ChartView::new(skin_root, td)?;
+        let mut view = ChartView::new(td, layout)?;
         view.build_laser_meshes(&chart);
+        view.build_scroll_speed(&chart);
         view.hispeed = (GameConfig::get().mod_speed
             / chart
                 .mode_bpm()
@@ -638,11 +913,15 @@ impl Game {
         slam_path.push("laser_slam.wav");
 
         let score_ticks = kson::score_ticks::generate_score_ticks(&chart);
+        let first_note_ms = chart.tick_to_ms(score_ticks.iter().map(|t| t.y).min().unwrap_or(0));
+        let bpm_changes = lua_data::BpmChange::from_chart(&chart);
 
         let mut res = Self {
             song,
             diff_idx,
             intro_done: false,
+            suspended: false,
+            shown: false,
             lua: LuaProvider::new_lua(),
             chart,
             view,
@@ -694,34 +973,85 @@ impl Game {
             biquad_control,
             background,
             foreground,
+            video_background,
             source_owner: Default::default(),
-            slam_sample: std::fs::File::open(slam_path)
-                .ok()
-                .and_then(|x| Decoder::new(x).ok())
-                .map(|x| x.buffered()),
+            slam_sound: SlamSound::new(&slam_path),
             slam_marker: Default::default(),
             service_provider,
             sync_delta: Default::default(),
             laser_wide: [0, 0],
             laser_alert: [0, 0],
             hit_window: GameConfig::get().hit_window,
+            hold_grace: GameConfig::get().hold_grace,
+            hold_buttons: Default::default(),
             laser_effects,
             default_laser_effect: AudioEffect::PeakingFilter(
                 kson::effects::PeakingFilter::default(),
             ),
             autoplay,
-            slam_volume: GameConfig::get().slam_volume,
-            chip_h,
             laser_buffer: [VecDeque::new(), VecDeque::new()],
             laser_input_delay: GameConfig::get().laser_input_delay,
             button_offset: -GameConfig::get().button_offset as _,
-            global_offset: -GameConfig::get().global_offset as _,
+            global_offset: -GameConfig::get().global_offset,
             laser_offset: -GameConfig::get().laser_offset as _,
+            scrubber: None,
+            chart_watcher: None,
+            practice_loop: None,
+            player: 0,
+            play_audio: true,
+            lead_in: GameConfig::get().lead_in,
+            first_note_ms,
+            countdown: None,
+            has_track_overlay: false,
+            track_overlay: None,
+            retries: 0,
+            modifiers: LaneModifiers::default(),
+            outro: None,
+            collect_judgements: false,
+            judgement_batch: Vec::new(),
+            quick_retry: Some(GameConfig::get().quick_retry.clone()).filter(|r| r.enabled),
+            retry_chord: ButtonChord::default(),
+            play_recorded: false,
+            bpm_changes,
         };
         res.set_track_uniforms();
+        for object in TrackObject::ALL {
+            res.track_mesh_mut(object)
+                .set_priority(object.default_priority());
+        }
+
+        // Silence before the audio so the first note comes at least `lead_in` after the start
+        let first_note_audio_ms = res.without_offset(first_note_ms);
+        let leadin = (res.lead_in.as_secs_f64() * 1000.0 - first_note_audio_ms).max(0.0);
+        res.playback
+            .set_leadin(Duration::from_secs_f64(leadin / 1000.0));
         Ok(res)
     }
 
+    fn track_mesh(&self, object: TrackObject) -> &ShadedMesh {
+        match object {
+            TrackObject::Track => &self.track_shader,
+            TrackObject::FxHold => &self.fx_long_shaders,
+            TrackObject::BtHold => &self.bt_long_shaders,
+            TrackObject::FxChip => &self.fx_chip_shaders,
+            TrackObject::BtChip => &self.bt_chip_shader,
+            TrackObject::LaneBeams => &self.lane_beam_shader,
+            TrackObject::Laser(side, part) => &self.laser_shaders[side][part],
+        }
+    }
+
+    fn track_mesh_mut(&mut self, object: TrackObject) -> &mut ShadedMesh {
+        match object {
+            TrackObject::Track => &mut self.track_shader,
+            TrackObject::FxHold => &mut self.fx_long_shaders,
+            TrackObject::BtHold => &mut self.bt_long_shaders,
+            TrackObject::FxChip => &mut self.fx_chip_shaders,
+            TrackObject::BtChip => &mut self.bt_chip_shader,
+            TrackObject::LaneBeams => &mut self.lane_beam_shader,
+            TrackObject::Laser(side, part) => &mut self.laser_shaders[side][part],
+        }
+    }
+
     fn set_track_uniforms(&mut self) {
         [
             &mut self.track_shader,
@@ -733,26 +1063,131 @@ impl Game {
         .into_iter()
         .chain(self.laser_shaders.iter_mut().flatten())
         .for_each(|shader| {
-            shader.set_param("trackPos", 0.0);
-            shader.set_param("trackScale", 1.0);
-            shader.set_param("hiddenCutoff", 0.0);
-            shader.set_param("hiddenFadeWindow", 100.0);
-            shader.set_param("suddenCutoff", 10.0);
-            shader.set_param("suddenFadeWindow", 1000.0);
-            shader.set_param("hitState", 1);
-            shader.set_param("objectGlow", 0.6);
+            shader.set_param_if_declared("trackPos", 0.0);
+            shader.set_param_if_declared("trackScale", 1.0);
+            shader.set_param_if_declared("hiddenCutoff", HIDDEN_CUTOFF);
+            shader.set_param_if_declared("hiddenFadeWindow", HIDDEN_FADE_WINDOW);
+            shader.set_param_if_declared("suddenCutoff", SUDDEN_CUTOFF);
+            shader.set_param_if_declared("suddenFadeWindow", SUDDEN_FADE_WINDOW);
+            shader.set_param_if_declared("hitState", 1);
+            shader.set_param_if_declared("objectGlow", 0.6);
         });
 
         self.laser_shaders.iter_mut().flatten().for_each(|laser| {
-            laser.set_param("objectGlow", 0.6);
-            laser.set_param("hitState", 1);
+            laser.set_param_if_declared("objectGlow", 0.6);
+            laser.set_param_if_declared("hitState", 1);
         });
         self.laser_shaders[0]
             .iter_mut()
-            .for_each(|ll| ll.set_param("color", self.laser_colors[0]));
+            .for_each(|ll| ll.set_param_if_declared("color", self.laser_colors[0]));
         self.laser_shaders[1]
             .iter_mut()
-            .for_each(|rl| rl.set_param("color", self.laser_colors[1]));
+            .for_each(|rl| rl.set_param_if_declared("color", self.laser_colors[1]));
+    }
+
+    /// Screen y of the hidden and sudden cutoffs in the middle of the track, for lane covers
+    fn cutoffs_on_screen(viewport: Viewport, camera: &Camera) -> (f32, f32) {
+        let screen = vec2(viewport.width as f32, viewport.height as f32);
+        let [hidden, sudden] = [HIDDEN_CUTOFF, SUDDEN_CUTOFF].map(|cutoff| {
+            let point = ChartView::TRACK_DIRECTION * cutoff.clamp(0.0, ChartView::TRACK_LENGTH);
+            graphics::camera_to_screen(camera, point, screen).y
+        });
+        (hidden, sudden)
+    }
+
+    /// Calls the skin's `render_console` with the held buttons and the laser positions
+    fn render_console(&self, dt: f64) -> anyhow::Result<()> {
+        let Ok(render) = self.lua.globals().get::<_, Function>("render_console") else {
+            return Ok(());
+        };
+
+        let buttons = [
+            UscButton::BT(BtLane::A),
+            UscButton::BT(BtLane::B),
+            UscButton::BT(BtLane::C),
+            UscButton::BT(BtLane::D),
+            UscButton::FX(Side::Left),
+            UscButton::FX(Side::Right),
+        ]
+        .map(|button| self.input_state.is_button_held(button).is_some());
+        let lasers = [Side::Left, Side::Right].map(|side| self.input_state.get_axis(side).pos);
+
+        let states = self.lua.create_table()?;
+        states.set("buttons", self.lua.create_sequence_from(buttons)?)?;
+        states.set("lasers", self.lua.create_sequence_from(lasers)?)?;
+        render.call::<_, ()>((dt / 1000.0, states))?;
+        Ok(())
+    }
+
+    /// Sends held buttons, lasers, gauge and combo to the controller lights
+    /// Sends the judgements of this frame to the companion server if anyone subscribed to them
+    fn send_judgements(&mut self) {
+        let Some(companion) = self.service_provider.get_mut::<CompanionServer>() else {
+            return;
+        };
+        let mut companion = companion.write().expect("Lock error");
+        self.collect_judgements = companion.wants_judgements();
+        if !self.collect_judgements {
+            self.judgement_batch.clear();
+            return;
+        }
+
+        companion.push_judgements(
+            std::mem::take(&mut self.judgement_batch),
+            PlayState {
+                score: self.actual_display_score() as u32,
+                gauge: self.gauge.active.value(),
+                combo: self.combo as u32,
+            },
+        );
+    }
+
+    fn clear_companion_state(&self) {
+        if let Some(companion) = self.service_provider.get_mut::<CompanionServer>() {
+            companion.write().expect("Lock error").clear_play_state();
+        }
+    }
+
+    fn update_lights(&self) {
+        let Some(lighting) = self.service_provider.get_mut::<LightingService>() else {
+            return;
+        };
+
+        let held = |button| self.input_state.is_button_held(button).is_some();
+        let mut state = LightState::default();
+        for (light, button) in state.buttons.iter_mut().zip([
+            UscButton::BT(BtLane::A),
+            UscButton::BT(BtLane::B),
+            UscButton::BT(BtLane::C),
+            UscButton::BT(BtLane::D),
+            UscButton::FX(Side::Left),
+            UscButton::FX(Side::Right),
+        ]) {
+            if held(button) {
+                *light = match button {
+                    UscButton::FX(_) => Rgb(255, 128, 0),
+                    _ => Rgb::WHITE,
+                };
+            }
+        }
+        for (side, laser) in state.lasers.iter_mut().enumerate() {
+            let color = self.laser_colors[side];
+            *laser = LaserLight {
+                color: Rgb::from_unit(color.x, color.y, color.z),
+                position: self.laser_cursors[side] as f32,
+                active: self.laser_target[side].is_some(),
+            };
+        }
+        state.gauge = self.gauge.active.value();
+        state.set_combo(self.combo as u32);
+
+        lighting.write().expect("Lock error").set_game_state(state);
+    }
+
+    fn clear_lights(&self) {
+        if let Some(lighting) = self.service_provider.get_mut::<LightingService>() {
+            lighting.write().expect("Lock error").clear_game_state();
+        }
     }
 
     fn lua_game_state(
@@ -764,17 +1199,37 @@ impl Game {
         let screen = vec2(viewport.width as f32, viewport.height as f32);
         let track_center = graphics::camera_to_screen(camera, Vec3::zero(), screen);
 
-        let track_left = graphics::camera_to_screen(camera, Vec3::unit_x() * -0.5, screen);
-        let track_right = graphics::camera_to_screen(camera, Vec3::unit_x() * 0.5, screen);
+        let layout = self.view.layout;
+        let half_width = layout.track_width() / 2.0;
+        let track_left = graphics::camera_to_screen(camera, Vec3::unit_x() * -half_width, screen);
+        let track_right = graphics::camera_to_screen(camera, Vec3::unit_x() * half_width, screen);
         let crit_line = track_right - track_left;
         let rotation = -crit_line.y.atan2(crit_line.x);
 
+        let hud = GameConfig::get().hud;
+        // Not every skin checks the hud table, an empty path makes them show their fallback jacket
+        let jacket_path = if hud.song_info {
+            self.song.as_ref().difficulties.read().expect("Lock error")[self.diff_idx]
+                .jacket_path
+                .clone()
+        } else {
+            PathBuf::new()
+        };
+
+        let (min_bpm, max_bpm) = self
+            .bpm_changes
+            .iter()
+            .fold(None, |range: Option<(f64, f64)>, c| {
+                Some(range.map_or((c.bpm, c.bpm), |(min, max)| {
+                    (min.min(c.bpm), max.max(c.bpm))
+                }))
+            })
+            .unwrap_or_default();
+
         lua_data::LuaGameState {
             title: self.chart.meta.title.clone(),
             artist: self.chart.meta.artist.clone(),
-            jacket_path: self.song.as_ref().difficulties.read().expect("Lock error")[self.diff_idx]
-                .jacket_path
-                .clone(),
+            jacket_path,
             demo_mode: false,
             difficulty: self.chart.meta.difficulty,
             level: self.chart.meta.level,
@@ -802,6 +1257,7 @@ impl Game {
                         self.laser_cursors[0] as f32 * self.laser_wide[0] as f32
                             - (0.5 * (self.laser_wide[0].saturating_sub(1)) as f32),
                         camera,
+                        &layout,
                         if self.laser_target[0].is_some() {
                             1.0
                         } else {
@@ -812,6 +1268,7 @@ impl Game {
                         self.laser_cursors[1] as f32 * self.laser_wide[1] as f32
                             - (0.5 * (self.laser_wide[1].saturating_sub(1)) as f32),
                         camera,
+                        &layout,
                         if self.laser_target[1].is_some() {
                             1.0
                         } else {
@@ -830,6 +1287,18 @@ impl Game {
             multiplayer: false,
             user_id: "Player".into(),
             practice_setup: false,
+            track_layout: layout,
+            retry_count: self.retries,
+            hud,
+            bpm_changes: self.bpm_changes.clone(),
+            next_bpm_change: self
+                .bpm_changes
+                .iter()
+                .find(|c| c.time > self.view.cursor)
+                .map(|c| c.time - self.view.cursor),
+            min_bpm,
+            max_bpm,
+            reduce_flashing: GameConfig::get().graphics.reduce_flashing,
         }
     }
 
@@ -839,12 +1308,58 @@ impl Game {
             return;
         };
 
-        let vgfx = vgfx.write().expect("Lock error");
-        let canvas = &mut vgfx.canvas.lock().expect("Lock error");
-        canvas.flush();
-        canvas.reset();
-        canvas.reset_transform();
-        canvas.reset_scissor();
+        Vgfx::with_canvas(&vgfx, |vgfx, canvas| {
+            canvas.flush();
+            canvas.reset();
+            vgfx.reset_viewport(canvas);
+        });
+    }
+
+    /// Draws the skin's `render_track_overlay` onto a canvas mapped onto the track
+    fn render_track_overlay(
+        &mut self,
+        dt: f64,
+        td_context: &three_d::Context,
+        target: &three_d::RenderTarget,
+        camera: &Camera,
+        viewport: Viewport,
+    ) -> Result<()> {
+        let render: Function = self.lua.globals().get("render_track_overlay")?;
+        let vgfx = self
+            .lua
+            .app_data_mut::<RefMut<Vgfx>>()
+            .ok_or(anyhow!("VGFX app data not set"))?
+            .clone();
+        if self.track_overlay.is_none() {
+            self.track_overlay = Some(TrackOverlay::new(
+                td_context,
+                ChartView::TRACK_LENGTH,
+                self.view.layout.track_width(),
+            )?);
+        }
+        let Some(overlay) = &mut self.track_overlay else {
+            return Ok(());
+        };
+
+        let size = TrackOverlay::canvas_size(
+            viewport,
+            ChartView::TRACK_LENGTH,
+            self.view.layout.track_width(),
+        );
+        Vgfx::with_canvas(&vgfx, |_, canvas| overlay.begin(canvas, size))?;
+
+        let rendered = render.call::<_, ()>((dt / 1000.0, size.0, size.1));
+
+        Vgfx::with_canvas(&vgfx, |vgfx, canvas| {
+            overlay.end(canvas)?;
+            canvas.reset();
+            vgfx.reset_viewport(canvas);
+            anyhow::Ok(())
+        })?;
+
+        rendered?;
+        overlay.render(target, camera);
+        Ok(())
     }
 
     fn on_hit(&mut self, hit_rating: HitRating) {
@@ -873,6 +1388,11 @@ impl Game {
             HitRating::None => false,
         };
 
+        if self.collect_judgements {
+            self.judgement_batch
+                .extend(hit_rating.companion_event(self.combo as u32));
+        }
+
         if combo_updated {
             if let Ok(update_combo) = self.lua.globals().get::<_, Function>("update_combo") {
                 crate::log_result!(update_combo.call::<_, ()>(self.combo));
@@ -880,11 +1400,7 @@ impl Game {
         }
 
         match hit_rating {
-            HitRating::Crit {
-                tick,
-                delta,
-                time: _,
-            } => match tick.tick {
+            HitRating::Crit { tick, delta, .. } => match tick.tick {
                 ScoreTick::Chip { lane } => {
                     self.beam_colors_current[lane] = self.get_beam_color(lane, 2, delta);
                 }
@@ -934,13 +1450,9 @@ impl Game {
                     //TODO: Does this actually help?
                     self.laser_buffer[lane].clear();
 
-                    if let Some(slam_sample) = self.slam_sample.clone() {
-                        drop(std::mem::take(&mut self.slam_marker));
-                        self.mixer.add(owned_source(
-                            slam_sample.convert_samples().amplify(self.slam_volume),
-                            &self.slam_marker,
-                        )); //TODO: Amplyfy with slam volume
-                    }
+                    drop(std::mem::take(&mut self.slam_marker));
+                    self.slam_sound
+                        .play(&self.mixer, &self.slam_marker, end - start);
 
                     if let Ok(laser_slam_hit) = laser_slam_hit {
                         log_result!(laser_slam_hit.call::<_, ()>((
@@ -953,11 +1465,7 @@ impl Game {
                 }
                 _ => (),
             },
-            HitRating::Good {
-                tick,
-                delta,
-                time: _,
-            } => {
+            HitRating::Good { tick, delta, .. } => {
                 if let ScoreTick::Chip { lane } = tick.tick {
                     self.beam_colors_current[lane] = self.get_beam_color(lane, 1, delta);
                     if let Ok(near_hit) = self.lua.globals().get::<_, Function>("near_hit") {
@@ -965,20 +1473,12 @@ impl Game {
                     }
                 }
             }
-            HitRating::Miss {
-                tick,
-                delta: _,
-                time: _,
-            } if tick.y > self.current_tick => {
+            HitRating::Miss { tick, .. } if tick.y > self.current_tick => {
                 if let ScoreTick::Chip { lane } = tick.tick {
                     self.beam_colors_current[lane] = self.get_beam_color(lane, 0, 0.0);
                 }
             }
-            HitRating::Miss {
-                tick,
-                delta,
-                time: _,
-            } => {
+            HitRating::Miss { tick, delta, .. } => {
                 if let ScoreTick::Chip { lane } = tick.tick {
                     if delta.abs() > f64::EPSILON {
                         self.beam_colors_current[lane] = self.get_beam_color(lane, 0, 0.0);
@@ -1029,14 +1529,27 @@ impl Game {
         }
     }
 
-    fn hold_ok(&self, lane: usize, start_tick: u32) -> bool {
-        let is_button_held = &self.input_state.is_button_held((lane as u8).into());
-        let start_ms = self.without_offset(self.chart.tick_to_ms(start_tick));
-        let hold_start = self.zero_time + Duration::from_secs_f64(start_ms / 1000.0);
-        let hold_start_thres = hold_start
-            .checked_sub(self.hit_window.hold)
-            .unwrap_or(hold_start);
-        is_button_held.is_some_and(|t| t > hold_start_thres)
+    /// Whether the tick at `tick` of a hold from `start_tick` to `end_tick` is credited
+    fn hold_ok(&self, lane: usize, start_tick: u32, end_tick: u32, tick: u32) -> bool {
+        let timing = HoldTiming::new(
+            &self.chart,
+            start_tick,
+            end_tick,
+            self.hit_window.hold.as_secs_f64() * 1000.0,
+            self.hold_grace,
+        );
+        self.hold_buttons[lane].judge(&timing, self.chart.tick_to_ms(tick))
+    }
+
+    /// Time of an input in chart milliseconds
+    fn input_time(&self, timestamp: SystemTime) -> f64 {
+        self.with_offset(
+            timestamp
+                .duration_since(self.zero_time)
+                .unwrap_or(Duration::ZERO)
+                .as_secs_f64()
+                * 1000.0,
+        )
     }
 
     fn process_tick(
@@ -1045,21 +1558,27 @@ impl Game {
         chip_miss_tick: u32,
         slam_miss_tick: u32,
     ) -> HitRating {
-        let time = self.current_time().as_secs_f64() * 1000.0;
+        let time = self.with_offset(self.current_time().as_secs_f64() * 1000.0);
 
         match tick.tick {
-            ScoreTick::Hold { lane, start_tick } => {
-                if self.hold_ok(lane, start_tick) || self.auto_buttons() {
+            ScoreTick::Hold {
+                lane,
+                start_tick,
+                end_tick,
+            } => {
+                if self.hold_ok(lane, start_tick, end_tick, tick.y) || self.auto_buttons() {
                     HitRating::Crit {
                         tick,
                         delta: 0.0,
                         time,
+                        input_time: None,
                     }
                 } else {
                     HitRating::Miss {
                         tick,
                         delta: 0.0,
                         time,
+                        input_time: None,
                     }
                 }
             }
@@ -1069,12 +1588,14 @@ impl Game {
                         tick,
                         delta: 0.0,
                         time,
+                        input_time: None,
                     }
                 } else {
                     HitRating::Miss {
                         tick,
                         delta: 0.0,
                         time,
+                        input_time: None,
                     }
                 }
             }
@@ -1086,9 +1607,10 @@ impl Game {
                     Ordering::Greater => 1,
                     Ordering::Equal => unreachable!(),
                 };
+                let input_time = self.laser_latest_dir_inputs[lane][dir];
                 let delta = ms
                     - self.with_offset(
-                        self.laser_latest_dir_inputs[lane][dir]
+                        input_time
                             .duration_since(self.zero_time)
                             .unwrap_or(Duration::ZERO)
                             .as_secs_f64()
@@ -1097,14 +1619,24 @@ impl Game {
                 let contains_cursor = true; //TODO: (start.min(end)..=start.max(end)).contains(&self.laser_cursors[lane]);
                 if tick.y < slam_miss_tick {
                     self.laser_assist_ticks[lane] = 0;
-                    HitRating::Miss { tick, delta, time }
+                    HitRating::Miss {
+                        tick,
+                        delta,
+                        time,
+                        input_time: None,
+                    }
                 } else if self.auto_lasers()
                     || (delta.abs() < (self.hit_window.slam.as_secs_f64() * 1000.0)
                         && contains_cursor)
                 {
                     self.laser_cursors[lane] = end;
                     self.laser_assist_ticks[lane] = 24;
-                    HitRating::Crit { tick, delta, time }
+                    HitRating::Crit {
+                        tick,
+                        delta,
+                        time: ms - delta,
+                        input_time: Some(input_time),
+                    }
                 } else {
                     HitRating::None
                 }
@@ -1115,12 +1647,14 @@ impl Game {
                         tick,
                         delta: 0.0,
                         time,
+                        input_time: None,
                     }
                 } else if self.auto_buttons() {
                     HitRating::Crit {
                         tick,
                         delta: 0.0,
                         time,
+                        input_time: None,
                     }
                 } else {
                     HitRating::None
@@ -1128,6 +1662,70 @@ impl Game {
             }
         }
     }
+
+    /// Starts the chart clock together with the audio, the lead-in silence plays first
+    fn start(&mut self) {
+        self.intro_done = true;
+        self.zero_time = SystemTime::now();
+        if !self.play_audio || self.playback.is_playing() {
+            return;
+        }
+
+        info!("Starting playback");
+        if !self.playback.play() {
+            log::error!("Could not play audio");
+            self.closed = true;
+            return;
+        };
+
+        let (biquad_control, biquad_events) = std::sync::mpsc::channel();
+
+        self.biquad_control = biquad_control;
+
+        self.mixer.add_resampled(owned_source(
+            biquad(
+                self.playback.get_source().expect("Audio not loaded"),
+                BiQuadState::new(BiQuadType::AllPass, SQRT_2, 100.0),
+                Some(biquad_events),
+            ),
+            &self.source_owner,
+        ));
+    }
+
+    /// Judgement input is ignored during the intro, until the lead-in before the first note and
+    /// during the outro
+    fn input_locked(&self) -> bool {
+        !self.intro_done
+            || self.outro.is_some()
+            || self.with_offset(self.current_time().as_secs_f64() * 1000.0)
+                < self.first_note_ms - self.lead_in.as_secs_f64() * 1000.0
+    }
+
+    /// Sends the beats left until the audio starts to the `countdown` lua callback
+    fn update_countdown(&mut self) {
+        let leadin_ms = self.playback.leadin().as_secs_f64() * 1000.0;
+        if !self.intro_done || leadin_ms <= 0.0 {
+            return;
+        }
+
+        // Player 2 in versus has no audio of its own
+        let remaining_ms = if self.play_audio {
+            -self.playback.get_ms().min(0.0)
+        } else {
+            (leadin_ms - self.current_time().as_secs_f64() * 1000.0).max(0.0)
+        };
+        let beat_ms = kson::beat_in_ms(self.chart.bpm_at_tick(0));
+        let beats_remaining = (remaining_ms / beat_ms).ceil() as u32;
+
+        if self.countdown == Some(beats_remaining) || self.countdown == Some(0) {
+            return;
+        }
+        self.countdown = Some(beats_remaining);
+        if let Ok(f) = self.lua.globals().get::<_, Function>("countdown") {
+            log_result!(f.call::<_, ()>(beats_remaining));
+        }
+    }
+
     fn current_time(&self) -> std::time::Duration {
         if !self.intro_done {
             Duration::ZERO
@@ -1152,18 +1750,212 @@ impl Game {
             + self.playback.leadin().as_secs_f64() * 1000.0
     }
 
-    fn fail_song(&mut self) -> anyhow::Result<()> {
-        //TODO: Enter fail transition state
-        self.transition_to_results()?;
+    /// Swaps in an edited version of the chart at the current time, the audio keeps playing
+    fn reload_chart(&mut self, mut chart: Chart) {
+        chart.apply_modifiers(&chart_hash(&self.song, self.diff_idx), &self.modifiers);
+        let time_ms = self.chart.tick_to_ms(self.current_tick);
+        let score_ticks = kson::score_ticks::generate_score_ticks(&chart);
+        self.current_tick = chart.ms_to_tick(time_ms);
+        self.bpm_changes = lua_data::BpmChange::from_chart(&chart);
+        self.duration = chart.ms_to_tick(3000.0 + chart.tick_to_ms(chart.get_last_tick()));
+        self.score_summary = score_ticks.summary();
+        self.score_ticks = score_ticks
+            .into_iter()
+            .filter(|t| t.y >= self.current_tick)
+            .collect();
+        self.view.build_laser_meshes(&chart);
+        self.view.build_scroll_speed(&chart);
+        self.laser_buffer.iter_mut().for_each(VecDeque::clear);
+        self.scrubber = None;
+        self.chart = chart;
+    }
+
+    /// Moves the chart and audio to `tick`, notes before `tick` are skipped
+    fn seek(&mut self, tick: u32) {
+        let time_ms = self.without_offset(self.chart.tick_to_ms(tick)).max(0.0);
+        self.zero_time = SystemTime::now().sub(Duration::from_secs_f64(time_ms / 1000.0));
+        self.playback.seek(time_ms);
+        self.sync_delta.clear();
+        self.current_tick = tick;
+        let chart_ms = self.chart.tick_to_ms(tick);
+        for button in &mut self.hold_buttons {
+            button.seek(chart_ms);
+        }
+        self.score_ticks = kson::score_ticks::generate_score_ticks(&self.chart)
+            .into_iter()
+            .filter(|t| t.y >= tick)
+            .collect();
+    }
+
+    /// Plays the chart again from the start, the chart, audio and skin stay loaded
+    /// Reports the current play to the [`StatsTracker`], once per play
+    fn record_play(&mut self, finished: bool) {
+        if !self.intro_done || self.play_recorded {
+            return;
+        }
+        self.play_recorded = true;
+
+        let Some(tracker) = self.service_provider.get_mut::<StatsTracker>() else {
+            return;
+        };
+        let accuracy = (self.score_current_max > 0)
+            .then(|| self.real_score as f64 / self.score_current_max as f64);
+        tracker.write().expect("Lock error").record(PlayRecord {
+            played: self.current_time(),
+            autoplay: !matches!(self.autoplay, AutoPlay::None),
+            finished,
+            cleared: finished && self.gauge.is_cleared(),
+            accuracy,
+        });
+    }
+
+    fn restart(&mut self) {
+        info!("Restarting chart");
+        self.record_play(false);
+        self.play_recorded = false;
+        self.retries += 1;
+        self.score_ticks = kson::score_ticks::generate_score_ticks(&self.chart);
+        self.score_current_max = 0;
+        self.real_score = 0;
+        self.display_score = u64::MAX;
+        self.combo = 0;
+        self.max_combo = 0;
+        self.hit_ratings.clear();
+        self.gauge = self.new_gauges();
+        self.results_requested = false;
+        self.outro = None;
+        self.countdown = None;
+        self.camera.spins.clear();
+        self.laser_buffer.iter_mut().for_each(VecDeque::clear);
+        self.laser_cursors = [0.0, 1.0];
+        self.laser_assist_ticks = [0; 2];
+        self.laser_alert = [0; 2];
+        self.beam_colors_current = [[0.0; 4]; 6];
+        // Rebuilt and sent to the skin on the next frame
+        self.lua_game_state = Default::default();
+
+        // From the start of the lead-in, like the first play
+        self.playback.seek(0.0);
+        self.sync_delta.clear();
+        self.current_tick = 0;
+        self.zero_time = SystemTime::now();
+        let start_ms = self.chart.tick_to_ms(0);
+        for button in &mut self.hold_buttons {
+            button.seek(start_ms);
+        }
+
+        if let Ok(update_combo) = self.lua.globals().get::<_, Function>("update_combo") {
+            log_result!(update_combo.call::<_, ()>(0));
+        }
+    }
+
+    fn on_action(&mut self, action: ActionKind) {
+        match action {
+            ActionKind::Restart if self.intro_done && !self.results_requested => self.restart(),
+            ActionKind::ToggleAutoplay if self.practice_loop.is_some() => {
+                self.autoplay = if self.autoplay.any() {
+                    AutoPlay::None
+                } else {
+                    AutoPlay::All
+                };
+                info!("Autoplay: {:?}", self.autoplay);
+            }
+            _ => {}
+        }
+    }
+
+    /// Restarts the chart once the quick retry buttons are held, long notes of the buttons don't
+    /// count as holding them
+    fn check_quick_retry(&mut self) {
+        let Some(quick_retry) = &self.quick_retry else {
+            return;
+        };
+        // Nothing to restart during the intro or once the results are on their way
+        if !self.intro_done || self.results_requested {
+            return;
+        }
+
+        let mut chord = std::mem::take(&mut self.retry_chord);
+        let fired = chord.update(
+            &quick_retry.buttons,
+            quick_retry.hold,
+            SystemTime::now(),
+            |button| {
+                self.input_state
+                    .is_button_held(button)
+                    .filter(|_| !self.in_long_note(button))
+            },
+        );
+        self.retry_chord = chord;
+
+        if fired {
+            self.restart();
+        }
+    }
+
+    /// Whether a long note of `button` is at the current tick
+    fn in_long_note(&self, button: UscButton) -> bool {
+        let notes = match Into::<u8>::into(button) as usize {
+            lane @ 0..=3 => &self.chart.note.bt[lane],
+            lane @ 4..=5 => &self.chart.note.fx[lane - 4],
+            _ => return false,
+        };
+        notes
+            .iter()
+            .any(|n| n.l > 0 && (n.y..=n.y + n.l).contains(&self.current_tick))
+    }
+
+    fn new_gauges(&self) -> Gauges {
+        let config = GameConfig::get();
+        let fallbacks = (config.start_gauge.fallback_supported() && config.fallback_gauge)
+            .then(|| Gauge::new(GaugeType::Normal, &self.score_summary))
+            .into_iter()
+            .collect();
+        Gauges::new(
+            Gauge::new(
+                GaugeSpec::new(config.start_gauge, config.blastive_level),
+                &self.score_summary,
+            ),
+            fallbacks,
+        )
+    }
+
+    /// Starts the skin's outro, skins without `render_outro` go to the results right away
+    fn end_play(&mut self) -> Result<()> {
+        if self.outro.is_some() || self.results_requested {
+            return Ok(());
+        }
+
+        if self
+            .lua
+            .globals()
+            .get::<_, Function>("render_outro")
+            .is_err()
+        {
+            self.results_requested = true;
+            return self.transition_to_results();
+        }
+
+        let clear_mark = calculate_clear_mark(
+            HitSummary::from(self.hit_ratings.as_slice()),
+            false,
+            &self.gauge.active,
+        );
+        self.outro = Some(Outro {
+            started: Instant::now(),
+            clear_state: clear_mark as u8,
+            done: false,
+        });
         Ok(())
     }
 
     fn transition_to_results(&mut self) -> Result<(), anyhow::Error> {
+        self.record_play(true);
         if let AutoPlay::None = self.autoplay {
             self.control_tx
                 .as_ref()
                 .ok_or(anyhow!("control_tx not set"))?
-                .send(ControlMessage::Result {
+                .send(ControlMessage::Result(GameResult {
                     song: self.song.clone(),
                     diff_idx: self.diff_idx,
                     score: self.actual_display_score() as u32,
@@ -1174,7 +1966,10 @@ impl Game {
                     hit_window: self.hit_window,
                     manual_exit: false,
                     max_combo: self.max_combo as _,
-                })
+                    player: self.player,
+                    retries: self.retries,
+                    modifiers: self.modifiers,
+                }))
                 .expect("Main loop messaging error");
         } else {
             self.closed = true;
@@ -1261,84 +2056,186 @@ impl Game {
         button: UscButton,
         button_num: u8,
         timestamp: SystemTime,
-        perfect: Duration,
-        good: Duration,
-        miss: Duration,
     ) -> HitRating {
+        let HitWindow {
+            good,
+            miss,
+            early_miss,
+            ..
+        } = self.hit_window;
         let last_tick = self.chart.ms_to_tick(
             self.with_offset(self.current_time().as_secs_f64() * 1000.0)
-                + miss.as_secs_f64() * 1000.0,
+                + good.max(miss).max(early_miss).as_secs_f64() * 1000.0,
         ) + 1;
-        let mut hittable_ticks = self.score_ticks.iter().take_while(|x| x.y < last_tick);
+        let hittable_ticks = self.score_ticks.iter().take_while(|x| x.y < last_tick);
         let mut hit_rating = HitRating::None;
         match button {
             crate::button_codes::UscButton::BT(_) | crate::button_codes::UscButton::FX(_)
                 if !self.auto_buttons() =>
             {
-                if let Some((index, score_tick)) = hittable_ticks.find_position(|x| {
-                    if let ScoreTick::Chip { lane } | ScoreTick::Hold { lane, .. } = x.tick {
-                        lane == button_num as usize
-                    } else {
-                        false
-                    }
-                }) {
-                    if let ScoreTick::Hold { .. } = score_tick.tick {
-                        return hit_rating; // Next tick in this lane is a hold, do nothing
-                    }
-                    let tick = *score_tick;
-                    let ms = self.chart.tick_to_ms(score_tick.y);
-                    let time = self.with_offset(
-                        timestamp
-                            .duration_since(self.zero_time)
-                            .unwrap_or(Duration::ZERO)
-                            .as_secs_f64()
-                            * 1000.0,
-                    );
-
-                    let delta = ms - time + self.button_offset;
-                    let abs_delta = Duration::from_secs_f64(delta.abs() / 1000.0);
+                let time = self.with_offset(
+                    timestamp
+                        .duration_since(self.zero_time)
+                        .unwrap_or(Duration::ZERO)
+                        .as_secs_f64()
+                        * 1000.0,
+                );
+
+                // Chips in this lane up to the next hold, holds take their own presses
+                let chips = hittable_ticks
+                    .enumerate()
+                    .filter_map(|(index, x)| match x.tick {
+                        ScoreTick::Chip { lane } | ScoreTick::Hold { lane, .. }
+                            if lane == button_num as usize =>
+                        {
+                            Some((index, x))
+                        }
+                        _ => None,
+                    })
+                    .map_while(|(index, x)| match x.tick {
+                        ScoreTick::Chip { .. } => Some((
+                            index,
+                            self.chart.tick_to_ms(x.y) - time + self.button_offset,
+                        )),
+                        _ => None,
+                    });
 
-                    hit_rating = if abs_delta <= perfect {
-                        HitRating::Crit { tick, delta, time }
-                    } else if abs_delta <= good {
-                        HitRating::Good { tick, delta, time }
-                    } else if abs_delta <= miss {
-                        HitRating::Miss { tick, delta, time }
-                    } else {
-                        HitRating::None
+                if let Some((index, judgement)) =
+                    chip_matching::match_press(chips, &self.hit_window)
+                {
+                    let tick = self.score_ticks[index];
+                    let delta = self.chart.tick_to_ms(tick.y) - time + self.button_offset;
+                    let input_time = Some(timestamp);
+                    hit_rating = match judgement {
+                        ChipJudgement::Crit => HitRating::Crit {
+                            tick,
+                            delta,
+                            time,
+                            input_time,
+                        },
+                        ChipJudgement::Near => HitRating::Good {
+                            tick,
+                            delta,
+                            time,
+                            input_time,
+                        },
+                        ChipJudgement::Miss => HitRating::Miss {
+                            tick,
+                            delta,
+                            time,
+                            input_time,
+                        },
                     };
 
-                    match hit_rating {
-                        HitRating::None => {}
-                        _ => {
-                            self.on_hit(hit_rating);
-                            self.score_ticks.remove(index);
-                            self.score_current_max += 2;
-                        }
-                    }
+                    self.on_hit(hit_rating);
+                    self.score_ticks.remove(index);
+                    self.score_current_max += 2;
                 }
             }
-            crate::button_codes::UscButton::Back => self.closed = true,
+            crate::button_codes::UscButton::Back => {
+                self.record_play(false);
+                self.closed = true;
+            }
             _ => {}
         }
         hit_rating
     }
 }
 
+impl Drop for Game {
+    fn drop(&mut self) {
+        self.clear_lights();
+        self.clear_companion_state();
+    }
+}
+
 impl Scene for Game {
     fn closed(&self) -> bool {
         self.closed
     }
+
+    fn has_egui(&self) -> bool {
+        self.chart_watcher
+            .as_ref()
+            .is_some_and(ChartWatcher::has_report)
+    }
+
+    fn render_egui(&mut self, ctx: &egui::Context) -> Result<()> {
+        let Some(watcher) = &self.chart_watcher else {
+            return Ok(());
+        };
+        if !watcher.has_report() {
+            return Ok(());
+        }
+
+        let mut jump = None;
+        egui::Window::new("Chart")
+            .id(egui::Id::new("chart_reload"))
+            .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+            .show(ctx, |ui| {
+                if let Some(status) = &watcher.status {
+                    ui.label(status);
+                }
+                if let Some(changes) = watcher.changes.as_ref().filter(|c| !c.is_empty()) {
+                    egui::CollapsingHeader::new("Changes")
+                        .id_source("chart_changes")
+                        .show(ui, |ui| {
+                            egui::ScrollArea::vertical()
+                                .max_height(200.0)
+                                .show(ui, |ui| {
+                                    for line in changes.to_string().lines() {
+                                        ui.monospace(line);
+                                    }
+                                });
+                        });
+                }
+                if let Some(error) = &watcher.error {
+                    ui.colored_label(egui::Color32::RED, format!("Reload failed: {error}"));
+                }
+                egui::Grid::new("chart_issues")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for issue in &watcher.issues {
+                            let measure = self.chart.tick_to_measure(issue.y);
+                            ui.label(format!("Measure {}", measure + 1));
+                            ui.label(&issue.message);
+                            if ui.button("Jump to").clicked() {
+                                jump = Some(self.chart.measure_to_tick(measure));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if let Some(tick) = jump {
+            self.seek(tick);
+        }
+        Ok(())
+    }
     fn render_ui(&mut self, _dt: f64) -> anyhow::Result<()> {
         Ok(())
     }
 
     fn is_suspended(&self) -> bool {
-        false
+        self.suspended
     }
 
     fn tick(&mut self, _dt: f64, _knob_state: crate::button_codes::LaserState) -> Result<()> {
         profile_function!();
+        if self.suspended {
+            return Ok(());
+        }
+        self.shown = true;
+        if let Some(chart) = self
+            .chart_watcher
+            .as_mut()
+            .and_then(|w| w.poll(&self.chart))
+        {
+            self.reload_chart(chart);
+        }
+        self.check_quick_retry();
+        self.send_judgements();
         const AVG_DELTA_LEN: usize = 32;
         let mut time = self.current_time();
         let sys_time = SystemTime::now();
@@ -1369,9 +2266,21 @@ impl Scene for Game {
             time = self.current_time();
         }
 
-        if self.current_tick >= self.duration && !self.results_requested {
-            self.transition_to_results()?;
-            self.results_requested = true;
+        if let Some((start, end)) = self.practice_loop {
+            if self.current_tick >= end {
+                self.seek(start);
+            }
+        }
+
+        if self.current_tick >= self.duration || self.gauge.is_dead() {
+            self.end_play()?;
+        }
+        if let Some(outro) = &self.outro {
+            if !self.results_requested && (outro.done || outro.started.elapsed() >= OUTRO_TIMEOUT) {
+                self.results_requested = true;
+                self.transition_to_results()?;
+            }
+            return Ok(());
         }
         let missed_chip_tick = self.chart.ms_to_tick(
             self.with_offset(time.saturating_sub(self.hit_window.good).as_secs_f64() * 1000.0),
@@ -1542,6 +2451,8 @@ impl Scene for Game {
         self.gauge
             .update_sample(GAUGE_SAMPLES * self.current_tick as usize / self.duration as usize);
 
+        self.update_countdown();
+
         //Laser alerts
         if self.intro_done {
             let check_tick = (time.as_millis() + 1500) as f64;
@@ -1577,44 +2488,43 @@ impl Scene for Game {
             }
         }
 
-        if self.gauge.is_dead() {
-            self.fail_song()?;
+        if self.closed {
+            self.clear_lights();
+        } else {
+            self.update_lights();
         }
 
         Ok(())
     }
 
     fn suspend(&mut self) {
+        if !self.shown {
+            self.suspended = true;
+            return;
+        }
+        self.record_play(false);
+        self.clear_lights();
+        self.clear_companion_state();
         self.closed = true;
     }
 
+    fn resume(&mut self) {
+        self.suspended = false;
+    }
+
     fn init(&mut self, app_control_tx: Sender<ControlMessage>) -> Result<()> {
         profile_function!();
         let lua_provider: Arc<LuaProvider> = self.service_provider.get_required();
         ensure!(self.score_summary.total != 0, "Empty chart");
-        let long_count = self.score_summary.hold_count + self.score_summary.laser_count;
-        let chip_count = self.score_summary.chip_count + self.score_summary.slam_count;
-        let ftotal = 2.10 + f32::EPSILON;
-        let (chip_gain, tick_gain) = if long_count == 0 && chip_count != 0 {
-            (ftotal / chip_count as f32, 0.0f32)
-        } else if long_count != 0 && chip_count == 0 {
-            (0f32, ftotal / long_count as f32)
-        } else {
-            let gain = (ftotal * 20.0) / (5.0 * (long_count as f32 + (4.0 * chip_count as f32)));
-            (gain, gain / 4.0)
-        };
 
-        let config = GameConfig::get();
-        let fallbacks = (config.start_gauge.fallback_supported() && config.fallback_gauge)
-            .then(|| GaugeType::Normal.get_gauge(chip_gain, tick_gain))
-            .into_iter()
-            .collect();
-        self.gauge = Gauges::new(
-            config.start_gauge.get_gauge(chip_gain, tick_gain),
-            fallbacks,
-        );
+        self.gauge = self.new_gauges();
         self.control_tx = Some(app_control_tx);
         lua_provider.register_libraries(self.lua.clone(), "gameplay.lua")?;
+        self.has_track_overlay = self
+            .lua
+            .globals()
+            .get::<_, Function>("render_track_overlay")
+            .is_ok();
         Ok(())
     }
 
@@ -1624,6 +2534,59 @@ impl Scene for Game {
             self.camera.egui_widget(ui);
             ui.checkbox(&mut self.draw_axis_guides, "Draw axies guides")
         });
+        Window::new("Chart").show(ctx, |ui| {
+            let scrubber = self
+                .scrubber
+                .get_or_insert_with(|| ChartScrubber::new(&self.chart, self.duration));
+            match scrubber.show(ui, self.current_tick, self.practice_loop) {
+                Some(ScrubberAction::Seek(tick)) => self.seek(tick),
+                Some(ScrubberAction::SetLoopStart(tick)) => {
+                    let end = self
+                        .practice_loop
+                        .map(|(_, end)| end)
+                        .unwrap_or(self.duration);
+                    self.practice_loop = Some((tick, end.max(tick)));
+                }
+                Some(ScrubberAction::SetLoopEnd(tick)) => {
+                    let start = self.practice_loop.map(|(start, _)| start).unwrap_or(0);
+                    self.practice_loop = Some((start.min(tick), tick));
+                }
+                Some(ScrubberAction::ClearLoop) => self.practice_loop = None,
+                None => {}
+            }
+        });
+        Window::new("Judgements")
+            .scroll2([false, true])
+            .show(ctx, |ui| {
+                egui::Grid::new("judgement_log")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Time");
+                        ui.label("Lane");
+                        ui.label("Delta");
+                        ui.label("Rating");
+                        ui.end_row();
+
+                        for (rating, tick) in self
+                            .hit_ratings
+                            .iter()
+                            .rev()
+                            .filter_map(|r| r.tick().map(|tick| (r, tick)))
+                            .take(JUDGEMENT_LOG_LEN)
+                        {
+                            ui.label(format!("{:.0} ms", rating.time()));
+                            ui.label(tick.tick.global_lane().to_string());
+                            ui.label(format!("{:+.1} ms", rating.delta()));
+                            ui.label(match rating {
+                                HitRating::Crit { .. } => "Crit",
+                                HitRating::Good { .. } => "Good",
+                                _ => "Miss",
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
         Window::new("Game Data")
             .scroll2([false, true])
             .show(ctx, |ui| {
@@ -1637,10 +2600,7 @@ impl Scene for Game {
                             .add(Slider::new(&mut self.current_tick, 0..=self.duration))
                             .changed()
                         {
-                            let new_time = self.chart.tick_to_ms(self.current_tick);
-
-                            self.zero_time =
-                                SystemTime::now().sub(Duration::from_millis(new_time as _))
+                            self.seek(self.current_tick);
                         }
 
                         ui.end_row();
@@ -1757,31 +2717,12 @@ impl Scene for Game {
     ) {
         profile_function!();
 
-        self.camera
-            .update(vec2(viewport.width as f32, viewport.height as f32));
-        if self.intro_done && !self.playback.is_playing() {
-            info!("Starting playback");
-            self.zero_time = SystemTime::now();
-            if !self.playback.play() {
-                log::error!("Could not play audio");
-                self.closed = true;
-                return;
-            };
-
-            let (biquad_control, biquad_events) = std::sync::mpsc::channel();
-
-            self.biquad_control = biquad_control;
-
-            self.mixer.add(owned_source(
-                biquad(
-                    self.playback.get_source().expect("Audio not loaded"),
-                    BiQuadState::new(BiQuadType::AllPass, SQRT_2, 100.0),
-                    Some(biquad_events),
-                ),
-                &self.source_owner,
-            ));
-        }
-
+        let rotation = GameConfig::get().graphics.rotation;
+        let logical_viewport = rotation.logical_viewport(viewport);
+        self.camera.update(vec2(
+            logical_viewport.width as f32,
+            logical_viewport.height as f32,
+        ));
         let leadin_ms = self.playback.get_ms().min(0.0);
 
         let time = self.current_time();
@@ -1858,7 +2799,8 @@ impl Scene for Game {
                 !x.completed()
             });
         }
-        let td_camera: Camera = Camera::from(&self.camera);
+        let mut td_camera: Camera = self.camera.rotated(rotation);
+        td_camera.set_viewport(viewport);
         if let Some(bg) = self.background.as_mut() {
             bg.render(
                 dt,
@@ -1870,12 +2812,30 @@ impl Scene for Game {
                 self.gauge.is_cleared(),
             );
         }
+        // Covers the static background once frames are decoded, which stays visible otherwise
+        if let Some(video) = self.video_background.as_mut() {
+            video.render(time_ms, viewport);
+        }
 
         self.beam_colors_current
             .iter_mut()
             .for_each(|c| c[3] = (c[3] - dt as f32 / 200.0).max(0.0));
 
-        let new_lua_state = self.lua_game_state(viewport, &td_camera, self.hit_window);
+        let (new_lua_state, (hidden_cutoff_y, sudden_cutoff_y)) =
+            if rotation == DisplayRotation::None {
+                (
+                    self.lua_game_state(viewport, &td_camera, self.hit_window),
+                    Self::cutoffs_on_screen(viewport, &td_camera),
+                )
+            } else {
+                // Skins see the track in their own rotated coordinates
+                let mut logical_camera = Camera::from(&self.camera);
+                logical_camera.set_viewport(logical_viewport);
+                (
+                    self.lua_game_state(logical_viewport, &logical_camera, self.hit_window),
+                    Self::cutoffs_on_screen(logical_viewport, &logical_camera),
+                )
+            };
         if new_lua_state != self.lua_game_state {
             self.lua_game_state = new_lua_state;
             let lua_game_state = match self.lua.to_value(&self.lua_game_state) {
@@ -1889,10 +2849,10 @@ impl Scene for Game {
         }
 
         //Set glow/hit states
-        let object_glow = ((time_ms as f32 % 100.0) / 50.0 - 1.0).abs() * 0.5 + 0.5;
-        let hit_state = (time_ms / 50.0).rem_euclid(2.0) as i32 + 2;
+        let (object_glow, hit_state) =
+            hold_glow(time_ms, GameConfig::get().graphics.reduce_flashing);
         for (side, [_, shader]) in self.laser_shaders.iter_mut().enumerate() {
-            shader.set_param(
+            shader.set_param_if_declared(
                 "hitState",
                 if self.laser_active[side] {
                     hit_state
@@ -1901,7 +2861,7 @@ impl Scene for Game {
                 },
             );
 
-            shader.set_param(
+            shader.set_param_if_declared(
                 "objectGlow",
                 if self.laser_active[side] {
                     object_glow
@@ -1911,13 +2871,11 @@ impl Scene for Game {
             );
         }
 
-        target.render(&td_camera, [&self.track_shader], &[]);
-        let render_data = match self.view.render(
+        let mut render_data = match self.view.render(
             &self.chart,
             td_context,
-            |lane, tick| self.hold_ok(lane, tick),
+            |lane, start, end| self.hold_ok(lane, start, end, self.current_tick),
             self.beam_colors_current,
-            self.chip_h,
         ) {
             Ok(d) => d,
             Err(e) => {
@@ -1926,66 +2884,85 @@ impl Scene for Game {
             }
         };
 
-        self.fx_long_shaders.draw_instanced_camera(
-            &td_camera,
-            render_data.fx_hold,
-            |material, transform, (hold, active)| {
-                material.use_uniform("world", transform * hold);
-                let (glow, state) = match active {
-                    HoldState::Idle => (0.6, 1),
-                    HoldState::Hit => (object_glow, hit_state), //FLIP_Y?
-                    HoldState::Miss => (0.3, 0),
-                };
-                material.use_uniform_if_required("objectGlow", glow);
-                material.use_uniform_if_required("hitState", state);
-            },
-        );
-
-        self.bt_long_shaders.draw_instanced_camera(
-            &td_camera,
-            render_data.bt_hold,
-            |material, transform, (bt, active)| {
-                material.use_uniform("world", transform * bt);
-                let (glow, state) = match active {
-                    HoldState::Idle => (0.6, 1),
-                    HoldState::Hit => (object_glow, hit_state),
-                    HoldState::Miss => (0.3, 0),
-                };
-                material.use_uniform_if_required("objectGlow", glow);
-                material.use_uniform_if_required("hitState", state);
-            },
-        );
-
-        self.fx_chip_shaders.draw_instanced_camera(
-            &td_camera,
-            render_data.fx_chip,
-            |material, transform, (fx, has_sample)| {
-                material.use_uniform("world", transform * fx);
-                material.use_uniform_if_required("hasSample", if has_sample { 1 } else { 0 });
-            },
-        );
-
-        self.bt_chip_shader.draw_instanced_camera(
-            &td_camera,
-            render_data.bt_chip,
-            |material, transform, bt| material.use_uniform("world", transform * bt),
-        );
-
-        self.lane_beam_shader.draw_instanced_camera(
-            &td_camera,
-            render_data.lane_beams,
-            |material, tranform, (light, color)| {
-                material.use_uniform_if_required::<Vec4>("color", color.into());
-                material.use_uniform("world", tranform * light);
-            },
-        );
-
         self.laser_shaders[0][0].set_data_mesh(&render_data.lasers[0]);
         self.laser_shaders[0][1].set_data_mesh(&render_data.lasers[1]);
         self.laser_shaders[1][0].set_data_mesh(&render_data.lasers[2]);
         self.laser_shaders[1][1].set_data_mesh(&render_data.lasers[3]);
 
-        target.render(&td_camera, self.laser_shaders.iter().flatten(), &[]);
+        let mut draw_list = TrackObject::ALL;
+        draw_list.sort_by_key(|object| self.track_mesh(*object).draw_order());
+
+        for object in draw_list {
+            let mesh = self.track_mesh(object);
+            match object {
+                TrackObject::Track | TrackObject::Laser(..) => {
+                    target.render(&td_camera, [mesh], &[]);
+                }
+                TrackObject::FxHold => mesh.draw_instanced_camera(
+                    &td_camera,
+                    std::mem::take(&mut render_data.fx_hold),
+                    |material, transform, (hold, active)| {
+                        material.use_uniform("world", transform * hold);
+                        let (glow, state) = match active {
+                            HoldState::Idle => (0.6, 1),
+                            HoldState::Hit => (object_glow, hit_state), //FLIP_Y?
+                            HoldState::Miss => (0.3, 0),
+                        };
+                        material.use_uniform_if_required("objectGlow", glow);
+                        material.use_uniform_if_required("hitState", state);
+                    },
+                ),
+                TrackObject::BtHold => mesh.draw_instanced_camera(
+                    &td_camera,
+                    std::mem::take(&mut render_data.bt_hold),
+                    |material, transform, (bt, active)| {
+                        material.use_uniform("world", transform * bt);
+                        let (glow, state) = match active {
+                            HoldState::Idle => (0.6, 1),
+                            HoldState::Hit => (object_glow, hit_state),
+                            HoldState::Miss => (0.3, 0),
+                        };
+                        material.use_uniform_if_required("objectGlow", glow);
+                        material.use_uniform_if_required("hitState", state);
+                    },
+                ),
+                TrackObject::FxChip => mesh.draw_instanced_camera(
+                    &td_camera,
+                    std::mem::take(&mut render_data.fx_chip),
+                    |material, transform, (fx, has_sample)| {
+                        material.use_uniform("world", transform * fx);
+                        material
+                            .use_uniform_if_required("hasSample", if has_sample { 1 } else { 0 });
+                    },
+                ),
+                TrackObject::BtChip => mesh.draw_instanced_camera(
+                    &td_camera,
+                    std::mem::take(&mut render_data.bt_chip),
+                    |material, transform, bt| material.use_uniform("world", transform * bt),
+                ),
+                TrackObject::LaneBeams => mesh.draw_instanced_camera(
+                    &td_camera,
+                    render_data.lane_beams,
+                    |material, tranform, (light, color)| {
+                        material.use_uniform_if_required::<Vec4>("color", color.into());
+                        material.use_uniform("world", tranform * light);
+                    },
+                ),
+            }
+        }
+
+        if self.has_track_overlay {
+            profile_scope!("lua render_track_overlay");
+            log_result!(self.render_track_overlay(dt, td_context, target, &td_camera, viewport));
+        }
+
+        if let Ok(func) = self.lua.globals().get::<_, Function>("render_lane_cover") {
+            profile_scope!("lua render_lane_cover");
+            if let Err(e) = func.call::<_, ()>((dt / 1000.0, hidden_cutoff_y, sudden_cutoff_y)) {
+                log::error!("{}", e);
+            };
+            self.reset_canvas();
+        }
 
         if !self.intro_done {
             if let Ok(func) = self.lua.globals().get::<_, Function>("render_intro") {
@@ -1994,7 +2971,8 @@ impl Scene for Game {
                     Err(e) => {
                         log::error!("{}", e);
                     }
-                    Ok(intro_complete) => self.intro_done = intro_complete,
+                    Ok(true) => self.start(),
+                    Ok(false) => {}
                 };
             }
         }
@@ -2034,6 +3012,26 @@ impl Scene for Game {
             };
         }
         self.reset_canvas();
+
+        if let Some(outro) = self.outro.as_mut() {
+            if let Ok(func) = self.lua.globals().get::<_, Function>("render_outro") {
+                profile_scope!("lua render_outro");
+                match func.call::<_, bool>((dt / 1000.0, outro.clear_state)) {
+                    Err(e) => {
+                        log::error!("{}", e);
+                        outro.done = true;
+                    }
+                    Ok(done) => outro.done |= done,
+                }
+            }
+            self.reset_canvas();
+        }
+
+        {
+            profile_scope!("lua render_console");
+            log_result!(self.render_console(dt));
+        }
+        self.reset_canvas();
         if self.draw_axis_guides {
             let axes = three_d::Axes::new(td_context, 0.01, 0.30);
             target.render(&td_camera, [axes], &[]);
@@ -2044,6 +3042,11 @@ impl Scene for Game {
         &mut self,
         event: &game_loop::winit::event::Event<crate::button_codes::UscInputEvent>,
     ) {
+        if let game_loop::winit::event::Event::UserEvent(UscInputEvent::Action(action)) = event {
+            self.on_action(*action);
+            return;
+        }
+
         if let game_loop::winit::event::Event::UserEvent(UscInputEvent::Laser(ls, timestamp)) =
             event
         {
@@ -2060,6 +3063,10 @@ impl Scene for Game {
                     config.mod_speed = (self.view.hispeed * self.lua_game_state.bpm) as f64;
                 }
 
+                if self.input_locked() {
+                    continue;
+                }
+
                 let input_dir = delta.total_cmp(&0.0);
                 match input_dir {
                     Ordering::Less => self.laser_latest_dir_inputs[index][0] = *timestamp,
@@ -2075,18 +3082,17 @@ impl Scene for Game {
     }
 
     fn on_button_pressed(&mut self, button: crate::button_codes::UscButton, timestamp: SystemTime) {
-        let HitWindow {
-            variant: _,
-            perfect,
-            good,
-            hold: _,
-            miss,
-            slam: _,
-        } = self.hit_window;
+        if self.input_locked() {
+            return;
+        }
 
         let button_num = Into::<u8>::into(button);
+        let time = self.input_time(timestamp);
+        if let Some(hold_button) = self.hold_buttons.get_mut(button_num as usize) {
+            hold_button.press(time);
+        }
 
-        let hit_rating = self.get_hit_rating(button, button_num, timestamp, perfect, good, miss);
+        let hit_rating = self.get_hit_rating(button, button_num, timestamp);
         if let HitRating::None = hit_rating {
             if (button_num as usize) < self.beam_colors_current.len() {
                 self.beam_colors_current[button_num as usize] =
@@ -2095,6 +3101,20 @@ impl Scene for Game {
         }
     }
 
+    fn on_button_released(&mut self, button: UscButton, timestamp: SystemTime) {
+        let time = self.input_time(timestamp);
+        if let Some(hold_button) = self.hold_buttons.get_mut(Into::<u8>::into(button) as usize) {
+            hold_button.release(time);
+        }
+    }
+
+    fn game_state(&self) -> GameState {
+        GameState::InGame {
+            chart_hash: chart_hash(&self.song, self.diff_idx),
+            modifiers: self.modifiers.into(),
+        }
+    }
+
     fn name(&self) -> &str {
         "Game"
     }
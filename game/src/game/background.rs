@@ -100,7 +100,7 @@ impl TealData for GameBackgroundLua {
             let bg = &mut lua
                 .app_data_mut::<ShadedMesh>()
                 .expect("Background or Foreground mesh data not set");
-            bg.set_param(name.as_str(), value);
+            bg.set_lua_param(name.as_str(), value);
             Ok(())
         });
 
@@ -109,7 +109,7 @@ impl TealData for GameBackgroundLua {
             let bg = &mut lua
                 .app_data_mut::<ShadedMesh>()
                 .expect("Background or Foreground mesh data not set");
-            bg.set_param(name.as_str(), value);
+            bg.set_lua_param(name.as_str(), value);
             Ok(())
         });
 
@@ -180,14 +180,14 @@ impl TealData for GameBackgroundLua {
                 .app_data_mut::<ShadedMesh>()
                 .expect("Background mesh not set");
 
-            bg.set_param(
+            bg.set_param_if_declared(
                 "screenCenter",
                 Vector2::new((data.screen_center.0) as i32, (data.screen_center.1) as i32),
             );
 
-            bg.set_param("timing", Vector3::from(data.timing));
-            bg.set_param("clearTransition", data.clear_transition);
-            bg.set_param("tilt", vec2(data.roll, 0.0)); //(camera roll, background spin)
+            bg.set_param_if_declared("timing", Vector3::from(data.timing));
+            bg.set_param_if_declared("clearTransition", data.clear_transition);
+            bg.set_param_if_declared("tilt", vec2(data.roll, 0.0)); //(camera roll, background spin)
 
             bg.draw_fullscreen(data.viewport);
             Ok(())
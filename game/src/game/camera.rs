@@ -2,6 +2,7 @@ use three_d::{vec2, Camera, Matrix4, Transform, Vec2, Vec3};
 use three_d_asset::{Deg, InnerSpace, Rad, Viewport};
 
 use super::chart_view;
+use crate::display_rotation::DisplayRotation;
 use chart_view::ChartView;
 
 #[derive(Debug, Clone)]
@@ -193,23 +194,31 @@ const KSON_ANGLE_FACTOR: f32 = 360.0 / 2400.0;
 
 impl From<&ChartCamera> for Camera {
     fn from(val: &ChartCamera) -> Self {
+        val.rotated(DisplayRotation::None)
+    }
+}
+
+impl ChartCamera {
+    /// Camera for a display rotated by `rotation`, the track is laid out for the logical view
+    /// size while the camera viewport is in physical pixels.
+    pub fn rotated(&self, rotation: DisplayRotation) -> Camera {
         let (fov, angle, radius) = {
-            if val.portrait {
+            if self.portrait {
                 (FOV_PORTRAIT, ANGLE_PORTRAIT, RADIUS_PORTRAIT)
             } else {
                 (FOV_LANDSCAPE, ANGLE_LANDSCAPE, RADIUS_LANDSCAPE)
             }
         };
 
-        let radius = radius * f32::powf(3.0, val.kson_radius / -300.0);
-        let angle = angle + (val.kson_angle * KSON_ANGLE_FACTOR);
+        let radius = radius * f32::powf(3.0, self.kson_radius / -300.0);
+        let angle = angle + (self.kson_angle * KSON_ANGLE_FACTOR);
 
         let angle_rad = (angle).to_radians();
         let fov_rad = fov.to_radians();
 
         //Wrong, idk why, doesn't really matter once correct values are found though
         let base_angle_rad =
-            { (fov_rad) / (2.0) - (if val.portrait { 0.27 } else { 0.05 } * fov_rad) };
+            { (fov_rad) / (2.0) - (if self.portrait { 0.27 } else { 0.05 } * fov_rad) };
 
         let track_end: Vec3 = ChartView::TRACK_DIRECTION * ChartView::TRACK_LENGTH;
         let final_camera_pos: Vec3 = -ChartView::UP * radius * angle_rad.cos()
@@ -232,13 +241,22 @@ impl From<&ChartCamera> for Camera {
         let begin_dist = (-track_end - final_camera_pos).magnitude();
         let z_far = end_dist.max(begin_dist);
 
-        let roll = Matrix4::from_axis_angle(ChartView::TRACK_DIRECTION, Deg(-val.tilt));
+        let roll = Matrix4::from_axis_angle(ChartView::TRACK_DIRECTION, Deg(-self.tilt));
         let target = roll.transform_vector(target - final_camera_pos) + final_camera_pos;
         let up = roll.transform_vector(up);
         // let final_camera_pos = roll.transform_vector(final_camera_pos);
 
+        let (width, height) =
+            rotation.logical_size((self.view_size.x as u32, self.view_size.y as u32));
+        // The vertical field of view of a sideways display spans the logical width
+        let fov_rad = if rotation.swaps_axes() {
+            2.0 * ((fov_rad / 2.0).tan() * self.view_size.x / self.view_size.y).atan()
+        } else {
+            fov_rad
+        };
+
         let mut cam = Camera::new_perspective(
-            Viewport::new_at_origo(val.view_size.x as u32, val.view_size.y as u32),
+            Viewport::new_at_origo(width, height),
             final_camera_pos,
             target,
             up.normalize(),
@@ -246,8 +264,11 @@ impl From<&ChartCamera> for Camera {
             ChartView::Z_NEAR,
             z_far,
         );
-        // cam.roll(Deg(val.tilt)); //TODO: Need to roll the position and stuff now
-        cam.yaw(Rad(val.shakes.iter().map(|x| x.get_shake()).sum()));
+        // cam.roll(Deg(self.tilt)); //TODO: Need to roll the position and stuff now
+        cam.yaw(Rad(self.shakes.iter().map(|x| x.get_shake()).sum()));
+        if rotation != DisplayRotation::None {
+            cam.roll(Rad(rotation.camera_roll()));
+        }
 
         cam
     }
@@ -0,0 +1,127 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, ensure};
+use kson::{
+    diff::{diff, ChartDiff},
+    validate::{validate, ChartIssue},
+    Chart, Ksh,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Failed parses of a changed file before the error is shown, editors may still be writing it
+const PARSE_RETRIES: u32 = 4;
+
+/// Watches the file of the playing chart so charters can see their edits without restarting
+pub struct ChartWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+    retries: u32,
+    pub issues: Vec<ChartIssue>,
+    pub error: Option<String>,
+    pub status: Option<String>,
+    /// Changes of the last reload
+    pub changes: Option<ChartDiff>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn read_chart(path: &Path) -> anyhow::Result<Chart> {
+    let data = std::fs::read(path)?;
+    if path.extension().is_some_and(|e| e == "kson") {
+        return Ok(serde_json::from_slice(&data)?);
+    }
+
+    let data = encoding::decode(
+        &data,
+        encoding::DecoderTrap::Strict,
+        encoding::all::WINDOWS_31J,
+    )
+    .0
+    .map_err(|_| anyhow!("Bad encoding"))?;
+
+    // A half written file can trip up the parser in ways it doesn't expect
+    let chart = std::panic::catch_unwind(|| Chart::from_ksh(&data))
+        .map_err(|_| anyhow!("Chart parser panicked"))??;
+    // Timing can't be calculated for these, so they can't be played at all
+    ensure!(
+        chart.beat.bpm.iter().all(|(_, bpm)| *bpm > 0.0)
+            && chart
+                .beat
+                .time_sig
+                .iter()
+                .all(|(_, sig)| sig.0 > 0 && sig.1 > 0),
+        "Zero BPM or time signature"
+    );
+    Ok(chart)
+}
+
+impl ChartWatcher {
+    pub fn new(path: PathBuf, chart: &Chart) -> Self {
+        Self {
+            modified: modified(&path),
+            path,
+            last_poll: Instant::now(),
+            retries: 0,
+            issues: validate(chart),
+            error: None,
+            status: None,
+            changes: None,
+        }
+    }
+
+    /// Anything to show in the overlay
+    pub fn has_report(&self) -> bool {
+        !self.issues.is_empty() || self.error.is_some() || self.status.is_some()
+    }
+
+    /// Returns the chart file once it changed and parses, `current` is used to describe the
+    /// changes
+    pub fn poll(&mut self, current: &Chart) -> Option<Chart> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+
+        let modified = modified(&self.path);
+        if modified == self.modified && self.retries == 0 {
+            return None;
+        }
+        self.modified = modified;
+
+        match read_chart(&self.path) {
+            Ok(chart) => {
+                self.retries = 0;
+                self.error = None;
+                self.issues = validate(&chart);
+                let changes = diff(current, &chart);
+                self.status = Some(match changes.first_note_tick() {
+                    Some(y) => format!(
+                        "Reloaded, notes changed from measure {}",
+                        chart.tick_to_measure(y) + 1
+                    ),
+                    None => "Reloaded, no note changes".to_string(),
+                });
+                self.changes = Some(changes);
+                log::info!("Reloaded {}", self.path.display());
+                Some(chart)
+            }
+            Err(e) if self.retries < PARSE_RETRIES => {
+                log::debug!("Retrying chart reload: {e}");
+                self.retries += 1;
+                None
+            }
+            Err(e) => {
+                log::warn!("Failed to reload {}: {e}", self.path.display());
+                self.retries = 0;
+                self.error = Some(e.to_string());
+                None
+            }
+        }
+    }
+}
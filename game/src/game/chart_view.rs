@@ -1,8 +1,12 @@
-use std::{path::Path, rc::Rc, sync::Arc};
+use std::rc::Rc;
 
 use crate::{config::GameConfig, game::HoldState};
 
-use super::graphics::{self, GlVertex};
+use super::{
+    graphics::{self, GlVertex},
+    scroll_speed::ScrollSpeedMap,
+    track_layout::TrackLayout,
+};
 
 pub struct ChartView {
     pub hispeed: f32,
@@ -10,15 +14,14 @@ pub struct ChartView {
     laser_meshes: [Vec<Vec<graphics::GlVertex>>; 2],
     track: CpuMesh,
     distant_button_scale: f32,
+    scroll_speed: ScrollSpeedMap,
+    pub layout: TrackLayout,
 }
 
 use anyhow::anyhow;
 use kson::KSON_RESOLUTION;
 use puffin::{profile_function, profile_scope};
-use three_d::{
-    vec2, vec3, Blend, ColorMaterial, CpuMesh, DepthTest, Indices, Mat3, RenderStates, Texture2D,
-    Vec3,
-};
+use three_d::{vec2, vec3, ColorMaterial, CpuMesh, Indices, Vec3};
 use three_d_asset::Srgba;
 impl ChartView {
     pub const TRACK_LENGTH: f32 = 16.0;
@@ -26,51 +29,13 @@ impl ChartView {
     pub const TRACK_DIRECTION: Vec3 = vec3(0.0, 1.0, 0.0);
     pub const Z_NEAR: f32 = 0.01;
 
-    pub fn new(skin_root: impl AsRef<Path>, td: &three_d::Context) -> anyhow::Result<Self> {
-        let _indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
-        let mut texure_path = skin_root.as_ref().to_path_buf();
-        texure_path.push("textures");
-        texure_path.push("file.png");
+    pub fn new(td: &three_d::Context, layout: TrackLayout) -> anyhow::Result<Self> {
         td.set_depth_test(three_d::DepthTest::Never);
 
-        let mut textures = three_d_asset::io::load(&[
-            texure_path.with_file_name("laser_l.png"),
-            texure_path.with_file_name("laser_r.png"),
-            texure_path.with_file_name("track.png"),
-            texure_path.with_file_name("fxbutton.png"),
-            texure_path.with_file_name("button.png"),
-        ])?;
-
-        let _laser_texture = Some(Arc::new(Texture2D::new(
-            td,
-            &textures.deserialize("laser_l")?,
-        )));
-        let _laser_render_states = RenderStates {
-            blend: Blend::ADD,
-            depth_test: DepthTest::Always,
-            ..Default::default()
-        };
-
-        let track_texture = Arc::new(Texture2D::new(td, &textures.deserialize("track")?));
-
-        let _track_mat = Rc::new(ColorMaterial {
-            color: Srgba::WHITE,
-            texture: Some(three_d::Texture2DRef {
-                texture: track_texture,
-                transformation: Mat3::from_scale(1.0),
-            }),
-            render_states: RenderStates {
-                depth_test: three_d::DepthTest::Always,
-                ..Default::default()
-            },
-            ..Default::default()
-        });
-
-        let track = graphics::xy_rect(vec3(0.0, 0.0, 0.0), vec2(1.0, Self::TRACK_LENGTH * 2.0));
-        let _button_render_states = RenderStates {
-            depth_test: DepthTest::Always,
-            ..Default::default()
-        };
+        let track = graphics::xy_rect(
+            vec3(0.0, 0.0, 0.0),
+            vec2(layout.track_width(), Self::TRACK_LENGTH * 2.0),
+        );
 
         Ok(ChartView {
             distant_button_scale: GameConfig::get().distant_button_scale,
@@ -78,109 +43,71 @@ impl ChartView {
             hispeed: 1.0,
             laser_meshes: [Vec::new(), Vec::new()],
             track,
+            scroll_speed: ScrollSpeedMap::default(),
+            layout,
         })
     }
 
+    pub fn build_scroll_speed(&mut self, chart: &kson::Chart) {
+        self.scroll_speed = ScrollSpeedMap::new(&chart.beat.scroll_speed);
+    }
+
     pub fn build_laser_meshes(&mut self, chart: &kson::Chart) {
         for i in 0..2 {
-            self.laser_meshes[i].clear();
-            for section in &chart.note.laser[i] {
-                let mut section_verts = Vec::new();
-                let w = 1.0 / 6.0;
-                let (xoff, track_w) = if section.wide() < 2 {
-                    (2.0 / 6.0, 5.0 / 6.0)
-                } else {
-                    (9.0 / 12.0, 10.0 / 6.0)
-                };
-                let mut is_first = true;
-                for se in section.segments() {
-                    let s = se[0];
-                    let e = se[1];
-                    let mut syoff = 0.0_f32;
-                    let mut start_value = s.v as f32 * track_w;
-
-                    if let Some(value) = s.vf {
-                        let value = value as f32 * track_w;
-                        syoff = KSON_RESOLUTION as f32 / 8.0;
-                        graphics::generate_slam_verts(
-                            &mut section_verts,
-                            start_value,
-                            value,
-                            syoff,
-                            xoff,
-                            s.ry as f32,
-                            w,
-                            is_first,
-                            false,
-                        );
-                        start_value = value;
-                    }
-                    let end_value = e.v as f32 * track_w;
-                    let x00 = end_value - w - xoff;
-                    let x01 = end_value - xoff;
-                    let x10 = start_value - w - xoff;
-                    let x11 = start_value - xoff;
-                    let y0 = e.ry as f32;
-                    let y1 = s.ry as f32 + syoff;
-
-                    section_verts.append(&mut vec![
-                        GlVertex::new([y0, 0.0, x00], [0.0, 0.0]),
-                        GlVertex::new([y0, 0.0, x01], [1.0, 0.0]),
-                        GlVertex::new([y1, 0.0, x11], [1.0, 1.0]),
-                        GlVertex::new([y0, 0.0, x00], [0.0, 0.0]),
-                        GlVertex::new([y1, 0.0, x10], [0.0, 1.0]),
-                        GlVertex::new([y1, 0.0, x11], [1.0, 1.0]),
-                    ]);
-                    is_first = false;
-                }
-                if let Some(e) = section.last() {
-                    if let Some(value) = e.vf {
-                        let start_value = e.v as f32 * track_w;
-                        let value = value as f32 * track_w;
-                        let syoff = KSON_RESOLUTION as f32 / 8.0;
-                        graphics::generate_slam_verts(
-                            &mut section_verts,
-                            start_value,
-                            value,
-                            syoff,
-                            xoff,
-                            e.ry as f32,
-                            w,
-                            is_first,
-                            true,
-                        );
-                    }
-                }
-                self.laser_meshes[i].push(section_verts);
-            }
+            self.laser_meshes[i] = chart.note.laser[i]
+                .iter()
+                .map(|section| laser_section_verts(section, &self.layout))
+                .collect();
         }
     }
     const LASER_SPEED_OFFSET: f32 = 0.9;
+
+    /// Tick at the crit line, negative during the lead-in before the chart starts
+    fn view_tick(&self, chart: &kson::Chart) -> i64 {
+        let view_offset = if self.cursor < 0.0 {
+            (self.cursor / chart.tick_to_ms(1)) as i64
+        } else {
+            0
+        };
+        chart.ms_to_tick(self.cursor) as i64 + view_offset
+    }
+
+    /// Ticks of track shown ahead of the crit line
+    fn view_distance(&self) -> f32 {
+        (KSON_RESOLUTION as f32 * 8.0) / self.hispeed
+    }
+
     pub fn render(
         &self,
         chart: &kson::Chart,
         td: &three_d::Context,
-        hold_ok: impl Fn(usize, u32) -> bool,
+        hold_ok: impl Fn(usize, u32, u32) -> bool,
         mut beam_colors: [[f32; 4]; 6],
-        chip_h: f32,
     ) -> anyhow::Result<graphics::TrackRenderMeshes> {
         use three_d::prelude::*;
         profile_function!();
-        let chip_h = chip_h.copysign(-1.0);
-        let view_time = self.cursor;
-        let view_offset = if view_time < 0.0 {
-            (view_time / chart.tick_to_ms(1)) as i64
-        } else {
-            0
-        };
+        let layout = self.layout;
+        let chip_h = layout.chip_height.copysign(-1.0);
 
         td.set_depth_test(three_d::DepthTest::Never);
 
         let _glow_state = if (0.0_f32 * 8.0).fract() > 0.5 { 2 } else { 3 };
-        let view_tick = chart.ms_to_tick(view_time) as i64 + view_offset;
-        let view_distance = (KSON_RESOLUTION as f32 * 8.0) / self.hispeed;
-        let last_view_tick = view_distance.ceil() as i64 + view_tick;
-        let first_view_tick = view_tick - view_distance as i64;
+        let view_tick = self.view_tick(chart);
+        let view_distance = self.view_distance();
+        // Notes are placed by their position on the track rather than by tick so scroll speed
+        // changes move them, judgement still happens by tick
+        let position = |tick: u32| self.scroll_speed.position(tick as f64);
+        let view_position = self.scroll_speed.position(view_tick as f64);
+        let last_view_position = view_position + view_distance as f64;
+        let first_view_position = view_position - view_distance as f64;
+        // Returns None to stop looking for more objects
+        let in_view = |start: f64, end: f64| {
+            if start.min(end) > last_view_position {
+                (!self.scroll_speed.is_monotonic()).then_some(false)
+            } else {
+                Some(start.max(end) >= first_view_position)
+            }
+        };
         let y_view_div = view_distance / -Self::TRACK_LENGTH;
         let laser_y_view_div = y_view_div * Self::LASER_SPEED_OFFSET;
         let _white_mat = Rc::new(ColorMaterial {
@@ -193,11 +120,11 @@ impl ChartView {
         enum NoteType {
             BtChip,
             BtHold,
-            BtHoldActive(usize, u32),
+            BtHoldActive(usize, u32, u32),
             FxChip,
             FxChipSample,
             FxHold,
-            FxHoldActive(usize, u32),
+            FxHoldActive(usize, u32, u32),
         }
         let mut notes = Vec::new();
 
@@ -207,20 +134,22 @@ impl ChartView {
             profile_scope!("Build notes");
             for i in 0..4 {
                 for n in &chart.note.bt[i] {
-                    if (n.y as i64) > last_view_tick {
-                        break;
-                    } else if ((n.y + n.l) as i64) < first_view_tick {
-                        continue;
+                    let start = position(n.y);
+                    let end = position(n.y + n.l);
+                    match in_view(start, end) {
+                        None => break,
+                        Some(false) => continue,
+                        Some(true) => {}
                     }
 
-                    let w = 0.9 / 6.0;
-                    let x = 1.5 / 6.0 + (i as f32 / 6.0);
+                    let w = layout.lane_width * 0.9;
+                    let x = 0.5 + layout.bt_x(i);
                     let h = if n.l == 0 {
                         chip_h
                     } else {
-                        (n.l as f32) / y_view_div
+                        (end - start) as f32 / y_view_div
                     };
-                    let yoff = (view_tick - n.y as i64) as f32;
+                    let yoff = (view_position - start) as f32;
                     let y = yoff / y_view_div;
                     let _p = if n.l == 0 { 2 } else { 1 }; //sorting priority
                     notes.push((
@@ -228,7 +157,7 @@ impl ChartView {
                         vec2(w, h),
                         if n.l > 0 {
                             if (n.y as i64) < view_tick && ((n.y + n.l) as i64) > view_tick {
-                                NoteType::BtHoldActive(i, n.y)
+                                NoteType::BtHoldActive(i, n.y, n.y + n.l)
                             } else {
                                 NoteType::BtHold
                             }
@@ -240,19 +169,21 @@ impl ChartView {
             }
             for i in 0..2 {
                 for n in &chart.note.fx[i] {
-                    if (n.y as i64) > last_view_tick {
-                        break;
-                    } else if ((n.y + n.l) as i64) < first_view_tick {
-                        continue;
+                    let start = position(n.y);
+                    let end = position(n.y + n.l);
+                    match in_view(start, end) {
+                        None => break,
+                        Some(false) => continue,
+                        Some(true) => {}
                     }
-                    let w = 1.0 / 3.0;
-                    let x = 1.0 / 3.0 + (1.0 / 3.0) * i as f32;
+                    let w = layout.fx_width;
+                    let x = 0.5 + layout.fx_x(i);
                     let h = if n.l == 0 {
                         chip_h
                     } else {
-                        (n.l as f32) / y_view_div
+                        (end - start) as f32 / y_view_div
                     };
-                    let yoff = (view_tick - n.y as i64) as f32;
+                    let yoff = (view_position - start) as f32;
                     let y = yoff / y_view_div;
                     let _p = if n.l == 0 { 3 } else { 0 }; //sorting priority
                     notes.push((
@@ -260,7 +191,7 @@ impl ChartView {
                         vec2(w, h),
                         if n.l > 0 {
                             if (n.y as i64) < view_tick && ((n.y + n.l) as i64) > view_tick {
-                                NoteType::FxHoldActive(i, n.y)
+                                NoteType::FxHoldActive(i, n.y, n.y + n.l)
                             } else {
                                 NoteType::FxHold
                             }
@@ -305,37 +236,20 @@ impl ChartView {
         beam_colors[4][3] *= 0.5;
         beam_colors[5][3] *= 0.5;
 
-        let lane_beams = [
-            (
-                Mat4::from_translation(vec3(-1.5 / 6.0, 0.0, 0.0))
-                    * Mat4::from_nonuniform_scale(1.0 / 6.0, ChartView::TRACK_LENGTH, 1.0),
-                Srgba::from(beam_colors[0]),
-            ),
-            (
-                Mat4::from_translation(-vec3(0.5 / 6.0, 0.0, 0.0))
-                    * Mat4::from_nonuniform_scale(1.0 / 6.0, ChartView::TRACK_LENGTH, 1.0),
-                Srgba::from(beam_colors[1]),
-            ),
-            (
-                Mat4::from_translation(vec3(0.5 / 6.0, 0.0, 0.0))
-                    * Mat4::from_nonuniform_scale(1.0 / 6.0, ChartView::TRACK_LENGTH, 1.0),
-                Srgba::from(beam_colors[2]),
-            ),
-            (
-                Mat4::from_translation(vec3(1.5 / 6.0, 0.0, 0.0))
-                    * Mat4::from_nonuniform_scale(1.0 / 6.0, ChartView::TRACK_LENGTH, 1.0),
-                Srgba::from(beam_colors[3]),
-            ),
+        let beam = |x: f32, width: f32, color: [f32; 4]| {
             (
-                Mat4::from_translation(-vec3(1.0 / 6.0, 0.0, 0.0))
-                    * Mat4::from_nonuniform_scale(2.0 / 6.0, ChartView::TRACK_LENGTH, 1.0),
-                Srgba::from(beam_colors[4]),
-            ),
-            (
-                Mat4::from_translation(vec3(1.0 / 6.0, 0.0, 0.0))
-                    * Mat4::from_nonuniform_scale(2.0 / 6.0, ChartView::TRACK_LENGTH, 1.0),
-                Srgba::from(beam_colors[5]),
-            ),
+                Mat4::from_translation(vec3(x, 0.0, 0.0))
+                    * Mat4::from_nonuniform_scale(width, ChartView::TRACK_LENGTH, 1.0),
+                Srgba::from(color),
+            )
+        };
+        let lane_beams = [
+            beam(layout.bt_x(0), layout.lane_width, beam_colors[0]),
+            beam(layout.bt_x(1), layout.lane_width, beam_colors[1]),
+            beam(layout.bt_x(2), layout.lane_width, beam_colors[2]),
+            beam(layout.bt_x(3), layout.lane_width, beam_colors[3]),
+            beam(layout.fx_x(0), layout.fx_width, beam_colors[4]),
+            beam(layout.fx_x(1), layout.fx_width, beam_colors[5]),
         ];
 
         {
@@ -344,9 +258,9 @@ impl ChartView {
                 match n.1 {
                     NoteType::BtChip => bt_chip.push(n.0),
                     NoteType::BtHold => bt_hold.push((n.0, HoldState::Idle)),
-                    NoteType::BtHoldActive(lane, y) => bt_hold.push((
+                    NoteType::BtHoldActive(lane, start, end) => bt_hold.push((
                         n.0,
-                        if hold_ok(lane, y) {
+                        if hold_ok(lane, start, end) {
                             HoldState::Hit
                         } else {
                             HoldState::Miss
@@ -355,9 +269,9 @@ impl ChartView {
                     NoteType::FxChip => fx_chip.push((n.0, false)),
                     NoteType::FxChipSample => fx_chip.push((n.0, true)),
                     NoteType::FxHold => fx_hold.push((n.0, HoldState::Idle)),
-                    NoteType::FxHoldActive(side, y) => fx_hold.push((
+                    NoteType::FxHoldActive(side, start, end) => fx_hold.push((
                         n.0,
-                        if hold_ok(side + 4, y) {
+                        if hold_ok(side + 4, start, end) {
                             HoldState::Hit
                         } else {
                             HoldState::Miss
@@ -376,22 +290,28 @@ impl ChartView {
                         + s.last()
                             .ok_or(anyhow!("Tried to render an empty laser section"))?
                             .ry;
-                    if (s.tick() as i64) > last_view_tick {
-                        break;
-                    } else if (end_y as i64) < first_view_tick {
-                        continue;
+                    match in_view(position(s.tick()), position(end_y)) {
+                        None => break,
+                        Some(false) => continue,
+                        Some(true) => {}
                     }
                     let vertices = self.laser_meshes[i]
                         .get(sidx)
                         .ok_or(anyhow!("Laser meshes not built correctly"))?;
-                    let yoff = (view_tick - s.tick() as i64) as f32;
                     let laser_mesh = CpuMesh {
                         indices: Indices::U32((0u32..(vertices.len() as u32)).collect()),
                         positions: three_d::Positions::F32(
                             vertices
                                 .iter()
                                 .map(|v| {
-                                    vec3(v.pos.z, (yoff - v.pos.x) / laser_y_view_div, v.pos.y)
+                                    let y = self
+                                        .scroll_speed
+                                        .position(s.tick() as f64 + v.pos.x as f64);
+                                    vec3(
+                                        v.pos.z,
+                                        (view_position - y) as f32 / laser_y_view_div,
+                                        v.pos.y,
+                                    )
                                 })
                                 .collect(),
                         ),
@@ -420,3 +340,144 @@ impl ChartView {
         })
     }
 }
+
+/// Vertices of a laser section, x is stored in z and the tick offset from the start of the
+/// section in x
+fn laser_section_verts(section: &kson::LaserSection, layout: &TrackLayout) -> Vec<GlVertex> {
+    let mut section_verts = Vec::new();
+    let w = layout.lane_width;
+    let track_w = if section.wide() < 2 {
+        layout.laser_width
+    } else {
+        layout.laser_width * 2.0
+    };
+    let xoff = (track_w - w) / 2.0;
+    let mut is_first = true;
+    for se in section.segments() {
+        let s = se[0];
+        let e = se[1];
+        let mut syoff = 0.0_f32;
+        let mut start_value = s.v as f32 * track_w;
+
+        if let Some(value) = s.vf {
+            let value = value as f32 * track_w;
+            syoff = KSON_RESOLUTION as f32 / 8.0;
+            graphics::generate_slam_verts(
+                &mut section_verts,
+                start_value,
+                value,
+                syoff,
+                xoff,
+                s.ry as f32,
+                w,
+                is_first,
+                false,
+            );
+            start_value = value;
+        }
+        let end_value = e.v as f32 * track_w;
+        let x00 = end_value - w - xoff;
+        let x01 = end_value - xoff;
+        let x10 = start_value - w - xoff;
+        let x11 = start_value - xoff;
+        let y0 = e.ry as f32;
+        let y1 = s.ry as f32 + syoff;
+
+        section_verts.append(&mut vec![
+            GlVertex::new([y0, 0.0, x00], [0.0, 0.0]),
+            GlVertex::new([y0, 0.0, x01], [1.0, 0.0]),
+            GlVertex::new([y1, 0.0, x11], [1.0, 1.0]),
+            GlVertex::new([y0, 0.0, x00], [0.0, 0.0]),
+            GlVertex::new([y1, 0.0, x10], [0.0, 1.0]),
+            GlVertex::new([y1, 0.0, x11], [1.0, 1.0]),
+        ]);
+        is_first = false;
+    }
+    if let Some(e) = section.last() {
+        if let Some(value) = e.vf {
+            let start_value = e.v as f32 * track_w;
+            let value = value as f32 * track_w;
+            let syoff = KSON_RESOLUTION as f32 / 8.0;
+            graphics::generate_slam_verts(
+                &mut section_verts,
+                start_value,
+                value,
+                syoff,
+                xoff,
+                e.ry as f32,
+                w,
+                is_first,
+                true,
+            );
+        }
+    }
+    section_verts
+}
+
+#[cfg(test)]
+mod tests {
+    use kson::{Chart, GraphSectionPoint, Interval, LaserSection};
+
+    use super::{laser_section_verts, ChartView, TrackLayout};
+    use crate::game::{graphics, scroll_speed::ScrollSpeedMap};
+
+    fn view(hispeed: f32, cursor: f64) -> ChartView {
+        let layout = TrackLayout::default();
+        ChartView {
+            hispeed,
+            cursor,
+            laser_meshes: [Vec::new(), Vec::new()],
+            track: graphics::xy_rect(
+                three_d::vec3(0.0, 0.0, 0.0),
+                three_d::vec2(layout.track_width(), ChartView::TRACK_LENGTH * 2.0),
+            ),
+            distant_button_scale: 1.0,
+            scroll_speed: ScrollSpeedMap::default(),
+            layout,
+        }
+    }
+
+    fn point(ry: u32, v: f64) -> GraphSectionPoint {
+        GraphSectionPoint {
+            ry,
+            v,
+            vf: None,
+            a: 0.0,
+            b: 0.0,
+        }
+    }
+
+    #[test]
+    fn laser_meshes_follow_layout() {
+        for layout in [TrackLayout::default(), TrackLayout::new(5, 0.2)] {
+            let section = LaserSection(0, vec![point(0, 0.0), point(240, 1.0)], 1);
+            let verts = laser_section_verts(&section, &layout);
+            let xs = || verts.iter().map(|v| v.pos.z);
+            let half = layout.track_width() / 2.0;
+            assert!((xs().fold(f32::MAX, f32::min) + half).abs() < 1e-5);
+            assert!((xs().fold(f32::MIN, f32::max) - half).abs() < 1e-5);
+
+            let wide = LaserSection(0, vec![point(0, 0.0), point(240, 1.0)], 2);
+            let verts = laser_section_verts(&wide, &layout);
+            let max = verts.iter().map(|v| v.pos.z).fold(f32::MIN, f32::max);
+            assert!((max - layout.laser_x(1.0, 2) - layout.lane_width / 2.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn first_note_shown_before_start() {
+        let mut chart = Chart::new();
+        chart.beat.bpm = vec![(0, 120.0)];
+        chart.note.bt[0].push(Interval { y: 480, l: 0 });
+
+        // The game is held at the start of its lead-in until the transition reveals it
+        let held = view(1.5, -1000.0);
+        let tick = held.view_tick(&chart);
+        assert!(tick < 0);
+        assert!(480 - tick <= held.view_distance() as i64);
+
+        // Time passing behind the transition would have scrolled it past the crit line
+        let late = view(1.5, 1500.0);
+        assert!(late.view_tick(&chart) > 480);
+    }
+}
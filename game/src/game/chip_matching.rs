@@ -0,0 +1,134 @@
+use super::HitWindow;
+
+/// Judgement of a chip hit by a press
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChipJudgement {
+    Crit,
+    Near,
+    Miss,
+}
+
+/// Picks the chip a single button press judges, if any.
+///
+/// `chips` are the unjudged chips in the pressed lane as `(index, delta)`, where `delta` is the
+/// chip time minus the press time in milliseconds, positive when pressing early. The closest chip
+/// whose window contains the press is judged:
+/// - within the good window it's hit,
+/// - pressing up to the early miss window before it is a miss, a chip further away in the lane
+///   can't be missed while another one is closer,
+/// - pressing even earlier does nothing.
+///
+/// A press judges at most one chip, so mashing can't clear several notes at once.
+pub fn match_press(
+    chips: impl IntoIterator<Item = (usize, f64)>,
+    window: &HitWindow,
+) -> Option<(usize, ChipJudgement)> {
+    let ms = |d: std::time::Duration| d.as_secs_f64() * 1000.0;
+    let perfect = ms(window.perfect);
+    let good = ms(window.good);
+    let early_limit = good.max(ms(window.early_miss));
+    let late_limit = good.max(ms(window.miss));
+
+    let (index, delta) = chips
+        .into_iter()
+        .filter(|&(_, delta)| {
+            if delta >= 0.0 {
+                delta <= early_limit
+            } else {
+                -delta <= late_limit
+            }
+        })
+        .min_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))?;
+
+    let judgement = if delta.abs() <= perfect {
+        ChipJudgement::Crit
+    } else if delta.abs() <= good {
+        ChipJudgement::Near
+    } else {
+        ChipJudgement::Miss
+    };
+
+    Some((index, judgement))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{match_press, ChipJudgement};
+    use crate::game::HitWindow;
+
+    /// Unjudged chips as `(lane, ms)`, presses remove what they judge like the game does
+    struct Lanes(Vec<(usize, f64)>);
+
+    impl Lanes {
+        fn press(&mut self, lane: usize, time: f64) -> Option<(f64, ChipJudgement)> {
+            let chips = self
+                .0
+                .iter()
+                .enumerate()
+                .filter(|(_, (l, _))| *l == lane)
+                .map(|(i, (_, ms))| (i, ms - time));
+            let (index, judgement) = match_press(chips, &HitWindow::NORMAL)?;
+            Some((self.0.remove(index).1, judgement))
+        }
+    }
+
+    #[test]
+    fn early_presses() {
+        let early_miss = HitWindow::NORMAL.early_miss.as_secs_f64() * 1000.0;
+        let good = HitWindow::NORMAL.good.as_secs_f64() * 1000.0;
+        let mut lanes = Lanes(vec![(0, 1000.0)]);
+
+        // Too early to count
+        assert_eq!(lanes.press(0, 1000.0 - early_miss - 1.0), None);
+        assert_eq!(
+            lanes.press(0, 1000.0 - (good + early_miss) / 2.0),
+            Some((1000.0, ChipJudgement::Miss))
+        );
+        assert!(lanes.0.is_empty());
+    }
+
+    #[test]
+    fn stream() {
+        // 16ths at 180 BPM
+        let mut lanes = Lanes((0..8).map(|i| (i % 4, 1000.0 + i as f64 * 83.3)).collect());
+        for i in 0..8 {
+            let ms = 1000.0 + i as f64 * 83.3;
+            assert_eq!(
+                lanes.press(i % 4, ms + 10.0),
+                Some((ms, ChipJudgement::Crit))
+            );
+        }
+        assert!(lanes.0.is_empty());
+    }
+
+    #[test]
+    fn jacks() {
+        // Fast jack, pressing late on the first note hits the closer second one
+        let mut lanes = Lanes(vec![(1, 1000.0), (1, 1100.0), (1, 1200.0)]);
+        assert_eq!(lanes.press(1, 1080.0), Some((1100.0, ChipJudgement::Crit)));
+        assert_eq!(lanes.press(1, 1095.0), Some((1000.0, ChipJudgement::Near)));
+        assert_eq!(lanes.press(1, 1190.0), Some((1200.0, ChipJudgement::Crit)));
+
+        // Double press on a slow jack, the second press can't take the next note
+        let mut lanes = Lanes(vec![(1, 1000.0), (1, 1500.0)]);
+        assert_eq!(lanes.press(1, 1000.0), Some((1000.0, ChipJudgement::Crit)));
+        assert_eq!(lanes.press(1, 1005.0), None);
+        assert_eq!(lanes.0, vec![(1, 1500.0)]);
+
+        // Mashing early misses only the closest note
+        let mut lanes = Lanes(vec![(1, 1000.0), (1, 1200.0)]);
+        assert_eq!(lanes.press(1, 800.0), Some((1000.0, ChipJudgement::Miss)));
+        assert_eq!(lanes.0, vec![(1, 1200.0)]);
+    }
+
+    #[test]
+    fn chords() {
+        let mut lanes = Lanes(vec![(0, 1000.0), (2, 1000.0), (3, 1000.0), (0, 1250.0)]);
+        assert_eq!(lanes.press(0, 1005.0), Some((1000.0, ChipJudgement::Crit)));
+        // A lane without a chip doesn't take one from another lane
+        assert_eq!(lanes.press(1, 1005.0), None);
+        assert_eq!(lanes.press(2, 1060.0), Some((1000.0, ChipJudgement::Near)));
+        assert_eq!(lanes.press(3, 990.0), Some((1000.0, ChipJudgement::Crit)));
+        assert_eq!(lanes.0, vec![(0, 1250.0)]);
+    }
+}
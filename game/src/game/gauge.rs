@@ -1,18 +1,29 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, ops::RangeInclusive};
 
 use anyhow::bail;
-use kson::score_ticks::{PlacedScoreTick, ScoreTick};
+use kson::score_ticks::{PlacedScoreTick, ScoreTick, ScoreTickSummary};
 
 use super::HitRating;
 
 pub const GAUGE_SAMPLES: usize = 128;
+/// Normal gauge gained by crits on every tick of a chart, more than the gauge can hold so a few
+/// misses can still end at 100%
+const NORMAL_GAUGE_TOTAL: f32 = 2.10 + f32::EPSILON;
+const HARD_MISS_DRAIN: f32 = 0.09;
 
-#[derive(Debug, Default, serde::Serialize, serde::Deserialize, Clone, Copy)]
+/// Lowest and highest blastive gauge levels, levels go up in half steps
+pub const BLASTIVE_LEVELS: RangeInclusive<f32> = 0.5..=10.0;
+/// Blastive level that drains like the hard gauge, clears from this level up count as hard clears
+pub const BLASTIVE_HARD_LEVEL: f32 = 3.0;
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum GaugeType {
     #[default]
     Normal,
     Hard,
+    /// Hard gauge with drains scaled by an adjustable level
+    Blastive,
 }
 
 impl TryFrom<Gauge> for GaugeType {
@@ -23,6 +34,7 @@ impl TryFrom<Gauge> for GaugeType {
             Gauge::None => bail!("Invalid gauge type"),
             Gauge::Normal { .. } => Ok(Self::Normal),
             Gauge::Hard { .. } => Ok(Self::Hard),
+            Gauge::Blastive { .. } => Ok(Self::Blastive),
         }
     }
 }
@@ -30,33 +42,101 @@ impl TryFrom<Gauge> for GaugeType {
 impl GaugeType {
     pub fn fallback_supported(self) -> bool {
         match self {
+            GaugeType::Normal => false,
+            GaugeType::Hard | GaugeType::Blastive => true,
+        }
+    }
+}
+
+/// Gauge picked for a play, everything the gauge math depends on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaugeSpec {
+    pub gauge_type: GaugeType,
+    /// Drain level of the blastive gauge, unused by the others
+    pub level: f32,
+}
+
+impl From<GaugeType> for GaugeSpec {
+    fn from(gauge_type: GaugeType) -> Self {
+        Self::new(gauge_type, BLASTIVE_HARD_LEVEL)
+    }
+}
+
+impl GaugeSpec {
+    /// `level` is rounded to the closest valid blastive level
+    pub fn new(gauge_type: GaugeType, level: f32) -> Self {
+        let level = if level.is_finite() {
+            ((level * 2.0).round() / 2.0).clamp(*BLASTIVE_LEVELS.start(), *BLASTIVE_LEVELS.end())
+        } else {
+            BLASTIVE_HARD_LEVEL
+        };
+        Self { gauge_type, level }
+    }
+
+    /// Spec from the gauge type and option stored with a score
+    pub fn from_score(gauge_type: u8, option: i32) -> Self {
+        match gauge_type {
+            1 => GaugeType::Hard.into(),
+            2 => Self::new(GaugeType::Blastive, option as f32 / 2.0),
+            _ => GaugeType::Normal.into(),
+        }
+    }
+
+    /// Type specific option stored with scores, the blastive level in half steps
+    pub fn option(&self) -> i32 {
+        match self.gauge_type {
+            GaugeType::Blastive => (self.level * 2.0).round() as i32,
+            GaugeType::Normal | GaugeType::Hard => 0,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self.gauge_type {
+            GaugeType::Normal => "Normal".into(),
+            GaugeType::Hard => "Hard".into(),
+            GaugeType::Blastive => format!("Blastive {:.1}", self.level),
+        }
+    }
+
+    /// Whether a clear with this gauge counts as a hard clear
+    pub fn is_hard(&self) -> bool {
+        match self.gauge_type {
             GaugeType::Normal => false,
             GaugeType::Hard => true,
+            GaugeType::Blastive => self.level >= BLASTIVE_HARD_LEVEL,
         }
     }
 
-    fn gain_rate(self) -> f32 {
-        match self {
+    fn gain_rate(&self) -> f32 {
+        match self.gauge_type {
             GaugeType::Normal => 1.0,
-            GaugeType::Hard => 12.0 / 21.0,
+            GaugeType::Hard | GaugeType::Blastive => 12.0 / 21.0,
         }
     }
 
     pub fn get_gauge(self, chip_gain: f32, tick_gain: f32) -> Gauge {
         let chip_gain = chip_gain * self.gain_rate();
         let tick_gain = tick_gain * self.gain_rate();
-        match self {
+        let samples = Box::new([0.0; GAUGE_SAMPLES]);
+        match self.gauge_type {
             GaugeType::Normal => Gauge::Normal {
                 chip_gain,
                 tick_gain,
                 value: 0.0,
-                samples: Box::new([0.0; GAUGE_SAMPLES]),
+                samples,
             },
             GaugeType::Hard => Gauge::Hard {
                 chip_gain,
                 tick_gain,
                 value: 1.0,
-                samples: Box::new([0.0; GAUGE_SAMPLES]),
+                samples,
+            },
+            GaugeType::Blastive => Gauge::Blastive {
+                chip_gain,
+                tick_gain,
+                value: 1.0,
+                level: self.level,
+                samples,
             },
         }
     }
@@ -78,6 +158,14 @@ pub enum Gauge {
         value: f32,
         samples: Box<[f32; GAUGE_SAMPLES]>,
     },
+    /// Hard gauge with its drains scaled by `level`
+    Blastive {
+        chip_gain: f32,
+        tick_gain: f32,
+        value: f32,
+        level: f32,
+        samples: Box<[f32; GAUGE_SAMPLES]>,
+    },
 }
 
 #[derive(Default)]
@@ -138,24 +226,52 @@ fn tick_is_short(score_tick: PlacedScoreTick) -> bool {
     }
 }
 
+/// Gains of a crit on chips and slams, and on hold and laser ticks, which are worth a quarter of
+/// a chip
+fn tick_gains(summary: &ScoreTickSummary) -> (f32, f32) {
+    let long_count = (summary.hold_count + summary.laser_count) as f32;
+    let chip_count = (summary.chip_count + summary.slam_count) as f32;
+
+    if long_count == 0.0 && chip_count != 0.0 {
+        (NORMAL_GAUGE_TOTAL / chip_count, 0.0)
+    } else if long_count != 0.0 && chip_count == 0.0 {
+        (0.0, NORMAL_GAUGE_TOTAL / long_count)
+    } else {
+        let gain = (NORMAL_GAUGE_TOTAL * 20.0) / (5.0 * (long_count + 4.0 * chip_count));
+        (gain, gain / 4.0)
+    }
+}
+
 fn hard_drain_multiplier(value: f32) -> f32 {
     f32::clamp(1.0 - ((0.3 - value) * 2.0), 0.5, 1.0)
 }
 
 impl Gauge {
-    pub fn gain_rate(&self) -> f32 {
+    /// Gauge of `spec` with gains spread over the score ticks of a chart
+    pub fn new(spec: impl Into<GaugeSpec>, summary: &ScoreTickSummary) -> Self {
+        let (chip_gain, tick_gain) = tick_gains(summary);
+        spec.into().get_gauge(chip_gain, tick_gain)
+    }
+
+    pub fn spec(&self) -> Option<GaugeSpec> {
         match self {
-            Gauge::None => 1.0,
-            Gauge::Normal { .. } => 1.0,
-            Gauge::Hard { .. } => 12.0 / 21.0,
+            Gauge::None => None,
+            Gauge::Normal { .. } => Some(GaugeType::Normal.into()),
+            Gauge::Hard { .. } => Some(GaugeType::Hard.into()),
+            Gauge::Blastive { level, .. } => Some(GaugeSpec::new(GaugeType::Blastive, *level)),
         }
     }
 
+    pub fn gain_rate(&self) -> f32 {
+        self.spec().map_or(1.0, |s| s.gain_rate())
+    }
+
     pub fn miss_drain_percent(&self) -> f32 {
         match self {
             Gauge::None => 0.02,
             Gauge::Normal { .. } => 0.02,
-            Gauge::Hard { .. } => 0.09,
+            Gauge::Hard { .. } => HARD_MISS_DRAIN,
+            Gauge::Blastive { level, .. } => HARD_MISS_DRAIN * level / BLASTIVE_HARD_LEVEL,
         }
     }
 
@@ -172,7 +288,7 @@ impl Gauge {
             } => match rating {
                 HitRating::Crit { tick: t, .. } if tick_is_short(t) => *value += *chip_gain,
                 HitRating::Crit { .. } => *value += *tick_gain,
-                HitRating::Good { .. } => *value += *chip_gain / 3.0, //Only chips can have a "good" rating
+                HitRating::Good { .. } => *value += *chip_gain / 2.0, //Only chips can have a "good" rating
                 HitRating::Miss { tick: t, .. } if tick_is_short(t) => *value -= short_miss_percent,
                 HitRating::Miss { .. } => *value -= short_miss_percent / 4.0,
                 HitRating::None => {}
@@ -182,10 +298,16 @@ impl Gauge {
                 tick_gain,
                 value,
                 ..
+            }
+            | Gauge::Blastive {
+                chip_gain,
+                tick_gain,
+                value,
+                ..
             } if *value > 0.0 => match rating {
                 HitRating::Crit { tick: t, .. } if tick_is_short(t) => *value += *chip_gain,
                 HitRating::Crit { .. } => *value += *tick_gain,
-                HitRating::Good { .. } => *value += *chip_gain / 3.0, //Only chips can have a "good" rating
+                HitRating::Good { .. } => *value += *chip_gain / 2.0, //Only chips can have a "good" rating
                 HitRating::Miss { tick: t, .. } if tick_is_short(t) => {
                     *value -= short_miss_percent * hard_drain_multiplier(*value)
                 }
@@ -195,21 +317,19 @@ impl Gauge {
                 HitRating::None => {}
             },
 
-            Gauge::Hard { .. } => {} // Failed hard gauge can't be updated
+            Gauge::Hard { .. } | Gauge::Blastive { .. } => {} // Failed hard gauge can't be updated
         }
 
         //Clamp
-        match self {
-            Gauge::None => todo!(),
-            Gauge::Normal { value, .. } => *value = value.clamp(0.0, 1.0),
-            Gauge::Hard { value, .. } => *value = value.clamp(0.0, 1.0),
+        if let Some(value) = self.value_mut() {
+            *value = value.clamp(0.0, 1.0);
         }
     }
 
     pub fn is_cleared(&self) -> bool {
         match self {
             Gauge::Normal { value, .. } => *value >= 0.7,
-            Gauge::Hard { value, .. } => *value >= 0.0,
+            Gauge::Hard { value, .. } | Gauge::Blastive { value, .. } => *value >= 0.0,
             Gauge::None => false,
         }
     }
@@ -218,31 +338,174 @@ impl Gauge {
         match self {
             Gauge::None => false,
             Gauge::Normal { .. } => false,
-            Gauge::Hard { value, .. } => *value == 0.0,
+            Gauge::Hard { value, .. } | Gauge::Blastive { value, .. } => *value == 0.0,
         }
     }
 
     pub fn value(&self) -> f32 {
         match self {
             Gauge::None => 0.0,
-            Gauge::Normal { value, .. } => *value,
-            Gauge::Hard { value, .. } => *value,
+            Gauge::Normal { value, .. }
+            | Gauge::Hard { value, .. }
+            | Gauge::Blastive { value, .. } => *value,
+        }
+    }
+
+    fn value_mut(&mut self) -> Option<&mut f32> {
+        match self {
+            Gauge::None => None,
+            Gauge::Normal { value, .. }
+            | Gauge::Hard { value, .. }
+            | Gauge::Blastive { value, .. } => Some(value),
+        }
+    }
+
+    /// Sets the value of a gauge recreated from a stored score
+    pub fn set_value(&mut self, new_value: f32) {
+        if let Some(value) = self.value_mut() {
+            *value = new_value;
         }
     }
 
     pub fn update_sample(&mut self, sample: usize) {
         match self {
             Gauge::None => {}
-            Gauge::Normal { value, samples, .. } => samples[sample.min(GAUGE_SAMPLES - 1)] = *value,
-            Gauge::Hard { value, samples, .. } => samples[sample.min(GAUGE_SAMPLES - 1)] = *value,
+            Gauge::Normal { value, samples, .. }
+            | Gauge::Hard { value, samples, .. }
+            | Gauge::Blastive { value, samples, .. } => {
+                samples[sample.min(GAUGE_SAMPLES - 1)] = *value
+            }
         }
     }
 
     pub fn get_samples(&self) -> &[f32] {
         match self {
             Gauge::None => &[],
-            Gauge::Normal { samples, .. } => samples.as_ref(),
-            Gauge::Hard { samples, .. } => samples.as_ref(),
+            Gauge::Normal { samples, .. }
+            | Gauge::Hard { samples, .. }
+            | Gauge::Blastive { samples, .. } => samples.as_ref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kson::score_ticks::{PlacedScoreTick, ScoreTick, ScoreTickSummary};
+
+    use super::{Gauge, GaugeSpec, GaugeType, BLASTIVE_HARD_LEVEL};
+    use crate::game::HitRating;
+
+    fn summary(chips: u32, holds: u32) -> ScoreTickSummary {
+        ScoreTickSummary {
+            chip_count: chips,
+            hold_count: holds,
+            total: chips + holds,
+            ..Default::default()
+        }
+    }
+
+    fn ticks(chips: u32, holds: u32) -> impl Iterator<Item = PlacedScoreTick> {
+        let chip = ScoreTick::Chip { lane: 0 };
+        let hold = ScoreTick::Hold {
+            lane: 0,
+            start_tick: 0,
+            end_tick: 0,
+        };
+        std::iter::repeat(chip)
+            .take(chips as usize)
+            .chain(std::iter::repeat(hold).take(holds as usize))
+            .map(|tick| PlacedScoreTick { y: 0, tick })
+    }
+
+    fn crit(tick: PlacedScoreTick) -> HitRating {
+        HitRating::Crit {
+            tick,
+            delta: 0.0,
+            time: 0.0,
+            input_time: None,
+        }
+    }
+
+    fn miss(tick: PlacedScoreTick) -> HitRating {
+        HitRating::Miss {
+            tick,
+            delta: 0.0,
+            time: 0.0,
+            input_time: None,
+        }
+    }
+
+    #[test]
+    fn perfect_play_fills_gauge() {
+        for (chips, holds) in [(1000, 0), (0, 1000), (400, 600), (1, 999)] {
+            for gauge_type in [GaugeType::Normal, GaugeType::Hard] {
+                let mut gauge = Gauge::new(gauge_type, &summary(chips, holds));
+                for tick in ticks(chips, holds) {
+                    gauge.on_hit(crit(tick));
+                }
+                assert_eq!(gauge.value(), 1.0, "{chips} chips, {holds} holds");
+            }
+        }
+
+        // Most of the chart is needed to fill the gauge
+        let mut gauge = Gauge::new(GaugeType::Normal, &summary(1000, 0));
+        for tick in ticks(400, 0) {
+            gauge.on_hit(crit(tick));
         }
+        assert!((gauge.value() - 0.84).abs() < 1e-3);
+    }
+
+    #[test]
+    fn misses_drain_gauge() {
+        let mut gauge = Gauge::new(GaugeType::Normal, &summary(1000, 0));
+        for tick in ticks(1000, 0) {
+            gauge.on_hit(miss(tick));
+        }
+        assert_eq!(gauge.value(), 0.0);
+
+        let mut gauge = Gauge::new(GaugeType::Hard, &summary(10, 10));
+        let (chip, hold) = (ticks(1, 0).next().unwrap(), ticks(0, 1).next().unwrap());
+        gauge.on_hit(miss(chip));
+        let chip_drain = 1.0 - gauge.value();
+        let before = gauge.value();
+        gauge.on_hit(miss(hold));
+        assert!(before - gauge.value() < chip_drain);
+
+        for tick in ticks(100, 0) {
+            gauge.on_hit(miss(tick));
+        }
+        assert_eq!(gauge.value(), 0.0);
+        assert!(gauge.is_dead());
+    }
+
+    #[test]
+    fn blastive_levels() {
+        let chip = ticks(1, 0).next().unwrap();
+        let drain = |spec: GaugeSpec| {
+            let mut gauge = Gauge::new(spec, &summary(10, 10));
+            gauge.on_hit(miss(chip));
+            1.0 - gauge.value()
+        };
+
+        let hard = drain(GaugeType::Hard.into());
+        let blastive = |level| GaugeSpec::new(GaugeType::Blastive, level);
+        assert!((drain(blastive(BLASTIVE_HARD_LEVEL)) - hard).abs() < 1e-6);
+        assert!((drain(blastive(1.5)) - hard / 2.0).abs() < 1e-6);
+        assert!(drain(blastive(10.0)) > hard);
+
+        // Stored with scores in half steps
+        let spec = blastive(2.74);
+        assert_eq!(spec.level, 2.5);
+        assert_eq!(spec.option(), 5);
+        assert_eq!(GaugeSpec::from_score(2, spec.option()), spec);
+        assert_eq!(spec.name(), "Blastive 2.5");
+        assert_eq!(blastive(40.0).level, 10.0);
+
+        assert!(!spec.is_hard());
+        assert!(blastive(BLASTIVE_HARD_LEVEL).is_hard());
+        assert_eq!(
+            Gauge::new(spec, &summary(10, 10)).spec(),
+            Some(GaugeSpec::new(GaugeType::Blastive, 2.5))
+        );
     }
 }
@@ -0,0 +1,216 @@
+use kson::{Chart, KSON_RESOLUTION};
+use serde::{Deserialize, Serialize};
+
+/// Part of the end of a hold in which the button may be released without missing the
+/// remaining ticks
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HoldGrace {
+    Ticks(u32),
+    Millis(f64),
+}
+
+impl Default for HoldGrace {
+    /// A third of a beat
+    fn default() -> Self {
+        Self::Ticks(KSON_RESOLUTION / 3)
+    }
+}
+
+/// Times of a hold in chart milliseconds
+#[derive(Debug, Clone, Copy)]
+pub struct HoldTiming {
+    /// Presses before this belong to an earlier note
+    earliest_press: f64,
+    /// Releases after this still credit the remaining ticks
+    grace_start: f64,
+}
+
+impl HoldTiming {
+    pub fn new(chart: &Chart, start: u32, end: u32, hold_window_ms: f64, grace: HoldGrace) -> Self {
+        let start_ms = chart.tick_to_ms(start);
+        let grace_start = match grace {
+            HoldGrace::Ticks(ticks) => chart.tick_to_ms(end.saturating_sub(ticks)),
+            HoldGrace::Millis(ms) => chart.tick_to_ms(end) - ms,
+        };
+
+        Self {
+            earliest_press: start_ms - hold_window_ms,
+            grace_start: grace_start.max(start_ms),
+        }
+    }
+}
+
+/// Presses and releases of a button, in chart milliseconds
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HoldButton {
+    /// Time of the current press while the button is held
+    pressed: Option<f64>,
+    /// Press and release times of the last completed press
+    last_press: Option<(f64, f64)>,
+}
+
+impl HoldButton {
+    pub fn press(&mut self, time: f64) {
+        self.pressed = Some(time);
+    }
+
+    pub fn release(&mut self, time: f64) {
+        if let Some(pressed) = self.pressed.take() {
+            self.last_press = Some((pressed, time));
+        }
+    }
+
+    /// Forgets past presses after the chart jumped to `time`, a held button counts as pressed at
+    /// `time`
+    pub fn seek(&mut self, time: f64) {
+        self.pressed = self.pressed.map(|_| time);
+        self.last_press = None;
+    }
+
+    /// Whether the tick of `hold` at `time` is credited.
+    ///
+    /// Ticks are credited while the button is held, also after pressing it again following an
+    /// accidental release. Ticks in the grace window at the end of the hold are also credited
+    /// when the button was released within the window.
+    pub fn judge(&self, hold: &HoldTiming, time: f64) -> bool {
+        let held_for_hold = self.pressed.is_some_and(|p| p > hold.earliest_press);
+        let released_in_grace = time >= hold.grace_start
+            && self
+                .last_press
+                .is_some_and(|(p, r)| p > hold.earliest_press && r >= hold.grace_start);
+
+        held_for_hold || released_in_grace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kson::{
+        score_ticks::{generate_score_ticks, ScoreTick},
+        Interval,
+    };
+
+    const HOLD_WINDOW_MS: f64 = 150.0;
+
+    /// Chart at 120 BPM that switches to 300 BPM at the start of the second measure, which halves
+    /// the number of hold ticks per beat
+    fn chart(hold: Interval) -> Chart {
+        let mut chart = Chart::new();
+        chart.beat.bpm = vec![(0, 120.0), (KSON_RESOLUTION * 4, 300.0)];
+        chart.note.bt[0].push(hold);
+        chart
+    }
+
+    enum Input {
+        Press(u32),
+        Release(u32),
+    }
+
+    /// Judges every tick of the only hold in `chart` after scripted inputs, inputs are given in
+    /// ticks and applied in order as the ticks are reached
+    fn judge(chart: &Chart, grace: HoldGrace, inputs: &[Input]) -> Vec<(u32, bool)> {
+        let ticks = generate_score_ticks(chart);
+        let mut button = HoldButton::default();
+        let mut inputs = inputs.iter().peekable();
+        let mut judged = vec![];
+
+        for tick in ticks {
+            let ScoreTick::Hold {
+                start_tick,
+                end_tick,
+                ..
+            } = tick.tick
+            else {
+                continue;
+            };
+
+            while let Some(input) = inputs.next_if(|i| match i {
+                Input::Press(y) | Input::Release(y) => *y <= tick.y,
+            }) {
+                match input {
+                    Input::Press(y) => button.press(chart.tick_to_ms(*y)),
+                    Input::Release(y) => button.release(chart.tick_to_ms(*y)),
+                }
+            }
+
+            let timing = HoldTiming::new(chart, start_tick, end_tick, HOLD_WINDOW_MS, grace);
+            judged.push((tick.y, button.judge(&timing, chart.tick_to_ms(tick.y))));
+        }
+
+        judged
+    }
+
+    fn misses(judged: &[(u32, bool)]) -> Vec<u32> {
+        judged
+            .iter()
+            .filter(|(_, ok)| !ok)
+            .map(|(y, _)| *y)
+            .collect()
+    }
+
+    #[test]
+    fn full_hold() {
+        let chart = chart(Interval { y: 0, l: 960 });
+        let judged = judge(&chart, HoldGrace::default(), &[Input::Press(0)]);
+        assert_eq!(judged.len(), 15);
+        assert!(misses(&judged).is_empty());
+    }
+
+    #[test]
+    fn early_release_in_grace() {
+        let chart = chart(Interval { y: 0, l: 960 });
+        // Grace window starts at 880, ticks at 900 and 840
+        let inputs = [Input::Press(0), Input::Release(890)];
+        assert!(misses(&judge(&chart, HoldGrace::default(), &inputs)).is_empty());
+
+        let inputs = [Input::Press(0), Input::Release(850)];
+        assert_eq!(misses(&judge(&chart, HoldGrace::default(), &inputs)), [900]);
+
+        let inputs = [Input::Press(0), Input::Release(890)];
+        assert_eq!(misses(&judge(&chart, HoldGrace::Ticks(0), &inputs)), [900]);
+    }
+
+    #[test]
+    fn grace_in_millis_across_bpm_change() {
+        // Ends in the 300 BPM section where hold ticks are half a beat apart
+        let chart = chart(Interval { y: 480, l: 1200 });
+        let judged = judge(&chart, HoldGrace::default(), &[Input::Press(480)]);
+        let ticks: Vec<_> = judged.iter().map(|(y, _)| *y).collect();
+        assert_eq!(
+            ticks,
+            [540, 600, 660, 720, 780, 840, 900, 960, 1080, 1200, 1320, 1440, 1560]
+        );
+
+        // 200ms is a whole beat at 300 BPM, so the last tick is in the window
+        let inputs = [Input::Press(480), Input::Release(1450)];
+        assert!(misses(&judge(&chart, HoldGrace::Millis(200.0), &inputs)).is_empty());
+        assert_eq!(
+            misses(&judge(&chart, HoldGrace::Millis(100.0), &inputs)),
+            [1560]
+        );
+    }
+
+    #[test]
+    fn repress_resumes() {
+        let chart = chart(Interval { y: 0, l: 960 });
+        let inputs = [Input::Press(0), Input::Release(250), Input::Press(370)];
+        assert_eq!(
+            misses(&judge(&chart, HoldGrace::default(), &inputs)),
+            [300, 360]
+        );
+    }
+
+    #[test]
+    fn press_before_hold_window() {
+        // A press for an earlier note doesn't count when held into the hold
+        let mut chart = chart(Interval { y: 480, l: 480 });
+        chart.note.bt[0].insert(0, Interval { y: 0, l: 0 });
+        let inputs = [Input::Press(0)];
+        let judged = judge(&chart, HoldGrace::default(), &inputs);
+        assert_eq!(misses(&judged).len(), judged.len());
+
+        let inputs = [Input::Press(0), Input::Release(10), Input::Press(470)];
+        assert!(misses(&judge(&chart, HoldGrace::default(), &inputs)).is_empty());
+    }
+}
@@ -15,6 +15,8 @@ use std::time::Duration;
 
 use std::path::PathBuf;
 
+use super::track_layout::TrackLayout;
+
 #[derive(Debug, Serialize, Default, Deserialize, Clone, PartialEq, ToLuaLsType)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct LuaGameState {
@@ -43,6 +45,73 @@ pub(crate) struct LuaGameState {
     pub(crate) multiplayer: bool,
     pub(crate) user_id: String,
     pub(crate) practice_setup: bool, // true: it's the setup, false: practicing n
+    pub(crate) track_layout: TrackLayout, // sizes of the track and its lanes in track units
+    pub(crate) retry_count: u32,     // times the chart was restarted without leaving gameplay
+    pub(crate) hud: HudVisibility,   // parts of the HUD the player wants to see
+    pub(crate) bpm_changes: Vec<BpmChange>, // every tempo change of the chart, starting with the initial tempo
+    pub(crate) next_bpm_change: Option<f64>, // milliseconds until the next tempo change, nil if there is none
+    pub(crate) min_bpm: f64,
+    pub(crate) max_bpm: f64,
+    /// The player asked for fewer and softer flashes, skins should tone down full screen flashes
+    /// and fast pulsing effects
+    pub(crate) reduce_flashing: bool,
+}
+
+#[derive(Debug, Serialize, Default, Deserialize, Clone, Copy, PartialEq, ToLuaLsType)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BpmChange {
+    pub(crate) time: f64, // chart time of the change in milliseconds
+    pub(crate) bpm: f64,
+}
+
+impl BpmChange {
+    pub(crate) fn from_chart(chart: &kson::Chart) -> Vec<Self> {
+        chart
+            .bpm_changes()
+            .into_iter()
+            .map(|(time, bpm)| Self { time, bpm })
+            .collect()
+    }
+}
+
+/// Parts of the gameplay HUD shown to the player, skins are expected to skip the hidden ones
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, ToLuaLsType)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HudVisibility {
+    pub score: bool,
+    pub combo: bool,
+    pub gauge: bool,
+    /// Title, artist and jacket
+    pub song_info: bool,
+    pub hit_deltas: bool,
+    pub crit_cursors: bool,
+}
+
+impl HudVisibility {
+    pub const ALL: Self = Self {
+        score: true,
+        combo: true,
+        gauge: true,
+        song_info: true,
+        hit_deltas: true,
+        crit_cursors: true,
+    };
+
+    /// Only what is needed to play, the gauge and the laser cursors
+    pub const MINIMAL: Self = Self {
+        score: false,
+        combo: false,
+        gauge: true,
+        song_info: false,
+        hit_deltas: false,
+        crit_cursors: true,
+    };
+}
+
+impl Default for HudVisibility {
+    fn default() -> Self {
+        Self::ALL
+    }
 }
 
 #[derive(Debug, Serialize, Default, Deserialize, Clone, PartialEq, ToLuaLsType)]
@@ -69,6 +138,11 @@ pub struct HitWindow {
     pub hold: Duration,
     #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
     pub miss: Duration,
+    /// Pressing earlier than the good window but within this misses the next chip, even earlier
+    /// presses are ignored
+    #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
+    #[serde(default = "HitWindow::default_early_miss")]
+    pub early_miss: Duration,
     #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
     pub slam: Duration,
 }
@@ -83,6 +157,7 @@ impl ToLuaLsType for HitWindow {
                 ("good".into(), LuaLsType::Primitive("number".into())),
                 ("hold".into(), LuaLsType::Primitive("number".into())),
                 ("miss".into(), LuaLsType::Primitive("number".into())),
+                ("earlyMiss".into(), LuaLsType::Primitive("number".into())),
                 ("slam".into(), LuaLsType::Primitive("number".into())),
             ]),
         )
@@ -96,6 +171,7 @@ impl HitWindow {
         good: Duration::from_millis(150),
         hold: Duration::from_millis(150),
         miss: Duration::from_millis(300),
+        early_miss: Duration::from_millis(250),
         slam: Duration::from_nanos(83_333_333),
     };
 
@@ -105,9 +181,14 @@ impl HitWindow {
         good: HitWindow::NORMAL.perfect,
         hold: Duration::from_millis(150),
         miss: Duration::from_millis(300),
+        early_miss: HitWindow::NORMAL.early_miss,
         slam: Duration::from_nanos(83_333_333),
     };
 
+    fn default_early_miss() -> Duration {
+        Self::NORMAL.early_miss
+    }
+
     pub fn new(variant: i32, perfect_ms: u64, good_ms: u64, hold_ms: u64, miss_ms: u64) -> Self {
         Self {
             variant,
@@ -115,6 +196,7 @@ impl HitWindow {
             good: Duration::from_millis(good_ms),
             hold: Duration::from_millis(hold_ms),
             miss: Duration::from_millis(miss_ms),
+            early_miss: Self::NORMAL.early_miss,
             slam: Duration::from_nanos(83_333_333),
         }
     }
@@ -139,8 +221,8 @@ pub(crate) struct Cursor {
 }
 
 impl Cursor {
-    pub fn new(pos: f32, camera: &Camera, alpha: f32) -> Self {
-        let pos = (pos - 0.5) * (5.0 / 6.0);
+    pub fn new(pos: f32, camera: &Camera, layout: &TrackLayout, alpha: f32) -> Self {
+        let pos = layout.laser_x(pos, 1);
 
         let crit_pos = Vec2::from(camera.pixel_at_position(vec3(0.0, 0.0, 0.0)));
         let c_pos = Vec2::from(camera.pixel_at_position(vec3(pos, 0.0, 0.0)));
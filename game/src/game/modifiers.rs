@@ -0,0 +1,32 @@
+use kson::LaneModifiers;
+pub use kson::RandomMode;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Lane modifiers picked before playing, applied to the chart once it's loaded
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct PlayModifiers {
+    pub mirror: bool,
+    pub random: RandomMode,
+}
+
+impl PlayModifiers {
+    pub fn is_active(&self) -> bool {
+        self.mirror || self.random != RandomMode::Off
+    }
+
+    /// Modifiers of a new play. Random gets a seed from the OS generator, the seed is kept with
+    /// the score so the lanes that were played can be reproduced.
+    pub fn roll(&self) -> LaneModifiers {
+        LaneModifiers {
+            mirror: self.mirror,
+            random: self.random,
+            seed: if self.random == RandomMode::Off {
+                0
+            } else {
+                OsRng.next_u32()
+            },
+        }
+    }
+}
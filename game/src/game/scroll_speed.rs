@@ -0,0 +1,145 @@
+use kson::{do_curve, GraphPoint};
+
+/// Steps used to integrate curved segments of the graph
+const CURVE_STEPS: usize = 32;
+
+/// Maps ticks to positions along the track following the scroll speed graph of a chart.
+///
+/// Positions are measured in ticks scrolled at a speed of 1, so without any scroll speed changes
+/// every tick maps to itself. Notes stop at a speed of 0 and move backwards at negative speeds.
+#[derive(Default)]
+pub struct ScrollSpeedMap {
+    points: Vec<GraphPoint>,
+    /// Position of each point in the graph
+    positions: Vec<f64>,
+    monotonic: bool,
+}
+
+impl ScrollSpeedMap {
+    pub fn new(graph: &[GraphPoint]) -> Self {
+        let mut positions = Vec::with_capacity(graph.len());
+        let mut position = graph.first().map_or(0.0, |p| p.y as f64 * p.v);
+        for (i, point) in graph.iter().enumerate() {
+            if let Some(prev) = i.checked_sub(1).map(|i| &graph[i]) {
+                position += Self::distance(prev, Some(point), point.y as f64);
+            }
+            positions.push(position);
+        }
+
+        Self {
+            points: graph.to_vec(),
+            positions,
+            monotonic: graph
+                .iter()
+                .all(|p| p.v >= 0.0 && p.vf.unwrap_or(p.v) >= 0.0),
+        }
+    }
+
+    /// Position of `tick` on the track
+    pub fn position(&self, tick: f64) -> f64 {
+        let Some(first) = self.points.first() else {
+            return tick;
+        };
+
+        match self.points.partition_point(|p| p.y as f64 <= tick) {
+            0 => self.positions[0] - (first.y as f64 - tick) * first.v,
+            i => {
+                self.positions[i - 1]
+                    + Self::distance(&self.points[i - 1], self.points.get(i), tick)
+            }
+        }
+    }
+
+    /// False if notes can move backwards, in which case a later note can be seen before an
+    /// earlier one.
+    pub fn is_monotonic(&self) -> bool {
+        self.monotonic || self.points.is_empty()
+    }
+
+    /// Distance scrolled from `start` to `tick`, where `tick` is before `end`
+    fn distance(start: &GraphPoint, end: Option<&GraphPoint>, tick: f64) -> f64 {
+        let start_v = start.vf.unwrap_or(start.v);
+        let dx = tick - start.y as f64;
+        let Some(end) = end else {
+            return start_v * dx;
+        };
+
+        let length = (end.y - start.y) as f64;
+        let width = end.v - start_v;
+        if (start.a - start.b).abs() > f64::EPSILON {
+            let step = dx / CURVE_STEPS as f64;
+            let sum: f64 = (0..CURVE_STEPS)
+                .map(|i| {
+                    let x = (i as f64 + 0.5) * step / length;
+                    start_v + do_curve(x, start.a, start.b) * width
+                })
+                .sum();
+            sum * step
+        } else {
+            start_v * dx + width * dx * dx / (2.0 * length)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(y: u32, v: f64, vf: Option<f64>) -> GraphPoint {
+        GraphPoint {
+            y,
+            v,
+            vf,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stop_and_double_speed() {
+        // Normal speed, stop for a measure, double speed for a measure, back to normal
+        let map = ScrollSpeedMap::new(&[
+            point(0, 1.0, None),
+            point(960, 1.0, Some(0.0)),
+            point(1920, 0.0, Some(2.0)),
+            point(2880, 2.0, Some(1.0)),
+        ]);
+
+        let expected = [
+            (0.0, 0.0),
+            (480.0, 480.0),
+            (960.0, 960.0),
+            (1440.0, 960.0),
+            (1920.0, 960.0),
+            (2400.0, 1920.0),
+            (2880.0, 2880.0),
+            (3360.0, 3360.0),
+        ];
+        for (tick, position) in expected {
+            assert_eq!(map.position(tick), position, "tick {tick}");
+        }
+        assert!(map.is_monotonic());
+    }
+
+    #[test]
+    fn linear_change() {
+        let map = ScrollSpeedMap::new(&[point(0, 1.0, None), point(960, 3.0, None)]);
+        assert_eq!(map.position(480.0), 720.0);
+        assert_eq!(map.position(960.0), 1920.0);
+        assert_eq!(map.position(1440.0), 3360.0);
+    }
+
+    #[test]
+    fn negative_speed() {
+        let map = ScrollSpeedMap::new(&[point(0, 1.0, None), point(480, 1.0, Some(-1.0))]);
+        assert_eq!(map.position(960.0), 0.0);
+        assert_eq!(map.position(-240.0), -240.0);
+        assert!(!map.is_monotonic());
+    }
+
+    #[test]
+    fn empty_graph() {
+        let map = ScrollSpeedMap::default();
+        assert_eq!(map.position(1234.0), 1234.0);
+        assert!(map.is_monotonic());
+    }
+}
@@ -0,0 +1,215 @@
+use egui::{
+    pos2, vec2, Color32, ColorImage, Rect, Sense, Stroke, TextureHandle, TextureOptions, Ui,
+};
+use kson::{
+    score_ticks::{generate_score_ticks, ScoreTick},
+    Chart, KSON_RESOLUTION,
+};
+
+const STRIP_HEIGHT: f32 = 48.0;
+const LASER_HEIGHT: f32 = 4.0;
+const LASER_COLORS: [Color32; 2] = [
+    Color32::from_rgb(0, 170, 255),
+    Color32::from_rgb(255, 0, 170),
+];
+
+pub enum ScrubberAction {
+    Seek(u32),
+    SetLoopStart(u32),
+    SetLoopEnd(u32),
+    ClearLoop,
+}
+
+/// Timeline of a whole chart showing the note density per beat
+pub struct ChartScrubber {
+    /// Average notes per beat of the measure normalized to `0.0..=1.0`, one value for each beat of
+    /// the chart
+    density: Vec<f32>,
+    texture: Option<TextureHandle>,
+    bpm_changes: Vec<u32>,
+    lasers: [Vec<(u32, u32)>; 2],
+    measure_starts: Vec<u32>,
+    duration: u32,
+}
+
+/// Average notes per beat of each measure normalized to `0.0..=1.0`, one value for each beat of
+/// the chart up to `duration`
+pub fn beat_density(chart: &Chart, duration: u32) -> Vec<f32> {
+    let measure_count = chart.tick_to_measure(duration) as usize + 1;
+    let measure_starts = measure_starts(chart, measure_count);
+    let mut notes_per_measure = vec![0usize; measure_count];
+    for tick in generate_score_ticks(chart) {
+        if let ScoreTick::Chip { .. } | ScoreTick::Slam { .. } = tick.tick {
+            let measure = (chart.tick_to_measure(tick.y) as usize).min(measure_count - 1);
+            notes_per_measure[measure] += 1;
+        }
+    }
+
+    // Normalize by measure length so odd time signatures don't stand out
+    let per_beat: Vec<f32> = notes_per_measure
+        .iter()
+        .enumerate()
+        .map(|(measure, notes)| {
+            let start = measure_starts[measure];
+            let end = measure_starts[measure + 1];
+            *notes as f32 * KSON_RESOLUTION as f32 / (end.saturating_sub(start)).max(1) as f32
+        })
+        .collect();
+    let max = per_beat.iter().copied().fold(f32::EPSILON, f32::max);
+
+    (0..=duration / KSON_RESOLUTION)
+        .map(|beat| {
+            let measure = chart.tick_to_measure(beat * KSON_RESOLUTION) as usize;
+            per_beat.get(measure).copied().unwrap_or_default() / max
+        })
+        .collect()
+}
+
+fn measure_starts(chart: &Chart, measure_count: usize) -> Vec<u32> {
+    (0..=measure_count as u32)
+        .map(|m| chart.measure_to_tick(m))
+        .collect()
+}
+
+impl ChartScrubber {
+    pub fn new(chart: &Chart, duration: u32) -> Self {
+        let measure_count = chart.tick_to_measure(duration) as usize + 1;
+        Self {
+            density: beat_density(chart, duration),
+            texture: None,
+            bpm_changes: chart.beat.bpm.iter().skip(1).map(|(y, _)| *y).collect(),
+            lasers: [0, 1].map(|side| {
+                chart.note.laser[side]
+                    .iter()
+                    .map(|s| (s.tick(), s.tick() + s.last().map(|p| p.ry).unwrap_or(0)))
+                    .collect()
+            }),
+            measure_starts: measure_starts(chart, measure_count),
+            duration: duration.max(1),
+        }
+    }
+
+    fn measure(&self, tick: u32) -> usize {
+        self.measure_starts.partition_point(|start| *start <= tick)
+    }
+
+    fn texture(&mut self, ui: &Ui) -> &TextureHandle {
+        let density = &self.density;
+        self.texture.get_or_insert_with(|| {
+            let pixels = density
+                .iter()
+                .map(|d| {
+                    let d = d.clamp(0.0, 1.0);
+                    Color32::from_rgb(
+                        (40.0 + 215.0 * d) as u8,
+                        (40.0 + 120.0 * d) as u8,
+                        (60.0 * (1.0 - d)) as u8,
+                    )
+                })
+                .collect();
+            let image = ColorImage {
+                size: [density.len().max(1), 1],
+                pixels,
+            };
+            ui.ctx()
+                .load_texture("chart_density", image, TextureOptions::LINEAR)
+        })
+    }
+
+    /// Shows the scrubber, left click or drag to seek, right click to set the loop start and
+    /// shift + right click to set the loop end.
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        current_tick: u32,
+        loop_region: Option<(u32, u32)>,
+    ) -> Option<ScrubberAction> {
+        let width = ui.available_width().max(100.0);
+        let response = ui.allocate_response(vec2(width, STRIP_HEIGHT), Sense::click_and_drag());
+        let rect = response.rect;
+        let duration = self.duration;
+        let tick_x = |tick: u32| rect.left() + rect.width() * (tick as f32 / duration as f32);
+        let painter = ui.painter_at(rect);
+
+        let texture = self.texture(ui).id();
+        painter.image(
+            texture,
+            rect,
+            Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+            Color32::WHITE,
+        );
+
+        if let Some((start, end)) = loop_region {
+            painter.rect_filled(
+                Rect::from_x_y_ranges(tick_x(start)..=tick_x(end), rect.y_range()),
+                0.0,
+                Color32::from_rgba_unmultiplied(0, 255, 0, 40),
+            );
+        }
+
+        for (side, sections) in self.lasers.iter().enumerate() {
+            let top = rect.top() + side as f32 * LASER_HEIGHT;
+            for (start, end) in sections {
+                painter.rect_filled(
+                    Rect::from_x_y_ranges(tick_x(*start)..=tick_x(*end), top..=top + LASER_HEIGHT),
+                    0.0,
+                    LASER_COLORS[side],
+                );
+            }
+        }
+
+        for y in &self.bpm_changes {
+            painter.vline(
+                tick_x(*y),
+                rect.y_range(),
+                Stroke::new(1.0, Color32::YELLOW),
+            );
+        }
+
+        painter.vline(
+            tick_x(current_tick),
+            rect.y_range(),
+            Stroke::new(2.0, Color32::WHITE),
+        );
+
+        let pointer_tick = response.interact_pointer_pos().map(|p| {
+            (((p.x - rect.left()) / rect.width()).clamp(0.0, 1.0) * duration as f32) as u32
+        });
+
+        let action = match pointer_tick {
+            Some(tick) if response.secondary_clicked() => {
+                if ui.input(|i| i.modifiers.shift) {
+                    Some(ScrubberAction::SetLoopEnd(tick))
+                } else {
+                    Some(ScrubberAction::SetLoopStart(tick))
+                }
+            }
+            Some(tick) if response.clicked() || response.dragged() => {
+                Some(ScrubberAction::Seek(tick))
+            }
+            _ => None,
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "Measure {} / {}",
+                self.measure(current_tick),
+                self.measure(duration)
+            ));
+            if let Some((start, end)) = loop_region {
+                ui.label(format!(
+                    "Loop: measure {} - {}",
+                    self.measure(start),
+                    self.measure(end)
+                ));
+            }
+            if ui.button("Clear loop").clicked() {
+                Some(ScrubberAction::ClearLoop)
+            } else {
+                None
+            }
+        })
+        .inner
+        .or(action)
+    }
+}
@@ -0,0 +1,205 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use kson_rodio_sources::{
+    biquad::{biquad, BiQuadState, BiQuadType},
+    noise::NoiseSource,
+    owned_source::{owned_source, Marker},
+};
+use rand::Rng;
+use rodio::{dynamic_mixer::DynamicMixerController, Decoder, Source};
+
+use crate::{
+    audio::{mixer_sample_rate, MixerExt},
+    config::GameConfig,
+};
+
+/// Largest random pitch offset of a slam sample, in semitones
+const PITCH_VARIANCE: f32 = 0.5;
+const TAIL_LENGTH: Duration = Duration::from_millis(120);
+const TAIL_AMPLITUDE: f32 = 0.35;
+/// High pass cutoff at the start and end of a tail for a slam to the right, reversed for the left
+const TAIL_CUTOFF: (f32, f32) = (800.0, 6000.0);
+/// Samples between cutoff updates while rendering a tail
+const TAIL_STEP: usize = 32;
+
+/// Decoded audio that can be played any number of times without decoding or copying it again
+#[derive(Clone)]
+struct SampleBuffer {
+    samples: Arc<[f32]>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl SampleBuffer {
+    fn decode(path: &Path) -> anyhow::Result<Self> {
+        let decoder = Decoder::new(std::fs::File::open(path)?)?;
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+        Ok(Self {
+            samples: decoder.convert_samples::<f32>().collect(),
+            channels,
+            sample_rate,
+        })
+    }
+
+    /// High pass filtered noise with a cutoff sweeping from `from` to `to`, fading out
+    fn noise_tail(sample_rate: u32, from: f32, to: f32) -> Self {
+        let len = (TAIL_LENGTH.as_secs_f32() * sample_rate as f32) as usize;
+        let state =
+            |t: f32| BiQuadState::new(BiQuadType::HighPass, 0.7, from * (to / from).powf(t));
+        let mut filter = biquad(
+            NoiseSource::new(sample_rate, TAIL_AMPLITUDE, 1),
+            state(0.0),
+            None,
+        );
+
+        let samples = (0..len)
+            .map(|i| {
+                let t = i as f32 / len as f32;
+                if i % TAIL_STEP == 0 {
+                    filter.update(state(t));
+                }
+                filter.next().unwrap_or_default() * (1.0 - t).powi(2)
+            })
+            .collect();
+
+        Self {
+            samples,
+            channels: 1,
+            sample_rate,
+        }
+    }
+}
+
+/// Plays a [`SampleBuffer`] at the mixer rate, stepping through it at a variable speed
+struct Voice {
+    buffer: SampleBuffer,
+    sample_rate: u32,
+    step: f64,
+    position: f64,
+    channel: u16,
+}
+
+impl Voice {
+    fn new(buffer: SampleBuffer, sample_rate: u32, speed: f32) -> Self {
+        Self {
+            step: speed as f64 * buffer.sample_rate as f64 / sample_rate as f64,
+            buffer,
+            sample_rate,
+            position: 0.0,
+            channel: 0,
+        }
+    }
+}
+
+impl Iterator for Voice {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let channels = self.buffer.channels as usize;
+        let i = self.position as usize * channels + self.channel as usize;
+        let a = *self.buffer.samples.get(i)?;
+        let b = self
+            .buffer
+            .samples
+            .get(i + channels)
+            .copied()
+            .unwrap_or(0.0);
+        let t = self.position.fract() as f32;
+
+        self.channel += 1;
+        if self.channel == self.buffer.channels {
+            self.channel = 0;
+            self.position += self.step;
+        }
+
+        Some(a + (b - a) * t)
+    }
+}
+
+impl Source for Voice {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.buffer.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Sound played when a slam is hit, everything is decoded and rendered up front so slams only
+/// add a couple of sources to the mixer
+pub struct SlamSound {
+    sample: Option<SampleBuffer>,
+    /// Noise tails for slams to the left and right
+    tails: Option<[SampleBuffer; 2]>,
+    pitch_variance: bool,
+    volume: f32,
+    sample_rate: u32,
+}
+
+impl SlamSound {
+    pub fn new(sample_path: &Path) -> Self {
+        let config = GameConfig::get();
+        let sample_rate = mixer_sample_rate();
+        let sample = match SampleBuffer::decode(sample_path) {
+            Ok(sample) => Some(sample),
+            Err(e) => {
+                log::warn!("Could not load slam sample {}: {e}", sample_path.display());
+                None
+            }
+        };
+
+        let (low, high) = TAIL_CUTOFF;
+        Self {
+            sample,
+            tails: config.slam_tail.then(|| {
+                [
+                    SampleBuffer::noise_tail(sample_rate, high, low),
+                    SampleBuffer::noise_tail(sample_rate, low, high),
+                ]
+            }),
+            pitch_variance: config.slam_pitch_variance,
+            volume: config.slam_volume,
+            sample_rate,
+        }
+    }
+
+    /// Stops playing anything, for players without audio of their own
+    pub fn mute(&mut self) {
+        self.sample = None;
+        self.tails = None;
+    }
+
+    /// Plays the slam sample and tail, `direction` is the signed width of the slam
+    pub fn play(&self, mixer: &DynamicMixerController<f32>, owner: &Marker, direction: f64) {
+        if let Some(sample) = &self.sample {
+            let speed = if self.pitch_variance {
+                let semitones = rand::thread_rng().gen_range(-PITCH_VARIANCE..=PITCH_VARIANCE);
+                2f32.powf(semitones / 12.0)
+            } else {
+                1.0
+            };
+            mixer.add_resampled(owned_source(
+                Voice::new(sample.clone(), self.sample_rate, speed).amplify(self.volume),
+                owner,
+            ));
+        }
+
+        if let Some(tails) = &self.tails {
+            let tail = tails[(direction > 0.0) as usize].clone();
+            mixer.add_resampled(owned_source(
+                Voice::new(tail, self.sample_rate, 1.0).amplify(self.volume),
+                owner,
+            ));
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use luals_gen::ToLuaLsType;
+use serde::{Deserialize, Serialize};
+
+use crate::skin_settings::SkinSettingValue;
+
+/// Sizes of the track and the objects on it, in track units where the default track is 1 wide.
+/// Skins can override every value through their settings, see [`TrackLayout::from_skin`]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, ToLuaLsType)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackLayout {
+    /// Number of BT lanes
+    pub lanes: u32,
+    pub lane_width: f32,
+    pub fx_width: f32,
+    /// Distance between a laser at 0 and a laser at 1
+    pub laser_width: f32,
+    pub chip_height: f32,
+}
+
+impl Default for TrackLayout {
+    fn default() -> Self {
+        Self::new(4, 1.0 / 6.0)
+    }
+}
+
+impl TrackLayout {
+    /// Layout with FX lanes covering two BT lanes and lasers reaching one lane past the BT lanes
+    pub fn new(lanes: u32, lane_width: f32) -> Self {
+        Self {
+            lanes,
+            lane_width,
+            fx_width: lane_width * lanes as f32 / 2.0,
+            laser_width: lane_width * (lanes + 1) as f32,
+            chip_height: lane_width,
+        }
+    }
+
+    /// Reads `track_lanes`, `track_lane_width`, `track_fx_width`, `track_laser_width` and
+    /// `track_chip_height` from the skin settings, `chip_aspect` is used for the chip height when
+    /// the skin doesn't set it
+    pub fn from_skin(settings: &HashMap<String, SkinSettingValue>, chip_aspect: f32) -> Self {
+        let float = |key: &str| match settings.get(key) {
+            Some(SkinSettingValue::Float(v)) if *v > 0.0 => Some(*v as f32),
+            Some(SkinSettingValue::Integer(v)) if *v > 0 => Some(*v as f32),
+            _ => None,
+        };
+
+        let lanes = match settings.get("track_lanes") {
+            Some(SkinSettingValue::Integer(v)) if *v > 0 => *v as u32,
+            _ => 4,
+        };
+        let base = Self::new(lanes, float("track_lane_width").unwrap_or(1.0 / 6.0));
+
+        Self {
+            fx_width: float("track_fx_width").unwrap_or(base.fx_width),
+            laser_width: float("track_laser_width").unwrap_or(base.laser_width),
+            chip_height: float("track_chip_height").unwrap_or(base.lane_width * chip_aspect),
+            ..base
+        }
+    }
+
+    pub fn track_width(&self) -> f32 {
+        self.laser_width + self.lane_width
+    }
+
+    /// Center of a BT lane relative to the center of the track
+    pub fn bt_x(&self, lane: usize) -> f32 {
+        self.lane_width * (lane as f32 - (self.lanes as f32 - 1.0) / 2.0)
+    }
+
+    /// Center of an FX lane relative to the center of the track
+    pub fn fx_x(&self, side: usize) -> f32 {
+        self.fx_width * (side as f32 - 0.5)
+    }
+
+    /// Center of a laser at `value` relative to the center of the track
+    pub fn laser_x(&self, value: f32, wide: u8) -> f32 {
+        (value - 0.5) * self.laser_width * wide.max(1) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::TrackLayout;
+    use crate::skin_settings::SkinSettingValue;
+
+    #[test]
+    fn default_layout() {
+        let layout = TrackLayout::default();
+        assert!((layout.track_width() - 1.0).abs() < 1e-6);
+        assert!((layout.bt_x(0) + 1.5 / 6.0).abs() < 1e-6);
+        assert!((layout.fx_x(1) - 1.0 / 6.0).abs() < 1e-6);
+        assert!((layout.laser_x(0.0, 1) + 2.5 / 6.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn skin_layout() {
+        let settings = HashMap::from([
+            ("track_lanes".to_string(), SkinSettingValue::Integer(5)),
+            ("track_lane_width".to_string(), SkinSettingValue::Float(0.2)),
+            (
+                "track_chip_height".to_string(),
+                SkinSettingValue::Float(0.1),
+            ),
+        ]);
+        let layout = TrackLayout::from_skin(&settings, 0.5);
+        assert_eq!(layout.lanes, 5);
+        assert!((layout.track_width() - 1.4).abs() < 1e-6);
+        assert!((layout.chip_height - 0.1).abs() < 1e-6);
+        assert!(layout.bt_x(2).abs() < 1e-6);
+
+        let layout = TrackLayout::from_skin(&HashMap::new(), 0.5);
+        assert_eq!(layout.lanes, 4);
+        assert!((layout.chip_height - 1.0 / 12.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,93 @@
+use femtovg::{renderer::OpenGl, Canvas, Color, ImageFlags, ImageId, PixelFormat, RenderTarget};
+use three_d::{vec2, vec3, Camera, RenderTarget as TdRenderTarget, Viewport};
+
+use crate::{config::GameConfig, shaded_mesh::ShadedMesh};
+
+use super::graphics;
+
+/// Part of the track covered by the overlay, in track lengths from the crit line towards the
+/// far end of the track
+const START: f32 = -0.0625;
+const END: f32 = 0.25;
+
+/// Canvas drawn by the skin's `render_track_overlay` and mapped onto the track plane
+pub struct TrackOverlay {
+    mesh: ShadedMesh,
+    image: Option<(ImageId, (usize, usize))>,
+}
+
+impl TrackOverlay {
+    pub fn new(
+        context: &three_d::Context,
+        track_length: f32,
+        track_width: f32,
+    ) -> anyhow::Result<Self> {
+        let mut mesh = ShadedMesh::new_from_source(
+            context,
+            include_str!("../static_assets/track_overlay.vs"),
+            include_str!("../static_assets/track_overlay.fs"),
+        )?;
+        mesh.set_data_mesh(&graphics::xy_rect(
+            vec3(0.0, -(START + END) / 2.0 * track_length, 0.0),
+            vec2(track_width, (END - START) * track_length),
+        ));
+
+        Ok(Self { mesh, image: None })
+    }
+
+    /// Size of the canvas for `viewport`, the width is the resolution setting relative to the
+    /// shorter side of the viewport and the height keeps the aspect of the covered track
+    pub fn canvas_size(viewport: Viewport, track_length: f32, track_width: f32) -> (usize, usize) {
+        let scale = GameConfig::get().graphics.track_overlay_resolution;
+        let width = (viewport.height.min(viewport.width) as f32 * scale).max(1.0);
+        let height = width * (END - START) * track_length / track_width;
+        (width as usize, height as usize)
+    }
+
+    /// Points the canvas at the overlay image, recreated when `size` changed
+    pub fn begin(
+        &mut self,
+        canvas: &mut Canvas<OpenGl>,
+        size: (usize, usize),
+    ) -> anyhow::Result<()> {
+        let image = match self.image {
+            Some((image, image_size)) if image_size == size => image,
+            old => {
+                if let Some((old, _)) = old {
+                    canvas.delete_image(old);
+                }
+                let image = canvas.create_image_empty(
+                    size.0,
+                    size.1,
+                    PixelFormat::Rgba8,
+                    ImageFlags::FLIP_Y | ImageFlags::PREMULTIPLIED,
+                )?;
+                self.image = Some((image, size));
+                image
+            }
+        };
+
+        canvas.flush();
+        canvas.set_render_target(RenderTarget::Image(image));
+        canvas.reset();
+        canvas.clear_rect(0, 0, size.0 as _, size.1 as _, Color::rgba(0, 0, 0, 0));
+        Ok(())
+    }
+
+    /// Finishes drawing to the overlay image and points the canvas back at the screen
+    pub fn end(&mut self, canvas: &mut Canvas<OpenGl>) -> anyhow::Result<()> {
+        canvas.flush();
+        canvas.set_render_target(RenderTarget::Screen);
+        if let Some((image, _)) = self.image {
+            self.mesh
+                .set_param("mainTex", canvas.get_native_texture(image)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn render(&self, target: &TdRenderTarget, camera: &Camera) {
+        if self.image.is_some() {
+            target.render(camera, [&self.mesh], &[]);
+        }
+    }
+}
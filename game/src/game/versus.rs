@@ -0,0 +1,251 @@
+use std::{
+    collections::HashMap,
+    sync::mpsc::{channel, Receiver, Sender},
+    time::SystemTime,
+};
+
+use anyhow::anyhow;
+use di::{RefMut, ServiceProvider};
+use game_loop::winit::event::{ElementState, Event};
+use rodio::Source;
+use three_d::Viewport;
+
+use super::{Game, GameData};
+use crate::{
+    button_codes::{LaserState, UscButton, UscInputEvent},
+    config::GameConfig,
+    game_main::{AutoPlay, GameResult},
+    input_state::InputSource,
+    scene::{Scene, SceneData},
+    screen_space::PixelSize,
+    vg_ui::Vgfx,
+    ControlMessage,
+};
+
+/// Two players playing the same chart side by side
+pub struct VersusData {
+    players: [GameData; 2],
+}
+
+impl VersusData {
+    pub fn new(player1: GameData) -> Self {
+        let player2 = GameData {
+            song: player1.song.clone(),
+            diff_idx: player1.diff_idx,
+            chart: player1.chart.clone(),
+            skin_folder: player1.skin_folder.clone(),
+            audio: (Box::new(rodio::source::Zero::<f32>::new(1, 44100))
+                as Box<dyn Source<Item = f32> + Send>)
+                .buffered(),
+            swap_audio: HashMap::new(),
+            autoplay: AutoPlay::None,
+            chart_path: None,
+            retries: 0,
+        };
+
+        Self {
+            players: [player1, player2],
+        }
+    }
+}
+
+impl SceneData for VersusData {
+    fn make_scene(
+        self: Box<Self>,
+        service_provider: ServiceProvider,
+    ) -> anyhow::Result<Box<dyn Scene>> {
+        let [player1, player2] = self.players;
+        let mut player1 = player1.into_game(service_provider.create_scope())?;
+        let mut player2 = player2.into_game(service_provider.create_scope())?;
+        // Restarting one side would leave the players out of sync
+        player1.quick_retry = None;
+        player2.quick_retry = None;
+        player2.player = 1;
+        player2.play_audio = false;
+        player2.slam_sound.mute();
+        player2.input_state = player2.input_state.detached();
+
+        let (result_tx, result_rx) = channel();
+        Ok(Box::new(Versus {
+            players: [player1, player2],
+            results: [None, None],
+            results_sent: false,
+            result_tx,
+            result_rx,
+            control_tx: None,
+            game_data: service_provider.get_required_mut(),
+            vgfx: service_provider.get_required_mut(),
+        }))
+    }
+}
+
+pub struct Versus {
+    players: [Game; 2],
+    results: [Option<GameResult>; 2],
+    results_sent: bool,
+    result_tx: Sender<ControlMessage>,
+    result_rx: Receiver<ControlMessage>,
+    control_tx: Option<Sender<ControlMessage>>,
+    game_data: RefMut<crate::game_data::GameData>,
+    vgfx: RefMut<Vgfx>,
+}
+
+impl Scene for Versus {
+    fn init(&mut self, app_control_tx: Sender<ControlMessage>) -> anyhow::Result<()> {
+        for player in &mut self.players {
+            player.init(self.result_tx.clone())?;
+        }
+        self.control_tx = Some(app_control_tx);
+        Ok(())
+    }
+
+    fn tick(&mut self, dt: f64, knob_state: LaserState) -> anyhow::Result<()> {
+        let [player1, player2] = &mut self.players;
+        player1.tick(dt, knob_state)?;
+        // Player 2 has no audio to sync to so it follows player 1
+        player2.zero_time = player1.zero_time;
+        player2.intro_done = player1.intro_done;
+        player2.tick(dt, knob_state)?;
+
+        for message in self.result_rx.try_iter() {
+            if let ControlMessage::Result(result) = message {
+                // A failed player sends its results again at the end of the chart
+                if let Some(slot) = self.results.get_mut(result.player as usize) {
+                    slot.get_or_insert(result);
+                }
+            }
+        }
+
+        if !self.results_sent && self.results.iter().all(Option::is_some) {
+            self.results_sent = true;
+            self.control_tx
+                .as_ref()
+                .ok_or(anyhow!("control_tx not set"))?
+                .send(ControlMessage::VersusResult(
+                    self.results.iter_mut().filter_map(Option::take).collect(),
+                ))
+                .expect("Main loop messaging error");
+        }
+
+        Ok(())
+    }
+
+    fn render(
+        &mut self,
+        dt: f64,
+        td_context: &three_d::Context,
+        target: &mut three_d::RenderTarget,
+        viewport: Viewport,
+    ) {
+        // Players are side by side on the rotated display
+        let rotation = GameConfig::get().graphics.rotation;
+        let logical = rotation.logical_viewport(viewport);
+        let half = logical.width / 2;
+        let areas = [
+            Viewport {
+                width: half,
+                ..logical
+            },
+            Viewport {
+                x: logical.x + half as i32,
+                width: logical.width - half,
+                ..logical
+            },
+        ];
+
+        // Scripts of each player only see their own half of the screen
+        let screen = self.game_data.read().expect("Lock error").screen;
+        for (player, area) in self.players.iter_mut().zip(areas) {
+            self.game_data
+                .write()
+                .expect("Lock error")
+                .set_screen(screen.area(
+                    (area.x - logical.x) as f64,
+                    0.0,
+                    PixelSize::new(area.width, area.height),
+                ));
+            self.vgfx.write().expect("Lock error").set_viewport(Some((
+                (area.x - logical.x) as f32,
+                0.0,
+                area.width as f32,
+                area.height as f32,
+            )));
+            player.reset_canvas();
+            player.render(
+                dt,
+                td_context,
+                target,
+                rotation.physical_viewport(area, viewport),
+            );
+        }
+
+        self.game_data
+            .write()
+            .expect("Lock error")
+            .set_screen(screen);
+        self.vgfx.write().expect("Lock error").set_viewport(None);
+        self.players[0].reset_canvas();
+    }
+
+    fn render_ui(&mut self, _dt: f64) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_event(&mut self, event: &Event<UscInputEvent>) {
+        let [player1, player2] = &mut self.players;
+        let Event::UserEvent(UscInputEvent::Player2(event)) = event else {
+            player1.on_event(event);
+            return;
+        };
+
+        if !player2.input_state.update(event, InputSource::Controller) {
+            return;
+        }
+        match event.as_ref() {
+            UscInputEvent::Button(button, ElementState::Pressed, timestamp) => {
+                player2.on_button_pressed(*button, *timestamp)
+            }
+            UscInputEvent::Button(button, ElementState::Released, timestamp) => {
+                player2.on_button_released(*button, *timestamp)
+            }
+            _ => {}
+        }
+        player2.on_event(&Event::UserEvent(event.as_ref().clone()));
+    }
+
+    fn on_button_pressed(&mut self, button: UscButton, timestamp: SystemTime) {
+        self.players[0].on_button_pressed(button, timestamp);
+    }
+
+    fn on_button_released(&mut self, button: UscButton, timestamp: SystemTime) {
+        self.players[0].on_button_released(button, timestamp);
+    }
+
+    fn suspend(&mut self) {
+        for player in &mut self.players {
+            player.suspend();
+        }
+    }
+
+    fn resume(&mut self) {
+        for player in &mut self.players {
+            player.resume();
+        }
+    }
+
+    fn is_suspended(&self) -> bool {
+        self.players[0].is_suspended()
+    }
+
+    fn debug_ui(&mut self, ctx: &egui::Context) -> anyhow::Result<()> {
+        self.players[0].debug_ui(ctx)
+    }
+
+    fn closed(&self) -> bool {
+        self.players.iter().any(|p| p.closed)
+    }
+
+    fn name(&self) -> &str {
+        "Versus"
+    }
+}
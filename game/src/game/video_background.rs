@@ -0,0 +1,429 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{sync_channel, Receiver, Sender, SyncSender, TryRecvError, TrySendError},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use log::warn;
+use three_d::{context::HasContext, Viewport};
+
+use crate::shaded_mesh::ShadedMesh;
+
+/// Decoded frames waiting to be shown, the decoder waits when the renderer is this far behind
+const FRAME_BUFFER: usize = 8;
+/// Frames are downscaled to at most this width before being sent to the renderer
+const MAX_WIDTH: u32 = 1280;
+/// Jumps ahead of the decoder by more than this seek instead of decoding every frame in between
+const MAX_SKIP: Duration = Duration::from_secs(2);
+
+pub struct VideoFrame {
+    /// Presentation time from the start of the video
+    time: Duration,
+    width: u32,
+    height: u32,
+    /// Tightly packed RGBA rows, top to bottom
+    data: Vec<u8>,
+    /// Seek the frame was decoded after
+    generation: u32,
+}
+
+pub trait VideoDecoder: Send {
+    /// Next frame in presentation order, `None` at the end of the video
+    fn next_frame(&mut self) -> Result<Option<VideoFrame>>;
+    fn seek(&mut self, time: Duration) -> Result<()>;
+}
+
+enum Control {
+    Seek(Duration, u32),
+}
+
+/// Background video of a chart, decoded on a worker thread and drawn behind the track
+pub struct VideoBackground {
+    context: three_d::Context,
+    mesh: ShadedMesh,
+    frames: Receiver<VideoFrame>,
+    control: Sender<Control>,
+    generation: u32,
+    /// Earliest frame that isn't due yet
+    next: Option<VideoFrame>,
+    texture: Option<(three_d::context::NativeTexture, (u32, u32))>,
+    /// Time of the video at the last render
+    time: Option<Duration>,
+    /// Chart time the video starts at in milliseconds
+    offset: f64,
+}
+
+impl VideoBackground {
+    pub fn new(context: &three_d::Context, path: &Path, offset_ms: i32) -> Result<Self> {
+        let decoder = open_decoder(path)?;
+        let (frame_tx, frames) = sync_channel(FRAME_BUFFER);
+        let (control, control_rx) = std::sync::mpsc::channel();
+
+        std::thread::Builder::new()
+            .name("video background".into())
+            .spawn(move || decode(decoder, frame_tx, control_rx))?;
+
+        let mesh = ShadedMesh::new_fullscreen(
+            context,
+            include_str!("../static_assets/video_background.fs"),
+        )?;
+
+        Ok(Self {
+            context: context.clone(),
+            mesh,
+            frames,
+            control,
+            generation: 0,
+            next: None,
+            texture: None,
+            time: None,
+            offset: offset_ms as f64,
+        })
+    }
+
+    /// Shows the frame due at `chart_time` in milliseconds, frames that are already late are
+    /// skipped and going back in time or far ahead seeks the decoder
+    pub fn render(&mut self, chart_time: f64, viewport: Viewport) {
+        let Some(time) = Duration::try_from_secs_f64((chart_time - self.offset) / 1000.0).ok()
+        else {
+            return;
+        };
+
+        let jumped = self
+            .time
+            .is_some_and(|last| time < last || time > last + MAX_SKIP);
+        self.time = Some(time);
+        if jumped {
+            self.generation = self.generation.wrapping_add(1);
+            self.next = None;
+            _ = self.control.send(Control::Seek(time, self.generation));
+        }
+
+        let mut due = None;
+        loop {
+            let frame = match self.next.take() {
+                Some(frame) => frame,
+                None => match self.frames.try_recv() {
+                    Ok(frame) => frame,
+                    Err(_) => break,
+                },
+            };
+
+            if frame.generation != self.generation {
+                continue;
+            }
+            if frame.time > time {
+                self.next = Some(frame);
+                break;
+            }
+            due = Some(frame);
+        }
+
+        if let Some(frame) = due {
+            if let Err(e) = self.upload(&frame) {
+                warn!("Could not upload video frame: {e}");
+            }
+        }
+
+        if let Some((texture, (width, height))) = self.texture {
+            self.mesh.set_param_if_declared("mainTex", texture);
+            self.mesh
+                .set_param_if_declared("videoSize", three_d::vec2(width as f32, height as f32));
+            self.mesh.draw_fullscreen(viewport);
+        }
+    }
+
+    fn upload(&mut self, frame: &VideoFrame) -> Result<()> {
+        use three_d::context::*;
+        let size = (frame.width, frame.height);
+        let context = &self.context;
+
+        unsafe {
+            match self.texture {
+                Some((texture, texture_size)) if texture_size == size => {
+                    context.bind_texture(TEXTURE_2D, Some(texture));
+                }
+                old => {
+                    if let Some((old, _)) = old {
+                        context.delete_texture(old);
+                    }
+                    let texture = context.create_texture().map_err(|e| anyhow!(e))?;
+                    context.bind_texture(TEXTURE_2D, Some(texture));
+                    context.tex_parameter_i32(TEXTURE_2D, TEXTURE_MIN_FILTER, LINEAR as i32);
+                    context.tex_parameter_i32(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR as i32);
+                    context.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_EDGE as i32);
+                    context.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_EDGE as i32);
+                    context.tex_image_2d(
+                        TEXTURE_2D,
+                        0,
+                        RGBA8 as i32,
+                        size.0 as i32,
+                        size.1 as i32,
+                        0,
+                        RGBA,
+                        UNSIGNED_BYTE,
+                        None,
+                    );
+                    self.texture = Some((texture, size));
+                }
+            }
+
+            context.tex_sub_image_2d(
+                TEXTURE_2D,
+                0,
+                0,
+                0,
+                size.0 as i32,
+                size.1 as i32,
+                RGBA,
+                UNSIGNED_BYTE,
+                PixelUnpackData::Slice(&frame.data),
+            );
+            context.bind_texture(TEXTURE_2D, None);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for VideoBackground {
+    fn drop(&mut self) {
+        if let Some((texture, _)) = self.texture.take() {
+            unsafe { self.context.delete_texture(texture) }
+        }
+    }
+}
+
+/// Runs until the `VideoBackground` is dropped or decoding fails, waits while the frame buffer
+/// is full so the renderer never blocks on the decoder
+fn decode(
+    mut decoder: Box<dyn VideoDecoder>,
+    frames: SyncSender<VideoFrame>,
+    control: Receiver<Control>,
+) {
+    let mut generation = 0;
+    let mut pending = None;
+
+    loop {
+        let command = match pending.take() {
+            Some(command) => Some(command),
+            None => match control.try_recv() {
+                Ok(command) => Some(command),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return,
+            },
+        };
+
+        if let Some(Control::Seek(time, seek_generation)) = command {
+            generation = seek_generation;
+            if let Err(e) = decoder.seek(time) {
+                warn!("Video seek failed: {e}");
+            }
+        }
+
+        let mut frame = match decoder.next_frame() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => match control.recv() {
+                // Nothing left to decode until the video is seeked
+                Ok(command) => {
+                    pending = Some(command);
+                    continue;
+                }
+                Err(_) => return,
+            },
+            Err(e) => {
+                warn!("Video decoding failed, showing the static background: {e}");
+                return;
+            }
+        };
+        frame.generation = generation;
+
+        loop {
+            match frames.try_send(frame) {
+                Ok(()) => break,
+                Err(TrySendError::Full(waiting)) => {
+                    frame = waiting;
+                    match control.recv_timeout(Duration::from_millis(5)) {
+                        Ok(command) => {
+                            // The waiting frame is from before the seek
+                            pending = Some(command);
+                            break;
+                        }
+                        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+                        Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                Err(TrySendError::Disconnected(_)) => return,
+            }
+        }
+    }
+}
+
+/// Size of frames sent to the renderer for a video of `width` x `height`
+fn output_size(width: u32, height: u32) -> (u32, u32) {
+    if width <= MAX_WIDTH {
+        return (width, height);
+    }
+    let height = (height as u64 * MAX_WIDTH as u64 / width as u64).max(1) as u32;
+    (MAX_WIDTH, height)
+}
+
+#[cfg(feature = "video")]
+fn open_decoder(path: &Path) -> Result<Box<dyn VideoDecoder>> {
+    Ok(Box::new(ffmpeg::FfmpegDecoder::new(path)?))
+}
+
+#[cfg(not(feature = "video"))]
+fn open_decoder(path: &Path) -> Result<Box<dyn VideoDecoder>> {
+    anyhow::bail!(
+        "Can't play {}, video backgrounds are not enabled in this build",
+        path.display()
+    )
+}
+
+/// Video file of `chart` next to the chart file, charts don't know their folder so it's taken
+/// from the jacket which is next to the chart for local songs
+pub fn chart_video(chart: &kson::Chart, jacket_path: &Path) -> Option<(PathBuf, i32)> {
+    let movie = chart.bg.legacy.as_ref()?.movie.as_ref()?;
+    let filename = movie.filename.as_ref().filter(|f| !f.is_empty())?;
+    Some((jacket_path.with_file_name(filename), movie.offset))
+}
+
+#[cfg(feature = "video")]
+mod ffmpeg {
+    use std::{path::Path, time::Duration};
+
+    use anyhow::{anyhow, Result};
+    use ffmpeg_next::{
+        codec, format,
+        software::scaling::{self, Flags},
+        util::frame,
+        Packet, Rational,
+    };
+
+    use super::{output_size, VideoDecoder, VideoFrame};
+
+    pub struct FfmpegDecoder {
+        input: format::context::Input,
+        stream: usize,
+        time_base: Rational,
+        decoder: codec::decoder::Video,
+        scaler: scaling::Context,
+        size: (u32, u32),
+        eof: bool,
+    }
+
+    // The scaler and decoder are only used from the decoding thread
+    unsafe impl Send for FfmpegDecoder {}
+
+    impl FfmpegDecoder {
+        pub fn new(path: &Path) -> Result<Self> {
+            ffmpeg_next::init()?;
+            let input = format::input(&path)?;
+            let stream = input
+                .streams()
+                .best(ffmpeg_next::media::Type::Video)
+                .ok_or(anyhow!("No video stream"))?;
+            let index = stream.index();
+            let time_base = stream.time_base();
+            let decoder = codec::context::Context::from_parameters(stream.parameters())?
+                .decoder()
+                .video()?;
+
+            let size = output_size(decoder.width(), decoder.height());
+            let scaler = scaling::Context::get(
+                decoder.format(),
+                decoder.width(),
+                decoder.height(),
+                format::Pixel::RGBA,
+                size.0,
+                size.1,
+                Flags::BILINEAR,
+            )?;
+
+            Ok(Self {
+                input,
+                stream: index,
+                time_base,
+                decoder,
+                scaler,
+                size,
+                eof: false,
+            })
+        }
+
+        fn convert(&mut self, decoded: &frame::Video) -> Result<VideoFrame> {
+            let mut rgba = frame::Video::empty();
+            self.scaler.run(decoded, &mut rgba)?;
+
+            let row = self.size.0 as usize * 4;
+            let stride = rgba.stride(0);
+            let data = rgba
+                .data(0)
+                .chunks(stride)
+                .take(self.size.1 as usize)
+                .flat_map(|line| &line[..row])
+                .copied()
+                .collect();
+
+            let pts = decoded.timestamp().or(decoded.pts()).unwrap_or(0).max(0);
+            let seconds = pts as f64 * self.time_base.numerator() as f64
+                / self.time_base.denominator().max(1) as f64;
+
+            Ok(VideoFrame {
+                time: Duration::from_secs_f64(seconds),
+                width: self.size.0,
+                height: self.size.1,
+                data,
+                generation: 0,
+            })
+        }
+    }
+
+    impl VideoDecoder for FfmpegDecoder {
+        fn next_frame(&mut self) -> Result<Option<VideoFrame>> {
+            loop {
+                let mut decoded = frame::Video::empty();
+                if self.decoder.receive_frame(&mut decoded).is_ok() {
+                    return self.convert(&decoded).map(Some);
+                }
+                if self.eof {
+                    return Ok(None);
+                }
+
+                let mut packet = Packet::empty();
+                match packet.read(&mut self.input) {
+                    Ok(()) if packet.stream() == self.stream => {
+                        self.decoder.send_packet(&packet)?
+                    }
+                    Ok(()) => {}
+                    Err(ffmpeg_next::Error::Eof) => {
+                        self.decoder.send_eof()?;
+                        self.eof = true;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+
+        fn seek(&mut self, time: Duration) -> Result<()> {
+            let timestamp = time.as_micros() as i64;
+            self.input.seek(timestamp, ..=timestamp)?;
+            self.decoder.flush();
+            self.eof = false;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{output_size, MAX_WIDTH};
+
+    #[test]
+    fn downscaled_to_max_width() {
+        assert_eq!(output_size(640, 360), (640, 360));
+        assert_eq!(output_size(MAX_WIDTH * 2, 1440), (MAX_WIDTH, 720));
+    }
+}
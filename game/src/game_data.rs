@@ -5,7 +5,7 @@ use std::{
     time::Duration,
 };
 
-use di::{Activator, InjectBuilder, Injectable};
+use di::{Activator, InjectBuilder, Injectable, RefMut};
 use egui::epaint::Hsva;
 use log::warn;
 use puffin::{ProfilerScope, ThreadProfiler};
@@ -13,28 +13,91 @@ use rodio::Source;
 use tealr::{
     mlu::{mlua::AppDataRef, TealData, UserDataProxy},
     mlu::{
-        mlua::{self},
+        mlua::{self, LuaSerdeExt},
         UserData,
     },
     SingleType, ToTypename,
 };
 
 use crate::{
-    button_codes::UscButton, config::GameConfig, help::add_lua_static_method,
-    input_state::InputState, skin_settings::SkinSettingValue, RuscMixer,
+    audio::MixerExt,
+    build_info,
+    button_codes::UscButton,
+    config::GameConfig,
+    help::add_lua_static_method,
+    input_state::InputState,
+    lighting::{LightingService, Rgb},
+    screen_space::ScreenSpace,
+    skin_settings::SkinSettingValue,
+    RuscMixer,
 };
 
 #[derive(UserData)]
 pub struct GameData {
+    /// Same as `screen.logical`, kept up to date for code written before [`ScreenSpace`]
+    #[deprecated(note = "use `screen.logical`")]
     pub resolution: (u32, u32),
+    /// Mouse position in skin coordinates, kept up to date for code written before [`ScreenSpace`]
+    #[deprecated(note = "use `screen.to_skin(window_mouse_pos)`")]
     pub mouse_pos: (f64, f64),
+    /// Where skins are drawn, changed with [`GameData::set_screen`]
+    pub screen: ScreenSpace,
+    /// Mouse position in window pixels
+    pub window_mouse_pos: (f64, f64),
+    /// False while the game window doesn't have focus
+    pub focused: bool,
     pub profile_stack: Vec<ProfilerScope>,
     pub input_state: InputState,
     pub audio_samples: HashMap<String, rodio::source::Buffered<rodio::Decoder<std::fs::File>>>,
     pub audio_sample_play_status: HashMap<String, Arc<AtomicUsize>>,
+    /// Set when the skin plays a sample, the game's menu sounds are skipped for that frame
+    pub skin_sample_played: bool,
+    /// Chart of the last single player game, to retry it from the result screen
+    pub last_played: Option<crate::game::GameData>,
+    pub lighting: RefMut<LightingService>,
+}
+
+impl GameData {
+    /// Changes where skins are drawn, e.g. to one player's half of the screen in versus
+    #[allow(deprecated)]
+    pub fn set_screen(&mut self, screen: ScreenSpace) {
+        self.screen = screen;
+        self.resolution = screen.logical.into();
+        self.mouse_pos = screen.to_skin(self.window_mouse_pos);
+    }
+
+    /// Mouse position in skin coordinates
+    pub fn skin_mouse_pos(&self) -> (f64, f64) {
+        self.screen.to_skin(self.window_mouse_pos)
+    }
+
+    /// Replaces the data of the last frame, keeping what lasts across frames
+    #[allow(deprecated)]
+    pub fn next_frame(
+        &mut self,
+        screen: ScreenSpace,
+        window_mouse_pos: (f64, f64),
+        input_state: InputState,
+    ) {
+        *self = GameData {
+            mouse_pos: screen.to_skin(window_mouse_pos),
+            resolution: screen.logical.into(),
+            screen,
+            window_mouse_pos,
+            profile_stack: std::mem::take(&mut self.profile_stack),
+            focused: input_state.window_focused(),
+            input_state,
+            audio_samples: std::mem::take(&mut self.audio_samples),
+            audio_sample_play_status: std::mem::take(&mut self.audio_sample_play_status),
+            skin_sample_played: self.skin_sample_played,
+            last_played: self.last_played.take(),
+            lighting: self.lighting.clone(),
+        };
+    }
 }
 
 impl Injectable for GameData {
+    #[allow(deprecated)]
     fn inject(lifetime: di::ServiceLifetime) -> di::InjectBuilder {
         InjectBuilder::new(
             Activator::new::<Self, Self>(
@@ -42,10 +105,16 @@ impl Injectable for GameData {
                     Arc::new(GameData {
                         resolution: (800, 600),
                         mouse_pos: (0.0, 0.0),
+                        screen: ScreenSpace::default(),
+                        window_mouse_pos: (0.0, 0.0),
+                        focused: true,
                         profile_stack: vec![],
                         input_state: InputState::clone(&sp.get_required()),
                         audio_samples: Default::default(),
                         audio_sample_play_status: Default::default(),
+                        skin_sample_played: false,
+                        last_played: None,
+                        lighting: sp.get_required_mut(),
                     })
                 },
                 |sp| {
@@ -53,10 +122,16 @@ impl Injectable for GameData {
                         GameData {
                             resolution: (800, 600),
                             mouse_pos: (0.0, 0.0),
+                            screen: ScreenSpace::default(),
+                            window_mouse_pos: (0.0, 0.0),
+                            focused: true,
                             profile_stack: vec![],
                             input_state: InputState::clone(&sp.get_required()),
                             audio_samples: Default::default(),
                             audio_sample_play_status: Default::default(),
+                            skin_sample_played: false,
+                            last_played: None,
+                            lighting: sp.get_required_mut(),
                         }
                         .into(),
                     )
@@ -67,6 +142,82 @@ impl Injectable for GameData {
     }
 }
 
+impl GameData {
+    /// Decodes a sample from the audio folder of the current skin, samples are only loaded once
+    pub fn load_skin_sample(&mut self, name: &str) -> anyhow::Result<()> {
+        if self.audio_samples.contains_key(name) {
+            return Ok(());
+        }
+        let config = GameConfig::get();
+
+        let mut folder = config.game_folder.clone();
+        folder.push("skins");
+        folder.push(&config.skin);
+        folder.push("audio");
+        folder.push(name);
+        if folder.extension().is_none() {
+            folder.set_extension("wav");
+        }
+
+        let file = std::fs::File::open(&folder)?;
+        let decoder = rodio::Decoder::new(file)?.buffered();
+        self.audio_samples.insert(name.to_string(), decoder);
+
+        Ok(())
+    }
+
+    /// Plays a loaded sample, stopping the sample if it was already playing
+    pub fn play_sample(&mut self, mixer: &RuscMixer, name: &str, do_loop: bool) {
+        let Some(sample) = self.audio_samples.get(name) else {
+            warn!("No sample named: {name}");
+            return;
+        };
+
+        let play_control = Arc::new(AtomicUsize::new(1));
+        let prev = self
+            .audio_sample_play_status
+            .insert(name.to_string(), play_control.clone());
+
+        if let Some(p) = prev {
+            p.store(0, std::sync::atomic::Ordering::SeqCst);
+        }
+
+        let to_play = sample.clone();
+        if do_loop {
+            mixer.add_resampled(
+                to_play
+                    .convert_samples()
+                    .repeat_infinite()
+                    .stoppable()
+                    .periodic_access(Duration::from_millis(10), move |x| {
+                        if play_control.load(std::sync::atomic::Ordering::SeqCst) == 0 {
+                            x.stop()
+                        }
+                    }),
+            )
+        } else {
+            let done_control = play_control.clone();
+            mixer.add_resampled(rodio::source::Done::new(
+                to_play.convert_samples().stoppable().periodic_access(
+                    Duration::from_millis(10),
+                    move |x| {
+                        if play_control.load(std::sync::atomic::Ordering::SeqCst) == 0 {
+                            x.stop()
+                        }
+                    },
+                ),
+                done_control,
+            ))
+        }
+    }
+
+    pub fn stop_sample(&mut self, name: &str) {
+        if let Some(status) = self.audio_sample_play_status.get(name) {
+            status.store(0, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+}
+
 impl ToTypename for GameData {
     fn to_typename() -> tealr::Type {
         tealr::Type::Single(SingleType {
@@ -79,13 +230,38 @@ impl ToTypename for GameData {
 impl TealData for GameData {
     fn add_methods<'lua, T: tealr::mlu::TealDataMethods<'lua, Self>>(methods: &mut T) {
         //GetMousePos
+        methods.document("Mouse position in the coordinates the skin draws with");
         add_lua_static_method(methods, "GetMousePos", |_, _game_data, _: ()| {
-            Ok(_game_data.mouse_pos)
+            Ok(_game_data.skin_mouse_pos())
         });
 
         //GetResolution
+        methods.document(
+            "Size of the area the skin draws in, already rotated with the display and only one \
+             player's half of the screen in versus",
+        );
         add_lua_static_method(methods, "GetResolution", |_, _game_data, _: ()| {
-            Ok(_game_data.resolution)
+            Ok(<(u32, u32)>::from(_game_data.screen.logical))
+        });
+
+        //GetAspect
+        methods.document("Width divided by height of the area the skin draws in");
+        add_lua_static_method(methods, "GetAspect", |_, _game_data, _: ()| {
+            Ok(_game_data.screen.aspect())
+        });
+
+        //IsFocused
+        add_lua_static_method(methods, "IsFocused", |_, _game_data, _: ()| {
+            Ok(_game_data.focused)
+        });
+
+        //IsFlashingReduced
+        methods.document(
+            "True if the player asked for fewer and softer flashes, skins should tone down full \
+             screen flashes and fast pulsing effects",
+        );
+        add_lua_static_method(methods, "IsFlashingReduced", |_, _game_data, _: ()| {
+            Ok(GameConfig::get().graphics.reduce_flashing)
         });
 
         //Log
@@ -142,31 +318,9 @@ impl TealData for GameData {
             "LoadSkinSample",
             |_, game_data, p: LoadSkinSampleParams| {
                 let LoadSkinSampleParams { name } = p;
-
-                if game_data.audio_samples.contains_key(&name) {
-                    return Ok(());
-                }
-                let config = GameConfig::get();
-
-                let mut folder = config.game_folder.clone();
-                folder.push("skins");
-                folder.push(&config.skin);
-                folder.push("audio");
-                folder.push(&name);
-                if folder.extension().is_none() {
-                    folder.set_extension("wav");
-                }
-
-                let file =
-                    std::fs::File::open(&folder).map_err(tealr::mlu::mlua::Error::external)?;
-
-                let decoder = rodio::Decoder::new(file)
-                    .map_err(tealr::mlu::mlua::Error::external)?
-                    .buffered();
-
-                game_data.audio_samples.insert(name, decoder);
-
-                Ok(())
+                game_data
+                    .load_skin_sample(&name)
+                    .map_err(tealr::mlu::mlua::Error::external)
             },
         );
 
@@ -182,51 +336,11 @@ impl TealData for GameData {
             |lua, game_data, p: PlaySampleParams| {
                 let PlaySampleParams { name, do_loop } = p;
 
-                let Some(sample) = game_data.audio_samples.get(&name) else {
-                    warn!("No sample named: {name}");
-                    return Ok(());
-                };
-
                 let mixer: AppDataRef<RuscMixer> = lua
                     .app_data_ref()
                     .ok_or(mlua::Error::external("Mixer app data not set"))?;
-
-                let play_control = Arc::new(AtomicUsize::new(1));
-                let prev = game_data
-                    .audio_sample_play_status
-                    .insert(name, play_control.clone());
-
-                if let Some(p) = prev {
-                    p.store(0, std::sync::atomic::Ordering::SeqCst);
-                }
-
-                let to_play = sample.clone();
-                if do_loop {
-                    mixer.add(
-                        to_play
-                            .convert_samples()
-                            .repeat_infinite()
-                            .stoppable()
-                            .periodic_access(Duration::from_millis(10), move |x| {
-                                if play_control.load(std::sync::atomic::Ordering::SeqCst) == 0 {
-                                    x.stop()
-                                }
-                            }),
-                    )
-                } else {
-                    let done_control = play_control.clone();
-                    mixer.add(rodio::source::Done::new(
-                        to_play.convert_samples().stoppable().periodic_access(
-                            Duration::from_millis(10),
-                            move |x| {
-                                if play_control.load(std::sync::atomic::Ordering::SeqCst) == 0 {
-                                    x.stop()
-                                }
-                            },
-                        ),
-                        done_control,
-                    ))
-                }
+                game_data.play_sample(&mixer, &name, do_loop);
+                game_data.skin_sample_played = true;
 
                 Ok(())
             },
@@ -242,11 +356,7 @@ impl TealData for GameData {
             "StopSample",
             |_, game_data, p: StopSampleParams| {
                 let StopSampleParams { name } = p;
-
-                game_data
-                    .audio_sample_play_status
-                    .entry(name)
-                    .and_modify(|x| x.store(0, std::sync::atomic::Ordering::SeqCst));
+                game_data.stop_sample(&name);
 
                 Ok(())
             },
@@ -325,9 +435,40 @@ impl TealData for GameData {
             },
         );
 
+        //SetButtonLight
+        tealr::mlu::create_named_parameters!(SetButtonLightParams with
+          button : usize,
+          r : u8,
+          g : u8,
+          b : u8,
+
+        );
+        add_lua_static_method(
+            methods,
+            "SetButtonLight",
+            |_, game_data, p: SetButtonLightParams| {
+                game_data
+                    .lighting
+                    .write()
+                    .expect("Lock error")
+                    .set_button_light(p.button, Rgb(p.r, p.g, p.b))
+                    .map_err(mlua::Error::external)
+            },
+        );
+
         //UpdateAvailable
         add_lua_static_method(methods, "UpdateAvailable", |_, _game_data, _: ()| Ok(()));
 
+        //GetVersion
+        add_lua_static_method(methods, "GetVersion", |_, _game_data, _: ()| {
+            Ok(build_info::version())
+        });
+
+        //GetFeatures
+        add_lua_static_method(methods, "GetFeatures", |lua, _game_data, _: ()| {
+            lua.to_value(&build_info::Features::current())
+        });
+
         //GetSkin
         add_lua_static_method(methods, "GetSkin", |_, _game_data, _: ()| {
             Ok(GameConfig::get().skin.clone())
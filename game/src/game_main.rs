@@ -1,12 +1,11 @@
 use std::{
     num::NonZeroU32,
-    ops::{Add, Sub},
     rc::Rc,
     sync::{
         mpsc::{channel, Receiver, Sender},
         Arc, RwLock,
     },
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
 use di::{RefMut, ServiceProvider};
@@ -14,10 +13,10 @@ use egui_glow::EguiGlow;
 use femtovg::Paint;
 use game_loop::winit::{
     dpi::{PhysicalPosition, PhysicalSize},
-    event,
+    event::ElementState,
     keyboard::{Key, NamedKey},
     platform::modifier_supplement::KeyEventExtModifierSupplement,
-    window::Window,
+    window::{CursorGrabMode, Theme, Window},
 };
 
 use glutin::{
@@ -34,22 +33,33 @@ use femtovg as vg;
 use three_d as td;
 
 use crate::{
-    button_codes::{LaserState, UscInputEvent},
+    audio,
+    button_codes::{offset_timestamp, ActionKind, LaserState, UscButton, UscInputEvent},
     companion_interface::{self},
-    config::{Fullscreen, GameConfig},
+    config::{Fullscreen, GameConfig, InputDevice},
+    control_dispatch::{ControlDispatcher, SceneCommand},
+    fallback_skin,
+    frame_graph::FrameGraph,
     game::{gauge::Gauge, HitRating},
     game_data::GameData,
     help,
-    input_state::InputState,
+    input_state::{InputSource, InputState},
+    input_thread::InputPoller,
+    lighting,
+    lock_recovery::RecoverLock,
     lua_http::LuaHttp,
+    lua_sandbox,
     lua_service::LuaProvider,
     main_menu::MainMenuButton,
+    menu_audio::{MenuAudio, NavSamples},
+    resource_counters::ResourceCounters,
     scene,
-    settings_screen::SettingsScreen,
+    screen_space::{PixelSize, ScreenSpace},
+    service_menu::{self, ServiceAction, ServiceMenu},
     song_provider, songselect,
-    transition::Transition,
     util::lua_address,
     vg_ui::Vgfx,
+    watchdog,
     window::find_monitor,
     worker_service::WorkerService,
     LuaArena, RuscMixer, Scenes, FRAME_ACC_SIZE,
@@ -77,24 +87,47 @@ pub enum ControlMessage {
         diff: usize,
         loader: song_provider::LoadSongFn,
         autoplay: AutoPlay,
+        versus: bool,
     },
     TransitionComplete(Box<dyn scene::Scene>),
-    Result {
+    Result(GameResult),
+    /// Results of both players in versus mode
+    VersusResult(Vec<GameResult>),
+    /// Plays the last chart again from the result screen, `retries` is the new retry count
+    Retry {
         song: Arc<songselect::Song>,
         diff_idx: usize,
-        score: u32,
-        gauge: Gauge,
-        hit_ratings: Vec<HitRating>,
-        hit_window: crate::game::HitWindow,
-        autoplay: AutoPlay,
-        max_combo: i32,
-        duration: i32,
-        manual_exit: bool,
+        retries: u32,
+    },
+    /// Plays the song `step` places away from `song` in the song select order, from the result
+    /// screen
+    AdjacentSong {
+        song: Arc<songselect::Song>,
+        diff_idx: usize,
+        step: i32,
     },
 
     ApplySettings,
 }
 
+pub struct GameResult {
+    pub song: Arc<songselect::Song>,
+    pub diff_idx: usize,
+    pub score: u32,
+    pub gauge: Gauge,
+    pub hit_ratings: Vec<HitRating>,
+    pub hit_window: crate::game::HitWindow,
+    pub autoplay: AutoPlay,
+    pub max_combo: i32,
+    pub duration: i32,
+    pub manual_exit: bool,
+    /// 0 outside of versus mode
+    pub player: u8,
+    /// Times the chart was restarted before this play
+    pub retries: u32,
+    pub modifiers: kson::LaneModifiers,
+}
+
 impl Default for ControlMessage {
     fn default() -> Self {
         Self::None
@@ -112,6 +145,7 @@ pub struct GameMain {
     knob_state: LaserState,
     frame_times: [f64; 16],
     frame_time_index: usize,
+    frame_graph: FrameGraph,
     fps_paint: Paint,
     transition_lua: Rc<Lua>,
     transition_song_lua: Rc<Lua>,
@@ -120,15 +154,63 @@ pub struct GameMain {
     frame_count: u32,
     gui: EguiGlow,
     show_debug_ui: bool,
+    profiler: Option<Profiler>,
     mousex: f64,
     mousey: f64,
     input_state: InputState,
     mixer: RuscMixer,
+    menu_audio: Arc<MenuAudio>,
+    nav_samples: NavSamples,
     modifiers: Modifiers,
     service_provider: ServiceProvider,
     show_fps: bool,
+    show_frame_graph: bool,
+    /// Taken once the next frame is drawn
+    screenshot_requested: bool,
     frame_end: std::time::SystemTime,
     frame_duration: Duration,
+    input_poller: Option<InputPoller>,
+    /// Dark or light preference of the OS, if the platform reports one
+    system_theme: Option<Theme>,
+    /// Whether egui currently uses the dark visuals
+    dark_ui: Option<bool>,
+    /// Open in cab mode after the service chord, takes the buttons until the chord closes it
+    service_menu: Option<ServiceMenu>,
+    last_input: Instant,
+}
+
+/// In-game puffin profiler, keeps the frames it has seen so they can be saved
+struct Profiler {
+    frames: puffin::GlobalFrameView,
+    scopes_were_on: bool,
+}
+
+impl Profiler {
+    fn start() -> Self {
+        let scopes_were_on = puffin::are_scopes_on();
+        puffin::set_scopes_on(true);
+        Self {
+            frames: puffin::GlobalFrameView::default(),
+            scopes_were_on,
+        }
+    }
+
+    /// Writes the captured frames to a file that can be opened with puffin_viewer
+    fn save(&self) -> anyhow::Result<std::path::PathBuf> {
+        let path = GameConfig::get().game_folder.join(format!(
+            "profile_{}.puffin",
+            chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")
+        ));
+        let mut file = std::fs::File::create(&path)?;
+        self.frames.lock().write(&mut file)?;
+        Ok(path)
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        puffin::set_scopes_on(self.scopes_were_on);
+    }
 }
 
 fn get_frame_duration(settings: &GameConfig) -> Duration {
@@ -147,6 +229,8 @@ impl GameMain {
         gui: EguiGlow,
         show_debug_ui: bool,
         service_provider: ServiceProvider,
+        input_poller: Option<InputPoller>,
+        system_theme: Option<Theme>,
     ) -> Self {
         let (control_tx, control_rx) = channel();
 
@@ -160,6 +244,7 @@ impl GameMain {
             knob_state: LaserState::default(),
             frame_times: [0.01; 16],
             frame_time_index: 0,
+            frame_graph: FrameGraph::default(),
             fps_paint,
             transition_lua: LuaProvider::new_lua(),
             transition_song_lua: LuaProvider::new_lua(),
@@ -168,23 +253,48 @@ impl GameMain {
             frame_count: 0,
             gui,
             show_debug_ui,
+            profiler: None,
             mousex: 0.0,
             mousey: 0.0,
             input_state: InputState::clone(&service_provider.get_required()),
             mixer: service_provider.get_required(),
+            menu_audio: service_provider.get_required(),
+            nav_samples: NavSamples::default(),
             modifiers: Modifiers::default(),
             service_provider,
             show_fps: GameConfig::get().graphics.show_fps,
+            show_frame_graph: GameConfig::get().graphics.show_frame_graph,
+            screenshot_requested: false,
             companion_update: 0,
             frame_end: SystemTime::UNIX_EPOCH,
             frame_duration: get_frame_duration(&GameConfig::get()),
+            input_poller,
+            system_theme,
+            dark_ui: None,
+            service_menu: None,
+            last_input: Instant::now(),
         }
     }
 
-    const KEYBOARD_LASER_SENS: f32 = 2.0 / 240.0;
     pub fn update(&mut self) {
+        if let Some(input_poller) = self.input_poller.as_mut() {
+            profile_scope!("Input poll");
+            crate::log_result!(input_poller.poll());
+        }
+
+        if !self.input_state.key_capture_active() {
+            let actions = self
+                .input_state
+                .chord_actions(&GameConfig::get().chords, SystemTime::now());
+            for action in actions {
+                self.handle_action(action);
+            }
+        }
+
+        self.close_idle_results();
         self.scenes
             .tick(1000.0 / 240.0, self.knob_state, self.control_tx.clone());
+        self.knob_state.zero_deltas();
 
         {
             for ele in self.service_provider.get_all_mut::<dyn WorkerService>() {
@@ -211,35 +321,107 @@ impl GameMain {
         }
 
         self.companion_update -= 1;
+    }
 
-        if GameConfig::get().keyboard_knobs {
-            let mut ls = LaserState::default();
-            for l in [kson::Side::Left, kson::Side::Right] {
-                for d in [kson::Side::Left, kson::Side::Right] {
-                    if self
-                        .input_state
-                        .is_button_held(crate::button_codes::UscButton::Laser(l, d))
-                        .is_some()
-                    {
-                        ls.update(
-                            l,
-                            match d {
-                                kson::Side::Left => -Self::KEYBOARD_LASER_SENS,
-                                kson::Side::Right => Self::KEYBOARD_LASER_SENS,
-                            },
-                        )
+    /// Handles the global chord actions, then passes the action on to the scenes
+    fn handle_action(&mut self, action: ActionKind) {
+        log::info!("Chord action: {}", action.as_str());
+        match action {
+            ActionKind::Screenshot => self.screenshot_requested = true,
+            ActionKind::ToggleFps => self.show_fps = !self.show_fps,
+            ActionKind::OpenSettings => Self::open_settings(&self.control_tx, &self.scenes),
+            ActionKind::ServiceMenu => {
+                if !GameConfig::get().cab_mode.enabled {
+                    return;
+                }
+                if self.service_menu.take().is_none() {
+                    // The scenes don't see the buttons while the menu is open
+                    let now = SystemTime::now();
+                    for button in self.input_state.held_buttons() {
+                        self.scenes
+                            .for_each_active_mut(|x| x.on_button_released(button, now));
                     }
+                    self.service_menu = Some(ServiceMenu::default());
                 }
+                return;
             }
+            ActionKind::Restart | ActionKind::ToggleAutoplay => {}
+        }
 
-            self.scenes.for_each_active_mut(|x| {
-                x.on_event(&event::Event::UserEvent(UscInputEvent::Laser(
-                    ls,
-                    SystemTime::now(),
-                )))
-            });
+        let event = game_loop::winit::event::Event::UserEvent(UscInputEvent::Action(action));
+        self.scenes
+            .active
+            .iter_mut()
+            .filter(|x| !x.is_suspended())
+            .for_each(|x| x.on_event(&event));
+    }
+
+    fn open_settings(control_tx: &Sender<ControlMessage>, scenes: &Scenes) {
+        let settings_open = scenes.active.last().is_some_and(|s| s.name() == "Settings");
+        if !settings_open {
+            _ = control_tx.send(ControlMessage::MainMenu(MainMenuButton::Options));
         }
     }
+
+    /// Exits go through the scenes being cleared like the exit of the main menu, so the config is
+    /// saved on the way out
+    fn run_service_action(
+        action: ServiceAction,
+        control_tx: &Sender<ControlMessage>,
+        scenes: &Scenes,
+    ) {
+        log::info!("Service action: {}", action.as_str());
+        match action {
+            ServiceAction::OpenSettings => Self::open_settings(control_tx, scenes),
+            ServiceAction::RestartApp => {
+                service_menu::request_restart();
+                _ = control_tx.send(ControlMessage::MainMenu(MainMenuButton::Exit));
+            }
+            ServiceAction::ExitGame => {
+                _ = control_tx.send(ControlMessage::MainMenu(MainMenuButton::Exit));
+            }
+        }
+    }
+
+    fn service_button(&mut self, button: UscButton, state: ElementState) {
+        let Some(menu) = self.service_menu.as_mut() else {
+            return;
+        };
+        let action = match state {
+            ElementState::Pressed => {
+                menu.on_button_pressed(button);
+                None
+            }
+            ElementState::Released => menu.on_button_released(button),
+        };
+        if let Some(action) = action {
+            self.service_menu = None;
+            Self::run_service_action(action, &self.control_tx, &self.scenes);
+        }
+    }
+
+    /// Cabs go back to the title screen instead of staying on the results of the last player
+    fn close_idle_results(&mut self) {
+        let idle_timeout = {
+            let cab_mode = &GameConfig::get().cab_mode;
+            if !cab_mode.enabled || cab_mode.idle_timeout.is_zero() {
+                return;
+            }
+            cab_mode.idle_timeout
+        };
+
+        let on_results = self
+            .scenes
+            .active
+            .last()
+            .is_some_and(|s| s.name() == "Song Result");
+        if on_results && self.service_menu.is_none() && self.last_input.elapsed() >= idle_timeout {
+            log::info!("Idle on the results, going back to the title screen");
+            self.scenes.close_to_title();
+            self.last_input = Instant::now();
+        }
+    }
+
     pub fn render(
         &mut self,
         frame_input: FrameInput,
@@ -252,8 +434,9 @@ impl GameMain {
             scenes,
             control_tx,
             control_rx,
-            knob_state,
+            knob_state: _,
             frame_times,
+            frame_graph,
             fps_paint,
             transition_lua,
             transition_song_lua,
@@ -261,45 +444,49 @@ impl GameMain {
             game_data,
             vgfx,
             show_debug_ui,
+            profiler,
             gui,
             frame_time_index,
             mousex,
             mousey,
             input_state: _,
             mixer,
+            menu_audio,
+            nav_samples,
             modifiers: _,
             service_provider,
             lua_provider,
             show_fps,
+            show_frame_graph,
+            screenshot_requested,
             companion_server: _,
             companion_update: _,
             frame_end,
             frame_duration,
+            input_poller: _,
+            system_theme,
+            dark_ui,
+            service_menu,
+            last_input: _,
         } = self;
 
-        knob_state.zero_deltas();
-
-        for lua in lua_arena.read().expect("Lock error").0.iter() {
-            lua.set_app_data(frame_input.clone());
-        }
-        let _lua_frame_input = frame_input.clone();
-        let _lua_mixer = mixer.clone();
+        puffin::GlobalProfiler::lock().new_frame();
 
         if frame_input.first_frame {
             frame_input.screen().clear(td::ClearState::default());
-            let vgfx = vgfx.write().expect("Lock error");
-            let mut canvas = vgfx.canvas.lock().expect("Lock error");
-            canvas.reset();
-            canvas.set_size(frame_input.viewport.width, frame_input.viewport.height, 1.0);
-            _ = canvas.fill_text(
-                10.0,
-                10.0,
-                "Loading...",
-                &vg::Paint::color(vg::Color::white())
-                    .with_font_size(32.0)
-                    .with_text_baseline(vg::Baseline::Top),
-            );
-            canvas.flush();
+            Vgfx::with_canvas(vgfx, |_, canvas| {
+                canvas.reset();
+                canvas.set_size(frame_input.viewport.width, frame_input.viewport.height, 1.0);
+                _ = canvas.fill_text(
+                    10.0,
+                    10.0,
+                    "Loading...",
+                    &vg::Paint::color(vg::Color::white())
+                        .with_font_size(32.0)
+                        .with_text_baseline(vg::Baseline::Top),
+                );
+                canvas.flush();
+            });
             *frame_count += 1;
 
             return FrameOutput {
@@ -320,98 +507,8 @@ impl GameMain {
         }
 
         while let Ok(control_msg) = control_rx.try_recv() {
-            match control_msg {
-                ControlMessage::None => {}
-                ControlMessage::MainMenu(b) => match b {
-                    MainMenuButton::Start => {
-                        scenes.suspend_top();
-
-                        if let Ok(_arena) = lua_arena.read() {
-                            let transition_lua = transition_lua.clone();
-                            scenes.transition = Transition::new(
-                                transition_lua,
-                                ControlMessage::MainMenu(MainMenuButton::Start),
-                                control_tx.clone(),
-                                vgfx.clone(),
-                                frame_input.viewport,
-                                service_provider.create_scope(),
-                            )
-                            .ok()
-                        }
-                    }
-                    MainMenuButton::Downloads => {}
-                    MainMenuButton::Exit => {
-                        scenes.clear();
-                    }
-                    MainMenuButton::Options => scenes.loaded.push(Box::new(SettingsScreen::new(
-                        service_provider.create_scope(),
-                        control_tx.clone(),
-                        window,
-                    ))),
-                    _ => {}
-                },
-                ControlMessage::Song {
-                    diff,
-                    loader,
-                    song,
-                    autoplay,
-                } => {
-                    if let Ok(_arena) = lua_arena.read() {
-                        let transition_lua = transition_song_lua.clone();
-                        scenes.transition = Transition::new(
-                            transition_lua,
-                            ControlMessage::Song {
-                                diff,
-                                loader,
-                                song,
-                                autoplay,
-                            },
-                            control_tx.clone(),
-                            vgfx.clone(),
-                            frame_input.viewport,
-                            service_provider.create_scope(),
-                        )
-                        .ok()
-                    }
-                }
-                ControlMessage::TransitionComplete(scene_data) => scenes.loaded.push(scene_data),
-                ControlMessage::Result {
-                    song,
-                    diff_idx,
-                    score,
-                    gauge,
-                    hit_ratings,
-                    hit_window,
-                    autoplay,
-                    max_combo,
-                    duration,
-                    manual_exit,
-                } => {
-                    if let Ok(_arena) = lua_arena.read() {
-                        let transition_lua = transition_lua.clone();
-                        scenes.transition = Transition::new(
-                            transition_lua,
-                            ControlMessage::Result {
-                                song,
-                                diff_idx,
-                                score,
-                                gauge,
-                                hit_ratings,
-                                hit_window,
-                                autoplay,
-                                max_combo,
-                                duration,
-                                manual_exit,
-                            },
-                            control_tx.clone(),
-                            vgfx.clone(),
-                            frame_input.viewport,
-                            service_provider.create_scope(),
-                        )
-                        .ok()
-                    }
-                }
-                ControlMessage::ApplySettings => {
+            match control_msg.into_command() {
+                SceneCommand::ApplySettings => {
                     //TODO: Reload skin
                     let settings = GameConfig::get();
                     _ = surface.set_swap_interval(
@@ -424,6 +521,7 @@ impl GameMain {
                     );
 
                     *show_fps = settings.graphics.show_fps;
+                    *show_frame_graph = settings.graphics.show_frame_graph;
 
                     *frame_duration = get_frame_duration(&settings);
 
@@ -448,15 +546,27 @@ impl GameMain {
                         }
                     });
 
-                    let sink = service_provider.get_required::<rodio::Sink>();
-                    sink.set_volume(settings.master_volume);
+                    let audio = service_provider.get_required::<audio::AudioService>();
+                    audio.set_volume(settings.master_volume);
                 }
+                command => command.apply(&mut ControlDispatcher {
+                    scenes,
+                    lua_arena,
+                    transition_lua,
+                    transition_song_lua,
+                    control_tx,
+                    vgfx,
+                    viewport: frame_input.viewport,
+                    service_provider,
+                    window,
+                }),
             }
         }
 
         frame_times[*frame_time_index] = frame_input.elapsed_time;
         *frame_time_index = (*frame_time_index + 1) % FRAME_ACC_SIZE;
         let fps = 1000_f64 / (frame_times.iter().sum::<f64>() / FRAME_ACC_SIZE as f64);
+        frame_graph.push(frame_input.elapsed_time);
 
         Self::update_game_data_and_clear(
             game_data,
@@ -466,24 +576,77 @@ impl GameMain {
             self.input_state.clone(),
         );
 
+        Vgfx::with_canvas(vgfx, |vgfx, canvas| {
+            vgfx.set_rotation(
+                GameConfig::get().graphics.rotation,
+                (
+                    frame_input.viewport.width as f32,
+                    frame_input.viewport.height as f32,
+                ),
+            );
+            vgfx.reset_viewport(canvas);
+        });
+
         scenes.render(frame_input.clone(), vgfx);
-        Self::render_overlays(vgfx, &frame_input, fps, fps_paint, *show_fps);
+        Self::render_overlays(
+            vgfx,
+            &frame_input,
+            fps,
+            fps_paint,
+            *show_fps,
+            show_frame_graph.then_some(&*frame_graph),
+        );
+
+        if std::mem::take(screenshot_requested) {
+            match help::take_screenshot(&vgfx.read_recover(), None) {
+                Ok(p) => log::info!("Saved screenshot to: {p:?}"),
+                Err(e) => log::warn!("Failed to save screenshot: {e}"),
+            }
+        }
 
+        let dark = GameConfig::get().graphics.ui_theme.is_dark(*system_theme);
+        if *dark_ui != Some(dark) {
+            *dark_ui = Some(dark);
+            gui.egui_ctx.set_visuals(if dark {
+                egui::Visuals::dark()
+            } else {
+                egui::Visuals::light()
+            });
+        }
+        gui.egui_ctx
+            .set_zoom_factor(GameConfig::get().graphics.ui_scale());
+        let mut service_action = None;
         gui.run(window, |ctx| {
             scenes.render_egui(ctx);
 
+            if let Some(menu) = service_menu.as_mut() {
+                service_action = menu.ui(ctx);
+            }
+
             if *show_debug_ui {
-                Self::debug_ui(ctx, scenes, &vgfx);
+                Self::debug_ui(ctx, scenes, &vgfx, lua_arena, profiler);
             }
+
+            Self::skin_notice(ctx);
+            Self::sandbox_notice(ctx);
+            lighting::debug_window(ctx);
         });
         gui.paint(window);
 
-        Self::run_lua_gc(lua_arena, &mut vgfx.write().expect("Lock error"));
-
-        if let Ok(mut a) = game_data.write() {
-            a.profile_stack.clear()
+        if let Some(action) = service_action {
+            *service_menu = None;
+            Self::run_service_action(action, control_tx, scenes);
         }
 
+        Self::run_lua_gc(lua_arena, &mut vgfx.write_recover());
+
+        let skin_played = {
+            let mut game_data = game_data.write_recover();
+            game_data.profile_stack.clear();
+            std::mem::take(&mut game_data.skin_sample_played)
+        };
+        nav_samples.play(mixer, &menu_audio.take_nav(), skin_played);
+
         let exit = scenes.is_empty();
         if exit {
             GameConfig::get().save()
@@ -494,6 +657,7 @@ impl GameMain {
             crate::help::wait_until(*frame_end);
             *frame_end = SystemTime::now() + *frame_duration;
         }
+        watchdog::frame_done();
         FrameOutput {
             exit,
             swap_buffers: true,
@@ -506,12 +670,37 @@ impl GameMain {
         event: &game_loop::winit::event::Event<UscInputEvent>,
     ) {
         use game_loop::winit::event::*;
+        if let Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { .. },
+            ..
+        } = event
+        {
+            //A key is being bound, skip egui and the keybinds
+            if self.input_state.key_capture_active() {
+                self.scenes
+                    .active
+                    .iter_mut()
+                    .filter(|x| !x.is_suspended())
+                    .for_each(|x| x.on_event(event));
+                return;
+            }
+        }
+
         if let Event::WindowEvent {
             window_id: _,
             event,
         } = event
         {
-            if self.show_debug_ui || self.scenes.should_render_egui() {
+            if let WindowEvent::ThemeChanged(theme) = event {
+                self.system_theme = Some(*theme);
+            }
+
+            // The display scale is tracked while egui is hidden so it's right once it's shown
+            if self.show_debug_ui
+                || self.service_menu.is_some()
+                || self.scenes.should_render_egui()
+                || matches!(event, WindowEvent::ScaleFactorChanged { .. })
+            {
                 let event_response = self.gui.on_window_event(window, event);
                 if event_response.consumed {
                     return;
@@ -521,11 +710,11 @@ impl GameMain {
 
         let mut transformed_event = None;
 
-        let (offset, offset_neg) = {
-            let global_offset = GameConfig::get().global_offset;
+        let (keyboard_offset, mouse_offset) = {
+            let config = GameConfig::get();
             (
-                Duration::from_millis(global_offset.unsigned_abs() as _),
-                global_offset < 0,
+                config.input_offset(InputDevice::Keyboard),
+                config.input_offset(InputDevice::Mouse),
             )
         };
         let text_input_active = self.input_state.text_input_active();
@@ -533,9 +722,20 @@ impl GameMain {
         //TODO: Refactor keyboard handling
         match event {
             Event::UserEvent(e) => {
-                self.input_state.update(e);
+                if !self.input_state.update(e, InputSource::Controller) {
+                    return;
+                }
+                if matches!(e, UscInputEvent::Button(..) | UscInputEvent::Laser(..)) {
+                    self.last_input = Instant::now();
+                }
+                if self.service_menu.is_some() {
+                    if let UscInputEvent::Button(b, s, _) = e {
+                        self.service_button(*b, *s);
+                    }
+                    return;
+                }
                 match e {
-                    UscInputEvent::Laser(ls, _time) => self.knob_state = *ls,
+                    UscInputEvent::Laser(ls, _time) => self.knob_state.accumulate(ls),
                     UscInputEvent::Button(b, s, time) => match s {
                         ElementState::Pressed => self
                             .scenes
@@ -545,6 +745,8 @@ impl GameMain {
                             .for_each_active_mut(|x| x.on_button_released(*b, *time)),
                     },
                     UscInputEvent::ClientEvent(_) => {}
+                    UscInputEvent::Player2(_) => {}
+                    UscInputEvent::Action(_) => {}
                 }
             }
             Event::WindowEvent {
@@ -567,6 +769,11 @@ impl GameMain {
                 }
             }
 
+            Event::WindowEvent {
+                event: WindowEvent::Focused(focused),
+                ..
+            } => self.set_focused(window, *focused),
+
             Event::WindowEvent {
                 event: WindowEvent::CursorMoved { position, .. },
                 ..
@@ -589,7 +796,13 @@ impl GameMain {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
-            } => self.scenes.clear(),
+            } => {
+                if GameConfig::get().cab_mode.enabled {
+                    log::info!("Ignored a close request in cab mode");
+                } else {
+                    self.scenes.clear()
+                }
+            }
             Event::WindowEvent {
                 event: WindowEvent::KeyboardInput { event: key, .. },
                 ..
@@ -632,18 +845,12 @@ impl GameMain {
                         .iter()
                         .filter_map(|x| x.match_button(*physical_key))
                     {
-                        if self.input_state.is_button_held(button).is_none()
-                            || *state == ElementState::Released
-                        {
-                            let button = UscInputEvent::Button(
-                                button,
-                                *state,
-                                if offset_neg {
-                                    SystemTime::now().add(offset)
-                                } else {
-                                    SystemTime::now().sub(offset)
-                                },
-                            );
+                        let button = UscInputEvent::Button(
+                            button,
+                            *state,
+                            offset_timestamp(SystemTime::now(), keyboard_offset),
+                        );
+                        if self.input_state.update(&button, InputSource::Window) {
                             transformed_event = Some(Event::UserEvent(button));
                         }
                     }
@@ -652,7 +859,10 @@ impl GameMain {
             Event::DeviceEvent {
                 event: game_loop::winit::event::DeviceEvent::MouseMotion { delta },
                 ..
-            } if !text_input_active && GameConfig::get().mouse_knobs => {
+            } if !text_input_active
+                && self.input_state.window_focused()
+                && GameConfig::get().mouse_knobs =>
+            {
                 {
                     //TODO: Move somewhere else?
                     let s = window.inner_size();
@@ -665,16 +875,22 @@ impl GameMain {
                 ls.update(kson::Side::Left, (delta.0 / sens) as _);
                 ls.update(kson::Side::Right, (delta.1 / sens) as _);
 
-                transformed_event = Some(Event::UserEvent(UscInputEvent::Laser(
-                    ls,
-                    SystemTime::now().sub(offset),
-                )));
+                let laser =
+                    UscInputEvent::Laser(ls, offset_timestamp(SystemTime::now(), mouse_offset));
+                self.input_state.update(&laser, InputSource::Window);
+                transformed_event = Some(Event::UserEvent(laser));
             }
             _ => (),
         }
 
         if let Some(Event::UserEvent(e)) = transformed_event.as_ref() {
-            self.input_state.update(e);
+            self.last_input = Instant::now();
+            if self.service_menu.is_some() {
+                if let UscInputEvent::Button(b, s, _) = e {
+                    self.service_button(*b, *s);
+                }
+                return;
+            }
             match e {
                 UscInputEvent::Button(b, ElementState::Pressed, time) => self
                     .scenes
@@ -682,8 +898,10 @@ impl GameMain {
                 UscInputEvent::Button(b, ElementState::Released, time) => self
                     .scenes
                     .for_each_active_mut(|x| x.on_button_released(*b, *time)),
-                UscInputEvent::Laser(_, _) => {}
+                UscInputEvent::Laser(ls, _) => self.knob_state.accumulate(ls),
                 UscInputEvent::ClientEvent(_) => {}
+                UscInputEvent::Player2(_) => {}
+                UscInputEvent::Action(_) => {}
             }
         }
 
@@ -696,7 +914,7 @@ impl GameMain {
 
     fn run_lua_gc(lua_arena: &mut RefMut<LuaArena>, vgfx: &mut Vgfx) {
         profile_scope!("Garbage collect");
-        lua_arena.write().expect("Lock error").0.retain(|lua| {
+        lua_arena.write_recover().0.retain(|lua| {
             //lua.gc_collect();
             if Rc::strong_count(lua) > 1 {
                 LuaHttp::poll(lua);
@@ -708,7 +926,13 @@ impl GameMain {
         });
     }
 
-    fn debug_ui(gui_context: &egui::Context, scenes: &mut Scenes, vgfx: &Arc<RwLock<Vgfx>>) {
+    fn debug_ui(
+        gui_context: &egui::Context,
+        scenes: &mut Scenes,
+        vgfx: &Arc<RwLock<Vgfx>>,
+        lua_arena: &RefMut<LuaArena>,
+        profiler: &mut Option<Profiler>,
+    ) {
         profile_function!();
         if let Some(s) = scenes.active.last_mut() {
             crate::log_result!(s.debug_ui(gui_context));
@@ -745,6 +969,21 @@ impl GameMain {
                 ui.label("Transitioning");
             }
 
+            ui.separator();
+            let images = vgfx.read().expect("Lock error").image_cache_stats();
+            ui.label(format!(
+                "Images: {} ({:.1} MB), {} evicted",
+                images.images,
+                images.bytes as f64 / (1024.0 * 1024.0),
+                images.evictions
+            ));
+            ui.label(format!(
+                "Audio: {} Hz, {} sources resampled",
+                crate::audio::mixer_sample_rate(),
+                crate::audio::resampled_sources()
+            ));
+            ui.label(ResourceCounters::collect(&lua_arena.read().expect("Lock error")).to_string());
+
             if ui.button("Take screenshot").clicked() {
                 match help::take_screenshot(&vgfx.read().unwrap(), None) {
                     Ok(p) => {
@@ -755,7 +994,67 @@ impl GameMain {
                     }
                 }
             }
+
+            ui.separator();
+            let mut show_profiler = profiler.is_some();
+            if ui.checkbox(&mut show_profiler, "Profiler").changed() {
+                *profiler = show_profiler.then(Profiler::start);
+            }
+            if let Some(profiler) = profiler {
+                if ui.button("Save capture").clicked() {
+                    match profiler.save() {
+                        Ok(p) => log::info!("Saved profiler capture to: {p:?}"),
+                        Err(e) => log::warn!("Failed to save profiler capture: {e}"),
+                    }
+                }
+            }
         });
+
+        if profiler.is_some() && !puffin_egui::profiler_window(gui_context) {
+            *profiler = None;
+        }
+    }
+
+    fn skin_notice(gui_context: &egui::Context) {
+        let Some(missing) = fallback_skin::pending_notice() else {
+            return;
+        };
+
+        egui::Window::new("Incomplete skin")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 10.0])
+            .show(gui_context, |ui| {
+                ui.label("The selected skin is missing these files, built-in fallbacks are used instead:");
+                for file in missing {
+                    ui.label(file);
+                }
+                if ui.button("OK").clicked() {
+                    fallback_skin::dismiss_notice();
+                }
+            });
+    }
+
+    fn sandbox_notice(gui_context: &egui::Context) {
+        let Some(violations) = lua_sandbox::pending_notice() else {
+            return;
+        };
+
+        egui::Window::new("Skin script blocked")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -10.0])
+            .show(gui_context, |ui| {
+                ui.label(
+                    "The skin sandbox stopped these actions, the skin may not work correctly:",
+                );
+                for violation in violations {
+                    ui.label(violation);
+                }
+                if ui.button("OK").clicked() {
+                    lua_sandbox::dismiss_notice();
+                }
+            });
     }
 
     fn render_overlays(
@@ -764,26 +1063,28 @@ impl GameMain {
         fps: f64,
         fps_paint: &vg::Paint,
         show_fps: bool,
+        frame_graph: Option<&FrameGraph>,
     ) {
         profile_function!();
-        let vgfx_lock = vgfx.write();
-        if let Ok(vgfx) = vgfx_lock {
-            let mut canvas_lock = vgfx.canvas.try_lock();
-            if let Ok(ref mut canvas) = canvas_lock {
-                canvas.reset();
-                if show_fps {
-                    _ = canvas.fill_text(
-                        frame_input.viewport.width as f32 - 5.0,
-                        frame_input.viewport.height as f32 - 5.0,
-                        format!("{:.1} FPS", fps),
-                        fps_paint,
-                    );
-                }
+        let vgfx = vgfx.write_recover();
+        let mut canvas_lock = vgfx.canvas.try_lock();
+        if let Ok(ref mut canvas) = canvas_lock {
+            canvas.reset();
+            if show_fps {
+                _ = canvas.fill_text(
+                    frame_input.viewport.width as f32 - 5.0,
+                    frame_input.viewport.height as f32 - 5.0,
+                    format!("{:.1} FPS", fps),
+                    fps_paint,
+                );
+            }
+            if let Some(frame_graph) = frame_graph {
+                frame_graph.render(&mut **canvas, 5.0, frame_input.viewport.height as f32 - 5.0);
+            }
 
-                {
-                    profile_scope!("Flush Canvas");
-                    canvas.flush(); //also flushes game game ui, can take longer than it looks like it should
-                }
+            {
+                profile_scope!("Flush Canvas");
+                canvas.flush(); //also flushes game game ui, can take longer than it looks like it should
             }
         }
     }
@@ -797,19 +1098,13 @@ impl GameMain {
     ) {
         profile_function!();
         {
-            let lock = game_data.write();
-            if let Ok(mut game_data) = lock {
-                *game_data = GameData {
-                    mouse_pos: (mousex, mousey),
-                    resolution: (frame_input.viewport.width, frame_input.viewport.height),
-                    profile_stack: std::mem::take(&mut game_data.profile_stack),
-                    input_state,
-                    audio_samples: std::mem::take(&mut game_data.audio_samples),
-                    audio_sample_play_status: std::mem::take(
-                        &mut game_data.audio_sample_play_status,
-                    ),
-                };
-            }
+            let screen = ScreenSpace::new(
+                PixelSize::new(frame_input.viewport.width, frame_input.viewport.height),
+                GameConfig::get().graphics.rotation,
+            );
+            game_data
+                .write_recover()
+                .next_frame(screen, (mousex, mousey), input_state);
         }
 
         {
@@ -832,6 +1127,31 @@ impl GameMain {
         }
     }
 
+    /// Stops input from reaching the scenes while unfocused, buttons held when focus is lost are
+    /// released so they aren't stuck once it returns
+    fn set_focused(&mut self, window: &Window, focused: bool) {
+        if focused == self.input_state.window_focused() {
+            return;
+        }
+        log::info!("Window {}", if focused { "focused" } else { "unfocused" });
+        self.input_state.set_window_focused(focused);
+
+        let now = SystemTime::now();
+        for button in self.input_state.release_all() {
+            self.scenes
+                .for_each_active_mut(|x| x.on_button_released(button, now));
+        }
+        self.knob_state.zero_deltas();
+        self.input_state.zero_laser_deltas();
+
+        if GameConfig::get().mouse_knobs {
+            if !focused {
+                _ = window.set_cursor_grab(CursorGrabMode::None);
+            }
+            window.set_cursor_visible(!focused);
+        }
+    }
+
     fn toggle_fullscreen(&self, window: &Window) {
         let fullscreen = &mut GameConfig::get_mut().graphics.fullscreen;
         match window.fullscreen() {
@@ -21,6 +21,7 @@ use winit::event::ElementState;
 use crate::{
     button_codes::{UscButton, UscInputEvent},
     config::GameConfig,
+    lock_recovery::RecoverLock,
     vg_ui::Vgfx,
     worker_service::WorkerService,
 };
@@ -70,10 +71,12 @@ pub(crate) fn add_lua_static_method<'lua, M, A, R, F, T: 'static + Sized + ToTyp
             let data = data_rc.clone();
             drop(data_rc);
             drop(maybe_data);
-            let data_lock = data.try_write();
-            match data_lock {
-                Ok(mut data) => function(lua, &mut data, p),
-                Err(e) => Err(mlua::Error::external(format!("{e}"))),
+            match data.try_write_recover() {
+                Some(mut data) => function(lua, &mut data, p),
+                None => Err(mlua::Error::external(format!(
+                    "{} is already locked",
+                    std::any::type_name::<T>()
+                ))),
             }
         } else {
             Err(mlua::Error::external("App data not set"))
@@ -1,62 +1,244 @@
 use std::{
     collections::HashMap,
     sync::{atomic::AtomicBool, Arc, Mutex, RwLock},
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
 use game_loop::winit::event::ElementState;
 use kson::Side;
+use smallvec::SmallVec;
 
-use crate::button_codes::{LaserAxis, LaserState, UscButton, UscInputEvent};
+use crate::{
+    button_codes::{ActionKind, LaserAxis, LaserState, UscButton, UscInputEvent},
+    config::ChordBinding,
+};
+
+/// Buttons held together, fires once they have all been held long enough
+#[derive(Debug, Default)]
+pub struct ButtonChord {
+    /// Set once the chord fired, it has to be released before it can fire again
+    fired: bool,
+}
+
+impl ButtonChord {
+    /// Returns true once every button has been held for `hold`, `held` gives the time a button
+    /// was pressed if it counts as held
+    pub fn update(
+        &mut self,
+        buttons: &[UscButton],
+        hold: Duration,
+        now: SystemTime,
+        held: impl Fn(UscButton) -> Option<SystemTime>,
+    ) -> bool {
+        let pressed = buttons
+            .iter()
+            .map(|b| held(*b))
+            .collect::<Option<Vec<_>>>()
+            .and_then(|times| times.into_iter().max());
+
+        let Some(pressed) = pressed else {
+            self.fired = false;
+            return false;
+        };
+
+        if self.fired {
+            return false;
+        }
+
+        self.fired = now.duration_since(pressed).is_ok_and(|held| held >= hold);
+        self.fired
+    }
+}
+
+/// Number of presses remembered per button, enough for double and triple taps
+pub const PRESS_HISTORY: usize = 4;
+
+/// Where a button event came from, a button may be held from several sources at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputSource {
+    /// Keyboard and mouse input of the window
+    Window,
+    /// Controllers read by the input thread and the companion
+    Controller,
+}
+
+#[derive(Debug, Default)]
+struct ButtonsHeld {
+    held: HashMap<(UscButton, InputSource), SystemTime>,
+    /// Times a button became held, newest first
+    presses: HashMap<UscButton, SmallVec<[SystemTime; PRESS_HISTORY]>>,
+}
+
+impl ButtonsHeld {
+    /// Returns false if the event doesn't change whether its button is held, like a key being
+    /// released while a controller still holds the same button
+    fn update(&mut self, e: &UscInputEvent, source: InputSource) -> bool {
+        let UscInputEvent::Button(button, state, time) = e else {
+            return true;
+        };
+
+        let was_held = self.pressed_at(*button).is_some();
+        match state {
+            ElementState::Pressed => {
+                self.held.entry((*button, source)).or_insert(*time);
+            }
+            ElementState::Released => {
+                self.held.remove(&(*button, source));
+            }
+        }
+        let held = self.pressed_at(*button).is_some();
+
+        if held && !was_held {
+            let presses = self.presses.entry(*button).or_default();
+            presses.insert(0, *time);
+            presses.truncate(PRESS_HISTORY);
+        }
+
+        held != was_held
+    }
+
+    /// Earliest press of `button` that is still held
+    fn pressed_at(&self, button: UscButton) -> Option<SystemTime> {
+        self.held
+            .iter()
+            .filter(|((b, _), _)| *b == button)
+            .map(|(_, time)| *time)
+            .min()
+    }
+
+    fn buttons(&self) -> Vec<UscButton> {
+        let mut buttons: Vec<_> = vec![];
+        for (button, _) in self.held.keys() {
+            if !buttons.contains(button) {
+                buttons.push(*button);
+            }
+        }
+        buttons
+    }
+}
+
+/// Time between two presses regardless of their order
+pub fn press_interval(a: SystemTime, b: SystemTime) -> Duration {
+    a.duration_since(b).unwrap_or_else(|e| e.duration())
+}
 
 #[derive(Debug, Clone)]
 pub struct InputState {
     text_input_active: Arc<AtomicBool>,
+    key_capture_active: Arc<AtomicBool>,
+    window_focused: Arc<AtomicBool>,
     laser_state: Arc<RwLock<LaserState>>,
     gilrs: Arc<Mutex<gilrs::Gilrs>>,
-    buttons_held: Arc<RwLock<HashMap<UscButton, SystemTime>>>,
+    buttons_held: Arc<RwLock<ButtonsHeld>>,
+    /// State of the configured chords, by index
+    chords: Arc<Mutex<Vec<ButtonChord>>>,
 }
 
 impl InputState {
     pub fn new(gilrs: Arc<Mutex<gilrs::Gilrs>>) -> Self {
         Self {
             text_input_active: Arc::new(AtomicBool::new(false)),
+            key_capture_active: Arc::new(AtomicBool::new(false)),
+            window_focused: Arc::new(AtomicBool::new(true)),
             laser_state: Arc::new(RwLock::new(LaserState::default())),
             gilrs,
-            buttons_held: Arc::new(RwLock::new(HashMap::default())),
+            buttons_held: Arc::new(RwLock::new(ButtonsHeld::default())),
+            chords: Arc::new(Mutex::new(vec![])),
         }
     }
 
-    pub fn update(&mut self, e: &UscInputEvent) {
+    /// Shares the controllers but keeps track of its own buttons and lasers, for player 2 in versus mode
+    pub fn detached(&self) -> Self {
+        Self {
+            text_input_active: self.text_input_active.clone(),
+            key_capture_active: self.key_capture_active.clone(),
+            window_focused: self.window_focused.clone(),
+            laser_state: Arc::new(RwLock::new(LaserState::default())),
+            gilrs: self.gilrs.clone(),
+            buttons_held: Arc::new(RwLock::new(ButtonsHeld::default())),
+            chords: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Returns false for button events that don't change whether the button is held, those
+    /// shouldn't reach the scenes
+    pub fn update(&mut self, e: &UscInputEvent, source: InputSource) -> bool {
         if let Ok(mut laser_state) = self.laser_state.write() {
             match e {
                 UscInputEvent::Laser(s, _) => *laser_state = *s,
                 UscInputEvent::Button(_, _, _) => {}
                 UscInputEvent::ClientEvent(_) => {}
+                UscInputEvent::Player2(_) => {}
+                UscInputEvent::Action(_) => {}
             }
         }
 
-        if let Ok(mut buttons_held) = self.buttons_held.write() {
-            match e {
-                UscInputEvent::Button(b, ElementState::Pressed, _) => {
-                    //TODO: Take time as an argument for better accuracy
-                    buttons_held.insert(*b, std::time::SystemTime::now());
-                }
-                UscInputEvent::Button(b, ElementState::Released, _) => {
-                    buttons_held.remove(b);
-                }
-                UscInputEvent::Laser(_, _) => {}
-                UscInputEvent::ClientEvent(_) => {}
-            }
+        self.buttons_held
+            .write()
+            .map(|mut b| b.update(e, source))
+            .unwrap_or(true)
+    }
+
+    /// Actions of the chords in `bindings` that fired since the last call, holds are measured
+    /// up to `now` so this has to be polled
+    pub fn chord_actions(&self, bindings: &[ChordBinding], now: SystemTime) -> Vec<ActionKind> {
+        let mut chords = self.chords.lock().expect("Lock error");
+        chords.resize_with(bindings.len(), ButtonChord::default);
+        bindings
+            .iter()
+            .zip(chords.iter_mut())
+            .filter_map(|(binding, chord)| {
+                chord
+                    .update(&binding.buttons, binding.hold, now, |b| {
+                        self.is_button_held(b)
+                    })
+                    .then_some(binding.action)
+            })
+            .collect()
+    }
+
+    /// Buttons that are currently held
+    pub fn held_buttons(&self) -> Vec<UscButton> {
+        self.buttons_held
+            .read()
+            .map(|l| l.buttons())
+            .unwrap_or_default()
+    }
+
+    /// Forgets all held buttons, returning them so their release can be sent to the scenes
+    pub fn release_all(&self) -> Vec<UscButton> {
+        self.buttons_held
+            .write()
+            .map(|mut l| {
+                let buttons = l.buttons();
+                l.held.clear();
+                buttons
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn zero_laser_deltas(&self) {
+        if let Ok(mut laser_state) = self.laser_state.write() {
+            laser_state.zero_deltas();
         }
     }
 
-    /// Returns time when button was pressed if held, None if button is not held
+    /// Returns time when button was pressed if held, None if button is not held. A button held
+    /// from several sources counts from the earliest press.
     pub fn is_button_held(&self, button: UscButton) -> Option<SystemTime> {
         self.buttons_held
             .read()
             .ok()
-            .and_then(|l| l.get(&button).copied())
+            .and_then(|l| l.pressed_at(button))
+    }
+
+    /// Times `button` was last pressed, newest first, for double taps and the like
+    pub fn last_press_times(&self, button: UscButton) -> SmallVec<[SystemTime; PRESS_HISTORY]> {
+        self.buttons_held
+            .read()
+            .ok()
+            .and_then(|l| l.presses.get(&button).cloned())
+            .unwrap_or_default()
     }
 
     pub fn get_axis(&self, side: Side) -> LaserAxis {
@@ -76,4 +258,114 @@ impl InputState {
         self.text_input_active
             .store(text_input_active, std::sync::atomic::Ordering::Relaxed);
     }
+
+    /// Whether the next key press should go to the scenes as is, for binding keys
+    pub fn key_capture_active(&self) -> bool {
+        self.key_capture_active
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn set_key_capture_active(&mut self, key_capture_active: bool) {
+        self.key_capture_active
+            .store(key_capture_active, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Controller and knob input is dropped while the window is unfocused
+    pub fn window_focused(&self) -> bool {
+        self.window_focused
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn set_window_focused(&mut self, focused: bool) {
+        self.window_focused
+            .store(focused, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use game_loop::winit::event::ElementState;
+    use kson::Side;
+
+    use super::{ButtonChord, ButtonsHeld, InputSource};
+    use crate::button_codes::{UscButton, UscInputEvent};
+
+    fn at(ms: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(ms)
+    }
+
+    fn press(button: UscButton, ms: u64) -> UscInputEvent {
+        UscInputEvent::Button(button, ElementState::Pressed, at(ms))
+    }
+
+    fn release(button: UscButton, ms: u64) -> UscInputEvent {
+        UscInputEvent::Button(button, ElementState::Released, at(ms))
+    }
+
+    #[test]
+    fn chord_fires_once() {
+        let buttons = [UscButton::FX(Side::Left), UscButton::FX(Side::Right)];
+        let hold = Duration::from_secs(1);
+        let start = SystemTime::UNIX_EPOCH;
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut chord = ButtonChord::default();
+
+        // FX-R pressed late, the chord counts from the last press
+        let both = |b: UscButton| match b {
+            UscButton::FX(Side::Left) => Some(at(0)),
+            _ => Some(at(500)),
+        };
+        assert!(!chord.update(&buttons, hold, at(1200), both));
+        assert!(chord.update(&buttons, hold, at(1500), both));
+        assert!(!chord.update(&buttons, hold, at(3000), both));
+
+        let left_only = |b: UscButton| (b == UscButton::FX(Side::Left)).then_some(at(0));
+        assert!(!chord.update(&buttons, hold, at(4000), left_only));
+        assert!(chord.update(&buttons, hold, at(5000), both));
+
+        assert!(!ButtonChord::default().update(&[], hold, at(5000), both));
+    }
+
+    #[test]
+    fn keyboard_and_controller_hold_together() {
+        let fx = UscButton::FX(Side::Left);
+        let mut held = ButtonsHeld::default();
+
+        assert!(held.update(&press(fx, 0), InputSource::Controller));
+        // Tapping the key while the pad holds the button changes nothing
+        assert!(!held.update(&press(fx, 100), InputSource::Window));
+        assert!(!held.update(&release(fx, 200), InputSource::Window));
+        assert_eq!(held.pressed_at(fx), Some(at(0)));
+
+        assert!(!held.update(&press(fx, 300), InputSource::Window));
+        assert!(!held.update(&release(fx, 400), InputSource::Controller));
+        assert_eq!(held.pressed_at(fx), Some(at(300)));
+        assert!(held.update(&release(fx, 500), InputSource::Window));
+        assert_eq!(held.pressed_at(fx), None);
+        assert!(held.buttons().is_empty());
+
+        // Repeated presses of a held key aren't new presses
+        assert!(held.update(&press(fx, 600), InputSource::Window));
+        assert!(!held.update(&press(fx, 650), InputSource::Window));
+        assert_eq!(held.pressed_at(fx), Some(at(600)));
+        assert_eq!(held.presses[&fx].as_slice(), [at(600), at(0)]);
+    }
+
+    #[test]
+    fn press_history() {
+        let bt = UscButton::BT(kson::BtLane::A);
+        let mut held = ButtonsHeld::default();
+        for i in 0..6 {
+            held.update(&press(bt, i * 100), InputSource::Controller);
+            held.update(&release(bt, i * 100 + 50), InputSource::Controller);
+        }
+
+        assert_eq!(
+            held.presses[&bt].as_slice(),
+            [at(500), at(400), at(300), at(200)]
+        );
+        assert!(held.presses.get(&UscButton::Start).is_none());
+    }
 }
@@ -0,0 +1,234 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
+};
+
+use game_loop::winit::{
+    event::ElementState,
+    event_loop::{EventLoopClosed, EventLoopProxy},
+};
+use gilrs::{Axis, EventType, Filter, Gilrs};
+use log::{info, warn};
+
+use crate::{
+    button_codes::{
+        offset_timestamp, CustomBindingFilter, LaserState, RuscFilter, UscButton, UscInputEvent,
+    },
+    config::{DeviceOffsets, GameConfig, InputDevice},
+    input_state::InputState,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+const KEYBOARD_KNOB_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / 240);
+const KEYBOARD_LASER_SENS: f32 = 2.0 / 240.0;
+
+/// Reads controller events and synthesizes keyboard knobs, forwarding them to the event loop
+pub struct InputPoller {
+    gilrs: Arc<Mutex<Gilrs>>,
+    input_state: InputState,
+    rusc_filter: RuscFilter,
+    binding_filter: CustomBindingFilter,
+    knob_state: LaserState,
+    versus_knob_state: LaserState,
+    event_proxy: EventLoopProxy<UscInputEvent>,
+    last_keyboard_knobs: Instant,
+}
+
+impl InputPoller {
+    pub fn new(
+        gilrs: Arc<Mutex<Gilrs>>,
+        input_state: InputState,
+        event_proxy: EventLoopProxy<UscInputEvent>,
+    ) -> (Self, Sender<(f64, DeviceOffsets)>) {
+        let (rusc_filter, offset_tx) = {
+            let config = GameConfig::get();
+            RuscFilter::new(config.global_offset, config.device_offsets.clone())
+        };
+        (
+            Self {
+                gilrs,
+                input_state,
+                rusc_filter,
+                binding_filter: CustomBindingFilter,
+                knob_state: LaserState::default(),
+                versus_knob_state: LaserState::default(),
+                event_proxy,
+                last_keyboard_knobs: Instant::now(),
+            },
+            offset_tx,
+        )
+    }
+
+    /// Forwards all pending input events, fails if the event loop has been closed.
+    pub fn poll(&mut self) -> Result<(), EventLoopClosed<UscInputEvent>> {
+        use ElementState::*;
+        self.rusc_filter.update();
+        let versus_controller = GameConfig::get().versus_controller;
+        // Events are still read while unfocused so they don't pile up, the knob positions are
+        // tracked so refocusing doesn't produce one large delta
+        let focused = self.input_state.window_focused();
+
+        loop {
+            let (e, player2) = {
+                let Ok(mut input) = self.gilrs.lock() else {
+                    break;
+                };
+                let e = input
+                    .next_event()
+                    .filter_ev(&self.rusc_filter, &mut input)
+                    .filter_ev(&self.binding_filter, &mut input);
+                let player2 = e.as_ref().is_some_and(|e| {
+                    versus_controller == Some(uuid::Uuid::from_bytes(input.gamepad(e.id).uuid()))
+                });
+                (e, player2)
+            };
+            let Some(e) = e else {
+                break;
+            };
+
+            let knob_state = if player2 {
+                &mut self.versus_knob_state
+            } else {
+                &mut self.knob_state
+            };
+            knob_state.zero_deltas();
+            match e.event {
+                EventType::ButtonPressed(_, _) | EventType::ButtonReleased(_, _) if !focused => {}
+                EventType::ButtonPressed(button, _) => {
+                    let button = UscButton::from(button);
+                    info!("Pressed {:?}", button);
+                    self.forward(UscInputEvent::Button(button, Pressed, e.time), player2)?
+                }
+                EventType::ButtonReleased(button, _) => {
+                    let button = UscButton::from(button);
+                    info!("Released {:?}", button);
+                    self.forward(UscInputEvent::Button(button, Released, e.time), player2)?
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    match axis {
+                        Axis::LeftStickX => knob_state.update(kson::Side::Left, value),
+                        Axis::RightStickX => knob_state.update(kson::Side::Right, value),
+                        _ => {}
+                    }
+                    let knob_state = *knob_state;
+                    if focused {
+                        self.forward(UscInputEvent::Laser(knob_state, e.time), player2)?
+                    }
+                }
+                EventType::Connected => {
+                    if let Ok(input) = self.gilrs.lock() {
+                        info!("Controller connected: {}", input.gamepad(e.id).name());
+                    }
+                }
+                EventType::Disconnected => info!("Controller disconnected"),
+                EventType::ButtonRepeated(_, _)
+                | EventType::ButtonChanged(_, _, _)
+                | EventType::Dropped => {}
+            }
+        }
+
+        self.synthesize_keyboard_knobs()
+    }
+
+    fn forward(
+        &self,
+        event: UscInputEvent,
+        player2: bool,
+    ) -> Result<(), EventLoopClosed<UscInputEvent>> {
+        if player2 {
+            self.event_proxy
+                .send_event(UscInputEvent::Player2(Box::new(event)))
+        } else {
+            self.event_proxy.send_event(event)
+        }
+    }
+
+    fn synthesize_keyboard_knobs(&mut self) -> Result<(), EventLoopClosed<UscInputEvent>> {
+        let offset = {
+            let config = GameConfig::get();
+            if !config.keyboard_knobs || !self.input_state.window_focused() {
+                self.last_keyboard_knobs = Instant::now();
+                return Ok(());
+            }
+            config.input_offset(InputDevice::Keyboard)
+        };
+
+        while self.last_keyboard_knobs.elapsed() >= KEYBOARD_KNOB_INTERVAL {
+            self.last_keyboard_knobs += KEYBOARD_KNOB_INTERVAL;
+
+            let mut ls = LaserState::default();
+            let mut any_held = false;
+            for l in [kson::Side::Left, kson::Side::Right] {
+                for d in [kson::Side::Left, kson::Side::Right] {
+                    if self
+                        .input_state
+                        .is_button_held(UscButton::Laser(l, d))
+                        .is_some()
+                    {
+                        any_held = true;
+                        ls.update(
+                            l,
+                            match d {
+                                kson::Side::Left => -KEYBOARD_LASER_SENS,
+                                kson::Side::Right => KEYBOARD_LASER_SENS,
+                            },
+                        )
+                    }
+                }
+            }
+
+            if any_held {
+                self.event_proxy.send_event(UscInputEvent::Laser(
+                    ls,
+                    offset_timestamp(SystemTime::now(), offset),
+                ))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves polling to a dedicated thread, the thread is stopped when the returned handle is dropped.
+    pub fn spawn(mut self) -> std::io::Result<InputThread> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("Input".into())
+            .spawn(move || {
+                while !thread_stop.load(Ordering::Relaxed) {
+                    if let Err(e) = self.poll() {
+                        info!("Input thread closing: {}", e);
+                        return;
+                    }
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                info!("Input thread stopped");
+            })?;
+
+        Ok(InputThread {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+pub struct InputThread {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for InputThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                warn!("Input thread panicked");
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::{config::write_atomic, results::ClearMark, song_provider::DiffId, songselect::Song};
+
+/// Columns of the CSV export, in the order of the [`LibraryRow`] fields
+const COLUMNS: [&str; 11] = [
+    "title",
+    "artist",
+    "bpm",
+    "difficulty",
+    "level",
+    "effector",
+    "hash",
+    "duration_ms",
+    "best_score",
+    "badge",
+    "folder",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("json") => Ok(Self::Json),
+            Some("csv") => Ok(Self::Csv),
+            _ => bail!(
+                "Can't export to {}, use a .json or .csv file",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// One difficulty of the library
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LibraryRow {
+    pub title: String,
+    pub artist: String,
+    pub bpm: String,
+    /// Index of the difficulty, 0 is novice
+    pub difficulty: u8,
+    pub level: u8,
+    pub effector: String,
+    pub hash: Option<String>,
+    pub duration_ms: Option<u32>,
+    pub best_score: Option<i32>,
+    pub badge: &'static str,
+    pub folder: Option<PathBuf>,
+}
+
+fn badge_name(badge: u8) -> &'static str {
+    [
+        (ClearMark::Played, "Played"),
+        (ClearMark::Cleared, "Cleared"),
+        (ClearMark::HardCleared, "Hard Cleared"),
+        (ClearMark::FullCombo, "Full Combo"),
+        (ClearMark::Perfect, "Perfect"),
+    ]
+    .into_iter()
+    .find_map(|(mark, name)| (mark as u8 == badge).then_some(name))
+    .unwrap_or_default()
+}
+
+/// Lists every difficulty of `songs` sorted by title, artist and difficulty, `chart_paths` gives
+/// the folder of each difficulty if the provider has one
+pub fn rows(songs: &[Arc<Song>], chart_paths: &HashMap<DiffId, PathBuf>) -> Vec<LibraryRow> {
+    let mut rows: Vec<_> = songs
+        .iter()
+        .flat_map(|song| {
+            let diffs = song.difficulties.read().expect("Lock error");
+            diffs
+                .iter()
+                .map(|diff| LibraryRow {
+                    title: song.title.clone(),
+                    artist: song.artist.clone(),
+                    bpm: song.bpm.clone(),
+                    difficulty: diff.difficulty,
+                    level: diff.level,
+                    effector: diff.effector.clone(),
+                    hash: diff.hash.clone(),
+                    duration_ms: diff.duration,
+                    best_score: diff
+                        .scores
+                        .iter()
+                        .filter(|s| s.counts_for_best())
+                        .map(|s| s.score)
+                        .max(),
+                    badge: badge_name(diff.top_badge),
+                    folder: chart_paths
+                        .get(&diff.id)
+                        .and_then(|p| p.parent())
+                        .map(Path::to_path_buf),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    rows.sort_by(|a, b| {
+        (a.title.to_lowercase(), &a.artist, a.difficulty, &a.hash).cmp(&(
+            b.title.to_lowercase(),
+            &b.artist,
+            b.difficulty,
+            &b.hash,
+        ))
+    });
+    rows
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(rows: &[LibraryRow]) -> String {
+    let optional = |v: Option<String>| v.unwrap_or_default();
+    let mut csv = COLUMNS.join(",");
+    csv.push_str("\r\n");
+    for row in rows {
+        let fields = [
+            row.title.clone(),
+            row.artist.clone(),
+            row.bpm.clone(),
+            row.difficulty.to_string(),
+            row.level.to_string(),
+            row.effector.clone(),
+            optional(row.hash.clone()),
+            optional(row.duration_ms.map(|d| d.to_string())),
+            optional(row.best_score.map(|s| s.to_string())),
+            row.badge.to_string(),
+            optional(
+                row.folder
+                    .as_ref()
+                    .map(|f| f.to_string_lossy().into_owned()),
+            ),
+        ];
+        csv.push_str(&fields.map(|f| csv_field(&f)).join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Writes `rows` to `path` in the format matching its extension
+pub fn write(path: &Path, rows: &[LibraryRow]) -> Result<()> {
+    let data = match ExportFormat::from_path(path)? {
+        ExportFormat::Json => serde_json::to_string_pretty(rows)?,
+        ExportFormat::Csv => to_csv(rows),
+    };
+    write_atomic(path, data.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+        sync::{Arc, RwLock},
+    };
+
+    use super::{rows, to_csv, ExportFormat};
+    use crate::{
+        results::Score,
+        song_provider::{DiffId, SongId},
+        songselect::{Difficulty, Song},
+    };
+
+    fn difficulty(hash: &str, difficulty: u8, level: u8, scores: &[i32]) -> Difficulty {
+        Difficulty {
+            jacket_path: PathBuf::new(),
+            level,
+            difficulty,
+            id: DiffId(SongId::StringId(hash.into())),
+            effector: "Effector".into(),
+            top_badge: if scores.is_empty() { 0 } else { 2 },
+            scores: scores
+                .iter()
+                .map(|&score| Score {
+                    score,
+                    ..Default::default()
+                })
+                .collect(),
+            hash: Some(hash.into()),
+            illustrator: String::new(),
+            radar: None,
+            duration: Some(120_000),
+            duration_string: None,
+            last_played: None,
+            audio_broken: false,
+            density_strip_path: None,
+            jacket_loaded: false,
+        }
+    }
+
+    fn library() -> Vec<Arc<Song>> {
+        vec![
+            Arc::new(Song {
+                title: "zeta".into(),
+                artist: "Artist".into(),
+                bpm: "180".into(),
+                id: SongId::IntId(1),
+                difficulties: Arc::new(RwLock::new(vec![
+                    difficulty("b", 3, 18, &[]),
+                    difficulty("a", 1, 12, &[9_000_000, 9_800_000]),
+                ])),
+                uploader: None,
+            }),
+            Arc::new(Song {
+                title: "Alpha, \"the first\"".into(),
+                artist: "Other".into(),
+                bpm: "120-240".into(),
+                id: SongId::IntId(2),
+                difficulties: Arc::new(RwLock::new(vec![difficulty("c", 2, 16, &[])])),
+                uploader: None,
+            }),
+        ]
+    }
+
+    #[test]
+    fn export_fixture_library() {
+        let paths = HashMap::from([(
+            DiffId(SongId::StringId("a".into())),
+            Path::new("songs").join("zeta").join("adv.ksh"),
+        )]);
+        let rows = rows(&library(), &paths);
+
+        assert_eq!(
+            rows.iter().map(|r| r.hash.as_deref()).collect::<Vec<_>>(),
+            [Some("c"), Some("a"), Some("b")]
+        );
+        assert_eq!(rows[1].best_score, Some(9_800_000));
+        assert_eq!(rows[1].badge, "Cleared");
+        assert_eq!(rows[1].folder, Some(Path::new("songs").join("zeta")));
+
+        let csv = to_csv(&rows);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "title,artist,bpm,difficulty,level,effector,hash,duration_ms,best_score,badge,folder"
+        );
+        assert_eq!(
+            lines[1],
+            "\"Alpha, \"\"the first\"\"\",Other,120-240,2,16,Effector,c,120000,,,"
+        );
+        assert_eq!(lines.len(), 4);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&rows).unwrap()).unwrap();
+        assert_eq!(json[2]["level"], 18);
+        assert_eq!(json[2]["best_score"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(
+            ExportFormat::from_path(Path::new("library.CSV")).unwrap(),
+            ExportFormat::Csv
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("out/library.json")).unwrap(),
+            ExportFormat::Json
+        );
+        assert!(ExportFormat::from_path(Path::new("library.txt")).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    net::UdpSocket,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use di::{inject, injectable};
+use log::warn;
+use serde::Serialize;
+
+use crate::{config::GameConfig, worker_service::WorkerService};
+
+/// Lights are updated at most this often
+const UPDATE_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// BT A-D, FX L/R and start, indexed like the `game.BUTTON_*` constants
+pub const BUTTON_LIGHTS: usize = 7;
+
+/// Combo counts that are multiples of this are reported as milestones
+pub const COMBO_MILESTONE: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const OFF: Rgb = Rgb(0, 0, 0);
+    pub const WHITE: Rgb = Rgb(255, 255, 255);
+
+    /// From a color with components between 0 and 1
+    pub fn from_unit(r: f32, g: f32, b: f32) -> Self {
+        let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Self(c(r), c(g), c(b))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct LaserLight {
+    pub color: Rgb,
+    /// Cursor position between 0 and 1
+    pub position: f32,
+    /// A laser is currently in the chart
+    pub active: bool,
+}
+
+/// Everything lights can show, sent as is to the backends
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LightState {
+    pub buttons: [Rgb; BUTTON_LIGHTS],
+    pub lasers: [LaserLight; 2],
+    /// Gauge value between 0 and 1
+    pub gauge: f32,
+    pub combo: u32,
+    /// Last combo milestone reached without breaking the combo, 0 if none
+    pub combo_milestone: u32,
+}
+
+impl LightState {
+    pub fn set_combo(&mut self, combo: u32) {
+        self.combo = combo;
+        self.combo_milestone = combo - combo % COMBO_MILESTONE;
+    }
+}
+
+/// Output device for the lights
+pub trait LightingBackend: Send + Sync {
+    fn set_state(&mut self, state: &LightState);
+}
+
+/// Sends every state as a JSON datagram, for controller firmwares listening on the network
+pub struct UdpBackend {
+    socket: UdpSocket,
+    address: String,
+}
+
+impl UdpBackend {
+    pub fn new(address: &str) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            address: address.to_string(),
+        })
+    }
+}
+
+impl LightingBackend for UdpBackend {
+    fn set_state(&mut self, state: &LightState) {
+        let Ok(data) = serde_json::to_vec(state) else {
+            return;
+        };
+        if let Err(e) = self.socket.send_to(&data, &self.address) {
+            // Dropped states are replaced by the next one anyway
+            if e.kind() != std::io::ErrorKind::WouldBlock {
+                warn!("Could not send lights to {}: {e}", self.address);
+            }
+        }
+    }
+}
+
+static DEBUG_STATE: Mutex<Option<LightState>> = Mutex::new(None);
+
+/// Keeps the last state for [`debug_window`], to check the lights without any hardware
+pub struct DebugBackend;
+
+impl LightingBackend for DebugBackend {
+    fn set_state(&mut self, state: &LightState) {
+        *DEBUG_STATE.lock().expect("Lock error") = Some(*state);
+    }
+}
+
+/// Shows the last state sent to the debug backend, if it's enabled
+pub fn debug_window(ctx: &egui::Context) {
+    let Some(state) = *DEBUG_STATE.lock().expect("Lock error") else {
+        return;
+    };
+
+    let color = |c: Rgb| egui::Color32::from_rgb(c.0, c.1, c.2);
+    let swatch = |ui: &mut egui::Ui, c: Rgb, label: &str| {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(32.0, 32.0), egui::Sense::hover());
+        ui.painter().rect_filled(rect, 4.0, color(c));
+        ui.painter().text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            label,
+            egui::FontId::proportional(12.0),
+            egui::Color32::GRAY,
+        );
+    };
+
+    egui::Window::new("Lights").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            for (c, label) in state
+                .buttons
+                .iter()
+                .zip(["A", "B", "C", "D", "L", "R", "ST"])
+            {
+                swatch(ui, *c, label);
+            }
+        });
+        for (laser, name) in state.lasers.iter().zip(["Left laser", "Right laser"]) {
+            ui.horizontal(|ui| {
+                swatch(ui, if laser.active { laser.color } else { Rgb::OFF }, "");
+                ui.add(egui::ProgressBar::new(laser.position).desired_width(160.0))
+                    .on_hover_text(name);
+            });
+        }
+        ui.add(egui::ProgressBar::new(state.gauge).text("Gauge"));
+        ui.label(format!(
+            "Combo {} (milestone {})",
+            state.combo, state.combo_milestone
+        ));
+    });
+}
+
+/// Sends the lights state to the configured backends.
+///
+/// The game scene sets the state while playing, outside of it the lights show what skins set
+/// with `game.SetButtonLight`.
+pub struct LightingService {
+    backends: Vec<Box<dyn LightingBackend>>,
+    /// Config the backends were created from
+    config: Option<crate::config::LightingSettings>,
+    game_state: Option<LightState>,
+    overrides: [Rgb; BUTTON_LIGHTS],
+    sent: Option<LightState>,
+    last_sent: Instant,
+}
+
+impl WorkerService for LightingService {
+    fn update(&mut self) {
+        let config = GameConfig::get().lighting.clone();
+        if self.config.as_ref() != Some(&config) {
+            self.create_backends(&config);
+            self.config = Some(config);
+        }
+
+        if self.backends.is_empty() || self.last_sent.elapsed() < UPDATE_INTERVAL {
+            return;
+        }
+
+        let state = self.current();
+        if self.sent == Some(state) {
+            return;
+        }
+        for backend in &mut self.backends {
+            backend.set_state(&state);
+        }
+        self.sent = Some(state);
+        self.last_sent = Instant::now();
+    }
+}
+
+#[injectable]
+impl LightingService {
+    #[inject]
+    pub fn new() -> Self {
+        Self {
+            backends: vec![],
+            config: None,
+            game_state: None,
+            overrides: [Rgb::OFF; BUTTON_LIGHTS],
+            sent: None,
+            last_sent: Instant::now(),
+        }
+    }
+
+    fn create_backends(&mut self, config: &crate::config::LightingSettings) {
+        self.backends.clear();
+        self.sent = None;
+        *DEBUG_STATE.lock().expect("Lock error") = None;
+        if !config.enabled {
+            return;
+        }
+
+        if let Some(address) = &config.udp_address {
+            match UdpBackend::new(address) {
+                Ok(backend) => self.backends.push(Box::new(backend)),
+                Err(e) => warn!("Could not open the lights socket: {e}"),
+            }
+        }
+        if config.debug_view {
+            self.backends.push(Box::new(DebugBackend));
+        }
+    }
+
+    /// State to show, the game state while playing, the skin overrides otherwise
+    pub fn current(&self) -> LightState {
+        self.game_state.unwrap_or(LightState {
+            buttons: self.overrides,
+            ..Default::default()
+        })
+    }
+
+    pub fn set_game_state(&mut self, state: LightState) {
+        self.game_state = Some(state);
+    }
+
+    /// Called when gameplay ends, the lights go back to the skin overrides
+    pub fn clear_game_state(&mut self) {
+        self.game_state = None;
+    }
+
+    pub fn set_button_light(&mut self, index: usize, color: Rgb) -> anyhow::Result<()> {
+        let Some(light) = self.overrides.get_mut(index) else {
+            anyhow::bail!("Invalid button light index: {index}");
+        };
+        *light = color;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LightState, LightingService, Rgb};
+
+    #[test]
+    fn game_state_replaces_overrides() {
+        let mut lighting = LightingService::new();
+        lighting.set_button_light(6, Rgb::WHITE).unwrap();
+        assert!(lighting.set_button_light(7, Rgb::WHITE).is_err());
+        assert_eq!(lighting.current().buttons[6], Rgb::WHITE);
+
+        let mut state = LightState::default();
+        state.set_combo(250);
+        lighting.set_game_state(state);
+        assert_eq!(lighting.current().buttons[6], Rgb::OFF);
+        assert_eq!(lighting.current().combo_milestone, 200);
+
+        lighting.clear_game_state();
+        assert_eq!(lighting.current().buttons[6], Rgb::WHITE);
+
+        let json = serde_json::to_value(state).unwrap();
+        assert_eq!(json["comboMilestone"], 200);
+        assert_eq!(json["buttons"][0], serde_json::json!([0, 0, 0]));
+    }
+}
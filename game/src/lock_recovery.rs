@@ -0,0 +1,140 @@
+use std::sync::{
+    Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError,
+};
+
+/// Types of the locks that were recovered, each is only logged the first time
+static RECOVERED: Mutex<Vec<&'static str>> = Mutex::new(vec![]);
+
+fn log_recovered<T: ?Sized>() {
+    let name = std::any::type_name::<T>();
+    let mut recovered = RECOVERED.lock().unwrap_or_else(PoisonError::into_inner);
+    if !recovered.contains(&name) {
+        recovered.push(name);
+        log::error!("Recovered a poisoned {name} lock, something panicked while holding it");
+    }
+}
+
+/// Locking for shared state that is rebuilt every frame, like the vgfx, game data and lua arena.
+///
+/// A panic while one of them is held poisons the lock and every later `expect` would panic as
+/// well. The poison is cleared instead and the data used as is, the next frame replaces it.
+pub trait RecoverLock<T: ?Sized> {
+    fn read_recover(&self) -> RwLockReadGuard<'_, T>;
+    fn write_recover(&self) -> RwLockWriteGuard<'_, T>;
+    /// `None` if the lock is held elsewhere
+    fn try_write_recover(&self) -> Option<RwLockWriteGuard<'_, T>>;
+}
+
+impl<T: ?Sized> RecoverLock<T> for RwLock<T> {
+    fn read_recover(&self) -> RwLockReadGuard<'_, T> {
+        self.read().unwrap_or_else(|e| {
+            log_recovered::<T>();
+            self.clear_poison();
+            e.into_inner()
+        })
+    }
+
+    fn write_recover(&self) -> RwLockWriteGuard<'_, T> {
+        self.write().unwrap_or_else(|e| {
+            log_recovered::<T>();
+            self.clear_poison();
+            e.into_inner()
+        })
+    }
+
+    fn try_write_recover(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        match self.try_write() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::Poisoned(e)) => {
+                log_recovered::<T>();
+                self.clear_poison();
+                Some(e.into_inner())
+            }
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+}
+
+pub trait RecoverMutex<T: ?Sized> {
+    fn lock_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T: ?Sized> RecoverMutex<T> for Mutex<T> {
+    fn lock_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|e| {
+            log_recovered::<T>();
+            self.clear_poison();
+            e.into_inner()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        panic::AssertUnwindSafe,
+        sync::{Arc, Mutex, RwLock},
+    };
+
+    use super::RecoverLock;
+    use crate::{
+        display_rotation::DisplayRotation,
+        game_data::GameData,
+        input_state::InputState,
+        lighting::LightingService,
+        screen_space::{PixelSize, ScreenSpace},
+    };
+
+    #[allow(deprecated)]
+    fn game_data() -> GameData {
+        let gilrs = match gilrs::GilrsBuilder::new().build() {
+            Ok(gilrs) | Err(gilrs::Error::NotImplemented(gilrs)) => gilrs,
+            Err(e) => panic!("Failed to create input context: {e}"),
+        };
+        GameData {
+            resolution: (800, 600),
+            mouse_pos: (0.0, 0.0),
+            screen: ScreenSpace::default(),
+            window_mouse_pos: (0.0, 0.0),
+            focused: true,
+            profile_stack: vec![],
+            input_state: InputState::new(Arc::new(Mutex::new(gilrs))),
+            audio_samples: Default::default(),
+            audio_sample_play_status: Default::default(),
+            skin_sample_played: false,
+            last_played: None,
+            lighting: Arc::new(RwLock::new(LightingService::new())),
+        }
+    }
+
+    #[test]
+    fn poisoned_lock_recovers() {
+        let data = RwLock::new(game_data());
+        let input_state = data.read().unwrap().input_state.clone();
+        let scope =
+            puffin::ThreadProfiler::call(|f| f.register_function_scope("Test scope", "", 0));
+
+        let panicked = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut frame = data.write().unwrap();
+            frame
+                .profile_stack
+                .push(puffin::ProfilerScope::new(scope, "render"));
+            panic!("Skin error while rendering");
+        }));
+        assert!(panicked.is_err());
+        assert!(data.is_poisoned());
+
+        // The next frame rebuilds the data and carries on
+        let screen = ScreenSpace::new(PixelSize::new(1280, 720), DisplayRotation::None);
+        data.write_recover()
+            .next_frame(screen, (640.0, 360.0), input_state);
+        assert!(!data.is_poisoned());
+
+        let frame = data.read().unwrap();
+        assert_eq!(frame.profile_stack.len(), 1);
+        assert_eq!(frame.screen.logical, PixelSize::new(1280, 720));
+        assert_eq!(frame.skin_mouse_pos(), (640.0, 360.0));
+        drop(frame);
+        assert!(data.try_write_recover().is_some());
+    }
+}
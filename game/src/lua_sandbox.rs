@@ -0,0 +1,267 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use log::warn;
+use tealr::mlu::mlua::{self, Function, Lua, MultiValue, Table, Value};
+
+/// Functions that can't be used at all from skin scripts
+const BLOCKED: [(&str, &str); 9] = [
+    ("os", "execute"),
+    ("os", "remove"),
+    ("os", "rename"),
+    ("os", "exit"),
+    ("os", "tmpname"),
+    ("io", "popen"),
+    ("io", "output"),
+    ("io", "input"),
+    ("io", "tmpfile"),
+];
+
+/// Folders skin scripts may access
+struct Roots {
+    skin: String,
+    scripts: PathBuf,
+    /// Skin folder, also writable for caches and such
+    skin_path: PathBuf,
+    songs_path: PathBuf,
+}
+
+impl Roots {
+    fn can_read(&self, path: &Path) -> bool {
+        // Missing files are left to the original functions to report
+        let Ok(path) = path.canonicalize() else {
+            return true;
+        };
+        path.starts_with(&self.skin_path) || path.starts_with(&self.songs_path)
+    }
+
+    fn can_write(&self, path: &Path) -> bool {
+        // New files don't exist yet, check the folder they would be created in
+        let folder = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let Ok(folder) = folder.canonicalize() else {
+            return false;
+        };
+        folder.starts_with(&self.skin_path) && path.file_name().is_some_and(|name| name != "..")
+    }
+
+    fn violation(&self, what: impl Into<String>) -> mlua::Error {
+        let what = what.into();
+        warn!("Skin '{}' tried to {what}", self.skin);
+        report_violation(&self.skin, &what);
+        mlua::Error::RuntimeError(format!("Blocked by the skin sandbox: {what}"))
+    }
+}
+
+/// Limits what the scripts of `skin` can do outside of the game APIs: no processes, writing only
+/// inside the skin folder, reading only from the skin and songs folders and `require` only from
+/// the skin scripts
+pub fn apply(lua: &Lua, skin: &str, skin_path: &Path, songs_path: &Path) -> mlua::Result<()> {
+    let canonical = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    let roots = Arc::new(Roots {
+        skin: skin.to_string(),
+        scripts: canonical(&skin_path.join("scripts")),
+        skin_path: canonical(skin_path),
+        songs_path: canonical(songs_path),
+    });
+
+    let globals = lua.globals();
+    for (table, name) in BLOCKED {
+        let roots = roots.clone();
+        let stub = lua.create_function(move |_, _: MultiValue| -> mlua::Result<()> {
+            Err(roots.violation(format!("call {table}.{name}")))
+        })?;
+        globals.get::<_, Table>(table)?.set(name, stub)?;
+    }
+
+    let io: Table = globals.get("io")?;
+    let open = lua.create_registry_value(io.get::<_, Function>("open")?)?;
+    let open_roots = roots.clone();
+    io.set(
+        "open",
+        lua.create_function(move |lua, (path, mode): (String, Option<String>)| {
+            let mode = mode.unwrap_or_else(|| "r".into());
+            if mode.contains(['w', 'a', '+']) {
+                if !open_roots.can_write(Path::new(&path)) {
+                    return Err(open_roots.violation(format!("write {path}")));
+                }
+            } else if !open_roots.can_read(Path::new(&path)) {
+                return Err(open_roots.violation(format!("read {path}")));
+            }
+            lua.registry_value::<Function>(&open)?
+                .call::<_, MultiValue>((path, mode))
+        })?,
+    )?;
+
+    for (table, name) in [("io", "lines"), ("_G", "loadfile"), ("_G", "dofile")] {
+        let table: Table = globals.get(table)?;
+        let original = lua.create_registry_value(table.get::<_, Function>(name)?)?;
+        let roots = roots.clone();
+        table.set(
+            name,
+            lua.create_function(move |lua, args: MultiValue| {
+                if let Some(Value::String(path)) = args.iter().next() {
+                    let path = path.to_string_lossy().into_owned();
+                    if !roots.can_read(Path::new(&path)) {
+                        return Err(roots.violation(format!("read {path}")));
+                    }
+                }
+                lua.registry_value::<Function>(&original)?
+                    .call::<_, MultiValue>(args)
+            })?,
+        )?;
+    }
+
+    let package: Table = globals.get("package")?;
+    let loadlib_roots = roots.clone();
+    package.set(
+        "loadlib",
+        lua.create_function(move |_, _: MultiValue| -> mlua::Result<()> {
+            Err(loadlib_roots.violation("load a native library"))
+        })?,
+    )?;
+    package.set("cpath", "")?;
+
+    // Only preloaded modules and Lua files from the skin scripts, package.path is ignored
+    let searchers: Table = package.get("searchers")?;
+    let preload: Function = searchers.get(1)?;
+    let skin_searcher = lua.create_function(move |lua, name: String| {
+        let relative = name.replace('.', "/");
+        for candidate in [format!("{relative}.lua"), relative.clone()] {
+            let path = roots.scripts.join(candidate);
+            if !path.is_file() {
+                continue;
+            }
+            if !path
+                .canonicalize()
+                .is_ok_and(|p| p.starts_with(&roots.scripts))
+            {
+                return Err(roots.violation(format!("require {name} from outside the skin")));
+            }
+            let code = std::fs::read_to_string(&path).map_err(mlua::Error::external)?;
+            let loader = lua
+                .load(code)
+                .set_name(path.to_string_lossy())
+                .into_function()?;
+            return Ok((
+                Value::Function(loader),
+                Value::String(lua.create_string(path.to_string_lossy().as_bytes())?),
+            ));
+        }
+
+        Ok((
+            Value::String(
+                lua.create_string(format!("\n\tno file '{relative}' in the skin scripts"))?,
+            ),
+            Value::Nil,
+        ))
+    })?;
+    package.set(
+        "searchers",
+        lua.create_sequence_from([preload, skin_searcher])?,
+    )?;
+
+    Ok(())
+}
+
+struct Notice {
+    violations: BTreeSet<String>,
+    dismissed: bool,
+}
+
+static NOTICE: Mutex<Notice> = Mutex::new(Notice {
+    violations: BTreeSet::new(),
+    dismissed: false,
+});
+
+fn report_violation(skin: &str, what: &str) {
+    let mut notice = NOTICE.lock().expect("Lock error");
+    if notice
+        .violations
+        .insert(format!("Skin '{skin}' tried to {what}"))
+    {
+        notice.dismissed = false;
+    }
+}
+
+/// Blocked actions of skin scripts, if the user hasn't dismissed the notice
+pub fn pending_notice() -> Option<Vec<String>> {
+    let notice = NOTICE.lock().expect("Lock error");
+    if notice.dismissed || notice.violations.is_empty() {
+        None
+    } else {
+        Some(notice.violations.iter().cloned().collect())
+    }
+}
+
+pub fn dismiss_notice() {
+    NOTICE.lock().expect("Lock error").dismissed = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use tealr::mlu::mlua::{Function, Lua, Table};
+
+    #[test]
+    fn escapes_are_blocked() {
+        let dir = std::env::temp_dir().join(format!("rusc_sandbox_{}", std::process::id()));
+        let skin = dir.join("skin");
+        let songs = dir.join("songs");
+        let outside = dir.join("outside");
+        for folder in [skin.join("scripts"), songs.clone(), outside.clone()] {
+            std::fs::create_dir_all(folder).unwrap();
+        }
+        std::fs::write(skin.join("scripts/helper.lua"), "return 42").unwrap();
+        std::fs::write(songs.join("chart.ksh"), "title=test").unwrap();
+        std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+        std::fs::write(outside.join("outside.lua"), "return 'escaped'").unwrap();
+
+        let lua = Lua::new();
+        super::apply(&lua, "Malicious", &skin, &songs).unwrap();
+        let globals = lua.globals();
+        globals
+            .set("OUTSIDE_DIR", outside.to_string_lossy().into_owned())
+            .unwrap();
+        globals
+            .set("SKIN_DIR", skin.to_string_lossy().into_owned())
+            .unwrap();
+        globals
+            .set("SONGS_DIR", songs.to_string_lossy().into_owned())
+            .unwrap();
+
+        let fixture: Table = lua
+            .load(include_str!("lua_sandbox/escape.lua"))
+            .eval()
+            .unwrap();
+
+        let escapes: Table = fixture.get("escapes").unwrap();
+        for pair in escapes.pairs::<String, Function>() {
+            let (name, escape) = pair.unwrap();
+            let error = escape
+                .call::<_, ()>(())
+                .expect_err(&format!("{name} was not blocked"));
+            assert!(
+                error.to_string().contains("Blocked by the skin sandbox"),
+                "{name}: {error}"
+            );
+        }
+
+        let allowed: Table = fixture.get("allowed").unwrap();
+        for pair in allowed.pairs::<String, Function>() {
+            let (name, allowed) = pair.unwrap();
+            assert!(allowed.call::<_, bool>(()).unwrap(), "{name} failed");
+        }
+
+        assert!(outside.join("secret.txt").exists());
+        assert!(super::pending_notice()
+            .unwrap()
+            .iter()
+            .any(|v| v.contains("Malicious")));
+        _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,55 @@
+-- Things a malicious skin could try, every escape must raise a sandbox error
+-- OUTSIDE_DIR, SKIN_DIR and SONGS_DIR are set by the test
+
+return {
+    escapes = {
+        execute = function() os.execute("echo escaped") end,
+        remove = function() os.remove(OUTSIDE_DIR .. "/secret.txt") end,
+        rename = function() os.rename(OUTSIDE_DIR .. "/secret.txt", OUTSIDE_DIR .. "/moved.txt") end,
+        exit = function() os.exit(1) end,
+        popen = function() io.popen("ls") end,
+        output = function() io.output(OUTSIDE_DIR .. "/written.txt") end,
+        write_outside = function() io.open(OUTSIDE_DIR .. "/written.txt", "w") end,
+        write_songs = function() io.open(SONGS_DIR .. "/chart.ksh", "a") end,
+        write_escaping_skin = function() io.open(SKIN_DIR .. "/../outside/written.txt", "w") end,
+        read_outside = function() io.open(OUTSIDE_DIR .. "/secret.txt") end,
+        read_escaping_skin = function() io.open(SKIN_DIR .. "/../outside/secret.txt", "rb") end,
+        lines_outside = function() for _ in io.lines(OUTSIDE_DIR .. "/secret.txt") do end end,
+        dofile_outside = function() dofile(OUTSIDE_DIR .. "/outside.lua") end,
+        loadfile_outside = function() loadfile(OUTSIDE_DIR .. "/outside.lua") end,
+        loadlib = function() package.loadlib("libc.so.6", "system") end,
+        require_outside = function()
+            package.path = OUTSIDE_DIR .. "/?.lua"
+            local ok, result = pcall(require, "outside")
+            if ok and result == "escaped" then
+                error("Blocked by the skin sandbox was bypassed")
+            end
+            -- Not found is fine too, report it the same way
+            error("Blocked by the skin sandbox: " .. tostring(result))
+        end,
+    },
+    allowed = {
+        read_skin = function()
+            local file = io.open(SKIN_DIR .. "/scripts/helper.lua")
+            local ok = file ~= nil and file:read("*a") == "return 42"
+            if file then file:close() end
+            return ok
+        end,
+        read_songs = function()
+            for line in io.lines(SONGS_DIR .. "/chart.ksh") do
+                return line == "title=test"
+            end
+            return false
+        end,
+        write_skin = function()
+            local file = io.open(SKIN_DIR .. "/cache.json", "w")
+            if not file then return false end
+            file:write("{}")
+            file:close()
+            return true
+        end,
+        require_skin = function() return require("helper") == 42 end,
+        missing_file = function() return io.open(SKIN_DIR .. "/missing.txt") == nil end,
+        clock = function() return type(os.clock()) == "number" end,
+    },
+}
@@ -2,15 +2,17 @@ use std::rc::Rc;
 
 use crate::{
     config::GameConfig,
+    fallback_skin,
     game_data::{self, ExportGame, LuaPath},
     lua_http::{ExportLuaHttp, LuaHttp},
+    lua_sandbox,
     util::lua_address,
     vg_ui::{ExportVgfx, Vgfx},
     InnerRuscMixer, LuaArena,
 };
 use anyhow::Result;
 use di::{injectable, Ref, RefMut};
-use log::info;
+use log::{info, warn};
 use puffin::profile_scope;
 use serde_json::json;
 use tealr::mlu::mlua::Lua;
@@ -69,15 +71,32 @@ impl LuaProvider {
             //lua.gc_stop();
         }
 
+        let sandboxed = {
+            let config = GameConfig::get();
+            if config.lua_sandbox {
+                lua_sandbox::apply(&lua, &config.skin, &real_script_path, &config.songs_path)?;
+            }
+            config.lua_sandbox
+        };
+
         {
             let package: tealr::mlu::mlua::Table = lua.globals().get("package")?;
             let old_path: String = package.get("path")?;
 
-            let package_path = format!(
-                "{0};{1}/scripts/?.lua;{1}/scripts/?",
-                old_path,
-                real_script_path.as_os_str().to_string_lossy()
-            );
+            // The sandbox only resolves modules from the skin scripts, outside paths would just
+            // be misleading
+            let package_path = if sandboxed {
+                format!(
+                    "{0}/scripts/?.lua;{0}/scripts/?",
+                    real_script_path.as_os_str().to_string_lossy()
+                )
+            } else {
+                format!(
+                    "{0};{1}/scripts/?.lua;{1}/scripts/?",
+                    old_path,
+                    real_script_path.as_os_str().to_string_lossy()
+                )
+            };
             package.set("path", package_path)?;
 
             lua.globals().set("package", package)?;
@@ -96,7 +115,17 @@ impl LuaProvider {
 
         real_script_path.push(script_path.as_ref());
         info!("Loading: {:?}", &real_script_path);
-        let test_code = std::fs::read_to_string(real_script_path)?;
+        let test_code = match fallback_skin::script(script_path.as_ref()) {
+            Some(fallback) if !real_script_path.exists() => {
+                warn!("{:?} not found, using fallback", &real_script_path);
+                fallback_skin::report_missing(script_path.as_ref());
+                lua.load(fallback_skin::HELPERS)
+                    .set_name("fallback_helpers.lua")
+                    .eval::<()>()?;
+                fallback.to_string()
+            }
+            _ => std::fs::read_to_string(real_script_path)?,
+        };
         {
             profile_scope!("evaluate lua file");
             lua.load(&test_code)
@@ -2,23 +2,21 @@ use std::{
     path::{Path, PathBuf},
     rc::Rc,
     sync::{mpsc::channel, Arc, Mutex, RwLock},
-    time::Duration,
 };
 
 use crate::{
-    button_codes::{LaserState, RuscFilter},
     config::Args,
     config::GameConfig,
     game_main::GameMain,
     input_state::InputState,
+    input_thread::InputPoller,
     scene::SceneData,
-    songselect::{Difficulty, Song},
+    songselect::{format_duration, Difficulty, Song},
     transition::Transition,
     vg_ui::Vgfx,
 };
 use anyhow::{anyhow, bail};
 use async_service::AsyncService;
-use button_codes::CustomBindingFilter;
 use clap::Parser;
 use directories::ProjectDirs;
 
@@ -40,7 +38,7 @@ use scene::Scene;
 pub(crate) use song_provider::{DiffId, FileSongProvider, NauticaSongProvider, SongId};
 use td::{FrameInput, Viewport};
 use tealr::mlu::mlua::Lua;
-use test_scenes::camera_test;
+use test_scenes::{camera_test, soak_test};
 use three_d as td;
 
 use di::*;
@@ -50,19 +48,37 @@ mod animation;
 mod async_service;
 mod audio;
 mod audio_test;
+mod build_info;
 mod button_codes;
 mod companion_interface;
 mod config;
+mod confirm_dialog;
+mod control_dispatch;
+mod display_rotation;
+mod fallback_skin;
+mod frame_graph;
 mod game;
 mod game_data;
 mod game_main;
 mod help;
 mod input_state;
+mod input_thread;
+mod library_export;
+mod lighting;
+mod lock_recovery;
 mod lua_http;
+mod lua_sandbox;
 mod lua_service;
 mod main_menu;
+mod maintenance;
+mod menu_audio;
+mod play_history;
+mod play_stats;
+mod resource_counters;
 mod results;
 mod scene;
+mod screen_space;
+mod service_menu;
 mod settings_dialog;
 mod settings_screen;
 mod shaded_mesh;
@@ -71,9 +87,11 @@ mod song_provider;
 mod songselect;
 mod take_duration_fade;
 mod test_scenes;
+mod thumbnailer;
 mod transition;
 mod util;
 mod vg_ui;
+mod watchdog;
 mod window;
 mod worker_service;
 
@@ -264,6 +282,11 @@ impl Scenes {
         }
 
         for ele in &mut self.active {
+            // Like events, knob movement only goes to the scenes that aren't suspended
+            let mut knob_state = knob_state;
+            if ele.is_suspended() {
+                knob_state.zero_deltas();
+            }
             log_result!(ele.tick(dt, knob_state));
         }
 
@@ -271,6 +294,12 @@ impl Scenes {
             for scene in &mut self.active {
                 scene.suspend();
             }
+            // New scenes wait behind the transition until it reveals them
+            if self.transition.is_some() {
+                for scene in &mut self.initialized {
+                    scene.suspend();
+                }
+            }
 
             self.should_outro = true;
         }
@@ -297,7 +326,10 @@ impl Scenes {
             if x.is_suspended()
                 && self.loaded.is_empty()
                 && self.initialized.is_empty()
-                && self.transition.is_none()
+                && self
+                    .transition
+                    .as_ref()
+                    .map_or(true, Transition::reveals_next)
             {
                 x.resume()
             }
@@ -371,6 +403,17 @@ impl Scenes {
         self.loaded.clear();
         self.transition = None;
     }
+
+    /// Closes every scene above the title screen, the next tick resumes it
+    pub fn close_to_title(&mut self) {
+        let Some(title) = self.active.iter().position(|s| s.name() == "Main Menu") else {
+            return;
+        };
+        self.active.truncate(title + 1);
+        self.initialized.clear();
+        self.loaded.clear();
+        self.transition = None;
+    }
 }
 
 impl Default for Scenes {
@@ -414,6 +457,7 @@ fn get_log_config(level: log::LevelFilter) -> log4rs::Config {
 fn main() -> anyhow::Result<()> {
     let _logger_handle =
         log4rs::init_config(get_log_config(LevelFilter::Info)).expect("Failed to get logger");
+    info!("{}", build_info::BuildInfo::current());
     let mut config_path = default_game_dir();
     config_path.push("Main.cfg");
     let args = Args::parse();
@@ -445,12 +489,33 @@ fn main() -> anyhow::Result<()> {
         info!("Running anyway");
     };
     GameConfig::init(config_path, args);
-    let (_output_stream, output_stream_handle) = rodio::OutputStream::try_default()?;
-    let sink = rodio::Sink::try_new(&output_stream_handle)?;
-    let (mixer_controls, mixer) = rodio::dynamic_mixer::mixer::<f32>(2, 44100);
-    mixer_controls.add(rodio::source::Zero::new(2, 44100));
+    if let Some(path) = GameConfig::get().args.export_library.clone() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        let (songs, chart_paths) = rt.block_on(FileSongProvider::read_library())?;
+        let rows = library_export::rows(&songs, &chart_paths);
+        library_export::write(&path, &rows)?;
+        info!("Exported {} charts to {}", rows.len(), path.display());
+        return Ok(());
+    }
+    {
+        let config = GameConfig::get();
+        let report = fallback_skin::SkinReport::check(&config.skin_path());
+        if !report.is_ok() {
+            warn!("Skin \"{}\" is incomplete:\n{report}", config.skin);
+        }
+    }
+    let (audio_service, _output_stream) =
+        audio::AudioService::open(GameConfig::get().args.no_audio);
+    let sample_rate = audio::output_sample_rate();
+    audio::set_mixer_sample_rate(sample_rate);
+    info!("Mixing audio at {sample_rate} Hz");
+    let (mixer_controls, mixer) = rodio::dynamic_mixer::mixer::<f32>(2, sample_rate);
+    mixer_controls.add(rodio::source::Zero::new(2, sample_rate));
 
     {
+        let sink = audio_service.sink();
         sink.append(mixer);
         sink.play();
         sink.set_volume(GameConfig::get().master_volume);
@@ -493,9 +558,10 @@ fn main() -> anyhow::Result<()> {
         eventloop.create_proxy(),
     ));
 
-    let services = ServiceCollection::new()
+    let mut services = ServiceCollection::new();
+    services
         .add(existing_as_self(companion_service))
-        .add(existing_as_self(sink))
+        .add(existing_as_self(audio_service))
         .add(AsyncService::singleton().as_mut())
         .add_worker::<AsyncService>()
         .add(existing_as_self(Mutex::new(canvas)))
@@ -510,11 +576,21 @@ fn main() -> anyhow::Result<()> {
             RwLock<dyn song_provider::SongProvider>,
             _,
         >(|sp| {
-            if GameConfig::get().songs_path.eq(&PathBuf::from("nautica")) {
-                sp.get_required_mut::<song_provider::NauticaSongProvider>()
-            } else {
+            let providers = {
+                let config = GameConfig::get();
+                if config.songs_path.eq(&PathBuf::from("nautica")) {
+                    vec!["nautica".to_string()]
+                } else {
+                    config.providers.clone()
+                }
+            };
+            song_provider::create_provider(&providers, sp).unwrap_or_else(|| {
+                warn!(
+                    "None of the song providers {providers:?} are available, registered: {:?}",
+                    song_provider::provider_names()
+                );
                 sp.get_required_mut::<song_provider::FileSongProvider>()
-            }
+            })
         }))
         .add(transient_factory::<
             RwLock<dyn song_provider::ScoreProvider>,
@@ -525,6 +601,13 @@ fn main() -> anyhow::Result<()> {
         .add_worker::<FileSongProvider>()
         .add_worker::<NauticaSongProvider>()
         .add_worker::<companion_interface::CompanionServer>()
+        .add(thumbnailer::Thumbnailer::singleton().as_mut())
+        .add_worker::<thumbnailer::Thumbnailer>()
+        .add(play_history::PlayHistory::singleton().as_mut())
+        .add(play_stats::StatsTracker::singleton().as_mut())
+        .add_worker::<play_stats::StatsTracker>()
+        .add(lighting::LightingService::singleton().as_mut())
+        .add_worker::<lighting::LightingService>()
         .add(singleton_factory(move |_| mixer_controls.clone()))
         .add(Vgfx::singleton().as_mut())
         .add(singleton_factory(|_| {
@@ -533,79 +616,51 @@ fn main() -> anyhow::Result<()> {
         .add(singleton_factory(move |_| {
             Arc::new(InputState::new(gilrs_state.clone()))
         }))
+        .add(singleton_factory(|_| {
+            Arc::new(menu_audio::MenuAudio::default())
+        }))
         .add(game_data::GameData::singleton().as_mut())
-        .add(LuaProvider::scoped())
-        .build_provider()?;
+        .add(LuaProvider::scoped());
+
+    // Providers outside the built-in ones are added to the services and the provider registry
+    #[cfg(feature = "folder-provider")]
+    {
+        services.add(singleton_factory(|_| {
+            RefMut::new(song_provider::FolderSongProvider::new().into())
+        }));
+        song_provider::register_provider("folder", |sp| {
+            sp.get_required_mut::<song_provider::FolderSongProvider>()
+        });
+    }
+
+    let services = services.build_provider()?;
 
     let _mousex = 0.0;
     let _mousey = 0.0;
     let _lua_provider: Arc<LuaProvider> = services.get_required();
     let vgfx = services.get_required_mut::<Vgfx>();
-    let event_proxy = eventloop.create_proxy();
-    let (mut rusc_filter, offset_tx) = RuscFilter::new(GameConfig::get().global_offset as _);
-
-    let _input_thread = poll_promise::Promise::spawn_thread("gilrs", move || {
-        let mut knob_state = LaserState::default();
-        let binding_filter = CustomBindingFilter;
-        loop {
-            rusc_filter.update();
-            use button_codes::*;
-            use game_loop::winit::event::ElementState::*;
-            use gilrs::*;
-            let e = {
-                if let Ok(mut input) = input.lock() {
-                    input
-                        .next_event()
-                        .filter_ev(&rusc_filter, &mut input)
-                        .filter_ev(&binding_filter, &mut input)
-                } else {
-                    None
-                }
-            };
-            knob_state.zero_deltas();
-            if let Some(e) = e {
-                let sent = match e.event {
-                    EventType::ButtonPressed(button, _) => {
-                        let button = UscButton::from(button);
-                        info!("Pressed {:?}", button);
-                        Some(event_proxy.send_event(UscInputEvent::Button(button, Pressed, e.time)))
-                    }
-                    EventType::ButtonRepeated(_, _) => None,
-                    EventType::ButtonReleased(button, _) => {
-                        let button = UscButton::from(button);
-                        info!("Released {:?}", button);
-                        Some(
-                            event_proxy.send_event(UscInputEvent::Button(button, Released, e.time)),
-                        )
-                    }
-                    EventType::ButtonChanged(_, _, _) => None,
-                    EventType::AxisChanged(axis, value, _) => {
-                        match axis {
-                            Axis::LeftStickX => knob_state.update(kson::Side::Left, value),
-                            Axis::RightStickX => knob_state.update(kson::Side::Right, value),
-                            _ => {}
-                        }
-                        Some(event_proxy.send_event(UscInputEvent::Laser(knob_state, e.time)))
-                    }
-                    EventType::Connected => None,
-                    EventType::Disconnected => None,
-                    EventType::Dropped => None,
-                };
-
-                if let Some(Err(send_err)) = sent {
-                    info!("Gilrs thread closing: {}", send_err);
-                    return;
-                }
-            } else {
-                std::thread::sleep(Duration::from_millis(1))
-            }
-        }
-    });
+    let (input_poller, offset_tx) = InputPoller::new(
+        input.clone(),
+        InputState::clone(&services.get_required()),
+        eventloop.create_proxy(),
+    );
+
+    let (_input_thread, input_poller) = if GameConfig::get().input_thread {
+        (Some(input_poller.spawn()?), None)
+    } else {
+        info!("Polling input in the game loop");
+        (None, Some(input_poller))
+    };
 
     // Export luals definitions
     export_luals_defs()?;
 
-    let gui = egui_glow::EguiGlow::new(&eventloop, gl_context, None, None);
+    let gui = egui_glow::EguiGlow::new(
+        &eventloop,
+        gl_context,
+        None,
+        Some(window.scale_factor() as f32),
+    );
 
     let _frame_times = [16.0; FRAME_ACC_SIZE];
     let _frame_time_index = 0;
@@ -627,10 +682,12 @@ fn main() -> anyhow::Result<()> {
         }
     }
 
+    let soak = GameConfig::get().args.soak;
     if let Some(chart_path) = GameConfig::get().args.chart.as_ref() {
         let chart_path = PathBuf::from(chart_path);
         let chart =
             kson::Chart::from_ksh(&std::io::read_to_string(std::fs::File::open(&chart_path)?)?)?;
+        let duration = chart.tick_to_ms(chart.get_last_tick()) as u32;
 
         let song = Song {
             title: chart.meta.title.clone(),
@@ -647,10 +704,18 @@ fn main() -> anyhow::Result<()> {
                     top_badge: 0,
                     hash: None,
                     scores: vec![],
-                    illustrator: String::new(),
+                    illustrator: chart.meta.jacket_author.clone(),
+                    radar: Some(chart.radar()),
+                    duration: Some(duration),
+                    duration_string: Some(format_duration(duration)),
+                    last_played: None,
+                    audio_broken: false,
+                    density_strip_path: None,
+                    jacket_loaded: false,
                 }]
                 .into(),
             ),
+            uploader: None,
         };
 
         let audio = rodio::Decoder::new(std::fs::File::open(
@@ -658,18 +723,35 @@ fn main() -> anyhow::Result<()> {
         )?)?;
 
         let skin_folder = { vgfx.read().expect("Lock error").skin_folder() };
-
-        scenes.loaded.push(
-            Box::new(game::GameData::new(
-                Arc::new(song),
+        let song = Arc::new(song);
+        let game_data = move |audio: Box<dyn Source<Item = f32> + Send>| {
+            game::GameData::new(
+                song.clone(),
                 0,
-                chart,
-                skin_folder,
-                Box::new(audio.convert_samples()),
+                chart.clone(),
+                skin_folder.clone(),
+                audio,
                 game_main::AutoPlay::None,
-            )?)
-            .make_scene(services.create_scope())?,
-        );
+            )
+            .map(|d| {
+                d.with_chart_path(Some(chart_path.clone()))
+                    .with_swap_audio()
+            })
+        };
+
+        if let Some(cycles) = soak {
+            let audio = audio.convert_samples::<f32>().buffered();
+            scenes.loaded.push(Box::new(soak_test::SoakTest::new(
+                services.create_scope(),
+                cycles,
+                move || Ok(Box::new(game_data(Box::new(audio.clone()))?) as Box<dyn SceneData>),
+            )));
+        } else {
+            scenes.loaded.push(
+                Box::new(game_data(Box::new(audio.convert_samples()))?)
+                    .make_scene(services.create_scope())?,
+            );
+        }
     }
 
     if GameConfig::get().args.sound_test {
@@ -696,10 +778,22 @@ fn main() -> anyhow::Result<()> {
             )));
     }
 
-    let game = GameMain::new(scenes, fps_paint, gui, show_debug_ui, services);
-
-    let mut last_offset = { GameConfig::get().global_offset };
+    let game = GameMain::new(
+        scenes,
+        fps_paint,
+        gui,
+        show_debug_ui,
+        services,
+        input_poller,
+        window.theme(),
+    );
+
+    let mut last_offsets = {
+        let config = GameConfig::get();
+        (config.global_offset, config.device_offsets.clone())
+    };
 
+    watchdog::spawn();
     game_loop::game_loop(
         eventloop,
         Arc::new(window),
@@ -710,10 +804,11 @@ fn main() -> anyhow::Result<()> {
         move |g| {
             // Check for offset changes
             {
-                let current_offset = GameConfig::get().global_offset;
-                if current_offset != last_offset {
-                    log_result!(offset_tx.send(current_offset));
-                    last_offset = current_offset;
+                let config = GameConfig::get();
+                if config.global_offset != last_offsets.0 || config.device_offsets != last_offsets.1
+                {
+                    last_offsets = (config.global_offset, config.device_offsets.clone());
+                    log_result!(offset_tx.send(last_offsets.clone()));
                 }
             }
 
@@ -751,7 +846,7 @@ fn main() -> anyhow::Result<()> {
         },
         move |g, e| g.game.handle(&g.window, e),
     )?;
-    Ok(())
+    service_menu::restart_if_requested()
 }
 
 fn export_luals_defs() -> Result<(), anyhow::Error> {
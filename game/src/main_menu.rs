@@ -1,28 +1,38 @@
 use std::{
+    process::Command,
     rc::Rc,
+    str::FromStr,
     sync::mpsc::{Receiver, Sender},
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use di::ServiceProvider;
 use game_loop::winit::event::{ElementState, Event, WindowEvent};
+use kson::Side;
+use poll_promise::Promise;
 use tealr::{
     mlu::{
-        mlua::{self, AppDataRef, Function, Lua},
+        mlua::{self, AppDataRef, Function, Lua, LuaSerdeExt, Table},
         ExportInstances, TealData, UserData, UserDataProxy,
     },
     ToTypename,
 };
 
 use crate::{
-    button_codes::{LaserState, UscInputEvent},
+    build_info,
+    button_codes::{LaserState, UscButton, UscInputEvent},
     companion_interface::GameState,
+    config::GameConfig,
+    confirm_dialog::ConfirmDialog,
+    log_result,
     lua_service::LuaProvider,
+    play_history::{HistoryEntry, PlayHistory},
+    play_stats::{PlayStats, StatsTracker},
     scene::Scene,
     ControlMessage,
 };
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MainMenuButton {
     Start,
     Downloads,
@@ -33,6 +43,53 @@ pub enum MainMenuButton {
     Challenges,
 }
 
+impl FromStr for MainMenuButton {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "start" => Self::Start,
+            "downloads" => Self::Downloads,
+            "multiplayer" => Self::Multiplayer,
+            "settings" => Self::Options,
+            "exit" => Self::Exit,
+            "update" => Self::Update,
+            "challenges" => Self::Challenges,
+            _ => bail!("Unknown menu action: {s}"),
+        })
+    }
+}
+
+/// Requests sent from the title screen script
+#[derive(Debug)]
+enum MenuRequest {
+    Button(MainMenuButton),
+    /// Press the entry of the `menu_buttons` table at the index
+    Press(usize),
+    OpenUrl(String),
+    CheckUpdates,
+    Stats,
+}
+
+impl FromStr for MenuRequest {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "check_updates" => Ok(Self::CheckUpdates),
+            "stats" => Ok(Self::Stats),
+            _ => s.parse().map(Self::Button),
+        }
+    }
+}
+
+fn send_request(lua: &Lua, request: MenuRequest) -> mlua::Result<()> {
+    let s: AppDataRef<Sender<MenuRequest>> = lua
+        .app_data_ref()
+        .ok_or(mlua::Error::external("Button app data not set"))?;
+    s.send(request).map_err(mlua::Error::external)
+}
+
 #[derive(Debug, UserData, ToTypename)]
 struct Bindings;
 
@@ -51,51 +108,102 @@ impl TealData for Bindings {
         m_luaBinds->AddFunction("Update", this, &TitleScreen_Impl::lUpdate);
          */
 
-        methods.add_function("Start", |lua, ()| {
-            let s: AppDataRef<Sender<MainMenuButton>> = lua
-                .app_data_ref()
-                .ok_or(mlua::Error::external("Button app data not set"))?;
-            s.send(MainMenuButton::Start).map_err(Error::external)
-        });
-        methods.add_function("DLScreen", |lua, ()| {
-            let s: AppDataRef<Sender<MainMenuButton>> = lua
-                .app_data_ref()
-                .ok_or(mlua::Error::external("Button app data not set"))?;
-            s.send(MainMenuButton::Downloads).map_err(Error::external)
-        });
-        methods.add_function("Multiplayer", |lua, ()| {
-            let s: AppDataRef<Sender<MainMenuButton>> = lua
-                .app_data_ref()
-                .ok_or(mlua::Error::external("Button app data not set"))?;
-            s.send(MainMenuButton::Multiplayer).map_err(Error::external)
-        });
-        methods.add_function("Exit", |lua, ()| {
-            let s: AppDataRef<Sender<MainMenuButton>> = lua
-                .app_data_ref()
-                .ok_or(mlua::Error::external("Button app data not set"))?;
-            s.send(MainMenuButton::Exit).map_err(Error::external)
+        // Kept for scripts written for USC
+        for (name, button) in [
+            ("Start", MainMenuButton::Start),
+            ("DLScreen", MainMenuButton::Downloads),
+            ("Multiplayer", MainMenuButton::Multiplayer),
+            ("Exit", MainMenuButton::Exit),
+            ("Settings", MainMenuButton::Options),
+            ("Update", MainMenuButton::Update),
+            ("Challenges", MainMenuButton::Challenges),
+        ] {
+            methods.add_function(name, move |lua, ()| {
+                send_request(lua, MenuRequest::Button(button))
+            });
+        }
+
+        methods.add_function("Action", |lua, action: String| {
+            send_request(lua, action.parse().map_err(Error::external)?)
         });
-        methods.add_function("Settings", |lua, ()| {
-            let s: AppDataRef<Sender<MainMenuButton>> = lua
-                .app_data_ref()
-                .ok_or(mlua::Error::external("Button app data not set"))?;
-            s.send(MainMenuButton::Options).map_err(Error::external)
+        methods.add_function("Press", |lua, index: usize| {
+            send_request(lua, MenuRequest::Press(index))
         });
-        methods.add_function("Update", |lua, ()| {
-            let s: AppDataRef<Sender<MainMenuButton>> = lua
-                .app_data_ref()
-                .ok_or(mlua::Error::external("Button app data not set"))?;
-            s.send(MainMenuButton::Update).map_err(Error::external)
+        methods.add_function("OpenUrl", |lua, url: String| {
+            send_request(lua, MenuRequest::OpenUrl(url))
         });
-        methods.add_function("Challenges", |lua, ()| {
-            let s: AppDataRef<Sender<MainMenuButton>> = lua
-                .app_data_ref()
-                .ok_or(mlua::Error::external("Button app data not set"))?;
-            s.send(MainMenuButton::Challenges).map_err(Error::external)
+        methods.add_function("CheckUpdates", |lua, ()| {
+            send_request(lua, MenuRequest::CheckUpdates)
         });
+        methods.add_function("Stats", |lua, ()| send_request(lua, MenuRequest::Stats));
     }
 }
 
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(8);
+
+struct Notification {
+    text: String,
+    url: Option<String>,
+    shown: SystemTime,
+}
+
+struct LatestVersion {
+    version: String,
+    url: Option<String>,
+}
+
+/// Reads the latest version from a JSON object with a `version` or `tag_name` field, like the
+/// GitHub releases API returns
+fn fetch_latest_version(url: &str) -> Result<LatestVersion> {
+    let response: serde_json::Value = reqwest::blocking::get(url)?.error_for_status()?.json()?;
+    let version = ["version", "tag_name"]
+        .iter()
+        .find_map(|key| response[key].as_str())
+        .ok_or(anyhow!("No version in update response"))?;
+    let url = ["url", "html_url"]
+        .iter()
+        .find_map(|key| response[key].as_str())
+        .map(str::to_string);
+
+    Ok(LatestVersion {
+        version: version.trim_start_matches('v').to_string(),
+        url,
+    })
+}
+
+/// Days with plays shown in the play statistics
+const HISTORY_DAYS: usize = 7;
+/// Last played charts shown in the play statistics
+const HISTORY_CHARTS: usize = 5;
+
+#[derive(Debug, Default)]
+struct HistoryStats {
+    days: Vec<(chrono::NaiveDate, u32)>,
+    /// Best scores over time of the last played charts
+    charts: Vec<(HistoryEntry, Vec<u32>)>,
+}
+
+fn format_play_time(ms: u64) -> String {
+    let minutes = ms / 60_000;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+fn open_url(url: &str) -> Result<()> {
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        bail!("Not a web url: {url}");
+    }
+
+    #[cfg(target_os = "windows")]
+    let mut command = Command::new("explorer");
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = Command::new("xdg-open");
+
+    command.arg(url).spawn()?;
+    Ok(())
+}
+
 #[derive(Debug, Default)]
 struct ExportBindings;
 impl ExportInstances for ExportBindings {
@@ -110,8 +218,14 @@ impl ExportInstances for ExportBindings {
 
 pub struct MainMenu {
     lua: Rc<Lua>,
-    button_rx: Receiver<MainMenuButton>,
+    button_rx: Receiver<MenuRequest>,
     control_tx: Option<Sender<ControlMessage>>,
+    confirm: Option<ConfirmDialog<MainMenu>>,
+    stats_open: bool,
+    /// Queried from the play history when the stats are opened
+    history_stats: HistoryStats,
+    notification: Option<Notification>,
+    update_check: Option<Promise<Result<LatestVersion>>>,
     should_suspended: bool,
     suspended: bool,
     service_provider: ServiceProvider,
@@ -123,14 +237,142 @@ impl MainMenu {
         let (tx, button_rx) = std::sync::mpsc::channel();
         lua.set_app_data(tx);
         tealr::mlu::set_global_env(ExportBindings, &lua).expect("Failed to set menu bindings");
-        Self {
+        let mut menu = Self {
             lua,
             button_rx,
             control_tx: None,
+            confirm: None,
+            stats_open: false,
+            history_stats: HistoryStats::default(),
+            notification: None,
+            update_check: None,
             suspended: false,
             should_suspended: false,
             service_provider,
+        };
+
+        if let Some(warning) = GameConfig::get_mut().load_warning.take() {
+            menu.notify(warning, None);
+        }
+        menu
+    }
+
+    fn send_button(&self, button: MainMenuButton) -> Result<()> {
+        log::info!("Pressed: {:?}", &button);
+        if button == MainMenuButton::Exit && GameConfig::get().cab_mode.enabled {
+            log::info!("Exit is only available from the service menu in cab mode");
+            return Ok(());
         }
+        self.control_tx
+            .as_ref()
+            .ok_or(anyhow!("control_tx not set"))?
+            .send(ControlMessage::MainMenu(button))
+            .map_err(|_| anyhow!("Failed to send button"))
+    }
+
+    fn notify(&mut self, text: String, url: Option<String>) {
+        self.notification = Some(Notification {
+            text,
+            url,
+            shown: SystemTime::now(),
+        });
+    }
+
+    /// Stats of the current session and of all sessions
+    fn play_stats(&self) -> (PlayStats, PlayStats) {
+        self.service_provider
+            .get_mut::<StatsTracker>()
+            .map(|t| {
+                let tracker = t.read().expect("Lock error");
+                (tracker.session(), tracker.total())
+            })
+            .unwrap_or_default()
+    }
+
+    fn history_stats(&self) -> HistoryStats {
+        self.service_provider
+            .get_mut::<PlayHistory>()
+            .map(|h| {
+                let history = h.read().expect("Lock error");
+                HistoryStats {
+                    days: history.plays_per_day(HISTORY_DAYS),
+                    charts: history.recent_progressions(HISTORY_CHARTS),
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    /// Sets the `play_stats` global to `{ session = ..., total = ... }`
+    fn update_lua_stats(&self) -> Result<()> {
+        let (session, total) = self.play_stats();
+        let stats = self.lua.create_table()?;
+        stats.set("session", self.lua.to_value(&session)?)?;
+        stats.set("total", self.lua.to_value(&total)?)?;
+        self.lua.globals().set("play_stats", stats)?;
+        Ok(())
+    }
+
+    /// Reads the request of an entry in the `menu_buttons` table, declared as
+    /// `{ label = "...", action = "start" }`, `{ label = "...", url = "https://..." }` or
+    /// `{ label = "...", callback = function() ... end }`
+    fn declared_request(button: &Table) -> Result<Option<MenuRequest>> {
+        if let Some(url) = button.get::<_, Option<String>>("url")? {
+            Ok(Some(MenuRequest::OpenUrl(url)))
+        } else if let Some(action) = button.get::<_, Option<String>>("action")? {
+            Ok(Some(action.parse()?))
+        } else if button.contains_key("callback")? {
+            Ok(None)
+        } else {
+            bail!("Menu button has no action, url or callback")
+        }
+    }
+
+    /// Warns about declared buttons that can't be pressed
+    fn validate_buttons(&self) -> Result<()> {
+        let Some(buttons) = self.lua.globals().get::<_, Option<Table>>("menu_buttons")? else {
+            return Ok(());
+        };
+
+        for (i, button) in buttons.sequence_values::<Table>().enumerate() {
+            if let Err(e) = Self::declared_request(&button?) {
+                log::warn!("menu_buttons[{}]: {}", i + 1, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn press(&mut self, index: usize) -> Result<()> {
+        let buttons: Table = self.lua.globals().get("menu_buttons")?;
+        let button: Table = buttons
+            .get::<_, Option<Table>>(index)?
+            .ok_or(anyhow!("No menu button at {index}"))?;
+
+        match Self::declared_request(&button)? {
+            Some(request) => self.handle_request(request),
+            None => Ok(button.get::<_, Function>("callback")?.call(())?),
+        }
+    }
+
+    fn handle_request(&mut self, request: MenuRequest) -> Result<()> {
+        match request {
+            MenuRequest::Button(button) => self.send_button(button)?,
+            MenuRequest::Press(index) => self.press(index)?,
+            MenuRequest::OpenUrl(url) => open_url(&url)?,
+            MenuRequest::CheckUpdates => match GameConfig::get().update_url.clone() {
+                Some(url) if self.update_check.is_none() => {
+                    self.update_check = Some(Promise::spawn_thread("Check updates", move || {
+                        fetch_latest_version(&url)
+                    }))
+                }
+                Some(_) => {}
+                None => self.notify("No update url configured".into(), None),
+            },
+            MenuRequest::Stats => {
+                self.history_stats = self.history_stats();
+                self.stats_open = true;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -142,36 +384,172 @@ impl Scene for MainMenu {
     }
 
     fn init(&mut self, app_control_tx: Sender<ControlMessage>) -> anyhow::Result<()> {
+        self.lua
+            .globals()
+            .set("game_version", build_info::version())?;
         self.service_provider
             .get_required::<LuaProvider>()
             .register_libraries(self.lua.clone(), "titlescreen.lua")?;
         self.control_tx = Some(app_control_tx);
+        self.update_lua_stats()?;
+        self.validate_buttons()
+    }
+
+    fn has_egui(&self) -> bool {
+        !self.suspended
+            && (self.confirm.is_some() || self.stats_open || self.notification.is_some())
+    }
+
+    fn render_egui(&mut self, ctx: &egui::Context) -> anyhow::Result<()> {
+        ConfirmDialog::show_in(self, |m| &mut m.confirm, ctx);
+
+        if self.stats_open {
+            let (session, total) = self.play_stats();
+            egui::Window::new("Play statistics")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .open(&mut self.stats_open)
+                .show(ctx, |ui| {
+                    egui::Grid::new("play_stats").striped(true).show(ui, |ui| {
+                        ui.label("");
+                        ui.strong("Session");
+                        ui.strong("All time");
+                        ui.end_row();
+
+                        let rows: [(&str, fn(&PlayStats) -> String); 4] = [
+                            ("Play time", |s| format_play_time(s.play_time_ms)),
+                            ("Plays", |s| s.plays.to_string()),
+                            ("Clears", |s| s.clears.to_string()),
+                            ("Average accuracy", |s| {
+                                format!("{:.2}%", s.average_accuracy * 100.0)
+                            }),
+                        ];
+                        for (name, value) in rows {
+                            ui.label(name);
+                            ui.label(value(&session));
+                            ui.label(value(&total));
+                            ui.end_row();
+                        }
+                    });
+
+                    let history = &self.history_stats;
+                    if !history.days.is_empty() {
+                        ui.separator();
+                        ui.strong("Plays per day");
+                        egui::Grid::new("plays_per_day").show(ui, |ui| {
+                            for (day, plays) in &history.days {
+                                ui.label(day.format("%Y-%m-%d").to_string());
+                                ui.label(plays.to_string());
+                                ui.end_row();
+                            }
+                        });
+                    }
+                    if !history.charts.is_empty() {
+                        ui.separator();
+                        ui.strong("Best score progression");
+                        egui::Grid::new("score_progression").show(ui, |ui| {
+                            for (chart, progression) in &history.charts {
+                                ui.label(format!("{} ({})", chart.title, chart.level));
+                                ui.label(
+                                    progression
+                                        .iter()
+                                        .map(u32::to_string)
+                                        .collect::<Vec<_>>()
+                                        .join(" → "),
+                                );
+                                ui.end_row();
+                            }
+                        });
+                    }
+                });
+        }
+
+        if let Some(notification) = &self.notification {
+            let mut open_link = false;
+            let mut dismiss = false;
+            egui::Window::new("Notification")
+                .title_bar(false)
+                .resizable(false)
+                .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+                .show(ctx, |ui| {
+                    ui.label(&notification.text);
+                    ui.horizontal(|ui| {
+                        if notification.url.is_some() {
+                            open_link = ui.button("View").clicked();
+                        }
+                        dismiss = ui.button("Close").clicked();
+                    });
+                });
+
+            if open_link {
+                if let Some(url) = &notification.url {
+                    open_url(url)?;
+                }
+            }
+            if dismiss || open_link {
+                self.notification = None;
+            }
+        }
+
         Ok(())
     }
 
     fn game_state(&self) -> crate::companion_interface::GameState {
-        GameState::TitleScreen
+        GameState::TitleScreen {
+            session_stats: self.play_stats().0,
+        }
     }
 
-    fn tick(&mut self, _dt: f64, _knob_state: LaserState) -> Result<()> {
+    fn tick(&mut self, _dt: f64, knob_state: LaserState) -> Result<()> {
         if self.should_suspended {
             self.suspended = true;
             self.should_suspended = false;
         }
 
-        while let Ok(button) = self.button_rx.try_recv() {
-            log::info!("Pressed: {:?}", &button);
-            self.control_tx
-                .as_ref()
-                .ok_or(anyhow!("control_tx not set"))?
-                .send(ControlMessage::MainMenu(button))
-                .map_err(|_| anyhow!("Failed to send button"))?;
+        if let Some(confirm) = &mut self.confirm {
+            confirm.on_knob(
+                knob_state.get_axis(Side::Left).delta + knob_state.get_axis(Side::Right).delta,
+            );
+        }
+
+        while let Ok(request) = self.button_rx.try_recv() {
+            if let Err(e) = self.handle_request(request) {
+                log::error!("{}", e);
+            }
+        }
+
+        if let Some(check) = self.update_check.take() {
+            match check.try_take() {
+                Ok(Ok(latest)) => {
+                    let text = if latest.version == build_info::VERSION {
+                        format!("You are running the latest version ({})", latest.version)
+                    } else {
+                        format!("Version {} is available", latest.version)
+                    };
+                    self.notify(text, latest.url)
+                }
+                Ok(Err(e)) => self.notify(format!("Failed to check for updates: {e}"), None),
+                Err(check) => self.update_check = Some(check),
+            }
+        }
+
+        if self
+            .notification
+            .as_ref()
+            .is_some_and(|n| n.shown.elapsed().unwrap_or_default() > NOTIFICATION_DURATION)
+        {
+            self.notification = None;
         }
 
         Ok(())
     }
 
     fn on_event(&mut self, event: &Event<UscInputEvent>) {
+        if self.confirm.is_some() {
+            return;
+        }
+
         if let Event::WindowEvent {
             event:
                 WindowEvent::MouseInput {
@@ -197,11 +575,35 @@ impl Scene for MainMenu {
         }
     }
 
-    fn on_button_pressed(
-        &mut self,
-        button: crate::button_codes::UscButton,
-        _timestamp: SystemTime,
-    ) {
+    fn on_button_pressed(&mut self, button: UscButton, _timestamp: SystemTime) {
+        if ConfirmDialog::route_button(self, |m| &mut m.confirm, button) {
+            return;
+        }
+
+        if self.stats_open {
+            if button == UscButton::Back {
+                self.stats_open = false;
+            }
+            return;
+        }
+
+        if button == UscButton::Back {
+            // Cabs only exit through the service menu
+            if !GameConfig::get().cab_mode.enabled {
+                self.confirm = Some(ConfirmDialog::new(
+                    "Exit the game?",
+                    "Exit",
+                    "Cancel",
+                    |menu: &mut MainMenu, exit| {
+                        if exit {
+                            log_result!(menu.send_button(MainMenuButton::Exit));
+                        }
+                    },
+                ));
+            }
+            return;
+        }
+
         if let Ok(button_pressed) = self.lua.globals().get::<_, Function>("button_pressed") {
             if let Some(e) = button_pressed.call::<u8, ()>(button.into()).err() {
                 log::error!("{:?}", e);
@@ -219,6 +621,7 @@ impl Scene for MainMenu {
 
     fn resume(&mut self) {
         self.suspended = false;
+        log_result!(self.update_lua_stats());
     }
 
     fn debug_ui(&mut self, _ctx: &egui::Context) -> anyhow::Result<()> {
@@ -0,0 +1,137 @@
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use anyhow::{bail, Result};
+
+/// Set while a maintenance task runs, only one may run at a time
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Data kept on disk to speed things up, which can be measured, thrown away and created again
+pub trait ManagedCache: Send {
+    fn name(&self) -> &'static str;
+    /// Bytes the cache takes up on disk
+    fn size(&self) -> Result<u64>;
+    fn clear(&self) -> Result<()>;
+    /// Clears the cache and fills it again. Caches that are filled when their entries are first
+    /// needed are only cleared.
+    fn rebuild(&self, _progress: &TaskProgress) -> Result<()> {
+        self.clear()
+    }
+}
+
+/// Progress of a running maintenance task, shared with the UI showing it
+#[derive(Debug, Default, Clone)]
+pub struct TaskProgress(Arc<Mutex<(String, Option<f32>)>>);
+
+impl TaskProgress {
+    /// `fraction` is `None` when the task can't tell how far along it is
+    pub fn set(&self, text: impl Into<String>, fraction: Option<f32>) {
+        *self.0.lock().expect("Lock error") = (text.into(), fraction);
+    }
+
+    pub fn get(&self) -> (String, Option<f32>) {
+        self.0.lock().expect("Lock error").clone()
+    }
+}
+
+/// Marks a maintenance task as running until it is dropped
+#[derive(Debug)]
+pub struct TaskGuard(());
+
+impl TaskGuard {
+    /// Fails if another task is still running
+    pub fn acquire() -> Result<Self> {
+        if RUNNING.swap(true, Ordering::AcqRel) {
+            bail!("Another maintenance task is still running");
+        }
+        Ok(Self(()))
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::Release);
+    }
+}
+
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::Acquire)
+}
+
+/// Total size of the files directly in `dir` accepted by `filter`, a missing folder is empty
+pub fn dir_size(dir: &Path, filter: impl Fn(&Path) -> bool) -> Result<u64> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut size = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if meta.is_file() && filter(&entry.path()) {
+            size += meta.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Removes the files directly in `dir` accepted by `filter`, returns how many were removed
+pub fn clear_dir(dir: &Path, filter: impl Fn(&Path) -> bool) -> Result<usize> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && filter(&path) {
+            std::fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_size, is_running, TaskGuard};
+
+    #[test]
+    fn sizes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024 * 1024), "5120.0 GiB");
+    }
+
+    #[test]
+    fn one_task_at_a_time() {
+        let guard = TaskGuard::acquire().unwrap();
+        assert!(is_running());
+        assert!(TaskGuard::acquire().is_err());
+        drop(guard);
+        assert!(!is_running());
+        assert!(TaskGuard::acquire().is_ok());
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read, Seek},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use rodio::{buffer::SamplesBuffer, Source};
+
+use crate::{audio::MixerExt, config::GameConfig, RuscMixer};
+
+/// Gain change per 10ms update of the menu sources, a full fade takes about 300ms
+const DUCK_STEP: f32 = 1.0 / 30.0;
+
+/// Fades the song select preview and BGM out while a song loads.
+///
+/// Song select applies it to its sources, the song transition ducks it when it starts and
+/// restores it if the song can't be loaded. Song select restores it when it's shown again.
+#[derive(Debug, Default)]
+pub struct MenuAudio {
+    ducked: AtomicBool,
+    /// Navigation sounds requested since the last frame
+    nav_queue: Mutex<Vec<NavSound>>,
+}
+
+impl MenuAudio {
+    pub fn duck(&self) {
+        self.ducked.store(true, Ordering::Relaxed);
+    }
+
+    pub fn restore(&self) {
+        self.ducked.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_ducked(&self) -> bool {
+        self.ducked.load(Ordering::Relaxed)
+    }
+
+    /// Moves `gain` one 10ms step towards silence while ducked and back to full volume otherwise
+    pub fn step_gain(&self, gain: &mut f32) {
+        let target = if self.is_ducked() { 0.0 } else { 1.0 };
+        *gain += (target - *gain).clamp(-DUCK_STEP, DUCK_STEP);
+    }
+
+    /// Requests a navigation sound, it's played at the end of the frame if enabled
+    pub fn nav(&self, sound: NavSound) {
+        self.nav_queue.lock().expect("Lock error").push(sound);
+    }
+
+    /// Navigation sounds requested since the last call, each sound only once
+    pub fn take_nav(&self) -> Vec<NavSound> {
+        let mut queue = std::mem::take(&mut *self.nav_queue.lock().expect("Lock error"));
+        let mut seen = vec![];
+        queue.retain(|s| {
+            let first = !seen.contains(s);
+            seen.push(*s);
+            first
+        });
+        queue
+    }
+}
+
+/// Menu sounds played by the game for skins that don't play their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NavSound {
+    /// Selection moved on a wheel
+    Tick,
+    Confirm,
+    Cancel,
+    DialogOpen,
+    DialogClose,
+}
+
+impl NavSound {
+    /// Name of the sample in the audio folder of a skin, which replaces the bundled one
+    fn file_name(self) -> &'static str {
+        match self {
+            NavSound::Tick => "nav_tick.wav",
+            NavSound::Confirm => "nav_confirm.wav",
+            NavSound::Cancel => "nav_cancel.wav",
+            NavSound::DialogOpen => "nav_dialog_open.wav",
+            NavSound::DialogClose => "nav_dialog_close.wav",
+        }
+    }
+
+    fn bundled(self) -> &'static [u8] {
+        match self {
+            NavSound::Tick => include_bytes!("static_assets/nav_sounds/tick.wav"),
+            NavSound::Confirm => include_bytes!("static_assets/nav_sounds/confirm.wav"),
+            NavSound::Cancel => include_bytes!("static_assets/nav_sounds/cancel.wav"),
+            NavSound::DialogOpen => include_bytes!("static_assets/nav_sounds/dialog_open.wav"),
+            NavSound::DialogClose => include_bytes!("static_assets/nav_sounds/dialog_close.wav"),
+        }
+    }
+}
+
+struct Sample {
+    channels: u16,
+    sample_rate: u32,
+    data: Vec<f32>,
+}
+
+impl Sample {
+    fn decode(reader: impl Read + Seek + Send + Sync + 'static) -> anyhow::Result<Self> {
+        let decoder = rodio::Decoder::new(reader)?;
+        Ok(Self {
+            channels: decoder.channels(),
+            sample_rate: decoder.sample_rate(),
+            data: decoder.convert_samples().collect(),
+        })
+    }
+
+    fn load(skin: &Path, sound: NavSound) -> anyhow::Result<Self> {
+        let path = skin.join("audio").join(sound.file_name());
+        if path.exists() {
+            Self::decode(std::fs::File::open(path)?)
+        } else {
+            Self::decode(Cursor::new(sound.bundled()))
+        }
+    }
+}
+
+/// Decoded navigation sounds of the current skin
+#[derive(Default)]
+pub struct NavSamples {
+    skin: String,
+    samples: HashMap<NavSound, Option<Sample>>,
+}
+
+impl NavSamples {
+    /// Plays `sounds` unless navigation sounds are disabled or the skin played a sample of its
+    /// own this frame, skins that play their own sounds keep full control
+    pub fn play(&mut self, mixer: &RuscMixer, sounds: &[NavSound], skin_played: bool) {
+        let config = GameConfig::get();
+        if sounds.is_empty() || skin_played || !config.nav_sounds {
+            return;
+        }
+
+        if self.skin != config.skin {
+            self.skin.clone_from(&config.skin);
+            self.samples.clear();
+        }
+
+        let skin = config.game_folder.join("skins").join(&config.skin);
+        for sound in sounds {
+            let sample = self.samples.entry(*sound).or_insert_with(|| {
+                Sample::load(&skin, *sound)
+                    .map_err(|e| log::warn!("Could not load {}: {e}", sound.file_name()))
+                    .ok()
+            });
+
+            if let Some(sample) = sample {
+                mixer.add_resampled(SamplesBuffer::new(
+                    sample.channels,
+                    sample.sample_rate,
+                    sample.data.clone(),
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MenuAudio, NavSound};
+
+    #[test]
+    fn duck_and_restore() {
+        let audio = MenuAudio::default();
+        let mut gain = 1.0;
+        audio.step_gain(&mut gain);
+        assert_eq!(gain, 1.0);
+
+        audio.duck();
+        for _ in 0..29 {
+            audio.step_gain(&mut gain);
+        }
+        assert!(gain > 0.0);
+        audio.step_gain(&mut gain);
+        audio.step_gain(&mut gain);
+        assert_eq!(gain, 0.0);
+
+        // A cancelled load ramps back up instead of jumping
+        audio.restore();
+        audio.step_gain(&mut gain);
+        assert!(gain > 0.0 && gain < 0.1);
+        for _ in 0..40 {
+            audio.step_gain(&mut gain);
+        }
+        assert_eq!(gain, 1.0);
+    }
+
+    #[test]
+    fn nav_sounds_once_per_frame() {
+        let audio = MenuAudio::default();
+        audio.nav(NavSound::Tick);
+        audio.nav(NavSound::Confirm);
+        audio.nav(NavSound::Tick);
+        assert_eq!(audio.take_nav(), [NavSound::Tick, NavSound::Confirm]);
+        assert!(audio.take_nav().is_empty());
+    }
+}
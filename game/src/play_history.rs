@@ -0,0 +1,348 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, RwLock,
+    },
+};
+
+use chrono::{Local, NaiveDate, TimeZone};
+use di::{inject, injectable};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::config::GameConfig;
+
+/// The log is rotated once it's larger than this
+const MAX_LOG_SIZE: u64 = 8 * 1024 * 1024;
+/// Rotated logs kept next to the current one, the oldest is removed when another one is rotated
+const ROTATED_LOGS: usize = 9;
+
+/// One play that reached the result screen, a line of the history log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    /// Unix timestamp in seconds
+    pub timestamp: i64,
+    pub chart_hash: String,
+    pub title: String,
+    pub level: u8,
+    pub score: u32,
+    pub gauge_type: u8,
+    pub gauge_option: i32,
+    pub gauge: f32,
+    pub mirror: bool,
+    pub random: bool,
+    pub hard_random: bool,
+    pub seed: Option<u32>,
+    pub autoplay: bool,
+    pub badge: u8,
+    pub perfects: i32,
+    pub goods: i32,
+    pub misses: i32,
+    pub max_combo: i32,
+    pub earlies: i32,
+    pub lates: i32,
+    /// Same for every play since the game was started
+    pub session: String,
+}
+
+/// Every play that reached the result screen, appended to `play_history.ndjson` in the game
+/// folder. Unlike the scores it keeps all plays, including the ones that weren't a best.
+///
+/// The log is read and written on its own thread, the entries are kept in memory for queries.
+pub struct PlayHistory {
+    session: String,
+    /// Oldest first
+    entries: Arc<RwLock<Vec<HistoryEntry>>>,
+    writes: Sender<HistoryEntry>,
+}
+
+#[injectable]
+impl PlayHistory {
+    #[inject]
+    pub fn new() -> Self {
+        let dir = GameConfig::get().game_folder.clone();
+        let entries = Arc::new(RwLock::new(vec![]));
+        let (writes, write_rx) = channel();
+
+        let loaded = entries.clone();
+        let spawned = std::thread::Builder::new()
+            .name("play history".into())
+            .spawn(move || run(&dir, &loaded, write_rx));
+        if let Err(e) = spawned {
+            warn!("Could not start the play history: {e}");
+        }
+
+        Self {
+            session: format!(
+                "{}-{}",
+                Local::now().format("%Y%m%d%H%M%S"),
+                std::process::id()
+            ),
+            entries,
+            writes,
+        }
+    }
+
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    pub fn record(&self, entry: HistoryEntry) {
+        self.entries
+            .write()
+            .expect("Lock error")
+            .push(entry.clone());
+        if self.writes.send(entry).is_err() {
+            warn!("Play history isn't running, the play isn't saved");
+        }
+    }
+
+    /// Last `count` plays of a chart, oldest first
+    pub fn recent(&self, chart_hash: &str, count: usize) -> Vec<HistoryEntry> {
+        let entries = self.entries.read().expect("Lock error");
+        let mut recent = entries
+            .iter()
+            .rev()
+            .filter(|e| e.chart_hash == chart_hash)
+            .take(count)
+            .cloned()
+            .collect::<Vec<_>>();
+        recent.reverse();
+        recent
+    }
+
+    /// Plays of the last `days` days that had any, newest first
+    pub fn plays_per_day(&self, days: usize) -> Vec<(NaiveDate, u32)> {
+        let entries = self.entries.read().expect("Lock error");
+        plays_per_day(&entries)
+            .into_iter()
+            .rev()
+            .take(days)
+            .collect()
+    }
+
+    /// Best score reached over time on the charts played last, the most recent chart first
+    pub fn recent_progressions(&self, charts: usize) -> Vec<(HistoryEntry, Vec<u32>)> {
+        let entries = self.entries.read().expect("Lock error");
+        let mut seen = vec![];
+        entries
+            .iter()
+            .rev()
+            .filter(|e| {
+                let new = !seen.contains(&&e.chart_hash);
+                if new {
+                    seen.push(&e.chart_hash);
+                }
+                new
+            })
+            .take(charts)
+            .map(|e| (e.clone(), score_progression(&entries, &e.chart_hash)))
+            .collect()
+    }
+}
+
+impl Default for PlayHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Plays by local date, oldest first. Autoplay isn't counted.
+pub fn plays_per_day(entries: &[HistoryEntry]) -> Vec<(NaiveDate, u32)> {
+    let mut days = BTreeMap::new();
+    for entry in entries.iter().filter(|e| !e.autoplay) {
+        let Some(time) = Local.timestamp_opt(entry.timestamp, 0).single() else {
+            continue;
+        };
+        *days.entry(time.date_naive()).or_insert(0) += 1;
+    }
+    days.into_iter().collect()
+}
+
+/// Best score after each play of a chart that improved it, autoplay isn't counted
+pub fn score_progression(entries: &[HistoryEntry], chart_hash: &str) -> Vec<u32> {
+    let mut best = None;
+    entries
+        .iter()
+        .filter(|e| e.chart_hash == chart_hash && !e.autoplay)
+        .filter_map(|e| {
+            if best.is_some_and(|b| e.score <= b) {
+                return None;
+            }
+            best = Some(e.score);
+            best
+        })
+        .collect()
+}
+
+fn log_path(dir: &Path, index: usize) -> PathBuf {
+    match index {
+        0 => dir.join("play_history.ndjson"),
+        i => dir.join(format!("play_history.{i}.ndjson")),
+    }
+}
+
+/// Reads the entries of a log, broken lines are skipped
+fn read_log(reader: impl BufRead) -> Vec<HistoryEntry> {
+    let mut broken = 0;
+    // Split on bytes so a line that isn't valid UTF-8 doesn't end the log early
+    let entries = reader
+        .split(b'\n')
+        .map_while(Result::ok)
+        .filter(|line| !line.trim_ascii().is_empty())
+        .filter_map(|line| serde_json::from_slice(&line).map_err(|_| broken += 1).ok())
+        .collect();
+    if broken > 0 {
+        warn!("Skipped {broken} broken play history entries");
+    }
+    entries
+}
+
+/// Reads the current and rotated logs, oldest first
+fn load(dir: &Path) -> Vec<HistoryEntry> {
+    (0..=ROTATED_LOGS)
+        .rev()
+        .filter_map(|i| File::open(log_path(dir, i)).ok())
+        .flat_map(|file| read_log(BufReader::new(file)))
+        .collect()
+}
+
+fn rotate(dir: &Path) -> std::io::Result<()> {
+    let oldest = log_path(dir, ROTATED_LOGS);
+    if oldest.exists() {
+        std::fs::remove_file(oldest)?;
+    }
+    for i in (0..ROTATED_LOGS).rev() {
+        let path = log_path(dir, i);
+        if path.exists() {
+            std::fs::rename(path, log_path(dir, i + 1))?;
+        }
+    }
+    Ok(())
+}
+
+fn append(dir: &Path, entry: &HistoryEntry) -> anyhow::Result<()> {
+    let path = log_path(dir, 0);
+    if std::fs::metadata(&path).is_ok_and(|m| m.len() >= MAX_LOG_SIZE) {
+        info!("Rotating the play history");
+        rotate(dir)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)?;
+    // A line cut off by a crash would swallow the entry
+    let mut line = if ends_with_newline(&mut file)? {
+        vec![]
+    } else {
+        vec![b'\n']
+    };
+    serde_json::to_writer(&mut line, entry)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    Ok(())
+}
+
+/// Whether the file is empty or ends with a newline
+fn ends_with_newline(file: &mut File) -> std::io::Result<bool> {
+    if file.metadata()?.len() == 0 {
+        return Ok(true);
+    }
+    let mut last = [0];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+fn run(dir: &Path, entries: &RwLock<Vec<HistoryEntry>>, write_rx: Receiver<HistoryEntry>) {
+    {
+        let loaded = load(dir);
+        let mut entries = entries.write().expect("Lock error");
+        // Plays recorded while loading are newer
+        let recorded = std::mem::replace(&mut *entries, loaded);
+        entries.extend(recorded);
+    }
+
+    for entry in write_rx {
+        if let Err(e) = append(dir, &entry) {
+            warn!("Could not save the play to the history: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append, load, log_path, plays_per_day, score_progression, HistoryEntry};
+
+    fn entry(timestamp: i64, chart_hash: &str, score: u32) -> HistoryEntry {
+        HistoryEntry {
+            timestamp,
+            chart_hash: chart_hash.into(),
+            title: String::new(),
+            level: 1,
+            score,
+            gauge_type: 0,
+            gauge_option: 0,
+            gauge: 0.7,
+            mirror: false,
+            random: false,
+            hard_random: false,
+            seed: None,
+            autoplay: false,
+            badge: 2,
+            perfects: 0,
+            goods: 0,
+            misses: 0,
+            max_combo: 0,
+            earlies: 0,
+            lates: 0,
+            session: "test".into(),
+        }
+    }
+
+    #[test]
+    fn broken_lines_skipped() {
+        let dir = std::env::temp_dir().join(format!("rusc_history_{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        append(&dir, &entry(1, "a", 8_000_000)).unwrap();
+        let path = log_path(&dir, 0);
+        let mut data = std::fs::read(&path).unwrap();
+        // A line that isn't UTF-8, then a line cut off by a crash
+        data.extend_from_slice(b"\xff\xfe\n{\"timestamp\":2,\"chartHa");
+        std::fs::write(&path, data).unwrap();
+        append(&dir, &entry(3, "a", 9_000_000)).unwrap();
+
+        let scores: Vec<_> = load(&dir).iter().map(|e| e.score).collect();
+        assert_eq!(scores, [8_000_000, 9_000_000]);
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn queries() {
+        let day = 24 * 60 * 60;
+        let noon = 1_700_000_000 - 1_700_000_000 % day + day / 2;
+        let entries = [
+            entry(noon, "a", 9_000_000),
+            entry(noon + 60, "b", 9_500_000),
+            entry(noon + 120, "a", 8_000_000),
+            HistoryEntry {
+                autoplay: true,
+                ..entry(noon + day, "a", 10_000_000)
+            },
+            entry(noon + day + 60, "a", 9_200_000),
+        ];
+
+        let days: Vec<_> = plays_per_day(&entries).iter().map(|(_, n)| *n).collect();
+        assert_eq!(days, [3, 1]);
+        assert_eq!(score_progression(&entries, "a"), [9_000_000, 9_200_000]);
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use di::{inject, injectable};
+use log::{error, info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::{
+    config::{write_atomic, GameConfig},
+    worker_service::WorkerService,
+};
+
+/// Changed stats are written at most this often, and when the game closes
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Totals over a set of plays, only plays that reached the end count as plays
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema, Type)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PlayStats {
+    pub plays: u32,
+    pub clears: u32,
+    /// Time spent playing charts, including aborted plays
+    pub play_time_ms: u64,
+    /// Share of the available score that was hit, between 0 and 1
+    pub average_accuracy: f64,
+}
+
+/// One play of a chart as reported by the game scene
+#[derive(Debug, Clone, Copy)]
+pub struct PlayRecord {
+    /// Chart time played, time the chart clock was stopped isn't included
+    pub played: Duration,
+    pub autoplay: bool,
+    /// False if the play was exited or restarted before the end
+    pub finished: bool,
+    pub cleared: bool,
+    pub accuracy: Option<f64>,
+}
+
+impl PlayStats {
+    pub fn add(&mut self, play: &PlayRecord) {
+        if play.autoplay {
+            return;
+        }
+
+        self.play_time_ms += play.played.as_millis() as u64;
+        if !play.finished {
+            return;
+        }
+
+        self.plays += 1;
+        if play.cleared {
+            self.clears += 1;
+        }
+        if let Some(accuracy) = play.accuracy {
+            self.average_accuracy += (accuracy - self.average_accuracy) / self.plays as f64;
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredStats {
+    total: PlayStats,
+}
+
+/// Play statistics of the current session and of all sessions, saved next to the score database
+pub struct StatsTracker {
+    path: PathBuf,
+    session: PlayStats,
+    total: PlayStats,
+    dirty: bool,
+    last_flush: Instant,
+}
+
+impl WorkerService for StatsTracker {
+    fn update(&mut self) {
+        if self.dirty && self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush();
+        }
+    }
+}
+
+#[injectable]
+impl StatsTracker {
+    #[inject]
+    pub fn new() -> Self {
+        let path = GameConfig::get().game_folder.join("play_stats.json");
+        let stored = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice::<StoredStats>(&data).unwrap_or_else(|e| {
+                warn!("Could not read play stats: {e}");
+                StoredStats::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => StoredStats::default(),
+            Err(e) => {
+                warn!("Could not read play stats: {e}");
+                StoredStats::default()
+            }
+        };
+
+        Self {
+            path,
+            session: PlayStats::default(),
+            total: stored.total,
+            dirty: false,
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, play: PlayRecord) {
+        if play.autoplay {
+            return;
+        }
+        info!("Recording play: {play:?}");
+        self.session.add(&play);
+        self.total.add(&play);
+        self.dirty = true;
+    }
+
+    pub fn session(&self) -> PlayStats {
+        self.session
+    }
+
+    pub fn total(&self) -> PlayStats {
+        self.total
+    }
+
+    pub fn flush(&mut self) {
+        self.dirty = false;
+        self.last_flush = Instant::now();
+        let stored = StoredStats { total: self.total };
+        if let Err(e) = serde_json::to_vec_pretty(&stored)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| write_atomic(&self.path, &data))
+        {
+            error!("Could not save play stats: {e}");
+        }
+    }
+}
+
+impl Drop for StatsTracker {
+    fn drop(&mut self) {
+        if self.dirty {
+            self.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{PlayRecord, PlayStats};
+
+    #[test]
+    fn play_stats() {
+        let play = |finished, cleared, accuracy| PlayRecord {
+            played: Duration::from_secs(60),
+            autoplay: false,
+            finished,
+            cleared,
+            accuracy: Some(accuracy),
+        };
+        let mut stats = PlayStats::default();
+        stats.add(&play(true, true, 1.0));
+        stats.add(&play(true, false, 0.5));
+        // Aborted plays only add to the play time
+        stats.add(&play(false, false, 0.0));
+        stats.add(&PlayRecord {
+            autoplay: true,
+            ..play(true, true, 1.0)
+        });
+
+        assert_eq!(stats.plays, 2);
+        assert_eq!(stats.clears, 1);
+        assert_eq!(stats.play_time_ms, 180_000);
+        assert!((stats.average_accuracy - 0.75).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,31 @@
+use std::fmt::Display;
+
+use crate::LuaArena;
+
+/// Resources that should return to the same count after a scene has been closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceCounters {
+    pub textures: usize,
+    pub lua_states: usize,
+    pub mixer_sources: usize,
+}
+
+impl ResourceCounters {
+    pub fn collect(lua_arena: &LuaArena) -> Self {
+        Self {
+            textures: crate::shaded_mesh::live_textures(),
+            lua_states: lua_arena.0.len(),
+            mixer_sources: crate::audio::live_sources(),
+        }
+    }
+}
+
+impl Display for ResourceCounters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Textures: {}, Lua states: {}, Mixer sources: {}",
+            self.textures, self.lua_states, self.mixer_sources
+        )
+    }
+}
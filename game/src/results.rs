@@ -5,29 +5,37 @@ use std::{
     time::SystemTime,
 };
 
+use anyhow::anyhow;
 use di::{RefMut, ServiceProvider};
-use kson::score_ticks::ScoreTick;
-use log::warn;
+use kson::{score_ticks::ScoreTick, BtLane, Side};
 use luals_gen::ToLuaLsType;
 use serde::Serialize;
 
 use crate::{
     async_service::AsyncService,
     button_codes::UscButton,
+    companion_interface::{GameState, PlayModifierInfo},
     config::GameConfig,
     game::{
         gauge::{Gauge, GaugeType},
+        modifiers::RandomMode,
         HitRating, HitSummary, HitWindow,
     },
-    game_main::AutoPlay,
+    game_main::{AutoPlay, GameResult},
     help,
     lua_service::LuaProvider,
+    play_history::{HistoryEntry, PlayHistory},
     scene::{Scene, SceneData},
     song_provider::{DiffId, ScoreProvider, SongDiffId, SongId},
     songselect::{Difficulty, Song},
     vg_ui::Vgfx,
-    ControlMessage,
+    ControlMessage, RuscMixer,
 };
+
+/// Skin sample played when entering the result screen after a clear
+const CLEAR_SAMPLE: &str = "applause";
+/// Plays of the chart in the `play_history` global of the skin
+const SKIN_HISTORY_LEN: usize = 20;
 use serde_with::*;
 use tealr::{
     mlu::{
@@ -46,12 +54,15 @@ struct HidSud {}
 #[serde(rename_all = "camelCase")]
 pub struct SongResultData {
     score: u32,
-    gauge_type: u8,    // 0 = normal, 1 = hard. Should be defined in constants sometime
-    gauge_option: i32, // type specific, such as difficulty level for the same gauge type if available
+    gauge_type: u8,     // 0 = normal, 1 = hard, 2 = blastive
+    gauge_option: i32,  // type specific, the blastive level in half steps
+    gauge_name: String, // ex. "Hard" or "Blastive 2.5"
     mirror: bool,
     random: bool,
-    auto_flags: i32, //bits for autoplay settings, 0 = no autoplay
-    gauge: f32,      // value of the gauge at the end of the song
+    hard_random: bool,
+    random_seed: Option<u32>, // Seed the lanes were shuffled with, only on random plays
+    auto_flags: i32,          //bits for autoplay settings, 0 = no autoplay
+    gauge: f32,               // value of the gauge at the end of the song
     misses: i32,
     goods: i32,
     perfects: i32,
@@ -84,13 +95,15 @@ pub struct SongResultData {
     autoplay: bool,
     playback_speed: f32,
     mission: String,               // Only on practice mode
-    retry_count: i32,              // Only on practice mode
+    retry_count: i32,              // Times the chart was restarted before this play
     is_self: bool, // Whether this score is viewer's in multiplayer; always true for singleplayer
     speed_mod_type: i32, // Only when isSelf is true; 0 for XMOD, 1 for MMOD, 2 for CMOD
     speed_mod_value: f64, // Only when isSelf is true; HiSpeed for XMOD, ModSpeed for MMOD and CMOD
     note_hit_stats: Vec<HitStat>, // Only when isSelf is true; contains HitStat for notes (excluding hold notes and lasers)
     hold_hit_stats: Vec<HitStat>, // Only when isSelf is true; contains HitStat for holds
     laser_hit_stats: Vec<HitStat>, // Only when isSelf is true; contains HitStat for lasers
+    lane_stats: Vec<LaneStats>,   // Summary for each lane, same lane order as HitStat
+    hit_graph: HitGraph,          // Hit deltas downsampled over the duration of the song
     is_local: bool,               // Whether this score was set locally
     song_id: SongDiffId,
 }
@@ -122,28 +135,32 @@ pub fn calculate_clear_mark(hits: HitSummary, manual: bool, gauge: &Gauge) -> Cl
         return ClearMark::FullCombo;
     }
 
-    match gauge {
-        Gauge::None => ClearMark::None,
-        Gauge::Normal { .. } => ClearMark::Cleared,
-        Gauge::Hard { .. } => ClearMark::HardCleared,
+    match gauge.spec() {
+        None => ClearMark::None,
+        Some(spec) if spec.is_hard() => ClearMark::HardCleared,
+        Some(_) => ClearMark::Cleared,
     }
 }
 
 impl SongResultData {
-    pub fn from_diff(
-        song: Arc<Song>,
-        diff_idx: usize,
-        score: u32,
-        hit_ratings: Vec<HitRating>,
-        gauge: Gauge,
-        hit_window: HitWindow,
-        autoplay: AutoPlay,
-        max_combo: i32,
-        duration: i32,
-        manual_exit: bool,
-    ) -> anyhow::Result<Self> {
+    pub fn from_result(result: GameResult) -> anyhow::Result<Self> {
         use itertools::Itertools;
         use statrs::statistics::{Data, Median, Statistics};
+        let GameResult {
+            song,
+            diff_idx,
+            score,
+            gauge,
+            hit_ratings,
+            hit_window,
+            autoplay,
+            max_combo,
+            duration,
+            manual_exit,
+            player: _,
+            retries,
+            modifiers,
+        } = result;
         let Difficulty {
             jacket_path,
             level,
@@ -154,6 +171,13 @@ impl SongResultData {
             scores,
             hash: _,
             illustrator,
+            radar: _,
+            duration: _,
+            duration_string: _,
+            last_played: _,
+            audio_broken: _,
+            density_strip_path: _,
+            jacket_loaded: _,
         } = song.difficulties.read().expect("Lock error")[diff_idx].clone();
 
         let Song {
@@ -162,6 +186,7 @@ impl SongResultData {
             bpm,
             id: _,
             difficulties: _,
+            uploader: _,
         } = (*song).clone();
 
         let grade = match score {
@@ -183,6 +208,7 @@ impl SongResultData {
             manual_exit,
             &gauge,
         );
+        let gauge_spec = gauge.spec().unwrap_or_else(|| GaugeType::Normal.into());
 
         let stat_times = hit_ratings
             .iter()
@@ -202,26 +228,13 @@ impl SongResultData {
         ) = hit_ratings.iter().try_fold(
             (vec![], vec![], vec![]),
             |(mut laser, mut note, mut hold), x| -> anyhow::Result<_> {
-                let mut rating: HitStat = (*x).try_into()?;
-                rating.time_frac = (rating.time as f32 / duration as f32).clamp(0.0, 1.0);
+                let rating: HitStat = (*x, duration).try_into()?;
 
                 match x {
                     HitRating::None => {}
-                    HitRating::Crit {
-                        tick,
-                        delta: _,
-                        time: _,
-                    }
-                    | HitRating::Good {
-                        tick,
-                        delta: _,
-                        time: _,
-                    }
-                    | HitRating::Miss {
-                        tick,
-                        delta: _,
-                        time: _,
-                    } => match tick.tick {
+                    HitRating::Crit { tick, .. }
+                    | HitRating::Good { tick, .. }
+                    | HitRating::Miss { tick, .. } => match tick.tick {
                         ScoreTick::Laser { lane: _, pos: _ }
                         | ScoreTick::Slam {
                             lane: _,
@@ -237,6 +250,14 @@ impl SongResultData {
             },
         )?;
 
+        // Converting every hit to a Lua table is slow for long charts
+        let (laser_hit_stats, note_hit_stats, hold_hit_stats) = if GameConfig::get().full_hit_stats
+        {
+            (laser_hit_stats, note_hit_stats, hold_hit_stats)
+        } else {
+            (vec![], vec![], vec![])
+        };
+
         Ok(Self {
             score,
             jacket_path,
@@ -259,7 +280,7 @@ impl SongResultData {
                         HitRating::Good {
                             tick: _,
                             delta: _,
-                            time: _
+                            ..
                         }
                     )
                 })
@@ -272,7 +293,7 @@ impl SongResultData {
                         HitRating::Crit {
                             tick: _,
                             delta: _,
-                            time: _
+                            ..
                         }
                     )
                 })
@@ -285,7 +306,7 @@ impl SongResultData {
                         HitRating::Miss {
                             tick: _,
                             delta: _,
-                            time: _
+                            ..
                         }
                     )
                 })
@@ -293,19 +314,17 @@ impl SongResultData {
 
             earlies: hit_ratings
                 .iter()
-                .filter(
-                    |x| matches!(x, HitRating::Good { tick: _, delta, time: _ } if *delta > 0.0),
-                )
+                .filter(|x| matches!(x, HitRating::Good { delta, .. } if *delta > 0.0))
                 .count() as i32,
             lates: hit_ratings
                 .iter()
-                .filter(
-                    |x| matches!(x, HitRating::Good { tick: _, delta, time: _ } if *delta < 0.0),
-                )
+                .filter(|x| matches!(x, HitRating::Good { delta, .. } if *delta < 0.0))
                 .count() as i32,
             laser_hit_stats,
             note_hit_stats,
             hold_hit_stats,
+            lane_stats: LaneStats::collect(&hit_ratings),
+            hit_graph: HitGraph::new(&hit_ratings, duration, HIT_GRAPH_BUCKETS),
             song_id: SongDiffId::SongDiff(
                 song.id.clone(),
                 song.difficulties.read().expect("Lock error")[diff_idx]
@@ -318,10 +337,8 @@ impl SongResultData {
                             .clone()
                     }),
             ),
-            gauge_type: GaugeType::try_from(gauge)
-                .map(|x| x as u8)
-                .inspect(|e| warn!("Could not convert gauge type: {e}"))
-                .unwrap_or_default(),
+            gauge_type: gauge_spec.gauge_type as u8,
+            gauge_name: gauge_spec.name(),
             hit_window,
             playback_speed: 1.0,
             auto_flags: match autoplay {
@@ -331,9 +348,11 @@ impl SongResultData {
                 AutoPlay::All => 3,
             },
             autoplay: autoplay.any(),
-            gauge_option: 0,
-            mirror: false,
-            random: false,
+            gauge_option: gauge_spec.option(),
+            mirror: modifiers.mirror,
+            random: modifiers.random != RandomMode::Off,
+            hard_random: modifiers.random == RandomMode::HardRandom,
+            random_seed: (modifiers.random != RandomMode::Off).then_some(modifiers.seed),
             max_combo,
             illustrator,
             duration,
@@ -346,7 +365,7 @@ impl SongResultData {
             display_index: 0,
             uid: None,
             mission: String::new(),
-            retry_count: 0,
+            retry_count: retries as i32,
             is_self: true,
             speed_mod_type: 0,
             speed_mod_value: GameConfig::get().mod_speed,
@@ -355,23 +374,78 @@ impl SongResultData {
     }
 }
 
-impl SceneData for SongResultData {
+/// Results of a single player, the chart can be played again from the result screen
+pub struct SingleResultData {
+    data: SongResultData,
+    song: Arc<Song>,
+    diff_idx: usize,
+}
+
+impl SingleResultData {
+    pub fn new(result: GameResult) -> anyhow::Result<Self> {
+        let song = result.song.clone();
+        let diff_idx = result.diff_idx;
+        Ok(Self {
+            data: SongResultData::from_result(result)?,
+            song,
+            diff_idx,
+        })
+    }
+}
+
+impl SceneData for SingleResultData {
     fn make_scene(self: Box<Self>, services: ServiceProvider) -> anyhow::Result<Box<dyn Scene>> {
-        services
-            .get_required_mut::<AsyncService>()
-            .read()
-            .expect("Lock error")
-            .save_config(); // Save config in case of changed hispeed
+        let Self {
+            data,
+            song,
+            diff_idx,
+        } = *self;
+        let mut result = SongResult::new(data, vec![], services);
+        result.retry = GameConfig::get()
+            .quick_retry
+            .enabled
+            .then_some((song.clone(), diff_idx));
+        result.played = Some((song, diff_idx));
+        Ok(Box::new(result))
+    }
+}
 
-        Ok(Box::new(SongResult {
-            score_service: services.get_required(),
-            close: false,
-            control_tx: None,
-            data: *self,
-            lua: LuaProvider::new_lua(),
-            services,
-            screenshot_state: ScreenshotState::NotRendered,
-        }))
+/// Results of both players in versus mode, shown like multiplayer results
+pub struct VersusResultData(Vec<SongResultData>);
+
+impl VersusResultData {
+    pub fn new(results: Vec<GameResult>) -> anyhow::Result<Self> {
+        let mut players = results
+            .into_iter()
+            .map(|result| {
+                let player = result.player;
+                let mut data = SongResultData::from_result(result)?;
+                data.player_name = format!("Player {}", player + 1);
+                data.title = format!("{} ({})", data.real_title, data.player_name);
+                data.display_index = player as i32;
+                data.uid = Some(data.player_name.clone());
+                Ok(data)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // The scores of both players are shown instead of the high scores
+        let scores: Vec<Score> = players.iter().map(Score::from).collect();
+        for data in &mut players {
+            data.high_scores.clone_from(&scores);
+        }
+
+        Ok(Self(players))
+    }
+}
+
+impl SceneData for VersusResultData {
+    fn make_scene(self: Box<Self>, services: ServiceProvider) -> anyhow::Result<Box<dyn Scene>> {
+        let Self(players) = *self;
+        let data = players
+            .first()
+            .cloned()
+            .ok_or(anyhow!("No player results"))?;
+        Ok(Box::new(SongResult::new(data, players, services)))
     }
 }
 
@@ -386,19 +460,169 @@ struct HitStat {
     hold: i32, // 0 for chip or laser, otherwise # of ticks in hold
 }
 
-impl TryFrom<HitRating> for HitStat {
+/// Position of `time` within the song, between 0 and 1
+fn time_frac(time: f64, duration: i32) -> f32 {
+    if duration <= 0 {
+        return 0.0;
+    }
+
+    (time / duration as f64).clamp(0.0, 1.0) as f32
+}
+
+const LANE_COUNT: usize = 8;
+
+#[derive(Debug, ToTypename, Clone, Serialize, Default, ToLuaLsType, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct LaneStats {
+    crits: i32,
+    goods: i32,
+    misses: i32,
+    mean_delta: f64, // In milliseconds, only counts crits and goods
+}
+
+impl LaneStats {
+    fn collect(hit_ratings: &[HitRating]) -> Vec<Self> {
+        let mut stats = vec![Self::default(); LANE_COUNT];
+        let mut delta_sums = [0.0; LANE_COUNT];
+
+        for rating in hit_ratings {
+            let (tick, delta) = match rating {
+                HitRating::None => continue,
+                HitRating::Crit { tick, delta, .. }
+                | HitRating::Good { tick, delta, .. }
+                | HitRating::Miss { tick, delta, .. } => (tick, delta),
+            };
+
+            let lane = tick.tick.global_lane();
+            let Some(lane_stats) = stats.get_mut(lane) else {
+                continue;
+            };
+
+            match rating {
+                HitRating::Crit { .. } => lane_stats.crits += 1,
+                HitRating::Good { .. } => lane_stats.goods += 1,
+                _ => {
+                    lane_stats.misses += 1;
+                    continue;
+                }
+            }
+            delta_sums[lane] += delta;
+        }
+
+        for (lane_stats, sum) in stats.iter_mut().zip(delta_sums) {
+            let hits = lane_stats.crits + lane_stats.goods;
+            if hits > 0 {
+                lane_stats.mean_delta = sum / hits as f64;
+            }
+        }
+
+        stats
+    }
+}
+
+const HIT_GRAPH_BUCKETS: usize = 128;
+
+#[derive(Debug, ToTypename, Clone, Serialize, Default, ToLuaLsType, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct HitGraphBucket {
+    mean_delta: f64, // In milliseconds, only counts crits and goods on chips
+    min_delta: f64,
+    max_delta: f64,
+    hits: i32,
+    misses: i32, // Misses of any object
+}
+
+#[derive(Debug, ToTypename, Clone, Serialize, Default, ToLuaLsType, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct HitGraph {
+    buckets: Vec<HitGraphBucket>, // Equal parts of the song
+    early_percent: f64,           // Share of chip hits that were early, between 0 and 100
+    late_percent: f64,
+}
+
+impl HitGraph {
+    fn new(hit_ratings: &[HitRating], duration: i32, bucket_count: usize) -> Self {
+        let mut buckets = vec![HitGraphBucket::default(); bucket_count];
+        let (mut earlies, mut lates, mut hits) = (0, 0, 0);
+
+        for rating in hit_ratings {
+            if matches!(rating, HitRating::None) {
+                continue;
+            }
+            let index = (time_frac(rating.time(), duration) * bucket_count as f32) as usize;
+            let Some(bucket) = buckets.get_mut(index.min(bucket_count.saturating_sub(1))) else {
+                continue;
+            };
+
+            match rating {
+                HitRating::Miss { .. } => bucket.misses += 1,
+                HitRating::Crit { tick, delta, .. } | HitRating::Good { tick, delta, .. }
+                    if matches!(tick.tick, ScoreTick::Chip { .. }) =>
+                {
+                    let delta = *delta;
+                    if bucket.hits == 0 {
+                        bucket.min_delta = delta;
+                        bucket.max_delta = delta;
+                    } else {
+                        bucket.min_delta = bucket.min_delta.min(delta);
+                        bucket.max_delta = bucket.max_delta.max(delta);
+                    }
+                    // Running sum, divided by the hit count below
+                    bucket.mean_delta += delta;
+                    bucket.hits += 1;
+
+                    hits += 1;
+                    if delta > 0.0 {
+                        earlies += 1;
+                    } else if delta < 0.0 {
+                        lates += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for bucket in &mut buckets {
+            if bucket.hits > 0 {
+                bucket.mean_delta /= bucket.hits as f64;
+            }
+        }
+
+        let percent = |count: i32| {
+            if hits > 0 {
+                count as f64 * 100.0 / hits as f64
+            } else {
+                0.0
+            }
+        };
+
+        Self {
+            buckets,
+            early_percent: percent(earlies),
+            late_percent: percent(lates),
+        }
+    }
+}
+
+impl TryFrom<(HitRating, i32)> for HitStat {
     type Error = anyhow::Error;
 
-    fn try_from(value: HitRating) -> Result<Self, Self::Error> {
+    fn try_from((value, duration): (HitRating, i32)) -> Result<Self, Self::Error> {
         let mut ret = match value {
             HitRating::None => return Err(anyhow::anyhow!("HitRating was None")),
-            HitRating::Crit { tick, delta, time }
-            | HitRating::Good { tick, delta, time }
-            | HitRating::Miss { tick, delta, time } => Self {
+            HitRating::Crit {
+                tick, delta, time, ..
+            }
+            | HitRating::Good {
+                tick, delta, time, ..
+            }
+            | HitRating::Miss {
+                tick, delta, time, ..
+            } => Self {
                 rating: 0,
                 lane: tick.tick.global_lane() as i32,
-                time: time as i32,
-                time_frac: 0.0,
+                time: time.round() as i32,
+                time_frac: time_frac(time, duration),
                 delta: delta as i32,
                 hold: match tick.tick {
                     kson::score_ticks::ScoreTick::Laser { lane: _, pos: _ } => 1,
@@ -429,12 +653,17 @@ impl TryFrom<HitRating> for HitStat {
 pub struct Score {
     ///range 0.0 -> 1.0
     pub gauge: f32,
-    /// 0 = normal, 1 = hard. Should be defined in constants sometime
+    /// 0 = normal, 1 = hard, 2 = blastive
     pub gauge_type: u8,
-    /// type specific, such as difficulty level for the same gauge type if available
+    /// type specific, the blastive level in half steps
     pub gauge_option: i32,
+    /// ex. "Hard" or "Blastive 2.5"
+    pub gauge_name: String,
     pub mirror: bool,
     pub random: bool,
+    pub hard_random: bool,
+    /// Seed of the lane shuffle, replays the same lanes with [`kson::Chart::apply_modifiers`]
+    pub random_seed: Option<u32>,
     /// bits for autoplay settings, 0 = no autoplay
     pub auto_flags: i32,
     pub score: i32,
@@ -451,6 +680,26 @@ pub struct Score {
     pub earlies: i32,
     pub lates: i32,
     pub combo: u32,
+    /// Only on multiplayer and versus
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+}
+
+impl Score {
+    /// Played with mirror or random
+    pub fn is_modified(&self) -> bool {
+        self.mirror || self.random
+    }
+
+    /// Modified plays only count as best scores when the config allows it
+    pub fn counts_for_best(&self) -> bool {
+        !self.is_modified() || GameConfig::get().modified_best_scores
+    }
+}
+
+/// Sorts `scores` best first, scores that don't count as best scores go after the others
+pub fn sort_scores(scores: &mut [Score]) {
+    scores.sort_by_cached_key(|s| (!s.counts_for_best(), -s.score));
 }
 
 impl From<&SongResultData> for Score {
@@ -459,8 +708,11 @@ impl From<&SongResultData> for Score {
             score,
             gauge_type,
             gauge_option,
+            gauge_name,
             mirror,
             random,
+            hard_random,
+            random_seed,
             auto_flags,
             gauge,
             misses,
@@ -473,14 +725,18 @@ impl From<&SongResultData> for Score {
             hit_window,
             is_local,
             max_combo,
+            uid,
             ..
         } = val;
         Score {
             gauge: *gauge,
             gauge_type: *gauge_type,
             gauge_option: *gauge_option,
+            gauge_name: gauge_name.clone(),
             mirror: *mirror,
             random: *random,
+            hard_random: *hard_random,
+            random_seed: *random_seed,
             auto_flags: *auto_flags,
             score: *score as _,
             perfects: *perfects,
@@ -497,6 +753,7 @@ impl From<&SongResultData> for Score {
             earlies: *earlies,
             lates: *lates,
             combo: *max_combo as _,
+            uid: uid.clone(),
         }
     }
 }
@@ -511,16 +768,183 @@ enum ScreenshotState {
 
 pub struct SongResult {
     data: SongResultData,
+    /// Results of every player in versus mode, empty otherwise
+    players: Vec<SongResultData>,
+    shown_player: usize,
     lua: Rc<Lua>,
     services: ServiceProvider,
     control_tx: Option<Sender<ControlMessage>>,
     close: bool,
     score_service: RefMut<dyn ScoreProvider>,
     screenshot_state: ScreenshotState,
+    /// Chart played again when Start is pressed with retry selected
+    retry: Option<(Arc<Song>, usize)>,
+    retry_selected: bool,
+    /// Chart that was played, the songs next to it in the song select can be started from here
+    played: Option<(Arc<Song>, usize)>,
+    clear_sample_playing: bool,
+}
+
+impl SongResult {
+    fn new(data: SongResultData, players: Vec<SongResultData>, services: ServiceProvider) -> Self {
+        services
+            .get_required_mut::<AsyncService>()
+            .read()
+            .expect("Lock error")
+            .save_config(); // Save config in case of changed hispeed
+
+        Self {
+            score_service: services.get_required(),
+            close: false,
+            control_tx: None,
+            data,
+            players,
+            shown_player: 0,
+            lua: LuaProvider::new_lua(),
+            services,
+            screenshot_state: ScreenshotState::NotRendered,
+            retry: None,
+            retry_selected: false,
+            played: None,
+            clear_sample_playing: false,
+        }
+    }
+
+    fn play_clear_sample(&mut self) -> anyhow::Result<()> {
+        let mixer: RuscMixer = self.services.get_required();
+        let game_data = self
+            .services
+            .get_required_mut::<crate::game_data::GameData>();
+        let mut game_data = game_data.write().expect("Lock error");
+        game_data.load_skin_sample(CLEAR_SAMPLE)?;
+        game_data.play_sample(&mixer, CLEAR_SAMPLE, false);
+        self.clear_sample_playing = true;
+        Ok(())
+    }
+
+    /// Closes the result screen, stopping the clear sample
+    fn leave(&mut self) {
+        if self.clear_sample_playing {
+            self.services
+                .get_required_mut::<crate::game_data::GameData>()
+                .write()
+                .expect("Lock error")
+                .stop_sample(CLEAR_SAMPLE);
+            self.clear_sample_playing = false;
+        }
+        self.close = true;
+    }
+
+    /// Starts the song `step` places away in the song select, with the same difficulty if it has
+    /// one
+    fn play_adjacent(&mut self, step: i32) -> anyhow::Result<()> {
+        let (song, diff_idx) = self.played.clone().ok_or(anyhow!("No song played"))?;
+        self.control_tx
+            .as_ref()
+            .ok_or(anyhow!("control_tx not set"))?
+            .send(ControlMessage::AdjacentSong {
+                song,
+                diff_idx,
+                step,
+            })?;
+        // Don't keep the decoded song around once another one is playing
+        self.services
+            .get_required_mut::<crate::game_data::GameData>()
+            .write()
+            .expect("Lock error")
+            .last_played = None;
+        Ok(())
+    }
+
+    /// Sets `retry_available`, `retry_selected` and `adjacent_songs_available` for the skin
+    fn set_lua_retry(&self) -> anyhow::Result<()> {
+        let globals = self.lua.globals();
+        globals.set("retry_available", self.retry.is_some())?;
+        globals.set("retry_selected", self.retry_selected)?;
+        globals.set("adjacent_songs_available", self.played.is_some())?;
+        Ok(())
+    }
+
+    fn retry(&mut self) -> anyhow::Result<()> {
+        let (song, diff_idx) = self.retry.clone().ok_or(anyhow!("Nothing to retry"))?;
+        self.control_tx
+            .as_ref()
+            .ok_or(anyhow!("control_tx not set"))?
+            .send(ControlMessage::Retry {
+                song,
+                diff_idx,
+                retries: self.data.retry_count as u32 + 1,
+            })?;
+        Ok(())
+    }
+
+    fn chart_hash(&self) -> String {
+        match self.data.song_id.get_diff() {
+            Some(DiffId(SongId::StringId(hash))) => hash.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /// Appends the play to the play history and sets the `play_history` global to the last plays
+    /// of the chart, oldest first
+    fn record_history(&self) -> anyhow::Result<()> {
+        let Some(history) = self.services.get_mut::<PlayHistory>() else {
+            return Ok(());
+        };
+        let history = history.read().expect("Lock error");
+        let data = &self.data;
+        let chart_hash = self.chart_hash();
+
+        // Manually exited plays don't get a badge
+        if data.badge != ClearMark::None as u8 {
+            history.record(HistoryEntry {
+                timestamp: chrono::Utc::now().timestamp(),
+                chart_hash: chart_hash.clone(),
+                title: data.real_title.clone(),
+                level: data.level,
+                score: data.score,
+                gauge_type: data.gauge_type,
+                gauge_option: data.gauge_option,
+                gauge: data.gauge,
+                mirror: data.mirror,
+                random: data.random,
+                hard_random: data.hard_random,
+                seed: data.random_seed,
+                autoplay: data.autoplay,
+                badge: data.badge,
+                perfects: data.perfects,
+                goods: data.goods,
+                misses: data.misses,
+                max_combo: data.max_combo,
+                earlies: data.earlies,
+                lates: data.lates,
+                session: history.session().to_owned(),
+            });
+        }
+
+        self.lua.globals().set(
+            "play_history",
+            self.lua
+                .to_value(&history.recent(&chart_hash, SKIN_HISTORY_LEN))?,
+        )?;
+        Ok(())
+    }
+
+    fn set_lua_result(&self) -> anyhow::Result<()> {
+        self.lua
+            .globals()
+            .set("result", self.lua.to_value(&self.data)?)?;
+
+        if let Ok(result_set) = self.lua.globals().get::<_, Function>("result_set") {
+            result_set.call::<_, ()>(())?;
+        }
+        Ok(())
+    }
 }
 
 impl Scene for SongResult {
     fn init(&mut self, app_control_tx: Sender<ControlMessage>) -> anyhow::Result<()> {
+        // Only the score of player 1 is saved in versus mode
         self.score_service
             .write()
             .expect("Lock error")
@@ -530,14 +954,19 @@ impl Scene for SongResult {
             .get_required::<LuaProvider>()
             .register_libraries(self.lua.clone(), "result.lua")?;
 
-        self.lua
-            .globals()
-            .set("result", self.lua.to_value(&self.data)?)?;
-
-        if let Ok(result_set) = self.lua.globals().get::<_, Function>("result_set") {
-            result_set.call::<_, ()>(())?;
+        // Before the result so `result_set` can use it
+        if let Err(e) = self.record_history() {
+            log::warn!("Could not record the play history: {e}");
         }
+        self.set_lua_result()?;
+        self.set_lua_retry()?;
         self.control_tx = Some(app_control_tx);
+
+        if self.data.badge >= ClearMark::Cleared as u8 {
+            if let Err(e) = self.play_clear_sample() {
+                log::debug!("No clear sample played: {e}");
+            }
+        }
         Ok(())
     }
 
@@ -549,11 +978,14 @@ impl Scene for SongResult {
             ScreenshotState::NotRendered => ScreenshotState::Rendered,
             ScreenshotState::Rendered => {
                 let screenshot_logic = GameConfig::get().score_screenshots;
-                let is_top_score = !self
-                    .data
-                    .high_scores
-                    .iter()
-                    .any(|s| s.score > self.data.score as i32);
+                let is_top_score = (!(self.data.mirror || self.data.random)
+                    || GameConfig::get().modified_best_scores)
+                    && !self
+                        .data
+                        .high_scores
+                        .iter()
+                        .filter(|s| s.counts_for_best())
+                        .any(|s| s.score > self.data.score as i32);
 
                 let take_screenshot = match screenshot_logic {
                     crate::config::ScoreScreenshot::Always => true,
@@ -599,15 +1031,63 @@ impl Scene for SongResult {
     }
 
     fn on_button_pressed(&mut self, button: crate::button_codes::UscButton, _time: SystemTime) {
-        if let UscButton::Start = button {
-            self.close = true;
+        match button {
+            UscButton::Start => {
+                if self.retry_selected {
+                    crate::log_result!(self.retry());
+                } else {
+                    // Don't keep the decoded song around in song select
+                    self.services
+                        .get_required_mut::<crate::game_data::GameData>()
+                        .write()
+                        .expect("Lock error")
+                        .last_played = None;
+                }
+                self.leave();
+            }
+            UscButton::FX(side) if self.played.is_some() && !self.retry_selected => {
+                let step = match side {
+                    Side::Left => -1,
+                    Side::Right => 1,
+                };
+                match self.play_adjacent(step) {
+                    Ok(()) => self.leave(),
+                    Err(e) => log::warn!("{e}"),
+                }
+            }
+            UscButton::BT(BtLane::A) if self.retry.is_some() => {
+                self.retry_selected = !self.retry_selected;
+                crate::log_result!(self.set_lua_retry());
+            }
+            UscButton::FX(side) if self.players.len() > 1 => {
+                let step = match side {
+                    Side::Left => self.players.len() - 1,
+                    Side::Right => 1,
+                };
+                self.shown_player = (self.shown_player + step) % self.players.len();
+                self.data = self.players[self.shown_player].clone();
+                crate::log_result!(self.set_lua_result());
+            }
+            _ => {}
         }
     }
 
     fn debug_ui(&mut self, ctx: &egui::Context) -> anyhow::Result<()> {
         egui::Window::new("Song Results").show(ctx, |ui| {
+            ui.label(format!(
+                "Mirror: {}, Random: {}",
+                self.data.mirror,
+                match (self.data.random, self.data.hard_random) {
+                    (false, _) => "Off",
+                    (true, false) => "Random",
+                    (true, true) => "Hard Random",
+                }
+            ));
+            if let Some(seed) = self.data.random_seed {
+                ui.label(format!("Random seed: {seed}"));
+            }
             if ui.button("Close").clicked() {
-                self.close = true;
+                self.leave();
             }
         });
 
@@ -618,7 +1098,183 @@ impl Scene for SongResult {
         self.close
     }
 
+    fn game_state(&self) -> GameState {
+        GameState::Results {
+            chart_hash: self.chart_hash(),
+            score: self.data.score,
+            modifiers: PlayModifierInfo {
+                mirror: self.data.mirror,
+                random: self.data.random,
+                hard_random: self.data.hard_random,
+                seed: self.data.random_seed,
+            },
+        }
+    }
+
     fn name(&self) -> &str {
         "Song Result"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use kson::score_ticks::{PlacedScoreTick, ScoreTick};
+
+    use super::{time_frac, HitGraph, HitGraphBucket, HitStat, LaneStats};
+    use crate::game::HitRating;
+
+    fn tick(tick: ScoreTick) -> PlacedScoreTick {
+        PlacedScoreTick { y: 0, tick }
+    }
+
+    #[test]
+    fn lane_stats() {
+        let chip = |lane| tick(ScoreTick::Chip { lane });
+        let laser = |lane| tick(ScoreTick::Laser { lane, pos: 0.5 });
+        let hits = [
+            HitRating::Crit {
+                tick: chip(0),
+                delta: 10.0,
+                time: 100.0,
+                input_time: None,
+            },
+            HitRating::Good {
+                tick: chip(0),
+                delta: -30.0,
+                time: 200.0,
+                input_time: None,
+            },
+            HitRating::Miss {
+                tick: chip(0),
+                delta: 100.0,
+                time: 300.0,
+                input_time: None,
+            },
+            HitRating::Miss {
+                tick: chip(5),
+                delta: 0.0,
+                time: 400.0,
+                input_time: None,
+            },
+            HitRating::None,
+            HitRating::Crit {
+                tick: laser(1),
+                delta: 0.0,
+                time: 500.0,
+                input_time: None,
+            },
+        ];
+
+        let stats = LaneStats::collect(&hits);
+        assert_eq!(stats.len(), 8);
+        assert_eq!(
+            stats[0],
+            LaneStats {
+                crits: 1,
+                goods: 1,
+                misses: 1,
+                mean_delta: -10.0,
+            }
+        );
+        assert_eq!(
+            stats[5],
+            LaneStats {
+                misses: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(stats[7].crits, 1);
+        assert!(stats[1..5].iter().all(|s| *s == LaneStats::default()));
+        assert_eq!(stats[6], LaneStats::default());
+    }
+
+    #[test]
+    fn hit_stat_time_frac() {
+        let hit = HitRating::Good {
+            tick: tick(ScoreTick::Chip { lane: 2 }),
+            delta: 20.0,
+            time: 1500.0,
+            input_time: None,
+        };
+        let stat: HitStat = (hit, 6000).try_into().unwrap();
+        assert_eq!(stat.lane, 2);
+        assert_eq!(stat.rating, 1);
+        assert_eq!(stat.time, 1500);
+        assert_eq!(stat.time_frac, 0.25);
+
+        let stat: HitStat = (hit, 3000).try_into().unwrap();
+        assert_eq!(stat.time_frac, 0.5);
+        let stat: HitStat = (hit, 1000).try_into().unwrap();
+        assert_eq!(stat.time_frac, 1.0);
+        assert!(HitStat::try_from((HitRating::None, 6000)).is_err());
+
+        assert_eq!(time_frac(7000.0, 6000), 1.0);
+        assert_eq!(time_frac(-100.0, 6000), 0.0);
+        assert_eq!(time_frac(100.0, 0), 0.0);
+    }
+
+    #[test]
+    fn hit_graph_buckets() {
+        let crit = |delta, time| HitRating::Crit {
+            tick: tick(ScoreTick::Chip { lane: 0 }),
+            delta,
+            time,
+            input_time: None,
+        };
+        let hits = [
+            crit(10.0, 0.0),
+            crit(-20.0, 249.0),
+            crit(4.0, 250.0),
+            HitRating::Miss {
+                tick: tick(ScoreTick::Laser { lane: 0, pos: 0.0 }),
+                delta: 0.0,
+                time: 999.0,
+                input_time: None,
+            },
+            crit(0.0, 1000.0),
+            crit(30.0, 1200.0),
+        ];
+
+        let graph = HitGraph::new(&hits, 1000, 4);
+        assert_eq!(graph.buckets.len(), 4);
+        assert_eq!(
+            graph.buckets[0],
+            HitGraphBucket {
+                mean_delta: -5.0,
+                min_delta: -20.0,
+                max_delta: 10.0,
+                hits: 2,
+                misses: 0,
+            }
+        );
+        assert_eq!(graph.buckets[1].hits, 1);
+        assert_eq!(graph.buckets[1].mean_delta, 4.0);
+        assert_eq!(graph.buckets[2], HitGraphBucket::default());
+        assert_eq!(
+            graph.buckets[3],
+            HitGraphBucket {
+                mean_delta: 15.0,
+                min_delta: 0.0,
+                max_delta: 30.0,
+                hits: 2,
+                misses: 1,
+            }
+        );
+        assert_eq!(graph.early_percent, 60.0);
+        assert_eq!(graph.late_percent, 20.0);
+    }
+
+    #[test]
+    fn empty_hit_graph() {
+        let graph = HitGraph::new(&[], 1000, 4);
+        assert_eq!(graph.buckets, vec![HitGraphBucket::default(); 4]);
+        assert_eq!(graph.early_percent, 0.0);
+        assert_eq!(graph.late_percent, 0.0);
+
+        let graph = HitGraph::new(&[HitRating::None], 0, 4);
+        assert!(graph
+            .buckets
+            .iter()
+            .all(|b| *b == HitGraphBucket::default()));
+    }
+}
@@ -16,6 +16,8 @@ pub trait Scene {
     fn init(&mut self, app_control_tx: Sender<ControlMessage>) -> Result<()> {
         Ok(())
     }
+    /// `knob_state` has the knob movement since the last tick from every input method, menus
+    /// navigate with it instead of the laser events. It has no deltas while suspended.
     fn tick(&mut self, dt: f64, knob_state: LaserState) -> Result<()> {
         Ok(())
     }
@@ -0,0 +1,125 @@
+use crate::display_rotation::DisplayRotation;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PixelSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PixelSize {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// Width divided by height, 0 for an empty size
+    pub fn aspect(self) -> f64 {
+        if self.height == 0 {
+            0.0
+        } else {
+            self.width as f64 / self.height as f64
+        }
+    }
+}
+
+impl From<PixelSize> for (u32, u32) {
+    fn from(size: PixelSize) -> Self {
+        (size.width, size.height)
+    }
+}
+
+/// Where skins draw on the window, maps window pixels like the mouse cursor to the coordinates
+/// skins use
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenSpace {
+    /// Framebuffer of the window
+    pub physical: PixelSize,
+    /// Area skins draw in, rotated like the display
+    pub logical: PixelSize,
+    pub rotation: DisplayRotation,
+    /// Top left of the skin area in the rotated window, skins only get part of it in versus
+    pub origin: (f64, f64),
+}
+
+impl Default for ScreenSpace {
+    fn default() -> Self {
+        Self::new(PixelSize::new(800, 600), DisplayRotation::None)
+    }
+}
+
+impl ScreenSpace {
+    /// Skins drawing on the whole window
+    pub fn new(physical: PixelSize, rotation: DisplayRotation) -> Self {
+        let (width, height) = rotation.logical_size(physical.into());
+        Self {
+            physical,
+            logical: PixelSize::new(width, height),
+            rotation,
+            origin: (0.0, 0.0),
+        }
+    }
+
+    /// Skins drawing on part of this space, `x` and `y` are relative to the top left of it
+    pub fn area(self, x: f64, y: f64, size: PixelSize) -> Self {
+        Self {
+            logical: size,
+            origin: (self.origin.0 + x, self.origin.1 + y),
+            ..self
+        }
+    }
+
+    pub fn aspect(&self) -> f64 {
+        self.logical.aspect()
+    }
+
+    fn physical_f64(&self) -> (f64, f64) {
+        (self.physical.width as f64, self.physical.height as f64)
+    }
+
+    /// Maps a position in window pixels to skin coordinates
+    pub fn to_skin(&self, window_pos: (f64, f64)) -> (f64, f64) {
+        let (x, y) = self.rotation.to_logical(window_pos, self.physical_f64());
+        (x - self.origin.0, y - self.origin.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PixelSize, ScreenSpace};
+    use crate::display_rotation::DisplayRotation;
+
+    #[test]
+    fn rotated() {
+        let screen = ScreenSpace::new(PixelSize::new(1280, 720), DisplayRotation::Clockwise90);
+        assert_eq!(screen.logical, PixelSize::new(720, 1280));
+        assert!((screen.aspect() - 0.5625).abs() < 1e-9);
+        // The top right of the window is the top left of the rotated picture
+        assert_eq!(screen.to_skin((1280.0, 0.0)), (0.0, 0.0));
+        assert_eq!(screen.to_skin((1180.0, 20.0)), (20.0, 100.0));
+
+        for rotation in DisplayRotation::ALL {
+            let screen = ScreenSpace::new(PixelSize::new(1280, 720), rotation);
+            let center = screen.to_skin((640.0, 360.0));
+            let logical = screen.logical;
+            assert_eq!(
+                center,
+                (logical.width as f64 / 2.0, logical.height as f64 / 2.0),
+                "{rotation}"
+            );
+        }
+    }
+
+    #[test]
+    fn areas() {
+        // Right player of versus on a display rotated by 180°
+        let screen = ScreenSpace::new(PixelSize::new(1280, 720), DisplayRotation::Clockwise180)
+            .area(640.0, 0.0, PixelSize::new(640, 720));
+        assert_eq!(screen.logical, PixelSize::new(640, 720));
+        assert_eq!(screen.to_skin((0.0, 720.0)), (640.0, 0.0));
+        assert_eq!(screen.to_skin((640.0, 360.0)), (0.0, 360.0));
+        assert_eq!(screen.to_skin((630.0, 700.0)), (10.0, 20.0));
+
+        // Nested areas add up
+        let inner = screen.area(100.0, 50.0, PixelSize::new(200, 100));
+        assert_eq!(inner.to_skin((1280.0 - 750.0, 720.0 - 60.0)), (10.0, 10.0));
+    }
+}
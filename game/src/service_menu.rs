@@ -0,0 +1,131 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+use kson::Side;
+
+use crate::button_codes::UscButton;
+
+/// Set when the game should start again once it has shut down
+static RESTART: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceAction {
+    OpenSettings,
+    RestartApp,
+    ExitGame,
+}
+
+impl ServiceAction {
+    pub const ALL: [Self; 3] = [Self::OpenSettings, Self::RestartApp, Self::ExitGame];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ServiceAction::OpenSettings => "Open Settings",
+            ServiceAction::RestartApp => "Restart App",
+            ServiceAction::ExitGame => "Exit Game",
+        }
+    }
+}
+
+/// Menu of cab mode opened with the service chord. It's navigated with the FX buttons and
+/// Start, only the chord closes it.
+#[derive(Debug, Default)]
+pub struct ServiceMenu {
+    selected: usize,
+    /// Start was pressed without another button after it. Actions run when it's released so
+    /// holding a chord that contains Start doesn't run one.
+    start_pressed: bool,
+}
+
+impl ServiceMenu {
+    pub fn on_button_pressed(&mut self, button: UscButton) {
+        let len = ServiceAction::ALL.len();
+        self.start_pressed = button == UscButton::Start;
+        match button {
+            UscButton::FX(Side::Left) => self.selected = (self.selected + len - 1) % len,
+            UscButton::FX(Side::Right) => self.selected = (self.selected + 1) % len,
+            _ => {}
+        }
+    }
+
+    pub fn on_button_released(&mut self, button: UscButton) -> Option<ServiceAction> {
+        if button == UscButton::Start && std::mem::take(&mut self.start_pressed) {
+            Some(ServiceAction::ALL[self.selected])
+        } else {
+            None
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context) -> Option<ServiceAction> {
+        let mut chosen = None;
+        egui::Window::new("Service Menu")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                for (i, action) in ServiceAction::ALL.into_iter().enumerate() {
+                    if ui
+                        .selectable_label(i == self.selected, action.as_str())
+                        .clicked()
+                    {
+                        self.selected = i;
+                        chosen = Some(action);
+                    }
+                }
+                ui.separator();
+                ui.label("FX-L/FX-R to select, Start to confirm, the service chord to close");
+            });
+        chosen
+    }
+}
+
+pub fn request_restart() {
+    RESTART.store(true, Ordering::Relaxed);
+}
+
+/// Starts the game again with the same arguments if a restart was requested, called once the
+/// game has shut down
+pub fn restart_if_requested() -> Result<()> {
+    if !RESTART.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()?;
+    log::info!("Restarting {}", exe.display());
+    std::process::Command::new(exe)
+        .args(std::env::args_os().skip(1))
+        .spawn()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use kson::{BtLane, Side};
+
+    use super::{ServiceAction, ServiceMenu};
+    use crate::button_codes::UscButton;
+
+    #[test]
+    fn navigate_and_confirm() {
+        let mut menu = ServiceMenu::default();
+        menu.on_button_pressed(UscButton::FX(Side::Left));
+        menu.on_button_pressed(UscButton::Start);
+        assert_eq!(
+            menu.on_button_released(UscButton::Start),
+            Some(ServiceAction::ExitGame)
+        );
+
+        menu.on_button_pressed(UscButton::FX(Side::Right));
+        menu.on_button_pressed(UscButton::FX(Side::Right));
+        // Part of a chord
+        menu.on_button_pressed(UscButton::Start);
+        menu.on_button_pressed(UscButton::BT(BtLane::A));
+        assert_eq!(menu.on_button_released(UscButton::Start), None);
+
+        menu.on_button_pressed(UscButton::Start);
+        assert_eq!(
+            menu.on_button_released(UscButton::Start),
+            Some(ServiceAction::RestartApp)
+        );
+    }
+}
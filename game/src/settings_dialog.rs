@@ -13,16 +13,28 @@ use tealr::mlu::mlua::{Function, IntoLua, Lua, LuaSerdeExt};
 
 use crate::{
     async_service::AsyncService,
-    button_codes::{UscButton, UscInputEvent},
+    button_codes::{LaserState, UscButton},
     config::{GameConfig, ScoreDisplayMode},
-    game::HitWindow,
+    confirm_dialog::ConfirmDialog,
+    game::{
+        gauge::{GaugeSpec, GaugeType, BLASTIVE_LEVELS},
+        modifiers::RandomMode,
+        HitWindow, HudVisibility,
+    },
     game_main::AutoPlay,
-    input_state::InputState,
+    input_state::{press_interval, InputState},
     lua_service::LuaProvider,
+    menu_audio::{MenuAudio, NavSound},
     settings_screen::HitFrames,
+    skin_settings::{SkinSettingEntry, SkinSettingValue},
     songselect::KNOB_NAV_THRESHOLD,
+    ControlMessage,
 };
 
+const RESET_MESSAGE: &str = "Reset the settings of this tab to their defaults?";
+const HARD_RANDOM_MESSAGE: &str =
+    "Hard random moves every note on its own and can create unplayable patterns. Enable it?";
+
 type Setter<T> = Box<dyn Fn(T) + Send>;
 type Getter<T> = Box<dyn Fn() -> T + Send>;
 
@@ -55,6 +67,8 @@ pub enum SettingsDialogSetting {
     },
     Button {
         action: Getter<()>,
+        /// Asked before running the action
+        confirm: Option<String>,
     },
 }
 
@@ -62,6 +76,14 @@ impl SettingsDialogSetting {
     fn button(action: impl Fn() + Send + 'static) -> Self {
         Self::Button {
             action: Box::new(action),
+            confirm: None,
+        }
+    }
+
+    fn confirm_button(message: impl Into<String>, action: impl Fn() + Send + 'static) -> Self {
+        Self::Button {
+            action: Box::new(action),
+            confirm: Some(message.into()),
         }
     }
 
@@ -119,6 +141,138 @@ impl SettingsDialogSetting {
     }
 }
 
+/// Every blastive level, from the lowest in half steps
+fn blastive_level_names() -> Vec<String> {
+    let steps = ((BLASTIVE_LEVELS.end() - BLASTIVE_LEVELS.start()) * 2.0).round() as usize;
+    (0..=steps)
+        .map(|i| format!("{:.1}", BLASTIVE_LEVELS.start() + i as f32 / 2.0))
+        .collect()
+}
+
+fn skin_value(name: &str) -> Option<SkinSettingValue> {
+    GameConfig::get().skin_settings.get(name).cloned()
+}
+
+fn set_skin_value(name: &str, value: SkinSettingValue) {
+    GameConfig::get_mut()
+        .skin_settings
+        .insert(name.to_string(), value);
+}
+
+/// Dialog setting for a skin setting flagged as `quick`, the value is read by skins with
+/// `game.GetSkinSetting` so changes show right away
+fn skin_setting(entry: &SkinSettingEntry) -> Option<(String, SettingsDialogSetting)> {
+    let label = |label: &Option<String>, name: &String| label.as_ref().unwrap_or(name).clone();
+    let to_i32 = |v: i64| v.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+
+    match entry {
+        SkinSettingEntry::Bool {
+            default,
+            label: l,
+            name,
+            quick: true,
+        } => {
+            let (default, get_name, set_name) = (*default, name.clone(), name.clone());
+            Some((
+                label(l, name),
+                SettingsDialogSetting::bool(
+                    move || match skin_value(&get_name) {
+                        Some(SkinSettingValue::Bool(v)) => v,
+                        _ => default,
+                    },
+                    move |v| set_skin_value(&set_name, SkinSettingValue::Bool(v)),
+                ),
+            ))
+        }
+        SkinSettingEntry::Selection {
+            default,
+            label: l,
+            name,
+            values,
+            quick: true,
+        } if !values.is_empty() => {
+            let (get_name, set_name) = (name.clone(), name.clone());
+            let (get_values, set_values) = (values.clone(), values.clone());
+            let default = default.clone();
+            Some((
+                label(l, name),
+                SettingsDialogSetting::options(
+                    move || {
+                        let current = match skin_value(&get_name) {
+                            Some(SkinSettingValue::Text(t)) => t,
+                            _ => default.clone(),
+                        };
+                        get_values
+                            .iter()
+                            .position(|v| *v == current)
+                            .unwrap_or_default()
+                    },
+                    move |i| {
+                        set_skin_value(&set_name, SkinSettingValue::Text(set_values[i].clone()))
+                    },
+                    values.clone(),
+                ),
+            ))
+        }
+        SkinSettingEntry::Float {
+            default,
+            label: l,
+            name,
+            min,
+            max,
+            quick: true,
+        } => {
+            let (default, min, max) = (*default, *min, *max);
+            let (get_name, set_name) = (name.clone(), name.clone());
+            Some((
+                label(l, name),
+                SettingsDialogSetting::float(
+                    move || match skin_value(&get_name) {
+                        Some(SkinSettingValue::Float(v)) => v as f32,
+                        _ => default as f32,
+                    },
+                    move |v| {
+                        set_skin_value(
+                            &set_name,
+                            SkinSettingValue::Float((v as f64).clamp(min, max)),
+                        )
+                    },
+                    min as f32,
+                    max as f32,
+                    // A half turn of the knob covers a tenth of the range
+                    ((max - min) / 10.0) as f32,
+                ),
+            ))
+        }
+        SkinSettingEntry::Integer {
+            default,
+            label: l,
+            name,
+            min,
+            max,
+            quick: true,
+        } => {
+            let default = to_i32(*default);
+            let (get_name, set_name) = (name.clone(), name.clone());
+            Some((
+                label(l, name),
+                SettingsDialogSetting::int(
+                    move || match skin_value(&get_name) {
+                        Some(SkinSettingValue::Integer(v)) => to_i32(v),
+                        _ => default,
+                    },
+                    move |v| set_skin_value(&set_name, SkinSettingValue::Integer(v as i64)),
+                    to_i32(*min),
+                    to_i32(*max),
+                    1,
+                    1,
+                ),
+            ))
+        }
+        _ => None,
+    }
+}
+
 pub struct SettingsDialogTab {
     name: String,
     settings: Vec<(String, SettingsDialogSetting)>,
@@ -176,7 +330,7 @@ impl<'lua> IntoLua<'lua> for &SettingsDialogTab {
                     setting_table.set("type", "bool")?;
                     setting_table.set("value", get())?;
                 }
-                SettingsDialogSetting::Button { action: _ } => {
+                SettingsDialogSetting::Button { .. } => {
                     setting_table.set("type", "button")?;
                 }
             }
@@ -228,6 +382,11 @@ pub struct SettingsDialog {
     lua: Rc<Lua>,
     setting_advance: f32,
     async_service: di::RefMut<AsyncService>,
+    menu_audio: Arc<MenuAudio>,
+    /// Config from when the dialog was opened, restored when it's cancelled
+    snapshot: Option<GameConfig>,
+    confirm: Option<ConfirmDialog<SettingsDialog>>,
+    control_tx: Option<Sender<ControlMessage>>,
 }
 
 impl<'lua> IntoLua<'lua> for &SettingsDialog {
@@ -246,6 +405,9 @@ impl<'lua> IntoLua<'lua> for &SettingsDialog {
         }
 
         table.set("tabs", tealr::mlu::mlua::Value::Table(tabs_table))?;
+        if let Some(confirm) = &self.confirm {
+            table.set("confirm", confirm)?;
+        }
 
         Ok(tealr::mlu::mlua::Value::Table(table))
     }
@@ -265,10 +427,44 @@ impl SettingsDialog {
             lua: LuaProvider::new_lua(),
             setting_advance: 0.0,
             async_service: services.get_required(),
+            menu_audio: services.get_required(),
+            snapshot: None,
+            confirm: None,
+            control_tx: None,
         }
     }
 
+    pub fn open(&mut self) {
+        self.snapshot = Some(GameConfig::get().clone());
+        self.show = true;
+        self.menu_audio.nav(NavSound::DialogOpen);
+    }
+
+    fn close(&mut self) {
+        self.snapshot = None;
+        self.show = false;
+        self.menu_audio.nav(NavSound::DialogClose);
+        self.async_service.read().expect("Lock error").save_config();
+    }
+
+    /// Closes the dialog, undoing every change made since it was opened
+    fn cancel(&mut self) {
+        if let Some(snapshot) = self.snapshot.take() {
+            *GameConfig::get_mut() = snapshot;
+            if let Some(tx) = &self.control_tx {
+                _ = tx.send(ControlMessage::ApplySettings);
+            }
+        }
+        self.show = false;
+        self.menu_audio.nav(NavSound::DialogClose);
+    }
+
     pub fn on_button_press(&mut self, button: UscButton) {
+        if ConfirmDialog::route_button(self, |d| &mut d.confirm, button) {
+            _ = self.lua.globals().set("SettingsDiag", &*self);
+            return;
+        }
+
         match button {
             UscButton::BT(l) => self.tabs[self.current_tab].change_setting(match l {
                 kson::BtLane::A => -5,
@@ -277,23 +473,22 @@ impl SettingsDialog {
                 kson::BtLane::D => 5,
             }),
             UscButton::FX(s) => {
-                let press_time = std::time::SystemTime::now();
-
-                if let Some(other_press_time) =
-                    self.input_state.is_button_held(UscButton::FX(s.opposite()))
-                {
-                    let detla_ms = press_time
-                        .duration_since(other_press_time)
-                        .expect("Time decreased?")
-                        .as_millis();
+                let other = UscButton::FX(s.opposite());
+                if self.input_state.is_button_held(other).is_some() {
+                    let last_press = |b| self.input_state.last_press_times(b).first().copied();
+                    let together = last_press(UscButton::FX(s))
+                        .zip(last_press(other))
+                        .is_some_and(|(a, b)| press_interval(a, b).as_millis() < 100);
 
-                    if detla_ms < 100 {
+                    if together {
                         if self.show {
-                            self.async_service.read().expect("Lock error").save_config();
+                            self.close();
+                        } else {
+                            self.open();
                         }
-                        self.show = !self.show;
                     }
                 } else {
+                    self.menu_audio.nav(NavSound::Tick);
                     self.current_tab = (self.current_tab as i32
                         + match s {
                             Side::Left => -1,
@@ -304,13 +499,38 @@ impl SettingsDialog {
                 }
             }
             UscButton::Start => {
-                let tab = &mut self.tabs[self.current_tab];
-                let setting = &mut tab.settings[tab.current_setting];
-                if let (_, SettingsDialogSetting::Button { action }) = setting {
-                    action();
+                let (tab_index, tab) = (self.current_tab, &self.tabs[self.current_tab]);
+                let setting_index = tab.current_setting;
+                match &tab.settings[setting_index].1 {
+                    SettingsDialogSetting::Button {
+                        action,
+                        confirm: None,
+                    } => action(),
+                    SettingsDialogSetting::Button {
+                        action: _,
+                        confirm: Some(message),
+                    } => {
+                        self.confirm = Some(ConfirmDialog::new(
+                            message.clone(),
+                            "Yes",
+                            "No",
+                            move |dialog: &mut SettingsDialog, confirmed| {
+                                if let (true, SettingsDialogSetting::Button { action, .. }) =
+                                    (confirmed, &dialog.tabs[tab_index].settings[setting_index].1)
+                                {
+                                    action();
+                                }
+                            },
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+            UscButton::Back => {
+                if self.show {
+                    self.cancel();
                 }
             }
-            UscButton::Back => self.show = false,
             UscButton::Laser(_, _) => {}
             UscButton::Other(_) => {}
             UscButton::Refresh => {}
@@ -318,10 +538,12 @@ impl SettingsDialog {
 
         _ = self.lua.globals().set("SettingsDiag", &*self);
     }
-    pub fn on_input(&mut self, input: &UscInputEvent) {
-        let UscInputEvent::Laser(ls, _) = input else {
+    pub fn on_knob(&mut self, ls: LaserState) {
+        if let Some(confirm) = &mut self.confirm {
+            confirm.on_knob(ls.get_axis(Side::Left).delta + ls.get_axis(Side::Right).delta);
+            _ = self.lua.globals().set("SettingsDiag", &*self);
             return;
-        };
+        }
 
         self.setting_advance += ls.get_axis(Side::Left).delta;
         let mut value_advance = ls.get_axis(Side::Right).delta / std::f32::consts::PI;
@@ -329,6 +551,9 @@ impl SettingsDialog {
         let settings_steps = (self.setting_advance / KNOB_NAV_THRESHOLD).trunc() as i32;
 
         self.setting_advance -= settings_steps as f32 * KNOB_NAV_THRESHOLD;
+        if settings_steps != 0 {
+            self.menu_audio.nav(NavSound::Tick);
+        }
 
         let tab = &mut self.tabs[self.current_tab];
 
@@ -342,7 +567,7 @@ impl SettingsDialog {
             SettingsDialogSetting::Float {
                 min,
                 max,
-                mult: _,
+                mult,
                 set,
                 get,
             },
@@ -384,11 +609,16 @@ impl SettingsDialog {
         }
 
         val = val.clamp(*min, *max);
-        set(val + value_advance);
+        set(val + value_advance * *mult);
         _ = self.lua.globals().set("SettingsDiag", &*self);
     }
-    pub fn init_lua(&self, load_lua: &LuaProvider) -> anyhow::Result<()> {
-        self.lua.globals().set("SettingsDiag", self)?;
+    pub fn init_lua(
+        &mut self,
+        load_lua: &LuaProvider,
+        control_tx: Sender<ControlMessage>,
+    ) -> anyhow::Result<()> {
+        self.control_tx = Some(control_tx);
+        self.lua.globals().set("SettingsDiag", &*self)?;
         load_lua.register_libraries(self.lua.clone(), "gamesettingsdialog.lua")?;
         Ok(())
     }
@@ -404,21 +634,20 @@ impl SettingsDialog {
         let itx = Arc::new(AtomicI32::new(0));
         let irx = itx.clone();
 
-        Self::new(
+        let mut dialog = Self::new(
             vec![
                 SettingsDialogTab::new(
                     "Offsets",
                     vec![
                         (
                             "Global Offset".into(),
-                            SettingsDialogSetting::Int {
-                                min: -100,
-                                max: 100,
-                                step: 1,
-                                div: 1,
-                                set: Box::new(|x| GameConfig::get_mut().global_offset = x),
-                                get: Box::new(|| GameConfig::get().global_offset),
-                            },
+                            SettingsDialogSetting::float(
+                                || GameConfig::get().global_offset as f32,
+                                |x| GameConfig::get_mut().global_offset = x as f64,
+                                -100.0,
+                                100.0,
+                                20.0,
+                            ),
                         ),
                         (
                             "Button Offset".into(),
@@ -442,6 +671,16 @@ impl SettingsDialog {
                                 1,
                             ),
                         ),
+                        (
+                            "Reset to defaults".into(),
+                            SettingsDialogSetting::confirm_button(RESET_MESSAGE, || {
+                                let defaults = GameConfig::default();
+                                let mut config = GameConfig::get_mut();
+                                config.global_offset = defaults.global_offset;
+                                config.button_offset = defaults.button_offset;
+                                config.laser_offset = defaults.laser_offset;
+                            }),
+                        ),
                     ],
                 ),
                 SettingsDialogTab::new(
@@ -451,16 +690,36 @@ impl SettingsDialog {
                             "Gauge".into(),
                             SettingsDialogSetting::options(
                                 || match GameConfig::get().start_gauge {
-                                    crate::game::gauge::GaugeType::Normal => 0,
-                                    crate::game::gauge::GaugeType::Hard => 1,
+                                    GaugeType::Normal => 0,
+                                    GaugeType::Hard => 1,
+                                    GaugeType::Blastive => 2,
                                 },
                                 |x| {
                                     GameConfig::get_mut().start_gauge = match x {
-                                        1 => crate::game::gauge::GaugeType::Hard,
-                                        _ => crate::game::gauge::GaugeType::Normal,
+                                        1 => GaugeType::Hard,
+                                        2 => GaugeType::Blastive,
+                                        _ => GaugeType::Normal,
                                     }
                                 },
-                                vec!["Normal".into(), "Hard".into()],
+                                vec!["Normal".into(), "Hard".into(), "Blastive".into()],
+                            ),
+                        ),
+                        (
+                            "Blastive Level".into(),
+                            SettingsDialogSetting::options(
+                                || {
+                                    let level = GaugeSpec::new(
+                                        GaugeType::Blastive,
+                                        GameConfig::get().blastive_level,
+                                    )
+                                    .level;
+                                    ((level - BLASTIVE_LEVELS.start()) * 2.0).round() as usize
+                                },
+                                |x| {
+                                    GameConfig::get_mut().blastive_level =
+                                        BLASTIVE_LEVELS.start() + x as f32 / 2.0
+                                },
+                                blastive_level_names(),
                             ),
                         ),
                         (
@@ -470,6 +729,42 @@ impl SettingsDialog {
                                 |x| GameConfig::get_mut().fallback_gauge = x,
                             ),
                         ),
+                        (
+                            "Mirror".into(),
+                            SettingsDialogSetting::bool(
+                                || GameConfig::get().modifiers.mirror,
+                                |x| GameConfig::get_mut().modifiers.mirror = x,
+                            ),
+                        ),
+                        (
+                            "Random".into(),
+                            SettingsDialogSetting::options(
+                                || match GameConfig::get().modifiers.random {
+                                    RandomMode::Off => 0,
+                                    RandomMode::Random => 1,
+                                    RandomMode::HardRandom => 2,
+                                },
+                                |x| {
+                                    // Hard random can only be turned on with its button, cycling
+                                    // past it turns random off
+                                    let random = &mut GameConfig::get_mut().modifiers.random;
+                                    *random = match x {
+                                        1 => RandomMode::Random,
+                                        2 if *random == RandomMode::HardRandom => {
+                                            RandomMode::HardRandom
+                                        }
+                                        _ => RandomMode::Off,
+                                    }
+                                },
+                                vec!["Off".into(), "Random".into(), "Hard Random".into()],
+                            ),
+                        ),
+                        (
+                            "Enable Hard Random".into(),
+                            SettingsDialogSetting::confirm_button(HARD_RANDOM_MESSAGE, || {
+                                GameConfig::get_mut().modifiers.random = RandomMode::HardRandom
+                            }),
+                        ),
                         (
                             "Hide Background".into(),
                             SettingsDialogSetting::bool(
@@ -477,6 +772,13 @@ impl SettingsDialog {
                                 |x| GameConfig::get_mut().graphics.disable_bg = x,
                             ),
                         ),
+                        (
+                            "Reduce Flashing".into(),
+                            SettingsDialogSetting::bool(
+                                || GameConfig::get().graphics.reduce_flashing,
+                                |x| GameConfig::get_mut().graphics.reduce_flashing = x,
+                            ),
+                        ),
                         (
                             "Score Display".into(),
                             SettingsDialogSetting::options(
@@ -506,6 +808,20 @@ impl SettingsDialog {
                                 autoplay_tx.send(AutoPlay::All).unwrap()
                             }),
                         ),
+                        (
+                            "Reset to defaults".into(),
+                            SettingsDialogSetting::confirm_button(RESET_MESSAGE, || {
+                                let defaults = GameConfig::default();
+                                let mut config = GameConfig::get_mut();
+                                config.start_gauge = defaults.start_gauge;
+                                config.blastive_level = defaults.blastive_level;
+                                config.fallback_gauge = defaults.fallback_gauge;
+                                config.modifiers = defaults.modifiers;
+                                config.graphics.disable_bg = defaults.graphics.disable_bg;
+                                config.graphics.reduce_flashing = defaults.graphics.reduce_flashing;
+                                config.score_display = defaults.score_display;
+                            }),
+                        ),
                     ],
                 ),
                 SettingsDialogTab::new(
@@ -561,6 +877,24 @@ impl SettingsDialog {
                                 1,
                             ),
                         ),
+                        (
+                            "Early miss window".into(),
+                            SettingsDialogSetting::int(
+                                || {
+                                    HitFrames::from(GameConfig::get().hit_window.early_miss)
+                                        .0
+                                        .round() as i32
+                                },
+                                |x| {
+                                    GameConfig::get_mut().hit_window.early_miss =
+                                        HitFrames(x as _).into()
+                                },
+                                1,
+                                30,
+                                1,
+                                1,
+                            ),
+                        ),
                         (
                             "Set Normal".into(),
                             SettingsDialogSetting::button(|| {
@@ -575,6 +909,65 @@ impl SettingsDialog {
                         ),
                     ],
                 ),
+                SettingsDialogTab::new(
+                    "HUD",
+                    vec![
+                        (
+                            "Score".into(),
+                            SettingsDialogSetting::bool(
+                                || GameConfig::get().hud.score,
+                                |x| GameConfig::get_mut().hud.score = x,
+                            ),
+                        ),
+                        (
+                            "Combo".into(),
+                            SettingsDialogSetting::bool(
+                                || GameConfig::get().hud.combo,
+                                |x| GameConfig::get_mut().hud.combo = x,
+                            ),
+                        ),
+                        (
+                            "Gauge".into(),
+                            SettingsDialogSetting::bool(
+                                || GameConfig::get().hud.gauge,
+                                |x| GameConfig::get_mut().hud.gauge = x,
+                            ),
+                        ),
+                        (
+                            "Song Info".into(),
+                            SettingsDialogSetting::bool(
+                                || GameConfig::get().hud.song_info,
+                                |x| GameConfig::get_mut().hud.song_info = x,
+                            ),
+                        ),
+                        (
+                            "Hit Deltas".into(),
+                            SettingsDialogSetting::bool(
+                                || GameConfig::get().hud.hit_deltas,
+                                |x| GameConfig::get_mut().hud.hit_deltas = x,
+                            ),
+                        ),
+                        (
+                            "Crit Cursors".into(),
+                            SettingsDialogSetting::bool(
+                                || GameConfig::get().hud.crit_cursors,
+                                |x| GameConfig::get_mut().hud.crit_cursors = x,
+                            ),
+                        ),
+                        (
+                            "Minimal".into(),
+                            SettingsDialogSetting::button(|| {
+                                GameConfig::get_mut().hud = HudVisibility::MINIMAL
+                            }),
+                        ),
+                        (
+                            "Show all".into(),
+                            SettingsDialogSetting::button(|| {
+                                GameConfig::get_mut().hud = HudVisibility::ALL
+                            }),
+                        ),
+                    ],
+                ),
                 SettingsDialogTab::new(
                     "Test",
                     vec![
@@ -616,7 +1009,19 @@ impl SettingsDialog {
             ],
             input_state,
             services,
-        )
+        );
+
+        let skin_settings: Vec<_> = GameConfig::get()
+            .skin_definition
+            .iter()
+            .filter_map(skin_setting)
+            .collect();
+        if !skin_settings.is_empty() {
+            dialog
+                .tabs
+                .push(SettingsDialogTab::new("Skin", skin_settings));
+        }
+        dialog
     }
 
     pub fn render(&mut self, dt: f64) -> anyhow::Result<()> {
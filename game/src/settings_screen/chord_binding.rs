@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use egui::{Color32, Stroke};
+use itertools::Itertools;
+
+use crate::{
+    button_codes::{ActionKind, UscButton},
+    config::{ChordBinding, GameConfig},
+    input_state::InputState,
+};
+
+pub struct ChordBindingUi {
+    /// Index of the chord being recorded and the buttons pressed so far
+    recording: Option<(usize, Vec<UscButton>)>,
+    input_state: InputState,
+}
+
+impl ChordBindingUi {
+    pub fn new(input_state: InputState) -> Self {
+        Self {
+            recording: None,
+            input_state,
+        }
+    }
+
+    /// Collects the buttons held while recording, the chord is bound once they are all released
+    pub fn run_checks(&mut self, settings: &mut GameConfig) {
+        let Some((index, buttons)) = self.recording.as_mut() else {
+            return;
+        };
+
+        let held = self.input_state.held_buttons();
+        for button in &held {
+            if !buttons.contains(button) {
+                buttons.push(*button);
+            }
+        }
+
+        if held.is_empty() && !buttons.is_empty() {
+            if let Some(chord) = settings.chords.get_mut(*index) {
+                chord.buttons = std::mem::take(buttons);
+            }
+            self.recording = None;
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, settings: &mut GameConfig) {
+        let active_stroke = Stroke::new(2.0, Color32::GREEN);
+
+        ui.label("Chords:");
+        ui.end_row();
+
+        let mut remove = None;
+        for (i, chord) in settings.chords.iter_mut().enumerate() {
+            egui::ComboBox::from_id_source(("chord_action", i))
+                .selected_text(chord.action.as_str())
+                .show_ui(ui, |ui| {
+                    for action in ActionKind::ALL {
+                        ui.selectable_value(&mut chord.action, action, action.as_str());
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                let recorded = match &self.recording {
+                    Some((index, buttons)) if *index == i => Some(buttons),
+                    _ => None,
+                };
+                let buttons = recorded.unwrap_or(&chord.buttons);
+                let text = match (buttons.is_empty(), recorded.is_some()) {
+                    (true, true) => "Hold buttons...".to_string(),
+                    (true, false) => "No buttons".to_string(),
+                    _ => buttons.iter().map(UscButton::as_str).join(" + "),
+                };
+
+                let mut button = egui::Button::new(text);
+                if recorded.is_some() {
+                    button = button.stroke(active_stroke);
+                }
+                if ui.add(button).clicked() {
+                    self.recording = match recorded {
+                        Some(_) => None,
+                        None => Some((i, vec![])),
+                    };
+                }
+
+                let mut hold_ms = chord.hold.as_millis() as u64;
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut hold_ms)
+                            .speed(10)
+                            .clamp_range(0..=5000)
+                            .suffix(" ms"),
+                    )
+                    .changed()
+                {
+                    chord.hold = Duration::from_millis(hold_ms);
+                }
+
+                if ui.button("Remove").clicked() {
+                    remove = Some(i);
+                }
+            });
+            ui.end_row();
+        }
+
+        if let Some(i) = remove {
+            settings.chords.remove(i);
+            self.recording = None;
+        }
+
+        if ui.button("Add chord").clicked() {
+            settings.chords.push(ChordBinding {
+                buttons: vec![],
+                hold: Duration::from_secs(1),
+                action: ActionKind::Screenshot,
+            });
+        }
+        ui.end_row();
+    }
+}
@@ -69,7 +69,12 @@ impl BindingUi {
         }
     }
 
-    pub fn ui(&mut self, ui: &mut egui::Ui, settings: &mut GameConfig) {
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Returns whether clearing every bind of the controller was requested
+    pub fn ui(&mut self, ui: &mut egui::Ui, settings: &mut GameConfig) -> bool {
         let bindable_buttons: Vec<UscButton> = (0..8u8).map(UscButton::from).collect();
         let bindings = settings.controller_binds.entry(self.uuid).or_default();
 
@@ -153,12 +158,7 @@ impl BindingUi {
         ui.end_row();
         //Clear button
         match self.currently_binding {
-            ActiveBinding::None => {
-                if ui.button("Clear All").clicked() {
-                    bindings.axis.clear();
-                    bindings.buttons.clear();
-                }
-            }
+            ActiveBinding::None => return ui.button("Clear All").clicked(),
             ActiveBinding::Button(button) => {
                 if ui
                     .button(format!("Clear {}", UscButton::from(button).as_str()))
@@ -185,5 +185,6 @@ impl BindingUi {
                 }
             }
         }
+        false
     }
 }
@@ -0,0 +1,171 @@
+use egui::{Color32, Stroke};
+use kson::{BtLane, Side};
+use winit::keyboard::PhysicalKey;
+
+use crate::{
+    button_codes::UscButton,
+    config::{GameConfig, Keybinds},
+    input_state::InputState,
+};
+
+const BINDABLE_BUTTONS: [UscButton; 13] = [
+    UscButton::BT(BtLane::A),
+    UscButton::BT(BtLane::B),
+    UscButton::BT(BtLane::C),
+    UscButton::BT(BtLane::D),
+    UscButton::FX(Side::Left),
+    UscButton::FX(Side::Right),
+    UscButton::Start,
+    UscButton::Back,
+    UscButton::Refresh,
+    UscButton::Laser(Side::Left, Side::Left),
+    UscButton::Laser(Side::Left, Side::Right),
+    UscButton::Laser(Side::Right, Side::Left),
+    UscButton::Laser(Side::Right, Side::Right),
+];
+
+/// A key that is already bound to another button, waiting for confirmation
+#[derive(Clone, Copy)]
+struct Conflict {
+    button: UscButton,
+    key: PhysicalKey,
+    bound_to: UscButton,
+}
+
+pub struct KeyBindingUi {
+    currently_binding: Option<UscButton>,
+    conflict: Option<Conflict>,
+    input_state: InputState,
+}
+
+impl KeyBindingUi {
+    pub fn new(input_state: InputState) -> Self {
+        Self {
+            currently_binding: None,
+            conflict: None,
+            input_state,
+        }
+    }
+
+    fn set_binding(&mut self, button: Option<UscButton>) {
+        self.currently_binding = button;
+        self.input_state.set_key_capture_active(button.is_some());
+    }
+
+    /// Binds the captured key to the button being bound
+    pub fn on_key(&mut self, key: PhysicalKey, settings: &mut GameConfig) {
+        let Some(button) = self.currently_binding else {
+            return;
+        };
+        self.set_binding(None);
+
+        let keybinds = keybinds(settings);
+        match BINDABLE_BUTTONS
+            .into_iter()
+            .find(|b| *b != button && keybinds.key(*b) == Some(key))
+        {
+            Some(bound_to) => {
+                self.conflict = Some(Conflict {
+                    button,
+                    key,
+                    bound_to,
+                })
+            }
+            None => {
+                if let Some(bound) = keybinds.key_mut(button) {
+                    *bound = key;
+                }
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, settings: &mut GameConfig) {
+        let keybinds = keybinds(settings);
+        let active_stroke = Stroke::new(2.0, Color32::GREEN);
+        let conflict_stroke = Stroke::new(2.0, Color32::RED);
+
+        ui.label("Keyboard:");
+        ui.end_row();
+        for btn in BINDABLE_BUTTONS {
+            let key = keybinds.key(btn);
+            let conflicting = match &self.conflict {
+                Some(conflict) => conflict.button == btn || conflict.bound_to == btn,
+                None => BINDABLE_BUTTONS
+                    .iter()
+                    .any(|b| *b != btn && key.is_some() && keybinds.key(*b) == key),
+            };
+
+            let active = self.currently_binding == Some(btn);
+            let mut button = egui::Button::new(btn.as_str());
+            if active {
+                button = button.stroke(active_stroke);
+            } else if conflicting {
+                button = button.stroke(conflict_stroke);
+            }
+
+            if ui.add(button).clicked() {
+                self.conflict = None;
+                self.set_binding(if active { None } else { Some(btn) });
+            }
+
+            if let Some(key) = key {
+                ui.label(format!(": {}", key_name(key)));
+            }
+            ui.end_row();
+        }
+
+        if let Some(Conflict {
+            button,
+            key,
+            bound_to,
+        }) = self.conflict
+        {
+            ui.colored_label(
+                Color32::RED,
+                format!(
+                    "{} is already bound to {}",
+                    key_name(key),
+                    bound_to.as_str()
+                ),
+            );
+            ui.end_row();
+            if ui.button("Bind anyway").clicked() {
+                if let Some(bound) = keybinds.key_mut(button) {
+                    *bound = key;
+                }
+                self.conflict = None;
+            }
+            if ui.button("Cancel").clicked() {
+                self.conflict = None;
+            }
+            ui.end_row();
+        }
+
+        if ui.button("Reset keyboard").clicked() {
+            *keybinds = Keybinds::default();
+            self.conflict = None;
+            self.set_binding(None);
+        }
+        ui.end_row();
+    }
+}
+
+impl Drop for KeyBindingUi {
+    fn drop(&mut self) {
+        self.input_state.set_key_capture_active(false);
+    }
+}
+
+fn keybinds(settings: &mut GameConfig) -> &mut Keybinds {
+    if settings.keybinds.is_empty() {
+        settings.keybinds.push(Keybinds::default());
+    }
+    &mut settings.keybinds[0]
+}
+
+fn key_name(key: PhysicalKey) -> String {
+    match key {
+        PhysicalKey::Code(code) => format!("{code:?}"),
+        PhysicalKey::Unidentified(code) => format!("{code:?}"),
+    }
+}
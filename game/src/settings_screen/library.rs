@@ -0,0 +1,158 @@
+use std::{path::PathBuf, time::Duration};
+
+use di::RefMut;
+use poll_promise::Promise;
+
+use crate::{config::GameConfig, help::AsyncPicker, library_export, song_provider::SongProvider};
+
+/// Choices offered for the scheduled rescan
+const RESCAN_INTERVALS: [Option<Duration>; 5] = [
+    None,
+    Some(Duration::from_secs(15 * 60)),
+    Some(Duration::from_secs(60 * 60)),
+    Some(Duration::from_secs(6 * 60 * 60)),
+    Some(Duration::from_secs(24 * 60 * 60)),
+];
+
+fn interval_text(interval: Option<Duration>) -> String {
+    match interval.map(|d| d.as_secs() / 60) {
+        None => "Off".into(),
+        Some(m) if m % 60 == 0 => format!("Every {} h", m / 60),
+        Some(m) => format!("Every {m} min"),
+    }
+}
+
+pub struct LibraryUi {
+    song_provider: RefMut<dyn SongProvider>,
+    export_path: String,
+    /// Running export, resolves to the message shown once it's done
+    export: Option<Promise<String>>,
+    export_result: Option<String>,
+}
+
+impl LibraryUi {
+    pub fn new(song_provider: RefMut<dyn SongProvider>) -> Self {
+        Self {
+            song_provider,
+            export_path: crate::default_game_dir()
+                .join("library.csv")
+                .to_string_lossy()
+                .into_owned(),
+            export: None,
+            export_result: None,
+        }
+    }
+
+    /// Writes the song list of the current provider on another thread
+    fn start_export(&mut self) {
+        let path = PathBuf::from(&self.export_path);
+        let (songs, chart_paths) = {
+            let provider = self.song_provider.read().expect("Lock error");
+            (provider.get_all().0, provider.chart_paths())
+        };
+
+        self.export_result = None;
+        self.export = Some(Promise::spawn_thread("library_export", move || {
+            let rows = library_export::rows(&songs, &chart_paths);
+            match library_export::write(&path, &rows) {
+                Ok(()) => format!("Exported {} charts to {}", rows.len(), path.display()),
+                Err(e) => format!("Could not export the library: {e}"),
+            }
+        }));
+    }
+
+    fn export_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Library export (.json or .csv)");
+        AsyncPicker::new()
+            .file()
+            .add_filter("Library export", &["csv", "json"])
+            .show("library_export".into(), &mut self.export_path, ui);
+        ui.end_row();
+
+        if let Some(export) = self.export.take() {
+            match export.try_take() {
+                Ok(result) => self.export_result = Some(result),
+                Err(export) => {
+                    ui.spinner();
+                    ui.label("Exporting...");
+                    ui.end_row();
+                    self.export = Some(export);
+                    return;
+                }
+            }
+        }
+
+        if ui.button("Export library").clicked() {
+            self.start_export();
+        }
+        if let Some(result) = &self.export_result {
+            ui.label(result);
+        }
+        ui.end_row();
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, settings: &mut GameConfig) {
+        let library = &mut settings.library;
+
+        egui::ComboBox::new("rescan_interval", "Scheduled rescan")
+            .selected_text(interval_text(library.rescan_interval))
+            .show_ui(ui, |ui| {
+                for interval in RESCAN_INTERVALS {
+                    ui.selectable_value(
+                        &mut library.rescan_interval,
+                        interval,
+                        interval_text(interval),
+                    );
+                }
+            });
+        if ui.button("Rescan now").clicked() {
+            self.song_provider.write().expect("Lock error").refresh();
+        }
+        ui.end_row();
+
+        ui.checkbox(&mut library.show_duplicates, "Show duplicate songs");
+        ui.end_row();
+
+        let mut threads = library.scan_threads();
+        if ui
+            .add(egui::Slider::new(&mut threads, 1..=num_cpus::get().max(1)).text("Scan threads"))
+            .changed()
+        {
+            library.scan_threads = Some(threads);
+        }
+        ui.end_row();
+
+        self.export_ui(ui);
+
+        let duplicates = self
+            .song_provider
+            .read()
+            .expect("Lock error")
+            .duplicate_songs();
+        if duplicates.is_empty() {
+            ui.label("No duplicate songs found");
+            ui.end_row();
+            return;
+        }
+
+        ui.label(format!(
+            "{} song folders only hold copies of charts found in another folder",
+            duplicates.len()
+        ));
+        ui.end_row();
+        egui::Grid::new("duplicate_songs")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Folder");
+                ui.strong("Copy of");
+                ui.end_row();
+                for duplicate in &duplicates {
+                    ui.label(duplicate.path.display().to_string());
+                    ui.label(duplicate.original.display().to_string());
+                    ui.end_row();
+                }
+            });
+        ui.end_row();
+    }
+}
@@ -0,0 +1,315 @@
+use anyhow::Result;
+use di::RefMut;
+use poll_promise::Promise;
+
+use crate::{
+    block_on,
+    maintenance::{self, format_size, ManagedCache, TaskGuard, TaskProgress},
+    song_provider::{OrphanedScores, SongProvider},
+    thumbnailer::Thumbnailer,
+    FileSongProvider,
+};
+
+enum TaskResult {
+    Done(String),
+    Orphans(Vec<OrphanedScores>),
+}
+
+struct RunningTask {
+    name: &'static str,
+    progress: TaskProgress,
+    promise: Promise<Result<TaskResult>>,
+}
+
+/// Cache and database upkeep, every action runs on a worker while a window shows its progress
+pub struct MaintenanceUi {
+    song_provider: RefMut<dyn SongProvider>,
+    thumbnailer: RefMut<Thumbnailer>,
+    running: Option<RunningTask>,
+    /// Shown once a task is done until it's dismissed
+    finished: Option<String>,
+    /// Orphaned scores waiting for the choice to delete or keep them
+    orphans: Option<Vec<OrphanedScores>>,
+    sizes: Option<Promise<Vec<(&'static str, Result<u64>)>>>,
+}
+
+impl MaintenanceUi {
+    pub fn new(song_provider: RefMut<dyn SongProvider>, thumbnailer: RefMut<Thumbnailer>) -> Self {
+        Self {
+            song_provider,
+            thumbnailer,
+            running: None,
+            finished: None,
+            orphans: None,
+            sizes: None,
+        }
+    }
+
+    fn song_cache(&self) -> Option<Box<dyn ManagedCache>> {
+        self.song_provider.read().expect("Lock error").song_cache()
+    }
+
+    fn preview_cache(&self) -> Option<Box<dyn ManagedCache>> {
+        self.song_provider
+            .read()
+            .expect("Lock error")
+            .preview_cache()
+    }
+
+    fn image_caches(&self) -> Vec<Box<dyn ManagedCache>> {
+        self.thumbnailer.read().expect("Lock error").caches()
+    }
+
+    fn measure(&mut self) {
+        let caches: Vec<_> = self
+            .song_cache()
+            .into_iter()
+            .chain(self.preview_cache())
+            .chain(self.image_caches())
+            .collect();
+        self.sizes = Some(Promise::spawn_blocking(move || {
+            caches.iter().map(|c| (c.name(), c.size())).collect()
+        }));
+    }
+
+    fn start(
+        &mut self,
+        name: &'static str,
+        task: impl FnOnce(&TaskProgress) -> Result<TaskResult> + Send + 'static,
+    ) {
+        let guard = match TaskGuard::acquire() {
+            Ok(guard) => guard,
+            Err(e) => {
+                self.finished = Some(e.to_string());
+                return;
+            }
+        };
+
+        let progress = TaskProgress::default();
+        let task_progress = progress.clone();
+        self.running = Some(RunningTask {
+            name,
+            progress,
+            promise: Promise::spawn_blocking(move || {
+                let _guard = guard;
+                task(&task_progress)
+            }),
+        });
+    }
+
+    /// Orphans are only looked for between scans, while a scan runs most charts are missing
+    fn scan_running(&self) -> bool {
+        self.song_provider
+            .read()
+            .expect("Lock error")
+            .scan_progress()
+            .is_some_and(|p| !p.done)
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        if self.sizes.is_none() {
+            self.measure();
+        }
+        match self.sizes.as_ref().and_then(Promise::ready) {
+            None => {
+                ui.spinner();
+                ui.end_row();
+            }
+            Some(sizes) => {
+                egui::Grid::new("cache_sizes")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (name, size) in sizes {
+                            ui.label(*name);
+                            match size {
+                                Ok(size) => ui.label(format_size(*size)),
+                                Err(e) => ui.label(format!("Unknown: {e}")),
+                            };
+                            ui.end_row();
+                        }
+                    });
+                ui.end_row();
+            }
+        }
+
+        let idle = self.running.is_none() && !maintenance::is_running();
+        ui.add_enabled_ui(idle, |ui| {
+            if let Some(cache) = self.song_cache() {
+                if ui
+                    .button("Rebuild song cache")
+                    .on_hover_text("Imports every chart again, scores are kept")
+                    .clicked()
+                {
+                    self.start("Rebuilding song cache", move |progress| {
+                        cache.rebuild(progress)?;
+                        Ok(TaskResult::Done("The song cache was rebuilt".into()))
+                    });
+                }
+            }
+
+            if let Some(cache) = self.preview_cache() {
+                if ui
+                    .button("Clear preview analysis")
+                    .on_hover_text("Previews are analyzed again when they are played")
+                    .clicked()
+                {
+                    self.start("Clearing preview analysis", move |_| {
+                        cache.clear()?;
+                        Ok(TaskResult::Done("Cleared the preview analysis".into()))
+                    });
+                }
+            }
+
+            if ui.button("Clear image caches").clicked() {
+                let caches = self.image_caches();
+                self.start("Clearing image caches", move |progress| {
+                    for cache in caches {
+                        progress.set(format!("Clearing {}", cache.name()), None);
+                        cache.clear()?;
+                    }
+                    Ok(TaskResult::Done("Cleared the image caches".into()))
+                });
+            }
+
+            if ui.button("Vacuum score database").clicked() {
+                self.start("Vacuuming score database", |_| {
+                    let (before, after) = block_on!(FileSongProvider::vacuum_database())?;
+                    Ok(TaskResult::Done(format!(
+                        "Vacuumed the database from {} to {}",
+                        format_size(before),
+                        format_size(after)
+                    )))
+                });
+            }
+
+            let scanning = self.scan_running();
+            if ui
+                .add_enabled(!scanning, egui::Button::new("Find orphaned scores"))
+                .on_hover_text("Scores of charts that aren't in the library anymore")
+                .on_disabled_hover_text("Wait for the library scan to finish")
+                .clicked()
+            {
+                self.start("Looking for orphaned scores", |_| {
+                    Ok(TaskResult::Orphans(block_on!(
+                        FileSongProvider::orphaned_scores()
+                    )?))
+                });
+            }
+        });
+        ui.end_row();
+    }
+
+    fn orphans_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(orphans) = &self.orphans else {
+            return;
+        };
+
+        ui.label(format!(
+            "{} scores of {} charts that aren't in the library",
+            orphans.iter().map(|o| o.count).sum::<usize>(),
+            orphans.len()
+        ));
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("orphaned_scores")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Chart hash");
+                        ui.strong("Scores");
+                        ui.strong("Best");
+                        ui.strong("Last played");
+                        ui.end_row();
+                        for orphan in orphans {
+                            ui.monospace(&orphan.hash);
+                            ui.label(orphan.count.to_string());
+                            ui.label(orphan.best.to_string());
+                            ui.label(
+                                chrono::DateTime::from_timestamp(orphan.last_played as _, 0)
+                                    .map(|d| {
+                                        d.with_timezone(&chrono::Local)
+                                            .format("%Y-%m-%d %H:%M")
+                                            .to_string()
+                                    })
+                                    .unwrap_or_default(),
+                            );
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        let (delete, keep) = ui
+            .horizontal(|ui| (ui.button("Delete").clicked(), ui.button("Keep").clicked()))
+            .inner;
+
+        if delete {
+            let hashes: Vec<_> = orphans.iter().map(|o| o.hash.clone()).collect();
+            self.orphans = None;
+            self.start("Deleting orphaned scores", move |_| {
+                let removed = block_on!(FileSongProvider::remove_orphaned_scores(&hashes))?;
+                Ok(TaskResult::Done(format!("Deleted {removed} scores")))
+            });
+        } else if keep {
+            self.orphans = None;
+        }
+    }
+
+    /// Shows the progress of the running task and its outcome once it's done
+    pub fn window(&mut self, ctx: &egui::Context) {
+        if let Some(task) = self.running.take() {
+            match task.promise.try_take() {
+                Ok(result) => {
+                    self.sizes = None;
+                    match result {
+                        Ok(TaskResult::Done(message)) => self.finished = Some(message),
+                        Ok(TaskResult::Orphans(orphans)) if orphans.is_empty() => {
+                            self.finished = Some("No orphaned scores found".into())
+                        }
+                        Ok(TaskResult::Orphans(orphans)) => self.orphans = Some(orphans),
+                        Err(e) => self.finished = Some(format!("{} failed: {e}", task.name)),
+                    }
+                }
+                Err(promise) => {
+                    self.running = Some(RunningTask { promise, ..task });
+                }
+            }
+        }
+
+        let title = match &self.running {
+            Some(task) => task.name,
+            None if self.orphans.is_some() => "Orphaned scores",
+            None if self.finished.is_some() => "Maintenance",
+            None => return,
+        };
+
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                if let Some(task) = &self.running {
+                    let (text, fraction) = task.progress.get();
+                    match fraction {
+                        Some(fraction) => {
+                            ui.add(egui::ProgressBar::new(fraction).text(text));
+                        }
+                        None => {
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label(text);
+                            });
+                        }
+                    }
+                } else if self.orphans.is_some() {
+                    self.orphans_ui(ui);
+                } else if let Some(message) = &self.finished {
+                    ui.label(message);
+                    if ui.button("OK").clicked() {
+                        self.finished = None;
+                    }
+                }
+            });
+    }
+}
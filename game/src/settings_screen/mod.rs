@@ -1,21 +1,41 @@
+mod chord_binding;
 mod controller_binding;
+mod keyboard_binding;
+mod library;
+mod maintenance;
+mod sections;
 pub mod skin_select;
+mod transfer;
 
-use std::{collections::HashMap, path::PathBuf, sync::mpsc::Sender, time::Duration};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::mpsc::Sender,
+    time::{Duration, Instant, SystemTime},
+};
 
 use di::ServiceProvider;
 use egui::{CollapsingResponse, InnerResponse, RichText, Separator, Slider, TextEdit, Ui};
 use gilrs::GamepadId;
 use itertools::Itertools;
+use kson::Side;
 use skin_select::SkinMeta;
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, Event, WindowEvent},
     monitor::MonitorHandle,
 };
 
 use crate::{
-    config::{Fullscreen, GameConfig, ScoreDisplayMode, ScoreScreenshot},
-    game::HitWindow,
+    button_codes::{UscButton, UscInputEvent},
+    config::{
+        Fullscreen, GameConfig, GraphicsSettings, InputDevice, Overrides, ScoreDisplayMode,
+        ScoreScreenshot, UiTheme,
+    },
+    confirm_dialog::ConfirmDialog,
+    display_rotation::DisplayRotation,
+    fallback_skin::SkinReport,
+    game::{hold::HoldGrace, HitWindow, HudVisibility},
     game_main::ControlMessage,
     help::AsyncPicker,
     input_state::InputState,
@@ -23,19 +43,39 @@ use crate::{
     skin_settings::SkinSettingValue,
 };
 
-use self::controller_binding::BindingUi;
+use self::{
+    chord_binding::ChordBindingUi, controller_binding::BindingUi, keyboard_binding::KeyBindingUi,
+    library::LibraryUi, maintenance::MaintenanceUi, sections::SettingsSection,
+    transfer::TransferUi,
+};
+
+const DISPLAY_REVERT_TIME: Duration = Duration::from_secs(10);
+const LOCKED_TEXT: &str = "🔒 Locked by Override.cfg";
 
 pub struct SettingsScreen {
     altered_settings: GameConfig,
+    /// Settings from when the screen was opened, restored on cancel
+    snapshot: GameConfig,
+    applied: bool,
+    /// Set after a display mode change until it's confirmed, holds the previous mode
+    display_confirm: Option<(Instant, Fullscreen)>,
+    /// Open while a destructive action waits for confirmation
+    confirm: Option<ConfirmDialog<SettingsScreen>>,
     close: bool,
     input_state: InputState,
     selected_controller: Option<GamepadId>,
     binding_ui: Option<BindingUi>,
+    key_binding_ui: KeyBindingUi,
+    chord_binding_ui: ChordBindingUi,
+    library_ui: LibraryUi,
+    transfer_ui: TransferUi,
+    maintenance_ui: MaintenanceUi,
     controllers: HashMap<GamepadId, String>,
+    controller_uuids: HashMap<uuid::Uuid, String>,
     monitors: Vec<MonitorHandle>,
     primary_monitor: Option<MonitorHandle>,
     tx: Sender<ControlMessage>,
-    skins: Vec<(SkinMeta, PathBuf)>,
+    skins: Vec<(SkinMeta, PathBuf, SkinReport)>,
 }
 
 impl SettingsScreen {
@@ -45,12 +85,18 @@ impl SettingsScreen {
         window: &winit::window::Window,
     ) -> Self {
         let input_state = InputState::clone(&services.get_required());
-        let controllers = {
+        let (controllers, controller_uuids) = {
             let lock_gilrs = input_state.lock_gilrs();
-            lock_gilrs
-                .gamepads()
-                .map(|(id, pad)| (id, pad.name().to_string()))
-                .collect()
+            (
+                lock_gilrs
+                    .gamepads()
+                    .map(|(id, pad)| (id, pad.name().to_string()))
+                    .collect(),
+                lock_gilrs
+                    .gamepads()
+                    .map(|(_, pad)| (uuid::Uuid::from_bytes(pad.uuid()), pad.name().to_string()))
+                    .collect(),
+            )
         };
 
         let monitors = window.available_monitors().collect_vec();
@@ -79,15 +125,34 @@ impl SettingsScreen {
                     )
                 }
             })
+            .map(|(meta, path)| {
+                let report = SkinReport::check(&path);
+                (meta, path, report)
+            })
             .collect();
 
+        let snapshot = GameConfig::get().clone();
+
         Self {
-            altered_settings: GameConfig::get().clone(),
+            altered_settings: snapshot.clone(),
+            snapshot,
+            applied: false,
+            display_confirm: None,
+            confirm: None,
             close: false,
-            input_state,
             selected_controller: None,
             binding_ui: None,
+            key_binding_ui: KeyBindingUi::new(input_state.clone()),
+            chord_binding_ui: ChordBindingUi::new(input_state.clone()),
+            library_ui: LibraryUi::new(services.get_required()),
+            transfer_ui: TransferUi::new(),
+            maintenance_ui: MaintenanceUi::new(
+                services.get_required(),
+                services.get_required_mut(),
+            ),
             controllers,
+            controller_uuids,
+            input_state,
             monitors,
             primary_monitor,
             tx,
@@ -95,11 +160,33 @@ impl SettingsScreen {
         }
     }
 
-    fn apply(&self) {
-        let mut c = GameConfig::get_mut();
-        *c = self.altered_settings.clone();
+    fn apply(&mut self) {
+        self.altered_settings.disable_unusable_cab_mode();
+        if let Err(e) = self.altered_settings.apply_overrides() {
+            log::error!("Could not apply config overrides: {e}");
+        }
+        {
+            let mut c = GameConfig::get_mut();
+            *c = self.altered_settings.clone();
+        }
+        self.applied = true;
         _ = self.tx.send(ControlMessage::ApplySettings);
     }
+
+    fn cancel(&mut self) {
+        if self.applied {
+            self.altered_settings = self.snapshot.clone();
+            self.apply();
+        }
+        self.close = true;
+    }
+
+    fn revert_display(&mut self) {
+        if let Some((_, previous)) = self.display_confirm.take() {
+            self.altered_settings.graphics.fullscreen = previous;
+            self.apply();
+        }
+    }
 }
 
 pub struct HitFrames(pub f64);
@@ -136,14 +223,49 @@ impl Scene for SettingsScreen {
         "Settings"
     }
 
+    fn on_event(&mut self, event: &Event<UscInputEvent>) {
+        if self.confirm.is_some() {
+            return;
+        }
+
+        if let Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { event, .. },
+            ..
+        } = event
+        {
+            if event.state == ElementState::Pressed && !event.repeat {
+                self.key_binding_ui
+                    .on_key(event.physical_key, &mut self.altered_settings);
+            }
+        }
+    }
+
+    fn on_button_pressed(&mut self, button: UscButton, _timestamp: SystemTime) {
+        ConfirmDialog::route_button(self, |s| &mut s.confirm, button);
+    }
+
     fn tick(
         &mut self,
         _dt: f64,
-        _knob_state: crate::button_codes::LaserState,
+        knob_state: crate::button_codes::LaserState,
     ) -> anyhow::Result<()> {
+        if let Some(confirm) = &mut self.confirm {
+            confirm.on_knob(
+                knob_state.get_axis(Side::Left).delta + knob_state.get_axis(Side::Right).delta,
+            );
+        }
         if let Some(binding_ui) = self.binding_ui.as_mut() {
             binding_ui.run_checks(&mut self.altered_settings)
         }
+        self.chord_binding_ui.run_checks(&mut self.altered_settings);
+
+        if self
+            .display_confirm
+            .as_ref()
+            .is_some_and(|(started, _)| started.elapsed() >= DISPLAY_REVERT_TIME)
+        {
+            self.revert_display();
+        }
 
         Ok(())
     }
@@ -153,34 +275,114 @@ impl Scene for SettingsScreen {
     }
 
     fn render_egui(&mut self, ctx: &egui::Context) -> anyhow::Result<()> {
+        if let Some((started, _)) = &self.display_confirm {
+            let remaining = DISPLAY_REVERT_TIME.saturating_sub(started.elapsed());
+            let mut keep = false;
+            let mut revert = false;
+            egui::Window::new("Display settings")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "Keep these display settings? Reverting in {} s",
+                        remaining.as_secs_f32().ceil()
+                    ));
+                    ui.horizontal(|ui| {
+                        keep = ui.button("Keep").clicked();
+                        revert = ui.button("Revert").clicked();
+                    });
+                });
+
+            if keep {
+                self.display_confirm = None;
+                self.close = true;
+            } else if revert {
+                self.revert_display();
+            }
+        }
+
         egui::panel::TopBottomPanel::bottom("settings_buttons").show(ctx, |ui| {
             if ui.button("Cancel").clicked() {
-                self.close = true;
+                self.cancel();
             }
 
             if ui.button("Apply").clicked() {
+                let previous = GameConfig::get().graphics.fullscreen.clone();
                 self.apply();
-                self.close = true;
+                if !previous.same_mode(&self.altered_settings.graphics.fullscreen) {
+                    self.display_confirm = Some((Instant::now(), previous));
+                } else {
+                    self.close = true;
+                }
             }
         });
 
+        let mut reset = None;
+        let mut clear_binds = None;
+        let overrides = self.altered_settings.overrides.clone();
+
         egui::panel::CentralPanel::default().show(ctx, |ui| {
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                settings_section("Input", ui, |ui| {
+            // Scrolls both ways so wide rows stay reachable at large UI scales
+            egui::ScrollArea::both().show(ui, |ui| {
+                settings_section(SettingsSection::Input, ui, &mut reset, |ui| {
                     ui.label("Offset");
-                    ui.add(Slider::new(
-                        &mut self.altered_settings.global_offset,
-                        -100..=100,
-                    ));
+                    pinned(ui, &overrides, "global_offset", |ui| {
+                        ui.add(
+                            Slider::new(&mut self.altered_settings.global_offset, -100.0..=100.0)
+                                .step_by(0.1)
+                                .suffix(" ms"),
+                        )
+                    });
                     ui.end_row();
-                    ui.checkbox(
-                        &mut self.altered_settings.keyboard_buttons,
-                        "Keyboard buttons",
-                    );
+                    let offsets = &mut self.altered_settings.device_offsets;
+                    let devices = [
+                        ("Keyboard offset".to_string(), InputDevice::Keyboard),
+                        ("Mouse knob offset".to_string(), InputDevice::Mouse),
+                    ]
+                    .into_iter()
+                    .chain(self.controller_uuids.iter().map(|(uuid, name)| {
+                        (format!("{name} offset"), InputDevice::Controller(*uuid))
+                    }));
+                    for (label, device) in devices {
+                        let mut offset = offsets.get(device);
+                        ui.label(label);
+                        if ui
+                            .add(
+                                egui::DragValue::new(&mut offset)
+                                    .speed(0.1)
+                                    .clamp_range(-100.0..=100.0)
+                                    .suffix(" ms"),
+                            )
+                            .changed()
+                        {
+                            *offsets.get_mut(device) = offset;
+                        }
+                        ui.end_row();
+                    }
+                    pinned(ui, &overrides, "keyboard_buttons", |ui| {
+                        ui.checkbox(
+                            &mut self.altered_settings.keyboard_buttons,
+                            "Keyboard buttons",
+                        )
+                    });
+                    ui.end_row();
+                    pinned(ui, &overrides, "keyboard_knobs", |ui| {
+                        ui.checkbox(&mut self.altered_settings.keyboard_knobs, "Keyboard knobs")
+                    });
                     ui.end_row();
-                    ui.checkbox(&mut self.altered_settings.keyboard_knobs, "Keyboard knobs");
+                    self.key_binding_ui.ui(ui, &mut self.altered_settings);
+                    self.chord_binding_ui.ui(ui, &mut self.altered_settings);
+                    pinned(ui, &overrides, "mouse_knobs", |ui| {
+                        ui.checkbox(&mut self.altered_settings.mouse_knobs, "Mouse knobs")
+                    });
                     ui.end_row();
-                    ui.checkbox(&mut self.altered_settings.mouse_knobs, "Mouse knobs");
+                    pinned(ui, &overrides, "input_thread", |ui| {
+                        ui.checkbox(
+                            &mut self.altered_settings.input_thread,
+                            "Poll controllers on a separate thread (requires restart)",
+                        )
+                    });
                     ui.end_row();
 
                     egui::ComboBox::from_label("Controller")
@@ -213,24 +415,55 @@ impl Scene for SettingsScreen {
                         });
                     ui.end_row();
                     if let Some(binding_ui) = self.binding_ui.as_mut() {
-                        binding_ui.ui(ui, &mut self.altered_settings);
+                        if binding_ui.ui(ui, &mut self.altered_settings) {
+                            clear_binds = Some(binding_ui.uuid());
+                        }
                     }
+                    ui.end_row();
+
+                    let versus_controller = match self.altered_settings.versus_controller {
+                        Some(uuid) => self
+                            .controller_uuids
+                            .get(&uuid)
+                            .map(String::as_str)
+                            .unwrap_or("Disconnected"),
+                        None => "None",
+                    };
+                    egui::ComboBox::from_label("Player 2 controller (versus mode)")
+                        .selected_text(versus_controller)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.altered_settings.versus_controller,
+                                None,
+                                "None",
+                            );
+                            for (uuid, name) in self.controller_uuids.iter() {
+                                ui.selectable_value(
+                                    &mut self.altered_settings.versus_controller,
+                                    Some(*uuid),
+                                    name,
+                                );
+                            }
+                        });
                 });
 
-                settings_section("Game", ui, |ui| {
+                settings_section(SettingsSection::Game, ui, &mut reset, |ui| {
                     let mut crit_frames: HitFrames =
                         self.altered_settings.hit_window.perfect.into();
                     let mut near_frames: HitFrames = self.altered_settings.hit_window.good.into();
                     let mut hold_frames: HitFrames = self.altered_settings.hit_window.hold.into();
+                    let mut early_miss_frames: HitFrames =
+                        self.altered_settings.hit_window.early_miss.into();
 
                     ui.label("Hit windows (in frames @ 60fps)");
                     ui.end_row();
                     egui::Grid::new("hit_windows")
-                        .num_columns(3)
+                        .num_columns(4)
                         .show(ui, |ui| {
                             ui.label("Crit");
                             ui.label("Near");
                             ui.label("Hold");
+                            ui.label("Early miss");
                             ui.end_row();
 
                             if ui
@@ -265,6 +498,21 @@ impl Scene for SettingsScreen {
                             {
                                 self.altered_settings.hit_window.hold = hold_frames.into();
                             }
+
+                            if ui
+                                .add(
+                                    egui::DragValue::new(&mut early_miss_frames.0)
+                                        .max_decimals(1)
+                                        .clamp_range(0.01..=100.0),
+                                )
+                                .on_hover_text(
+                                    "Presses this early before a chip miss it, earlier presses are ignored",
+                                )
+                                .changed()
+                            {
+                                self.altered_settings.hit_window.early_miss =
+                                    early_miss_frames.into();
+                            }
                         });
                     ui.end_row();
                     if ui.button("Set Normal").clicked() {
@@ -276,6 +524,89 @@ impl Scene for SettingsScreen {
 
                     ui.end_row();
 
+                    ui.label("Hold end grace");
+                    ui.horizontal(|ui| {
+                        let grace = &mut self.altered_settings.hold_grace;
+                        match grace {
+                            HoldGrace::Ticks(ticks) => {
+                                ui.add(egui::DragValue::new(ticks).clamp_range(0..=240));
+                            }
+                            HoldGrace::Millis(ms) => {
+                                ui.add(
+                                    egui::DragValue::new(ms)
+                                        .max_decimals(1)
+                                        .clamp_range(0.0..=500.0),
+                                );
+                            }
+                        }
+
+                        egui::ComboBox::from_id_source("hold_grace_unit")
+                            .selected_text(match grace {
+                                HoldGrace::Ticks(_) => "ticks",
+                                HoldGrace::Millis(_) => "ms",
+                            })
+                            .show_ui(ui, |ui| {
+                                let ticks = matches!(grace, HoldGrace::Ticks(_));
+                                if ui.selectable_label(ticks, "ticks").clicked() && !ticks {
+                                    *grace = HoldGrace::default();
+                                }
+                                if ui.selectable_label(!ticks, "ms").clicked() && ticks {
+                                    *grace = HoldGrace::Millis(100.0);
+                                }
+                            });
+                    });
+                    ui.end_row();
+
+                    ui.label("Lead-in");
+                    let mut lead_in = self.altered_settings.lead_in.as_secs_f64();
+                    if ui
+                        .add(
+                            egui::DragValue::new(&mut lead_in)
+                                .suffix(" s")
+                                .speed(0.1)
+                                .clamp_range(0.0..=10.0),
+                        )
+                        .changed()
+                    {
+                        self.altered_settings.lead_in = Duration::from_secs_f64(lead_in);
+                    }
+                    ui.end_row();
+
+                    let has_service_chord = self.altered_settings.has_service_chord();
+                    let cab_mode = &mut self.altered_settings.cab_mode;
+                    ui.add_enabled(
+                        has_service_chord || cab_mode.enabled,
+                        egui::Checkbox::new(&mut cab_mode.enabled, "Cab mode"),
+                    )
+                    .on_hover_text(
+                        "Players can't close the game, the service menu chord opens a menu to exit \
+                         it. The close button of the window changes after a restart.",
+                    )
+                    .on_disabled_hover_text("Bind a service menu chord in the input settings first");
+                    if cab_mode.enabled && !has_service_chord {
+                        ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            "No service menu chord, cab mode is turned off when applied",
+                        );
+                    }
+                    ui.end_row();
+                    if cab_mode.enabled {
+                        ui.label("Results idle timeout");
+                        let mut idle_timeout = cab_mode.idle_timeout.as_secs_f64();
+                        if ui
+                            .add(
+                                egui::DragValue::new(&mut idle_timeout)
+                                    .suffix(" s")
+                                    .clamp_range(0.0..=600.0),
+                            )
+                            .on_hover_text("Goes back to the title screen, 0 stays on the results")
+                            .changed()
+                        {
+                            cab_mode.idle_timeout = Duration::from_secs_f64(idle_timeout);
+                        }
+                        ui.end_row();
+                    }
+
                     let mut songs_path = self
                         .altered_settings
                         .songs_path
@@ -349,24 +680,137 @@ impl Scene for SettingsScreen {
                     );
 
                     self.altered_settings.screenshot_path = PathBuf::from(screenshot_path);
+                    ui.end_row();
+
+                    ui.checkbox(
+                        &mut self.altered_settings.full_hit_stats,
+                        "Send every hit to the result screen (slower)",
+                    );
+                    ui.end_row();
+
+                    ui.checkbox(
+                        &mut self.altered_settings.modified_best_scores,
+                        "Count mirror and random plays as best scores",
+                    );
+                    ui.end_row();
                 });
 
-                settings_section("Graphics", ui, |ui| {
-                    ui.checkbox(&mut self.altered_settings.graphics.vsync, "VSync");
+                settings_section(SettingsSection::Hud, ui, &mut reset, |ui| {
+                    pinned(ui, &overrides, "hud.score", |ui| {
+                        ui.checkbox(&mut self.altered_settings.hud.score, "Score")
+                    });
                     ui.end_row();
-                    ui.checkbox(&mut self.altered_settings.graphics.show_fps, "Show FPS");
+                    pinned(ui, &overrides, "hud.combo", |ui| {
+                        ui.checkbox(&mut self.altered_settings.hud.combo, "Combo")
+                    });
+                    ui.end_row();
+                    pinned(ui, &overrides, "hud.gauge", |ui| {
+                        ui.checkbox(&mut self.altered_settings.hud.gauge, "Gauge")
+                    });
+                    ui.end_row();
+                    pinned(ui, &overrides, "hud.songInfo", |ui| {
+                        ui.checkbox(
+                            &mut self.altered_settings.hud.song_info,
+                            "Song title, artist and jacket",
+                        )
+                    });
+                    ui.end_row();
+                    pinned(ui, &overrides, "hud.hitDeltas", |ui| {
+                        ui.checkbox(&mut self.altered_settings.hud.hit_deltas, "Hit deltas")
+                    });
+                    ui.end_row();
+                    pinned(ui, &overrides, "hud.critCursors", |ui| {
+                        ui.checkbox(
+                            &mut self.altered_settings.hud.crit_cursors,
+                            "Crit line cursors",
+                        )
+                    });
+                    ui.end_row();
+                    if ui.button("Minimal").clicked() {
+                        self.altered_settings.hud = HudVisibility::MINIMAL;
+                    }
+                    ui.end_row();
+                });
+
+                settings_section(SettingsSection::Library, ui, &mut reset, |ui| {
+                    self.library_ui.ui(ui, &mut self.altered_settings);
+                });
+
+                settings_section(SettingsSection::Graphics, ui, &mut reset, |ui| {
+                    pinned(ui, &overrides, "graphics.vsync", |ui| {
+                        ui.checkbox(&mut self.altered_settings.graphics.vsync, "VSync")
+                    });
+                    ui.end_row();
+                    pinned(ui, &overrides, "graphics.show_fps", |ui| {
+                        ui.checkbox(&mut self.altered_settings.graphics.show_fps, "Show FPS")
+                    });
+                    ui.end_row();
+                    pinned(ui, &overrides, "graphics.show_frame_graph", |ui| {
+                        ui.checkbox(
+                            &mut self.altered_settings.graphics.show_frame_graph,
+                            "Show frame time graph",
+                        )
+                    });
                     ui.end_row();
                     ui.label("Target FPS");
-                    ui.add(
-                        egui::DragValue::new(&mut self.altered_settings.graphics.target_fps)
-                            .clamp_range(0..=1000),
-                    );
+                    pinned(ui, &overrides, "graphics.target_fps", |ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut self.altered_settings.graphics.target_fps)
+                                .clamp_range(0..=1000),
+                        )
+                    });
 
                     ui.end_row();
 
-                    ui.checkbox(
-                        &mut self.altered_settings.graphics.disable_bg,
-                        "Disable Backgrounds",
+                    pinned(ui, &overrides, "graphics.disable_bg", |ui| {
+                        ui.checkbox(
+                            &mut self.altered_settings.graphics.disable_bg,
+                            "Disable Backgrounds",
+                        )
+                    });
+                    ui.end_row();
+                    pinned(ui, &overrides, "graphics.reduce_flashing", |ui| {
+                        ui.checkbox(
+                            &mut self.altered_settings.graphics.reduce_flashing,
+                            "Reduce flashing",
+                        )
+                    });
+                    ui.end_row();
+                    pinned(ui, &overrides, "graphics.ui_theme", |ui| {
+                        let theme = &mut self.altered_settings.graphics.ui_theme;
+                        egui::ComboBox::new("ui_theme", "UI theme")
+                            .selected_text(theme.to_string())
+                            .show_ui(ui, |ui| {
+                                for value in [UiTheme::System, UiTheme::Dark, UiTheme::Light] {
+                                    ui.selectable_value(theme, value, value.to_string());
+                                }
+                            })
+                    });
+                    ui.end_row();
+                    ui.label("UI scale");
+                    pinned(ui, &overrides, "graphics.ui_scale", |ui| {
+                        ui.add(
+                            Slider::new(
+                                &mut self.altered_settings.graphics.ui_scale,
+                                GraphicsSettings::UI_SCALE_RANGE,
+                            )
+                            .step_by(0.05)
+                            .suffix("x"),
+                        )
+                    });
+                    ui.end_row();
+                    ui.label("Image cache (MB)");
+                    ui.add(
+                        egui::DragValue::new(&mut self.altered_settings.graphics.image_cache_mb)
+                            .clamp_range(0..=8192),
+                    );
+                    ui.end_row();
+                    ui.label("Thumbnail cache (MB)");
+                    ui.add(
+                        egui::DragValue::new(
+                            &mut self.altered_settings.graphics.thumbnail_cache_mb,
+                        )
+                        .clamp_range(0..=8192),
                     );
                     ui.end_row();
                     egui::ComboBox::from_label("Anti Aliasing")
@@ -386,6 +830,18 @@ impl Scene for SettingsScreen {
                             }
                         });
                     ui.end_row();
+                    egui::ComboBox::from_label("Display rotation")
+                        .selected_text(self.altered_settings.graphics.rotation.to_string())
+                        .show_ui(ui, |ui| {
+                            for rotation in DisplayRotation::ALL {
+                                ui.selectable_value(
+                                    &mut self.altered_settings.graphics.rotation,
+                                    rotation,
+                                    rotation.to_string(),
+                                );
+                            }
+                        });
+                    ui.end_row();
                     let window_mode = match self.altered_settings.graphics.fullscreen {
                         crate::config::Fullscreen::Windowed { .. } => 0,
                         crate::config::Fullscreen::Borderless { .. } => 1,
@@ -520,38 +976,63 @@ impl Scene for SettingsScreen {
                     }
                 });
 
-                settings_section("Audio", ui, |ui| {
+                settings_section(SettingsSection::Audio, ui, &mut reset, |ui| {
                     ui.label("Master avolume");
-                    ui.add(
-                        Slider::new(&mut self.altered_settings.master_volume, 0.0..=1.0)
-                            .custom_formatter(|x, _| format!("{:.0}%", x * 100.0))
-                            .custom_parser(|x| x.trim_matches('%').trim().parse().ok()),
-                    );
+                    pinned(ui, &overrides, "master_volume", |ui| {
+                        ui.add(
+                            Slider::new(&mut self.altered_settings.master_volume, 0.0..=1.0)
+                                .custom_formatter(|x, _| format!("{:.0}%", x * 100.0))
+                                .custom_parser(|x| x.trim_matches('%').trim().parse().ok()),
+                        )
+                    });
 
                     ui.label("Slam volume");
-                    ui.add(
-                        Slider::new(&mut self.altered_settings.slam_volume, 0.0..=1.0)
-                            .custom_formatter(|x, _| format!("{:.0}%", x * 100.0))
-                            .custom_parser(|x| x.trim_matches('%').trim().parse().ok()),
-                    )
+                    pinned(ui, &overrides, "slam_volume", |ui| {
+                        ui.add(
+                            Slider::new(&mut self.altered_settings.slam_volume, 0.0..=1.0)
+                                .custom_formatter(|x, _| format!("{:.0}%", x * 100.0))
+                                .custom_parser(|x| x.trim_matches('%').trim().parse().ok()),
+                        )
+                    });
+
+                    pinned(ui, &overrides, "slam_pitch_variance", |ui| {
+                        ui.checkbox(
+                            &mut self.altered_settings.slam_pitch_variance,
+                            "Vary slam pitch",
+                        )
+                    });
+                    pinned(ui, &overrides, "slam_tail", |ui| {
+                        ui.checkbox(&mut self.altered_settings.slam_tail, "Slam noise tail")
+                    });
+                    pinned(ui, &overrides, "nav_sounds", |ui| {
+                        ui.checkbox(&mut self.altered_settings.nav_sounds, "Menu sounds")
+                            .on_hover_text("For skins that don't play their own menu sounds")
+                    });
                 });
 
-                settings_section("Skin", ui, |ui| {
-                    let current_skin = self
+                settings_section(SettingsSection::Skin, ui, &mut reset, |ui| {
+                    let selected = self
                         .skins
                         .iter()
-                        .find(|x| x.1.ends_with(&self.altered_settings.skin))
-                        .map(|x| x.0.name.clone())
-                        .unwrap_or_default();
+                        .find(|x| x.1.ends_with(&self.altered_settings.skin));
+                    let current_skin = selected.map(|x| x.0.name.clone()).unwrap_or_default();
+                    let current_report = selected.map(|x| x.2.clone());
 
                     egui::ComboBox::new("skin_select", "Selected skin")
                         .selected_text(&current_skin)
                         .show_ui(ui, |ui| {
-                            for (meta, path) in self.skins.iter() {
-                                if ui
-                                    .selectable_label(path.ends_with(&current_skin), &meta.name)
-                                    .clicked()
-                                {
+                            for (meta, path, report) in self.skins.iter() {
+                                let label = if report.is_ok() {
+                                    meta.name.clone()
+                                } else {
+                                    format!("⚠ {}", meta.name)
+                                };
+                                let mut response =
+                                    ui.selectable_label(path.ends_with(&current_skin), label);
+                                if !report.is_ok() {
+                                    response = response.on_hover_text(report.to_string());
+                                }
+                                if response.clicked() {
                                     if let Some(v) = path
                                         .file_name()
                                         .and_then(|x| x.to_str())
@@ -564,6 +1045,13 @@ impl Scene for SettingsScreen {
                         });
 
                     ui.end_row();
+                    if let Some(report) = current_report.filter(|r| !r.is_ok()) {
+                        ui.colored_label(
+                            egui::Color32::YELLOW,
+                            format!("Skin is incomplete\n{report}"),
+                        );
+                        ui.end_row();
+                    }
                     ui.separator();
                     ui.end_row();
 
@@ -580,6 +1068,7 @@ impl Scene for SettingsScreen {
                                 label,
                                 name,
                                 values,
+                                quick: _,
                             } => {
                                 let Some(SkinSettingValue::Text(t)) =
                                     self.altered_settings.skin_settings.get_mut(name)
@@ -627,6 +1116,7 @@ impl Scene for SettingsScreen {
                                 default: _,
                                 label,
                                 name,
+                                quick: _,
                             } => {
                                 let Some(SkinSettingValue::Bool(v)) =
                                     self.altered_settings.skin_settings.get_mut(name)
@@ -641,6 +1131,7 @@ impl Scene for SettingsScreen {
                                 name,
                                 min,
                                 max,
+                                quick: _,
                             } => {
                                 let Some(SkinSettingValue::Float(v)) =
                                     self.altered_settings.skin_settings.get_mut(name)
@@ -656,6 +1147,7 @@ impl Scene for SettingsScreen {
                                 name,
                                 min,
                                 max,
+                                quick: _,
                             } => {
                                 let Some(SkinSettingValue::Integer(v)) =
                                     self.altered_settings.skin_settings.get_mut(name)
@@ -669,9 +1161,54 @@ impl Scene for SettingsScreen {
                         ui.end_row();
                     }
                 });
+
+                ui.collapsing(RichText::new("Export / import").heading(), |ui| {
+                    ui.horizontal_wrapped(|ui| {
+                        self.transfer_ui.ui(ui, &mut self.altered_settings);
+                    });
+                });
+
+                ui.collapsing(RichText::new("Maintenance").heading(), |ui| {
+                    ui.horizontal_wrapped(|ui| {
+                        self.maintenance_ui.ui(ui);
+                    });
+                });
             });
         });
 
+        if let Some(section) = reset {
+            self.confirm = Some(ConfirmDialog::new(
+                format!("Reset the {} settings to their defaults?", section.name()),
+                "Reset",
+                "Cancel",
+                move |screen: &mut SettingsScreen, reset| {
+                    if reset {
+                        section.reset(&mut screen.altered_settings);
+                    }
+                },
+            ));
+        }
+
+        if let Some(uuid) = clear_binds {
+            self.confirm = Some(ConfirmDialog::new(
+                "Clear all binds of this controller?",
+                "Clear",
+                "Cancel",
+                move |screen: &mut SettingsScreen, clear| {
+                    if clear {
+                        if let Some(binds) = screen.altered_settings.controller_binds.get_mut(&uuid)
+                        {
+                            binds.axis.clear();
+                            binds.buttons.clear();
+                        }
+                    }
+                },
+            ));
+        }
+
+        self.maintenance_ui.window(ctx);
+        ConfirmDialog::show_in(self, |s| &mut s.confirm, ctx);
+
         Ok(())
     }
 }
@@ -722,11 +1259,41 @@ fn aa_text(aa: u8) -> String {
 }
 
 fn settings_section<T>(
-    name: &str,
+    section: SettingsSection,
     ui: &mut Ui,
+    reset: &mut Option<SettingsSection>,
     add_contents: impl FnOnce(&mut Ui) -> T,
 ) -> CollapsingResponse<InnerResponse<T>> {
-    ui.collapsing(RichText::new(name).heading(), |ui| {
-        ui.horizontal_wrapped(add_contents)
+    let locked = GameConfig::get()
+        .overrides
+        .is_section_locked(section.name());
+    ui.collapsing(RichText::new(section.name()).heading(), |ui| {
+        if locked {
+            ui.label(LOCKED_TEXT);
+        }
+        ui.add_enabled_ui(!locked, |ui| {
+            let response = ui.horizontal_wrapped(add_contents);
+            ui.separator();
+            if ui.button("Reset section to defaults").clicked() {
+                *reset = Some(section);
+            }
+            response
+        })
+        .inner
     })
 }
+
+/// Shows the setting at the dotted `key` as locked if `Override.cfg` pins it
+fn pinned<R>(
+    ui: &mut Ui,
+    overrides: &Overrides,
+    key: &str,
+    add_contents: impl FnOnce(&mut Ui) -> R,
+) -> R {
+    let is_pinned = overrides.is_pinned(key);
+    let response = ui.add_enabled_ui(!is_pinned, add_contents);
+    if is_pinned {
+        response.response.on_disabled_hover_text(LOCKED_TEXT);
+    }
+    response.inner
+}
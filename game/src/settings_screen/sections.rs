@@ -0,0 +1,80 @@
+use crate::config::GameConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsSection {
+    Input,
+    Game,
+    Hud,
+    Library,
+    Graphics,
+    Audio,
+    Skin,
+}
+
+impl SettingsSection {
+    pub fn name(self) -> &'static str {
+        match self {
+            SettingsSection::Input => "Input",
+            SettingsSection::Game => "Game",
+            SettingsSection::Hud => "HUD",
+            SettingsSection::Library => "Library",
+            SettingsSection::Graphics => "Graphics",
+            SettingsSection::Audio => "Audio",
+            SettingsSection::Skin => "Skin",
+        }
+    }
+
+    /// Resets every setting shown in this section, leaving the rest untouched
+    pub fn reset(self, config: &mut GameConfig) {
+        let d = GameConfig::default();
+        match self {
+            SettingsSection::Input => {
+                config.global_offset = d.global_offset;
+                config.device_offsets = d.device_offsets;
+                config.keyboard_buttons = d.keyboard_buttons;
+                config.keyboard_knobs = d.keyboard_knobs;
+                config.keybinds = d.keybinds;
+                config.chords = d.chords;
+                config.mouse_knobs = d.mouse_knobs;
+                config.input_thread = d.input_thread;
+                config.controller_binds = d.controller_binds;
+                config.versus_controller = d.versus_controller;
+            }
+            SettingsSection::Game => {
+                config.hit_window = d.hit_window;
+                config.hold_grace = d.hold_grace;
+                config.lead_in = d.lead_in;
+                // The songs folder is left alone, resetting it would empty the library
+                config.score_display = d.score_display;
+                config.score_screenshots = d.score_screenshots;
+                config.screenshot_path = d.screenshot_path;
+                config.full_hit_stats = d.full_hit_stats;
+                config.modified_best_scores = d.modified_best_scores;
+                config.cab_mode = d.cab_mode;
+            }
+            SettingsSection::Hud => {
+                config.hud = d.hud;
+            }
+            SettingsSection::Library => {
+                config.library = d.library;
+            }
+            SettingsSection::Graphics => {
+                config.graphics = d.graphics;
+                config.distant_button_scale = d.distant_button_scale;
+                config.laser_hues = d.laser_hues;
+            }
+            SettingsSection::Audio => {
+                config.master_volume = d.master_volume;
+                config.slam_volume = d.slam_volume;
+                config.slam_pitch_variance = d.slam_pitch_variance;
+                config.slam_tail = d.slam_tail;
+                config.nav_sounds = d.nav_sounds;
+            }
+            SettingsSection::Skin => {
+                config.skin = d.skin;
+                config.skin_definition = d.skin_definition;
+                config.skin_settings = d.skin_settings;
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::Path;
+
+use crate::{config::GameConfig, help::AsyncPicker};
+
+/// Export and import of the settings being edited, for copying them to another machine
+pub struct TransferUi {
+    path: String,
+    report: Vec<String>,
+}
+
+impl TransferUi {
+    pub fn new() -> Self {
+        Self {
+            path: crate::default_game_dir()
+                .join("settings.json")
+                .to_string_lossy()
+                .into_owned(),
+            report: vec![],
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, settings: &mut GameConfig) {
+        ui.label("Settings file");
+        AsyncPicker::new()
+            .file()
+            .show("settings_file".into(), &mut self.path, ui);
+        ui.end_row();
+
+        if ui.button("Export").clicked() {
+            self.report = match settings.export_settings(Path::new(&self.path)) {
+                Ok(()) => vec![format!("Exported settings to {}", self.path)],
+                Err(e) => vec![format!("Could not export settings: {e}")],
+            };
+        }
+
+        if ui.button("Import").clicked() {
+            self.report = match settings.import_settings(Path::new(&self.path)) {
+                Ok(conflicts) => std::iter::once("Imported settings, apply to keep them".into())
+                    .chain(conflicts.iter().map(ToString::to_string))
+                    .collect(),
+                Err(e) => vec![format!("Could not import settings: {e}")],
+            };
+        }
+        ui.end_row();
+
+        for line in &self.report {
+            ui.label(line);
+            ui.end_row();
+        }
+    }
+}
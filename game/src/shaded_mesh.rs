@@ -1,10 +1,13 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::Path,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
 };
 
-use anyhow::ensure;
+use anyhow::{bail, ensure};
 use di::RefMut;
 use itertools::Itertools;
 use puffin::profile_function;
@@ -15,14 +18,22 @@ use tealr::{
     },
     ToTypename,
 };
+use three_d::context::HasContext;
 use three_d::{
     vec2, vec3, vec4, AxisAlignedBoundingBox, Blend, BufferDataType, Context, CpuTexture,
-    ElementBuffer, ElementBufferDataType, FrameInput, Geometry, Mat4, Object, Program,
+    DepthTest, ElementBuffer, ElementBufferDataType, FrameInput, Geometry, Mat4, Object, Program,
     RenderStates, SquareMatrix, Texture2D, Vec2, Vec3, Vec4, VertexBuffer, Wrapping,
 };
 use three_d_asset::{Srgba, Vector2, Vector3, Vector4};
 
-use crate::{config::GameConfig, game_data, help::transform_shader, vg_ui::Vgfx};
+use crate::{config::GameConfig, fallback_skin, game_data, help::transform_shader, vg_ui::Vgfx};
+
+static LIVE_TEXTURES: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of textures currently held by shaded meshes
+pub fn live_textures() -> usize {
+    LIVE_TEXTURES.load(Ordering::Relaxed)
+}
 
 pub enum ShaderParam {
     Int(i32),
@@ -34,6 +45,9 @@ pub enum ShaderParam {
     IVec3(Vector3<i32>),
     IVec4(Vector4<i32>),
     Texture(Texture2D),
+    /// Texture owned by something else, such as a vgfx image, always bound to the first texture
+    /// unit so it can't be combined with other textures
+    NativeTexture(three_d::context::NativeTexture),
 }
 
 impl From<Vector2<i32>> for ShaderParam {
@@ -77,10 +91,25 @@ impl From<Vec4> for ShaderParam {
 }
 impl From<Texture2D> for ShaderParam {
     fn from(value: Texture2D) -> Self {
+        LIVE_TEXTURES.fetch_add(1, Ordering::Relaxed);
         Self::Texture(value)
     }
 }
 
+impl Drop for ShaderParam {
+    fn drop(&mut self) {
+        if let Self::Texture(_) = self {
+            LIVE_TEXTURES.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl From<three_d::context::NativeTexture> for ShaderParam {
+    fn from(value: three_d::context::NativeTexture) -> Self {
+        Self::NativeTexture(value)
+    }
+}
+
 impl From<i32> for ShaderParam {
     fn from(value: i32) -> Self {
         Self::Int(value)
@@ -93,6 +122,79 @@ impl From<Srgba> for ShaderParam {
     }
 }
 
+/// Names of the uniforms declared in a shader source, declared uniforms the compiler removed
+/// because they are unused are included
+fn declared_uniforms(source: &str) -> HashSet<String> {
+    let code = source
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .join("\n");
+
+    let mut uniforms = HashSet::new();
+    for statement in code.split(';') {
+        let mut tokens = statement
+            .split_whitespace()
+            .skip_while(|t| *t != "uniform")
+            .skip(1)
+            .filter(|t| !matches!(*t, "lowp" | "mediump" | "highp"));
+        // Type
+        if tokens.next().is_none() {
+            continue;
+        }
+        let names = tokens.join(" ");
+        uniforms.extend(
+            names
+                .split(',')
+                .filter_map(|name| name.split('[').next())
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string),
+        );
+    }
+    uniforms
+}
+
+/// Uniforms of a linked program, looked up once so setting parameters doesn't have to query it
+struct Uniforms {
+    /// Declared uniforms and whether they are still active in the linked program
+    declared: HashMap<String, bool>,
+}
+
+impl Uniforms {
+    fn new(program: &Program, sources: &[&str]) -> Self {
+        Self::from_declared(sources.iter().flat_map(|s| declared_uniforms(s)), |name| {
+            program.requires_uniform(name)
+        })
+    }
+
+    fn from_declared(
+        names: impl IntoIterator<Item = String>,
+        active: impl Fn(&str) -> bool,
+    ) -> Self {
+        Self {
+            declared: names
+                .into_iter()
+                .map(|name| {
+                    let active = active(&name);
+                    (name, active)
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether a parameter named `name` has to be set, errors if the shader doesn't declare it
+    fn check(&self, name: &str) -> anyhow::Result<bool> {
+        match self.declared.get(name) {
+            Some(active) => Ok(*active),
+            None => bail!("Shader has no uniform named {name}"),
+        }
+    }
+
+    fn is_active(&self, name: &str) -> bool {
+        self.declared.get(name).copied().unwrap_or(false)
+    }
+}
+
 /// https://www.khronos.org/opengl/wiki/Primitive#Triangle_primitives for calculating indecies
 enum DrawingMode {
     Triangles = 0,
@@ -104,8 +206,13 @@ enum DrawingMode {
 #[derive(UserData, ToTypename)]
 pub struct ShadedMesh {
     params: HashMap<String, ShaderParam>,
+    uniforms: Uniforms,
+    /// Unknown parameter names skins tried to set, each is only warned about once
+    unknown_params: HashSet<String>,
     material: three_d::Program,
     state: RenderStates,
+    /// Meshes with a lower priority are drawn first
+    priority: i32,
     vertex_count: usize,
     draw_mode: DrawingMode,
     indecies: ElementBuffer,
@@ -143,18 +250,28 @@ impl ShadedMesh {
         let fragment_shader_source =
             transform_shader(std::fs::read_to_string(shader_path.with_extension("fs"))?);
 
+        Self::new_from_source(context, &vertex_shader_source, &fragment_shader_source)
+    }
+
+    pub fn new_from_source(
+        context: &three_d::Context,
+        vertex_shader: &str,
+        fragment_shader: &str,
+    ) -> anyhow::Result<Self> {
         let mut params = HashMap::new();
 
-        let material =
-            Program::from_source(context, &vertex_shader_source, &fragment_shader_source)?;
+        let material = Program::from_source(context, vertex_shader, fragment_shader)?;
+        let uniforms = Uniforms::new(&material, &[vertex_shader, fragment_shader]);
 
-        if material.requires_uniform("color") {
+        if uniforms.is_active("color") {
             params.insert("color".into(), vec4(1.0, 1.0, 1.0, 1.0).into());
         }
         let requires_in_color = material.requires_attribute("inColor");
         let requires_in_tex = material.requires_attribute("inTex");
         Ok(Self {
             params,
+            uniforms,
+            unknown_params: HashSet::new(),
             material,
             state: RenderStates {
                 cull: three_d::Cull::None,
@@ -162,6 +279,7 @@ impl ShadedMesh {
                 depth_test: three_d::DepthTest::Always,
                 write_mask: three_d::WriteMask::COLOR,
             },
+            priority: 0,
             vertex_count: 0,
             draw_mode: DrawingMode::Triangles,
             indecies: ElementBuffer::new(context),
@@ -185,20 +303,22 @@ impl ShadedMesh {
             &(0..3).map(|x| vec3(x as f32, 0.0, 0.0)).collect_vec(),
         );
 
+        //https://stackoverflow.com/questions/2588875/whats-the-best-way-to-draw-a-fullscreen-quad-in-opengl-3-2
+        let vertex_shader = include_str!("static_assets/fullscreen.vs");
+        let material = Program::from_source(context, vertex_shader, fragment_shader)?;
+
         Ok(Self {
             params: HashMap::default(),
-            material: Program::from_source(
-                //https://stackoverflow.com/questions/2588875/whats-the-best-way-to-draw-a-fullscreen-quad-in-opengl-3-2
-                context,
-                include_str!("static_assets/fullscreen.vs"),
-                fragment_shader,
-            )?,
+            uniforms: Uniforms::new(&material, &[vertex_shader, fragment_shader]),
+            unknown_params: HashSet::new(),
+            material,
             state: RenderStates {
                 cull: three_d::Cull::None,
                 blend: Blend::TRANSPARENCY,
                 depth_test: three_d::DepthTest::Always,
                 write_mask: three_d::WriteMask::COLOR,
             },
+            priority: 0,
             vertex_count: 0,
             draw_mode: DrawingMode::Triangles,
             indecies: ElementBuffer::new(context),
@@ -222,6 +342,19 @@ impl ShadedMesh {
         self.state.blend = blend;
     }
 
+    pub fn set_depth_test(&mut self, depth_test: DepthTest) {
+        self.state.depth_test = depth_test;
+    }
+
+    pub fn set_priority(&mut self, priority: i32) {
+        self.priority = priority;
+    }
+
+    /// Sort key for draw lists, by priority and then opaque meshes before blended ones
+    pub fn draw_order(&self) -> (i32, bool) {
+        (self.priority, !matches!(self.state.blend, Blend::Disabled))
+    }
+
     fn update_indecies(&mut self) -> anyhow::Result<()> {
         profile_function!();
         let vertex_multiple = match self.draw_mode {
@@ -263,10 +396,28 @@ impl ShadedMesh {
         Ok(())
     }
 
-    pub fn set_param(&mut self, key: &str, param: impl Into<ShaderParam>) {
-        if self.material.requires_uniform(key) {
-            let key: String = key.into();
-            self.params.insert(key, param.into());
+    /// Sets a uniform for the following draws, errors if the shader doesn't declare `key`
+    pub fn set_param(&mut self, key: &str, param: impl Into<ShaderParam>) -> anyhow::Result<()> {
+        if self.uniforms.check(key)? {
+            self.params.insert(key.into(), param.into());
+        }
+        Ok(())
+    }
+
+    /// Sets a uniform that shaders are free to leave out, like the ones the game always provides
+    pub fn set_param_if_declared(&mut self, key: &str, param: impl Into<ShaderParam>) {
+        if self.uniforms.is_active(key) {
+            self.params.insert(key.into(), param.into());
+        }
+    }
+
+    /// [`Self::set_param`] for skin scripts, unknown names are logged once instead of failing the
+    /// script since USC ignored them
+    pub fn set_lua_param(&mut self, key: &str, param: impl Into<ShaderParam>) {
+        if let Err(e) = self.set_param(key, param) {
+            if self.unknown_params.insert(key.to_string()) {
+                log::warn!("{e}");
+            }
         }
     }
 
@@ -279,7 +430,14 @@ impl ShadedMesh {
     ) -> anyhow::Result<CpuTexture> {
         profile_function!();
         let name = name.into();
-        let mut cpu_texture: CpuTexture = three_d_asset::io::load_and_deserialize(path)?;
+        let mut cpu_texture: CpuTexture = match fallback_skin::texture(path.as_ref()) {
+            Some(data) => {
+                let mut raw = three_d_asset::io::RawAssets::new();
+                raw.insert(path.as_ref(), data.to_vec());
+                raw.deserialize(path.as_ref())?
+            }
+            None => three_d_asset::io::load_and_deserialize(path)?,
+        };
 
         log::info!("{}", &cpu_texture.name);
         cpu_texture.data = match cpu_texture.data {
@@ -336,9 +494,9 @@ impl ShadedMesh {
 
         let texture = three_d::Texture2D::new(&self.context, &cpu_texture);
 
-        if self.material.requires_uniform(&name) {
+        if self.uniforms.is_active(&name) {
             self.material.use_texture(&name, &texture);
-            self.params.insert(name, ShaderParam::Texture(texture));
+            self.params.insert(name, texture.into());
         }
         Ok(cpu_texture)
     }
@@ -356,6 +514,14 @@ impl ShadedMesh {
                 ShaderParam::IVec4(v) => self.material.use_uniform(name, v),
                 ShaderParam::Int(v) => self.material.use_uniform(name, v),
                 ShaderParam::Texture(v) => self.material.use_texture(name, v),
+                ShaderParam::NativeTexture(v) => {
+                    unsafe {
+                        self.context.active_texture(three_d::context::TEXTURE0);
+                        self.context
+                            .bind_texture(three_d::context::TEXTURE_2D, Some(*v));
+                    }
+                    self.material.use_uniform(name, 0i32);
+                }
             }
         }
     }
@@ -508,14 +674,12 @@ impl ShadedMesh {
         resolution: (u32, u32),
         vgfx: &RwLock<Vgfx>,
     ) -> Result<(), tealr::mlu::mlua::Error> {
-        let [c0r0, c0r1, c1r0, c1r1, c2r0, c2r1] = {
-            let vgfx = vgfx.write().expect("Lock error");
-            let canvas = vgfx.canvas.lock().expect("Lock error");
+        let [c0r0, c0r1, c1r0, c1r1, c2r0, c2r1] = Vgfx::with_canvas(vgfx, |_, canvas| {
             let transform = canvas.transform();
             //transform.scale(1.0, -1.0);
 
             transform.0
-        };
+        });
 
         self.use_params();
         self.material.use_uniform(
@@ -682,7 +846,9 @@ impl TealData for ShadedMesh {
                 .ok_or(mlua::Error::external("App data not set"))?
                 .read()
                 .expect("Lock error")
-                .resolution;
+                .screen
+                .logical
+                .into();
             let vgfx = &lua
                 .app_data_ref::<RefMut<Vgfx>>()
                 .ok_or(mlua::Error::external("VGFX App data not set"))?;
@@ -715,19 +881,19 @@ impl TealData for ShadedMesh {
         );
 
         methods.add_method_mut("SetParam", |_, this, params: (String, f32)| {
-            this.set_param(params.0.as_str(), params.1);
+            this.set_lua_param(params.0.as_str(), params.1);
             Ok(())
         });
         methods.add_method_mut("SetParamVec2", |_, this, params: (String, f32, f32)| {
             let data = vec2(params.1, params.2);
-            this.set_param(params.0.as_str(), data);
+            this.set_lua_param(params.0.as_str(), data);
             Ok(())
         });
         methods.add_method_mut(
             "SetParamVec3",
             |_, this, params: (String, f32, f32, f32)| {
                 let data = vec3(params.1, params.2, params.3);
-                this.set_param(params.0.as_str(), data);
+                this.set_lua_param(params.0.as_str(), data);
                 Ok(())
             },
         );
@@ -735,7 +901,7 @@ impl TealData for ShadedMesh {
             "SetParamVec4",
             |_, this, params: (String, f32, f32, f32, f32)| {
                 let data = vec4(params.1, params.2, params.3, params.4);
-                this.set_param(params.0.as_str(), data);
+                this.set_lua_param(params.0.as_str(), data);
                 Ok(())
             },
         );
@@ -822,3 +988,31 @@ fn lua_todo<T>() -> Result<T, tealr::mlu::mlua::Error> {
         "Not implemented".into(),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{declared_uniforms, Uniforms};
+
+    #[test]
+    fn unknown_uniforms() {
+        let source = "
+            uniform sampler2D mainTex;
+            uniform highp float objectGlow; // uniform float commented;
+            uniform vec4 lCol, rCol;
+            uniform int unused;
+            uniform vec2 offsets[4];
+            void main() {}
+        ";
+        let declared = declared_uniforms(source);
+        assert_eq!(declared.len(), 6);
+        assert!(!declared.contains("commented"));
+
+        let uniforms = Uniforms::from_declared(declared, |name| name != "unused");
+        assert!(uniforms.check("objectGlow").unwrap());
+        assert!(uniforms.check("rCol").unwrap());
+        assert!(uniforms.check("offsets").unwrap());
+        // Declared but removed by the compiler, setting it does nothing
+        assert!(!uniforms.check("unused").unwrap());
+        assert!(uniforms.check("objectGlw").is_err());
+    }
+}
@@ -60,6 +60,10 @@ impl<'de> Deserialize<'de> for SettingsColor {
     }
 }
 
+/// Entry of a skin's `config-definitions.json`.
+///
+/// Settings that can be changed with the knobs have a `quick` flag to also show them in the
+/// in-game settings dialog, it's false when missing.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum SkinSettingEntry {
@@ -72,6 +76,8 @@ pub enum SkinSettingEntry {
         label: Option<String>,
         name: String,
         values: Vec<String>,
+        #[serde(default)]
+        quick: bool,
     },
     Text {
         default: String,
@@ -90,6 +96,8 @@ pub enum SkinSettingEntry {
         default: bool,
         label: Option<String>,
         name: String,
+        #[serde(default)]
+        quick: bool,
     },
 
     Float {
@@ -98,6 +106,8 @@ pub enum SkinSettingEntry {
         name: String,
         min: f64,
         max: f64,
+        #[serde(default)]
+        quick: bool,
     },
 
     #[serde(alias = "int")]
@@ -107,6 +117,8 @@ pub enum SkinSettingEntry {
         name: String,
         min: i64,
         max: i64,
+        #[serde(default)]
+        quick: bool,
     },
 }
 
@@ -183,3 +195,32 @@ impl<'lua> IntoLuaMulti<'lua> for SkinSettingValue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SkinSettingEntry;
+
+    #[test]
+    fn quick_flag_is_optional() {
+        let definitions: Vec<SkinSettingEntry> = serde_json::from_str(
+            r#"[
+                {"type": "label", "v": "Gameplay"},
+                {"type": "bool", "name": "lane_dividers", "label": "Show lane dividers", "default": true, "quick": true},
+                {"type": "int", "name": "jacket_size", "label": null, "default": 3, "min": 1, "max": 5},
+                {"type": "selection", "name": "theme", "label": "Theme", "default": "dark", "values": ["dark", "light"], "quick": false}
+            ]"#,
+        )
+        .unwrap();
+
+        let quick: Vec<_> = definitions
+            .iter()
+            .map(|d| match d {
+                SkinSettingEntry::Bool { quick, .. }
+                | SkinSettingEntry::Integer { quick, .. }
+                | SkinSettingEntry::Selection { quick, .. } => Some(*quick),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(quick, [None, Some(true), Some(false), Some(false)]);
+    }
+}
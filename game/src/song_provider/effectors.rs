@@ -0,0 +1,146 @@
+use std::collections::{HashMap, HashSet};
+
+use super::SongId;
+
+/// Separators used between the names of charters collaborating on a chart, matched case
+/// insensitively
+const SEPARATORS: [&str; 14] = [
+    " (feat. ", " (ft. ", " feat. ", " feat ", " ft. ", " ft ", " vs. ", " vs ", " & ", " x ",
+    " × ", " and ", "/", ",",
+];
+
+/// Splits an effector string into the names of the individual charters
+pub fn split_effectors(effector: &str) -> Vec<&str> {
+    // ASCII lowercase keeps byte offsets the same as in the original string
+    let lower = effector.to_ascii_lowercase();
+    let mut names = vec![];
+    let mut start = 0;
+    let mut i = 0;
+    while i < lower.len() {
+        if let Some(separator) = SEPARATORS.iter().find(|s| lower[i..].starts_with(*s)) {
+            names.push(&effector[start..i]);
+            i += separator.len();
+            start = i;
+        } else {
+            i += lower[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    names.push(&effector[start..]);
+
+    names
+        .into_iter()
+        .map(|name| name.trim().trim_end_matches(')').trim())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+struct Effector {
+    /// Name as first seen in a chart
+    name: String,
+    /// Number of charts of each song
+    charts: HashMap<SongId, usize>,
+}
+
+/// Songs charted by each effector
+#[derive(Default)]
+pub struct EffectorIndex {
+    effectors: HashMap<String, Effector>,
+    songs: HashMap<SongId, HashSet<String>>,
+}
+
+impl EffectorIndex {
+    /// Adds or replaces a song, `charts` are the effectors of each of its charts
+    pub fn insert<'a>(&mut self, id: &SongId, charts: impl IntoIterator<Item = &'a str>) {
+        self.remove(id);
+
+        let mut keys = HashSet::new();
+        for name in charts.into_iter().flat_map(split_effectors) {
+            let key = normalize(name);
+            let effector = self
+                .effectors
+                .entry(key.clone())
+                .or_insert_with(|| Effector {
+                    name: name.to_string(),
+                    charts: HashMap::new(),
+                });
+            *effector.charts.entry(id.clone()).or_default() += 1;
+            keys.insert(key);
+        }
+
+        self.songs.insert(id.clone(), keys);
+    }
+
+    pub fn remove(&mut self, id: &SongId) {
+        for key in self.songs.remove(id).into_iter().flatten() {
+            if let Some(effector) = self.effectors.get_mut(&key) {
+                effector.charts.remove(id);
+                if effector.charts.is_empty() {
+                    self.effectors.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Songs with at least one chart by `effector`
+    pub fn songs(&self, effector: &str) -> HashSet<SongId> {
+        self.effectors
+            .get(&normalize(effector))
+            .map(|e| e.charts.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Names of the effectors with more than `min_charts` charts, sorted by name
+    pub fn effectors(&self, min_charts: usize) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .effectors
+            .values()
+            .filter(|e| e.charts.values().sum::<usize>() > min_charts)
+            .map(|e| e.name.clone())
+            .collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collabs() {
+        assert_eq!(split_effectors("Alice"), ["Alice"]);
+        assert_eq!(split_effectors("Alice feat. Bob"), ["Alice", "Bob"]);
+        assert_eq!(split_effectors("Alice (FT. Bob)"), ["Alice", "Bob"]);
+        assert_eq!(
+            split_effectors("Alice x Bob & Carol"),
+            ["Alice", "Bob", "Carol"]
+        );
+        assert_eq!(split_effectors("Alice/Bob, "), ["Alice", "Bob"]);
+        assert_eq!(split_effectors("Xander"), ["Xander"]);
+    }
+
+    #[test]
+    fn index() {
+        let mut index = EffectorIndex::default();
+        index.insert(&SongId::IntId(1), ["Alice", "alice", "Alice feat. Bob"]);
+        index.insert(&SongId::IntId(2), ["ALICE"]);
+
+        assert_eq!(index.effectors(0), ["Alice", "Bob"]);
+        assert_eq!(index.effectors(1), ["Alice"]);
+        assert_eq!(
+            index.songs("alice"),
+            HashSet::from([SongId::IntId(1), SongId::IntId(2)])
+        );
+
+        index.remove(&SongId::IntId(1));
+        assert_eq!(index.effectors(0), ["Alice"]);
+        assert!(index.songs("Bob").is_empty());
+    }
+}
@@ -1,27 +1,37 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     path::{Path, PathBuf},
     sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         mpsc::{channel, Receiver, Sender, TryRecvError},
-        Arc, RwLock,
+        Arc, Mutex, RwLock,
     },
-    time::{Duration, SystemTime},
+    time::{Duration, Instant},
 };
 
 use crate::{
     block_on,
     config::{GameConfig, SongSelectSettings},
-    game::{gauge::Gauge, HitSummary, HitWindow},
+    game::{gauge::GaugeSpec, HitSummary, HitWindow},
     log_result,
-    results::{calculate_clear_mark, Score},
-    song_provider::SongFilterType,
-    songselect::{Difficulty, Song},
+    maintenance::{ManagedCache, TaskProgress},
+    results::{calculate_clear_mark, sort_scores, Score},
+    song_provider::{SongFilterType, SongSortType},
+    songselect::{format_duration, Difficulty, Song},
     worker_service::WorkerService,
 };
 
 use super::{
-    DiffId, LoadSongFn, ScoreProvider, ScoreProviderEvent, SongDiffId, SongFilter, SongId,
-    SongProvider, SongProviderEvent, SongSort,
+    decode_file,
+    effectors::EffectorIndex,
+    group_songs,
+    metadata::{read_metadata, write_metadata},
+    open_audio,
+    preview::PreviewCache,
+    recently_played, resolve_audio, resolve_jacket, swap_audio_files, ChartMetadata, DiffId,
+    DuplicateSong, LoadProgress, LoadSongFn, PreviewResult, ScanProgress, ScoreBacklog,
+    ScoreProvider, ScoreProviderEvent, SongDiffId, SongFilter, SongId, SongProvider,
+    SongProviderEvent, SongSort,
 };
 use anyhow::{anyhow, bail, ensure};
 
@@ -32,13 +42,36 @@ use log::{info, warn};
 use puffin::profile_function;
 use rodio::Source;
 use rusc_database::{ChartEntry, LocalSongsDb, ScoreEntry};
-use tokio::io::AsyncRead;
+use tokio::{io::AsyncRead, sync::Semaphore};
+
+/// Time a rebuild waits for the worker to start scanning
+const SCAN_START_TIMEOUT: Duration = Duration::from_secs(10);
+/// Effectors need more charts than this to be listed as a filter
+const EFFECTOR_FILTER_MIN_CHARTS: usize = 4;
+/// Songs found while scanning are sent to the wheel in batches of this many
+const SCAN_BATCH_SIZE: usize = 50;
 
 enum WorkerControlMessage {
     Stop,
     Refresh,
+    /// Removes all charts from the database before importing them again
+    Rebuild,
     LoadDb,
-    Query(String, SongFilter, SongSort),
+    /// Search query, filter, sort, the songs of the effector being filtered by and the recently
+    /// played songs when filtering by them
+    Query(
+        String,
+        SongFilter,
+        SongSort,
+        Option<HashSet<SongId>>,
+        Option<Vec<SongId>>,
+    ),
+    /// Re-imports a single chart file after it was changed
+    Rescan {
+        path: PathBuf,
+        folder_id: i64,
+        old_hash: String,
+    },
 }
 
 #[derive(Debug, PartialEq)]
@@ -58,9 +91,121 @@ impl std::fmt::Display for ImporterState {
     }
 }
 
+/// Scan progress shared between the importer tasks and the provider
+#[derive(Default)]
+struct ScanCounters {
+    discovered: AtomicUsize,
+    parsed: AtomicUsize,
+    failed: Mutex<Vec<PathBuf>>,
+    missing_audio: Mutex<Vec<PathBuf>>,
+    started: AtomicBool,
+    scanning: AtomicBool,
+    /// Why the last scan stopped early
+    error: Mutex<Option<String>>,
+}
+
+impl ScanCounters {
+    fn start(&self) {
+        self.discovered.store(0, Ordering::Relaxed);
+        self.parsed.store(0, Ordering::Relaxed);
+        self.failed.lock().expect("Lock error").clear();
+        self.missing_audio.lock().expect("Lock error").clear();
+        *self.error.lock().expect("Lock error") = None;
+        self.started.store(true, Ordering::Relaxed);
+        self.scanning.store(true, Ordering::Relaxed);
+    }
+
+    fn stop(&self, error: Option<String>) {
+        *self.error.lock().expect("Lock error") = error;
+        self.scanning.store(false, Ordering::Relaxed);
+    }
+
+    fn fail(&self, path: PathBuf) {
+        self.failed.lock().expect("Lock error").push(path);
+    }
+
+    fn missing_audio(&self, path: PathBuf) {
+        self.missing_audio.lock().expect("Lock error").push(path);
+    }
+
+    fn progress(&self) -> Option<ScanProgress> {
+        self.started.load(Ordering::Relaxed).then(|| ScanProgress {
+            discovered: self.discovered.load(Ordering::Relaxed),
+            parsed: self.parsed.load(Ordering::Relaxed),
+            failed: self.failed.lock().expect("Lock error").len(),
+            missing_audio: self.missing_audio.lock().expect("Lock error").len(),
+            done: !self.scanning.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// The charts cached in the song database
+struct SongCache {
+    worker_tx: Sender<WorkerControlMessage>,
+    scan: Arc<ScanCounters>,
+}
+
+impl ManagedCache for SongCache {
+    fn name(&self) -> &'static str {
+        "Song and score database"
+    }
+
+    fn size(&self) -> anyhow::Result<u64> {
+        Ok(std::fs::metadata(database_path())?.len())
+    }
+
+    /// Starts importing all charts again, the wheel would be empty without them
+    fn clear(&self) -> anyhow::Result<()> {
+        ensure!(
+            !self.scan.scanning.load(Ordering::Relaxed),
+            "The library is already being scanned"
+        );
+        self.worker_tx
+            .send(WorkerControlMessage::Rebuild)
+            .map_err(|_| anyhow!("The song importer has stopped"))
+    }
+
+    fn rebuild(&self, progress: &TaskProgress) -> anyhow::Result<()> {
+        self.clear()?;
+        progress.set("Starting scan", None);
+        let started = Instant::now();
+        while !self.scan.scanning.load(Ordering::Relaxed) {
+            ensure!(
+                started.elapsed() < SCAN_START_TIMEOUT,
+                "The library scan didn't start"
+            );
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        while let Some(scan) = self.scan.progress().filter(|p| !p.done) {
+            progress.set(
+                format!("Imported {} of {} charts", scan.parsed, scan.discovered),
+                (scan.discovered > 0).then(|| scan.parsed as f32 / scan.discovered as f32),
+            );
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        match self.scan.error.lock().expect("Lock error").clone() {
+            Some(e) => bail!(e),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Scores of a chart that isn't in the song database anymore
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrphanedScores {
+    pub hash: String,
+    pub count: usize,
+    pub best: i32,
+    pub last_played: i32,
+}
+
 enum WorkerEvent {
     SongProvider(SongProviderEvent),
     ImporterState(ImporterState),
+    /// Duplicate songs found when the songs were loaded from the database
+    Duplicates(Vec<DuplicateSong>),
 }
 
 pub struct FileSongProvider {
@@ -71,39 +216,36 @@ pub struct FileSongProvider {
     worker_rx: Receiver<WorkerEvent>,
     worker_tx: Sender<WorkerControlMessage>,
     score_bus: bus::Bus<ScoreProviderEvent>,
+    score_backlog: ScoreBacklog,
     song_bus: bus::Bus<SongProviderEvent>,
     sort: SongSort,
     filter: SongFilter,
     query: String,
     importer_state: ImporterState,
-    last_full_update: SystemTime,
+    scan: Arc<ScanCounters>,
+    preview_cache: PreviewCache,
+    effector_index: EffectorIndex,
+    duplicates: HashMap<SongId, DuplicateSong>,
+    /// Value of the show duplicates setting the current order was queried with
+    showing_duplicates: bool,
+    last_rescan: Instant,
 }
 
 impl From<ScoreEntry> for Score {
     fn from(value: ScoreEntry) -> Self {
-        let samples = Box::new([0.0; 128]);
-        let gauge = if value.gauge_type == 1 {
-            Gauge::Hard {
-                chip_gain: 1.0,
-                tick_gain: 1.0,
-                value: value.gauge as _,
-                samples,
-            }
-        } else {
-            Gauge::Normal {
-                chip_gain: 1.0,
-                tick_gain: 1.0,
-                value: value.gauge as _,
-                samples,
-            }
-        };
+        let spec = GaugeSpec::from_score(value.gauge_type as _, value.gauge_opt as _);
+        let mut gauge = spec.get_gauge(1.0, 1.0);
+        gauge.set_value(value.gauge as _);
 
         Score {
             gauge: value.gauge as f32,
             gauge_type: value.gauge_type as u8,
             gauge_option: value.gauge_opt as i32,
+            gauge_name: spec.name(),
             mirror: value.mirror,
             random: value.random,
+            hard_random: value.hard_random,
+            random_seed: value.random_seed.map(|s| s as u32),
             auto_flags: value.auto_flags as i32,
             score: value.score as i32,
             perfects: value.crit as i32,
@@ -127,16 +269,18 @@ impl From<ScoreEntry> for Score {
             earlies: value.early as _,
             lates: value.late as _,
             combo: value.combo as _,
+            uid: None,
         }
     }
 }
 
+fn database_path() -> PathBuf {
+    GameConfig::get().game_folder.join("maps.db")
+}
+
 impl FileSongProvider {
     pub async fn new() -> Self {
-        let mut db_file = GameConfig::get().game_folder.clone();
-        db_file.push("maps.db");
-
-        let database = LocalSongsDb::new(db_file)
+        let database = LocalSongsDb::new(database_path())
             .await
             .expect("Failed to open database");
 
@@ -144,8 +288,10 @@ impl FileSongProvider {
         let (worker_tx, sender_rx) = channel(); //TODO: Async channels?
 
         let worker_db = database.clone();
+        let scan = Arc::new(ScanCounters::default());
+        let worker_scan = scan.clone();
         let worker = poll_promise::Promise::spawn_async(async move {
-            files_worker(sender_tx, sender_rx, worker_db).await
+            files_worker(sender_tx, sender_rx, worker_db, worker_scan).await
         });
 
         worker_tx.send(WorkerControlMessage::LoadDb);
@@ -161,21 +307,178 @@ impl FileSongProvider {
             worker,
             worker_rx,
             score_bus: bus::Bus::new(128),
+            score_backlog: ScoreBacklog::default(),
             song_bus: bus::Bus::new(128),
             worker_tx,
             sort: *sorting,
             filter: filter.clone(),
             query: String::new(),
             importer_state: ImporterState::Idle,
-            last_full_update: SystemTime::now(),
+            scan,
+            preview_cache: PreviewCache::default(),
+            effector_index: EffectorIndex::default(),
+            duplicates: HashMap::new(),
+            showing_duplicates: GameConfig::get().library.show_duplicates,
+            last_rescan: Instant::now(),
         }
     }
 }
 
+impl FileSongProvider {
+    /// Songs in the database with their scores and the chart file of each difficulty, read
+    /// without starting the importer
+    pub async fn read_library() -> anyhow::Result<(Vec<Arc<Song>>, HashMap<DiffId, PathBuf>)> {
+        let database = LocalSongsDb::new(database_path()).await?;
+        let entries = database.get_songs().await?;
+        let paths = entries
+            .iter()
+            .map(|e| {
+                (
+                    DiffId(SongId::StringId(e.hash.clone())),
+                    PathBuf::from(&e.path),
+                )
+            })
+            .collect();
+        let songs = entries_to_songs(entries);
+        apply_scores(songs.iter(), database.get_all_scores().await?);
+        Ok((songs, paths))
+    }
+
+    /// Charts with scores that aren't in the song database, because they were removed or changed
+    /// outside of the game
+    pub async fn orphaned_scores() -> anyhow::Result<Vec<OrphanedScores>> {
+        let database = LocalSongsDb::new(database_path()).await?;
+        let mut orphans: Vec<_> = database
+            .get_orphaned_scores()
+            .await?
+            .into_iter()
+            .map(|(hash, count, best, last_played)| OrphanedScores {
+                hash,
+                count: count as usize,
+                best: best as i32,
+                last_played: last_played as i32,
+            })
+            .collect();
+        orphans.sort_by_key(|o| std::cmp::Reverse(o.last_played));
+        Ok(orphans)
+    }
+
+    /// Removes the scores of the charts with the given hashes, charts that were added back to the
+    /// library since keep theirs. Returns the number of removed scores.
+    pub async fn remove_orphaned_scores(hashes: &[String]) -> anyhow::Result<u64> {
+        let database = LocalSongsDb::new(database_path()).await?;
+        let mut removed = 0;
+        for hash in hashes {
+            removed += database.remove_orphaned_scores(hash).await?.rows_affected();
+        }
+        Ok(removed)
+    }
+
+    /// Rewrites the database without its unused space, returns its size before and after
+    pub async fn vacuum_database() -> anyhow::Result<(u64, u64)> {
+        let before = std::fs::metadata(database_path())?.len();
+        LocalSongsDb::new(database_path()).await?.vacuum().await?;
+        Ok((before, std::fs::metadata(database_path())?.len()))
+    }
+
+    /// Preview of a song folder, using the audio of the chart with `hash` if it has any
+    fn folder_preview(
+        &self,
+        folder: i64,
+        hash: Option<String>,
+    ) -> poll_promise::Promise<PreviewResult> {
+        let db = self.database.clone();
+        let preview_cache = self.preview_cache.clone();
+        poll_promise::Promise::spawn_async(async move {
+            profile_function!();
+            let mut charts = block_on(db.get_charts_for_folder(folder))?;
+            ensure!(!charts.is_empty(), "No chart found");
+
+            // Prefer the audio of the requested or else the first difficulty, but any difficulty
+            // with audio will do
+            charts.sort_by_key(|c| (Some(&c.hash) != hash.as_ref(), c.diff_index));
+            let Some((chart, path)) = charts.into_iter().find_map(|c| {
+                let declared = PathBuf::from(&c.path).with_file_name(c.preview_file.as_ref()?);
+                resolve_audio(&declared).map(|path| (c, path))
+            }) else {
+                bail!("No preview file")
+            };
+
+            let (offset, length) = match preview_cache.get(&chart.hash) {
+                Some(region) => region,
+                None => {
+                    let region = (
+                        Duration::from_millis(chart.preview_offset as u64),
+                        Duration::from_millis(chart.preview_length as u64),
+                    );
+                    preview_cache.analyze(chart.hash, path.clone(), region.0, region.1);
+                    region
+                }
+            };
+
+            Ok((decode_file(&path)?, offset, length))
+        })
+    }
+
+    fn chart_entry(&self, id: &SongDiffId) -> anyhow::Result<ChartEntry> {
+        let _diff_index = match id {
+            SongDiffId::DiffOnly(diff_id) | SongDiffId::SongDiff(_, diff_id) => match &diff_id.0 {
+                SongId::IntId(id) => *id,
+                SongId::StringId(hash) => {
+                    block_on(self.database.get_hash_id(hash))?.ok_or(anyhow!("No song hash"))?
+                }
+                SongId::Missing => todo!(),
+            },
+            _ => todo!(),
+        };
+
+        let db = self.database.clone();
+        Ok(block_on!(db.get_song(_diff_index as _))?)
+    }
+
+    /// Songs allowed by an effector filter
+    fn effector_songs(&self) -> Option<HashSet<SongId>> {
+        match &self.filter.filter_type {
+            SongFilterType::Effector(effector) => Some(self.effector_index.songs(effector)),
+            _ => None,
+        }
+    }
+
+    /// Songs allowed by the recently played filter, in the order they are listed
+    fn recent_songs(&self) -> Option<Vec<SongId>> {
+        (self.filter.filter_type == SongFilterType::RecentlyPlayed)
+            .then(|| recently_played(self.all_songs.values().map(|s| &**s)))
+    }
+
+    fn send_query(&self) {
+        self.worker_tx.send(WorkerControlMessage::Query(
+            self.query.clone(),
+            self.filter.clone(),
+            self.sort,
+            self.effector_songs(),
+            self.recent_songs(),
+        ));
+    }
+
+    /// Removes duplicate songs from a wheel order unless they are configured to be shown
+    fn hide_duplicates(&self, order: &mut Vec<SongId>) {
+        if !self.showing_duplicates {
+            order.retain(|id| !self.duplicates.contains_key(id));
+        }
+    }
+
+    fn index_song(&mut self, song: &Song) {
+        let diffs = song.difficulties.read().expect("Lock error");
+        self.effector_index
+            .insert(&song.id, diffs.iter().map(|d| d.effector.as_str()));
+    }
+}
+
 async fn files_worker(
     worker_tx: Sender<WorkerEvent>,
     worker_rx: Receiver<WorkerControlMessage>,
     database: LocalSongsDb,
+    scan: Arc<ScanCounters>,
 ) {
     loop {
         let cmd = match worker_rx.try_recv() {
@@ -191,166 +494,472 @@ async fn files_worker(
 
         match cmd {
             WorkerControlMessage::Stop => return,
-            WorkerControlMessage::Refresh => {
+            cmd @ (WorkerControlMessage::Refresh | WorkerControlMessage::Rebuild) => {
+                if scan.scanning.load(Ordering::Relaxed) {
+                    info!("Library scan already running");
+                    continue;
+                }
+                // Started here so a refresh sent while this one starts is skipped
+                scan.start();
+                let rebuild = matches!(cmd, WorkerControlMessage::Rebuild);
                 let worker_tx = worker_tx.clone();
                 let database = database.clone();
+                let scan = scan.clone();
                 tokio::task::spawn(async move {
                     worker_tx.send(WorkerEvent::ImporterState(ImporterState::Starting));
-                    let hashes = refresh_songs(&worker_tx, &database)
+                    let known_songs = song_ids(&database).await;
+                    if rebuild {
+                        info!("Rebuilding song db");
+                        if let Err(e) = database.clear_chart_stats().await {
+                            warn!("Failed to rebuild song db: {e}");
+                            scan.stop(Some(e.to_string()));
+                            worker_tx.send(WorkerEvent::ImporterState(ImporterState::Idle));
+                            return;
+                        }
+                    }
+                    let charts = refresh_songs(&worker_tx, &database, &scan)
                         .await
                         .unwrap_or_default();
 
                     worker_tx.send(WorkerEvent::ImporterState(ImporterState::Loading(
                         "Cleaning".into(),
                     )));
-                    let db_hashes: HashSet<String> = database
-                        .get_all_hashes()
-                        .await
-                        .unwrap_or_default()
-                        .into_iter()
-                        .collect();
-
-                    for removed in db_hashes.difference(&hashes) {
-                        database.remove_hash(removed).await;
+                    let db_charts = database.get_all_chart_files().await.unwrap_or_default();
+                    for (path, hash) in db_charts {
+                        if !charts.contains(&(path.clone(), hash.clone())) {
+                            log_result!(database.remove_chart_file(&path, &hash).await);
+                        }
                     }
 
                     database.remove_empty_folders().await;
 
                     worker_tx.send(WorkerEvent::ImporterState(ImporterState::Idle));
-                    load_db(&database, &worker_tx).await;
-                    info!("Finished importing");
+                    let loaded = load_db(&database, &worker_tx).await;
+                    let removed: HashSet<SongId> =
+                        known_songs.difference(&loaded).cloned().collect();
+                    if !removed.is_empty() {
+                        info!("{} songs were removed", removed.len());
+                        _ = worker_tx.send(WorkerEvent::SongProvider(
+                            SongProviderEvent::SongsRemoved(removed),
+                        ));
+                    }
+                    scan.stop(None);
+                    if let Some(progress) = scan.progress() {
+                        info!(
+                            "Finished importing, {} charts loaded and {} failed",
+                            progress.parsed, progress.failed
+                        );
+                    }
                 });
             }
-            WorkerControlMessage::Query(q, song_filter, song_sort) => {
+            WorkerControlMessage::Query(
+                q,
+                song_filter,
+                song_sort,
+                effector_songs,
+                recent_songs,
+            ) => {
                 info!("Querying db");
-                if let Ok(order) = query_songs(&database, &q, &song_filter, song_sort).await {
+                if let Ok(order) = query_songs(
+                    &database,
+                    &q,
+                    &song_filter,
+                    song_sort,
+                    effector_songs.as_ref(),
+                    recent_songs.as_deref(),
+                )
+                .await
+                {
                     worker_tx.send(WorkerEvent::SongProvider(SongProviderEvent::OrderChanged(
                         order,
                     )));
                 }
             }
-            WorkerControlMessage::LoadDb => load_db(&database, &worker_tx).await,
+            WorkerControlMessage::LoadDb => {
+                load_db(&database, &worker_tx).await;
+            }
+            WorkerControlMessage::Rescan {
+                path,
+                folder_id,
+                old_hash,
+            } => {
+                let worker_tx = worker_tx.clone();
+                let database = database.clone();
+                let scan = scan.clone();
+                tokio::task::spawn(async move {
+                    log_result!(
+                        rescan_chart(path, folder_id, &old_hash, &worker_tx, &database, &scan)
+                            .await
+                    );
+                });
+            }
         }
     }
 }
 
-async fn load_db(database: &LocalSongsDb, worker_tx: &Sender<WorkerEvent>) {
-    let mut diffs = database
+/// Ids of the songs currently in the database
+async fn song_ids(database: &LocalSongsDb) -> HashSet<SongId> {
+    database
+        .get_songs()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| SongId::IntId(c.folderid))
+        .collect()
+}
+
+/// Finds songs whose charts were all already seen in a song with a lower id, songs are keyed by
+/// id and hold the hash and path of each of their charts
+fn find_duplicates(songs: &BTreeMap<i64, Vec<(String, PathBuf)>>) -> Vec<DuplicateSong> {
+    let folder = |charts: &[(String, PathBuf)]| {
+        charts
+            .first()
+            .and_then(|(_, path)| path.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    };
+
+    let mut seen: HashMap<&str, i64> = HashMap::new();
+    let mut duplicates = vec![];
+    for (id, charts) in songs {
+        let original = charts
+            .iter()
+            .map(|(hash, _)| seen.get(hash.as_str()).copied())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|ids| ids.first().copied());
+
+        if let Some(original) = original {
+            duplicates.push(DuplicateSong {
+                id: SongId::IntId(*id),
+                path: folder(charts),
+                original: folder(&songs[&original]),
+            });
+        } else {
+            for (hash, _) in charts {
+                seen.entry(hash).or_insert(*id);
+            }
+        }
+    }
+
+    duplicates
+}
+
+/// Sends every song in the database to the provider and returns their ids
+async fn load_db(database: &LocalSongsDb, worker_tx: &Sender<WorkerEvent>) -> HashSet<SongId> {
+    let diffs = database
         .get_songs()
         .await
         .expect("Failed to load songs from database");
-    let mut difficulty_id_path_map: HashMap<u64, PathBuf> = HashMap::default();
-    let mut all_songs: Vec<_> = diffs
+
+    let mut song_charts: BTreeMap<i64, Vec<(String, PathBuf)>> = BTreeMap::new();
+    for diff in &diffs {
+        song_charts
+            .entry(diff.folderid)
+            .or_default()
+            .push((diff.hash.clone(), PathBuf::from(&diff.path)));
+    }
+    let duplicates = find_duplicates(&song_charts);
+    if !duplicates.is_empty() {
+        info!("Found {} duplicate songs", duplicates.len());
+    }
+    _ = worker_tx.send(WorkerEvent::Duplicates(duplicates));
+    let all_songs = entries_to_songs(diffs);
+    info!("Loaded {} songs from db", all_songs.len());
+    let ids = all_songs.iter().map(|s| s.id.clone()).collect();
+    worker_tx.send(WorkerEvent::SongProvider(SongProviderEvent::SongsAdded(
+        all_songs,
+    )));
+    ids
+}
+
+/// Groups the charts of the database into songs by folder
+fn entries_to_songs(mut diffs: Vec<ChartEntry>) -> Vec<Arc<Song>> {
+    diffs
         .drain(0..)
         .into_grouping_map_by(|x| x.folderid)
         .fold(Song::default(), |mut song, id, diff| {
             //This fold clones the initial state, and with our difficulties being RCd, we need to reinit the diffs
             if song.id == SongId::Missing {
                 song.id = SongId::IntId(*id);
-                song.artist = diff.artist;
-                song.bpm = diff.bpm;
-                song.title = diff.title;
+                song.artist.clone_from(&diff.artist);
+                song.bpm.clone_from(&diff.bpm);
+                song.title.clone_from(&diff.title);
                 song.difficulties = Arc::new(RwLock::new(vec![]));
             }
             let mut difficulties = song.difficulties.write().expect("Lock error");
-
-            difficulty_id_path_map.insert(diff.rowid as u64, PathBuf::from(&diff.path));
-            let diff_path = PathBuf::from(diff.path);
-            difficulties.push(Difficulty {
-                jacket_path: diff_path.with_file_name(diff.jacket_path),
-                level: diff.level as u8,
-                difficulty: diff.diff_index as u8,
-                id: DiffId(SongId::StringId(diff.hash.clone())),
-                effector: diff.effector,
-                top_badge: 0,           //TODO
-                scores: Vec::default(), //TODO
-                hash: Some(diff.hash),
-                illustrator: diff.illustrator,
-            });
+            difficulties.push(entry_to_difficulty(diff));
             drop(difficulties);
             song
         })
         .drain()
         .map(|(_, song)| Arc::new(song))
-        .collect();
-    info!("Loaded {} songs from db", all_songs.len());
-    worker_tx.send(WorkerEvent::SongProvider(SongProviderEvent::SongsAdded(
-        all_songs,
+        .collect()
+}
+
+/// Sets the scores, top badges and play times of the difficulties of `songs`
+fn apply_scores<'a>(songs: impl Iterator<Item = &'a Arc<Song>>, scores: Vec<ScoreEntry>) {
+    let mut scores = scores
+        .into_iter()
+        .group_by(|x| DiffId(SongId::StringId(x.chart_hash.clone()))) //TODO: Excessive cloning
+        .into_iter()
+        .map(|(key, scores)| (key, scores.map(Score::from).collect_vec()))
+        .collect::<HashMap<_, _>>();
+
+    songs.for_each(|song| {
+        let mut diffs = song.difficulties.write().expect("Lock error");
+        for diff in diffs.iter_mut() {
+            diff.scores = scores.remove(&diff.id).unwrap_or_default();
+            sort_scores(&mut diff.scores);
+            diff.top_badge = diff
+                .scores
+                .iter()
+                .map(|x| x.badge)
+                .max()
+                .unwrap_or_default();
+            diff.last_played = diff.scores.iter().map(|x| x.timestamp).max();
+        }
+
+        diffs.sort_by_key(|x| (x.difficulty, x.level))
+    });
+}
+
+fn entry_to_difficulty(diff: ChartEntry) -> Difficulty {
+    let diff_path = PathBuf::from(diff.path);
+    Difficulty {
+        jacket_path: diff_path.with_file_name(diff.jacket_path),
+        level: diff.level as u8,
+        difficulty: diff.diff_index as u8,
+        id: DiffId(SongId::StringId(diff.hash.clone())),
+        effector: diff.effector,
+        top_badge: 0,           //TODO
+        scores: Vec::default(), //TODO
+        hash: Some(diff.hash),
+        illustrator: diff.illustrator,
+        radar: diff.radar.and_then(|r| serde_json::from_str(&r).ok()),
+        duration: diff.duration.map(|d| d as u32),
+        duration_string: diff.duration.map(|d| format_duration(d as u32)),
+        last_played: None,
+        audio_broken: false,
+        density_strip_path: None,
+        jacket_loaded: false,
+    }
+}
+
+/// Imports a changed chart file again and replaces its song with the updated one. The old
+/// version is kept if the file can't be parsed, an editor may still be writing it.
+async fn rescan_chart(
+    path: PathBuf,
+    folder_id: i64,
+    old_hash: &str,
+    worker_tx: &Sender<WorkerEvent>,
+    database: &LocalSongsDb,
+    scan: &Arc<ScanCounters>,
+) -> anyhow::Result<()> {
+    let data = tokio::fs::read(&path).await?;
+    let hash = chart_hash(&data);
+    let chart = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || parse_chart(&path, &data)).await??
+    };
+    check_chart_audio(&path, &chart, scan);
+
+    database
+        .remove_chart_file(&path.to_string_lossy(), old_hash)
+        .await?;
+    database
+        .add_chart(chart_to_entry(&chart, &path, folder_id, &hash))
+        .await?;
+    database.move_scores(old_hash, &hash).await?;
+
+    let song = folder_song(database, folder_id).await?;
+
+    _ = worker_tx.send(WorkerEvent::SongProvider(SongProviderEvent::SongsRemoved(
+        HashSet::from([song.id.clone()]),
     )));
+    _ = worker_tx.send(WorkerEvent::SongProvider(SongProviderEvent::SongsAdded(
+        vec![Arc::new(song)],
+    )));
+    Ok(())
 }
 
+/// Imports all charts in the songs folder, returns the path and hash of every chart found
 async fn refresh_songs(
     worker_tx: &Sender<WorkerEvent>,
     worker_db: &LocalSongsDb,
-) -> anyhow::Result<HashSet<String>> {
+    scan: &Arc<ScanCounters>,
+) -> anyhow::Result<HashSet<(String, String)>> {
     let songs_folder = songs_path();
     info!("Refreshing song db");
-    let dir = tokio::fs::read_dir(&songs_folder).await?;
 
-    Ok(read_song_dir(dir, worker_tx, worker_db)
+    Ok(read_song_dir(songs_folder, worker_tx, worker_db, scan)
         .await?
         .into_iter()
         .collect())
 }
 
+/// Folders below `root` that hold chart files, with their charts, both in path order
+async fn find_chart_folders(
+    root: PathBuf,
+    worker_tx: &Sender<WorkerEvent>,
+) -> anyhow::Result<Vec<(PathBuf, Vec<PathBuf>)>> {
+    let mut folders = vec![];
+    let mut dirs = vec![root.clone()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if dir == root => return Err(e.into()),
+            Err(e) => {
+                warn!("Failed to read {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+
+        let msg = format!("{}", dir.display());
+        _ = worker_tx.send(WorkerEvent::ImporterState(ImporterState::Loading(msg)));
+        let mut chart_files = vec![];
+        while let Ok(Some(e)) = entries.next_entry().await {
+            let p = e.path();
+            if p.is_dir() {
+                dirs.push(p);
+            } else if is_chart_file(&p).is_some() {
+                chart_files.push(p);
+            }
+        }
+
+        if !chart_files.is_empty() {
+            chart_files.sort();
+            folders.push((dir, chart_files));
+        }
+    }
+
+    folders.sort();
+    Ok(folders)
+}
+
+/// Imports the charts below `root`, as many folders at a time as the configured scan threads,
+/// and sends the songs to the provider in batches as their folders are done
 async fn read_song_dir(
-    mut dir: tokio::fs::ReadDir,
+    root: PathBuf,
     worker_tx: &Sender<WorkerEvent>,
     worker_db: &LocalSongsDb,
-) -> anyhow::Result<Vec<String>> {
-    let mut chart_files = vec![];
-    let mut hashes = vec![];
-
-    while let Some(e) = dir.next_entry().await? {
-        let p = e.path();
-        if p.is_dir() {
-            let msg = format!("{}", p.display());
-            worker_tx.send(WorkerEvent::ImporterState(ImporterState::Loading(msg)));
-            let dir = tokio::fs::read_dir(p).await?;
-            if let Ok(mut r) = Box::pin(read_song_dir(dir, worker_tx, worker_db)).await {
-                hashes.append(&mut r);
+    scan: &Arc<ScanCounters>,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let folders = find_chart_folders(root, worker_tx).await?;
+    scan.discovered.fetch_add(
+        folders.iter().map(|(_, files)| files.len()).sum(),
+        Ordering::Relaxed,
+    );
+
+    // Folders are added one by one in path order so new folders get the same ids on every scan
+    let mut folder_ids = vec![];
+    for (folder, chart_files) in folders {
+        match worker_db.get_or_insert_folder(&folder).await {
+            Ok(id) => folder_ids.push((id, chart_files)),
+            Err(e) => {
+                warn!("Failed to add folder {}: {}", folder.display(), e);
+                chart_files.into_iter().for_each(|p| scan.fail(p));
             }
-        } else if is_chart_file(&p).is_some() {
-            chart_files.push(p);
         }
     }
 
-    let mut chart_loaders = vec![];
-    if !chart_files.is_empty() {
-        let folder_id = worker_db
-            .get_or_insert_folder(chart_files[0].parent().unwrap())
-            .await?;
-        for p in chart_files {
-            chart_loaders.push((
-                p.clone(),
-                tokio::spawn(read_chart_file(
-                    p,
-                    worker_tx.clone(),
-                    worker_db.clone(),
-                    folder_id,
-                )),
-            ));
+    // Only as many folders as there are permits have files open at a time
+    let permits = Arc::new(Semaphore::new(GameConfig::get().library.scan_threads()));
+    let (song_tx, mut song_rx) = tokio::sync::mpsc::unbounded_channel();
+    let folder_tasks = folder_ids
+        .into_iter()
+        .map(|(folder_id, chart_files)| {
+            let permits = permits.clone();
+            let song_tx = song_tx.clone();
+            let worker_tx = worker_tx.clone();
+            let worker_db = worker_db.clone();
+            let scan = scan.clone();
+            tokio::spawn(async move {
+                let _permit = permits.acquire_owned().await?;
+                let charts =
+                    read_folder_charts(chart_files, &worker_tx, &worker_db, folder_id, &scan).await;
+                if !charts.is_empty() {
+                    match folder_song(&worker_db, folder_id).await {
+                        Ok(song) => _ = song_tx.send(song),
+                        Err(e) => warn!("Failed to load song {folder_id}: {e}"),
+                    }
+                }
+                anyhow::Ok(charts)
+            })
+        })
+        .collect_vec();
+    drop(song_tx);
+
+    let mut batch = vec![];
+    while let Some(song) = song_rx.recv().await {
+        batch.push(Arc::new(song));
+        if batch.len() >= SCAN_BATCH_SIZE {
+            _ = worker_tx.send(WorkerEvent::SongProvider(SongProviderEvent::SongsAdded(
+                std::mem::take(&mut batch),
+            )));
         }
+    }
+    if !batch.is_empty() {
+        _ = worker_tx.send(WorkerEvent::SongProvider(SongProviderEvent::SongsAdded(
+            batch,
+        )));
+    }
 
-        let mut song = Song {
-            id: SongId::IntId(folder_id),
-            ..Default::default()
-        };
+    let mut charts = vec![];
+    for task in folder_tasks {
+        charts.append(&mut task.await??);
+    }
+    Ok(charts)
+}
 
-        for (p, t) in chart_loaders {
-            match t.await? {
-                Ok(hash) => hashes.push(hash),
-                Err(e) => {
-                    warn!("Failed to load chart {}: {}", p.display(), e);
-                }
+/// Imports the charts of one folder one after another
+async fn read_folder_charts(
+    chart_files: Vec<PathBuf>,
+    worker_tx: &Sender<WorkerEvent>,
+    worker_db: &LocalSongsDb,
+    folder_id: i64,
+    scan: &Arc<ScanCounters>,
+) -> Vec<(String, String)> {
+    let mut charts = vec![];
+    for p in chart_files {
+        match read_chart_file(
+            p.clone(),
+            worker_tx.clone(),
+            worker_db.clone(),
+            folder_id,
+            scan.clone(),
+        )
+        .await
+        {
+            Ok(hash) => {
+                scan.parsed.fetch_add(1, Ordering::Relaxed);
+                charts.push((p.to_string_lossy().to_string(), hash))
+            }
+            Err(e) => {
+                warn!("Failed to load chart {}: {}", p.display(), e);
+                scan.fail(p);
             }
         }
     }
+    charts
+}
 
-    Ok(hashes)
+/// Song of a folder as it is in the database
+async fn folder_song(database: &LocalSongsDb, folder_id: i64) -> anyhow::Result<Song> {
+    let charts = database.get_charts_for_folder(folder_id).await?;
+    let first = charts.first().ok_or(anyhow!("Folder has no charts"))?;
+    Ok(Song {
+        id: SongId::IntId(folder_id),
+        title: first.title.clone(),
+        artist: first.artist.clone(),
+        bpm: first.bpm.clone(),
+        difficulties: Arc::new(RwLock::new(
+            charts.into_iter().map(entry_to_difficulty).collect(),
+        )),
+        uploader: None,
+    })
 }
 
-fn is_chart_file(p: &PathBuf) -> Option<String> {
+fn is_chart_file(p: &Path) -> Option<String> {
     p.extension()
         .and_then(|x| x.to_str())
         .map(|x| x.to_lowercase())
@@ -362,35 +971,84 @@ async fn read_chart_file(
     worker_tx: Sender<WorkerEvent>,
     worker_db: LocalSongsDb,
     folder_id: i64,
+    scan: Arc<ScanCounters>,
 ) -> anyhow::Result<String> {
     let data = tokio::fs::read(&p).await?;
-    let mut hasher = sha1_smol::Sha1::new();
-    hasher.update(&data);
-    let hash = hasher.digest().to_string();
+    let hash = chart_hash(&data);
 
-    if worker_db.get_hash_id(&hash).await?.is_some() {
+    // Copies of the chart in other folders get their own entry so they can be reported
+    let path = p.to_string_lossy();
+    let exists = worker_db
+        .get_hash_paths(&hash)
+        .await?
+        .iter()
+        .any(|x| *x == path);
+    if exists && worker_db.has_chart_stats(&hash).await? {
         return Ok(hash); //Already exists
     }
-    let ext = is_chart_file(&p).expect("Got non chart file");
+    let chart = {
+        let p = p.clone();
+        tokio::task::spawn_blocking(move || parse_chart(&p, &data)).await??
+    };
+
+    check_chart_audio(&p, &chart, &scan);
+
+    if exists {
+        // Added before radars and durations were cached, or the database is being rebuilt
+        let id = worker_db
+            .get_chart_id(&path, &hash)
+            .await?
+            .ok_or(anyhow!("Chart disappeared from the database"))?;
+        worker_db
+            .update_chart(chart_to_entry(&chart, &p, folder_id, &hash), id as i32)
+            .await?;
+        return Ok(hash);
+    }
+
+    worker_db
+        .add_chart(chart_to_entry(&chart, &p, folder_id, &hash))
+        .await;
+
+    Ok(hash)
+}
+
+fn chart_hash(data: &[u8]) -> String {
+    let mut hasher = sha1_smol::Sha1::new();
+    hasher.update(data);
+    hasher.digest().to_string()
+}
+
+/// Reports missing audio files of a chart
+fn check_chart_audio(p: &Path, chart: &kson::Chart, scan: &ScanCounters) {
+    if resolve_audio(&p.with_file_name(&chart.audio.bgm.filename)).is_none() {
+        warn!("No audio found for chart {}", p.display());
+        scan.missing_audio(p.to_path_buf());
+    }
+    for name in swap_audio_files(chart) {
+        if !p.with_file_name(&name).is_file() {
+            warn!("Swap audio {name} of chart {} is missing", p.display());
+        }
+    }
+}
+
+/// Decodes and parses a chart file, this takes a while for big charts so it is run on a
+/// blocking thread
+pub fn parse_chart(p: &Path, data: &[u8]) -> anyhow::Result<kson::Chart> {
+    let ext = is_chart_file(p).expect("Got non chart file");
     let chart: kson::Chart = if ext == "ksh" {
         let (c, _) = encoding::types::decode(
-            &data,
+            data,
             encoding::DecoderTrap::Strict,
             encoding::all::WINDOWS_31J,
         );
         let c = c.map_err(|x| anyhow::anyhow!("{x}"))?;
         kson::Chart::from_ksh(&c)?
     } else {
-        serde_json::from_slice(&data)?
+        serde_json::from_slice(data)?
     };
 
     ensure!(chart.get_last_tick() > 0, "Empty chart");
-
-    worker_db
-        .add_chart(chart_to_entry(&chart, &p, folder_id, &hash))
-        .await;
-
-    Ok(hash)
+    Ok(chart)
 }
 
 fn chart_to_entry(
@@ -408,8 +1066,7 @@ fn chart_to_entry(
         artist: c.meta.artist.clone(),
         title_translit: String::new(),
         artist_translit: String::new(),
-        jacket_path: path
-            .with_file_name(c.meta.jacket_filename.clone())
+        jacket_path: resolve_jacket(path, &c.meta.jacket_filename)
             .to_string_lossy()
             .to_string(),
         effector: c.meta.chart_author.clone(),
@@ -433,14 +1090,23 @@ fn chart_to_entry(
             .map(|x| x.as_secs())
             .unwrap_or_default() as _,
         custom_offset: 0,
+        radar: serde_json::to_string(&c.radar()).ok(),
+        duration: Some(chart_duration(c)),
     }
 }
 
+/// Time until the last object of the chart in milliseconds
+fn chart_duration(c: &kson::Chart) -> i64 {
+    c.tick_to_ms(c.get_last_tick()) as i64
+}
+
 async fn query_songs(
     database: &LocalSongsDb,
     q: &str,
     filter: &SongFilter,
     sort: SongSort,
+    effector_songs: Option<&HashSet<SongId>>,
+    recent_songs: Option<&[SongId]>,
 ) -> anyhow::Result<Vec<SongId>> {
     let folder = if let SongFilterType::Folder(folder) = &filter.filter_type {
         let mut p = songs_path();
@@ -460,10 +1126,26 @@ async fn query_songs(
         }
     };
 
-    Ok(charts.iter().map(|x| SongId::IntId(*x)).collect_vec())
+    let order = charts
+        .iter()
+        .map(|x| SongId::IntId(*x))
+        .filter(|id| effector_songs.map_or(true, |songs| songs.contains(id)));
+
+    // Recently played songs keep their own order, the query still applies the search and level
+    Ok(match recent_songs {
+        Some(recent) => {
+            let matching: HashSet<_> = order.collect();
+            recent
+                .iter()
+                .filter(|id| matching.contains(id))
+                .cloned()
+                .collect_vec()
+        }
+        None => order.collect_vec(),
+    })
 }
 
-fn songs_path() -> PathBuf {
+pub(super) fn songs_path() -> PathBuf {
     let song_path = crate::config::GameConfig::get().songs_path.clone();
 
     if song_path.is_absolute() {
@@ -481,16 +1163,36 @@ impl WorkerService for FileSongProvider {
             .ready()
             .is_some()
             .then(|| panic!("Song file provider worker returned")); //panics if worker paniced
-        let mut importer_dirty = false;
+
+        let library = GameConfig::get().library.clone();
+        if library.show_duplicates != self.showing_duplicates {
+            self.showing_duplicates = library.show_duplicates;
+            self.send_query();
+        }
+        if library
+            .rescan_interval
+            .is_some_and(|interval| self.last_rescan.elapsed() >= interval)
+        {
+            info!("Starting scheduled rescan");
+            self.last_rescan = Instant::now();
+            self.refresh();
+        }
+
         while let Some(ev) = self.worker_rx.try_recv().ok() {
             match ev {
                 WorkerEvent::ImporterState(s) => {
-                    if self.last_full_update.elapsed().unwrap().as_secs() > 2 {
-                        self.worker_tx.send(WorkerControlMessage::LoadDb);
-                        self.last_full_update = SystemTime::now();
-                    }
+                    // Shown in the song select through `scan_progress`
                     self.importer_state = s;
-                    importer_dirty = true;
+                }
+                WorkerEvent::Duplicates(duplicates) => {
+                    let changed = duplicates.len() != self.duplicates.len()
+                        || duplicates
+                            .iter()
+                            .any(|d| self.duplicates.get(&d.id) != Some(d));
+                    self.duplicates = duplicates.into_iter().map(|d| (d.id.clone(), d)).collect();
+                    if changed {
+                        self.send_query();
+                    }
                 }
                 WorkerEvent::SongProvider(mut ev) => {
                     match &mut ev {
@@ -498,6 +1200,7 @@ impl WorkerService for FileSongProvider {
                             //TODO: Consider full update event
                             let mut new_songs = vec![];
                             for s in s.iter() {
+                                self.index_song(s);
                                 if self.all_songs.insert(s.id.clone(), s.clone()).is_none() {
                                     new_songs.push(s.clone());
                                 }
@@ -506,83 +1209,82 @@ impl WorkerService for FileSongProvider {
                             *s = new_songs
                         }
                         SongProviderEvent::SongsRemoved(r) => {
+                            for id in r.iter() {
+                                self.effector_index.remove(id);
+                            }
                             self.all_songs.retain(|k, _| !r.contains(k))
                         }
-                        SongProviderEvent::OrderChanged(_) => {}
+                        SongProviderEvent::OrderChanged(order) => self.hide_duplicates(order),
+                        SongProviderEvent::GroupsChanged(_) => {}
                         SongProviderEvent::StatusUpdate(_) => {}
                     }
-                    match &ev {
-                        SongProviderEvent::OrderChanged(_) => {}
-                        _ => self.set_sort(self.sort),
-                    }
+                    let groups = match &ev {
+                        SongProviderEvent::OrderChanged(order) => Some(group_songs(
+                            self.sort.sort_type,
+                            order
+                                .iter()
+                                .filter_map(|id| Some(&**self.all_songs.get(id)?)),
+                        )),
+                        _ => {
+                            self.set_sort(self.sort);
+                            None
+                        }
+                    };
                     log_result!(self
                         .song_bus
                         .try_broadcast(ev)
                         .map_err(|_| "Song event bus full"));
+                    if let Some(groups) = groups {
+                        log_result!(self
+                            .song_bus
+                            .try_broadcast(SongProviderEvent::GroupsChanged(groups))
+                            .map_err(|_| "Song event bus full"));
+                    }
                 }
                 _ => (),
             }
         }
-
-        if importer_dirty {
-            log_result!(self
-                .song_bus
-                .try_broadcast(SongProviderEvent::StatusUpdate(
-                    self.importer_state.to_string(),
-                ))
-                .map_err(|_| "Song event bus full"));
-        }
     }
 }
 
 impl SongProvider for FileSongProvider {
     fn set_search(&mut self, q: &str) {
         self.query = q.to_string();
-        self.worker_tx.send(WorkerControlMessage::Query(
-            q.to_string(),
-            self.filter.clone(),
-            self.sort,
-        ));
+        self.send_query();
     }
 
     fn set_sort(&mut self, sort: super::SongSort) {
         self.sort = sort;
-        self.worker_tx.send(WorkerControlMessage::Query(
-            self.query.clone(),
-            self.filter.clone(),
-            self.sort,
-        ));
+        self.send_query();
         GameConfig::get_mut().song_select.sorting = self.sort;
     }
 
     fn set_filter(&mut self, filter: super::SongFilter) {
         self.filter = filter;
-        self.worker_tx.send(WorkerControlMessage::Query(
-            self.query.clone(),
-            self.filter.clone(),
-            self.sort,
-        ));
+        self.send_query();
         GameConfig::get_mut().song_select.filter = self.filter.clone();
     }
 
     fn set_current_index(&mut self, _index: u64) {}
 
-    fn load_song(&self, id: &SongDiffId) -> anyhow::Result<LoadSongFn> {
-        let _diff_index = match id {
-            SongDiffId::DiffOnly(diff_id) | SongDiffId::SongDiff(_, diff_id) => match &diff_id.0 {
-                SongId::IntId(id) => *id,
-                SongId::StringId(hash) => {
-                    block_on(self.database.get_hash_id(hash))?.ok_or(anyhow!("No song hash"))?
-                }
-                SongId::Missing => todo!(),
-            },
-            _ => todo!(),
-        };
+    fn chart_path(&self, id: &SongDiffId) -> Option<PathBuf> {
+        self.chart_entry(id).ok().map(|c| PathBuf::from(c.path))
+    }
 
+    fn chart_paths(&self) -> HashMap<DiffId, PathBuf> {
         let db = self.database.clone();
-        let path = PathBuf::from(block_on!(db.get_song(_diff_index as _))?.path);
+        block_on!(db.get_all_chart_files())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(path, hash)| (DiffId(SongId::StringId(hash)), PathBuf::from(path)))
+            .collect()
+    }
 
-        Ok(Box::new(move || {
+    fn load_song(&self, id: &SongDiffId) -> anyhow::Result<LoadSongFn> {
+        let path = PathBuf::from(self.chart_entry(id)?.path);
+
+        Ok(Box::new(move |progress: Sender<LoadProgress>| {
+            _ = progress.send(LoadProgress::Chart);
             let data = std::fs::read(&path)?;
             let data = encoding::decode(
                 &data,
@@ -594,11 +1296,18 @@ impl SongProvider for FileSongProvider {
 
             let chart = kson::Chart::from_ksh(&data)?;
 
-            let audio = rodio::decoder::Decoder::new(std::fs::File::open(
-                path.with_file_name(&chart.audio.bgm.filename),
-            )?)?;
+            let declared = path.with_file_name(&chart.audio.bgm.filename);
+            let audio_path = resolve_audio(&declared)
+                .ok_or_else(|| anyhow!("No audio found for {}", declared.display()))?;
+            info!("Using audio file {}", audio_path.display());
+
+            let audio = open_audio(
+                std::io::BufReader::new(std::fs::File::open(&audio_path)?),
+                &audio_path.display().to_string(),
+                &progress,
+            )?;
 
-            Ok((chart, Box::new(audio.convert_samples())))
+            Ok((chart, audio))
         }))
     }
 
@@ -612,32 +1321,31 @@ impl SongProvider for FileSongProvider {
             std::time::Duration,
         )>,
     > {
-        let db = self.database.clone();
-        let id = id.clone();
-        poll_promise::Promise::spawn_async(async move {
-            profile_function!();
-            let SongId::IntId(id) = id else {
-                bail!("Unsupported id type")
-            };
-            let mut charts = block_on(db.get_charts_for_folder(id))?;
-            let Some(mut chart) = charts.pop() else {
-                bail!("No chart found")
-            };
+        match id {
+            SongId::IntId(folder) => self.folder_preview(*folder, None),
+            _ => poll_promise::Promise::from_ready(Err(anyhow!("Unsupported id type"))),
+        }
+    }
 
-            let Some(path) = chart.preview_file.take() else {
-                bail!("No preview file")
-            };
+    fn get_diff_preview(&self, id: &SongDiffId) -> poll_promise::Promise<PreviewResult> {
+        let entry = match id.get_diff() {
+            Some(_) => self.chart_entry(id),
+            None => Err(anyhow!("No difficulty to preview")),
+        };
+        match entry {
+            Ok(chart) => self.folder_preview(chart.folderid, Some(chart.hash)),
+            Err(e) => poll_promise::Promise::from_ready(Err(e)),
+        }
+    }
 
-            let source = rodio::Decoder::new(std::fs::File::open(
-                PathBuf::from(&chart.path).with_file_name(path),
-            )?)?
-            .convert_samples();
-            Ok((
-                Box::new(source) as Box<dyn Source<Item = f32> + Send>,
-                Duration::from_millis(chart.preview_offset as u64),
-                Duration::from_millis(chart.preview_length as u64),
-            ))
-        })
+    fn preview_key(&self, id: &SongDiffId) -> Option<u64> {
+        let chart = self.chart_entry(id).ok()?;
+        Some(egui::util::hash((
+            chart.folderid,
+            chart.preview_file,
+            chart.preview_offset,
+            chart.preview_length,
+        )))
     }
 
     fn subscribe(&mut self) -> bus::BusReader<SongProviderEvent> {
@@ -646,13 +1354,16 @@ impl SongProvider for FileSongProvider {
 
     fn get_all(&self) -> (Vec<Arc<Song>>, Vec<SongId>) {
         //TODO: a bit ugly but trigger query here to initialize the sort array as well
-        let order = block_on(query_songs(
+        let mut order = block_on(query_songs(
             &self.database,
             &self.query,
             &self.filter,
             self.sort,
+            self.effector_songs().as_ref(),
+            self.recent_songs().as_deref(),
         ))
         .unwrap_or_default();
+        self.hide_duplicates(&mut order);
         (self.all_songs.values().cloned().collect_vec(), order)
     }
 
@@ -673,11 +1384,16 @@ impl SongProvider for FileSongProvider {
             let diffs = &mut song.difficulties.write().expect("Lock error");
             let diff = diffs.iter_mut().find(|x| x.id == *diff);
             if let Some(diff) = diff {
-                diff.top_badge = diff.top_badge.max(score.badge);
-                diff.scores.push(score);
-                diff.scores.sort_by_key(|x| -x.score);
+                diff.add_score(score);
             }
         }
+
+        // The score is already in the database, so the order can be updated right away
+        if self.filter.filter_type == SongFilterType::RecentlyPlayed
+            || self.sort.sort_type == SongSortType::LastPlayed
+        {
+            self.send_query();
+        }
     }
 
     fn get_available_sorts(&self) -> Vec<super::SongSort> {
@@ -722,6 +1438,22 @@ impl SongProvider for FileSongProvider {
                 crate::song_provider::SongSortType::Effector,
                 crate::song_provider::SortDir::Desc,
             ),
+            super::SongSort::new(
+                crate::song_provider::SongSortType::Level,
+                crate::song_provider::SortDir::Asc,
+            ),
+            super::SongSort::new(
+                crate::song_provider::SongSortType::Level,
+                crate::song_provider::SortDir::Desc,
+            ),
+            super::SongSort::new(
+                crate::song_provider::SongSortType::LastPlayed,
+                crate::song_provider::SortDir::Desc,
+            ),
+            super::SongSort::new(
+                crate::song_provider::SongSortType::LastPlayed,
+                crate::song_provider::SortDir::Asc,
+            ),
         ]
     }
 
@@ -734,7 +1466,10 @@ impl SongProvider for FileSongProvider {
             return vec![];
         };
 
-        let mut res = vec![super::SongFilterType::None];
+        let mut res = vec![
+            super::SongFilterType::None,
+            super::SongFilterType::RecentlyPlayed,
+        ];
 
         res.extend(
             song_path_contents
@@ -757,20 +1492,77 @@ impl SongProvider for FileSongProvider {
                     super::SongFilterType::Folder(x.file_name().to_string_lossy().to_string())
                 }),
         );
+        res.extend(
+            self.effector_index
+                .effectors(EFFECTOR_FILTER_MIN_CHARTS)
+                .into_iter()
+                .map(super::SongFilterType::Effector),
+        );
         res
     }
 
+    fn scan_progress(&self) -> Option<ScanProgress> {
+        self.scan.progress()
+    }
+
+    fn scan_failures(&self) -> Vec<PathBuf> {
+        self.scan.failed.lock().expect("Lock error").clone()
+    }
+
+    fn scan_missing_audio(&self) -> Vec<PathBuf> {
+        self.scan.missing_audio.lock().expect("Lock error").clone()
+    }
+
+    fn duplicate_songs(&self) -> Vec<DuplicateSong> {
+        self.duplicates
+            .values()
+            .cloned()
+            .sorted_by(|a, b| a.path.cmp(&b.path))
+            .collect()
+    }
+
     fn refresh(&mut self) {
         if let ImporterState::Idle = self.importer_state {
             self.importer_state = ImporterState::Starting;
             self.worker_tx.send(WorkerControlMessage::Refresh);
         }
     }
+
+    fn song_cache(&self) -> Option<Box<dyn ManagedCache>> {
+        Some(Box::new(SongCache {
+            worker_tx: self.worker_tx.clone(),
+            scan: self.scan.clone(),
+        }))
+    }
+
+    fn preview_cache(&self) -> Option<Box<dyn ManagedCache>> {
+        Some(Box::new(self.preview_cache.clone()))
+    }
+
+    fn get_metadata(&self, id: &SongDiffId) -> anyhow::Result<ChartMetadata> {
+        read_metadata(Path::new(&self.chart_entry(id)?.path))
+    }
+
+    fn set_metadata(&mut self, id: &SongDiffId, meta: ChartMetadata) -> anyhow::Result<()> {
+        let entry = self.chart_entry(id)?;
+        let path = PathBuf::from(entry.path);
+        write_metadata(&path, &meta)?;
+        info!("Updated metadata of {}", path.display());
+        self.worker_tx.send(WorkerControlMessage::Rescan {
+            path,
+            folder_id: entry.folderid,
+            old_hash: entry.hash,
+        });
+        Ok(())
+    }
 }
 
 impl ScoreProvider for FileSongProvider {
-    fn get_scores(&mut self, _id: &SongDiffId) -> Vec<Score> {
-        todo!()
+    fn get_scores(&self, chart_hash: &str) -> anyhow::Result<Vec<Score>> {
+        Ok(block_on(self.database.get_scores_for_chart(chart_hash))?
+            .into_iter()
+            .map(Score::from)
+            .collect())
     }
 
     fn insert_score(&mut self, id: &SongDiffId, score: Score) -> anyhow::Result<()> {
@@ -781,6 +1573,8 @@ impl ScoreProvider for FileSongProvider {
                 gauge_option,
                 mirror,
                 random,
+                hard_random,
+                random_seed,
                 auto_flags,
                 score,
                 perfects,
@@ -823,12 +1617,15 @@ impl ScoreProvider for FileSongProvider {
                 window_miss: hit_window.miss.as_millis() as _,
                 window_slam: hit_window.good.as_millis() as _,
                 gauge_type: gauge_type as _,
-                gauge_opt: 0,
+                gauge_opt: gauge_option as _,
                 mirror,
                 random,
+                hard_random,
+                random_seed: random_seed.map(i64::from),
             }))?;
         }
 
+        self.score_backlog.push(id.clone(), score.clone());
         self.score_bus
             .broadcast(ScoreProviderEvent::NewScore(id.clone(), score));
 
@@ -839,32 +1636,47 @@ impl ScoreProvider for FileSongProvider {
         self.score_bus.add_rx()
     }
 
+    fn scores_since(&self, timestamp: i32) -> Vec<(SongDiffId, Score)> {
+        self.score_backlog.since(timestamp)
+    }
+
     fn init_scores(&self, songs: &mut dyn Iterator<Item = &Arc<Song>>) -> anyhow::Result<()> {
-        let mut scores = block_on(self.database.get_all_scores())?;
+        apply_scores(songs, block_on(self.database.get_all_scores())?);
+        Ok(())
+    }
+}
 
-        let mut scores = scores
-            .into_iter()
-            .group_by(|x| DiffId(SongId::StringId(x.chart_hash.clone()))) //TODO: Excessive cloning
-            .into_iter()
-            .map(|(key, scores)| (key, scores.map(Score::from).collect_vec()))
-            .collect::<HashMap<_, _>>();
-
-        songs.for_each(|song| {
-            let mut diffs = song.difficulties.write().expect("Lock error");
-            for diff in diffs.iter_mut() {
-                diff.scores = scores.remove(&diff.id).unwrap_or_default();
-                diff.scores.sort_by_key(|x| -x.score);
-                diff.top_badge = diff
-                    .scores
-                    .iter()
-                    .map(|x| x.badge)
-                    .max()
-                    .unwrap_or_default();
-            }
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, path::PathBuf};
 
-            diffs.sort_by_key(|x| (x.difficulty, x.level))
-        });
+    use super::find_duplicates;
+    use crate::song_provider::SongId;
 
-        Ok(())
+    #[test]
+    fn duplicate_songs() {
+        let chart = |hash: &str, folder: &str| {
+            (
+                hash.to_string(),
+                PathBuf::from_iter([folder, &format!("{hash}.ksh")]),
+            )
+        };
+        let songs = BTreeMap::from([
+            (1, vec![chart("a", "one"), chart("b", "one")]),
+            // All charts already in song 1
+            (2, vec![chart("b", "two"), chart("a", "two")]),
+            // Shares a chart but also has its own
+            (3, vec![chart("a", "three"), chart("c", "three")]),
+            (4, vec![chart("c", "four")]),
+            (5, vec![]),
+        ]);
+
+        let duplicates = find_duplicates(&songs);
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].id, SongId::IntId(2));
+        assert_eq!(duplicates[0].path, PathBuf::from("two"));
+        assert_eq!(duplicates[0].original, PathBuf::from("one"));
+        assert_eq!(duplicates[1].id, SongId::IntId(4));
+        assert_eq!(duplicates[1].original, PathBuf::from("three"));
     }
 }
@@ -0,0 +1,284 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{mpsc::Sender, Arc},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail};
+use itertools::Itertools;
+use log::warn;
+use poll_promise::Promise;
+
+use crate::{
+    results::Score,
+    songselect::{Difficulty, Song},
+};
+
+use super::{
+    decode_file, group_songs, open_audio, resolve_audio, resolve_jacket, DiffId, LoadProgress,
+    LoadSongFn, PreviewResult, SongDiffId, SongFilter, SongFilterType, SongId, SongProvider,
+    SongProviderEvent, SongSort, SongSortType, SortDir,
+};
+
+struct FolderChart {
+    path: PathBuf,
+    chart: kson::Chart,
+    song: Arc<Song>,
+}
+
+/// Read-only provider for a single folder of `.kson` charts, an example of a provider added
+/// through `register_provider`.
+///
+/// Every chart is listed as its own song and no scores are kept.
+pub struct FolderSongProvider {
+    charts: HashMap<SongId, FolderChart>,
+    bus: bus::Bus<SongProviderEvent>,
+    query: String,
+    filter: SongFilter,
+    sort: SongSort,
+}
+
+impl FolderSongProvider {
+    /// Lists the charts directly inside the configured songs folder
+    pub fn new() -> Self {
+        let folder = super::files::songs_path();
+        let charts = std::fs::read_dir(&folder)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| {
+                        p.extension()
+                            .is_some_and(|e| e.eq_ignore_ascii_case("kson"))
+                    })
+                    .filter_map(|path| match read_chart(&path) {
+                        Ok(chart) => Some(chart),
+                        Err(e) => {
+                            warn!("Could not read {}: {e}", path.display());
+                            None
+                        }
+                    })
+                    .map(|chart| (chart.song.id.clone(), chart))
+                    .collect()
+            })
+            .unwrap_or_else(|e| {
+                warn!("Could not read song folder {}: {e}", folder.display());
+                HashMap::new()
+            });
+
+        Self {
+            charts,
+            bus: bus::Bus::new(32),
+            query: String::new(),
+            filter: SongFilter::default(),
+            sort: SongSort::default(),
+        }
+    }
+
+    fn order(&self) -> Vec<SongId> {
+        let query = self.query.to_lowercase();
+        self.charts
+            .values()
+            .filter(|c| {
+                query.is_empty()
+                    || c.chart.meta.title.to_lowercase().contains(&query)
+                    || c.chart.meta.artist.to_lowercase().contains(&query)
+            })
+            .filter(|c| self.filter.level == 0 || c.chart.meta.level == self.filter.level)
+            .filter(|c| match &self.filter.filter_type {
+                SongFilterType::Effector(e) => c.chart.meta.chart_author == *e,
+                _ => true,
+            })
+            .sorted_by(|a, b| {
+                let (a, b) = match self.sort.direction {
+                    SortDir::Asc => (&a.chart.meta, &b.chart.meta),
+                    SortDir::Desc => (&b.chart.meta, &a.chart.meta),
+                };
+                match self.sort.sort_type {
+                    SongSortType::Artist => a.artist.cmp(&b.artist),
+                    SongSortType::Effector => a.chart_author.cmp(&b.chart_author),
+                    SongSortType::Level => a.level.cmp(&b.level),
+                    _ => a.title.cmp(&b.title),
+                }
+            })
+            .map(|c| c.song.id.clone())
+            .collect()
+    }
+
+    fn send_order(&mut self) {
+        let order = self.order();
+        let groups = group_songs(
+            self.sort.sort_type,
+            order
+                .iter()
+                .filter_map(|id| Some(&*self.charts.get(id)?.song)),
+        );
+        _ = self
+            .bus
+            .try_broadcast(SongProviderEvent::OrderChanged(order));
+        _ = self
+            .bus
+            .try_broadcast(SongProviderEvent::GroupsChanged(groups));
+    }
+
+    fn chart(&self, id: &SongId) -> anyhow::Result<&FolderChart> {
+        self.charts.get(id).ok_or(anyhow!("Song not in folder"))
+    }
+}
+
+fn read_chart(path: &Path) -> anyhow::Result<FolderChart> {
+    let chart: kson::Chart = serde_json::from_slice(&std::fs::read(path)?)?;
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or(anyhow!("No file name"))?;
+    let id = SongId::StringId(name);
+
+    let song = Song {
+        title: chart.meta.title.clone(),
+        artist: chart.meta.artist.clone(),
+        bpm: chart.meta.disp_bpm.clone(),
+        id: id.clone(),
+        difficulties: Arc::new(
+            vec![Difficulty {
+                jacket_path: resolve_jacket(path, &chart.meta.jacket_filename),
+                level: chart.meta.level,
+                difficulty: chart.meta.difficulty,
+                id: DiffId(id),
+                effector: chart.meta.chart_author.clone(),
+                top_badge: 0,
+                scores: vec![],
+                hash: None,
+                illustrator: chart.meta.jacket_author.clone(),
+                radar: Some(chart.radar()),
+                duration: None,
+                duration_string: None,
+                last_played: None,
+                audio_broken: false,
+                density_strip_path: None,
+                jacket_loaded: false,
+            }]
+            .into(),
+        ),
+        uploader: None,
+    };
+
+    Ok(FolderChart {
+        path: path.to_path_buf(),
+        chart,
+        song: Arc::new(song),
+    })
+}
+
+impl Default for FolderSongProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SongProvider for FolderSongProvider {
+    fn subscribe(&mut self) -> bus::BusReader<SongProviderEvent> {
+        self.bus.add_rx()
+    }
+
+    fn set_search(&mut self, query: &str) {
+        self.query = query.to_string();
+        self.send_order();
+    }
+
+    fn get_available_sorts(&self) -> Vec<SongSort> {
+        [
+            SongSortType::Title,
+            SongSortType::Artist,
+            SongSortType::Effector,
+            SongSortType::Level,
+        ]
+        .into_iter()
+        .flat_map(|t| {
+            [
+                SongSort::new(t, SortDir::Asc),
+                SongSort::new(t, SortDir::Desc),
+            ]
+        })
+        .collect()
+    }
+
+    fn get_available_filters(&self) -> Vec<SongFilterType> {
+        std::iter::once(SongFilterType::None)
+            .chain(
+                self.charts
+                    .values()
+                    .map(|c| c.chart.meta.chart_author.clone())
+                    .unique()
+                    .sorted()
+                    .map(SongFilterType::Effector),
+            )
+            .collect()
+    }
+
+    fn set_sort(&mut self, sort: SongSort) {
+        self.sort = sort;
+        self.send_order();
+    }
+
+    fn set_filter(&mut self, filter: SongFilter) {
+        self.filter = filter;
+        self.send_order();
+    }
+
+    fn set_current_index(&mut self, _index: u64) {}
+
+    fn chart_path(&self, id: &SongDiffId) -> Option<PathBuf> {
+        let song = id.get_song().or(id.get_diff().map(|d| &d.0))?;
+        self.chart(song).ok().map(|c| c.path.clone())
+    }
+
+    fn load_song(&self, id: &SongDiffId) -> anyhow::Result<LoadSongFn> {
+        let Some(song) = id.get_song().or(id.get_diff().map(|d| &d.0)) else {
+            bail!("Bad song id")
+        };
+        let FolderChart { path, chart, .. } = self.chart(song)?;
+        let chart = chart.clone();
+        let declared = path.with_file_name(&chart.audio.bgm.filename);
+
+        Ok(Box::new(move |progress: Sender<LoadProgress>| {
+            _ = progress.send(LoadProgress::Chart);
+            let bgm = resolve_audio(&declared)
+                .ok_or_else(|| anyhow!("No audio found for {}", declared.display()))?;
+            log::info!("Using audio file {}", bgm.display());
+            let audio = open_audio(
+                std::io::BufReader::new(std::fs::File::open(&bgm)?),
+                &bgm.display().to_string(),
+                &progress,
+            )?;
+            Ok((chart, audio))
+        }))
+    }
+
+    fn add_score(&self, _id: SongDiffId, _score: Score) {}
+
+    fn get_preview(&self, id: &SongId) -> Promise<PreviewResult> {
+        let preview = self.chart(id).map(|c| {
+            let bgm = &c.chart.audio.bgm;
+            (
+                c.path.with_file_name(&bgm.filename),
+                Duration::from_millis(bgm.preview.offset as _),
+                Duration::from_millis(bgm.preview.duration as _),
+            )
+        });
+
+        Promise::spawn_thread("folder preview", move || {
+            let (declared, offset, length) = preview?;
+            let path = resolve_audio(&declared)
+                .ok_or_else(|| anyhow!("No audio found for {}", declared.display()))?;
+            Ok((decode_file(&path)?, offset, length))
+        })
+    }
+
+    fn get_all(&self) -> (Vec<Arc<Song>>, Vec<SongId>) {
+        (
+            self.charts.values().map(|c| c.song.clone()).collect(),
+            self.order(),
+        )
+    }
+}
@@ -0,0 +1,509 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::Display,
+    io::{Read, Seek},
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, ensure, Context};
+use itertools::Itertools;
+use kson::{effects::AudioEffect, Chart};
+use rodio::{buffer::SamplesBuffer, source::UniformSourceIterator, Decoder, Sample, Source};
+
+use super::SongDiffId;
+
+/// Audio decoded before the game is allowed to start, the rest is decoded in the background
+const PREBUFFER: Duration = Duration::from_secs(5);
+/// Frames decoded between each progress report and sent to the playback at a time
+const CHUNK_FRAMES: usize = 8192;
+/// Extensions tried when the audio declared by a chart doesn't exist
+const AUDIO_EXTENSIONS: [&str; 4] = ["ogg", "mp3", "wav", "flac"];
+/// Audio decoded past the length the decoder reports, decoders of truncated files can keep
+/// returning silence forever
+const DECODE_MARGIN: Duration = Duration::from_secs(1);
+
+/// Stages of loading a song, sent from the loader to the transition screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadProgress {
+    Chart,
+    Audio,
+    /// Fraction of the audio that has to be decoded before the game can start
+    Decoding(f32),
+    Ready,
+}
+
+impl LoadProgress {
+    /// Overall loading progress in `0.0..=1.0`
+    pub fn fraction(self) -> f32 {
+        match self {
+            LoadProgress::Chart => 0.1,
+            LoadProgress::Audio => 0.2,
+            LoadProgress::Decoding(f) => 0.2 + 0.8 * f.clamp(0.0, 1.0),
+            LoadProgress::Ready => 1.0,
+        }
+    }
+}
+
+/// Finds the audio file for the `declared` path from a chart, trying in order:
+///
+/// 1. The declared file
+/// 2. The same file name with any of the other known audio extensions
+/// 3. The only audio file in the folder, if there is exactly one
+pub fn resolve_audio(declared: &Path) -> Option<PathBuf> {
+    if declared.is_file() {
+        return Some(declared.to_path_buf());
+    }
+
+    if let Some(other) = AUDIO_EXTENSIONS
+        .iter()
+        .map(|ext| declared.with_extension(ext))
+        .find(|p| p.is_file())
+    {
+        return Some(other);
+    }
+
+    let mut audio_files = std::fs::read_dir(declared.parent()?)
+        .ok()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && is_audio_file(p));
+    let only = audio_files.next()?;
+    audio_files.next().is_none().then_some(only)
+}
+
+/// Finds the jacket `filename` from the chart at `chart`, so difficulties naming the same image
+/// in different ways get the same path. Backslashes separate folders, `.` and `..` are resolved
+/// and a file that doesn't exist is looked for again ignoring case.
+pub fn resolve_jacket(chart: &Path, filename: &str) -> PathBuf {
+    let mut path = chart.parent().map(Path::to_path_buf).unwrap_or_default();
+    for part in filename.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                path.pop();
+            }
+            part => path.push(part),
+        }
+    }
+    if path.is_file() {
+        return path;
+    }
+
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase());
+    path.parent()
+        .and_then(|dir| std::fs::read_dir(dir).ok())
+        .and_then(|entries| {
+            entries.filter_map(|e| e.ok().map(|e| e.path())).find(|p| {
+                p.is_file() && p.file_name().map(|n| n.to_string_lossy().to_lowercase()) == name
+            })
+        })
+        .unwrap_or(path)
+}
+
+/// Files the `AudioSwap` effects of `chart` switch to
+pub fn swap_audio_files(chart: &Chart) -> Vec<String> {
+    chart
+        .get_effect_tracks()
+        .into_iter()
+        .filter_map(|track| match track.effect {
+            AudioEffect::AudioSwap(name) if !name.is_empty() => Some(name),
+            _ => None,
+        })
+        .unique()
+        .collect()
+}
+
+/// Decodes the files the `AudioSwap` effects of the chart at `chart_path` switch to, converted
+/// to the `channels` and `sample_rate` of the song. Files that can't be decoded are left out,
+/// their effects play the song as is.
+pub fn load_swap_audio(
+    chart: &Chart,
+    chart_path: &Path,
+    channels: u16,
+    sample_rate: u32,
+) -> HashMap<String, Arc<[f32]>> {
+    swap_audio_files(chart)
+        .into_iter()
+        .filter_map(|name| {
+            let path = chart_path.with_file_name(&name);
+            match decode_file(&path) {
+                Ok(source) => {
+                    let samples: Arc<[f32]> =
+                        UniformSourceIterator::new(source, channels, sample_rate).collect();
+                    Some((name, samples))
+                }
+                Err(e) => {
+                    log::warn!("Could not load swap audio {}: {e:#}", path.display());
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Error context of audio files that exist but can't be played
+#[derive(Debug)]
+pub struct BrokenAudio(pub String);
+
+impl Display for BrokenAudio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Could not decode {}", self.0)
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error")
+}
+
+/// Opens a decoder for the audio file `name`, failures including decoder panics are reported as
+/// [`BrokenAudio`]
+pub fn decode<R>(data: R, name: &str) -> anyhow::Result<Decoder<R>>
+where
+    R: Read + Seek + Send + Sync + 'static,
+{
+    let decoder = std::panic::catch_unwind(AssertUnwindSafe(|| Decoder::new(data)))
+        .map_err(|panic| anyhow!("Decoder panicked: {}", panic_message(panic.as_ref())))
+        .and_then(|decoder| Ok(decoder?))
+        .with_context(|| BrokenAudio(name.to_string()))?;
+    ensure!(
+        decoder.channels() > 0 && decoder.sample_rate() > 0,
+        BrokenAudio(name.to_string())
+    );
+    Ok(decoder)
+}
+
+/// Opens `path` for a preview, the source ends early if the file turns out to be truncated
+pub fn decode_file(path: &Path) -> anyhow::Result<Box<dyn Source<Item = f32> + Send>> {
+    let name = path.display().to_string();
+    let file = std::fs::File::open(path).with_context(|| BrokenAudio(name.clone()))?;
+    let source = PanicSafe::new(decode(std::io::BufReader::new(file), &name)?.convert_samples());
+    Ok(
+        match source
+            .total_duration()
+            .and_then(|d| d.checked_add(DECODE_MARGIN))
+        {
+            Some(limit) => Box::new(source.take_duration(limit)),
+            None => Box::new(source),
+        },
+    )
+}
+
+/// Decodes the first few seconds of the audio file `name` and streams the rest from a decoding
+/// thread.
+///
+/// Sources that can't seek are fully decoded up front instead, as those are usually formats
+/// where the decoder can't be trusted to keep up with playback.
+pub fn open_audio<R>(
+    data: R,
+    name: &str,
+    progress: &Sender<LoadProgress>,
+) -> anyhow::Result<Box<dyn Source<Item = f32> + Send>>
+where
+    R: Read + Seek + Send + Sync + 'static,
+{
+    _ = progress.send(LoadProgress::Audio);
+    let mut decoder = decode(data, name)?;
+    let channels = decoder.channels();
+    let sample_rate = decoder.sample_rate();
+    let total_duration = decoder.total_duration();
+    let seekable = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        decoder.try_seek(Duration::ZERO).is_ok()
+    }))
+    .unwrap_or(false);
+
+    let sample_count =
+        |d: Duration| (d.as_secs_f64() * sample_rate as f64) as usize * channels as usize;
+    let chunk = CHUNK_FRAMES * channels as usize;
+    let limit = total_duration
+        .and_then(|d| d.checked_add(DECODE_MARGIN))
+        .map_or(usize::MAX, sample_count);
+    let mut samples = PanicSafe::new(decoder.convert_samples::<f32>()).take(limit);
+
+    if !seekable {
+        log::info!("Audio is not seekable, decoding all of it");
+        let expected = total_duration.map(sample_count);
+        let mut buffer = vec![];
+        while decode_chunk(&mut samples, &mut buffer, chunk) {
+            if let Some(expected) = expected {
+                _ = progress.send(LoadProgress::Decoding(
+                    buffer.len() as f32 / expected.max(1) as f32,
+                ));
+            }
+        }
+        ensure!(!buffer.is_empty(), BrokenAudio(name.to_string()));
+        _ = progress.send(LoadProgress::Ready);
+        return Ok(Box::new(SamplesBuffer::new(channels, sample_rate, buffer)));
+    }
+
+    let prebuffer = sample_count(PREBUFFER);
+    let mut head = Vec::with_capacity(prebuffer + chunk);
+    let mut more = true;
+    while more && head.len() < prebuffer {
+        more = decode_chunk(&mut samples, &mut head, chunk);
+        _ = progress.send(LoadProgress::Decoding(head.len() as f32 / prebuffer as f32));
+    }
+    ensure!(!head.is_empty(), BrokenAudio(name.to_string()));
+
+    let (chunk_tx, chunk_rx) = channel();
+    _ = chunk_tx.send(head);
+    if more {
+        // Unbounded so the decoder can run as far ahead of the playback as it's able to
+        std::thread::Builder::new()
+            .name("Audio stream".into())
+            .spawn(move || loop {
+                let mut buffer = Vec::with_capacity(chunk);
+                let more = decode_chunk(&mut samples, &mut buffer, chunk);
+                if chunk_tx.send(buffer).is_err() || !more {
+                    break;
+                }
+            })?;
+    }
+
+    _ = progress.send(LoadProgress::Ready);
+    Ok(Box::new(StreamedSource {
+        channels,
+        sample_rate,
+        total_duration,
+        current: Vec::new().into_iter(),
+        chunks: chunk_rx,
+    }))
+}
+
+/// Appends up to `len` samples to `buffer`, returns false once the source has ended
+fn decode_chunk(source: &mut impl Iterator<Item = f32>, buffer: &mut Vec<f32>, len: usize) -> bool {
+    let before = buffer.len();
+    buffer.extend(source.by_ref().take(len));
+    buffer.len() - before == len
+}
+
+/// Ends the source instead of panicking when the decoder hits malformed data partway through
+pub struct PanicSafe<S> {
+    inner: S,
+    failed: bool,
+}
+
+impl<S> PanicSafe<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            failed: false,
+        }
+    }
+}
+
+impl<S> Iterator for PanicSafe<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        if self.failed {
+            return None;
+        }
+
+        std::panic::catch_unwind(AssertUnwindSafe(|| self.inner.next())).unwrap_or_else(|panic| {
+            log::warn!("Audio decoding failed: {}", panic_message(panic.as_ref()));
+            self.failed = true;
+            None
+        })
+    }
+}
+
+impl<S> Source for PanicSafe<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.failed {
+            Some(0)
+        } else {
+            self.inner.current_frame_len()
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
+
+static BROKEN_AUDIO: Mutex<Vec<SongDiffId>> = Mutex::new(Vec::new());
+
+/// Remembers that `id` can't be played if `error` is a [`BrokenAudio`] error, song select flags
+/// the difficulty once it takes the report with [`take_broken_audio`]
+pub fn report_broken_audio(id: SongDiffId, error: &anyhow::Error) {
+    if error.downcast_ref::<BrokenAudio>().is_some() {
+        BROKEN_AUDIO.lock().expect("Lock error").push(id);
+    }
+}
+
+pub fn take_broken_audio() -> Vec<SongDiffId> {
+    std::mem::take(&mut *BROKEN_AUDIO.lock().expect("Lock error"))
+}
+
+/// Plays back chunks of samples as they are received from the decoding thread
+struct StreamedSource {
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+    current: std::vec::IntoIter<f32>,
+    chunks: Receiver<Vec<f32>>,
+}
+
+impl Iterator for StreamedSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        loop {
+            if let Some(sample) = self.current.next() {
+                return Some(sample);
+            }
+
+            // Only blocks if decoding is slower than the playback
+            self.current = self.chunks.recv().ok()?.into_iter();
+        }
+    }
+}
+
+impl Source for StreamedSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::BufReader,
+        path::{Path, PathBuf},
+    };
+
+    use anyhow::anyhow;
+
+    use super::{
+        decode_file, open_audio, report_broken_audio, resolve_audio, resolve_jacket,
+        take_broken_audio, BrokenAudio,
+    };
+    use crate::song_provider::SongDiffId;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/song_provider/loading")
+            .join(name)
+    }
+
+    #[test]
+    fn broken_audio_files() {
+        let (progress, _rx) = std::sync::mpsc::channel();
+        for name in ["empty.ogg", "truncated.ogg"] {
+            let file = BufReader::new(File::open(fixture(name)).unwrap());
+            let error = open_audio(file, name, &progress).err().expect(name);
+            assert!(
+                error.downcast_ref::<BrokenAudio>().is_some(),
+                "{name}: {error}"
+            );
+            assert!(error.to_string().contains(name));
+            assert!(decode_file(&fixture(name)).is_err());
+        }
+
+        // Declares one second of stereo audio but holds much less, decoding must not go on
+        // past the declared length and the margin
+        let file = BufReader::new(File::open(fixture("truncated.wav")).unwrap());
+        match open_audio(file, "truncated.wav", &progress) {
+            Ok(audio) => assert!(audio.count() <= 2 * 2 * 44100),
+            Err(e) => assert!(e.downcast_ref::<BrokenAudio>().is_some(), "{e}"),
+        }
+
+        let error = decode_file(&fixture("empty.ogg")).err().unwrap();
+        report_broken_audio(SongDiffId::Missing, &error);
+        report_broken_audio(SongDiffId::Missing, &anyhow!("No preview file"));
+        assert_eq!(take_broken_audio().len(), 1);
+        assert!(take_broken_audio().is_empty());
+    }
+
+    #[test]
+    fn audio_fallback_order() {
+        let dir = std::env::temp_dir().join(format!("rusc_audio_{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str| {
+            std::fs::write(dir.join(name), []).unwrap();
+            dir.join(name)
+        };
+
+        assert_eq!(resolve_audio(&dir.join("song.ogg")), None);
+        let exh = file("exh.mp3");
+        assert_eq!(resolve_audio(&dir.join("song.ogg")), Some(exh.clone()));
+        let song = file("song.wav");
+        assert_eq!(resolve_audio(&dir.join("song.ogg")), Some(song));
+        assert_eq!(resolve_audio(&exh), Some(exh));
+        assert_eq!(resolve_audio(&dir.join("other.ogg")), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn jacket_spellings() {
+        let dir = std::env::temp_dir().join(format!("rusc_jacket_{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("img")).unwrap();
+        std::fs::write(dir.join("img").join("jacket.png"), []).unwrap();
+        let chart = dir.join("exh.ksh");
+        let jacket = dir.join("img").join("jacket.png");
+
+        for name in [
+            "img/jacket.png",
+            "img\\jacket.png",
+            "./img/jacket.png",
+            "img/../img/jacket.png",
+            "img/Jacket.PNG",
+        ] {
+            assert_eq!(resolve_jacket(&chart, name), jacket, "{name}");
+        }
+        assert_eq!(
+            resolve_jacket(&chart, "missing.png"),
+            dir.join("missing.png")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use encoding::{EncoderTrap, Encoding};
+use kson::{replace_ksh_header_values, Ksh};
+
+/// Chart metadata that can be edited from the song wheel
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChartMetadata {
+    pub title: String,
+    pub artist: String,
+    pub effector: String,
+    pub level: u8,
+    pub difficulty: u8,
+    pub offset: i32,
+}
+
+impl ChartMetadata {
+    pub fn from_chart(chart: &kson::Chart) -> Self {
+        Self {
+            title: chart.meta.title.clone(),
+            artist: chart.meta.artist.clone(),
+            effector: chart.meta.chart_author.clone(),
+            level: chart.meta.level,
+            difficulty: chart.meta.difficulty,
+            offset: chart.audio.bgm.offset,
+        }
+    }
+
+    fn apply(&self, chart: &mut kson::Chart) {
+        chart.meta.title.clone_from(&self.title);
+        chart.meta.artist.clone_from(&self.artist);
+        chart.meta.chart_author.clone_from(&self.effector);
+        chart.meta.level = self.level;
+        chart.meta.difficulty = self.difficulty;
+        chart.audio.bgm.offset = self.offset;
+    }
+
+    fn ksh_values(&self) -> [(&'static str, String); 6] {
+        let difficulty = match self.difficulty {
+            0 => "light",
+            1 => "challenge",
+            2 => "extended",
+            _ => "infinite",
+        };
+
+        [
+            ("title", self.title.clone()),
+            ("artist", self.artist.clone()),
+            ("effect", self.effector.clone()),
+            ("level", self.level.to_string()),
+            ("difficulty", difficulty.to_string()),
+            ("o", self.offset.to_string()),
+        ]
+    }
+}
+
+/// Reads the metadata of a chart file
+pub fn read_metadata(path: &Path) -> Result<ChartMetadata> {
+    let data = std::fs::read(path)?;
+    let chart = if is_ksh(path) {
+        let data = encoding::all::WINDOWS_31J
+            .decode(&data, encoding::DecoderTrap::Strict)
+            .map_err(|e| anyhow!("{e}"))?;
+        kson::Chart::from_ksh(&data)?
+    } else {
+        serde_json::from_slice(&data)?
+    };
+
+    Ok(ChartMetadata::from_chart(&chart))
+}
+
+/// Writes the metadata back to a chart file, a `.bak` copy of the original file is made before
+/// it's written to for the first time.
+pub fn write_metadata(path: &Path, meta: &ChartMetadata) -> Result<()> {
+    let data = std::fs::read(path)?;
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = Path::new(&backup);
+    if !backup.exists() {
+        std::fs::write(backup, &data)?;
+    }
+
+    let new_data = if is_ksh(path) {
+        let utf8 = data.starts_with(&[0xEF, 0xBB, 0xBF]);
+        let values = meta
+            .ksh_values()
+            .into_iter()
+            .map(|(key, value)| {
+                let value = if utf8 {
+                    value.into_bytes()
+                } else {
+                    encoding::all::WINDOWS_31J
+                        .encode(&value, EncoderTrap::Strict)
+                        .map_err(|e| anyhow!("Can't encode \"{value}\": {e}"))?
+                };
+                Ok((key, value))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let values: Vec<_> = values.iter().map(|(k, v)| (*k, v.as_slice())).collect();
+        replace_ksh_header_values(&data, &values)
+    } else if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("kson"))
+    {
+        let mut chart: kson::Chart = serde_json::from_slice(&data)?;
+        meta.apply(&mut chart);
+        serde_json::to_vec(&chart)?
+    } else {
+        bail!("Not a chart file: {}", path.display());
+    };
+
+    std::fs::write(path, new_data)?;
+    Ok(())
+}
+
+fn is_ksh(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("ksh"))
+}
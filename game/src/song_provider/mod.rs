@@ -1,7 +1,7 @@
 #![allow(unused)]
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet, VecDeque},
     default,
     fmt::{format, Debug, Display, Write},
     str::FromStr,
@@ -9,8 +9,9 @@ use std::{
     time::Duration,
 };
 
-use anyhow::ensure;
+use anyhow::{bail, ensure};
 use egui::util::hash;
+use itertools::Itertools;
 use kson::Chart;
 use log::LevelFilter;
 use luals_gen::ToLuaLsType;
@@ -25,14 +26,29 @@ use tealr::{
 
 use crate::{results::Score, songselect::Song};
 use specta::Type;
+mod effectors;
 mod files;
+#[cfg(feature = "folder-provider")]
+mod folder;
+mod loading;
+mod metadata;
 mod nautica;
+mod preview;
+mod registry;
+
+pub use loading::{
+    decode, decode_file, load_swap_audio, open_audio, report_broken_audio, resolve_audio,
+    resolve_jacket, swap_audio_files, take_broken_audio, BrokenAudio, LoadProgress, PanicSafe,
+};
+pub use metadata::ChartMetadata;
 
 #[derive(Debug, Clone)]
 pub enum SongProviderEvent {
     SongsAdded(Vec<Arc<Song>>),
     SongsRemoved(HashSet<SongId>),
     OrderChanged(Vec<SongId>),
+    /// Group titles and their songs for the current order, empty when the sort has no groups
+    GroupsChanged(Vec<(String, Vec<SongId>)>),
     StatusUpdate(String),
 }
 
@@ -41,6 +57,34 @@ pub enum ScoreProviderEvent {
     NewScore(SongDiffId, Score), //(diff.id, score)
 }
 
+/// Recently inserted scores, kept so they can be given to subscribers that weren't around when
+/// the events were sent
+#[derive(Debug, Default)]
+pub struct ScoreBacklog {
+    scores: VecDeque<(SongDiffId, Score)>,
+}
+
+impl ScoreBacklog {
+    const LEN: usize = 64;
+
+    pub fn push(&mut self, id: SongDiffId, score: Score) {
+        if self.scores.len() == Self::LEN {
+            self.scores.pop_front();
+        }
+        self.scores.push_back((id, score));
+    }
+
+    /// Scores set at or after `timestamp`, scores from the same second can't be told apart so
+    /// already seen ones may be included
+    pub fn since(&self, timestamp: i32) -> Vec<(SongDiffId, Score)> {
+        self.scores
+            .iter()
+            .filter(|(_, score)| score.timestamp >= timestamp)
+            .cloned()
+            .collect()
+    }
+}
+
 pub enum ScoreFilter {
     Local,
     Online,
@@ -82,6 +126,9 @@ pub enum SongSortType {
     Date,
     Artist,
     Effector,
+    Level,
+    /// Time the newest score on any difficulty was set
+    LastPlayed,
 }
 
 #[derive(
@@ -109,6 +156,8 @@ impl From<SongSort> for (rusc_database::SortColumn, rusc_database::SortDir) {
                 SongSortType::Date => rusc_database::SortColumn::Date,
                 SongSortType::Artist => rusc_database::SortColumn::Artist,
                 SongSortType::Effector => rusc_database::SortColumn::Effector,
+                SongSortType::Level => rusc_database::SortColumn::Level,
+                SongSortType::LastPlayed => rusc_database::SortColumn::LastPlayed,
             },
             match val.direction {
                 SortDir::Asc => rusc_database::SortDir::Asc,
@@ -135,6 +184,8 @@ impl Display for SongSort {
             SongSortType::Date => formatter.write_str("Date"),
             SongSortType::Artist => formatter.write_str("Artist"),
             SongSortType::Effector => formatter.write_str("Effector"),
+            SongSortType::Level => formatter.write_str("Level"),
+            SongSortType::LastPlayed => formatter.write_str("Last played"),
         }?;
 
         formatter.write_str(" ")?;
@@ -146,6 +197,80 @@ impl Display for SongSort {
     }
 }
 
+/// Title of the group a song is listed under when sorted by `sort`, `None` if the sort isn't
+/// grouped
+pub fn sort_group(sort: SongSortType, song: &Song) -> Option<String> {
+    let initial = |name: &str| {
+        let c = name.chars().next()?;
+        Some(if c.is_alphabetic() {
+            c.to_uppercase().to_string()
+        } else {
+            "#".to_string()
+        })
+    };
+    let diffs = song.difficulties.read().expect("Lock error");
+
+    match sort {
+        SongSortType::Title => initial(&song.title),
+        SongSortType::Artist => initial(&song.artist),
+        SongSortType::Effector => initial(&diffs.first()?.effector),
+        SongSortType::Level => Some(format!("Level {}", diffs.iter().map(|d| d.level).max()?)),
+        SongSortType::Score | SongSortType::Date | SongSortType::LastPlayed => None,
+    }
+}
+
+/// Songs listed by the [`SongFilterType::RecentlyPlayed`] filter
+pub const RECENTLY_PLAYED_LEN: usize = 50;
+
+/// Played songs, most recently played first
+pub fn recently_played<'a>(songs: impl IntoIterator<Item = &'a Song>) -> Vec<SongId> {
+    songs
+        .into_iter()
+        .filter_map(|song| Some((song.last_played()?, song.id.clone())))
+        .sorted_by(|a, b| b.cmp(a))
+        .take(RECENTLY_PLAYED_LEN)
+        .map(|(_, id)| id)
+        .collect()
+}
+
+/// Song `step` places away from `current` in `order`, wrapping around the ends. `None` if
+/// `current` is no longer in the order, for example because it was filtered out
+pub fn adjacent_song<'a>(order: &'a [SongId], current: &SongId, step: i32) -> Option<&'a SongId> {
+    let index = order.iter().position(|id| id == current)?;
+    let len = order.len() as i64;
+    order.get((index as i64 + step as i64).rem_euclid(len) as usize)
+}
+
+/// Index of the difficulty closest to `difficulty`, for playing the same difficulty of another
+/// song
+pub fn closest_difficulty(song: &Song, difficulty: u8) -> Option<usize> {
+    song.difficulties
+        .read()
+        .expect("Lock error")
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, d)| d.difficulty.abs_diff(difficulty))
+        .map(|(i, _)| i)
+}
+
+/// Splits songs in order into runs with the same [`sort_group`]
+pub fn group_songs<'a>(
+    sort: SongSortType,
+    songs: impl IntoIterator<Item = &'a Song>,
+) -> Vec<(String, Vec<SongId>)> {
+    let mut groups: Vec<(String, Vec<SongId>)> = vec![];
+    for song in songs {
+        let Some(title) = sort_group(sort, song) else {
+            continue;
+        };
+        match groups.last_mut() {
+            Some((last, ids)) if *last == title => ids.push(song.id.clone()),
+            _ => groups.push((title, vec![song.id.clone()])),
+        }
+    }
+    groups
+}
+
 #[derive(
     Debug, Clone, Serialize, Deserialize, Default, schemars::JsonSchema, PartialEq, specta::Type,
 )]
@@ -154,6 +279,19 @@ pub enum SongFilterType {
     None,
     Folder(String),
     Collection(String),
+    Effector(String),
+    /// Online songs uploaded in the current calendar month or year
+    Uploaded(UploadPeriod),
+    /// The last [`RECENTLY_PLAYED_LEN`] played songs, newest first
+    RecentlyPlayed,
+}
+
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, schemars::JsonSchema, PartialEq, Eq, specta::Type,
+)]
+pub enum UploadPeriod {
+    Month,
+    Year,
 }
 
 impl Display for SongFilterType {
@@ -162,6 +300,14 @@ impl Display for SongFilterType {
             SongFilterType::None => formatter.write_str("All"),
             SongFilterType::Folder(f) => formatter.write_fmt(format_args!("Folder: {f}")),
             SongFilterType::Collection(c) => formatter.write_fmt(format_args!("Collection: {c}")),
+            SongFilterType::Effector(e) => formatter.write_fmt(format_args!("Effector: {e}")),
+            SongFilterType::Uploaded(UploadPeriod::Month) => {
+                formatter.write_str("Uploaded this month")
+            }
+            SongFilterType::Uploaded(UploadPeriod::Year) => {
+                formatter.write_str("Uploaded this year")
+            }
+            SongFilterType::RecentlyPlayed => formatter.write_str("Recently played"),
         }
     }
 }
@@ -311,8 +457,47 @@ impl SongDiffId {
 
 impl TealData for SongDiffId {}
 pub type PreviewResult = anyhow::Result<(Box<dyn Source<Item = f32> + Send>, Duration, Duration)>;
-pub type LoadSongFn =
-    Box<dyn FnOnce() -> anyhow::Result<(Chart, Box<dyn rodio::Source<Item = f32> + Send>)> + Send>;
+pub type LoadSongFn = Box<
+    dyn FnOnce(
+            std::sync::mpsc::Sender<LoadProgress>,
+        ) -> anyhow::Result<(Chart, Box<dyn rodio::Source<Item = f32> + Send>)>
+        + Send,
+>;
+
+/// Progress of a song library scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ScanProgress {
+    /// Chart files found so far, grows while folders are being read
+    pub discovered: usize,
+    pub parsed: usize,
+    pub failed: usize,
+    /// Charts that loaded but whose audio couldn't be found
+    pub missing_audio: usize,
+    pub done: bool,
+}
+
+/// Song folder whose charts were all found in another folder before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateSong {
+    pub id: SongId,
+    pub path: std::path::PathBuf,
+    /// Folder holding the copies that are listed instead
+    pub original: std::path::PathBuf,
+}
+
+impl Display for ScanProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.done {
+            return Ok(());
+        }
+        write!(
+            f,
+            "Scanning {}/{}…",
+            self.parsed + self.failed,
+            self.discovered
+        )
+    }
+}
 
 pub trait SongProvider: Send {
     fn subscribe(&mut self) -> bus::BusReader<SongProviderEvent>;
@@ -324,18 +509,249 @@ pub trait SongProvider: Send {
     fn set_current_index(&mut self, index: u64);
     fn load_song(&self, id: &SongDiffId) -> anyhow::Result<LoadSongFn>;
     fn add_score(&self, id: SongDiffId, score: Score);
-    /// Returns: `(music, skip, duration)`
+    /// Returns: `(music, skip, duration)` of the preview of a song
     fn get_preview(&self, id: &SongId) -> Promise<PreviewResult>;
+    /// Returns: `(music, skip, duration)` of the preview of a difficulty, which may use its own
+    /// audio file or region. Providers without per difficulty previews play the song preview.
+    fn get_diff_preview(&self, id: &SongDiffId) -> Promise<PreviewResult> {
+        match id.get_song() {
+            Some(song) => self.get_preview(song),
+            None => Promise::from_ready(Err(anyhow::anyhow!("No song to preview"))),
+        }
+    }
+    /// Identifies the audio and region `get_diff_preview` plays for a difficulty, difficulties
+    /// with the same key have the same preview
+    fn preview_key(&self, id: &SongDiffId) -> Option<u64> {
+        id.get_song().map(SongId::as_u64)
+    }
     fn get_all(&self) -> (Vec<Arc<Song>>, Vec<SongId>);
     fn refresh(&mut self) {}
+    /// Chart data the provider keeps on disk, for providers that have any
+    fn song_cache(&self) -> Option<Box<dyn crate::maintenance::ManagedCache>> {
+        None
+    }
+    /// Analyzed preview regions, for providers that analyze them
+    fn preview_cache(&self) -> Option<Box<dyn crate::maintenance::ManagedCache>> {
+        None
+    }
+    /// Reads the editable metadata of a difficulty
+    fn get_metadata(&self, _id: &SongDiffId) -> anyhow::Result<ChartMetadata> {
+        bail!("Metadata editing not supported")
+    }
+    /// Writes new metadata to the chart file of a difficulty
+    fn set_metadata(&mut self, _id: &SongDiffId, _meta: ChartMetadata) -> anyhow::Result<()> {
+        bail!("Metadata editing not supported")
+    }
+    /// Progress of the running or last library scan, polled by the song select every tick
+    fn scan_progress(&self) -> Option<ScanProgress> {
+        None
+    }
+    /// Chart files that failed to load in the last library scan
+    fn scan_failures(&self) -> Vec<std::path::PathBuf> {
+        vec![]
+    }
+    /// Charts without any usable audio file found in the last library scan
+    fn scan_missing_audio(&self) -> Vec<std::path::PathBuf> {
+        vec![]
+    }
+    /// Song folders that only hold copies of charts from other folders
+    fn duplicate_songs(&self) -> Vec<DuplicateSong> {
+        vec![]
+    }
+    /// File a difficulty is loaded from, for providers that read charts from disk
+    fn chart_path(&self, _id: &SongDiffId) -> Option<std::path::PathBuf> {
+        None
+    }
+    /// Chart files of all difficulties, for providers that read charts from disk
+    fn chart_paths(&self) -> HashMap<DiffId, std::path::PathBuf> {
+        HashMap::new()
+    }
 }
 
 pub trait ScoreProvider {
     fn subscribe(&mut self) -> bus::BusReader<ScoreProviderEvent>;
-    fn get_scores(&mut self, id: &SongDiffId) -> Vec<Score>;
+    /// All stored scores of the chart with the given hash
+    fn get_scores(&self, chart_hash: &str) -> anyhow::Result<Vec<Score>>;
     fn insert_score(&mut self, id: &SongDiffId, score: Score) -> anyhow::Result<()>;
     fn init_scores(&self, songs: &mut dyn Iterator<Item = &Arc<Song>>) -> anyhow::Result<()>;
+    /// Scores inserted at or after `timestamp`, to catch up on events sent while not subscribed
+    fn scores_since(&self, _timestamp: i32) -> Vec<(SongDiffId, Score)> {
+        vec![]
+    }
 }
 
-pub use files::FileSongProvider;
+pub use files::{parse_chart, FileSongProvider, OrphanedScores};
+#[cfg(feature = "folder-provider")]
+pub use folder::FolderSongProvider;
 pub use nautica::NauticaSongProvider;
+pub use registry::{create_provider, provider_names, register_provider, ProviderFactory};
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{
+        adjacent_song, group_songs, recently_played, DiffId, ScoreBacklog, SongDiffId, SongId,
+        SongSortType, RECENTLY_PLAYED_LEN,
+    };
+    use crate::{
+        results::Score,
+        songselect::{Difficulty, Song},
+    };
+
+    fn score(timestamp: i32, score: i32) -> Score {
+        Score {
+            timestamp,
+            score,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn missed_scores() {
+        let diff_id = DiffId(SongId::StringId("hash".into()));
+        let id = SongDiffId::DiffOnly(diff_id.clone());
+        let mut backlog = ScoreBacklog::default();
+        let mut diff = Difficulty {
+            jacket_path: PathBuf::new(),
+            level: 1,
+            difficulty: 0,
+            id: diff_id.clone(),
+            effector: String::new(),
+            top_badge: 0,
+            scores: vec![],
+            hash: None,
+            illustrator: String::new(),
+            radar: None,
+            duration: None,
+            duration_string: None,
+            last_played: None,
+            audio_broken: false,
+            density_strip_path: None,
+            jacket_loaded: false,
+        };
+
+        // Seen by the song select before it was closed
+        backlog.push(id.clone(), score(100, 9_000_000));
+        diff.add_score(score(100, 9_000_000));
+        let last_seen = 100;
+
+        // Inserted while the song select was closed
+        backlog.push(id.clone(), score(200, 9_500_000));
+
+        // Song select opened again
+        for (missed_id, missed) in backlog.since(last_seen) {
+            assert_eq!(missed_id.get_diff(), Some(&diff_id));
+            diff.add_score(missed);
+        }
+
+        let timestamps: Vec<_> = diff.scores.iter().map(|s| s.timestamp).collect();
+        assert_eq!(timestamps, [200, 100]);
+    }
+
+    #[test]
+    fn backlog_is_bounded() {
+        let mut backlog = ScoreBacklog::default();
+        for i in 0..ScoreBacklog::LEN as i32 * 2 {
+            backlog.push(SongDiffId::Missing, score(i, 0));
+        }
+
+        let scores = backlog.since(0);
+        assert_eq!(scores.len(), ScoreBacklog::LEN);
+        assert_eq!(scores[0].1.timestamp, ScoreBacklog::LEN as i32);
+    }
+
+    #[test]
+    fn title_groups() {
+        let songs: Vec<_> = ["alpha", "Apple", "2nd", "Beta", "alt"]
+            .iter()
+            .enumerate()
+            .map(|(i, title)| Song {
+                title: title.to_string(),
+                id: SongId::IntId(i as i64),
+                ..Default::default()
+            })
+            .collect();
+
+        let groups: Vec<_> = group_songs(SongSortType::Title, &songs)
+            .into_iter()
+            .map(|(title, ids)| (title, ids.len()))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                ("A".to_string(), 2),
+                ("#".to_string(), 1),
+                ("B".to_string(), 1),
+                ("A".to_string(), 1)
+            ]
+        );
+        assert!(group_songs(SongSortType::Date, &songs).is_empty());
+    }
+
+    #[test]
+    fn recent_songs() {
+        let played = |id: i64, timestamps: &[i32]| {
+            let song = Song {
+                id: SongId::IntId(id),
+                ..Default::default()
+            };
+            let mut diff = Difficulty {
+                jacket_path: PathBuf::new(),
+                level: 1,
+                difficulty: 0,
+                id: DiffId(SongId::IntId(id)),
+                effector: String::new(),
+                top_badge: 0,
+                scores: vec![],
+                hash: None,
+                illustrator: String::new(),
+                radar: None,
+                duration: None,
+                duration_string: None,
+                last_played: None,
+                audio_broken: false,
+                density_strip_path: None,
+                jacket_loaded: false,
+            };
+            for t in timestamps {
+                diff.add_score(score(*t, *t));
+            }
+            song.difficulties.write().unwrap().push(diff);
+            song
+        };
+
+        let songs = [played(0, &[300, 100]), played(1, &[]), played(2, &[200])];
+        assert_eq!(
+            recently_played(&songs),
+            [SongId::IntId(0), SongId::IntId(2)]
+        );
+
+        let songs: Vec<_> = (0..RECENTLY_PLAYED_LEN as i64 + 10)
+            .map(|i| played(i, &[i as i32]))
+            .collect();
+        let recent = recently_played(&songs);
+        assert_eq!(recent.len(), RECENTLY_PLAYED_LEN);
+        assert_eq!(recent[0], SongId::IntId(RECENTLY_PLAYED_LEN as i64 + 9));
+    }
+
+    #[test]
+    fn adjacent_songs() {
+        let order = [SongId::IntId(1), SongId::IntId(2), SongId::IntId(3)];
+        assert_eq!(
+            adjacent_song(&order, &SongId::IntId(2), 1),
+            Some(&SongId::IntId(3))
+        );
+        assert_eq!(
+            adjacent_song(&order, &SongId::IntId(3), 1),
+            Some(&SongId::IntId(1))
+        );
+        assert_eq!(
+            adjacent_song(&order, &SongId::IntId(1), -1),
+            Some(&SongId::IntId(3))
+        );
+        // Removed or filtered out while playing
+        assert_eq!(adjacent_song(&order, &SongId::IntId(4), 1), None);
+        assert_eq!(adjacent_song(&[], &SongId::IntId(1), 1), None);
+    }
+}
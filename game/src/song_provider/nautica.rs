@@ -5,10 +5,11 @@ use std::{
     io::{BufReader, BufWriter, Read},
     path::PathBuf,
     str::FromStr,
-    sync::{mpsc::Sender, Arc},
-    time::Duration,
+    sync::{mpsc::Sender, Arc, Mutex},
+    time::{Duration, Instant},
 };
 
+use chrono::{DateTime, Datelike, NaiveDateTime, Utc};
 use di::RefMut;
 use futures::AsyncWriteExt;
 use itertools::Itertools;
@@ -24,13 +25,27 @@ use crate::{
     worker_service::WorkerService,
 };
 
-use super::{DiffId, LoadSongFn, SongDiffId, SongFilter, SongId, SongProvider, SongProviderEvent};
+use super::{
+    decode, decode_file, group_songs, open_audio, DiffId, LoadProgress, LoadSongFn, PanicSafe,
+    SongDiffId, SongFilter, SongId, SongProvider, SongProviderEvent, SongSort, SongSortType,
+    SortDir, UploadPeriod,
+};
 use anyhow::{anyhow, bail, ensure, Result};
 use kson::Ksh;
 use poll_promise::Promise;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use uuid::Uuid;
 
+const SONGS_URL: &str = "https://ksm.dev/app/songs";
+/// Attempts made for each request before giving up on it
+const REQUEST_ATTEMPTS: u32 = 3;
+/// Delay before the second attempt of a request, doubled for each one after it
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Time between connectivity checks while the API can't be reached
+const OFFLINE_RETRY: Duration = Duration::from_secs(30);
+/// Pages of the unfiltered listing kept on disk to browse while offline
+const CACHED_PAGES: usize = 10;
+
 #[derive(Serialize, Deserialize)]
 pub struct NauticaSongs {
     pub(crate) data: Vec<Datum>,
@@ -43,6 +58,22 @@ struct LocalData {
     songs: HashMap<Uuid, Datum>,
 }
 
+/// First pages of the listing from the last time the API could be reached
+#[derive(Default, Serialize, Deserialize)]
+struct ListingCache {
+    pages: Vec<Vec<Datum>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Connection {
+    Online,
+    /// `url` failed and is requested again at `retry_at`
+    Offline {
+        retry_at: Instant,
+        url: String,
+    },
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct NauticaSong {
     pub(crate) data: Datum,
@@ -128,6 +159,17 @@ pub struct Meta {
 }
 
 impl Datum {
+    /// Upload time, the listing uses ISO 8601 but older cache entries may not
+    fn upload_date(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.uploaded_at)
+            .map(|d| d.with_timezone(&Utc))
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(&self.uploaded_at, "%Y-%m-%d %H:%M:%S")
+                    .map(|d| d.and_utc())
+            })
+            .ok()
+    }
+
     fn as_song(&self) -> Song {
         let Datum {
             id,
@@ -147,7 +189,7 @@ impl Datum {
             jacket_url,
             preview_url: _,
             cdn_download_url: _,
-            user: _,
+            user,
             charts,
             tags: _,
         } = self;
@@ -175,10 +217,16 @@ impl Datum {
                     .collect_vec()
                     .into(),
             ),
+            uploader: Some(user.name.clone()),
         }
     }
 }
 
+/// Whether `date` is in the same calendar month or year as `now`
+fn uploaded_within(date: DateTime<Utc>, period: UploadPeriod, now: DateTime<Utc>) -> bool {
+    date.year() == now.year() && (period == UploadPeriod::Year || date.month() == now.month())
+}
+
 impl Chart {
     fn as_diff(&self, jacket_path: PathBuf) -> Difficulty {
         let Chart {
@@ -203,19 +251,37 @@ impl Chart {
             scores: vec![],
             hash: None,
             illustrator: String::new(),
+            radar: None,
+            duration: None,
+            duration_string: None,
+            last_played: None,
+            audio_broken: false,
+            density_strip_path: None,
+            jacket_loaded: false,
         }
     }
 }
 
 pub struct NauticaSongProvider {
-    next: Option<Promise<Result<NauticaSongs>>>,
+    /// Page being requested and its url
+    next: Option<(String, Promise<Result<NauticaSongs>>)>,
     events: VecDeque<SongProviderEvent>,
     all_songs: Vec<Arc<Song>>,
+    /// Upload times of the listed songs, used for sorting and the upload filters
+    uploaded_at: HashMap<SongId, DateTime<Utc>>,
     next_url: String,
     bus: bus::Bus<SongProviderEvent>,
     filter: SongFilter,
+    sort: SongSort,
     query: HashMap<&'static str, String>,
     local_data: LocalData,
+    listing_cache: ListingCache,
+    /// The listed songs come from `listing_cache`
+    serving_cache: bool,
+    connection: Connection,
+    /// Songs that were played while the API was unreachable, downloaded once it's back
+    queued_downloads: Mutex<Vec<Uuid>>,
+    status: String,
     song_loaded: (
         std::sync::mpsc::Sender<Datum>,
         std::sync::mpsc::Receiver<Datum>,
@@ -229,10 +295,52 @@ impl Debug for NauticaSongProvider {
     }
 }
 
+/// Delays before each retry of a request
+fn retry_delays() -> impl Iterator<Item = Duration> {
+    (0..REQUEST_ATTEMPTS - 1).map(|i| RETRY_DELAY * 2u32.pow(i))
+}
+
+async fn get_json<T: DeserializeOwned>(url: &str) -> Result<T> {
+    let mut delays = retry_delays();
+    loop {
+        let result = async {
+            reqwest::get(url)
+                .await?
+                .error_for_status()?
+                .json::<T>()
+                .await
+        };
+        match (result.await, delays.next()) {
+            (Ok(value), _) => return Ok(value),
+            (Err(e), Some(delay)) => {
+                log::debug!("Nautica request failed, retrying in {delay:?}: {e}");
+                tokio::time::sleep(delay).await;
+            }
+            (Err(e), None) => return Err(e.into()),
+        }
+    }
+}
+
+fn blocking_get(url: &str) -> Result<reqwest::blocking::Response> {
+    let mut delays = retry_delays();
+    loop {
+        match (
+            reqwest::blocking::get(url).and_then(|r| r.error_for_status()),
+            delays.next(),
+        ) {
+            (Ok(response), _) => return Ok(response),
+            (Err(e), Some(delay)) => {
+                log::debug!("Nautica request failed, retrying in {delay:?}: {e}");
+                std::thread::sleep(delay);
+            }
+            (Err(e), None) => return Err(e.into()),
+        }
+    }
+}
+
 async fn next_songs(path: String) -> Result<NauticaSongs> {
     log::info!("Getting more nautica songs: {}", path);
-    //TODO: Async requests
-    let nautica_songs = reqwest::get(&path).await?.json::<NauticaSongs>().await?;
+    let nautica_songs = get_json::<NauticaSongs>(&path).await?;
     for x in &nautica_songs.data {
         let mut song_path = project_dirs().cache_dir().to_path_buf();
         song_path.push(x.id.hyphenated().to_string());
@@ -282,36 +390,114 @@ impl NauticaSongProvider {
             .ok()
             .and_then(|x| serde_json::from_str(&x).ok())
             .unwrap_or_default();
+        let listing_cache = std::fs::read_to_string(listing_cache_path())
+            .ok()
+            .and_then(|x| serde_json::from_str(&x).ok())
+            .unwrap_or_default();
 
         Self {
             next: None,
             events: VecDeque::new(),
             all_songs: vec![],
-            next_url: "https://ksm.dev/app/songs".into(),
+            uploaded_at: HashMap::new(),
+            next_url: SONGS_URL.into(),
             bus: bus::Bus::new(32),
             filter: SongFilter::new(SongFilterType::None, 0),
+            sort: SongSort::new(SongSortType::Date, SortDir::Desc),
             query: HashMap::new(),
             local_data,
+            listing_cache,
+            serving_cache: false,
+            connection: Connection::Online,
+            queued_downloads: Mutex::new(vec![]),
+            status: String::new(),
             song_loaded: std::sync::mpsc::channel(),
             async_worker,
         }
     }
 
-    fn query_changed(&mut self) {
+    fn order(&self) -> Vec<SongId> {
+        let uploaded = |s: &Song| self.uploaded_at.get(&s.id).copied();
+        let effector = |s: &Song| {
+            let diffs = s.difficulties.read().expect("Lock error");
+            diffs.first().map(|d| d.effector.to_lowercase())
+        };
+        let level = |s: &Song| {
+            let diffs = s.difficulties.read().expect("Lock error");
+            diffs.iter().map(|d| d.level).max()
+        };
+
+        let mut songs = self.all_songs.iter().map(|s| &**s).collect_vec();
+        match self.sort.sort_type {
+            SongSortType::Title => songs.sort_by_key(|s| s.title.to_lowercase()),
+            SongSortType::Artist => songs.sort_by_key(|s| s.artist.to_lowercase()),
+            SongSortType::Effector => songs.sort_by_key(|s| effector(s)),
+            SongSortType::Level => songs.sort_by_key(|s| level(s)),
+            SongSortType::Date => songs.sort_by_key(|s| uploaded(s)),
+            SongSortType::LastPlayed => songs.sort_by_key(|s| s.last_played()),
+            SongSortType::Score => {}
+        }
+        if self.sort.direction == SortDir::Desc {
+            songs.reverse();
+        }
+        songs.into_iter().map(|s| s.id.clone()).collect()
+    }
+
+    fn send_order(&mut self) {
+        let order = self.order();
+        let groups = group_songs(
+            self.sort.sort_type,
+            order
+                .iter()
+                .filter_map(|id| self.all_songs.iter().find(|s| s.id == *id))
+                .map(|s| &**s),
+        );
+        self.events
+            .push_back(SongProviderEvent::OrderChanged(order));
+        self.events
+            .push_back(SongProviderEvent::GroupsChanged(groups));
+    }
+
+    /// Lists the songs that pass the upload filter
+    fn add_songs<'a>(&mut self, data: impl IntoIterator<Item = &'a Datum>) {
+        let now = Utc::now();
+        let mut new_songs = vec![];
+        for datum in data {
+            let uploaded_at = datum.upload_date();
+            if let SongFilterType::Uploaded(period) = self.filter.filter_type {
+                if !uploaded_at.is_some_and(|d| uploaded_within(d, period, now)) {
+                    continue;
+                }
+            }
+
+            let song = Arc::new(datum.as_song());
+            if let Some(uploaded_at) = uploaded_at {
+                self.uploaded_at.insert(song.id.clone(), uploaded_at);
+            }
+            new_songs.push(song);
+        }
+
+        self.all_songs.extend(new_songs.iter().cloned());
+        self.events
+            .push_back(SongProviderEvent::SongsAdded(new_songs));
+        self.send_order();
+    }
+
+    fn clear_songs(&mut self) {
         let old_songs = std::mem::take(&mut self.all_songs);
+        self.uploaded_at.clear();
+        self.serving_cache = false;
         self.events.push_back(SongProviderEvent::SongsRemoved(
             old_songs.into_iter().map(|x| x.id.clone()).collect(),
         ));
-        if let SongFilterType::Collection(c) = &self.filter.filter_type {
-            self.all_songs = self
-                .local_data
-                .songs
-                .iter()
-                .map(|x| Arc::new(x.1.as_song()))
-                .collect();
+    }
 
-            self.events
-                .push_back(SongProviderEvent::SongsAdded(self.all_songs.clone()));
+    fn query_changed(&mut self) {
+        self.clear_songs();
+        if matches!(self.filter.filter_type, SongFilterType::Collection(_)) {
+            let local_data = std::mem::take(&mut self.local_data);
+            self.add_songs(local_data.songs.values());
+            self.local_data = local_data;
         } else {
             let query = self
                 .query
@@ -319,36 +505,150 @@ impl NauticaSongProvider {
                 .map(|x| format!("{}={}", x.0, x.1))
                 .join("&");
             self.next_url = if query.is_empty() {
-                "https://ksm.dev/app/songs".to_owned()
+                SONGS_URL.to_owned()
             } else {
-                format!("https://ksm.dev/app/songs?{}", query)
+                format!("{SONGS_URL}?{query}")
             };
-            self.next = Some(Promise::spawn_async(next_songs(self.next_url.clone())));
+
+            if let Connection::Offline { url, .. } = &mut self.connection {
+                // Checked again with the new query once the retry is due
+                *url = self.next_url.clone();
+                self.serve_cache();
+            } else {
+                self.request(self.next_url.clone());
+            }
+        }
+    }
+
+    fn request(&mut self, url: String) {
+        if url.is_empty() {
+            return;
+        }
+        self.next = Some((url.clone(), Promise::spawn_async(next_songs(url))));
+    }
+
+    fn set_status(&mut self, status: String) {
+        if status != self.status {
+            self.status = status.clone();
+            self.events
+                .push_back(SongProviderEvent::StatusUpdate(status));
+        }
+    }
+
+    fn page_loaded(&mut self, url: String, songs: NauticaSongs) {
+        if self.connection != Connection::Online {
+            log::info!("Nautica is reachable again");
+            self.connection = Connection::Online;
+            self.set_status(String::new());
+            let queued = std::mem::take(&mut *self.queued_downloads.lock().expect("Lock error"));
+            for id in queued {
+                self.download_queued(id);
+            }
+        }
+        if self.serving_cache {
+            self.clear_songs();
+        }
+
+        self.add_songs(&songs.data);
+        self.next_url = songs.links.next.unwrap_or_default();
+
+        if !self.query.is_empty() {
+            return;
+        }
+        if url == SONGS_URL {
+            self.listing_cache.pages.clear();
+        }
+        if self.listing_cache.pages.len() < CACHED_PAGES {
+            self.listing_cache.pages.push(songs.data);
+            if let Ok(json) = serde_json::to_string(&self.listing_cache) {
+                self.write_cache(listing_cache_path(), json);
+            }
         }
     }
+
+    fn request_failed(&mut self, url: String, e: anyhow::Error) {
+        if self.connection == Connection::Online {
+            // Reported once, requests are expected to fail until the API is reachable again
+            warn!("Nautica is unreachable: {e}");
+        } else {
+            log::debug!("Nautica is still unreachable: {e}");
+        }
+
+        self.connection = Connection::Offline {
+            retry_at: Instant::now() + OFFLINE_RETRY,
+            url,
+        };
+        if self.all_songs.is_empty() {
+            self.serve_cache();
+        }
+    }
+
+    /// Lists the cached pages matching the current query, read-only until the API is back
+    fn serve_cache(&mut self) {
+        let search = self.query.get("q").map(|q| q.to_lowercase());
+        let level = self.query.get("levels").and_then(|l| l.parse::<i64>().ok());
+        let cache = std::mem::take(&mut self.listing_cache);
+        self.add_songs(cache.pages.iter().flatten().filter(|datum| {
+            search.as_ref().map_or(true, |q| {
+                datum.title.to_lowercase().contains(q) || datum.artist.to_lowercase().contains(q)
+            }) && level.map_or(true, |l| datum.charts.iter().any(|c| c.level == l))
+        }));
+        self.listing_cache = cache;
+        self.serving_cache = true;
+    }
+
+    /// Downloads a song that was played while offline, it's loaded from the cache afterwards
+    fn download_queued(&self, id: Uuid) {
+        let on_loaded = self.song_loaded.0.clone();
+        self.async_worker
+            .read()
+            .expect("Lock error")
+            .run(async move {
+                match download_zip(id).await {
+                    Ok(nautica) => {
+                        log::info!("Downloaded {} after reconnecting", nautica.title);
+                        _ = on_loaded.send(nautica);
+                    }
+                    Err(e) => warn!("Could not download queued song: {e}"),
+                }
+            })
+    }
+
+    fn write_cache(&self, path: PathBuf, json: String) {
+        self.async_worker.read().unwrap().run(async move {
+            use tokio::io::*;
+            let Ok(mut file) = tokio::fs::File::create(&path).await else {
+                warn!("Could not create nautica cache file {}", path.display());
+                return;
+            };
+
+            if let Some(e) = file.write_all(json.as_bytes()).await.err() {
+                warn!("Could not write nautica cache file: {e}");
+            }
+        })
+    }
 }
 
 impl WorkerService for NauticaSongProvider {
     fn update(&mut self) {
-        if let Some(next) = self.next.take() {
+        if let Some((url, next)) = self.next.take() {
             match next.try_take() {
-                Ok(Ok(songs)) => {
-                    let new_songs = songs
-                        .data
-                        .iter()
-                        .map(|d| Arc::new(d.as_song()))
-                        .collect_vec();
-
-                    self.all_songs.append(&mut new_songs.clone());
-                    self.next_url = songs.links.next.unwrap_or_default();
-                    self.events
-                        .push_back(SongProviderEvent::SongsAdded(new_songs));
-                }
-                Ok(Err(e)) => log::error!("{}", e),
-                Err(next) => self.next = Some(next),
+                Ok(Ok(songs)) => self.page_loaded(url, songs),
+                Ok(Err(e)) => self.request_failed(url, e),
+                Err(next) => self.next = Some((url, next)),
+            }
+        } else if let Connection::Offline { retry_at, url } = &self.connection {
+            let remaining = retry_at.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                let url = url.clone();
+                self.set_status("Nautica unreachable, reconnecting".into());
+                self.request(url);
+            } else {
+                self.set_status(format!(
+                    "Nautica unreachable, retrying in {}s",
+                    remaining.as_secs() + 1
+                ));
             }
-        } else {
-            //TODO: Check scroll position and request more songs
         }
 
         if self.bus.rx_count() > 0 {
@@ -361,18 +661,7 @@ impl WorkerService for NauticaSongProvider {
             self.local_data.songs.insert(loaded.id, loaded);
 
             if let Ok(local_data_json) = serde_json::to_string(&self.local_data) {
-                self.async_worker.read().unwrap().run(async move {
-                    use tokio::io::*;
-                    let path = cache_path();
-                    let Ok(mut file) = tokio::fs::File::create(&path).await else {
-                        warn!("Could not create nautica cache file");
-                        return;
-                    };
-
-                    if let Some(e) = file.write_all(local_data_json.as_bytes()).await.err() {
-                        warn!("Could not write nautica cache file: {e}");
-                    }
-                })
+                self.write_cache(cache_path(), local_data_json);
             }
         }
     }
@@ -384,11 +673,19 @@ fn cache_path() -> PathBuf {
     path
 }
 
+fn listing_cache_path() -> PathBuf {
+    let mut path = project_dirs().cache_dir().to_path_buf();
+    path.push("nautica_listing.json");
+    path
+}
+
 impl SongProvider for NauticaSongProvider {
     fn get_available_filters(&self) -> Vec<super::SongFilterType> {
         vec![
             SongFilterType::None,
             SongFilterType::Collection("Played".into()),
+            SongFilterType::Uploaded(UploadPeriod::Month),
+            SongFilterType::Uploaded(UploadPeriod::Year),
         ]
     }
 
@@ -401,8 +698,10 @@ impl SongProvider for NauticaSongProvider {
         self.query_changed();
     }
 
-    fn set_sort(&mut self, _sort: super::SongSort) {
-        todo!()
+    fn set_sort(&mut self, sort: super::SongSort) {
+        // Only the loaded pages are sorted, the listing itself is always newest first
+        self.sort = sort;
+        self.send_order();
     }
 
     fn set_filter(&mut self, filter: super::SongFilter) {
@@ -433,15 +732,13 @@ impl SongProvider for NauticaSongProvider {
             let diffs = &mut song.difficulties.write().expect("Lock error");
             let diff = diffs.iter_mut().find(|x| x.id == *diff);
             if let Some(diff) = diff {
-                diff.top_badge = diff.top_badge.max(score.badge);
-                diff.scores.push(score);
-                diff.scores.sort_by_key(|x| -x.score);
+                diff.add_score(score);
             }
         }
     }
 
     fn set_current_index(&mut self, index: u64) {
-        if self.next.is_some() {
+        if self.next.is_some() || self.connection != Connection::Online {
             return;
         }
 
@@ -452,7 +749,7 @@ impl SongProvider for NauticaSongProvider {
             .find(|x| x.1.id.as_u64() == index)
         {
             if i > self.all_songs.len().saturating_sub(10) {
-                self.next = Some(Promise::spawn_async(next_songs(self.next_url.clone())));
+                self.request(self.next_url.clone());
             }
         }
     }
@@ -483,6 +780,14 @@ impl SongProvider for NauticaSongProvider {
             .find(|x| x.id == *diff_id)
             .ok_or(anyhow!("diff id not in songs difficulties"))?;
 
+        if self.connection != Connection::Online && !song_zip_path(song_uuid).exists() {
+            let mut queued = self.queued_downloads.lock().expect("Lock error");
+            if !queued.contains(&song_uuid) {
+                queued.push(song_uuid);
+            }
+            bail!("Nautica is unreachable, the song will be downloaded once it's back");
+        }
+
         download_song(song_uuid, diff.difficulty, self.song_loaded.0.clone())
     }
 
@@ -497,6 +802,7 @@ impl SongProvider for NauticaSongProvider {
         )>,
     > {
         let id = id.clone();
+        let online = self.connection == Connection::Online;
         poll_promise::Promise::spawn_async(async move {
             let SongId::StringId(song_id) = id else {
                 bail!("Unsupported id type")
@@ -512,17 +818,11 @@ impl SongProvider for NauticaSongProvider {
             song_path.push("preview");
 
             let source: Box<dyn Source<Item = f32> + Send> = if song_path.exists() {
-                Box::new(rodio::Decoder::new(std::fs::File::open(song_path)?)?.convert_samples())
+                decode_file(&song_path)?
             } else {
-                let NauticaSong { data: nautica } = reqwest::get(format!(
-                    "https://ksm.dev/app/songs/{}",
-                    song_uuid.as_hyphenated()
-                ))
-                .await
-                .expect("Failed to get song")
-                .json()
-                .await
-                .expect("Failed to parse nautica song");
+                ensure!(online, "Nautica is unreachable");
+                let NauticaSong { data: nautica } =
+                    get_json(&format!("{SONGS_URL}/{}", song_uuid.as_hyphenated())).await?;
 
                 let Some(preview_url) = nautica.preview_url else {
                     bail!("No preview url")
@@ -530,9 +830,12 @@ impl SongProvider for NauticaSongProvider {
 
                 let mut bytes = reqwest::get(preview_url).await?.bytes().await?;
 
-                std::fs::write(song_path, &bytes)?;
+                std::fs::write(&song_path, &bytes)?;
 
-                Box::new(rodio::Decoder::new(std::io::Cursor::new(bytes))?.convert_samples())
+                let name = song_path.display().to_string();
+                Box::new(PanicSafe::new(
+                    decode(std::io::Cursor::new(bytes), &name)?.convert_samples(),
+                ))
             };
             Ok((
                 source as Box<dyn Source<Item = f32> + Send>,
@@ -543,22 +846,32 @@ impl SongProvider for NauticaSongProvider {
     }
 
     fn subscribe(&mut self) -> bus::BusReader<SongProviderEvent> {
-        if self.next.is_none() {
-            self.next = Some(Promise::spawn_async(next_songs(self.next_url.clone())));
+        if self.next.is_none() && self.connection == Connection::Online {
+            self.request(self.next_url.clone());
         }
 
         self.bus.add_rx()
     }
 
     fn get_all(&self) -> (Vec<Arc<Song>>, Vec<SongId>) {
-        (
-            self.all_songs.clone(),
-            self.all_songs.iter().map(|x| x.id.clone()).collect(),
-        )
+        (self.all_songs.clone(), self.order())
     }
 
     fn get_available_sorts(&self) -> Vec<super::SongSort> {
-        vec![]
+        [
+            SongSortType::Date,
+            SongSortType::Title,
+            SongSortType::Artist,
+            SongSortType::Level,
+        ]
+        .into_iter()
+        .flat_map(|t| {
+            [
+                SongSort::new(t, SortDir::Desc),
+                SongSort::new(t, SortDir::Asc),
+            ]
+        })
+        .collect()
     }
 
     fn refresh(&mut self) {
@@ -566,35 +879,52 @@ impl SongProvider for NauticaSongProvider {
     }
 }
 
-fn download_song(id: Uuid, diff: u8, on_loaded: Sender<Datum>) -> anyhow::Result<LoadSongFn> {
-    Ok(Box::new(move || {
-        let mut song_path = project_dirs().cache_dir().to_path_buf();
+fn song_zip_path(id: Uuid) -> PathBuf {
+    let mut path = project_dirs().cache_dir().to_path_buf();
+    path.push(id.hyphenated().to_string());
+    path.push("data.zip");
+    path
+}
 
-        song_path.push(id.hyphenated().to_string());
-        song_path.push("data.zip");
+async fn download_zip(id: Uuid) -> Result<Datum> {
+    let NauticaSong { data: nautica } =
+        get_json(&format!("{SONGS_URL}/{}", id.as_hyphenated())).await?;
+    let data = reqwest::get(&nautica.cdn_download_url)
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    tokio::fs::write(song_zip_path(id), data).await?;
+    Ok(nautica)
+}
+
+fn download_song(id: Uuid, diff: u8, on_loaded: Sender<Datum>) -> anyhow::Result<LoadSongFn> {
+    Ok(Box::new(move |progress: Sender<LoadProgress>| {
+        let song_path = song_zip_path(id);
 
         if song_path.exists() {
             let file = File::open(song_path)?;
             let file = BufReader::new(file);
-            return song_from_zip(file, diff);
+            return song_from_zip(file, diff, &progress);
         }
 
         let NauticaSong { data: nautica } =
-            reqwest::blocking::get(format!("https://ksm.dev/app/songs/{}", id.as_hyphenated()))?
-                .json()?;
-        let mut data = reqwest::blocking::get(&nautica.cdn_download_url)?.bytes()?;
+            blocking_get(&format!("{SONGS_URL}/{}", id.as_hyphenated()))?.json()?;
+        let mut data = blocking_get(&nautica.cdn_download_url)?.bytes()?;
         std::fs::write(&song_path, data)?;
 
         let file = File::open(song_path)?;
         on_loaded.send(nautica);
-        song_from_zip(BufReader::new(file), diff)
+        song_from_zip(BufReader::new(file), diff, &progress)
     }))
 }
 
 fn song_from_zip(
     data: impl std::io::Read + std::io::Seek,
     diff: u8,
+    progress: &Sender<LoadProgress>,
 ) -> Result<(kson::Chart, Box<dyn rodio::Source<Item = f32> + Send>)> {
+    _ = progress.send(LoadProgress::Chart);
     let mut archive = zip::read::ZipArchive::new(data)?;
     for i in 0..archive.len() {
         let mut file = archive.by_index(i)?;
@@ -623,10 +953,7 @@ fn song_from_zip(
                 bgm_entry.read_to_end(&mut bgm_buf)?;
                 let bgm_cursor = std::io::Cursor::new(bgm_buf);
 
-                return Ok((
-                    chart,
-                    Box::new(rodio::Decoder::new(bgm_cursor)?.convert_samples()),
-                ));
+                return Ok((chart, open_audio(bgm_cursor, &bgm_path, progress)?));
             }
         }
     }
@@ -0,0 +1,295 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+use rodio::Source;
+
+use crate::maintenance::ManagedCache;
+
+/// Length of the blocks the audio gets split into for the energy scan
+const BLOCK: Duration = Duration::from_millis(100);
+/// Blocks with an RMS below this are considered silent
+const SILENCE_RMS: f32 = 0.01;
+/// Max amount of leading silence that will be skipped in a declared preview region
+const MAX_SILENCE_SKIP: Duration = Duration::from_secs(10);
+/// Length of the fallback window when the declared region is unusable
+const FALLBACK_LENGTH: Duration = Duration::from_secs(15);
+/// Part of the song searched for the fallback window
+const FALLBACK_SCAN: Duration = Duration::from_secs(180);
+/// Previews waiting for analysis, more requests are dropped and retried on the next preview
+const QUEUE_LEN: usize = 8;
+
+/// `(offset, length)` of the preview that should be played
+pub type PreviewRegion = (Duration, Duration);
+
+type Regions = Arc<RwLock<HashMap<String, Option<PreviewRegion>>>>;
+
+struct Job {
+    hash: String,
+    audio: PathBuf,
+    offset: Duration,
+    length: Duration,
+}
+
+/// Improved preview regions by chart hash, `None` while the analysis is running or if it failed
+#[derive(Clone)]
+pub struct PreviewCache {
+    regions: Regions,
+    jobs: Option<SyncSender<Job>>,
+}
+
+impl Default for PreviewCache {
+    fn default() -> Self {
+        let regions = Regions::default();
+        let (jobs, job_rx) = sync_channel(QUEUE_LEN);
+        let worker_regions = regions.clone();
+        let spawned = std::thread::Builder::new()
+            .name("Preview analysis".into())
+            .spawn(move || run(&worker_regions, job_rx));
+
+        let jobs = match spawned {
+            Ok(_) => Some(jobs),
+            Err(e) => {
+                warn!("Failed to start preview analysis: {e}");
+                None
+            }
+        };
+
+        Self { regions, jobs }
+    }
+}
+
+impl PreviewCache {
+    pub fn get(&self, hash: &str) -> Option<PreviewRegion> {
+        self.regions
+            .read()
+            .expect("Lock error")
+            .get(hash)
+            .copied()
+            .flatten()
+    }
+
+    /// Queues the preview for analysis on the worker thread unless it has already been
+    /// analyzed, the result will be used the next time the preview is requested.
+    pub fn analyze(&self, hash: String, audio: PathBuf, offset: Duration, length: Duration) {
+        let Some(jobs) = &self.jobs else {
+            return;
+        };
+
+        {
+            let mut regions = self.regions.write().expect("Lock error");
+            if regions.contains_key(&hash) {
+                return;
+            }
+            regions.insert(hash.clone(), None);
+        }
+
+        let job = Job {
+            hash,
+            audio,
+            offset,
+            length,
+        };
+        if let Err(TrySendError::Full(job) | TrySendError::Disconnected(job)) = jobs.try_send(job) {
+            // Try again the next time the song is previewed
+            self.regions.write().expect("Lock error").remove(&job.hash);
+        }
+    }
+}
+
+/// Kept in memory only, the size is what the entries take up there
+impl ManagedCache for PreviewCache {
+    fn name(&self) -> &'static str {
+        "Preview analysis"
+    }
+
+    fn size(&self) -> anyhow::Result<u64> {
+        let regions = self.regions.read().expect("Lock error");
+        let entry = std::mem::size_of::<(String, Option<PreviewRegion>)>();
+        Ok(regions.keys().map(|hash| (entry + hash.len()) as u64).sum())
+    }
+
+    /// Previews are analyzed again the next time they are played
+    fn clear(&self) -> anyhow::Result<()> {
+        self.regions.write().expect("Lock error").clear();
+        Ok(())
+    }
+}
+
+fn run(regions: &Regions, jobs: Receiver<Job>) {
+    for job in jobs {
+        let region = match analyze_region(&job.audio, job.offset, job.length) {
+            Ok(region) => region,
+            Err(e) => {
+                warn!("Could not analyze preview for {:?}: {e}", &job.audio);
+                None
+            }
+        };
+
+        if let Some((offset, length)) = region {
+            info!(
+                "Preview for {:?}: {}ms ({}ms)",
+                &job.audio,
+                offset.as_millis(),
+                length.as_millis()
+            );
+        }
+
+        regions
+            .write()
+            .expect("Lock error")
+            .insert(job.hash, region);
+    }
+}
+
+/// Only decodes the declared region, the start of the song is scanned as well if it's silent
+fn analyze_region(
+    audio: &Path,
+    offset: Duration,
+    length: Duration,
+) -> anyhow::Result<Option<PreviewRegion>> {
+    let declared = block_energies(audio, offset, length)?;
+    if let Some(region) = find_region(&declared, offset, length) {
+        return Ok(Some(region));
+    }
+
+    let blocks = block_energies(audio, Duration::ZERO, FALLBACK_SCAN)?;
+    Ok(loudest_window(&blocks, block_index(FALLBACK_LENGTH).max(1))
+        .map(|i| (block_time(i), FALLBACK_LENGTH)))
+}
+
+/// Mean square of each [`BLOCK`] of `length` of the audio file from `start`, all channels mixed
+/// together
+fn block_energies(audio: &Path, start: Duration, length: Duration) -> anyhow::Result<Vec<f32>> {
+    let source = super::decode_file(audio)?;
+
+    let block_len = (source.sample_rate() as u128 * source.channels() as u128 * BLOCK.as_millis()
+        / 1000)
+        .max(1) as usize;
+
+    let mut blocks = vec![];
+    let mut sum = 0.0f32;
+    let mut count = 0usize;
+    for sample in source.skip_duration(start).take_duration(length) {
+        sum += sample * sample;
+        count += 1;
+        if count == block_len {
+            blocks.push(sum / count as f32);
+            sum = 0.0;
+            count = 0;
+        }
+    }
+    if count > 0 {
+        blocks.push(sum / count as f32);
+    }
+
+    Ok(blocks)
+}
+
+fn is_silent(energy: f32) -> bool {
+    energy.sqrt() < SILENCE_RMS
+}
+
+fn block_index(d: Duration) -> usize {
+    (d.as_millis() / BLOCK.as_millis()) as usize
+}
+
+fn block_time(i: usize) -> Duration {
+    BLOCK * i as u32
+}
+
+/// Skips leading silence in the declared region, `declared` are the blocks from `offset`.
+/// `None` if the region is silent or out of range.
+fn find_region(declared: &[f32], offset: Duration, length: Duration) -> Option<PreviewRegion> {
+    let first_sound = declared.iter().position(|e| !is_silent(*e))?;
+    let skip = block_time(first_sound).min(MAX_SILENCE_SKIP);
+    Some((offset + skip, length))
+}
+
+/// Index of the first block of the window with the highest total energy
+fn loudest_window(blocks: &[f32], window: usize) -> Option<usize> {
+    if blocks.iter().all(|e| is_silent(*e)) {
+        return None;
+    }
+
+    let window = window.min(blocks.len());
+    let mut energy: f32 = blocks[..window].iter().sum();
+    let mut best = (0, energy);
+    for i in window..blocks.len() {
+        energy += blocks[i] - blocks[i - window];
+        if energy > best.1 {
+            best = (i + 1 - window, energy);
+        }
+    }
+
+    Some(best.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{find_region, loudest_window, PreviewCache};
+    use crate::maintenance::ManagedCache;
+
+    const LOUD: f32 = 0.25;
+
+    #[test]
+    fn skips_leading_silence() {
+        let offset = Duration::from_secs(30);
+        let length = Duration::from_secs(15);
+        let mut declared = vec![0.0; 20];
+        declared.extend([LOUD; 10]);
+        assert_eq!(
+            find_region(&declared, offset, length),
+            Some((offset + Duration::from_secs(2), length))
+        );
+
+        // Skipping is bounded
+        let mut declared = vec![0.0; 150];
+        declared.push(LOUD);
+        assert_eq!(
+            find_region(&declared, offset, length),
+            Some((offset + Duration::from_secs(10), length))
+        );
+
+        assert_eq!(find_region(&[0.0; 10], offset, length), None);
+        assert_eq!(find_region(&[], offset, length), None);
+    }
+
+    #[test]
+    fn loudest() {
+        let mut blocks = vec![0.0; 10];
+        blocks.extend([0.1, LOUD, LOUD, LOUD, 0.1]);
+        blocks.extend([0.0; 10]);
+        assert_eq!(loudest_window(&blocks, 3), Some(11));
+        // Windows longer than the song start at the beginning
+        assert_eq!(loudest_window(&blocks, 100), Some(0));
+        assert_eq!(loudest_window(&[0.0; 10], 3), None);
+        assert_eq!(loudest_window(&[], 3), None);
+    }
+
+    #[test]
+    fn clear_cache() {
+        let cache = PreviewCache::default();
+        let region = (Duration::from_secs(1), Duration::from_secs(2));
+        cache
+            .regions
+            .write()
+            .unwrap()
+            .insert("hash".into(), Some(region));
+        assert_eq!(cache.get("hash"), Some(region));
+        assert!(cache.size().unwrap() > 0);
+
+        cache.clear().unwrap();
+        assert_eq!(cache.get("hash"), None);
+        assert_eq!(cache.size().unwrap(), 0);
+    }
+}
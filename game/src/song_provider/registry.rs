@@ -0,0 +1,76 @@
+use std::sync::{Arc, RwLock};
+
+use di::{RefMut, ServiceProvider};
+use once_cell::sync::Lazy;
+
+use super::{FileSongProvider, NauticaSongProvider, SongProvider};
+
+/// Resolves a song provider, selected by name with the `providers` config key.
+///
+/// The factory is called every time the song select resolves its provider, so it should hand
+/// out a shared instance, usually a singleton added to the service collection in `main`.
+/// Providers are used from the main thread behind a `RwLock`, `SongProvider` only requires
+/// `Send` since song loading and previews run on other threads. Song list changes reach the
+/// song select through the bus returned by `SongProvider::subscribe`, so providers that load
+/// songs in the background have to broadcast `SongProviderEvent`s from a `WorkerService` or
+/// their own thread instead of relying on `get_all` being called again.
+pub type ProviderFactory = dyn Fn(&ServiceProvider) -> RefMut<dyn SongProvider> + Send + Sync;
+
+static PROVIDERS: Lazy<RwLock<Vec<(String, Arc<ProviderFactory>)>>> = Lazy::new(|| {
+    RwLock::new(vec![
+        (
+            "files".into(),
+            factory(|sp| sp.get_required_mut::<FileSongProvider>()),
+        ),
+        (
+            "nautica".into(),
+            factory(|sp| sp.get_required_mut::<NauticaSongProvider>()),
+        ),
+    ])
+});
+
+fn factory(
+    f: impl Fn(&ServiceProvider) -> RefMut<dyn SongProvider> + Send + Sync + 'static,
+) -> Arc<ProviderFactory> {
+    Arc::new(f)
+}
+
+/// Makes a provider selectable by `name`, replacing any provider registered with the same name.
+///
+/// Has to be called before the song select is opened, usually at startup in `main`.
+pub fn register_provider(
+    name: impl Into<String>,
+    factory: impl Fn(&ServiceProvider) -> RefMut<dyn SongProvider> + Send + Sync + 'static,
+) {
+    let name = name.into();
+    let mut providers = PROVIDERS.write().expect("Lock error");
+    providers.retain(|(n, _)| *n != name);
+    providers.push((name, self::factory(factory)));
+}
+
+pub fn provider_names() -> Vec<String> {
+    PROVIDERS
+        .read()
+        .expect("Lock error")
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// Resolves the first provider in `names` that has been registered
+pub fn create_provider(
+    names: &[String],
+    services: &ServiceProvider,
+) -> Option<RefMut<dyn SongProvider>> {
+    let factory = {
+        let providers = PROVIDERS.read().expect("Lock error");
+        names.iter().find_map(|name| {
+            providers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, factory)| factory.clone())
+        })
+    }?;
+
+    Some(factory(services))
+}
@@ -1,22 +1,27 @@
 use crate::{
     async_service::AsyncService,
-    button_codes::{LaserAxis, LaserState, UscButton, UscInputEvent},
+    audio::{mixer_sample_rate, MixerExt},
+    button_codes::{LaserState, UscButton, UscInputEvent},
     config::GameConfig,
     game_main::AutoPlay,
     help::await_task,
-    input_state::InputState,
+    input_state::{press_interval, InputState},
     lua_service::LuaProvider,
-    results::Score,
+    menu_audio::{MenuAudio, NavSound},
+    results::{sort_scores, Score},
     scene::{Scene, SceneData},
     settings_dialog::SettingsDialog,
     song_provider::{
-        self, DiffId, ScoreProvider, ScoreProviderEvent, SongDiffId, SongFilter, SongFilterType,
-        SongId, SongProvider, SongProviderEvent, SongSort,
+        self, report_broken_audio, take_broken_audio, DiffId, ScanProgress, ScoreProvider,
+        ScoreProviderEvent, SongDiffId, SongFilter, SongFilterType, SongId, SongProvider,
+        SongProviderEvent, SongSort,
     },
     take_duration_fade::take_duration_fade,
+    thumbnailer::{StripRequest, Thumbnailer},
+    vg_ui::Vgfx,
     ControlMessage, RuscMixer,
 };
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 use di::{RefMut, ServiceProvider};
 use game_loop::winit::event::{ElementState, Event, Ime, WindowEvent};
 use itertools::Itertools;
@@ -50,7 +55,13 @@ use winit::{
     keyboard::{Key, NamedKey},
 };
 
+mod knob_acceleration;
+mod leaderboard;
+mod radio;
 mod song_collection;
+use knob_acceleration::{advance_groups, Acceleration, KnobAccelerator};
+use leaderboard::Leaderboard;
+use radio::Radio;
 use song_collection::*;
 
 #[derive(Debug, ToTypename, Clone, Serialize, UserData)]
@@ -65,6 +76,41 @@ pub struct Difficulty {
     pub scores: Vec<Score>, //array of all scores on this diff
     pub hash: Option<String>,
     pub illustrator: String,
+    pub radar: Option<kson::radar::Radar>,
+    pub duration: Option<u32>, //in milliseconds, can differ between difficulties
+    pub duration_string: Option<String>, //ex. "2:04"
+    pub last_played: Option<i32>, //unix timestamp of the newest score
+    /// Set when the audio turned out to be unplayable, to grey out the difficulty
+    pub audio_broken: bool,
+    /// Note density of the chart as a grayscale image, rendered once the song is selected
+    pub density_strip_path: Option<PathBuf>,
+    /// Set once the jacket is decoded, skins can keep showing the previous jacket until then
+    pub jacket_loaded: bool,
+}
+
+impl Difficulty {
+    /// Adds a score unless it's already listed, the same score can be received again when
+    /// catching up on missed score events
+    pub fn add_score(&mut self, score: Score) {
+        if self
+            .scores
+            .iter()
+            .any(|s| s.timestamp == score.timestamp && s.score == score.score)
+        {
+            return;
+        }
+
+        self.top_badge = self.top_badge.max(score.badge);
+        self.last_played = self.last_played.max(Some(score.timestamp));
+        self.scores.push(score);
+        sort_scores(&mut self.scores);
+    }
+}
+
+/// Formats a duration in milliseconds as minutes and seconds, like "2:04"
+pub fn format_duration(ms: u32) -> String {
+    let seconds = ms / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
 }
 
 impl TealData for Difficulty {
@@ -82,16 +128,48 @@ impl TealData for Difficulty {
         fields.add_field_method_get("effector", |_, diff| Ok(diff.effector.clone()));
         fields.add_field_method_get("topBadge", |_, diff| Ok(diff.top_badge));
         fields.add_field_method_get("scores", |_, diff| Ok(diff.scores.clone()));
+        fields.add_field_method_get("illustrator", |_, diff| Ok(diff.illustrator.clone()));
+        fields.add_field_method_get("duration", |_, diff| Ok(diff.duration));
+        fields.add_field_method_get("durationString", |_, diff| Ok(diff.duration_string.clone()));
+        fields.add_field_method_get("lastPlayed", |_, diff| Ok(diff.last_played));
+        fields.add_field_method_get("jacketLoaded", |_, diff| Ok(diff.jacket_loaded));
     }
 }
 
-#[derive(Debug, ToTypename, UserData, Clone, Serialize, Default)]
+#[derive(Debug, ToTypename, UserData, Clone, Default)]
 pub struct Song {
     pub title: String,
     pub artist: String,
     pub bpm: String,                                //ex. "170-200"
     pub id: SongId,                                 //unique static identifier
     pub difficulties: Arc<RwLock<Vec<Difficulty>>>, //array of all difficulties for this song
+    pub uploader: Option<String>,                   //name of the uploader, only for online songs
+}
+
+impl Song {
+    /// Newest [`Difficulty::last_played`] of the song
+    pub fn last_played(&self) -> Option<i32> {
+        let diffs = self.difficulties.read().expect("Lock error");
+        diffs.iter().filter_map(|d| d.last_played).max()
+    }
+}
+
+impl Serialize for Song {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("Song", 7)?;
+        s.serialize_field("title", &self.title)?;
+        s.serialize_field("artist", &self.artist)?;
+        s.serialize_field("bpm", &self.bpm)?;
+        s.serialize_field("id", &self.id)?;
+        s.serialize_field("difficulties", &self.difficulties)?;
+        s.serialize_field("uploader", &self.uploader)?;
+        s.serialize_field("lastPlayed", &self.last_played())?;
+        s.end()
+    }
 }
 
 //Keep tealdata for generating type definitions
@@ -101,9 +179,29 @@ impl TealData for Song {
         fields.add_field_method_get("artist", |_, song| Ok(song.artist.clone()));
         fields.add_field_method_get("bpm", |_, song| Ok(song.bpm.clone()));
         fields.add_field_method_get("id", |_, song| Ok(song.id.clone()));
+        fields.add_field_method_get("uploader", |_, song| Ok(song.uploader.clone()));
         fields.add_field_method_get("difficulties", |_, song| {
             Ok(song.difficulties.read().expect("Lock error").clone())
         });
+        fields.add_field_method_get("lastPlayed", |_, song| Ok(song.last_played()));
+    }
+}
+
+/// Header of a group of songs on the wheel
+#[derive(Debug, ToTypename, Clone, Serialize, UserData)]
+#[serde(rename_all = "camelCase")]
+pub struct SongGroup {
+    pub title: String,
+    /// Wheel index of the first song in the group, starting at 1
+    pub first: usize,
+    pub count: usize,
+}
+
+impl TealData for SongGroup {
+    fn add_fields<'lua, F: tealr::mlu::TealDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("title", |_, group| Ok(group.title.clone()));
+        fields.add_field_method_get("first", |_, group| Ok(group.first));
+        fields.add_field_method_get("count", |_, group| Ok(group.count));
     }
 }
 
@@ -111,6 +209,7 @@ impl TealData for Song {
 #[serde(rename_all = "camelCase")]
 pub struct SongSelect {
     songs: SongCollection,
+    groups: Vec<SongGroup>,
     search_input_active: bool, //true when the user is currently inputting search text
     search_text: String,       //current string used by the song search
     search_status: String,     //database status
@@ -119,17 +218,24 @@ pub struct SongSelect {
     preview_countdown: f64,
     preview_finished: Arc<AtomicUsize>,
     preview_playing: Arc<AtomicU64>,
+    demo_progress: f32, //0.0 to 1.0 while start is being held to start a demo
+    versus: bool,       //toggled with start on the player 2 controller
+    radio: bool,        //true while idle wheel advancing is enabled
 }
 
 impl TealData for SongSelect {
     fn add_fields<'lua, F: tealr::mlu::TealDataFields<'lua, Self>>(fields: &mut F) {
         fields.add_field_method_get("songs", |_, _| Ok([] as [Song; 0]));
+        fields.add_field_method_get("groups", |_, songwheel| Ok(songwheel.groups.clone()));
         fields.add_field_method_get("searchInputActive", |_, songwheel| {
             Ok(songwheel.search_input_active)
         });
         fields.add_field_method_get("searchText", |_, songwheel| {
             Ok(songwheel.search_text.clone())
         });
+        fields.add_field_method_get("demoProgress", |_, songwheel| Ok(songwheel.demo_progress));
+        fields.add_field_method_get("versus", |_, songwheel| Ok(songwheel.versus));
+        fields.add_field_method_get("radio", |_, songwheel| Ok(songwheel.radio));
         fields.add_field_method_get(
             "searchStatus",
             |_, _| -> Result<Option<String>, tealr::mlu::mlua::Error> { Ok(None) },
@@ -150,6 +256,7 @@ impl SongSelect {
     pub fn new() -> Self {
         Self {
             songs: Default::default(),
+            groups: vec![],
             search_input_active: false,
             search_text: String::new(),
             search_status: String::new(),
@@ -158,6 +265,9 @@ impl SongSelect {
             preview_countdown: 1500.0,
             preview_finished: Arc::new(AtomicUsize::new(0)),
             preview_playing: Arc::new(AtomicU64::new(0)),
+            demo_progress: 0.0,
+            versus: false,
+            radio: false,
         }
     }
 }
@@ -171,6 +281,29 @@ impl SceneData for SongSelect {
     }
 }
 pub const KNOB_NAV_THRESHOLD: f32 = std::f32::consts::PI / 3.0;
+/// How long start has to be held to start a song with autoplay
+const DEMO_HOLD: Duration = Duration::from_secs(1);
+/// Milliseconds the selected difficulty has to stay the same before its preview starts
+const DIFF_PREVIEW_DELAY: f64 = 400.0;
+/// Milliseconds a song has to stay selected before the jackets of its difficulties are preloaded
+const JACKET_PRELOAD_DELAY: f64 = 250.0;
+/// Previews stop after this long whatever length the chart or the decoder report
+const MAX_PREVIEW_LENGTH: Duration = Duration::from_secs(60);
+/// Opens and closes the leaderboard of the selected difficulty
+const LEADERBOARD_BUTTON: UscButton = UscButton::BT(kson::BtLane::A);
+/// Held to make the knob jump between groups, if enabled
+const GROUP_BUTTON: UscButton = UscButton::BT(kson::BtLane::D);
+
+/// Change to the songs of the wheel, applied to `songwheel.songs` in place for skins that
+/// handle them
+enum WheelChange {
+    /// Songs appended to the wheel, starting at the index
+    Added(usize, Vec<Arc<Song>>),
+    /// Indices of the removed songs, in the order before they were removed
+    Removed(Vec<usize>),
+    /// Song with a new score or other changed difficulty data
+    Updated(SongDiffId),
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum MenuState {
@@ -178,6 +311,7 @@ enum MenuState {
     Levels,
     Folders,
     Sorting,
+    Scores,
 }
 
 pub struct SongSelectScene {
@@ -188,7 +322,9 @@ pub struct SongSelectScene {
     program_control: Option<Sender<ControlMessage>>,
     song_advance: f32,
     diff_advance: f32,
+    song_knob: KnobAccelerator,
     suspended: Arc<AtomicBool>,
+    menu_audio: Arc<MenuAudio>,
     closed: bool,
     mixer: RuscMixer,
     sample_owner: owned_source::Marker,
@@ -206,9 +342,27 @@ pub struct SongSelectScene {
     level_filter: u8,
     folder_filter_index: usize,
     sort_index: usize,
+    /// The sort wheel is on its radio entry, after the sorts
+    radio_selected: bool,
+    radio: Radio,
     filters: Vec<song_provider::SongFilterType>,
     sorts: Vec<song_provider::SongSort>,
     auto_rx: Receiver<crate::game_main::AutoPlay>,
+    start_held: Option<SystemTime>,
+    metadata_edit: Option<(SongDiffId, song_provider::ChartMetadata)>,
+    leaderboard: Leaderboard,
+    scan_progress: Option<ScanProgress>,
+    /// Finished scan with charts that failed to load or have no audio, shown until dismissed
+    scan_failed: Option<ScanProgress>,
+    thumbnailer: RefMut<Thumbnailer>,
+    /// Density strips requested for the selected song, by chart hash
+    pending_strips: Vec<(SongDiffId, String)>,
+    vgfx: RefMut<Vgfx>,
+    /// Song the jacket preload countdown runs for
+    jackets_song: Option<SongId>,
+    jacket_countdown: f64,
+    /// Jackets preloaded for the selected song
+    pending_jackets: Vec<(SongId, PathBuf)>,
 }
 
 impl SongSelectScene {
@@ -225,6 +379,21 @@ impl SongSelectScene {
             .expect("Lock error")
             .init_scores(&mut initial_songs.iter());
         song_select.songs.add(initial_songs, initial_order);
+
+        //Catch up on scores set while no song select was subscribed
+        let last_seen = GameConfig::get().song_select.last_score_seen;
+        let missed_scores = score_provider
+            .read()
+            .expect("Lock error")
+            .scores_since(last_seen);
+        for (id, score) in missed_scores {
+            note_score_seen(&score);
+            song_provider
+                .write()
+                .expect("Lock error")
+                .add_score(id, score);
+        }
+
         let (auto_tx, auto_rx) = mpsc::channel();
         Self {
             filter_lua: LuaProvider::new_lua(),
@@ -235,7 +404,9 @@ impl SongSelectScene {
             program_control: None,
             diff_advance: 0.0,
             song_advance: 0.0,
+            song_knob: KnobAccelerator::default(),
             suspended: Arc::new(AtomicBool::new(false)),
+            menu_audio: services.get_required(),
             closed: false,
             mixer: services.get_required(),
             sample_owner,
@@ -255,10 +426,23 @@ impl SongSelectScene {
             level_filter: 0,
             folder_filter_index: 0,
             sort_index: 0,
+            radio_selected: false,
+            radio: Radio::default(),
             filters: vec![],
             sorts: vec![],
             settings_closed: SystemTime::UNIX_EPOCH,
             auto_rx,
+            start_held: None,
+            metadata_edit: None,
+            leaderboard: Leaderboard::default(),
+            scan_progress: None,
+            scan_failed: None,
+            thumbnailer: services.get_required_mut(),
+            pending_strips: vec![],
+            vgfx: services.get_required_mut(),
+            jackets_song: None,
+            jacket_countdown: JACKET_PRELOAD_DELAY,
+            pending_jackets: vec![],
         }
     }
 
@@ -270,6 +454,37 @@ impl SongSelectScene {
             .set_search(&self.state.search_text);
     }
 
+    /// Song index after moving `steps` on the wheel
+    fn advance_songs(&self, steps: i32, acceleration: Acceleration) -> i32 {
+        let len = self.state.songs.len().max(1);
+        let index = self.state.selected_index;
+        let groups = &self.state.groups;
+
+        let multiplier = match acceleration {
+            Acceleration::Songs(multiplier) => multiplier,
+            Acceleration::Groups if !groups.is_empty() => {
+                let group = |i: usize| {
+                    let g = groups
+                        .partition_point(|g| g.first - 1 <= i)
+                        .checked_sub(1)?;
+                    (i < groups[g].first - 1 + groups[g].count).then_some(g)
+                };
+                return advance_groups(index as usize, len, steps, group) as i32;
+            }
+            // Sorts without groups use the fastest song acceleration instead
+            Acceleration::Groups => GameConfig::get()
+                .song_select
+                .knob_acceleration
+                .steps
+                .iter()
+                .map(|(_, songs)| *songs)
+                .max()
+                .unwrap_or(1),
+        };
+
+        (index + steps * multiplier as i32).rem_euclid(len as i32)
+    }
+
     fn update_lua(&self) -> anyhow::Result<()> {
         profile_function!();
         Ok(self
@@ -278,15 +493,125 @@ impl SongSelectScene {
             .set("songwheel", self.lua.to_value(&self.state)?)?)
     }
 
+    /// Updates everything in the `songwheel` table except the songs
+    fn update_lua_fields(&mut self) -> anyhow::Result<()> {
+        let songs = std::mem::take(&mut self.state.songs);
+        let state = self.lua.to_value(&self.state);
+        self.state.songs = songs;
+
+        let mlua::Value::Table(state) = state? else {
+            return Ok(());
+        };
+        state.raw_remove("songs")?;
+        let raw_state: mlua::Table = self.lua.globals().get("songwheel")?;
+        for pair in state.pairs::<mlua::Value, mlua::Value>() {
+            let (key, value) = pair?;
+            raw_state.raw_set(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Only updates `songwheel.demoProgress`, it changes every frame while Start is held
+    fn set_demo_progress(&mut self, progress: f32) -> anyhow::Result<()> {
+        self.state.demo_progress = progress;
+        let raw_state: mlua::Table = self.lua.globals().get("songwheel")?;
+        raw_state.set("demoProgress", progress)?;
+        Ok(())
+    }
+
+    fn song_index(&self, id: &SongDiffId) -> Option<usize> {
+        if let Some(song) = id.get_song() {
+            return self.state.songs.find_index(song);
+        }
+        let diff = id.get_diff()?;
+        self.state.songs.iter().position(|song| {
+            song.difficulties
+                .read()
+                .expect("Lock error")
+                .iter()
+                .any(|d| d.id == *diff)
+        })
+    }
+
+    /// Applies `changes` to `songwheel.songs` in place and passes them to the skin's
+    /// `songs_added`, `songs_removed` and `song_updated`, so state the skin keeps on the song
+    /// tables survives. `songs_removed` is called before the songs are removed. Replaces the
+    /// whole `songwheel` table instead if the skin doesn't define all three or there are no
+    /// `changes` to apply, e.g. after the songs were reordered.
+    fn update_songs_lua(&mut self, changes: Option<Vec<WheelChange>>) -> anyhow::Result<()> {
+        profile_function!();
+        let lua = self.lua.clone();
+        let globals = lua.globals();
+        let (Some(changes), Ok(added), Ok(removed), Ok(updated)) = (
+            changes,
+            globals.get::<_, Function>("songs_added"),
+            globals.get::<_, Function>("songs_removed"),
+            globals.get::<_, Function>("song_updated"),
+        ) else {
+            return self.update_lua();
+        };
+
+        let raw_state: mlua::Table = globals.get("songwheel")?;
+        let songs: mlua::Table = raw_state.get("songs")?;
+        for change in changes {
+            match change {
+                WheelChange::Added(start, new_songs) => {
+                    let mlua::Value::Table(new_songs) = lua.to_value(&new_songs)? else {
+                        continue;
+                    };
+                    for (i, song) in new_songs
+                        .clone()
+                        .sequence_values::<mlua::Value>()
+                        .enumerate()
+                    {
+                        songs.raw_set(start + i + 1, song?)?;
+                    }
+                    added.call::<_, ()>((start + 1, new_songs))?;
+                }
+                WheelChange::Removed(indices) => {
+                    removed.call::<_, ()>(indices.iter().map(|i| i + 1).collect_vec())?;
+                    for i in indices.iter().rev() {
+                        songs.raw_remove(i + 1)?;
+                    }
+                }
+                WheelChange::Updated(id) => {
+                    let Some(index) = self.song_index(&id) else {
+                        continue;
+                    };
+                    let (Some(entry), mlua::Value::Table(song)) = (
+                        songs.raw_get::<_, Option<mlua::Table>>(index + 1)?,
+                        lua.to_value(&self.state.songs[index])?,
+                    ) else {
+                        return self.update_lua();
+                    };
+                    for pair in song.pairs::<mlua::Value, mlua::Value>() {
+                        let (key, value) = pair?;
+                        entry.raw_set(key, value)?;
+                    }
+                    updated.call::<_, ()>((index + 1, entry))?;
+                }
+            }
+        }
+
+        self.update_lua_fields()
+    }
+
     fn update_filter_sort_lua(&self) -> anyhow::Result<(Vec<SongFilterType>, Vec<SongSort>)> {
         let (filters, sorts) = {
             let sp = self.song_provider.read().expect("Lock error");
             (sp.get_available_filters(), sp.get_available_sorts())
         };
 
-        self.sort_lua
-            .globals()
-            .set("sorts", sorts.iter().map(ToString::to_string).collect_vec())?;
+        // The radio toggle is the last entry of the sort wheel
+        self.sort_lua.globals().set(
+            "sorts",
+            sorts
+                .iter()
+                .map(ToString::to_string)
+                .chain(["Radio".to_owned()])
+                .collect_vec(),
+        )?;
+        self.sort_lua.globals().set("radio", self.radio.enabled())?;
 
         self.filter_lua.globals().set(
             "filters",
@@ -303,38 +628,79 @@ impl SongSelectScene {
         Ok((filters, sorts))
     }
 
+    fn set_radio(&mut self, enabled: bool) {
+        log::info!("Radio {}", if enabled { "on" } else { "off" });
+        self.radio.set_enabled(enabled);
+        self.state.radio = enabled;
+        _ = self.sort_lua.globals().set("radio", enabled);
+        _ = self.update_lua_fields();
+    }
+
+    /// Input from the player, cancels the radio if it advances the wheel
+    fn radio_input(&mut self) {
+        let enabled = self.radio.enabled();
+        self.radio.input();
+        if enabled != self.radio.enabled() {
+            self.set_radio(false);
+        }
+    }
+
     fn start_preview(&mut self) {
-        let Some(song_id) = self
-            .state
-            .songs
-            .get(self.state.selected_index as usize)
-            .map(|x| x.id.clone())
-        else {
+        // A song is loading
+        if self.menu_audio.is_ducked() {
+            return;
+        }
+        let Some(song) = self.state.songs.get(self.state.selected_index as usize) else {
             return;
         };
+        let song_id = song.id.clone();
+        let id = song
+            .difficulties
+            .read()
+            .expect("Lock error")
+            .get(self.state.selected_diff_index as usize)
+            .map(|d| SongDiffId::SongDiff(song_id.clone(), d.id.clone()));
+        let preview_key = id
+            .as_ref()
+            .and_then(|id| {
+                self.song_provider
+                    .read()
+                    .expect("Lock error")
+                    .preview_key(id)
+            })
+            .unwrap_or_else(|| song_id.as_u64());
         let services = self.services.create_scope();
 
         let suspended = self.suspended.clone();
         let preview_playing = self.state.preview_playing.clone();
         let preview_finished = self.state.preview_finished.clone();
+        let menu_audio = self.menu_audio.clone();
         let owner = self.sample_owner.clone();
         let mixer = self.mixer.clone();
 
-        if preview_playing.load(std::sync::atomic::Ordering::Relaxed) == song_id.as_u64() {
+        // Switching to a difficulty with the same preview keeps it playing
+        if preview_playing.load(std::sync::atomic::Ordering::Relaxed) == preview_key {
             return;
         }
 
         self.async_worker.read().unwrap().run(async move {
             let preview = {
                 let song_provider = services.get_required_mut::<dyn SongProvider>();
-                let preview = song_provider.read().unwrap().get_preview(&song_id);
+                let song_provider = song_provider.read().unwrap();
+                let preview = match &id {
+                    Some(id) => song_provider.get_diff_preview(id),
+                    None => song_provider.get_preview(&song_id),
+                };
                 preview
             };
 
             let (preview, skip, duration) = match await_task(preview).await {
                 Ok(e) => e,
                 Err(e) => {
-                    warn!("Could not load preview: {e}");
+                    warn!("Could not load preview: {e:#}");
+                    if let Some(id) = id {
+                        report_broken_audio(id, &e);
+                    }
                     return;
                 }
             };
@@ -346,13 +712,131 @@ impl SongSelectScene {
                 suspended,
                 preview_playing,
                 preview_finished,
+                menu_audio,
                 &owner,
-                song_id.as_u64(),
+                preview_key,
                 mixer,
             );
         });
     }
 
+    /// Requests the density strips of the selected song that aren't ready yet, closest to the
+    /// selected difficulty first
+    fn request_density_strips(&mut self) {
+        let Some(song) = self.state.songs.get(self.state.selected_index as usize) else {
+            return;
+        };
+        let selected = self.state.selected_diff_index as usize;
+        let mut charts: Vec<_> = {
+            let song_provider = self.song_provider.read().expect("Lock error");
+            song.difficulties
+                .read()
+                .expect("Lock error")
+                .iter()
+                .enumerate()
+                .filter(|(_, d)| d.density_strip_path.is_none())
+                .filter_map(|(i, d)| {
+                    let id = SongDiffId::SongDiff(song.id.clone(), d.id.clone());
+                    let request = StripRequest {
+                        hash: d.hash.clone()?,
+                        chart: song_provider.chart_path(&id)?,
+                    };
+                    Some((i.abs_diff(selected), id, request))
+                })
+                .collect()
+        };
+        charts.sort_by_key(|(distance, _, _)| *distance);
+
+        self.pending_strips = charts
+            .iter()
+            .map(|(_, id, request)| (id.clone(), request.hash.clone()))
+            .collect();
+        self.thumbnailer
+            .write()
+            .expect("Lock error")
+            .request_density_strips(charts.into_iter().map(|(_, _, request)| request));
+    }
+
+    /// Preloads the jackets of the selected song that aren't loaded yet, closest to the selected
+    /// difficulty first, so switching difficulties doesn't wait for the image to decode
+    fn request_jackets(&mut self) {
+        let Some(song) = self.state.songs.get(self.state.selected_index as usize) else {
+            return;
+        };
+        let selected = self.state.selected_diff_index as usize;
+        let jackets: Vec<_> = song
+            .difficulties
+            .read()
+            .expect("Lock error")
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| !d.jacket_loaded)
+            .map(|(i, d)| (i.abs_diff(selected), d.jacket_path.clone()))
+            .sorted_by_key(|(distance, _)| *distance)
+            .map(|(_, path)| path)
+            .unique()
+            .collect();
+
+        let mut vgfx = self.vgfx.write().expect("Lock error");
+        for path in &jackets {
+            vgfx.preload_image(path);
+        }
+        self.pending_jackets = jackets
+            .into_iter()
+            .map(|path| (song.id.clone(), path))
+            .collect();
+    }
+
+    fn metadata_editor(&mut self, ctx: &egui::Context) {
+        let Some((id, meta)) = &mut self.metadata_edit else {
+            return;
+        };
+
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new("Metadata")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("metadata-grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Title");
+                        ui.text_edit_singleline(&mut meta.title);
+                        ui.end_row();
+                        ui.label("Artist");
+                        ui.text_edit_singleline(&mut meta.artist);
+                        ui.end_row();
+                        ui.label("Effector");
+                        ui.text_edit_singleline(&mut meta.effector);
+                        ui.end_row();
+                        ui.label("Level");
+                        ui.add(egui::DragValue::new(&mut meta.level).clamp_range(1..=20));
+                        ui.end_row();
+                        ui.label("Difficulty");
+                        ui.add(egui::DragValue::new(&mut meta.difficulty).clamp_range(0..=3));
+                        ui.end_row();
+                        ui.label("Offset (ms)");
+                        ui.add(egui::DragValue::new(&mut meta.offset));
+                        ui.end_row();
+                    });
+                save = ui.button("Save").clicked();
+            });
+
+        if save {
+            let result = self
+                .song_provider
+                .write()
+                .expect("Lock error")
+                .set_metadata(id, meta.clone());
+            match result {
+                Ok(()) => self.metadata_edit = None,
+                Err(e) => log::error!("Failed to save metadata: {e}"),
+            }
+        } else if !open {
+            self.metadata_edit = None;
+        }
+    }
+
     fn start_song(&mut self, autoplay: AutoPlay) {
         let state = &self.state;
         let song = self.state.songs.get(state.selected_index as usize).cloned();
@@ -378,6 +862,7 @@ impl SongSelectScene {
                         loader,
                         song: song.clone(),
                         autoplay,
+                        versus: state.versus && !autoplay.any(),
                     });
                 }
                 Err(err) => {
@@ -387,6 +872,68 @@ impl SongSelectScene {
         }
     }
 
+    /// Shows the leaderboard of the selected difficulty
+    fn open_leaderboard(&mut self) -> Result<()> {
+        let song = self
+            .state
+            .songs
+            .get(self.state.selected_index as usize)
+            .ok_or(anyhow!("Selected index not in collection"))?;
+        let (hash, level) = {
+            let diffs = song.difficulties.read().expect("Lock error");
+            let diff = diffs
+                .get(self.state.selected_diff_index as usize)
+                .ok_or(anyhow!("Selected difficulty not in song"))?;
+            let hash = match (&diff.hash, &diff.id) {
+                (Some(hash), _) | (None, DiffId(SongId::StringId(hash))) => hash.clone(),
+                _ => bail!("Chart hash required"),
+            };
+            (hash, diff.level)
+        };
+
+        let title = format!("{} [{level}]", song.title);
+        self.leaderboard.open(
+            &hash,
+            title,
+            &*self.score_provider.read().expect("Lock error"),
+        )?;
+        self.menu_state = MenuState::Scores;
+        Ok(())
+    }
+
+    /// Moves the wheel to the last played chart, which the result screen may have changed by
+    /// starting the next song
+    fn select_last_played(&mut self) -> anyhow::Result<()> {
+        let last_played = GameConfig::get().song_select.last_played.clone();
+        let (Some(song_id), Some(diff_id)) = (last_played.get_song(), last_played.get_diff())
+        else {
+            return Ok(());
+        };
+        let Some(index) = self.state.songs.find_index(song_id) else {
+            return Ok(());
+        };
+        let diff_index = self.state.songs.get(index).and_then(|song| {
+            song.difficulties
+                .read()
+                .expect("Lock error")
+                .iter()
+                .position(|d| d.id == *diff_id)
+        });
+
+        if index as i32 != self.state.selected_index {
+            self.state.selected_index = index as _;
+            let set_song_idx: Function = self.lua.globals().get("set_index")?;
+            set_song_idx.call::<_, i32>(self.state.selected_index + 1)?;
+        }
+        if let Some(diff_index) = diff_index.filter(|d| *d as i32 != self.state.selected_diff_index)
+        {
+            self.state.selected_diff_index = diff_index as _;
+            let set_diff_idx: Function = self.lua.globals().get("set_diff")?;
+            set_diff_idx.call::<_, ()>(self.state.selected_diff_index + 1)?;
+        }
+        Ok(())
+    }
+
     fn reload_scores(&mut self) -> std::result::Result<(), anyhow::Error> {
         let mut songs = self.state.songs.values();
         self.score_provider
@@ -396,6 +943,11 @@ impl SongSelectScene {
     }
 }
 
+fn note_score_seen(score: &Score) {
+    let last_seen = &mut GameConfig::get_mut().song_select.last_score_seen;
+    *last_seen = (*last_seen).max(score.timestamp);
+}
+
 fn add_preview_source<T: Source<Item = f32> + Send + 'static>(
     preview: T,
     skip: Duration,
@@ -403,19 +955,21 @@ fn add_preview_source<T: Source<Item = f32> + Send + 'static>(
     suspended: Arc<AtomicBool>,
     preview_playing: Arc<AtomicU64>,
     preview_finished: Arc<AtomicUsize>,
+    menu_audio: Arc<MenuAudio>,
     owner: &owned_source::Marker,
-    song_id_u64: u64,
+    preview_key: u64,
     mixer: RuscMixer,
 ) {
     let mut amp = 1.0f32;
-    preview_playing.store(song_id_u64, std::sync::atomic::Ordering::Relaxed);
+    let mut duck_gain = 1.0f32;
+    preview_playing.store(preview_key, std::sync::atomic::Ordering::Relaxed);
     preview_finished.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
     let source = take_duration_fade(
         rodio::source::Source::skip_duration(preview, skip)
             .pausable(false)
             .stoppable(),
-        duration,
+        duration.min(MAX_PREVIEW_LENGTH),
         Duration::from_millis(500),
         preview_finished,
     )
@@ -431,7 +985,7 @@ fn add_preview_source<T: Source<Item = f32> + Send + 'static>(
 
         let amp = &mut amp;
         let current_preview = preview_playing.load(std::sync::atomic::Ordering::Relaxed);
-        if current_preview != song_id_u64 {
+        if current_preview != preview_key {
             *amp -= 1.0 / 50.0;
             if *amp < 0.0 {
                 state.inner_mut().inner_mut().inner_mut().stop();
@@ -439,10 +993,11 @@ fn add_preview_source<T: Source<Item = f32> + Send + 'static>(
         } else if *amp < 1.0 {
             *amp += 1.0 / 50.0;
         }
-        state.set_factor(amp.clamp(0.0, 1.0));
+        menu_audio.step_gain(&mut duck_gain);
+        state.set_factor(amp.clamp(0.0, 1.0) * duck_gain);
     });
 
-    mixer.as_ref().add(owned_source(source, owner));
+    mixer.as_ref().add_resampled(owned_source(source, owner));
 }
 
 impl Scene for SongSelectScene {
@@ -463,6 +1018,8 @@ impl Scene for SongSelectScene {
         let render_sorting: Function = self.sort_lua.globals().get("render")?;
         render_sorting.call((dt / 1000.0, self.menu_state == MenuState::Sorting))?;
 
+        self.leaderboard.render(dt)?;
+
         self.settings_dialog.render(dt)?;
 
         Ok(())
@@ -472,6 +1029,52 @@ impl Scene for SongSelectScene {
         self.suspended.load(std::sync::atomic::Ordering::Relaxed)
     }
 
+    fn has_egui(&self) -> bool {
+        self.leaderboard.has_egui() || self.scan_failed.is_some()
+    }
+
+    fn render_egui(&mut self, ctx: &egui::Context) -> Result<()> {
+        self.leaderboard.render_egui(ctx);
+
+        if let Some(progress) = self.scan_failed {
+            let mut log_paths = false;
+            let mut dismiss = false;
+            egui::Window::new("Scan failures")
+                .title_bar(false)
+                .resizable(false)
+                .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+                .show(ctx, |ui| {
+                    if progress.failed > 0 {
+                        ui.label(format!("{} charts failed to load", progress.failed));
+                    }
+                    if progress.missing_audio > 0 {
+                        ui.label(format!("{} charts have no audio", progress.missing_audio));
+                    }
+                    ui.horizontal(|ui| {
+                        log_paths = ui.button("Log paths").clicked();
+                        dismiss = ui.button("Close").clicked();
+                    });
+                });
+
+            if log_paths {
+                let (failures, missing_audio) = {
+                    let provider = self.song_provider.read().expect("Lock error");
+                    (provider.scan_failures(), provider.scan_missing_audio())
+                };
+                for path in failures {
+                    warn!("Failed to load chart: {}", path.display());
+                }
+                for path in missing_audio {
+                    warn!("No audio found for chart: {}", path.display());
+                }
+            }
+            if dismiss || log_paths {
+                self.scan_failed = None;
+            }
+        }
+        Ok(())
+    }
+
     fn debug_ui(&mut self, ctx: &egui::Context) -> Result<()> {
         let song_count = self.state.songs.len();
 
@@ -503,6 +1106,8 @@ impl Scene for SongSelectScene {
                             }
                         }
                         ui.end_row();
+                        ui.checkbox(&mut self.state.versus, "Versus");
+                        ui.end_row();
                         if ui.button("Start").clicked() {
                             self.suspend();
                             let state = &mut self.state;
@@ -529,11 +1134,35 @@ impl Scene for SongSelectScene {
                                     diff,
                                     song,
                                     loader,
-                                    autoplay: crate::game_main::AutoPlay::None
+                                    autoplay: crate::game_main::AutoPlay::None,
+                                    versus: state.versus,
                                 })
                                 .is_ok());
                         }
                         ui.end_row();
+                        if ui.button("Edit metadata").clicked() {
+                            let state = &self.state;
+                            let song = state
+                                .songs
+                                .get(state.selected_index as usize)
+                                .ok_or(anyhow!("Selected index not in collection"))?;
+                            let diff_id = song
+                                .difficulties
+                                .read()
+                                .expect("Lock error")
+                                .get(state.selected_diff_index as usize)
+                                .ok_or(anyhow!("Selected difficulty not in song"))?
+                                .id
+                                .clone();
+                            let id = SongDiffId::SongDiff(song.id.clone(), diff_id);
+                            let meta = self
+                                .song_provider
+                                .read()
+                                .expect("Lock error")
+                                .get_metadata(&id)?;
+                            self.metadata_edit = Some((id, meta));
+                        }
+                        ui.end_row();
                         Ok(())
                     } else {
                         ui.label("No songs");
@@ -542,6 +1171,8 @@ impl Scene for SongSelectScene {
                 })
         });
 
+        self.metadata_editor(ctx);
+
         Ok(())
     }
 
@@ -550,7 +1181,8 @@ impl Scene for SongSelectScene {
 
         let lua_provider = self.services.get_required::<LuaProvider>();
 
-        self.settings_dialog.init_lua(&lua_provider)?;
+        self.settings_dialog
+            .init_lua(&lua_provider, app_control_tx.clone())?;
         self.program_control = Some(app_control_tx);
         lua_provider.register_libraries(self.lua.clone(), "songselect/songwheel.lua")?;
         lua_provider
@@ -558,13 +1190,16 @@ impl Scene for SongSelectScene {
 
         lua_provider.register_libraries(self.filter_lua.clone(), "songselect/filterwheel.lua")?;
         lua_provider.register_libraries(self.sort_lua.clone(), "songselect/sortwheel.lua")?;
+        self.leaderboard.init(&lua_provider);
         (self.filters, self.sorts) = self.update_filter_sort_lua()?;
 
         let mut bgm_amp = 1_f32;
+        let mut duck_gain = 1_f32;
         let preview_playing = self.state.preview_finished.clone();
         let suspended = self.suspended.clone();
-        self.mixer.add(owned_source(
-            rodio::source::Zero::new(2, 44100) //TODO: Load something from skin audio
+        let menu_audio = self.menu_audio.clone();
+        self.mixer.add_resampled(owned_source(
+            rodio::source::Zero::new(2, mixer_sample_rate()) //TODO: Load something from skin audio
                 .amplify(0.2)
                 .pausable(false)
                 .amplify(1.0)
@@ -580,7 +1215,8 @@ impl Scene for SongSelectScene {
                         *amp -= 1.0 / 50.0;
                     }
                     *amp = amp.clamp(0.0, 1.0);
-                    state.set_factor(*amp);
+                    menu_audio.step_gain(&mut duck_gain);
+                    state.set_factor(*amp * duck_gain);
                 }),
             &self.sample_owner,
         ));
@@ -588,17 +1224,49 @@ impl Scene for SongSelectScene {
         Ok(())
     }
 
-    fn tick(&mut self, _dt: f64, _knob_state: LaserState) -> Result<()> {
+    fn tick(&mut self, _dt: f64, knob_state: LaserState) -> Result<()> {
         profile_function!();
         if self.suspended.load(std::sync::atomic::Ordering::Relaxed) {
             return Ok(());
         }
+        if self.settings_dialog.show {
+            self.settings_dialog.on_knob(knob_state);
+        } else {
+            let song_delta = knob_state.get_axis(kson::Side::Right).delta;
+            self.song_advance += song_delta;
+            self.song_knob.add(song_delta);
+            self.diff_advance += knob_state.get_axis(kson::Side::Left).delta;
+        }
         let song_advance_steps = (self.song_advance / KNOB_NAV_THRESHOLD).trunc() as i32;
         self.song_advance -= song_advance_steps as f32 * KNOB_NAV_THRESHOLD;
+        let mut song_acceleration = self
+            .song_knob
+            .tick(_dt, &GameConfig::get().song_select.knob_acceleration);
+        if GameConfig::get().song_select.knob_acceleration.group_button
+            && self.input_state.is_button_held(GROUP_BUTTON).is_some()
+        {
+            song_acceleration = Acceleration::Groups;
+        }
 
         let diff_advance_steps = (self.diff_advance / KNOB_NAV_THRESHOLD).trunc() as i32;
         self.diff_advance -= diff_advance_steps as f32 * KNOB_NAV_THRESHOLD;
 
+        if song_advance_steps != 0 || diff_advance_steps != 0 {
+            self.radio_input();
+        }
+        // The radio waits while the player is in a menu of song select
+        let radio_advance = self.menu_state == MenuState::Songs
+            && !self.settings_dialog.show
+            && !self.state.search_input_active
+            && self.radio.tick(
+                _dt,
+                self.state
+                    .preview_finished
+                    .load(std::sync::atomic::Ordering::Relaxed)
+                    > 0,
+            );
+        let song_advance_steps = if radio_advance { 1 } else { song_advance_steps };
+
         // Tick song audio preview
         if song_advance_steps == 0
             && self.state.preview_countdown > 0.0
@@ -607,17 +1275,38 @@ impl Scene for SongSelectScene {
             if self.state.preview_countdown <= _dt {
                 //Start playing preview
                 self.start_preview();
+                self.request_density_strips();
             }
             self.state.preview_countdown -= _dt;
+        } else if radio_advance {
+            // Start right away so the previous preview crossfades into it
+            self.state.preview_countdown = 1.0;
         } else if song_advance_steps != 0 {
             self.state.preview_countdown = 1500.0;
         }
 
+        let selected_song = self
+            .state
+            .songs
+            .get(self.state.selected_index as usize)
+            .map(|s| s.id.clone());
+        if selected_song != self.jackets_song {
+            self.jackets_song = selected_song;
+            self.jacket_countdown = JACKET_PRELOAD_DELAY;
+        } else if self.jacket_countdown > 0.0 {
+            self.jacket_countdown -= _dt;
+            if self.jacket_countdown <= 0.0 {
+                self.request_jackets();
+            }
+        }
+
         let mut songs_dirty = false;
-        let mut index_dirty = false;
+        // Cleared when the songs have to be sent to the skin again as a whole
+        let mut changes = Some(vec![]);
+        let mut scores_needed = false;
 
         let had_no_songs = self.state.songs.is_empty();
-        let selected_index: SongId = self
+        let selected_id: SongId = self
             .state
             .songs
             .get(self.state.selected_index as _)
@@ -630,32 +1319,38 @@ impl Scene for SongSelectScene {
             match provider_event {
                 SongProviderEvent::SongsAdded(new_songs) => {
                     songs_dirty = true;
+                    scores_needed = true;
+                    if let Some(changes) = changes.as_mut() {
+                        changes.push(WheelChange::Added(
+                            self.state.songs.len(),
+                            new_songs.clone(),
+                        ));
+                    }
                     self.state.songs.append(new_songs);
                 }
                 SongProviderEvent::SongsRemoved(removed_ids) => {
                     songs_dirty = true;
-                    if removed_ids.contains(&selected_index) {
-                        self.state.selected_index = 0;
-                        index_dirty = true;
+                    if let Some(changes) = changes.as_mut() {
+                        let indices = self
+                            .state
+                            .songs
+                            .iter()
+                            .positions(|song| removed_ids.contains(&song.id))
+                            .collect();
+                        changes.push(WheelChange::Removed(indices));
                     }
                     self.state.songs.remove(removed_ids)
                 }
                 SongProviderEvent::OrderChanged(order) => {
                     songs_dirty = true;
-                    let current_index = self.state.selected_index;
-
-                    let id = self
-                        .state
-                        .songs
-                        .get(self.state.selected_index as usize)
-                        .map(|x| x.id.clone())
-                        .unwrap_or_default();
-
+                    scores_needed = true;
+                    changes = None;
                     self.state.songs.set_order(order);
-                    self.state.selected_index =
-                        self.state.songs.find_index(&id).unwrap_or_default() as _;
-
-                    index_dirty = self.state.selected_index != current_index;
+                }
+                SongProviderEvent::GroupsChanged(groups) => {
+                    // Only headers change, the selected index stays on the same song
+                    songs_dirty = true;
+                    self.state.songs.set_groups(groups);
                 }
                 SongProviderEvent::StatusUpdate(s) => {
                     self.state.search_status = s;
@@ -665,10 +1360,34 @@ impl Scene for SongSelectScene {
             }
         }
 
+        let scan_progress = self
+            .song_provider
+            .read()
+            .expect("Lock error")
+            .scan_progress();
+        if scan_progress != self.scan_progress {
+            if let Some(progress) =
+                scan_progress.filter(|p| p.done && (p.failed > 0 || p.missing_audio > 0))
+            {
+                self.scan_failed = Some(progress);
+            }
+            self.scan_progress = scan_progress;
+            self.state.search_status = scan_progress.map(|p| p.to_string()).unwrap_or_default();
+            let raw_state: mlua::Table = self.lua.globals().get("songwheel")?;
+            raw_state.set("searchStatus", self.state.search_status.clone())?;
+        }
+
         while let Ok(score_event) = self.score_events.try_recv() {
             songs_dirty = true;
             match score_event {
                 ScoreProviderEvent::NewScore(id, score) => {
+                    note_score_seen(&score);
+                    if let Some(DiffId(SongId::StringId(hash))) = id.get_diff() {
+                        self.leaderboard.invalidate(hash);
+                    }
+                    if let Some(changes) = changes.as_mut() {
+                        changes.push(WheelChange::Updated(id.clone()));
+                    }
                     self.song_provider
                         .write()
                         .expect("Lock error")
@@ -677,10 +1396,105 @@ impl Scene for SongSelectScene {
             }
         }
 
+        for id in take_broken_audio() {
+            let (Some(index), Some(diff_id)) = (self.song_index(&id), id.get_diff()) else {
+                continue;
+            };
+            for diff in self.state.songs[index]
+                .difficulties
+                .write()
+                .expect("Lock error")
+                .iter_mut()
+                .filter(|d| d.id == *diff_id)
+            {
+                diff.audio_broken = true;
+            }
+            songs_dirty = true;
+            if let Some(changes) = changes.as_mut() {
+                changes.push(WheelChange::Updated(id));
+            }
+        }
+
+        let ready_strips: Vec<_> = {
+            let thumbnailer = self.thumbnailer.read().expect("Lock error");
+            self.pending_strips
+                .iter()
+                .filter_map(|(id, hash)| {
+                    Some((id.clone(), hash.clone(), thumbnailer.density_strip(hash)?))
+                })
+                .collect()
+        };
+        for (id, hash, path) in ready_strips {
+            self.pending_strips.retain(|(_, pending)| *pending != hash);
+            let (Some(index), Some(diff_id)) = (self.song_index(&id), id.get_diff()) else {
+                continue;
+            };
+            for diff in self.state.songs[index]
+                .difficulties
+                .write()
+                .expect("Lock error")
+                .iter_mut()
+                .filter(|d| d.id == *diff_id)
+            {
+                diff.density_strip_path = Some(path.clone());
+            }
+            songs_dirty = true;
+            if let Some(changes) = changes.as_mut() {
+                changes.push(WheelChange::Updated(id));
+            }
+        }
+
+        let ready_jackets: Vec<_> = {
+            let vgfx = self.vgfx.read().expect("Lock error");
+            self.pending_jackets
+                .iter()
+                .filter(|(_, path)| vgfx.is_image_loaded(path))
+                .cloned()
+                .collect()
+        };
+        for (song_id, path) in ready_jackets {
+            self.pending_jackets.retain(|(_, pending)| *pending != path);
+            let Some(index) = self.state.songs.find_index(&song_id) else {
+                continue;
+            };
+            let mut diffs = self.state.songs[index]
+                .difficulties
+                .write()
+                .expect("Lock error");
+            // Difficulties sharing the jacket all get it at once
+            for diff in diffs.iter_mut().filter(|d| d.jacket_path == path) {
+                diff.jacket_loaded = true;
+            }
+            songs_dirty = true;
+            if let (Some(changes), Some(diff)) = (changes.as_mut(), diffs.first()) {
+                changes.push(WheelChange::Updated(SongDiffId::SongDiff(
+                    song_id,
+                    diff.id.clone(),
+                )));
+            }
+        }
+
         if songs_dirty {
             profile_scope!("Updating state after songs change");
-            self.reload_scores()?;
-            self.update_lua()?;
+            let index = self.state.songs.retarget_after_change(&selected_id) as i32;
+            let mut index_dirty = index != self.state.selected_index;
+            self.state.selected_index = index;
+            // New scores are already added to their songs
+            if scores_needed {
+                self.reload_scores()?;
+            }
+            self.state.groups = self
+                .state
+                .songs
+                .group_ranges()
+                .into_iter()
+                .map(|g| SongGroup {
+                    title: g.title,
+                    first: g.start + 1,
+                    count: g.count,
+                })
+                .collect();
+            self.update_songs_lua(changes)?;
 
             if had_no_songs {
                 if let Some(id) = GameConfig::get().song_select.last_played.get_song() {
@@ -719,8 +1533,8 @@ impl Scene for SongSelectScene {
 
         match self.menu_state {
             MenuState::Songs => {
-                self.state.selected_index = (self.state.selected_index + song_advance_steps)
-                    .rem_euclid(self.state.songs.len().max(1) as i32);
+                self.state.selected_index =
+                    self.advance_songs(song_advance_steps, song_acceleration);
                 if let Some(s) = self.state.songs.get(self.state.selected_index as _) {
                     let song_idx = s.id.as_u64();
                     self.song_provider
@@ -729,6 +1543,9 @@ impl Scene for SongSelectScene {
                         .set_current_index(song_idx as _);
 
                     if song_advance_steps != 0 {
+                        if !radio_advance {
+                            self.menu_audio.nav(NavSound::Tick);
+                        }
                         let set_song_idx: Function = self.lua.globals().get("set_index")?;
 
                         set_song_idx.call::<_, ()>(self.state.selected_index + 1)?;
@@ -748,31 +1565,50 @@ impl Scene for SongSelectScene {
                             );
 
                         if prev_diff != self.state.selected_diff_index {
+                            self.menu_audio.nav(NavSound::Tick);
                             let set_diff_idx: Function = self.lua.globals().get("set_diff")?;
                             set_diff_idx.call::<_, ()>(self.state.selected_diff_index + 1)?;
+
+                            // Difficulties may have their own preview, wait for the
+                            // selection to settle before switching to it
+                            if song_advance_steps == 0 {
+                                self.state.preview_countdown =
+                                    self.state.preview_countdown.max(DIFF_PREVIEW_DELAY);
+                            }
                         }
                     }
                 }
             }
             MenuState::Sorting => {
-                if !self.sorts.is_empty() {
-                    self.sort_index = diff_advance_steps
+                if (diff_advance_steps + song_advance_steps) != 0 {
+                    let position = if self.radio_selected {
+                        self.sorts.len()
+                    } else {
+                        self.sort_index
+                    };
+                    let position = diff_advance_steps
                         .add(song_advance_steps)
-                        .add(self.sort_index as i32)
-                        .rem_euclid(self.sorts.len() as _)
-                        as _;
-
-                    if (diff_advance_steps + song_advance_steps) != 0 {
+                        .add(position as i32)
+                        .rem_euclid(self.sorts.len() as i32 + 1)
+                        as usize;
+
+                    self.menu_audio.nav(NavSound::Tick);
+                    self.radio_selected = position == self.sorts.len();
+                    if !self.radio_selected {
+                        self.sort_index = position;
                         self.song_provider
                             .write()
                             .expect("Lock error")
                             .set_sort(self.sorts[self.sort_index]);
-                        let set_selection: Function =
-                            self.sort_lua.globals().get("set_selection")?;
-                        set_selection.call(self.sort_index + 1)?;
                     }
+                    let set_selection: Function = self.sort_lua.globals().get("set_selection")?;
+                    set_selection.call(position + 1)?;
                 }
             }
+            MenuState::Scores => {
+                self.leaderboard
+                    .advance(diff_advance_steps + song_advance_steps)?;
+            }
             MenuState::Levels => {
                 self.level_filter = (diff_advance_steps + song_advance_steps)
                     .add(self.level_filter as i32)
@@ -814,15 +1650,27 @@ impl Scene for SongSelectScene {
             self.start_song(autoplay);
         }
 
+        // The settings dialog takes over the buttons
+        if self.settings_dialog.show && self.start_held.take().is_some() {
+            self.set_demo_progress(0.0)?;
+        }
+
+        if let Some(held) = self.start_held {
+            let held = held.elapsed().unwrap_or_default();
+            if held >= DEMO_HOLD {
+                self.start_held = None;
+                self.set_demo_progress(0.0)?;
+                self.start_song(AutoPlay::All);
+            } else {
+                self.set_demo_progress(held.as_secs_f32() / DEMO_HOLD.as_secs_f32())?;
+            }
+        }
+
         Ok(())
     }
 
     fn on_event(&mut self, event: &Event<UscInputEvent>) {
         if self.settings_dialog.show {
-            if let Event::UserEvent(e) = event {
-                self.settings_dialog.on_input(e);
-            }
-
             return;
         }
 
@@ -883,6 +1731,14 @@ impl Scene for SongSelectScene {
                         _ = self.update_filter_sort_lua();
                     }
                 }
+                crate::companion_interface::ClientEvent::StartDemo
+                    if self.menu_state == MenuState::Songs =>
+                {
+                    self.start_song(AutoPlay::All);
+                }
+                crate::companion_interface::ClientEvent::SetRadio(enabled) => {
+                    self.set_radio(*enabled);
+                }
                 crate::companion_interface::ClientEvent::SetSongSort(song_sort) => {
                     if let Some(pos) = self.sorts.iter().find_position(|x| **x == *song_sort) {
                         self.sort_index = pos.0;
@@ -939,17 +1795,21 @@ impl Scene for SongSelectScene {
             }
 
             if updated {
+                self.radio_input();
                 self.on_search();
             }
         }
 
-        if let Event::UserEvent(UscInputEvent::Laser(ls, _time)) = event {
-            self.song_advance += LaserAxis::from(ls.get(kson::Side::Right)).delta;
-            self.diff_advance += LaserAxis::from(ls.get(kson::Side::Left)).delta;
+        if let Event::UserEvent(UscInputEvent::Player2(e)) = event {
+            if let UscInputEvent::Button(UscButton::Start, ElementState::Pressed, _) = e.as_ref() {
+                self.state.versus = !self.state.versus;
+                _ = self.update_lua();
+            }
         }
     }
 
     fn on_button_pressed(&mut self, button: crate::button_codes::UscButton, timestamp: SystemTime) {
+        self.radio_input();
         if self.settings_dialog.show {
             self.settings_dialog.on_button_press(button);
             self.settings_closed = SystemTime::now();
@@ -968,12 +1828,25 @@ impl Scene for SongSelectScene {
 
         match button {
             UscButton::Back if MenuState::Songs == self.menu_state => {
+                self.menu_audio.nav(NavSound::Cancel);
                 self.closed = true;
             }
+            UscButton::Back | LEADERBOARD_BUTTON if MenuState::Scores == self.menu_state => {
+                self.menu_audio.nav(NavSound::Cancel);
+                self.leaderboard.close();
+                self.menu_state = MenuState::Songs;
+            }
+            LEADERBOARD_BUTTON if MenuState::Songs == self.menu_state => {
+                if let Err(e) = self.open_leaderboard() {
+                    warn!("Could not open leaderboard: {e}");
+                }
+            }
             UscButton::Start => {
+                self.menu_audio.nav(NavSound::Confirm);
                 match self.menu_state {
                     MenuState::Songs => {
-                        self.start_song(AutoPlay::None);
+                        // Started on release, or as a demo if held long enough
+                        self.start_held = Some(timestamp);
                     }
                     MenuState::Levels => {
                         self.menu_state = MenuState::Folders;
@@ -981,7 +1854,10 @@ impl Scene for SongSelectScene {
                     MenuState::Folders => {
                         self.menu_state = MenuState::Levels;
                     }
-                    MenuState::Sorting => {}
+                    MenuState::Sorting if self.radio_selected => {
+                        self.set_radio(!self.radio.enabled());
+                    }
+                    MenuState::Sorting | MenuState::Scores => {}
                 }
 
                 if let MenuState::Folders | MenuState::Levels = self.menu_state {
@@ -994,12 +1870,9 @@ impl Scene for SongSelectScene {
                 if let Some(other_press_time) =
                     self.input_state.is_button_held(UscButton::FX(s.opposite()))
                 {
-                    let detla_ms = timestamp
-                        .duration_since(other_press_time)
-                        .unwrap_or_default()
-                        .as_millis();
+                    let detla_ms = press_interval(timestamp, other_press_time).as_millis();
                     if detla_ms < 100 && self.menu_state == MenuState::Songs {
-                        self.settings_dialog.show = true;
+                        self.settings_dialog.open();
                     }
                 }
             }
@@ -1021,9 +1894,17 @@ impl Scene for SongSelectScene {
                 .as_secs_f32()
                 < 0.25
         {
+            if button == UscButton::Start && self.start_held.take().is_some() {
+                _ = self.set_demo_progress(0.0);
+            }
             return;
         }
 
+        if button == UscButton::Start && self.start_held.take().is_some() {
+            _ = self.set_demo_progress(0.0);
+            self.start_song(AutoPlay::None);
+        }
+
         if let UscButton::FX(side) = button {
             self.menu_state = match (side, self.menu_state) {
                 (kson::Side::Left, MenuState::Songs) => MenuState::Folders,
@@ -1034,6 +1915,7 @@ impl Scene for SongSelectScene {
                 (kson::Side::Right, MenuState::Levels) => MenuState::Levels,
                 (kson::Side::Right, MenuState::Folders) => MenuState::Folders,
                 (kson::Side::Right, MenuState::Sorting) => MenuState::Songs,
+                (_, MenuState::Scores) => MenuState::Scores,
             };
 
             if let MenuState::Folders | MenuState::Levels = self.menu_state {
@@ -1049,10 +1931,14 @@ impl Scene for SongSelectScene {
     }
 
     fn resume(&mut self) {
+        self.menu_audio.restore();
         // Reload scores for redundancy
         if let Some(e) = self.reload_scores().err() {
             warn!("Could not reload scores: {e}");
         }
+        if let Err(e) = self.select_last_played() {
+            warn!("Could not select the last played song: {e}");
+        }
 
         self.suspended
             .store(false, std::sync::atomic::Ordering::Relaxed);
@@ -1074,6 +1960,7 @@ impl Scene for SongSelectScene {
             sort_index: self.sort_index,
             filters: self.filters.clone(),
             sorts: self.sorts.clone(),
+            radio: self.radio.enabled(),
         }
     }
 }
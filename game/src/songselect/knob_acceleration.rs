@@ -0,0 +1,139 @@
+use std::f32::consts::TAU;
+
+use crate::config::KnobAcceleration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Acceleration {
+    /// Songs moved per step
+    Songs(u32),
+    /// Every step moves to the next sort group
+    Groups,
+}
+
+/// Tracks how fast the song wheel knob is turned
+#[derive(Debug, Default)]
+pub struct KnobAccelerator {
+    /// Smoothed speed in rotations per second
+    speed: f32,
+    /// Knob movement since the last tick
+    travel: f32,
+}
+
+impl KnobAccelerator {
+    pub fn add(&mut self, delta: f32) {
+        // Turning back stops the acceleration right away
+        if delta * self.travel < 0.0 {
+            self.speed = 0.0;
+            self.travel = 0.0;
+        }
+        self.travel += delta;
+    }
+
+    pub fn tick(&mut self, dt_ms: f64, config: &KnobAcceleration) -> Acceleration {
+        let dt = (dt_ms as f32).max(1.0);
+        let current = self.travel.abs() / TAU / (dt / 1000.0);
+        self.travel = 0.0;
+
+        self.speed = if current >= self.speed {
+            current
+        } else {
+            (self.speed * (-dt / config.decay_ms.max(1.0)).exp()).max(current)
+        };
+
+        if config.group_speed > 0.0 && self.speed >= config.group_speed {
+            return Acceleration::Groups;
+        }
+
+        Acceleration::Songs(
+            config
+                .steps
+                .iter()
+                .filter(|(speed, _)| self.speed >= *speed)
+                .map(|(_, songs)| *songs)
+                .max()
+                .unwrap_or(1)
+                .max(1),
+        )
+    }
+}
+
+/// Index reached by moving `steps` sort groups from `index`, landing on the first song of a group
+pub fn advance_groups<K: PartialEq>(
+    index: usize,
+    len: usize,
+    steps: i32,
+    group: impl Fn(usize) -> K,
+) -> usize {
+    if len == 0 {
+        return 0;
+    }
+
+    let prev = |i: usize| (i + len - 1) % len;
+    let group_start = |mut i: usize| {
+        let key = group(i);
+        for _ in 1..len {
+            if group(prev(i)) != key {
+                break;
+            }
+            i = prev(i);
+        }
+        i
+    };
+
+    let mut index = index % len;
+    for _ in 0..steps.unsigned_abs() {
+        if steps > 0 {
+            let key = group(index);
+            for _ in 1..len {
+                index = (index + 1) % len;
+                if group(index) != key {
+                    break;
+                }
+            }
+        } else {
+            index = group_start(prev(group_start(index)));
+        }
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::{advance_groups, Acceleration, KnobAccelerator};
+    use crate::config::KnobAcceleration;
+
+    #[test]
+    fn accelerates_and_decays() {
+        let config = KnobAcceleration::default();
+        let mut knob = KnobAccelerator::default();
+
+        knob.add(TAU * 0.01);
+        assert_eq!(knob.tick(16.0, &config), Acceleration::Songs(1));
+
+        knob.add(TAU * 0.06);
+        assert_eq!(knob.tick(16.0, &config), Acceleration::Songs(10));
+
+        for _ in 0..60 {
+            knob.tick(16.0, &config);
+        }
+        assert_eq!(knob.tick(16.0, &config), Acceleration::Songs(1));
+
+        knob.add(TAU * 0.06);
+        knob.add(-TAU * 0.001);
+        assert_eq!(knob.tick(16.0, &config), Acceleration::Songs(1));
+    }
+
+    #[test]
+    fn group_steps() {
+        let keys = ['A', 'A', 'B', 'C', 'C', 'C'];
+        let group = |i: usize| keys[i];
+        assert_eq!(advance_groups(0, keys.len(), 1, group), 2);
+        assert_eq!(advance_groups(1, keys.len(), 2, group), 3);
+        assert_eq!(advance_groups(4, keys.len(), 1, group), 0);
+        assert_eq!(advance_groups(4, keys.len(), -1, group), 2);
+        assert_eq!(advance_groups(0, keys.len(), -1, group), 3);
+        assert_eq!(advance_groups(3, 1, 1, |_| 'A'), 0);
+    }
+}
@@ -0,0 +1,272 @@
+use std::{collections::HashMap, rc::Rc};
+
+use anyhow::Result;
+use itertools::Itertools;
+use log::warn;
+use serde::Serialize;
+use tealr::mlu::mlua::{Function, Lua, LuaSerdeExt};
+
+use crate::{
+    config::GameConfig, lua_service::LuaProvider, results::Score, song_provider::ScoreProvider,
+};
+
+/// Number of scores listed on a leaderboard
+pub const LEADERBOARD_SIZE: usize = 20;
+
+const SCRIPT: &str = "songselect/scores.lua";
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum ScoreSource {
+    Local,
+    #[serde(rename = "IR")]
+    Ir,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardEntry {
+    pub player_name: String,
+    pub score: i32,
+    pub badge: u8,
+    pub gauge: f32,
+    pub timestamp: i32,
+    /// `timestamp` formatted in local time
+    pub date: String,
+    pub source: ScoreSource,
+}
+
+impl From<&Score> for LeaderboardEntry {
+    fn from(score: &Score) -> Self {
+        let date = chrono::DateTime::from_timestamp(score.timestamp as _, 0)
+            .map(|d| {
+                d.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default();
+
+        Self {
+            player_name: score.player_name.clone(),
+            score: score.score,
+            badge: score.badge,
+            gauge: score.gauge,
+            timestamp: score.timestamp,
+            date,
+            source: if score.is_local {
+                ScoreSource::Local
+            } else {
+                ScoreSource::Ir
+            },
+        }
+    }
+}
+
+/// Best `LEADERBOARD_SIZE` scores, ties go to the earlier score
+pub fn top_scores(scores: &[Score]) -> Vec<LeaderboardEntry> {
+    scores
+        .iter()
+        .sorted_by_key(|s| (-s.score, s.timestamp))
+        .take(LEADERBOARD_SIZE)
+        .map(LeaderboardEntry::from)
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Board {
+    title: String,
+    scores: Vec<LeaderboardEntry>,
+    /// 1-based for lua
+    selected: usize,
+}
+
+/// Scores of a single chart shown over the song select.
+///
+/// Rendered by the skin's `songselect/scores.lua` when it has one, otherwise by egui.
+#[derive(Default)]
+pub struct Leaderboard {
+    lua: Option<Rc<Lua>>,
+    /// Entries by chart hash, kept until a new score is set on the chart
+    cache: HashMap<String, Vec<LeaderboardEntry>>,
+    board: Option<Board>,
+}
+
+impl Leaderboard {
+    pub fn init(&mut self, lua_provider: &LuaProvider) {
+        let path = GameConfig::get().skin_path().join("scripts").join(SCRIPT);
+        if !path.exists() {
+            return;
+        }
+
+        let lua = LuaProvider::new_lua();
+        match lua_provider.register_libraries(lua.clone(), SCRIPT) {
+            Ok(()) => self.lua = Some(lua),
+            Err(e) => warn!("Could not load {SCRIPT}, using fallback: {e}"),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.board.is_some()
+    }
+
+    pub fn open(
+        &mut self,
+        hash: &str,
+        title: String,
+        score_provider: &dyn ScoreProvider,
+    ) -> Result<()> {
+        let scores = match self.cache.get(hash) {
+            Some(scores) => scores.clone(),
+            None => {
+                let scores = top_scores(&score_provider.get_scores(hash)?);
+                self.cache.insert(hash.to_string(), scores.clone());
+                scores
+            }
+        };
+
+        self.board = Some(Board {
+            title,
+            scores,
+            selected: 1,
+        });
+        self.update_lua()
+    }
+
+    pub fn close(&mut self) {
+        self.board = None;
+    }
+
+    pub fn invalidate(&mut self, hash: &str) {
+        self.cache.remove(hash);
+    }
+
+    pub fn advance(&mut self, steps: i32) -> Result<()> {
+        let Some(board) = &mut self.board else {
+            return Ok(());
+        };
+        if steps == 0 || board.scores.is_empty() {
+            return Ok(());
+        }
+
+        board.selected = (board.selected as i32 + steps).clamp(1, board.scores.len() as _) as _;
+        let selected = board.selected;
+        self.set_lua_selection(selected)
+    }
+
+    fn set_lua_selection(&self, selected: usize) -> Result<()> {
+        if let Some(lua) = &self.lua {
+            if let Ok(set_selection) = lua.globals().get::<_, Function>("set_selection") {
+                set_selection.call::<_, ()>(selected)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn update_lua(&self) -> Result<()> {
+        let Some(lua) = &self.lua else {
+            return Ok(());
+        };
+        lua.globals()
+            .set("leaderboard", lua.to_value(&self.board)?)?;
+        self.set_lua_selection(1)
+    }
+
+    pub fn render(&self, dt: f64) -> Result<()> {
+        let Some(lua) = &self.lua else {
+            return Ok(());
+        };
+        let render: Function = lua.globals().get("render")?;
+        render.call((dt / 1000.0, self.is_open()))?;
+        Ok(())
+    }
+
+    /// Whether the leaderboard is drawn with egui
+    pub fn has_egui(&self) -> bool {
+        self.lua.is_none() && self.is_open()
+    }
+
+    pub fn render_egui(&self, ctx: &egui::Context) {
+        let Some(board) = &self.board else {
+            return;
+        };
+
+        egui::Window::new(board.title.as_str())
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                if board.scores.is_empty() {
+                    ui.label("No scores");
+                    return;
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("leaderboard-grid")
+                        .num_columns(7)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (i, entry) in board.scores.iter().enumerate() {
+                                let selected = i + 1 == board.selected;
+                                let row = ui.selectable_label(selected, format!("#{}", i + 1));
+                                if selected {
+                                    row.scroll_to_me(None);
+                                }
+                                ui.label(entry.player_name.as_str());
+                                ui.label(format!("{:08}", entry.score));
+                                ui.label(badge_name(entry.badge));
+                                ui.label(format!("{:.1}%", entry.gauge * 100.0));
+                                ui.label(entry.date.as_str());
+                                ui.label(match entry.source {
+                                    ScoreSource::Local => "Local",
+                                    ScoreSource::Ir => "IR",
+                                });
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+    }
+}
+
+fn badge_name(badge: u8) -> &'static str {
+    match badge {
+        1 => "Played",
+        2 => "Clear",
+        3 => "Hard clear",
+        4 => "Full combo",
+        5 => "Perfect",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{top_scores, ScoreSource, LEADERBOARD_SIZE};
+    use crate::results::Score;
+
+    #[test]
+    fn ranked_and_labeled() {
+        let mut scores: Vec<_> = (0..30)
+            .map(|i| Score {
+                score: 9_000_000 + i * 1000,
+                timestamp: 100 + i,
+                is_local: i % 2 == 0,
+                ..Default::default()
+            })
+            .collect();
+        scores.push(Score {
+            score: 9_029_000,
+            timestamp: 50,
+            is_local: true,
+            ..Default::default()
+        });
+
+        let top = top_scores(&scores);
+        assert_eq!(top.len(), LEADERBOARD_SIZE);
+        assert_eq!(top[0].timestamp, 50);
+        assert_eq!(top[1].timestamp, 129);
+        assert!(top.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_eq!(top[0].source, ScoreSource::Local);
+        assert_eq!(top[1].source, ScoreSource::Ir);
+    }
+}
@@ -0,0 +1,87 @@
+/// Milliseconds without input before the radio takes over the wheel
+pub const RADIO_IDLE: f64 = 5000.0;
+/// Milliseconds each song is played before the radio moves on
+pub const RADIO_INTERVAL: f64 = 20000.0;
+
+/// Moves the song wheel to the next song by itself while nobody is using it, so the previews play
+/// one after another
+#[derive(Debug, Default)]
+pub struct Radio {
+    enabled: bool,
+    /// Milliseconds since the last input
+    idle: f64,
+    /// Milliseconds since the last input or song change
+    song_time: f64,
+}
+
+impl Radio {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.idle = 0.0;
+        self.song_time = 0.0;
+    }
+
+    /// Whether the radio has taken over the wheel
+    pub fn on_air(&self) -> bool {
+        self.enabled && self.idle >= RADIO_IDLE
+    }
+
+    /// Input from the player, turns the radio off once it has taken over
+    pub fn input(&mut self) {
+        if self.on_air() {
+            log::info!("Radio cancelled by input");
+            self.enabled = false;
+        }
+        self.idle = 0.0;
+        self.song_time = 0.0;
+    }
+
+    /// Returns true when the wheel should move to the next song, which is after the interval or
+    /// as soon as the preview of the current one has ended
+    pub fn tick(&mut self, dt: f64, preview_playing: bool) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        self.idle += dt;
+        self.song_time += dt;
+
+        let next = self.on_air()
+            && (self.song_time >= RADIO_INTERVAL
+                || (!preview_playing && self.song_time >= RADIO_IDLE));
+        if next {
+            self.song_time = 0.0;
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Radio, RADIO_IDLE, RADIO_INTERVAL};
+
+    #[test]
+    fn advances_while_idle() {
+        let mut radio = Radio::default();
+        assert!(!radio.tick(RADIO_INTERVAL, false));
+
+        radio.set_enabled(true);
+        assert!(!radio.tick(RADIO_IDLE / 2.0, true));
+        // Input before it's on air only delays it
+        radio.input();
+        assert!(radio.enabled());
+        assert!(!radio.tick(RADIO_IDLE, true));
+        assert!(radio.on_air());
+        assert!(radio.tick(RADIO_INTERVAL - RADIO_IDLE, true));
+        assert!(!radio.tick(RADIO_IDLE, true));
+        // Previews shorter than the interval move on when they end
+        assert!(radio.tick(1.0, false));
+
+        radio.input();
+        assert!(!radio.enabled());
+        assert!(!radio.tick(RADIO_INTERVAL, false));
+    }
+}
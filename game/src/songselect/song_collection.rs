@@ -16,6 +16,18 @@ use super::Song;
 pub struct SongCollection {
     songs: HashMap<SongId, Arc<Song>>,
     order: Vec<SongId>,
+    groups: Vec<(String, Vec<SongId>)>,
+    /// Order as of the last [`SongCollection::retarget_after_change`]
+    previous_order: Vec<SongId>,
+}
+
+/// Run of songs in the collection shown under one header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupRange {
+    pub title: String,
+    /// Index of the first song of the group
+    pub start: usize,
+    pub count: usize,
 }
 
 pub struct SongCollectionIter<'a> {
@@ -31,6 +43,43 @@ impl SongCollection {
     pub fn set_order(&mut self, order: Vec<SongId>) {
         self.order = order;
     }
+    pub fn set_groups(&mut self, groups: Vec<(String, Vec<SongId>)>) {
+        self.groups = groups;
+    }
+
+    /// Groups as index ranges of the current order, songs that aren't in the order anymore are
+    /// left out so the ranges stay valid while the provider catches up
+    pub fn group_ranges(&self) -> Vec<GroupRange> {
+        let positions: HashMap<&SongId, usize> = self
+            .order
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id, i))
+            .collect();
+
+        let mut ranges: Vec<GroupRange> = vec![];
+        for (title, ids) in &self.groups {
+            for i in ids
+                .iter()
+                .filter_map(|id| positions.get(id))
+                .copied()
+                .sorted()
+            {
+                match ranges.last_mut() {
+                    Some(last) if last.title == *title && last.start + last.count == i => {
+                        last.count += 1
+                    }
+                    _ => ranges.push(GroupRange {
+                        title: title.clone(),
+                        start: i,
+                        count: 1,
+                    }),
+                }
+            }
+        }
+        ranges.sort_by_key(|r| r.start);
+        ranges
+    }
     pub fn append(&mut self, mut songs: Vec<Arc<Song>>) {
         for song in songs.drain(..) {
             self.order.push(song.id.clone());
@@ -43,10 +92,44 @@ impl SongCollection {
 
     pub fn add(&mut self, songs: Vec<Arc<Song>>, order: Vec<SongId>) {
         self.order = order;
+        self.previous_order = self.order.clone();
         for song in songs.iter() {
             self.songs.insert(song.id.clone(), song.clone());
         }
     }
+
+    /// Index to select after songs were added, removed or reordered. Stays on `old_id` if it is
+    /// still in the collection, otherwise moves to the closest song that survived, by position in
+    /// the order before the change, preferring the songs after it.
+    pub fn retarget_after_change(&mut self, old_id: &SongId) -> usize {
+        let previous_order = std::mem::replace(&mut self.previous_order, self.order.clone());
+        if let Some(index) = self.find_index(old_id) {
+            return index;
+        }
+
+        let Some(old_position) = previous_order.iter().position(|id| id == old_id) else {
+            return 0;
+        };
+
+        let positions: HashMap<&SongId, usize> = self
+            .order
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id, i))
+            .collect();
+
+        (1..previous_order.len())
+            .flat_map(|distance| {
+                [
+                    Some(old_position + distance),
+                    old_position.checked_sub(distance),
+                ]
+            })
+            .flatten()
+            .filter_map(|i| previous_order.get(i))
+            .find_map(|id| positions.get(id).copied())
+            .unwrap_or_default()
+    }
     pub fn get(&self, index: usize) -> Option<&Arc<Song>> {
         self.order.get(index).and_then(|id| self.songs.get(id))
     }
@@ -101,3 +184,67 @@ impl Index<usize> for SongCollection {
             .expect("Index out of bounds")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::{Song, SongCollection};
+    use crate::song_provider::SongId;
+
+    fn collection(count: i64) -> SongCollection {
+        let songs: Vec<_> = (0..count)
+            .map(|i| {
+                Arc::new(Song {
+                    id: SongId::IntId(i),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let order = songs.iter().map(|s| s.id.clone()).collect();
+        let mut collection = SongCollection::default();
+        collection.add(songs, order);
+        collection
+    }
+
+    fn ids(ids: &[i64]) -> Vec<SongId> {
+        ids.iter().copied().map(SongId::IntId).collect()
+    }
+
+    #[test]
+    fn removing_selected_song_selects_neighbour() {
+        let mut songs = collection(5);
+        songs.remove(HashSet::from([SongId::IntId(2)]));
+        assert_eq!(songs.retarget_after_change(&SongId::IntId(2)), 2);
+        assert_eq!(songs[2].id, SongId::IntId(3));
+
+        // Nothing after it survived, fall back to the closest song before it
+        songs.remove(HashSet::from([SongId::IntId(3), SongId::IntId(4)]));
+        assert_eq!(songs.retarget_after_change(&SongId::IntId(3)), 1);
+        assert_eq!(songs[1].id, SongId::IntId(1));
+
+        songs.remove(HashSet::from([SongId::IntId(0), SongId::IntId(1)]));
+        assert_eq!(songs.retarget_after_change(&SongId::IntId(1)), 0);
+    }
+
+    #[test]
+    fn removing_earlier_song_shifts_index() {
+        let mut songs = collection(5);
+        songs.remove(HashSet::from([SongId::IntId(0), SongId::IntId(1)]));
+        assert_eq!(songs.retarget_after_change(&SongId::IntId(3)), 1);
+    }
+
+    #[test]
+    fn reordering_follows_selected_song() {
+        let mut songs = collection(5);
+        songs.set_order(ids(&[4, 2, 0, 3, 1]));
+        assert_eq!(songs.retarget_after_change(&SongId::IntId(3)), 3);
+
+        // Selected song filtered out while shuffling, 1 was after it in the shown order
+        songs.set_order(ids(&[1, 0, 2, 4]));
+        assert_eq!(songs.retarget_after_change(&SongId::IntId(3)), 0);
+
+        songs.set_order(ids(&[1, 0]));
+        assert_eq!(songs.retarget_after_change(&SongId::IntId(4)), 1);
+    }
+}
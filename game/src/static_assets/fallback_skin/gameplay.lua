@@ -0,0 +1,21 @@
+-- Built-in fallback, used when the selected skin has no gameplay.lua
+local score = 0
+local combo = 0
+
+-- The chart only starts once the intro is done
+function render_intro(deltaTime)
+    return true
+end
+
+function render(deltaTime)
+    fallback.text(string.format("%08d", score), 20, 20, 32)
+    fallback.text(string.format("Combo: %d", combo), 20, 60, 24)
+end
+
+function update_score(newScore)
+    score = newScore
+end
+
+function update_combo(newCombo)
+    combo = newCombo
+end
@@ -0,0 +1,6 @@
+-- Built-in fallback, used when the selected skin has no gamesettingsdialog.lua
+function render(deltaTime, visible)
+    if visible then
+        fallback.text("Skin is missing gamesettingsdialog.lua", 20, 20, 24)
+    end
+end
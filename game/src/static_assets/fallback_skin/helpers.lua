@@ -0,0 +1,18 @@
+-- Shared helpers for the built-in fallback skin
+fallback = {}
+
+function fallback.text(str, x, y, size)
+    gfx.BeginPath()
+    gfx.TextAlign(gfx.TEXT_ALIGN_LEFT + gfx.TEXT_ALIGN_TOP)
+    gfx.FontSize(size or 24)
+    gfx.FillColor(255, 255, 255)
+    gfx.Text(str, x, y)
+end
+
+function fallback.clear()
+    local resx, resy = game.GetResolution()
+    gfx.BeginPath()
+    gfx.Rect(0, 0, resx, resy)
+    gfx.FillColor(20, 20, 30)
+    gfx.Fill()
+end
@@ -0,0 +1,9 @@
+-- Built-in fallback, used when the selected skin has no result.lua
+function render(deltaTime)
+    fallback.clear()
+    fallback.text(string.format("%s - %s", result.title, result.artist), 20, 20, 32)
+    fallback.text(string.format("Score: %08d (%s)", result.score, result.grade), 20, 70, 32)
+    fallback.text(
+        string.format("Critical: %d  Near: %d  Error: %d", result.perfects, result.goods, result.misses),
+        20, 120, 24)
+end
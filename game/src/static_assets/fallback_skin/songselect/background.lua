@@ -0,0 +1,4 @@
+-- Built-in fallback, used when the selected skin has no songselect/background.lua
+render = function(deltaTime)
+    fallback.clear()
+end
@@ -0,0 +1,6 @@
+-- Built-in fallback, used when the selected skin has no songselect/filterwheel.lua
+render = function(deltaTime, shown)
+end
+
+set_selection = function(index, isFolder)
+end
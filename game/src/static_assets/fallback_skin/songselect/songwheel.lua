@@ -0,0 +1,27 @@
+-- Built-in fallback, used when the selected skin has no songselect/songwheel.lua
+local selectedIndex = 1
+local selectedDiff = 1
+
+render = function(deltaTime)
+    fallback.text("Skin is missing songselect/songwheel.lua", 20, 20, 24)
+    local song = songwheel.songs[selectedIndex]
+    if song == nil then
+        fallback.text("No songs", 20, 60, 32)
+        return
+    end
+    fallback.text(song.title, 20, 60, 32)
+    fallback.text(song.artist, 20, 100, 24)
+    local diff = song.difficulties[selectedDiff]
+    if diff ~= nil then
+        fallback.text(string.format("Level %d", diff.level), 20, 140, 24)
+    end
+end
+
+set_index = function(newIndex)
+    selectedIndex = newIndex
+    return 0
+end
+
+set_diff = function(newDiff)
+    selectedDiff = newDiff
+end
@@ -0,0 +1,6 @@
+-- Built-in fallback, used when the selected skin has no songselect/sortwheel.lua
+function render(deltaTime, shown)
+end
+
+function set_selection(index)
+end
@@ -0,0 +1,13 @@
+-- Built-in fallback, used when the selected skin has no songtransition.lua
+function render(deltaTime)
+    fallback.clear()
+    fallback.text(string.format("Loading... %d%%", (progress or 0) * 100), 20, 20, 32)
+    return true
+end
+
+function render_out(deltaTime)
+    return true
+end
+
+function reset()
+end
@@ -0,0 +1,29 @@
+-- Built-in fallback, used when the selected skin has no titlescreen.lua
+local buttons = {
+    { "Start", Menu.Start },
+    { "Settings", Menu.Settings },
+    { "Exit", Menu.Exit },
+}
+local selected = 1
+
+function render(deltaTime)
+    fallback.clear()
+    fallback.text("Skin is missing titlescreen.lua", 20, 20, 32)
+    for i, button in ipairs(buttons) do
+        local prefix = i == selected and "> " or "  "
+        fallback.text(prefix .. button[1], 40, 60 + i * 40, 32)
+    end
+    if game_version then
+        fallback.text(game_version, 20, 80 + (#buttons + 1) * 40, 20)
+    end
+end
+
+function button_pressed(button)
+    if button == game.BUTTON_STA then
+        buttons[selected][2]()
+    elseif button == game.BUTTON_BCK then
+        Menu.Exit()
+    elseif button == game.BUTTON_BTA then
+        selected = selected % #buttons + 1
+    end
+end
@@ -0,0 +1,15 @@
+in vec2 fsTex;
+out vec4 target;
+
+uniform sampler2D mainTex;
+
+void main()
+{
+    // vgfx draws with premultiplied alpha
+    vec4 col = texture(mainTex, fsTex);
+    if (col.a > 0.0)
+    {
+        col.rgb /= col.a;
+    }
+    target = col;
+}
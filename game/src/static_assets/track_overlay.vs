@@ -0,0 +1,14 @@
+in vec3 inPos;
+in vec2 inTex;
+out vec2 fsTex;
+
+uniform mat4 proj;
+uniform mat4 camera;
+uniform mat4 world;
+
+void main()
+{
+    // The top of the canvas is the far end of the track
+    fsTex = vec2(inTex.x, 1.0 - inTex.y);
+    gl_Position = proj * camera * world * vec4(inPos, 1);
+}
@@ -0,0 +1,15 @@
+in vec2 texVp;
+out vec4 target;
+
+uniform ivec2 viewport;
+uniform vec2 videoSize;
+uniform sampler2D mainTex;
+
+void main()
+{
+    // Scale the video to cover the viewport, cropping the sides that don't fit
+    vec2 vp = vec2(viewport);
+    float scale = max(vp.x / videoSize.x, vp.y / videoSize.y);
+    vec2 uv = (texVp - 0.5 * vp) / (videoSize * scale) + vec2(0.5);
+    target = vec4(texture(mainTex, vec2(uv.x, 1.0 - uv.y)).rgb, 1.0);
+}
@@ -62,7 +62,10 @@ impl DurationFilter {
 const NANOS_PER_SEC: u64 = 1_000_000_000;
 
 /// A source that truncates the given source to a certain duration.
-#[derive(Clone, Debug)]
+///
+/// `signal` is decremented exactly once, when the source ends, whether the duration ran out,
+/// the input ended early or the source was dropped before finishing.
+#[derive(Debug)]
 pub struct TakeDurationFade<I> {
     input: I,
     remaining_duration: Duration,
@@ -84,7 +87,7 @@ where
     /// Returns the duration elapsed for each sample extracted.
     #[inline]
     fn get_duration_per_sample(input: &I) -> Duration {
-        let ns = NANOS_PER_SEC / (input.sample_rate() as u64 * input.channels() as u64);
+        let ns = NANOS_PER_SEC / (input.sample_rate() as u64 * input.channels() as u64).max(1);
         // \|/ the maximum value of `ns` is one billion, so this can't fail
         Duration::new(0, ns as u32)
     }
@@ -101,12 +104,6 @@ where
         &mut self.input
     }
 
-    /// Returns the inner source.
-    #[inline]
-    pub fn into_inner(self) -> I {
-        self.input
-    }
-
     pub fn set_filter_fadeout(&mut self, fade: Duration) {
         self.filter = Some(DurationFilter::FadeOut(fade));
     }
@@ -114,7 +111,9 @@ where
     pub fn clear_filter(&mut self) {
         self.filter = None;
     }
+}
 
+impl<I> TakeDurationFade<I> {
     fn send_signal(&mut self) {
         if !self.signal_sent {
             self.signal
@@ -166,6 +165,12 @@ where
     // TODO: size_hint
 }
 
+impl<I> Drop for TakeDurationFade<I> {
+    fn drop(&mut self) {
+        self.send_signal();
+    }
+}
+
 impl<I> Source for TakeDurationFade<I>
 where
     I: Iterator + Source,
@@ -214,3 +219,49 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use rodio::buffer::SamplesBuffer;
+
+    use super::take_duration_fade;
+    use crate::song_provider::decode_file;
+
+    #[test]
+    fn signals_once() {
+        let fade = Duration::from_millis(500);
+        let signal = Arc::new(AtomicUsize::new(3));
+        let short = || SamplesBuffer::new(2, 44100, vec![0.5f32; 100]);
+
+        // The input ends before the duration
+        let mut source = take_duration_fade(short(), Duration::from_secs(10), fade, signal.clone());
+        assert_eq!(source.by_ref().count(), 100);
+        assert_eq!(source.next(), None);
+        drop(source);
+        assert_eq!(signal.load(Ordering::Relaxed), 2);
+
+        // Dropped while playing
+        let mut source = take_duration_fade(short(), Duration::from_secs(10), fade, signal.clone());
+        source.next();
+        drop(source);
+        assert_eq!(signal.load(Ordering::Relaxed), 1);
+
+        // Truncated preview without a usable length
+        let preview = decode_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("src/song_provider/loading/truncated.wav"),
+        )
+        .unwrap();
+        let source = take_duration_fade(preview, Duration::MAX, fade, signal.clone());
+        assert!(source.count() <= 2 * 2 * 44100);
+        assert_eq!(signal.load(Ordering::Relaxed), 0);
+    }
+}
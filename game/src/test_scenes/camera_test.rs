@@ -94,8 +94,8 @@ impl CameraTest {
             vec2(1.0, ChartView::TRACK_LENGTH * 2.0),
         ));
 
-        track_shader.set_param("lCol", Srgba::BLUE);
-        track_shader.set_param("rCol", Srgba::RED);
+        track_shader.set_param_if_declared("lCol", Srgba::BLUE);
+        track_shader.set_param_if_declared("rCol", Srgba::RED);
 
         track_shader
             .use_texture(
@@ -1 +1,2 @@
 pub mod camera_test;
+pub mod soak_test;
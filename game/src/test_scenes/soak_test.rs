@@ -0,0 +1,174 @@
+use std::sync::mpsc::Sender;
+
+use anyhow::ensure;
+use di::{RefMut, ServiceProvider};
+use log::info;
+use three_d::{RenderTarget, Viewport};
+
+use crate::{
+    button_codes::LaserState,
+    game_main::ControlMessage,
+    resource_counters::ResourceCounters,
+    scene::{Scene, SceneData},
+    LuaArena,
+};
+
+/// Frames to wait after closing a scene so lua states and mixer sources get cleaned up
+const SETTLE_FRAMES: u32 = 30;
+/// Milliseconds each run is played for, long enough to get through the intro to the first notes
+const RUN_TIME: f64 = 10_000.0;
+
+type SceneFactory = dyn Fn() -> anyhow::Result<Box<dyn SceneData>>;
+
+/// Plays a scene for a few seconds and closes it repeatedly, checking that resources return to
+/// their counts from after the first run
+pub struct SoakTest {
+    services: ServiceProvider,
+    lua_arena: RefMut<LuaArena>,
+    make_scene: Box<SceneFactory>,
+    control_tx: Option<Sender<ControlMessage>>,
+    /// Scene of the current run and how long it has been played
+    running: Option<(Box<dyn Scene>, f64)>,
+    cycles: u32,
+    completed: u32,
+    settle: u32,
+    baseline: Option<ResourceCounters>,
+    close: bool,
+}
+
+impl SoakTest {
+    pub fn new(
+        services: ServiceProvider,
+        cycles: u32,
+        make_scene: impl Fn() -> anyhow::Result<Box<dyn SceneData>> + 'static,
+    ) -> Self {
+        Self {
+            lua_arena: services.get_required_mut(),
+            services,
+            make_scene: Box::new(make_scene),
+            control_tx: None,
+            running: None,
+            cycles,
+            completed: 0,
+            settle: 0,
+            baseline: None,
+            close: false,
+        }
+    }
+
+    fn counters(&self) -> ResourceCounters {
+        ResourceCounters::collect(&self.lua_arena.read().expect("Lock error"))
+    }
+
+    fn run_cycle(&mut self) -> anyhow::Result<()> {
+        let mut scene = (self.make_scene)()?.make_scene(self.services.create_scope())?;
+        if let Some(tx) = self.control_tx.clone() {
+            scene.init(tx)?;
+        }
+        self.running = Some((scene, 0.0));
+        Ok(())
+    }
+}
+
+impl Scene for SoakTest {
+    fn init(&mut self, app_control_tx: Sender<ControlMessage>) -> anyhow::Result<()> {
+        self.control_tx = Some(app_control_tx);
+        Ok(())
+    }
+
+    fn tick(&mut self, dt: f64, knob_state: LaserState) -> anyhow::Result<()> {
+        if self.close {
+            return Ok(());
+        }
+
+        if let Some((scene, played)) = &mut self.running {
+            scene.tick(dt, knob_state)?;
+            if *played >= RUN_TIME || scene.closed() {
+                self.running = None;
+                self.settle = SETTLE_FRAMES;
+            }
+            return Ok(());
+        }
+
+        if self.settle > 0 {
+            self.settle -= 1;
+            return Ok(());
+        }
+
+        // The first run fills caches that are kept for the whole session, so it's not counted
+        let Some(baseline) = self.baseline else {
+            if self.completed == 0 {
+                self.run_cycle()?;
+                self.completed = 1;
+            } else {
+                let baseline = self.counters();
+                info!("Soak test baseline: {baseline}");
+                self.baseline = Some(baseline);
+            }
+            return Ok(());
+        };
+
+        if self.completed <= self.cycles {
+            self.run_cycle()?;
+            self.completed += 1;
+            return Ok(());
+        }
+
+        self.close = true;
+        let counters = self.counters();
+        ensure!(
+            counters == baseline,
+            "Resources leaked after {} runs, expected {baseline}, got {counters}",
+            self.cycles
+        );
+        info!("Soak test passed after {} runs: {counters}", self.cycles);
+        Ok(())
+    }
+
+    fn render(
+        &mut self,
+        dt: f64,
+        td_context: &three_d::Context,
+        target: &mut RenderTarget,
+        viewport: Viewport,
+    ) {
+        if let Some((scene, played)) = &mut self.running {
+            scene.render(dt, td_context, target, viewport);
+            *played += dt;
+        }
+    }
+
+    fn render_ui(&mut self, dt: f64) -> anyhow::Result<()> {
+        match &mut self.running {
+            Some((scene, _)) => scene.render_ui(dt),
+            None => Ok(()),
+        }
+    }
+
+    fn is_suspended(&self) -> bool {
+        false
+    }
+
+    fn debug_ui(&mut self, ctx: &egui::Context) -> anyhow::Result<()> {
+        egui::Window::new("Soak test").show(ctx, |ui| {
+            ui.label(format!(
+                "Run {}/{}",
+                self.completed.saturating_sub(1),
+                self.cycles
+            ));
+            if let Some(baseline) = self.baseline {
+                ui.label(format!("Baseline: {baseline}"));
+            }
+            ui.label(format!("Current: {}", self.counters()));
+        });
+        Ok(())
+    }
+
+    fn closed(&self) -> bool {
+        self.close
+    }
+
+    fn name(&self) -> &str {
+        "Soak Test"
+    }
+}
@@ -0,0 +1,486 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use di::{inject, injectable};
+use log::{info, warn};
+
+use crate::{
+    config::GameConfig,
+    maintenance::{clear_dir, dir_size, ManagedCache},
+    project_dirs,
+    worker_service::WorkerService,
+};
+
+/// Largest draw size served from thumbnails, bigger images are loaded from the source
+pub const MAX_THUMBNAIL_SIZE: u32 = 512;
+const MIN_THUMBNAIL_SIZE: u32 = 64;
+/// Pause between decodes so the worker doesn't compete with the game for IO and CPU
+const JOB_INTERVAL: Duration = Duration::from_millis(50);
+const EXTENSIONS: [&str; 2] = ["jpg", "png"];
+pub const DENSITY_STRIP_WIDTH: u32 = 200;
+pub const DENSITY_STRIP_HEIGHT: u32 = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Request {
+    source: PathBuf,
+    size: u32,
+}
+
+/// Density strip of a chart, cached by the chart hash
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StripRequest {
+    pub hash: String,
+    pub chart: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Job {
+    Thumbnail(Request),
+    DensityStrip(StripRequest),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageKind {
+    Thumbnails,
+    DensityStrips,
+}
+
+/// The files of one kind of image in the thumbnail cache
+struct ImageCache {
+    kind: ImageKind,
+    dir: PathBuf,
+    /// Set once the files are removed, the thumbnailer then forgets the images it handed out
+    cleared: Arc<AtomicBool>,
+}
+
+impl ImageCache {
+    fn holds(&self, path: &Path) -> bool {
+        let is_strip = path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with("strip_"));
+        is_strip == (self.kind == ImageKind::DensityStrips)
+    }
+}
+
+impl ManagedCache for ImageCache {
+    fn name(&self) -> &'static str {
+        match self.kind {
+            ImageKind::Thumbnails => "Thumbnails",
+            ImageKind::DensityStrips => "Density strips",
+        }
+    }
+
+    fn size(&self) -> anyhow::Result<u64> {
+        dir_size(&self.dir, |p| self.holds(p))
+    }
+
+    fn clear(&self) -> anyhow::Result<()> {
+        let removed = clear_dir(&self.dir, |p| self.holds(p))?;
+        info!("Removed {removed} files from the {} cache", self.name());
+        self.cleared.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+enum Thumbnail {
+    Pending,
+    Ready(PathBuf),
+    Failed,
+}
+
+/// Creates downscaled copies of large images, like jackets, on a background thread.
+///
+/// Thumbnails are cached on disk by the content hash of the source and their size. The same
+/// thread renders the chart density strips shown by song select, see
+/// [`Thumbnailer::request_density_strips`].
+pub struct Thumbnailer {
+    requests: Sender<Job>,
+    results: Receiver<(Job, Option<PathBuf>)>,
+    thumbnails: HashMap<Request, Thumbnail>,
+    strips: HashMap<String, Thumbnail>,
+    /// Strips waiting to be sent to the worker, only one is sent at a time so the queue can be
+    /// replaced when the selection changes
+    strip_queue: VecDeque<StripRequest>,
+    strip_in_progress: bool,
+    thumbnails_cleared: Arc<AtomicBool>,
+    strips_cleared: Arc<AtomicBool>,
+}
+
+impl WorkerService for Thumbnailer {
+    fn update(&mut self) {
+        if self.thumbnails_cleared.swap(false, Ordering::Relaxed) {
+            self.thumbnails.clear();
+        }
+        if self.strips_cleared.swap(false, Ordering::Relaxed) {
+            self.strips.clear();
+        }
+
+        for (job, result) in self.results.try_iter() {
+            let result = result.map_or(Thumbnail::Failed, Thumbnail::Ready);
+            match job {
+                Job::Thumbnail(request) => {
+                    self.thumbnails.insert(request, result);
+                }
+                Job::DensityStrip(request) => {
+                    self.strip_in_progress = false;
+                    self.strips.insert(request.hash, result);
+                }
+            }
+        }
+
+        if self.strip_in_progress {
+            return;
+        }
+        if let Some(request) = self.strip_queue.pop_front() {
+            let strip = match self.requests.send(Job::DensityStrip(request.clone())) {
+                Ok(()) => {
+                    self.strip_in_progress = true;
+                    Thumbnail::Pending
+                }
+                Err(_) => Thumbnail::Failed,
+            };
+            self.strips.insert(request.hash, strip);
+        }
+    }
+}
+
+#[injectable]
+impl Thumbnailer {
+    #[inject]
+    pub fn new() -> Self {
+        let dir = cache_dir();
+        let cache_limit = GameConfig::get().graphics.thumbnail_cache_mb as u64 * 1024 * 1024;
+        let (requests, job_rx) = channel();
+        let (result_tx, results) = channel();
+
+        let spawned = std::thread::Builder::new()
+            .name("thumbnailer".into())
+            .spawn(move || {
+                if let Err(e) = std::fs::create_dir_all(&dir) {
+                    warn!("Could not create thumbnail cache: {e}");
+                    return;
+                }
+                clean_cache(&dir, cache_limit);
+                run(&dir, job_rx, result_tx);
+            });
+
+        if let Err(e) = spawned {
+            warn!("Could not start thumbnailer: {e}");
+        }
+
+        Self {
+            requests,
+            results,
+            thumbnails: HashMap::new(),
+            strips: HashMap::new(),
+            strip_queue: VecDeque::new(),
+            strip_in_progress: false,
+            thumbnails_cleared: Arc::default(),
+            strips_cleared: Arc::default(),
+        }
+    }
+
+    /// The thumbnail and density strip caches, images removed from them are created again the
+    /// next time they are shown
+    pub fn caches(&self) -> Vec<Box<dyn ManagedCache>> {
+        [
+            (ImageKind::Thumbnails, &self.thumbnails_cleared),
+            (ImageKind::DensityStrips, &self.strips_cleared),
+        ]
+        .into_iter()
+        .map(|(kind, cleared)| {
+            Box::new(ImageCache {
+                kind,
+                dir: cache_dir(),
+                cleared: cleared.clone(),
+            }) as Box<dyn ManagedCache>
+        })
+        .collect()
+    }
+
+    /// Path to a thumbnail of `source` at least `size` pixels on its longest side.
+    ///
+    /// Returns `None` while it is being created, or when the source should be used instead.
+    pub fn get(&mut self, source: &Path, size: u32) -> Option<PathBuf> {
+        if size > MAX_THUMBNAIL_SIZE {
+            return None;
+        }
+
+        let request = Request {
+            source: source.to_path_buf(),
+            size: bucket_size(size),
+        };
+
+        match self.thumbnails.get(&request) {
+            Some(Thumbnail::Ready(path)) => Some(path.clone()),
+            Some(Thumbnail::Pending | Thumbnail::Failed) => None,
+            None => {
+                let thumbnail = match self.requests.send(Job::Thumbnail(request.clone())) {
+                    Ok(()) => Thumbnail::Pending,
+                    Err(_) => Thumbnail::Failed,
+                };
+                self.thumbnails.insert(request, thumbnail);
+                None
+            }
+        }
+    }
+
+    /// Queues density strips for the charts of the selected song, in order.
+    ///
+    /// Strips queued for a previous selection that haven't started yet are dropped, scrolling
+    /// through the wheel only renders the songs it stops on.
+    pub fn request_density_strips(&mut self, charts: impl IntoIterator<Item = StripRequest>) {
+        self.strip_queue = charts
+            .into_iter()
+            .filter(|c| !self.strips.contains_key(&c.hash))
+            .collect();
+    }
+
+    /// Path to the density strip of the chart with `hash`, `None` until it has been rendered
+    pub fn density_strip(&self, hash: &str) -> Option<PathBuf> {
+        match self.strips.get(hash) {
+            Some(Thumbnail::Ready(path)) => Some(path.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Thumbnailer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn cache_dir() -> PathBuf {
+    project_dirs().cache_dir().join("thumbnails")
+}
+
+/// Rounds up to a power of two so similar draw sizes share a thumbnail
+fn bucket_size(size: u32) -> u32 {
+    size.next_power_of_two()
+        .clamp(MIN_THUMBNAIL_SIZE, MAX_THUMBNAIL_SIZE)
+}
+
+fn run(dir: &Path, requests: Receiver<Job>, results: Sender<(Job, Option<PathBuf>)>) {
+    while let Ok(job) = requests.recv() {
+        let (kind, source) = match &job {
+            Job::Thumbnail(request) => ("thumbnail", &request.source),
+            Job::DensityStrip(request) => ("density strip", &request.chart),
+        };
+        // Decoders may panic on malformed files, which should only cost that one thumbnail
+        let created = std::panic::catch_unwind(|| match &job {
+            Job::Thumbnail(request) => create_thumbnail(dir, request),
+            Job::DensityStrip(request) => create_density_strip(dir, request),
+        });
+        let thumbnail = match created {
+            Ok(Ok(path)) => Some(path),
+            Ok(Err(e)) => {
+                warn!("Could not create {kind} for {}: {e}", source.display());
+                None
+            }
+            Err(_) => {
+                warn!("Decoder panicked creating {kind} for {}", source.display());
+                None
+            }
+        };
+
+        if results.send((job, thumbnail)).is_err() {
+            return;
+        }
+        std::thread::sleep(JOB_INTERVAL);
+    }
+}
+
+fn create_thumbnail(dir: &Path, request: &Request) -> anyhow::Result<PathBuf> {
+    let data = std::fs::read(&request.source)?;
+    let mut hasher = sha1_smol::Sha1::new();
+    hasher.update(&data);
+    let name = format!("{}_{}", hasher.digest(), request.size);
+
+    if let Some(path) = EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(&name).with_extension(ext))
+        .find(|p| p.exists())
+    {
+        // The modified time orders the cache cleanup
+        std::fs::File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(SystemTime::now())?;
+        return Ok(path);
+    }
+
+    let image = image::load_from_memory(&data)?;
+    if image.width().max(image.height()) <= request.size {
+        return Ok(request.source.clone());
+    }
+
+    let thumbnail = image.thumbnail(request.size, request.size);
+    let (thumbnail, format) = if thumbnail.color().has_alpha() {
+        (thumbnail, image::ImageFormat::Png)
+    } else {
+        (
+            image::DynamicImage::ImageRgb8(thumbnail.to_rgb8()),
+            image::ImageFormat::Jpeg,
+        )
+    };
+
+    let path = dir.join(&name).with_extension(format.extensions_str()[0]);
+    // Write next to the target first so an interrupted write never leaves a truncated thumbnail
+    let partial = path.with_extension("part");
+    thumbnail.save_with_format(&partial, format)?;
+    std::fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+fn create_density_strip(dir: &Path, request: &StripRequest) -> anyhow::Result<PathBuf> {
+    let path = dir.join(format!("strip_{}.png", request.hash));
+    if path.exists() {
+        std::fs::File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(SystemTime::now())?;
+        return Ok(path);
+    }
+
+    let data = std::fs::read(&request.chart)?;
+    let chart = crate::song_provider::parse_chart(&request.chart, &data)?;
+    let density = crate::game::scrubber::beat_density(&chart, chart.get_last_tick());
+    let strip = density_strip(&density);
+
+    let partial = path.with_extension("part");
+    strip.save_with_format(&partial, image::ImageFormat::Png)?;
+    std::fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+/// Draws `density` as white bars on black, each column shows the densest beat it covers
+fn density_strip(density: &[f32]) -> image::GrayImage {
+    let columns: Vec<f32> = (0..DENSITY_STRIP_WIDTH as usize)
+        .map(|x| {
+            let start = x * density.len() / DENSITY_STRIP_WIDTH as usize;
+            let end = ((x + 1) * density.len() / DENSITY_STRIP_WIDTH as usize).max(start + 1);
+            density
+                .get(start..end.min(density.len()))
+                .unwrap_or_default()
+                .iter()
+                .copied()
+                .fold(0.0, f32::max)
+        })
+        .collect();
+
+    image::GrayImage::from_fn(DENSITY_STRIP_WIDTH, DENSITY_STRIP_HEIGHT, |x, y| {
+        let height = (columns[x as usize].clamp(0.0, 1.0) * DENSITY_STRIP_HEIGHT as f32).round();
+        let lit = (DENSITY_STRIP_HEIGHT - y) as f32 <= height;
+        image::Luma([if lit { 255 } else { 0 }])
+    })
+}
+
+/// Removes the least recently used thumbnails until the cache fits in `limit` bytes
+fn clean_cache(dir: &Path, limit: u64) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    let files = entries
+        .filter_map(|e| {
+            let e = e.ok()?;
+            let meta = e.metadata().ok()?;
+            meta.is_file().then(|| {
+                (
+                    e.path(),
+                    meta.len(),
+                    meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                )
+            })
+        })
+        .collect();
+
+    let expired = expired_files(files, limit);
+    if !expired.is_empty() {
+        info!("Removing {} thumbnails from cache", expired.len());
+    }
+    for path in expired {
+        if let Err(e) = std::fs::remove_file(&path) {
+            warn!("Could not remove {}: {e}", path.display());
+        }
+    }
+}
+
+fn expired_files(mut files: Vec<(PathBuf, u64, SystemTime)>, limit: u64) -> Vec<PathBuf> {
+    files.sort_by(|a, b| b.2.cmp(&a.2));
+    let mut total = 0;
+    files
+        .into_iter()
+        .filter_map(|(path, len, _)| {
+            total += len;
+            let partial = path.extension().is_some_and(|ext| ext == "part");
+            (partial || total > limit).then_some(path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use super::{bucket_size, density_strip, expired_files, DENSITY_STRIP_HEIGHT};
+
+    #[test]
+    fn removes_least_recent() {
+        let time = |s| SystemTime::UNIX_EPOCH + Duration::from_secs(s);
+        let files = vec![
+            (PathBuf::from("b.jpg"), 40, time(2)),
+            (PathBuf::from("a.jpg"), 40, time(1)),
+            (PathBuf::from("d.png"), 40, time(4)),
+            (PathBuf::from("c.part"), 1, time(5)),
+            (PathBuf::from("e.jpg"), 40, time(3)),
+        ];
+
+        let expired = expired_files(files, 100);
+        assert_eq!(
+            expired,
+            vec![
+                PathBuf::from("c.part"),
+                PathBuf::from("b.jpg"),
+                PathBuf::from("a.jpg")
+            ]
+        );
+        assert_eq!(bucket_size(200), 256);
+        assert_eq!(bucket_size(10), 64);
+    }
+
+    #[test]
+    fn density_strip_columns() {
+        // Short chart, every beat covers several columns
+        let strip = density_strip(&[0.0, 1.0, 0.5, 0.0]);
+        let lit = |x| {
+            (0..DENSITY_STRIP_HEIGHT)
+                .filter(|&y| strip.get_pixel(x, y).0[0] == 255)
+                .count() as u32
+        };
+        assert_eq!(lit(0), 0);
+        assert_eq!(lit(50), DENSITY_STRIP_HEIGHT);
+        assert_eq!(lit(100), DENSITY_STRIP_HEIGHT / 2);
+        assert_eq!(strip.get_pixel(100, DENSITY_STRIP_HEIGHT - 1).0[0], 255);
+
+        // Long chart, a column shows the densest beat it covers
+        let mut density = vec![0.0; 1000];
+        density[7] = 1.0;
+        let strip = density_strip(&density);
+        assert_eq!(strip.get_pixel(1, 0).0[0], 255);
+        assert_eq!(strip.get_pixel(2, DENSITY_STRIP_HEIGHT - 1).0[0], 0);
+        assert!(density_strip(&[]).pixels().all(|p| p.0[0] == 0));
+    }
+}
@@ -1,7 +1,10 @@
 use std::{
     path::PathBuf,
     rc::Rc,
-    sync::{mpsc::Sender, Arc},
+    sync::{
+        mpsc::{Receiver, Sender},
+        Arc,
+    },
 };
 
 use anyhow::anyhow;
@@ -18,13 +21,26 @@ use crate::{
     game_main::AutoPlay,
     log_result,
     main_menu::MainMenuButton,
-    results::SongResultData,
+    menu_audio::MenuAudio,
+    results::{SingleResultData, VersusResultData},
     scene::{Scene, SceneData},
+    song_provider::{report_broken_audio, LoadProgress, SongDiffId, SongProvider},
     songselect::{Song, SongSelect},
     util::{back_pixels, lua_address},
     ControlMessage,
 };
 
+fn diff_id(song: &Song, diff: usize) -> Option<SongDiffId> {
+    let diff_id = song
+        .difficulties
+        .read()
+        .expect("Lock error")
+        .get(diff)?
+        .id
+        .clone();
+    Some(SongDiffId::SongDiff(song.id.clone(), diff_id))
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum TransitionState {
     Intro,
@@ -37,6 +53,7 @@ pub enum TransitionState {
 pub struct Transition {
     target: ControlMessage,
     target_state: Option<Promise<anyhow::Result<Box<dyn SceneData + Send>>>>,
+    load_progress: Option<Receiver<LoadProgress>>,
     control_tx: Sender<ControlMessage>,
     pub state: TransitionState,
     transition_lua: Rc<Lua>,
@@ -56,22 +73,39 @@ fn load_chart(
     skin_folder: PathBuf,
     audio: Box<dyn Source<Item = f32> + Send>,
     autoplay: AutoPlay,
+    versus: bool,
+    chart_path: Option<PathBuf>,
 ) -> anyhow::Result<Box<dyn SceneData + Send>> {
-    Ok(Box::new(crate::game::GameData::new(
-        song,
-        diff_idx,
-        chart,
-        skin_folder,
-        audio,
-        autoplay,
-    )?))
+    let game_data =
+        crate::game::GameData::new(song, diff_idx, chart, skin_folder, audio, autoplay)?
+            .with_chart_path(chart_path)
+            .with_swap_audio();
+    if versus {
+        Ok(Box::new(crate::game::VersusData::new(game_data)))
+    } else {
+        Ok(Box::new(game_data))
+    }
 }
 
 impl Transition {
+    /// Chart file of a difficulty, if the song provider reads charts from files
+    fn chart_path(&self, song: &Song, diff: usize) -> Option<PathBuf> {
+        self.service_provider
+            .get_required_mut::<dyn SongProvider>()
+            .read()
+            .expect("Lock error")
+            .chart_path(&diff_id(song, diff)?)
+    }
+
     pub fn do_outro(&mut self) {
         self.state = TransitionState::Countdown(5);
     }
 
+    /// The scene under the transition shows through from the start of the outro
+    pub fn reveals_next(&self) -> bool {
+        matches!(self.state, TransitionState::Outro | TransitionState::Done)
+    }
+
     pub fn new(
         transition_lua: Rc<Lua>,
         target: ControlMessage,
@@ -94,6 +128,8 @@ impl Transition {
 
         let prev_grab = screen_grab(context, viewport);
 
+        log_result!(transition_lua.globals().set("progress", 0.0));
+
         if let ControlMessage::Song { song, diff, .. } = &target {
             let mut vgfx = vgfx.write().expect("Failed to lock VG");
             let diff = song
@@ -116,12 +152,17 @@ impl Transition {
                     "effector": diff.effector
                 }))?
             ));
+
+            if let Some(menu_audio) = service_provider.get::<MenuAudio>() {
+                menu_audio.duck();
+            }
         }
 
         Ok(Self {
             target,
             transition_lua,
             target_state: None,
+            load_progress: None,
             control_tx,
             state: TransitionState::Intro,
             vgfx,
@@ -197,15 +238,10 @@ impl Scene for Transition {
     }
 
     fn render_ui(&mut self, dt: f64) -> anyhow::Result<()> {
-        {
-            self.vgfx
-                .read()
-                .expect("Lock error")
-                .canvas
-                .lock()
-                .expect("Lock error")
-                .reset();
-        }
+        crate::Vgfx::with_canvas(&self.vgfx, |vgfx, canvas| {
+            canvas.reset();
+            vgfx.reset_viewport(canvas);
+        });
         //TODO: Render last frame before transition
         //TODO: Handle rendering of next scene during outro
         match self.state {
@@ -228,39 +264,41 @@ impl Scene for Transition {
                             diff,
                             loader,
                             autoplay,
+                            versus,
                         } => {
                             let skin_folder = self.vgfx.read().expect("Lock error").skin_folder();
+                            let chart_path = self.chart_path(&song, diff);
+                            let diff_id = diff_id(&song, diff);
+                            let (progress_tx, progress_rx) = std::sync::mpsc::channel();
+                            self.load_progress = Some(progress_rx);
                             Some(Promise::spawn_thread("Load song", move || {
-                                let (chart, audio) = loader()?;
-                                load_chart(chart, song, diff, skin_folder, audio, autoplay)
+                                let (chart, audio) = loader(progress_tx).inspect_err(|e| {
+                                    if let Some(id) = diff_id {
+                                        report_broken_audio(id, e);
+                                    }
+                                })?;
+                                load_chart(
+                                    chart,
+                                    song,
+                                    diff,
+                                    skin_folder,
+                                    audio,
+                                    autoplay,
+                                    versus,
+                                    chart_path,
+                                )
                             }))
                         }
-                        ControlMessage::Result {
-                            song,
-                            diff_idx,
-                            score,
-                            gauge,
-                            hit_ratings,
-                            hit_window,
-                            autoplay,
-                            max_combo,
-                            duration,
-                            manual_exit,
-                        } => Some(Promise::spawn_thread(
+                        ControlMessage::Result(result) => Some(Promise::spawn_thread(
                             "Load song",
                             move || -> anyhow::Result<Box<dyn SceneData + Send>> {
-                                Ok(Box::new(SongResultData::from_diff(
-                                    song,
-                                    diff_idx,
-                                    score,
-                                    hit_ratings,
-                                    gauge,
-                                    hit_window,
-                                    autoplay,
-                                    max_combo,
-                                    duration,
-                                    manual_exit,
-                                )?))
+                                Ok(Box::new(SingleResultData::new(result)?))
+                            },
+                        )),
+                        ControlMessage::VersusResult(results) => Some(Promise::spawn_thread(
+                            "Load song",
+                            move || -> anyhow::Result<Box<dyn SceneData + Send>> {
+                                Ok(Box::new(VersusResultData::new(results)?))
                             },
                         )),
                         _ => None,
@@ -268,6 +306,16 @@ impl Scene for Transition {
                 }
             }
             TransitionState::Loading | TransitionState::Countdown(_) => {
+                if let Some(progress) = self
+                    .load_progress
+                    .as_ref()
+                    .and_then(|rx| rx.try_iter().last())
+                {
+                    log_result!(self
+                        .transition_lua
+                        .globals()
+                        .set("progress", progress.fraction()));
+                }
                 let render: Function = self.transition_lua.globals().get("render")?;
                 render.call(dt / 1000_f64)?;
                 if let Some(target_state) = self.target_state.take() {
@@ -280,6 +328,9 @@ impl Scene for Transition {
                             .expect("Failed to communicate with main game"),
                         Ok(Err(loading_error)) => {
                             log::error!("{}", loading_error);
+                            if let Some(menu_audio) = self.service_provider.get::<MenuAudio>() {
+                                menu_audio.restore();
+                            }
                             self.state = TransitionState::Countdown(5);
                         }
                         Err(loading) => self.target_state = Some(loading),
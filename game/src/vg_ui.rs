@@ -6,11 +6,15 @@ use std::{
     sync::{Arc, Mutex, RwLock},
 };
 
+mod image_cache;
+
 const COMPAT_TEXT_SCALE: f32 = 21.5 / 30.0; // Needed because old usc has two different text rendering methods for text, and fasttext/labels
 
-use anyhow::anyhow;
+use anyhow::ensure;
 use di::{Activator, InjectBuilder, Injectable};
-use femtovg::{renderer::OpenGl, Canvas, Color, FontId, ImageFlags, ImageId, Paint, Path};
+use femtovg::{
+    renderer::OpenGl, Canvas, Color, FontId, ImageFlags, ImageId, Paint, Path, Renderer,
+};
 
 use log::warn;
 use poll_promise::Promise;
@@ -23,22 +27,90 @@ use tealr::{
 use tealr::mlu::mlua;
 
 use crate::{
-    animation::VgAnimation, config::GameConfig, default_game_dir, help::add_lua_static_method,
-    log_result, settings_screen::skin_select::SkinMeta, shaded_mesh::ShadedMesh, util::lua_address,
+    animation::VgAnimation,
+    config::GameConfig,
+    default_game_dir,
+    display_rotation::DisplayRotation,
+    help::add_lua_static_method,
+    lock_recovery::{RecoverLock, RecoverMutex},
+    log_result,
+    settings_screen::skin_select::SkinMeta,
+    shaded_mesh::ShadedMesh,
+    thumbnailer::Thumbnailer,
+    util::lua_address,
 };
 
+pub use image_cache::ImageCacheStats;
+use image_cache::{ImageCache, ImageKey};
+
 const FALLBACK_ID: u32 = u32::MAX;
+const BYTES_PER_MB: usize = 1024 * 1024;
+
+fn image_cache_budget() -> usize {
+    GameConfig::get().graphics.image_cache_mb as usize * BYTES_PER_MB
+}
+
+/// Fonts in the `fonts` folder tried in order for glyphs missing from the font a skin uses
+const FALLBACK_FONTS: [&str; 2] = ["NotoSans-Regular.ttf", "NotoSansCJKjp-Regular.otf"];
+
+/// Installed fonts tried after the bundled ones, mostly for emoji
+#[cfg(feature = "system-fonts")]
+const SYSTEM_FALLBACK_FAMILIES: [&str; 3] =
+    ["Noto Color Emoji", "Segoe UI Emoji", "Apple Color Emoji"];
+
+fn load_fallback_fonts<T: Renderer>(
+    canvas: &mut Canvas<T>,
+    font_dir: &std::path::Path,
+) -> Vec<FontId> {
+    #[allow(unused_mut)]
+    let mut fonts: Vec<FontId> = FALLBACK_FONTS
+        .iter()
+        .filter_map(|name| match canvas.add_font(font_dir.join(name)) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                warn!("Failed to load fallback font {name}: {e}");
+                None
+            }
+        })
+        .collect();
+
+    #[cfg(feature = "system-fonts")]
+    fonts.extend(load_system_font(canvas));
+
+    fonts
+}
+
+#[cfg(feature = "system-fonts")]
+fn load_system_font<T: Renderer>(canvas: &mut Canvas<T>) -> Option<FontId> {
+    use font_kit::{family_name::FamilyName, properties::Properties, source::SystemSource};
+
+    let families: Vec<_> = SYSTEM_FALLBACK_FAMILIES
+        .iter()
+        .map(|f| FamilyName::Title(f.to_string()))
+        .collect();
+    let font = SystemSource::new()
+        .select_best_match(&families, &Properties::new())
+        .ok()?
+        .load()
+        .ok()?;
+    log::info!("Using system fallback font: {}", font.full_name());
+    let data = font.copy_font_data()?;
+    canvas
+        .add_font_mem(&data)
+        .map_err(|e| warn!("Failed to load system fallback font: {e}"))
+        .ok()
+}
 
 #[derive(Debug)]
 enum VgImage {
-    Static(ImageId),
+    Shared(ImageKey, ImageId),
     Animation(VgAnimation),
 }
 
 impl VgImage {
     fn current_id(&self) -> Option<ImageId> {
         match self {
-            VgImage::Static(id) => Some(*id),
+            VgImage::Shared(_, id) => Some(*id),
             VgImage::Animation(id) => id.current_img_id(),
         }
     }
@@ -101,14 +173,12 @@ impl ScopedAssets {
 impl Drop for ScopedAssets {
     fn drop(&mut self) {
         if let Ok(mut canvas) = self.canvas.lock() {
-            self.images.iter().for_each(|(_, img)| match img {
-                VgImage::Static(id) => canvas.delete_image(*id),
-                VgImage::Animation(anim) => anim.delete_imgs(&mut canvas),
+            // Paint images are owned by `images` and shared images are released through the cache
+            self.images.iter().for_each(|(_, img)| {
+                if let VgImage::Animation(anim) = img {
+                    anim.delete_imgs(&mut canvas)
+                }
             });
-
-            self.paint_imgs
-                .iter()
-                .for_each(|(_, id)| canvas.delete_image(*id));
         }
     }
 }
@@ -135,21 +205,42 @@ pub struct Vgfx {
     stroke_paint: Paint,
     gradient_colors: [Color; 2],
     game_folder: std::path::PathBuf,
+    thumbnailer: Arc<RwLock<Thumbnailer>>,
     next_img_id: u32,
     next_paint_id: u32,
     next_label_id: u32,
     scoped_assets: HashMap<usize, ScopedAssets>,
+    image_cache: ImageCache<ImageId>,
     fonts: HashMap<String, FontId>,
+    /// Tried after the font of a paint or label, see [`FALLBACK_FONTS`]
+    fallback_fonts: Vec<FontId>,
     image_jobs: HashMap<String, Promise<image::DynamicImage>>,
+    /// Size asked for by the latest `LoadImageJob`, preloads use it to match what the skin draws
+    last_job_size: Option<(u32, u32)>,
     label_align: (femtovg::Align, femtovg::Baseline),
+    /// Part of the canvas scripts are drawn to as x, y, width, height
+    viewport: Option<(f32, f32, f32, f32)>,
+    /// Translation and rotation from skin coordinates to the rotated display
+    root_transform: ((f32, f32), f32),
 }
 
 impl Injectable for Vgfx {
     fn inject(lifetime: di::ServiceLifetime) -> di::InjectBuilder {
         InjectBuilder::new(
             Activator::new::<Self, Self>(
-                |sp| Arc::new(Self::new(sp.get_required(), default_game_dir())),
-                |sp| Arc::new(Self::new(sp.get_required(), default_game_dir()).into()),
+                |sp| {
+                    Arc::new(Self::new(
+                        sp.get_required(),
+                        sp.get_required_mut(),
+                        default_game_dir(),
+                    ))
+                },
+                |sp| {
+                    Arc::new(
+                        Self::new(sp.get_required(), sp.get_required_mut(), default_game_dir())
+                            .into(),
+                    )
+                },
             ),
             lifetime,
         )
@@ -174,21 +265,23 @@ struct Label {
 }
 
 impl Vgfx {
-    pub fn new(canvas: Arc<Mutex<Canvas<OpenGl>>>, game_folder: std::path::PathBuf) -> Self {
-        let default_fonts = {
+    pub fn new(
+        canvas: Arc<Mutex<Canvas<OpenGl>>>,
+        thumbnailer: Arc<RwLock<Thumbnailer>>,
+        game_folder: std::path::PathBuf,
+    ) -> Self {
+        let fallback_fonts = {
             let mut canvas = canvas.lock().expect("Lock error");
 
             let mut font_dir = game_folder.clone();
             font_dir.push("fonts");
-            let default_fonts = canvas
-                .add_font_dir(&font_dir)
-                .expect("Failed to load default fonts");
+            let fallback_fonts = load_fallback_fonts(&mut canvas, &font_dir);
             font_dir.push("settings");
             _ = canvas
                 .add_font_dir(&font_dir)
                 .expect("Failed to load settings fonts");
 
-            default_fonts
+            fallback_fonts
         };
 
         let config = &GameConfig::get();
@@ -205,6 +298,7 @@ impl Vgfx {
             restore_stack: vec![],
             canvas,
             game_folder,
+            thumbnailer,
             skin: config.skin.clone(),
             path: Some(Path::new()),
             fill_paint: None,
@@ -215,23 +309,94 @@ impl Vgfx {
             next_paint_id: 1,
             next_label_id: 1,
             image_jobs: Default::default(),
+            last_job_size: None,
             scoped_assets: Default::default(),
+            image_cache: ImageCache::new(image_cache_budget()),
             image_tint: None,
             label_color: Color::white(),
-            label_font: *default_fonts.first().expect("No default font loaded"),
+            label_font: *fallback_fonts.first().expect("No default font loaded"),
+            fallback_fonts,
             label_align: (femtovg::Align::Left, femtovg::Baseline::Alphabetic),
             _skin_meta: skin_meta,
+            viewport: None,
+            root_transform: ((0.0, 0.0), 0.0),
+        }
+    }
+
+    /// `font` followed by the fallback fonts, femtovg uses the first one that has every glyph of
+    /// a word so advances and alignment come from the font actually drawn
+    fn font_chain(&self, font: FontId) -> Vec<FontId> {
+        std::iter::once(font)
+            .chain(self.fallback_fonts.iter().copied().filter(|f| *f != font))
+            .collect()
+    }
+
+    /// Font for new labels and the current text paint
+    fn use_font(&mut self, font: FontId) {
+        self.label_font = font;
+        let fonts = self.font_chain(font);
+        if let Some(paint) = self.fill_paint.as_mut() {
+            paint.set_font(&fonts);
+        }
+    }
+
+    /// Locks `vgfx` and then its canvas. Code that needs both goes through here so they are
+    /// always locked in the same order and two threads can't end up waiting on each other.
+    pub fn with_canvas<R>(
+        vgfx: &RwLock<Vgfx>,
+        f: impl FnOnce(&mut Vgfx, &mut Canvas<OpenGl>) -> R,
+    ) -> R {
+        let mut vgfx = vgfx.write_recover();
+        let canvas = vgfx.canvas.clone();
+        let mut canvas = canvas.lock_recover();
+        f(&mut vgfx, &mut canvas)
+    }
+
+    /// Lays scripts out for a display rotated by `rotation`, `size` is the physical canvas size
+    pub fn set_rotation(&mut self, rotation: DisplayRotation, size: (f32, f32)) {
+        self.root_transform = rotation.canvas_transform(size);
+    }
+
+    /// Confines scripts to a part of the canvas, used for split screen gameplay
+    pub fn set_viewport(&mut self, viewport: Option<(f32, f32, f32, f32)>) {
+        self.viewport = viewport;
+    }
+
+    /// Resets the transform and scissor to the current viewport
+    pub fn reset_viewport(&self, canvas: &mut Canvas<OpenGl>) {
+        canvas.reset_transform();
+        canvas.reset_scissor();
+        Self::apply_root_transform(canvas, self.root_transform, self.viewport);
+        if let Some((_, _, w, h)) = self.viewport {
+            canvas.scissor(0.0, 0.0, w, h);
+        }
+    }
+
+    fn apply_root_transform(
+        canvas: &mut Canvas<OpenGl>,
+        ((x, y), angle): ((f32, f32), f32),
+        viewport: Option<(f32, f32, f32, f32)>,
+    ) {
+        if angle != 0.0 {
+            canvas.translate(x, y);
+            canvas.rotate(angle);
+        }
+        if let Some((x, y, _, _)) = viewport {
+            canvas.translate(x, y);
         }
     }
 
     pub fn drop_assets(&mut self, lua_index: usize) {
         let removed_assets = self.scoped_assets.remove(&lua_index);
-        //TODO: Call deleteimage on canvas for removed images
+        log_result!(self.with_image_cache(|cache, canvas| cache.release_owner(canvas, lua_index)));
         if let Some(removed_assets) = removed_assets {
+            let stats = self.image_cache.stats();
             log::info!(
-                "Dropped assets:\n  {} Images/Animation\n  {} Labels",
+                "Dropped assets:\n  {} Images/Animation\n  {} Labels\n  {} Cached images ({} MB)",
                 removed_assets.images.len(),
-                removed_assets.labels.len()
+                removed_assets.labels.len(),
+                stats.images,
+                stats.bytes / BYTES_PER_MB
             );
 
             //Just clear cache here, first frame of restored scene will take longer but most important stuff should be cached quickly
@@ -257,27 +422,119 @@ impl Vgfx {
         Ok(f(canvas))
     }
 
+    fn with_image_cache<R>(
+        &mut self,
+        f: impl FnOnce(&mut ImageCache<ImageId>, &mut Canvas<OpenGl>) -> R,
+    ) -> Result<R, mlua::Error> {
+        let canvas = &mut self
+            .canvas
+            .try_lock()
+            .map_err(|_| mlua::Error::external("Canvas in use".to_string()))?;
+
+        self.image_cache.set_budget(image_cache_budget());
+        Ok(f(&mut self.image_cache, canvas))
+    }
+
+    pub fn image_cache_stats(&self) -> ImageCacheStats {
+        self.image_cache.stats()
+    }
+
+    /// Adds an image from the cache to the assets of a Lua state, returns the id used by the Lua state
+    fn add_shared_image(&mut self, key: ImageKey, img: ImageId, lua_index: usize) -> u32 {
+        let this_id = self.next_img_id;
+        self.next_img_id += 1;
+        if let Some(assets) = self.scoped_assets.get_mut(&lua_index) {
+            assets.images.insert(this_id, VgImage::Shared(key, img));
+        }
+        this_id
+    }
+
+    fn load_shared_image(&mut self, key: ImageKey, lua_index: usize) -> anyhow::Result<u32> {
+        ensure!(
+            self.scoped_assets.contains_key(&lua_index),
+            "Assets not initialized"
+        );
+        let img =
+            self.with_image_cache(|cache, canvas| cache.acquire(canvas, &key, lua_index))??;
+        Ok(self.add_shared_image(key, img, lua_index))
+    }
+
+    /// Starts decoding `path` on a thread unless it's already being decoded
+    fn spawn_image_job(&mut self, path: &str, size: Option<(u32, u32)>) {
+        // Small draws load a thumbnail when one is ready, large jackets take a long time to
+        // decode for what ends up on screen
+        let source = size
+            .and_then(|(w, h)| {
+                self.thumbnailer
+                    .write()
+                    .ok()?
+                    .get(std::path::Path::new(path), w.max(h))
+            })
+            .unwrap_or_else(|| PathBuf::from(path));
+        self.image_jobs
+            .entry(path.to_string())
+            .or_insert_with(move || {
+                Promise::spawn_thread("load image", move || {
+                    image::open(source)
+                        .map(|img| match size {
+                            Some((w, h)) => {
+                                img.resize(w, h, image::imageops::FilterType::CatmullRom)
+                            }
+                            None => img,
+                        })
+                        .unwrap_or_default()
+                })
+            });
+    }
+
+    fn job_key(&self, path: &str) -> ImageKey {
+        ImageKey {
+            path: PathBuf::from(path),
+            flags: ImageFlags::empty().bits(),
+            size: self.last_job_size,
+        }
+    }
+
+    /// Decodes an image in the background so a later `LoadImageJob` for it is ready right away
+    pub fn preload_image(&mut self, path: &std::path::Path) {
+        let path = path.to_string_lossy();
+        if !self.image_cache.contains(&self.job_key(&path)) {
+            self.spawn_image_job(&path, self.last_job_size);
+        }
+    }
+
+    /// Whether a `LoadImageJob` for `path` would return the image instead of its placeholder
+    pub fn is_image_loaded(&self, path: &std::path::Path) -> bool {
+        let path = path.to_string_lossy();
+        self.image_jobs
+            .get(path.as_ref())
+            .and_then(Promise::ready)
+            .is_some_and(|img| img.width() > 0)
+            || self.image_cache.contains(&self.job_key(&path))
+    }
+
     pub fn load_image(
         &mut self,
         path: impl AsRef<std::path::Path>,
         lua_index: usize,
     ) -> anyhow::Result<u32> {
-        let img = self.with_canvas(|x| x.load_image_file(&path, ImageFlags::empty()))??;
-        self.scoped_assets
-            .get_mut(&lua_index)
-            .ok_or(anyhow!("Assets not initialized"))?
-            .images
-            .insert(self.next_img_id, VgImage::Static(img));
-        let result = self.next_img_id;
-        self.next_img_id += 1;
-
-        Ok(result)
+        self.load_shared_image(
+            ImageKey::new(path.as_ref(), ImageFlags::empty().bits()),
+            lua_index,
+        )
     }
 
     pub fn delete_image(&mut self, image: u32, lua_index: usize) {
-        if let Some(VgImage::Static(id)) = self.scoped_assets[&lua_index].images.get(&image) {
-            let id = *id;
-            log_result!(self.with_canvas(|x| x.delete_image(id)));
+        let Some(assets) = self.scoped_assets.get_mut(&lua_index) else {
+            return;
+        };
+        if !matches!(assets.images.get(&image), Some(VgImage::Shared(..))) {
+            return;
+        }
+        if let Some(VgImage::Shared(key, _)) = assets.images.remove(&image) {
+            log_result!(
+                self.with_image_cache(|cache, canvas| cache.release(canvas, &key, lua_index))
+            );
         }
     }
 
@@ -379,7 +636,8 @@ impl TealData for Vgfx {
             if let Some(paint) = _vgfx.fill_paint.as_mut() {
                 paint.set_color(color);
             } else {
-                _vgfx.fill_paint = Some(Paint::color(color));
+                let fonts = _vgfx.font_chain(_vgfx.label_font);
+                _vgfx.fill_paint = Some(Paint::color(color).with_font(&fonts));
             }
             Ok(())
         });
@@ -399,27 +657,9 @@ impl TealData for Vgfx {
                     imageflags,
                 } = p;
 
-                let Ok(img) = _vgfx
-                    .with_canvas(|canvas| {
-                        canvas.load_image_file(
-                            &filename,
-                            ImageFlags::from_bits(imageflags).unwrap_or(ImageFlags::empty()),
-                        )
-                    })?
-                    .map_err(mlua::Error::external)
-                else {
-                    return Ok(0);
-                };
-
-                let this_id = _vgfx.next_img_id;
-                _vgfx.next_img_id += 1;
-                _vgfx
-                    .scoped_assets
-                    .get_mut(&lua_address(lua))
-                    .ok_or(mlua::Error::external("Assets not initialized"))?
-                    .images
-                    .insert(this_id, VgImage::Static(img));
-                Ok(this_id)
+                Ok(_vgfx
+                    .load_shared_image(ImageKey::new(filename, imageflags), lua_address(lua))
+                    .unwrap_or(0))
             },
         );
 
@@ -443,40 +683,13 @@ impl TealData for Vgfx {
                 path.push(&_vgfx.skin);
                 path.push("textures");
                 path.push(&filename);
-                let img = match _vgfx.with_canvas(|canvas| {
-                    canvas
-                        .load_image_file(
-                            &path,
-                            ImageFlags::from_bits(imageflags).unwrap_or(ImageFlags::empty()),
-                        )
-                        .or_else(|_| {
-                            profile_scope!("reformat image");
-                            let img = image::open(&path)?;
-                            canvas.create_image(
-                                femtovg::ImageSource::try_from(&image::DynamicImage::ImageRgba8(
-                                    img.to_rgba8(),
-                                ))
-                                .expect("Bad image format"),
-                                ImageFlags::from_bits(imageflags).unwrap_or(ImageFlags::empty()),
-                            )
-                        })
-                })? {
-                    Ok(img) => img,
+                match _vgfx.load_shared_image(ImageKey::new(path, imageflags), lua_address(lua)) {
+                    Ok(img) => Ok(Some(img)),
                     Err(err) => {
                         log::error!("Failed to load image \"{}\": {:?}", &filename, err);
-                        return Ok(None);
+                        Ok(None)
                     }
-                };
-
-                let this_id = _vgfx.next_img_id;
-                _vgfx.next_img_id += 1;
-                _vgfx
-                    .scoped_assets
-                    .get_mut(&lua_address(lua))
-                    .ok_or(mlua::Error::external("Assets not initialized"))?
-                    .images
-                    .insert(this_id, VgImage::Static(img));
-                Ok(Some(this_id))
+                }
             },
         );
 
@@ -625,11 +838,8 @@ impl TealData for Vgfx {
 
         );
         add_lua_static_method(methods, "FontFace", |_, _vgfx, p: FontFaceParams| {
-            if let Some(font_id) = _vgfx.fonts.get(&p.s) {
-                _vgfx.label_font = *font_id;
-                if let Some(text_paint) = _vgfx.fill_paint.as_mut() {
-                    text_paint.set_font(&[*font_id]);
-                }
+            if let Some(font_id) = _vgfx.fonts.get(&p.s).copied() {
+                _vgfx.use_font(font_id);
             } else {
                 warn!("No loaded font named: {}", &p.s)
             }
@@ -684,7 +894,11 @@ impl TealData for Vgfx {
 
         //ResetTransform
         add_lua_static_method(methods, "ResetTransform", |_, _vgfx, _: ()| {
-            _vgfx.with_canvas(|canvas| canvas.reset_transform())?;
+            let (root, viewport) = (_vgfx.root_transform, _vgfx.viewport);
+            _vgfx.with_canvas(|canvas| {
+                canvas.reset_transform();
+                Vgfx::apply_root_transform(canvas, root, viewport);
+            })?;
             Ok(())
         });
 
@@ -696,20 +910,16 @@ impl TealData for Vgfx {
         );
         add_lua_static_method(methods, "LoadFont", |_, _vgfx, p: LoadFontParams| {
             let name = p.name;
-            if let (Some(font_id), Some(paint)) =
-                (_vgfx.fonts.get(&name), _vgfx.fill_paint.as_mut())
+            if let (Some(font_id), true) =
+                (_vgfx.fonts.get(&name).copied(), _vgfx.fill_paint.is_some())
             {
-                paint.set_font(&[*font_id]);
-                _vgfx.label_font = *font_id;
+                _vgfx.use_font(font_id);
             } else {
                 let path = p.filename.unwrap_or_else(|| name.clone());
                 let font_id = _vgfx
                     .with_canvas(|canvas| canvas.add_font(&path))?
                     .map_err(mlua::Error::external)?;
-                _vgfx.label_font = font_id;
-                if let Some(paint) = _vgfx.fill_paint.as_mut() {
-                    paint.set_font(&[font_id]);
-                }
+                _vgfx.use_font(font_id);
                 _vgfx.fonts.insert(name, font_id);
             }
 
@@ -727,11 +937,10 @@ impl TealData for Vgfx {
             "LoadSkinFont",
             |_, _vgfx, p: LoadSkinFontParams| {
                 let name = p.name;
-                if let (Some(font_id), Some(paint)) =
-                    (_vgfx.fonts.get(&name), _vgfx.fill_paint.as_mut())
+                if let (Some(font_id), true) =
+                    (_vgfx.fonts.get(&name).copied(), _vgfx.fill_paint.is_some())
                 {
-                    paint.set_font(&[*font_id]);
-                    _vgfx.label_font = *font_id;
+                    _vgfx.use_font(font_id);
                 } else {
                     let path = p.filename.unwrap_or_else(|| name.clone());
                     let mut font_path = _vgfx.game_folder.clone();
@@ -743,11 +952,7 @@ impl TealData for Vgfx {
                     let font_id = _vgfx
                         .with_canvas(|canvas| canvas.add_font(&font_path))?
                         .map_err(mlua::Error::external)?;
-                    _vgfx.label_font = font_id;
-
-                    if let Some(paint) = _vgfx.fill_paint.as_mut() {
-                        paint.set_font(&[font_id]);
-                    }
+                    _vgfx.use_font(font_id);
                     _vgfx.fonts.insert(name, font_id);
                 }
 
@@ -850,7 +1055,7 @@ impl TealData for Vgfx {
                     .fill_paint
                     .clone()
                     .unwrap_or_else(|| _vgfx.stroke_paint.clone())
-                    .with_font(&[label.font])
+                    .with_font(&_vgfx.font_chain(label.font))
                     .with_font_size(label.size as f32)
                     .with_color(_vgfx.label_color)
                     .with_text_align(_vgfx.label_align.0)
@@ -1569,7 +1774,11 @@ impl TealData for Vgfx {
         add_lua_static_method(methods, "Reset", |_lua_index, _vgfx, _: ()| {
             _vgfx.restore_stack.clear();
             _vgfx.image_tint = None;
-            _vgfx.with_canvas(|canvas| canvas.reset())
+            let (root, viewport) = (_vgfx.root_transform, _vgfx.viewport);
+            _vgfx.with_canvas(|canvas| {
+                canvas.reset();
+                Vgfx::apply_root_transform(canvas, root, viewport);
+            })
         });
 
         //PathWinding
@@ -1609,6 +1818,13 @@ impl TealData for Vgfx {
                     h,
                 } = p;
 
+                let lua_index = lua_address(lua);
+                if !_vgfx.scoped_assets.contains_key(&lua_index) {
+                    return Err(mlua::Error::external("Assets not initialized"));
+                }
+                _vgfx.last_job_size = w.zip(h);
+                let cache_key = _vgfx.job_key(&path);
+
                 if let Some((key, job)) = _vgfx.image_jobs.remove_entry(&path) {
                     match job.try_take() {
                         Ok(img) if img.width() > 0 => {
@@ -1620,20 +1836,18 @@ impl TealData for Vgfx {
                                 )
                                 .map_err(mlua::Error::external)
                             })??;
+                            let img_id = _vgfx.with_image_cache(|cache, canvas| {
+                                cache.insert(canvas, cache_key.clone(), lua_index, img_id)
+                            })?;
 
+                            let this_id =
+                                _vgfx.add_shared_image(cache_key.clone(), img_id, lua_index);
                             _vgfx
                                 .scoped_assets
-                                .get_mut(&lua_address(lua))
-                                .ok_or(mlua::Error::external("Assets not initialized"))?
-                                .images
-                                .insert(_vgfx.next_img_id, VgImage::Static(img_id));
-                            _vgfx
-                                .scoped_assets
-                                .get_mut(&lua_address(lua))
+                                .get_mut(&lua_index)
                                 .ok_or(mlua::Error::external("Assets not initialized"))?
                                 .job_imgs
-                                .insert(key, _vgfx.next_img_id);
-                            _vgfx.next_img_id += 1;
+                                .insert(key, this_id);
                         }
                         Ok(_) => {}
                         Err(job) => {
@@ -1642,40 +1856,29 @@ impl TealData for Vgfx {
                     }
                 }
 
-                let key = path.clone();
-                if !_vgfx.scoped_assets[&lua_address(lua)]
-                    .job_imgs
-                    .contains_key(&path)
-                {
-                    _vgfx
-                        .image_jobs
-                        .entry(path.clone())
-                        .or_insert_with(move || {
-                            Promise::spawn_thread("load image", move || {
-                                image::open(key)
-                                    .map(|img| {
-                                        if let (Some(w), Some(h)) = (w, h) {
-                                            img.resize(
-                                                w,
-                                                h,
-                                                image::imageops::FilterType::CatmullRom,
-                                            )
-                                        } else {
-                                            img
-                                        }
-                                    })
-                                    .unwrap_or_default()
-                            })
-                        });
+                let loading = _vgfx.scoped_assets[&lua_index].job_imgs.contains_key(&path);
+                if !loading {
+                    if let Some(img_id) = _vgfx.image_cache.get(&cache_key, lua_index) {
+                        let this_id = _vgfx.add_shared_image(cache_key, img_id, lua_index);
+                        _vgfx
+                            .scoped_assets
+                            .get_mut(&lua_index)
+                            .ok_or(mlua::Error::external("Assets not initialized"))?
+                            .job_imgs
+                            .insert(path.clone(), this_id);
+                        return Ok(this_id);
+                    }
+
+                    _vgfx.spawn_image_job(&path, w.zip(h));
                     _vgfx
                         .scoped_assets
-                        .get_mut(&lua_address(lua))
+                        .get_mut(&lua_index)
                         .ok_or(mlua::Error::external("Assets not initialized"))?
                         .job_imgs
                         .insert(path.clone(), placeholder.unwrap_or_default());
                 }
 
-                Ok(*_vgfx.scoped_assets[&lua_address(lua)]
+                Ok(*_vgfx.scoped_assets[&lua_index]
                     .job_imgs
                     .get(&path)
                     .unwrap_or(&placeholder.unwrap_or_default()))
@@ -1782,7 +1985,7 @@ impl TealData for Vgfx {
 
             let canvas = _vgfx.canvas.lock().expect("Lock error");
             if let Some(label) = _vgfx.scoped_assets[&lua_address(lua)].labels.get(&p.label) {
-                paint.set_font(&[label.font]);
+                paint.set_font(&_vgfx.font_chain(label.font));
                 paint.set_font_size(label.size as f32);
                 paint.set_text_align(_vgfx.label_align.0);
                 paint.set_text_baseline(_vgfx.label_align.1);
@@ -2275,3 +2478,32 @@ impl tealr::mlu::ExportInstances for ExportVgfx {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use femtovg::{renderer::Void, Canvas, Color, Paint};
+
+    use super::load_fallback_fonts;
+
+    #[test]
+    fn cjk_fallback() {
+        let mut canvas = Canvas::new(Void).unwrap();
+        let font_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fonts");
+        let fonts = load_fallback_fonts(&mut canvas, &font_dir);
+        assert!(fonts.len() >= 2);
+
+        let paint = Paint::color(Color::white()).with_font(&fonts);
+        let metrics = canvas
+            .measure_text(0.0, 0.0, "Title 曲名 (Remix)", &paint)
+            .unwrap();
+        assert!(metrics.glyphs.iter().all(|g| g.glyph_id != 0));
+        for glyph in &metrics.glyphs {
+            let expected = if glyph.c.is_ascii() {
+                fonts[0]
+            } else {
+                fonts[1]
+            };
+            assert_eq!(glyph.font_id, expected, "{}", glyph.c);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use femtovg::{renderer::OpenGl, Canvas, ImageFlags, ImageId};
+use puffin::profile_scope;
+
+/// Identifies a cached image, the same file loaded with different flags or sizes is a different image
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ImageKey {
+    pub path: PathBuf,
+    pub flags: u32,
+    pub size: Option<(u32, u32)>,
+}
+
+impl ImageKey {
+    pub fn new(path: impl Into<PathBuf>, flags: u32) -> Self {
+        Self {
+            path: path.into(),
+            flags,
+            size: None,
+        }
+    }
+}
+
+/// Creates and deletes the textures backing the cached images
+pub trait TextureAllocator {
+    type Id: Copy;
+
+    fn load(&mut self, key: &ImageKey) -> anyhow::Result<Self::Id>;
+    /// Approximate amount of memory used by the texture
+    fn byte_size(&self, id: Self::Id) -> usize;
+    fn delete(&mut self, id: Self::Id);
+}
+
+impl TextureAllocator for Canvas<OpenGl> {
+    type Id = ImageId;
+
+    fn load(&mut self, key: &ImageKey) -> anyhow::Result<ImageId> {
+        let flags = ImageFlags::from_bits(key.flags).unwrap_or(ImageFlags::empty());
+        if key.size.is_none() {
+            if let Ok(id) = self.load_image_file(&key.path, flags) {
+                return Ok(id);
+            }
+        }
+
+        profile_scope!("reformat image");
+        let mut img = image::open(&key.path)?;
+        if let Some((w, h)) = key.size {
+            img = img.resize(w, h, image::imageops::FilterType::CatmullRom);
+        }
+        let img = image::DynamicImage::ImageRgba8(img.to_rgba8());
+        Ok(self.create_image(femtovg::ImageSource::try_from(&img)?, flags)?)
+    }
+
+    fn byte_size(&self, id: ImageId) -> usize {
+        self.image_size(id).map(|(w, h)| w * h * 4).unwrap_or(0)
+    }
+
+    fn delete(&mut self, id: ImageId) {
+        self.delete_image(id)
+    }
+}
+
+struct CacheEntry<Id> {
+    id: Id,
+    bytes: usize,
+    /// Number of references held by each Lua state
+    refs: HashMap<usize, usize>,
+    last_used: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImageCacheStats {
+    pub images: usize,
+    pub bytes: usize,
+    pub evictions: usize,
+}
+
+/// Images shared between all Lua states, images without any references are kept around until
+/// the cache goes over its budget and are then evicted starting with the least recently used.
+pub struct ImageCache<Id> {
+    entries: HashMap<ImageKey, CacheEntry<Id>>,
+    budget: usize,
+    bytes: usize,
+    evictions: usize,
+    clock: u64,
+}
+
+impl<Id: Copy> ImageCache<Id> {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: Default::default(),
+            budget,
+            bytes: 0,
+            evictions: 0,
+            clock: 0,
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+    }
+
+    pub fn stats(&self) -> ImageCacheStats {
+        ImageCacheStats {
+            images: self.entries.len(),
+            bytes: self.bytes,
+            evictions: self.evictions,
+        }
+    }
+
+    pub fn contains(&self, key: &ImageKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Adds a reference for `owner` to an image if it is already cached
+    pub fn get(&mut self, key: &ImageKey, owner: usize) -> Option<Id> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        *entry.refs.entry(owner).or_default() += 1;
+        entry.last_used = self.clock;
+        Some(entry.id)
+    }
+
+    /// Adds a reference for `owner` to an image, loading it if it is not cached
+    pub fn acquire<A: TextureAllocator<Id = Id>>(
+        &mut self,
+        allocator: &mut A,
+        key: &ImageKey,
+        owner: usize,
+    ) -> anyhow::Result<Id> {
+        if let Some(id) = self.get(key, owner) {
+            return Ok(id);
+        }
+
+        let id = allocator.load(key)?;
+        Ok(self.insert(allocator, key.clone(), owner, id))
+    }
+
+    /// Adds an image that was created outside of the cache with a reference for `owner`. If the
+    /// image was cached in the meantime the new texture is deleted and the cached one returned.
+    pub fn insert<A: TextureAllocator<Id = Id>>(
+        &mut self,
+        allocator: &mut A,
+        key: ImageKey,
+        owner: usize,
+        id: Id,
+    ) -> Id {
+        if let Some(cached) = self.get(&key, owner) {
+            allocator.delete(id);
+            return cached;
+        }
+
+        let bytes = allocator.byte_size(id);
+        self.bytes += bytes;
+        self.entries.insert(
+            key,
+            CacheEntry {
+                id,
+                bytes,
+                refs: HashMap::from([(owner, 1)]),
+                last_used: self.clock,
+            },
+        );
+        self.evict(allocator);
+        id
+    }
+
+    /// Removes one reference held by `owner`
+    pub fn release<A: TextureAllocator<Id = Id>>(
+        &mut self,
+        allocator: &mut A,
+        key: &ImageKey,
+        owner: usize,
+    ) {
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            if let Some(refs) = entry.refs.get_mut(&owner) {
+                *refs -= 1;
+                if *refs == 0 {
+                    entry.refs.remove(&owner);
+                }
+            }
+            entry.last_used = self.clock;
+        }
+        self.evict(allocator);
+    }
+
+    /// Removes all references held by `owner`
+    pub fn release_owner<A: TextureAllocator<Id = Id>>(&mut self, allocator: &mut A, owner: usize) {
+        for entry in self.entries.values_mut() {
+            entry.refs.remove(&owner);
+        }
+        self.evict(allocator);
+    }
+
+    fn evict<A: TextureAllocator<Id = Id>>(&mut self, allocator: &mut A) {
+        while self.bytes > self.budget {
+            let Some(key) = self
+                .entries
+                .iter()
+                .filter(|(_, e)| e.refs.is_empty())
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+
+            if let Some(entry) = self.entries.remove(&key) {
+                allocator.delete(entry.id);
+                self.bytes -= entry.bytes;
+                self.evictions += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{ImageCache, ImageKey, TextureAllocator};
+
+    const IMAGE_SIZE: usize = 100;
+
+    #[derive(Default)]
+    struct MockAllocator {
+        next_id: u32,
+        live: HashMap<u32, ImageKey>,
+        deleted: Vec<ImageKey>,
+    }
+
+    impl TextureAllocator for MockAllocator {
+        type Id = u32;
+
+        fn load(&mut self, key: &ImageKey) -> anyhow::Result<u32> {
+            self.next_id += 1;
+            self.live.insert(self.next_id, key.clone());
+            Ok(self.next_id)
+        }
+
+        fn byte_size(&self, _id: u32) -> usize {
+            IMAGE_SIZE
+        }
+
+        fn delete(&mut self, id: u32) {
+            let key = self.live.remove(&id).expect("Deleted unknown texture");
+            self.deleted.push(key);
+        }
+    }
+
+    fn key(name: &str) -> ImageKey {
+        ImageKey::new(name, 0)
+    }
+
+    #[test]
+    fn shared_between_owners() {
+        let mut alloc = MockAllocator::default();
+        let mut cache = ImageCache::new(0);
+
+        let a = cache.acquire(&mut alloc, &key("a"), 1).unwrap();
+        let b = cache.acquire(&mut alloc, &key("a"), 2).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(alloc.live.len(), 1);
+
+        cache.release_owner(&mut alloc, 1);
+        assert!(alloc.deleted.is_empty());
+        cache.release_owner(&mut alloc, 2);
+        assert_eq!(alloc.deleted, vec![key("a")]);
+        assert_eq!(cache.stats().images, 0);
+        assert_eq!(cache.stats().bytes, 0);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut alloc = MockAllocator::default();
+        let mut cache = ImageCache::new(IMAGE_SIZE * 2);
+
+        for name in ["a", "b"] {
+            cache.acquire(&mut alloc, &key(name), 1).unwrap();
+        }
+        cache.release(&mut alloc, &key("b"), 1);
+        cache.release(&mut alloc, &key("a"), 1);
+        assert!(alloc.deleted.is_empty());
+
+        cache.acquire(&mut alloc, &key("c"), 1).unwrap();
+        assert_eq!(alloc.deleted, vec![key("b")]);
+
+        cache.release(&mut alloc, &key("c"), 1);
+        cache.acquire(&mut alloc, &key("b"), 1).unwrap();
+        assert_eq!(alloc.deleted, vec![key("b"), key("a")]);
+        assert_eq!(cache.stats().evictions, 2);
+        assert_eq!(cache.stats().bytes, IMAGE_SIZE * 2);
+    }
+
+    #[test]
+    fn referenced_images_are_kept() {
+        let mut alloc = MockAllocator::default();
+        let mut cache = ImageCache::new(IMAGE_SIZE);
+
+        for name in ["a", "b", "c"] {
+            cache.acquire(&mut alloc, &key(name), 1).unwrap();
+        }
+        cache.acquire(&mut alloc, &key("a"), 2).unwrap();
+        assert!(alloc.deleted.is_empty());
+        assert_eq!(cache.stats().bytes, IMAGE_SIZE * 3);
+
+        cache.release_owner(&mut alloc, 1);
+        assert_eq!(alloc.deleted.len(), 2);
+        assert!(!alloc.deleted.contains(&key("a")));
+        assert_eq!(cache.stats().images, 1);
+    }
+
+    #[test]
+    fn insert_keeps_cached_image() {
+        let mut alloc = MockAllocator::default();
+        let mut cache = ImageCache::new(IMAGE_SIZE * 4);
+
+        let cached = cache.acquire(&mut alloc, &key("a"), 1).unwrap();
+        let loaded = alloc.load(&key("a")).unwrap();
+        assert_eq!(cache.insert(&mut alloc, key("a"), 2, loaded), cached);
+        assert_eq!(alloc.deleted, vec![key("a")]);
+        assert!(!alloc.live.contains_key(&loaded));
+    }
+}
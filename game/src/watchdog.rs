@@ -0,0 +1,117 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Time without a finished frame before the game is considered stuck
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Frames finished so far, advanced at the end of every frame
+static FRAMES: AtomicU64 = AtomicU64::new(0);
+
+pub fn frame_done() {
+    FRAMES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Tracks the frame counter as seen by the watchdog thread
+#[derive(Debug)]
+struct Watchdog {
+    frames: u64,
+    last_frame: Instant,
+    stalled: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Change {
+    Stalled,
+    Resumed(Duration),
+}
+
+impl Watchdog {
+    fn new(now: Instant) -> Self {
+        Self {
+            frames: 0,
+            last_frame: now,
+            stalled: false,
+        }
+    }
+
+    /// Reports a stall once when `frames` hasn't changed for `timeout` and once when it changes
+    /// again
+    fn check(&mut self, frames: u64, now: Instant, timeout: Duration) -> Option<Change> {
+        if frames != self.frames {
+            let stalled_for = now - self.last_frame;
+            self.frames = frames;
+            self.last_frame = now;
+            return std::mem::take(&mut self.stalled).then_some(Change::Resumed(stalled_for));
+        }
+
+        if !self.stalled && now - self.last_frame >= timeout {
+            self.stalled = true;
+            return Some(Change::Stalled);
+        }
+
+        None
+    }
+}
+
+/// Starts a thread that logs when no frame finishes for [`STALL_TIMEOUT`], a stuck main thread
+/// is most likely waiting on a lock that is never released
+pub fn spawn() {
+    let spawned = std::thread::Builder::new()
+        .name("Watchdog".into())
+        .spawn(|| {
+            let mut watchdog = Watchdog::new(Instant::now());
+            loop {
+                std::thread::sleep(STALL_TIMEOUT / 4);
+                match watchdog.check(
+                    FRAMES.load(Ordering::Relaxed),
+                    Instant::now(),
+                    STALL_TIMEOUT,
+                ) {
+                    Some(Change::Stalled) => log::error!(
+                        "No frame finished in {} seconds, the game may be deadlocked. Attach a \
+                         debugger to process {} to see where its threads are waiting, with gdb: \
+                         gdb -p {} -batch -ex \"thread apply all bt\"",
+                        STALL_TIMEOUT.as_secs(),
+                        std::process::id(),
+                        std::process::id()
+                    ),
+                    Some(Change::Resumed(stalled_for)) => log::warn!(
+                        "Frames resumed after {:.1} seconds",
+                        stalled_for.as_secs_f32()
+                    ),
+                    None => {}
+                }
+            }
+        });
+
+    if let Err(e) = spawned {
+        log::warn!("Could not start the watchdog: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Change, Watchdog};
+
+    #[test]
+    fn reports_stalls_once() {
+        let timeout = Duration::from_secs(10);
+        let start = Instant::now();
+        let at = |s| start + Duration::from_secs(s);
+        let mut watchdog = Watchdog::new(start);
+
+        assert_eq!(watchdog.check(100, at(2), timeout), None);
+        assert_eq!(watchdog.check(100, at(11), timeout), None);
+        assert_eq!(watchdog.check(100, at(12), timeout), Some(Change::Stalled));
+        assert_eq!(watchdog.check(100, at(20), timeout), None);
+        assert_eq!(
+            watchdog.check(101, at(30), timeout),
+            Some(Change::Resumed(Duration::from_secs(28)))
+        );
+        assert_eq!(watchdog.check(200, at(31), timeout), None);
+    }
+}
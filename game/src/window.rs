@@ -5,7 +5,7 @@ use femtovg::{renderer::OpenGl, Canvas};
 use game_loop::winit::{
     self,
     event_loop::{EventLoop, EventLoopBuilder},
-    window::WindowBuilder,
+    window::{WindowBuilder, WindowButtons},
 };
 use glow::Context;
 use glutin::{
@@ -39,13 +39,18 @@ type WindowCreation = (
 
 /// Mostly borrowed code from femtovg/examples
 pub fn create_window() -> anyhow::Result<WindowCreation> {
+    let cab_mode = GameConfig::get().cab_mode.enabled;
     let settings = &GameConfig::get().graphics;
 
     let event_loop = EventLoopBuilder::<UscInputEvent>::with_user_event().build()?;
 
-    let window_builder = WindowBuilder::new()
+    let mut window_builder = WindowBuilder::new()
         .with_resizable(true)
         .with_title("USC Game");
+    if cab_mode {
+        window_builder =
+            window_builder.with_enabled_buttons(WindowButtons::all() - WindowButtons::CLOSE);
+    }
 
     let window_builder = match settings.fullscreen {
         crate::config::Fullscreen::Windowed { pos, size } => {
@@ -28,7 +28,16 @@ export const SongSelect: Component<{
       <div class="grid grid-cols-12 overflow-auto">
         <h2 class="col-span-2 text-2xl font-bold">Folders</h2>
         <h2 class="col-span-2 text-2xl font-bold">Levels</h2>
-        <div class="col-span-8"></div>
+        <div class="col-span-6"></div>
+        <div class="col-span-2">
+          <ListItem
+            name="Radio"
+            selected={p.state().radio}
+            onClick={() =>
+              p.send({ variant: "SetRadio", v: !p.state().radio })
+            }
+          ></ListItem>
+        </div>
         <div class="flex flex-col h-full items-start gap-3 p-2 col-span-2 overflow-auto relative">
           <For each={p.state().filters}>
             {(x, i) => {
@@ -4,9 +4,15 @@ export type SortDir = "Asc" | "Desc"
 
 export type SongFilterType = "None" | { Folder: string } | { Collection: string }
 
-export type GameState = { variant: "None" } | { variant: "TitleScreen" } | { variant: "SongSelect"; search_string: string; level_filter: number; folder_filter_index: number; sort_index: number; filters: SongFilterType[]; sorts: SongSort[] }
+export type GameState = { variant: "None" } | { variant: "TitleScreen" } | { variant: "SongSelect"; search_string: string; level_filter: number; folder_filter_index: number; sort_index: number; filters: SongFilterType[]; sorts: SongSort[]; radio: boolean } | { variant: "Judgements"; events: JudgementEvent[] } | { variant: "PlaySnapshot"; score: number; gauge: number; combo: number; dropped_batches: number } | { variant: "InGame"; chart_hash: string; modifiers: PlayModifierInfo } | { variant: "Results"; chart_hash: string; score: number; modifiers: PlayModifierInfo }
 
-export type ClientEvent = { variant: "Invalid"; v: string } | { variant: "Start" } | { variant: "Back" } | { variant: "SetSearch"; v: string } | { variant: "SetLevelFilter"; v: number } | { variant: "SetSongFilterType"; v: SongFilterType } | { variant: "SetSongSort"; v: SongSort }
+export type PlayModifierInfo = { mirror: boolean; random: boolean; hard_random: boolean; seed: number | null }
+
+export type JudgementRating = "Crit" | "Near" | "Miss"
+
+export type JudgementEvent = { lane: number; rating: JudgementRating; delta: number; combo: number }
+
+export type ClientEvent = { variant: "Invalid"; v: string } | { variant: "Start" } | { variant: "StartDemo" } | { variant: "Back" } | { variant: "SetSearch"; v: string } | { variant: "SetLevelFilter"; v: number } | { variant: "SetSongFilterType"; v: SongFilterType } | { variant: "SetSongSort"; v: SongSort } | { variant: "SetRadio"; v: boolean } | { variant: "Subscribe"; v: { judgements: boolean } }
 
 export type SongSort = { sort_type: SongSortType; direction: SortDir }
 
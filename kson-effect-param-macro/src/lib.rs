@@ -45,8 +45,9 @@ pub fn derive_effect_param(input: TokenStream) -> TokenStream {
             for f in &s.fields {
                 if let Some(ident) = &f.ident {
                     fields.push(quote!(stringify!(#ident)));
+                    // Values that don't parse keep the previous one
                     match_arms.push(quote!(stringify!(#ident) => Self {
-                        #ident: param.parse().unwrap_or_default(),
+                        #ident: param.parse().unwrap_or_else(|_| self.#ident.clone()),
                         ..self.clone()
                     },))
                 }
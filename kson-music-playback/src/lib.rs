@@ -1,49 +1,99 @@
 use anyhow::Result;
 use itertools::Itertools;
+use kson::effects::AudioEffect;
 use kson::overlaps::Overlaps;
 use kson::Chart;
 
 use rodio::source::{Buffered, SkipDuration};
 pub use rodio::Source;
 
-use std::collections::VecDeque;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use kson_rodio_sources::{
     self,
-    bitcrush::bit_crusher,
-    effected_part::effected_part,
-    flanger::flanger,
-    gate::gate,
-    mix_source::{MixSource, NoMix},
-    pitch_shift::pitch_shift,
-    re_trigger::re_trigger,
-    side_chain::side_chain,
-    tape_stop::tape_stop,
-    wobble::wobble,
+    audio_effect::{filter_state, is_supported, EffectSourceBuilder},
+    effected_part::{effected_part, replaced_part},
 };
 
+type AudioSource = SkipDuration<Buffered<Box<dyn Source<Item = f32> + Send>>>;
 type ActiveEffect = ((u64, u64), Box<dyn Source<Item = f32> + Send>);
+/// Sample range of the effect and what builds it, ordered by start
+type ChartEffect = ((u64, u64), Box<EffectBuilder>);
 
 pub struct AudioFile {
-    audio: SkipDuration<Buffered<Box<dyn Source<Item = f32> + Send>>>,
-    audio_base: SkipDuration<Buffered<Box<dyn Source<Item = f32> + Send>>>,
-    effected: Option<SkipDuration<Buffered<Box<dyn Source<Item = f32> + Send>>>>,
-    effected_base: Option<SkipDuration<Buffered<Box<dyn Source<Item = f32> + Send>>>>,
+    audio: AudioSource,
+    audio_base: AudioSource,
+    effected: Option<AudioSource>,
+    effected_base: Option<AudioSource>,
     leadin: Arc<AtomicUsize>,
     stopped: Arc<AtomicBool>,
     fx_enable: [Arc<AtomicBool>; 2],
     channels: u16,
     sample_rate: u32,
     pos: Arc<AtomicUsize>,
-    effects: VecDeque<((u64, u64), Box<EffectBuilder>)>,
+    seek: Arc<SeekSlot>,
+    effects: Arc<Vec<ChartEffect>>,
+    /// Index of the first effect in `effects` that hasn't started yet
+    next_effect: usize,
     active_effects: Vec<ActiveEffect>,
 }
 
+/// Playback at a seek target, skipping through the buffered audio is slow so it's prepared on
+/// its own thread instead of the audio thread
+struct Seeked {
+    sample: usize,
+    audio: AudioSource,
+    active_effects: Vec<ActiveEffect>,
+    next_effect: usize,
+}
+
+impl Seeked {
+    fn new(base: &AudioSource, effects: &[ChartEffect], sample: usize) -> Self {
+        let skipped = |samples: usize| {
+            let mut audio = base.clone();
+            if samples > 0 {
+                audio.nth(samples - 1);
+            }
+            audio
+        };
+
+        let next_effect = effects.partition_point(|((start, _), _)| *start < sample as u64);
+        let active_effects = effects[..next_effect]
+            .iter()
+            .filter(|((_, end), _)| *end > sample as u64)
+            .map(|(range, builder)| {
+                // Built like it would have been when playback reached the start of the effect
+                let built_at = range.0 as usize + 1;
+                let mut effect = builder(Box::new(skipped(built_at)), built_at);
+                if sample > built_at {
+                    effect.nth(sample - built_at - 1);
+                }
+                (*range, effect)
+            })
+            .collect();
+
+        Self {
+            sample,
+            audio: skipped(sample),
+            active_effects,
+            next_effect,
+        }
+    }
+}
+
+#[derive(Default)]
+struct SeekSlot {
+    /// Incremented for every seek, only the latest one gets applied
+    generation: AtomicUsize,
+    ready: AtomicBool,
+    seeked: Mutex<Option<Seeked>>,
+}
+
 pub struct EventList<T> {
     events: Vec<(u32, T)>,
 }
@@ -83,6 +133,13 @@ impl Iterator for AudioFile {
             return None;
         }
 
+        if self.seek.ready.swap(false, Ordering::Relaxed) {
+            let seeked = self.seek.seeked.lock().expect("Lock error").take();
+            if let Some(seeked) = seeked {
+                self.apply_seek(seeked);
+            }
+        }
+
         let leadin = self.leadin.load(Ordering::Relaxed);
         if leadin > 0 {
             self.leadin.store(leadin - 1, Ordering::Relaxed);
@@ -103,15 +160,15 @@ impl Iterator for AudioFile {
         self.active_effects
             .retain(|((_, end), _)| *end > (pos as u64));
 
-        while let Some(((start, end), builder)) = self.effects.pop_front() {
-            if start > pos as _ {
-                self.effects.push_front(((start, end), builder));
+        while let Some((range, builder)) = self.effects.get(self.next_effect) {
+            if range.0 > pos as _ {
                 break;
             }
 
-            let new_effect = builder(Box::new(self.audio.clone()));
+            let new_effect = builder(Box::new(self.audio.clone()), pos + 1);
 
-            self.active_effects.push(((start, end), new_effect));
+            self.active_effects.push((*range, new_effect));
+            self.next_effect += 1;
         }
 
         if effected.is_some() && enable_fx {
@@ -155,6 +212,13 @@ impl AudioFile {
         }
     }
 
+    fn apply_seek(&mut self, seeked: Seeked) {
+        self.audio = seeked.audio;
+        self.pos.store(seeked.sample, Ordering::SeqCst);
+        self.active_effects = seeked.active_effects;
+        self.next_effect = seeked.next_effect;
+    }
+
     fn set_stopped(&mut self, val: bool) {
         self.stopped.store(val, Ordering::SeqCst);
     }
@@ -168,14 +232,17 @@ impl AudioFile {
     }
 }
 
-type EffectBuilder =
-    dyn Fn(Box<dyn Source<Item = f32> + Send>) -> Box<dyn Source<Item = f32> + Send> + Send;
+/// Applies effects to the audio, which is at the sample position given with it
+type EffectBuilder = dyn Fn(BoxedSource, usize) -> BoxedSource + Send + Sync;
+type BoxedSource = Box<dyn Source<Item = f32> + Send>;
 
 pub struct AudioPlayback {
     file: Option<AudioFile>,
     last_file: String,
-    effects: Vec<((u64, u64), Box<EffectBuilder>)>,
+    effects: Arc<Vec<ChartEffect>>,
     leadin: Duration,
+    /// Alternate audio of the `AudioSwap` effects by file name
+    swap_audio: HashMap<String, Arc<[f32]>>,
 }
 
 impl AudioPlayback {
@@ -183,8 +250,9 @@ impl AudioPlayback {
         AudioPlayback {
             file: None,
             last_file: String::new(),
-            effects: vec![],
+            effects: Arc::default(),
             leadin: Duration::ZERO,
+            swap_audio: HashMap::new(),
         }
     }
 
@@ -206,6 +274,13 @@ impl AudioPlayback {
         self.leadin
     }
 
+    /// Adds the audio `AudioSwap` effects with the file `name` switch to, `samples` have to be in
+    /// the channels and sample rate of the opened audio and start at the same time. Has to be
+    /// called before [`AudioPlayback::build_effects`].
+    pub fn add_swap_audio(&mut self, name: impl Into<String>, samples: Arc<[f32]>) {
+        self.swap_audio.insert(name.into(), samples);
+    }
+
     pub fn build_effects(&mut self, chart: &Chart) {
         let offset = Duration::from_millis(chart.audio.bgm.offset.max(0) as _);
         let neg_offset = Duration::from_millis(chart.audio.bgm.offset.min(0).unsigned_abs() as _);
@@ -219,7 +294,7 @@ impl AudioPlayback {
 
         //TODO: Clean up
         //TODO: Effect priority
-        self.effects = chart
+        let effects = chart
             .get_effect_tracks()
             .into_iter()
             .map(|x| vec![x])
@@ -267,9 +342,10 @@ impl AudioPlayback {
                         )
                     })
                     .collect_vec();
+                let swap_audio = self.swap_audio.clone();
                 (
                     (start_pos as u64, end_pos as u64),
-                    Box::new(move |base| {
+                    Box::new(move |base, position| {
                         effect_part
                             .iter()
                             .fold(base, |base, ((start_ms, end_ms, bpm), effect)| {
@@ -277,96 +353,24 @@ impl AudioPlayback {
                                 let end = Duration::from_nanos((end_ms * 1000000.0) as _);
                                 let duration = end - start;
                                 let bpm = *bpm;
-                                let effected: Box<dyn MixSource<Item = f32> + Send> = match effect {
-                                    kson::effects::AudioEffect::ReTrigger(r) => {
-                                        let duration = Duration::from_secs_f64(
-                                            (240.0 * r.wave_length.interpolate(1.0, true) as f64)
-                                                / bpm,
-                                        );
-
-                                        let update_duration = Duration::from_secs_f64(
-                                            (240.0 * r.update_period.interpolate(1.0, true) as f64)
-                                                / bpm,
-                                        );
-                                        Box::new(re_trigger(
-                                            base,
-                                            start,
-                                            duration,
-                                            update_duration,
-                                            1.0,
-                                        ))
-                                    }
-                                    kson::effects::AudioEffect::Gate(g) => {
-                                        let period = Duration::from_secs_f64(
-                                            (240.0 * g.wave_length.interpolate(1.0, true) as f64)
-                                                / bpm,
-                                        );
-                                        Box::new(gate(base, start, period, 0.6, 0.4))
-                                    }
-                                    kson::effects::AudioEffect::Flanger(_f) => Box::new(flanger(
-                                        base,
-                                        Duration::from_millis(4),
-                                        Duration::from_millis(1),
-                                        0.5,
-                                        0.05,
-                                    )),
-                                    kson::effects::AudioEffect::PitchShift(p) => Box::new(
-                                        pitch_shift(base, p.pitch.interpolate(1.0, true) as _),
-                                    ),
-                                    kson::effects::AudioEffect::BitCrusher(b) => Box::new(
-                                        bit_crusher(base, b.reduction.interpolate(1.0, true) as _),
-                                    ),
-                                    kson::effects::AudioEffect::Phaser(_p) => Box::new(
-                                        //TODO
-                                        flanger(
-                                            base,
-                                            Duration::from_millis(4),
-                                            Duration::from_millis(1),
-                                            0.5,
-                                            0.05,
-                                        ),
-                                    ),
-                                    kson::effects::AudioEffect::Wobble(w) => Box::new(wobble(
-                                        base,
-                                        1.0 / ((240.0 * w.wave_length.interpolate(1.0, true))
-                                            / bpm as f32),
-                                        w.lo_freq.interpolate(1.0, true) as _,
-                                        w.hi_freq.interpolate(1.0, true) as _,
-                                    )),
-                                    kson::effects::AudioEffect::TapeStop(_t) => {
-                                        Box::new(tape_stop(base, start, duration))
-                                    }
-                                    kson::effects::AudioEffect::Echo(r) => {
-                                        let duration = Duration::from_secs_f64(
-                                            (240.0 * r.wave_length.interpolate(1.0, true) as f64)
-                                                / bpm,
-                                        );
-                                        let feedback =
-                                            r.feedback_level.interpolate(1.0, true).clamp(0.0, 1.0);
-
-                                        Box::new(re_trigger(
-                                            base,
-                                            start,
-                                            duration,
-                                            Duration::ZERO,
-                                            feedback,
-                                        ))
-                                    }
-                                    kson::effects::AudioEffect::SideChain(s) => {
-                                        let bpm = bpm as f32;
-
-                                        Box::new(side_chain(
-                                            base,
-                                            start,
-                                            s.period.to_duration(bpm, 1.0, true),
-                                            s.attack_time.to_duration(bpm, 1.0, true),
-                                            s.hold_time.to_duration(bpm, 1.0, true),
-                                            s.release_time.to_duration(bpm, 1.0, true),
-                                            s.ratio.interpolate(1.0, true),
-                                        ))
-                                    }
-                                    _ => Box::new(NoMix(base)),
-                                };
+                                if let AudioEffect::AudioSwap(name) = effect {
+                                    // Swaps without audio play the normal audio
+                                    let Some(samples) = swap_audio.get(name).cloned() else {
+                                        return base;
+                                    };
+                                    let alternate =
+                                        (position..samples.len()).map(move |i| samples[i]);
+                                    return Box::new(replaced_part(base, alternate, start, duration))
+                                        as Box<dyn Source<Item = f32> + Send>;
+                                }
+                                if !is_supported(effect) {
+                                    return base;
+                                }
+                                let (effected, _) = EffectSourceBuilder::new(effect, bpm as f32)
+                                    .start(start)
+                                    .duration(duration)
+                                    .build(base)
+                                    .expect("Unsupported effect");
                                 Box::new(effected_part(effected, start, duration, 1.0))
                                     as Box<dyn Source<Item = f32> + Send>
                             }) as Box<dyn Source<Item = f32> + Send>
@@ -374,6 +378,7 @@ impl AudioPlayback {
                 )
             })
             .collect();
+        self.effects = Arc::new(effects);
     }
 
     pub fn get_ms(&self) -> f64 {
@@ -395,6 +400,35 @@ impl AudioPlayback {
         }
     }
 
+    /// Moves the playback to `ms`, using the same time base as [`AudioPlayback::get_ms`]. The
+    /// audio keeps playing from where it was until the new position has been prepared.
+    pub fn seek(&mut self, ms: f64) {
+        if let Some(file) = &self.file {
+            let audio_ms = ms - self.leadin.as_secs_f64() * 1000.0;
+            file.set_leadin(Duration::from_secs_f64((-audio_ms).max(0.0) / 1000.0));
+            let frames = (audio_ms.max(0.0) * file.sample_rate as f64 / 1000.0) as usize;
+            let sample = frames * file.channels as usize;
+
+            let generation = file.seek.generation.fetch_add(1, Ordering::SeqCst) + 1;
+            let slot = file.seek.clone();
+            let base = file.audio_base.clone();
+            let effects = self.effects.clone();
+            let spawned = std::thread::Builder::new()
+                .name("Audio seek".into())
+                .spawn(move || {
+                    let seeked = Seeked::new(&base, &effects, sample);
+                    let mut ready = slot.seeked.lock().expect("Lock error");
+                    if slot.generation.load(Ordering::SeqCst) == generation {
+                        *ready = Some(seeked);
+                        slot.ready.store(true, Ordering::SeqCst);
+                    }
+                });
+            if let Err(e) = spawned {
+                log::warn!("Could not seek: {e}");
+            }
+        }
+    }
+
     pub fn is_playing(&self) -> bool {
         match &self.file {
             Some(f) => !f.stopped.load(Ordering::SeqCst),
@@ -415,7 +449,9 @@ impl AudioPlayback {
                 channels: file.channels,
                 sample_rate: file.sample_rate,
                 pos: file.pos.clone(),
-                effects: std::mem::take(&mut self.effects).into_iter().collect(),
+                seek: file.seek.clone(),
+                effects: self.effects.clone(),
+                next_effect: 0,
                 active_effects: vec![],
             })
         } else {
@@ -441,13 +477,24 @@ impl AudioPlayback {
         source: Box<dyn Source<Item = f32> + Send>,
         filename: &str,
         effected: Option<Box<dyn Source<Item = f32> + Send>>,
+    ) -> Result<()> {
+        self.open_buffered(source.buffered(), filename, effected)
+    }
+
+    /// Same as [`AudioPlayback::open`] for audio that is already buffered, clones of `source` share
+    /// the decoded samples with the playback
+    pub fn open_buffered(
+        &mut self,
+        source: Buffered<Box<dyn Source<Item = f32> + Send>>,
+        filename: &str,
+        effected: Option<Box<dyn Source<Item = f32> + Send>>,
     ) -> Result<()> {
         let rate = source.sample_rate();
         let channels = source.channels();
 
-        let effected: Option<SkipDuration<Buffered<Box<dyn Source<Item = f32> + Send>>>> =
+        let effected: Option<AudioSource> =
             effected.map(|e| e.buffered().skip_duration(Duration::ZERO));
-        let audio = source.buffered().skip_duration(Duration::ZERO);
+        let audio = source.skip_duration(Duration::ZERO);
         self.file = Some(AudioFile {
             audio: audio.clone(),
             audio_base: audio,
@@ -462,7 +509,9 @@ impl AudioPlayback {
             channels,
             sample_rate: rate,
             pos: Arc::new(AtomicUsize::new(0)),
-            effects: VecDeque::new(),
+            seek: Arc::default(),
+            effects: Arc::default(),
+            next_effect: 0,
             active_effects: vec![],
         });
         self.last_file = filename.to_string();
@@ -517,25 +566,71 @@ pub trait GetBiQuadState {
 
 impl GetBiQuadState for kson::effects::AudioEffect {
     fn get_biquad_state(&self, p: f32) -> Option<kson_rodio_sources::biquad::BiQuadState> {
-        use kson_rodio_sources::biquad::BiQuadState;
-        use kson_rodio_sources::biquad::BiQuadType;
-        match self {
-            kson::effects::AudioEffect::HighPassFilter(v) => Some(BiQuadState::new(
-                BiQuadType::HighPass,
-                v.q.interpolate(p, true),
-                v.freq.interpolate(p, true),
-            )),
-            kson::effects::AudioEffect::LowPassFilter(v) => Some(BiQuadState::new(
-                BiQuadType::LowPass,
-                v.q.interpolate(p, true),
-                v.freq.interpolate(p, true),
-            )),
-            kson::effects::AudioEffect::PeakingFilter(v) => Some(BiQuadState::new(
-                BiQuadType::Peaking(v.gain.interpolate(p, true) * 20.0),
-                v.q.interpolate(p, true),
-                v.freq.interpolate(p, true),
-            )),
-            _ => None,
+        filter_state(self, p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::Ordering, Arc};
+
+    use kson::{Chart, Ksh};
+    use rodio::buffer::SamplesBuffer;
+
+    use super::AudioPlayback;
+
+    #[test]
+    fn audio_swap() {
+        // Holds FX-L with the swap from 2s to 4s
+        let chart = Chart::from_ksh(include_str!("fixtures/audio_swap.ksh")).unwrap();
+        let mut playback = AudioPlayback::new();
+        playback
+            .open(
+                Box::new(SamplesBuffer::new(1, 1000, vec![0.25; 6000])),
+                "song.ogg",
+                None,
+            )
+            .unwrap();
+        let alternate: Vec<f32> = (0..6000).map(|i| 0.5 + i as f32 / 12000.0).collect();
+        playback.add_swap_audio("swap.ogg", Arc::from(alternate.clone()));
+        playback.build_effects(&chart);
+        playback.set_fx_enable(true, false);
+
+        let samples: Vec<f32> = playback.get_source().unwrap().collect();
+        assert_eq!(samples.len(), 6000);
+        assert!(samples[..2000].iter().all(|s| *s == 0.25));
+        // The alternate audio plays from the same point in time
+        assert_eq!(samples[2001..3999], alternate[2001..3999]);
+        assert!(samples[4001..].iter().all(|s| *s == 0.25));
+    }
+
+    #[test]
+    fn seek_keeps_effects() {
+        let chart = Chart::from_ksh(include_str!("fixtures/audio_swap.ksh")).unwrap();
+        let mut playback = AudioPlayback::new();
+        playback
+            .open(
+                Box::new(SamplesBuffer::new(1, 1000, vec![0.25; 6000])),
+                "song.ogg",
+                None,
+            )
+            .unwrap();
+        let alternate: Vec<f32> = (0..6000).map(|i| 0.5 + i as f32 / 12000.0).collect();
+        playback.add_swap_audio("swap.ogg", Arc::from(alternate.clone()));
+        playback.build_effects(&chart);
+        playback.set_fx_enable(true, false);
+
+        let mut source = playback.get_source().unwrap();
+        assert_eq!(source.by_ref().take(5000).count(), 5000);
+
+        // Loop back into the middle of the swap
+        playback.seek(2500.0);
+        while !source.seek.ready.load(Ordering::SeqCst) {
+            std::thread::yield_now();
         }
+        let samples: Vec<f32> = source.collect();
+        assert_eq!(samples.len(), 3500);
+        assert_eq!(samples[..1499], alternate[2500..3999]);
+        assert!(samples[1501..].iter().all(|s| *s == 0.25));
     }
 }
@@ -0,0 +1,279 @@
+use std::{fmt::Display, sync::mpsc::channel, time::Duration};
+
+use kson::{effects::AudioEffect, parameter::EffectParameter};
+use rodio::Source;
+
+use crate::{
+    biquad::{biquad, BiQuadState, BiQuadType, BiquadController},
+    bitcrush::bit_crusher,
+    flanger::flanger,
+    gate::gate,
+    mix_source::MixSource,
+    pitch_shift::pitch_shift,
+    re_trigger::re_trigger,
+    side_chain::side_chain,
+    tape_stop::tape_stop,
+    wobble::wobble,
+};
+
+pub type EffectSource = Box<dyn MixSource<Item = f32> + Send>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EffectSourceError {
+    /// The effect has no source implementing it
+    Unimplemented(&'static str),
+}
+
+impl Display for EffectSourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EffectSourceError::Unimplemented(name) => write!(f, "{name} is not implemented"),
+        }
+    }
+}
+
+impl std::error::Error for EffectSourceError {}
+
+/// Parameters of a built effect that can be changed while it plays
+#[derive(Default)]
+pub struct EffectHandles {
+    /// Filter state and mix of the filter effects
+    pub filter: Option<BiquadController>,
+}
+
+/// Length of a duration parameter, tempo synced lengths are fractions of a 4/4 measure at `bpm`
+pub fn param_duration(param: &EffectParameter<f32>, bpm: f32, v: f32) -> Duration {
+    param.to_duration(bpm, v, true)
+}
+
+/// Whether [`EffectSourceBuilder::build`] can make a source for `effect`
+pub fn is_supported(effect: &AudioEffect) -> bool {
+    !matches!(effect, AudioEffect::AudioSwap(_))
+}
+
+/// Filter state of the filter effects at parameter position `v`
+pub fn filter_state(effect: &AudioEffect, v: f32) -> Option<BiQuadState> {
+    match effect {
+        AudioEffect::HighPassFilter(f) => Some(BiQuadState::new(
+            BiQuadType::HighPass,
+            f.q.interpolate(v, true),
+            f.freq.interpolate(v, true),
+        )),
+        AudioEffect::LowPassFilter(f) => Some(BiQuadState::new(
+            BiQuadType::LowPass,
+            f.q.interpolate(v, true),
+            f.freq.interpolate(v, true),
+        )),
+        AudioEffect::PeakingFilter(f) => Some(BiQuadState::new(
+            BiQuadType::Peaking(f.gain.interpolate(v, true) * 20.0),
+            f.q.interpolate(v, true),
+            f.freq.interpolate(v, true),
+        )),
+        _ => None,
+    }
+}
+
+/// Turns an [`AudioEffect`] into a source applying it
+pub struct EffectSourceBuilder<'a> {
+    effect: &'a AudioEffect,
+    bpm: f32,
+    start: Duration,
+    duration: Duration,
+    v: f32,
+}
+
+impl<'a> EffectSourceBuilder<'a> {
+    pub fn new(effect: &'a AudioEffect, bpm: f32) -> Self {
+        Self {
+            effect,
+            bpm,
+            start: Duration::ZERO,
+            duration: Duration::from_secs_f32(240.0 / bpm),
+            v: 1.0,
+        }
+    }
+
+    /// Time into the input where the effect starts
+    pub fn start(mut self, start: Duration) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// Length of the effect, used by effects that change over their length like the tape stop
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Position between the start and end of ranged parameters, 1 for FX holds
+    pub fn param(mut self, v: f32) -> Self {
+        self.v = v;
+        self
+    }
+
+    pub fn build(
+        self,
+        input: impl Source<Item = f32> + Send + 'static,
+    ) -> Result<(EffectSource, EffectHandles), EffectSourceError> {
+        let Self {
+            effect,
+            bpm,
+            start,
+            duration,
+            v,
+        } = self;
+        let length = |p: &EffectParameter<f32>| param_duration(p, bpm, v);
+
+        let source: EffectSource = match effect {
+            AudioEffect::ReTrigger(r) => Box::new(re_trigger(
+                input,
+                start,
+                length(&r.wave_length),
+                length(&r.update_period),
+                1.0,
+            )),
+            AudioEffect::Gate(g) => Box::new(gate(input, start, length(&g.wave_length), 0.6, 0.4)),
+            AudioEffect::Flanger(_) => Box::new(flanger(
+                input,
+                Duration::from_millis(4),
+                Duration::from_millis(1),
+                0.5,
+                0.05,
+            )),
+            AudioEffect::PitchShift(p) => {
+                Box::new(pitch_shift(input, p.pitch.interpolate(v, true) as _))
+            }
+            AudioEffect::BitCrusher(b) => {
+                Box::new(bit_crusher(input, b.reduction.interpolate(v, true) as _))
+            }
+            //TODO: Use the phaser source once it supports mixing
+            AudioEffect::Phaser(_) => Box::new(flanger(
+                input,
+                Duration::from_millis(4),
+                Duration::from_millis(1),
+                0.5,
+                0.05,
+            )),
+            AudioEffect::Wobble(w) => Box::new(wobble(
+                input,
+                1.0 / length(&w.wave_length).as_secs_f32(),
+                w.lo_freq.interpolate(v, true) as _,
+                w.hi_freq.interpolate(v, true) as _,
+            )),
+            AudioEffect::TapeStop(_) => Box::new(tape_stop(input, start, duration)),
+            AudioEffect::Echo(e) => Box::new(re_trigger(
+                input,
+                start,
+                length(&e.wave_length),
+                Duration::ZERO,
+                e.feedback_level.interpolate(v, true).clamp(0.0, 1.0),
+            )),
+            AudioEffect::SideChain(s) => Box::new(side_chain(
+                input,
+                start,
+                length(&s.period),
+                length(&s.attack_time),
+                length(&s.hold_time),
+                length(&s.release_time),
+                s.ratio.interpolate(v, true),
+            )),
+            AudioEffect::HighPassFilter(_)
+            | AudioEffect::LowPassFilter(_)
+            | AudioEffect::PeakingFilter(_) => {
+                let (tx, rx) = channel();
+                let state = filter_state(effect, v).unwrap_or_default();
+                return Ok((
+                    Box::new(biquad(input, state, Some(rx))),
+                    EffectHandles { filter: Some(tx) },
+                ));
+            }
+            AudioEffect::AudioSwap(_) => {
+                return Err(EffectSourceError::Unimplemented(effect.name()))
+            }
+        };
+
+        Ok((source, EffectHandles::default()))
+    }
+}
+
+/// Applies `effect` to `input` for one measure from the start, see [`EffectSourceBuilder`] for
+/// more control and live parameters
+pub fn build_effect_source(
+    effect: &AudioEffect,
+    bpm: f32,
+    input: impl Source<Item = f32> + Send + 'static,
+) -> Result<Box<dyn Source<Item = f32> + Send>, EffectSourceError> {
+    let (source, _) = EffectSourceBuilder::new(effect, bpm).build(input)?;
+    Ok(Box::new(source))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use kson::{
+        effects::{AudioEffect, Gate, HighPassFilter, SideChain},
+        parameter::EffectParameter,
+    };
+    use rodio::source::SineWave;
+
+    use super::{param_duration, EffectSourceBuilder, EffectSourceError};
+
+    fn param(v: &str) -> EffectParameter<f32> {
+        v.parse().unwrap()
+    }
+
+    #[test]
+    fn tempo_synced_lengths() {
+        let close = |a: Duration, b: f64| (a.as_secs_f64() - b).abs() < 1e-4;
+
+        for (bpm, quarter) in [
+            (120.0, 0.5),
+            (150.0, 0.4),
+            (180.0, 1.0 / 3.0),
+            (240.0, 0.25),
+        ] {
+            assert!(close(param_duration(&param("1/4"), bpm, 1.0), quarter));
+            assert!(close(
+                param_duration(&param("1/16"), bpm, 1.0),
+                quarter / 4.0
+            ));
+        }
+
+        // Absolute lengths don't depend on the tempo
+        for bpm in [90.0, 200.0] {
+            assert!(close(param_duration(&param("50ms"), bpm, 1.0), 0.05));
+        }
+
+        let side_chain = SideChain::default();
+        assert!(close(param_duration(&side_chain.period, 150.0, 1.0), 0.4));
+        assert!(close(
+            param_duration(&side_chain.release_time, 150.0, 1.0),
+            0.1
+        ));
+    }
+
+    #[test]
+    fn effect_sources() {
+        let gate = AudioEffect::Gate(Gate::default());
+        let (_, handles) = EffectSourceBuilder::new(&gate, 120.0)
+            .build(SineWave::new(440.0))
+            .unwrap();
+        assert!(handles.filter.is_none());
+
+        let filter = AudioEffect::HighPassFilter(HighPassFilter::default());
+        let (_, handles) = EffectSourceBuilder::new(&filter, 120.0)
+            .param(0.5)
+            .build(SineWave::new(440.0))
+            .unwrap();
+        assert!(handles.filter.is_some());
+
+        let swap = AudioEffect::AudioSwap("swap.ogg".into());
+        assert_eq!(
+            EffectSourceBuilder::new(&swap, 120.0)
+                .build(SineWave::new(440.0))
+                .err(),
+            Some(EffectSourceError::Unimplemented("AudioSwap"))
+        );
+    }
+}
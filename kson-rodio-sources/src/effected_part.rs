@@ -77,3 +77,85 @@ where
         self.effected.total_duration()
     }
 }
+
+/// Plays `input` except for `take` after `skip`, where the samples of `replacement` are played
+/// instead. Both advance together so the replacement has to start at the same point in time as
+/// `input` and have the same channels and sample rate.
+pub fn replaced_part<I, R, D>(
+    input: I,
+    replacement: R,
+    skip: Duration,
+    take: Duration,
+) -> ReplacedPart<I, R>
+where
+    I: Source<Item = D>,
+    R: Iterator<Item = D>,
+    D: Sample,
+{
+    let samples = |d: Duration| {
+        ((d.as_nanos() * input.sample_rate() as u128 * input.channels() as u128) / 1_000_000_000)
+            as u64
+    };
+
+    ReplacedPart {
+        skip: samples(skip),
+        take: samples(take),
+        input,
+        replacement,
+    }
+}
+
+pub struct ReplacedPart<I, R> {
+    input: I,
+    replacement: R,
+    skip: u64,
+    take: u64,
+}
+
+impl<I, R, D> Iterator for ReplacedPart<I, R>
+where
+    I: Source<Item = D>,
+    R: Iterator<Item = D>,
+    D: Sample,
+{
+    type Item = D;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.input.next()?;
+        if self.take == 0 {
+            return Some(sample);
+        }
+
+        let replaced = self.replacement.next();
+        if self.skip > 0 {
+            self.skip -= 1;
+            Some(sample)
+        } else {
+            self.take -= 1;
+            Some(replaced.unwrap_or(sample))
+        }
+    }
+}
+
+impl<I, R, D> Source for ReplacedPart<I, R>
+where
+    I: Source<Item = D>,
+    R: Iterator<Item = D>,
+    D: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
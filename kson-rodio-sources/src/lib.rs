@@ -1,3 +1,5 @@
+#[cfg(feature = "kson")]
+pub mod audio_effect;
 pub mod biquad;
 pub mod bitcrush;
 pub mod effected_part;
@@ -9,6 +11,7 @@ pub mod owned_source;
 pub mod phaser;
 pub mod pitch_shift;
 pub mod re_trigger;
+pub mod resample;
 pub mod side_chain;
 pub mod takeable_source;
 pub mod tape_stop;
@@ -42,16 +42,18 @@ where
     I: Source,
     I::Item: Sample,
 {
-    let update_frequency = (20 * source.sample_rate()) / 1000 * source.channels() as u32;
+    let update_frequency = ((20 * source.sample_rate()) / 1000 * source.channels() as u32).max(1);
     OwnedSource {
         owner: owner.get_handle(),
         input: source,
         closed: false,
-        samples_until_check: update_frequency,
+        samples_until_check: 1,
         update_frequency,
     }
 }
 
+/// Plays the inner source until either it or the owning [`Marker`] is gone. Once closed it never
+/// yields again, so the mixer can drop it.
 pub struct OwnedSource<I> {
     owner: MarkerHandle,
     input: I,
@@ -82,6 +84,12 @@ where
     pub fn into_inner(self) -> I {
         self.input
     }
+
+    /// Whether the source has finished, either on its own or because the owner was dropped.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
 }
 
 impl<I> Iterator for OwnedSource<I>
@@ -92,22 +100,28 @@ where
     type Item = I::Item;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.closed {
+            return None;
+        }
+
         self.samples_until_check -= 1;
         if self.samples_until_check == 0 {
             self.closed = self.owner.is_dead();
             self.samples_until_check = self.update_frequency;
         }
 
-        if self.closed {
-            None
-        } else {
-            self.input.next()
-        }
+        let sample = if self.closed { None } else { self.input.next() };
+        self.closed = sample.is_none();
+        sample
     }
 
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        self.input.size_hint()
+        if self.closed {
+            (0, Some(0))
+        } else {
+            self.input.size_hint()
+        }
     }
 }
 
@@ -118,7 +132,11 @@ where
 {
     #[inline]
     fn current_frame_len(&self) -> Option<usize> {
-        self.input.current_frame_len()
+        if self.closed {
+            Some(0)
+        } else {
+            self.input.current_frame_len()
+        }
     }
 
     #[inline]
@@ -0,0 +1,197 @@
+use rodio::Source;
+
+/// Converts `input` to `sample_rate` using cubic interpolation.
+///
+/// The channel count of the input is assumed to stay the same, its sample rate may change between
+/// frames.
+pub fn resample<I: Source<Item = f32>>(input: I, sample_rate: u32) -> Resample<I> {
+    let channels = input.channels().max(1) as usize;
+    Resample {
+        input,
+        channels,
+        sample_rate,
+        frames: std::array::from_fn(|_| vec![0.0; channels]),
+        // Fills the history so the first output frame is the first input frame
+        position: 3.0,
+        padding: 0,
+        output: Vec::with_capacity(channels),
+        output_index: 0,
+    }
+}
+
+pub struct Resample<I: Source<Item = f32>> {
+    input: I,
+    channels: usize,
+    sample_rate: u32,
+    /// Last four input frames, output is interpolated between the second and third
+    frames: [Vec<f32>; 4],
+    /// Position between the second and third frame, in input frames
+    position: f64,
+    /// Silent frames added after the input ended
+    padding: usize,
+    output: Vec<f32>,
+    output_index: usize,
+}
+
+impl<I: Source<Item = f32>> Resample<I> {
+    /// Shifts the frame history by one, false once the input is exhausted
+    fn advance(&mut self) -> bool {
+        self.frames.rotate_left(1);
+        let frame = &mut self.frames[3];
+        let mut read = 0;
+        for sample in frame.iter_mut() {
+            *sample = match self.input.next() {
+                Some(s) => {
+                    read += 1;
+                    s
+                }
+                None => 0.0,
+            };
+        }
+
+        if read == 0 {
+            self.padding += 1;
+        }
+
+        // The second frame is the one the output starts from
+        self.padding < 3
+    }
+}
+
+/// Catmull-Rom spline through four points, evaluated between `b` and `c`
+fn cubic(a: f32, b: f32, c: f32, d: f32, t: f32) -> f32 {
+    b + 0.5 * t * (c - a + t * (2.0 * a - 5.0 * b + 4.0 * c - d + t * (3.0 * (b - c) + d - a)))
+}
+
+impl<I: Source<Item = f32>> Iterator for Resample<I> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(sample) = self.output.get(self.output_index) {
+            self.output_index += 1;
+            return Some(*sample);
+        }
+
+        while self.position >= 1.0 {
+            if !self.advance() {
+                return None;
+            }
+            self.position -= 1.0;
+        }
+
+        let t = self.position as f32;
+        let [a, b, c, d] = &self.frames;
+        self.output.clear();
+        self.output
+            .extend((0..self.channels).map(|ch| cubic(a[ch], b[ch], c[ch], d[ch], t)));
+        self.output_index = 1;
+        self.position += self.input.sample_rate() as f64 / self.sample_rate as f64;
+
+        self.output.first().copied()
+    }
+}
+
+impl<I: Source<Item = f32>> Source for Resample<I> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.input.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Sine {
+        frequency: f32,
+        sample_rate: u32,
+        index: u32,
+        length: u32,
+    }
+
+    impl Iterator for Sine {
+        type Item = f32;
+
+        fn next(&mut self) -> Option<f32> {
+            if self.index >= self.length {
+                return None;
+            }
+            let t = self.index as f32 / self.sample_rate as f32;
+            self.index += 1;
+            Some((t * self.frequency * std::f32::consts::TAU).sin())
+        }
+    }
+
+    impl Source for Sine {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> u16 {
+            1
+        }
+
+        fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn total_duration(&self) -> Option<std::time::Duration> {
+            None
+        }
+    }
+
+    /// Frequency from the first and last rising zero crossing
+    fn measure_frequency(samples: &[f32], sample_rate: u32) -> f64 {
+        let crossings: Vec<f64> = samples
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| w[0] < 0.0 && w[1] >= 0.0)
+            .map(|(i, w)| i as f64 + (w[0] / (w[0] - w[1])) as f64)
+            .collect();
+        let (first, last) = (crossings[0], crossings[crossings.len() - 1]);
+        (crossings.len() - 1) as f64 * sample_rate as f64 / (last - first)
+    }
+
+    #[test]
+    fn preserves_pitch() {
+        let sine = Sine {
+            frequency: 440.0,
+            sample_rate: 48000,
+            index: 0,
+            length: 48000 * 2,
+        };
+        let samples: Vec<f32> = resample(sine, 44100).collect();
+
+        assert_eq!(samples.len(), 44100 * 2);
+        let frequency = measure_frequency(&samples, 44100);
+        let cents = 1200.0 * (frequency / 440.0).log2();
+        assert!(cents.abs() < 1.0, "{frequency} Hz");
+    }
+
+    #[test]
+    fn same_rate_is_unchanged() {
+        let sine = || Sine {
+            frequency: 440.0,
+            sample_rate: 44100,
+            index: 0,
+            length: 1000,
+        };
+        let resampled: Vec<f32> = resample(sine(), 44100).collect();
+        let original: Vec<f32> = sine().collect();
+        assert_eq!(resampled.len(), original.len());
+        for (a, b) in resampled.iter().zip(original) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+}
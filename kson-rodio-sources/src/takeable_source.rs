@@ -2,10 +2,13 @@ use std::sync::{Arc, RwLock};
 
 use rodio::{Sample, Source};
 
+/// A source that can be taken out of the mixer from another thread. It finishes for good once the
+/// inner source is taken or runs out, so a source put back into the slot is not played again.
 pub struct TakeableSource<I: Source<Item = D> + Send, D: Sample> {
     source: Arc<RwLock<Option<I>>>,
     channels: u16,
     sample_rate: u32,
+    finished: bool,
 }
 
 impl<I: Source<Item = D> + Send, D: Sample> TakeableSource<I, D> {
@@ -18,6 +21,7 @@ impl<I: Source<Item = D> + Send, D: Sample> TakeableSource<I, D> {
                 source: source.clone(),
                 channels,
                 sample_rate,
+                finished: false,
             },
             source,
         )
@@ -32,11 +36,18 @@ where
     type Item = D;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.source
+        if self.finished {
+            return None;
+        }
+
+        let sample = self
+            .source
             .write()
             .ok()
             .as_mut()
-            .and_then(|x| x.as_mut().and_then(|x| x.next()))
+            .and_then(|x| x.as_mut().and_then(|x| x.next()));
+        self.finished = sample.is_none();
+        sample
     }
 }
 
@@ -46,10 +57,13 @@ where
     D: Sample,
 {
     fn current_frame_len(&self) -> Option<usize> {
-        if let Ok(s) = self.source.read() {
-            s.as_ref().and_then(|s| s.current_frame_len())
-        } else {
-            None
+        if self.finished {
+            return Some(0);
+        }
+
+        match self.source.read() {
+            Ok(s) => s.as_ref().map_or(Some(0), |s| s.current_frame_len()),
+            Err(_) => Some(0),
         }
     }
 
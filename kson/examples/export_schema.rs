@@ -0,0 +1,24 @@
+extern crate anyhow;
+extern crate clap;
+extern crate kson;
+extern crate serde_json;
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Where to write the JSON Schema of the kson format
+    #[clap(short, long, value_parser, default_value = "kson.schema.json")]
+    outfile: PathBuf,
+}
+
+pub fn main() -> Result<()> {
+    let Args { outfile } = Args::parse();
+    let schema = kson::schema::chart_schema();
+    serde_json::to_writer_pretty(std::fs::File::create(outfile)?, &schema)?;
+    Ok(())
+}
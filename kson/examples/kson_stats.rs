@@ -3,24 +3,48 @@ extern crate clap;
 extern crate kson;
 extern crate serde_json;
 
-use std::{io::BufReader, path::PathBuf};
+use std::{
+    io::BufReader,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::Parser;
+use kson::Ksh;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
     /// Name of the person to greet
     #[clap(short, long, value_parser)]
-    infile: PathBuf,
+    infile: Option<PathBuf>,
+    /// Prints the changes between two versions of a chart instead
+    #[clap(long, num_args = 2, value_names = ["OLD", "NEW"])]
+    diff: Option<Vec<PathBuf>>,
 }
+
+/// Reads a .ksh chart or a kson file
+fn read_chart(path: &Path) -> Result<kson::Chart> {
+    if path.extension().is_some_and(|e| e == "ksh") {
+        let data = std::fs::read(path)?;
+        return Ok(kson::Chart::from_ksh(&String::from_utf8_lossy(&data))?);
+    }
+    let reader = BufReader::new(std::fs::File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
 pub fn main() -> Result<()> {
-    let Args { infile } = Args::parse();
+    let Args { infile, diff } = Args::parse();
 
-    let reader = BufReader::new(std::fs::File::open(infile)?);
+    if let Some([old, new]) = diff.as_deref() {
+        print!("{}", kson::diff::diff(&read_chart(old)?, &read_chart(new)?));
+        return Ok(());
+    }
+    let Some(infile) = infile else {
+        bail!("No chart given, use --infile or --diff");
+    };
 
-    let chart: kson::Chart = serde_json::from_reader(reader)?;
+    let chart = read_chart(&infile)?;
 
     let score_ticks = kson::score_ticks::generate_score_ticks(&chart);
 
@@ -7,13 +7,18 @@ use serde::{Deserialize, Serialize};
 use schemars::JsonSchema;
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[serde(default)]
 pub struct CameraInfo {
     pub tilt: TiltInfo,
     pub cam: CamInfo,
+    /// (pulse, duration) of lane toggle animations
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lane_toggle: ByPulse<u32>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[serde(default)]
 pub struct TiltInfo {
     pub scale: ByPulse<f64>,
@@ -61,6 +66,7 @@ fn cmp_graph_section((y, graph): &(u32, Vec<GraphSectionPoint>), cmp_y: u32) ->
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[serde(default)]
 pub struct CamInfo {
     pub body: CamGraphs,
@@ -69,6 +75,7 @@ pub struct CamInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[serde(default)]
 pub struct CamPatternInfo {
     #[serde(skip_serializing_if = "CamPatternLaserInfo::is_empty")]
@@ -82,6 +89,7 @@ impl CamPatternInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[serde(default)]
 pub struct CamPatternLaserInfo {
     #[serde(skip_serializing_if = "CamPatternLaserInvokeList::is_empty")]
@@ -95,6 +103,7 @@ impl CamPatternLaserInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[serde(default)]
 pub struct CamPatternLaserInvokeList {
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -113,6 +122,7 @@ impl CamPatternLaserInvokeList {
 
 /// (pulse, direction, duration)
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct CamPatternInvokeSpin(pub u32, pub i32, pub u32);
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, Default)]
 pub struct CamPatternInvokeSwing(
@@ -124,6 +134,7 @@ pub struct CamPatternInvokeSwing(
 );
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct CamPatternInvokeSwingValue {
     pub scale: f32,  // scale
     pub repeat: u32, // number of repetitions
@@ -146,6 +157,7 @@ impl Default for CamPatternInvokeSwingValue {
 type GraphVec = Vec<GraphPoint>;
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[serde(default)]
 pub struct CamGraphs {
     pub zoom: GraphVec,
@@ -0,0 +1,491 @@
+use std::fmt::{self, Display};
+
+use crate::{Chart, Interval, LaserSection, KSON_RESOLUTION};
+
+/// Lane of a [`NoteDiff`], the index is 0-3 for BT-A to BT-D and 0-1 for FX-L and FX-R
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteLane {
+    Bt(usize),
+    Fx(usize),
+}
+
+impl Display for NoteLane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoteLane::Bt(lane) => write!(f, "BT-{}", ["A", "B", "C", "D"][*lane]),
+            NoteLane::Fx(lane) => write!(f, "FX-{}", ["L", "R"][*lane]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteChange {
+    Added(Interval),
+    Removed(Interval),
+    /// A note that was nudged by up to a beat or a hold whose length changed
+    Moved {
+        from: Interval,
+        to: Interval,
+    },
+}
+
+impl NoteChange {
+    /// First tick the change touches
+    pub fn tick(&self) -> u32 {
+        match self {
+            NoteChange::Added(i) | NoteChange::Removed(i) => i.y,
+            NoteChange::Moved { from, to } => from.y.min(to.y),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteDiff {
+    pub lane: NoteLane,
+    pub change: NoteChange,
+}
+
+/// Ticks `start..=end` of a laser side that differ between the charts, 0 is the left laser
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LaserDiff {
+    pub side: usize,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// `None` is a change that wasn't in the chart on that side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimingDiff {
+    Bpm {
+        y: u32,
+        old: Option<f64>,
+        new: Option<f64>,
+    },
+    /// Signatures as (numerator, denominator)
+    TimeSignature {
+        measure: u32,
+        old: Option<(u32, u32)>,
+        new: Option<(u32, u32)>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaDiff {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// Changes between two versions of a chart, each list is sorted by tick
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChartDiff {
+    pub notes: Vec<NoteDiff>,
+    pub lasers: Vec<LaserDiff>,
+    pub timing: Vec<TimingDiff>,
+    pub meta: Vec<MetaDiff>,
+}
+
+impl ChartDiff {
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+            && self.lasers.is_empty()
+            && self.timing.is_empty()
+            && self.meta.is_empty()
+    }
+
+    /// Earliest tick where the notes or lasers differ
+    pub fn first_note_tick(&self) -> Option<u32> {
+        let notes = self.notes.iter().map(|n| n.change.tick());
+        let lasers = self.lasers.iter().map(|l| l.start);
+        notes.chain(lasers).min()
+    }
+}
+
+/// Notes in `old` and `new` that the other doesn't have, both sorted
+fn unmatched(old: &[Interval], new: &[Interval]) -> (Vec<Interval>, Vec<Interval>) {
+    let sorted = |notes: &[Interval]| {
+        let mut notes = notes.to_vec();
+        notes.sort_by_key(|i| (i.y, i.l));
+        notes
+    };
+    let (old, new) = (sorted(old), sorted(new));
+    let (mut removed, mut added) = (vec![], vec![]);
+    let (mut o, mut n) = (0, 0);
+    while o < old.len() || n < new.len() {
+        match (old.get(o), new.get(n)) {
+            (Some(a), Some(b)) if a == b => {
+                o += 1;
+                n += 1;
+            }
+            (Some(a), Some(b)) if (a.y, a.l) < (b.y, b.l) => {
+                removed.push(*a);
+                o += 1;
+            }
+            (Some(a), None) => {
+                removed.push(*a);
+                o += 1;
+            }
+            (_, Some(b)) => {
+                added.push(*b);
+                n += 1;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    (removed, added)
+}
+
+fn note_changes(old: &[Interval], new: &[Interval]) -> Vec<NoteChange> {
+    let (removed, mut added) = unmatched(old, new);
+    let mut changes = vec![];
+    for from in removed {
+        let moved_to = added
+            .iter()
+            .enumerate()
+            .filter(|(_, to)| {
+                to.y == from.y || (to.l == from.l && to.y.abs_diff(from.y) <= KSON_RESOLUTION)
+            })
+            .min_by_key(|(_, to)| to.y.abs_diff(from.y))
+            .map(|(i, _)| i);
+        changes.push(match moved_to {
+            Some(i) => NoteChange::Moved {
+                from,
+                to: added.remove(i),
+            },
+            None => NoteChange::Removed(from),
+        });
+    }
+    changes.extend(added.into_iter().map(NoteChange::Added));
+    changes.sort_by_key(NoteChange::tick);
+    changes
+}
+
+fn laser_eq(a: &LaserSection, b: &LaserSection) -> bool {
+    a.0 == b.0
+        && a.2 == b.2
+        && a.1.len() == b.1.len()
+        && a.1
+            .iter()
+            .zip(&b.1)
+            .all(|(a, b)| a.ry == b.ry && a.v == b.v && a.vf == b.vf && a.a == b.a && a.b == b.b)
+}
+
+/// Merged tick ranges of the sections that are only in one of `old` and `new`
+fn laser_changes(old: &[LaserSection], new: &[LaserSection]) -> Vec<(u32, u32)> {
+    let only_in = |a: &[LaserSection], b: &[LaserSection]| -> Vec<(u32, u32)> {
+        a.iter()
+            .filter(|s| !b.iter().any(|o| laser_eq(s, o)))
+            .map(|s| (s.tick(), s.tick() + s.last().map(|p| p.ry).unwrap_or(0)))
+            .collect()
+    };
+    let mut ranges = only_in(old, new);
+    ranges.extend(only_in(new, old));
+    ranges.sort();
+
+    let mut merged: Vec<(u32, u32)> = vec![];
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Changes of values keyed by tick or measure, both lists sorted by key
+fn keyed_changes<T: Copy + PartialEq>(
+    old: &[(u32, T)],
+    new: &[(u32, T)],
+) -> Vec<(u32, Option<T>, Option<T>)> {
+    let mut keys: Vec<u32> = old.iter().chain(new).map(|(k, _)| *k).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter_map(|key| {
+            let find = |list: &[(u32, T)]| list.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+            let (old, new) = (find(old), find(new));
+            (old != new).then_some((key, old, new))
+        })
+        .collect()
+}
+
+fn meta_changes(old: &Chart, new: &Chart) -> Vec<MetaDiff> {
+    let fields = |chart: &Chart| {
+        let meta = &chart.meta;
+        let optional = |v: &Option<String>| v.clone().unwrap_or_default();
+        [
+            ("title", meta.title.clone()),
+            ("subtitle", optional(&meta.subtitle)),
+            ("artist", meta.artist.clone()),
+            ("chart_author", meta.chart_author.clone()),
+            ("difficulty", meta.difficulty.to_string()),
+            ("level", meta.level.to_string()),
+            ("disp_bpm", meta.disp_bpm.clone()),
+            (
+                "std_bpm",
+                meta.std_bpm.map(|b| b.to_string()).unwrap_or_default(),
+            ),
+            ("jacket_filename", meta.jacket_filename.clone()),
+            ("jacket_author", meta.jacket_author.clone()),
+            ("information", optional(&meta.information)),
+        ]
+    };
+
+    fields(old)
+        .into_iter()
+        .zip(fields(new))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, old), (_, new))| MetaDiff { field, old, new })
+        .collect()
+}
+
+/// Compares two versions of a chart
+pub fn diff(old: &Chart, new: &Chart) -> ChartDiff {
+    let bt = (0..4).map(|lane| (NoteLane::Bt(lane), &old.note.bt[lane], &new.note.bt[lane]));
+    let fx = (0..2).map(|lane| (NoteLane::Fx(lane), &old.note.fx[lane], &new.note.fx[lane]));
+    let mut notes: Vec<NoteDiff> = bt
+        .chain(fx)
+        .flat_map(|(lane, old, new)| {
+            note_changes(old, new)
+                .into_iter()
+                .map(move |change| NoteDiff { lane, change })
+        })
+        .collect();
+    notes.sort_by_key(|n| n.change.tick());
+
+    let mut lasers: Vec<LaserDiff> = (0..2)
+        .flat_map(|side| {
+            laser_changes(&old.note.laser[side], &new.note.laser[side])
+                .into_iter()
+                .map(move |(start, end)| LaserDiff { side, start, end })
+        })
+        .collect();
+    lasers.sort_by_key(|l| l.start);
+
+    let signatures = |chart: &Chart| -> Vec<(u32, (u32, u32))> {
+        chart
+            .beat
+            .time_sig
+            .iter()
+            .map(|(measure, sig)| (*measure, (sig.0, sig.1)))
+            .collect()
+    };
+    let timing = keyed_changes(&old.beat.bpm, &new.beat.bpm)
+        .into_iter()
+        .map(|(y, old, new)| TimingDiff::Bpm { y, old, new })
+        .chain(
+            keyed_changes(&signatures(old), &signatures(new))
+                .into_iter()
+                .map(|(measure, old, new)| TimingDiff::TimeSignature { measure, old, new }),
+        )
+        .collect();
+
+    ChartDiff {
+        notes,
+        lasers,
+        timing,
+        meta: meta_changes(old, new),
+    }
+}
+
+fn describe_note(note: &Interval) -> String {
+    if note.l == 0 {
+        format!("chip at {}", note.y)
+    } else {
+        format!("hold at {} ({} long)", note.y, note.l)
+    }
+}
+
+impl Display for NoteDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.change {
+            NoteChange::Added(note) => write!(f, "{}: added {}", self.lane, describe_note(note)),
+            NoteChange::Removed(note) => {
+                write!(f, "{}: removed {}", self.lane, describe_note(note))
+            }
+            NoteChange::Moved { from, to } => write!(
+                f,
+                "{}: moved {} to {}",
+                self.lane,
+                describe_note(from),
+                describe_note(to)
+            ),
+        }
+    }
+}
+
+impl Display for LaserDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = ["Left", "Right"][self.side];
+        write!(
+            f,
+            "{side} laser changed from {} to {}",
+            self.start, self.end
+        )
+    }
+}
+
+impl Display for TimingDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn value<T>(v: Option<T>, show: impl Fn(T) -> String) -> String {
+            v.map(show).unwrap_or_else(|| "none".to_string())
+        }
+        match *self {
+            TimingDiff::Bpm { y, old, new } => write!(
+                f,
+                "BPM at {y}: {} -> {}",
+                value(old, |b| b.to_string()),
+                value(new, |b| b.to_string())
+            ),
+            TimingDiff::TimeSignature { measure, old, new } => {
+                let sig = |(n, d)| format!("{n}/{d}");
+                write!(
+                    f,
+                    "Time signature in measure {}: {} -> {}",
+                    measure + 1,
+                    value(old, sig),
+                    value(new, sig)
+                )
+            }
+        }
+    }
+}
+
+impl Display for MetaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {:?} -> {:?}", self.field, self.old, self.new)
+    }
+}
+
+impl Display for ChartDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        for line in &self.meta {
+            writeln!(f, "{line}")?;
+        }
+        for line in &self.timing {
+            writeln!(f, "{line}")?;
+        }
+        for line in &self.notes {
+            writeln!(f, "{line}")?;
+        }
+        for line in &self.lasers {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, LaserDiff, MetaDiff, NoteChange, NoteDiff, NoteLane, TimingDiff};
+    use crate::{Chart, Interval, Ksh};
+
+    fn chart(ksh: &str) -> Chart {
+        Chart::from_ksh(ksh).expect("Failed to parse chart")
+    }
+
+    const OLD: &str = "title=Song\r\nartist=Artist\r\nt=120\r\nbeat=4/4\r\nlevel=15\r\n--\r\n1000|00|--\r\n0100|00|--\r\n0010|10|0-\r\n0000|10|:-\r\n--\r\n0001|00|5-\r\n0000|00|o-\r\n0000|00|--\r\n0000|00|--\r\n--";
+    const NEW: &str = "title=Song\r\nartist=Artist\r\nt=120\r\nbeat=4/4\r\nlevel=16\r\n--\r\n1000|00|--\r\n0000|00|--\r\n0110|10|0-\r\n0000|00|:-\r\n--\r\nbeat=3/4\r\nt=150\r\n0000|00|5-\r\n1000|00|k-\r\n0000|00|--\r\n--";
+
+    #[test]
+    fn fixture_edits() {
+        let diff = diff(&chart(OLD), &chart(NEW));
+        assert_eq!(diff.first_note_tick(), Some(240));
+
+        assert_eq!(
+            diff.meta,
+            [MetaDiff {
+                field: "level",
+                old: "15".into(),
+                new: "16".into(),
+            }]
+        );
+        assert_eq!(
+            diff.timing,
+            [
+                TimingDiff::Bpm {
+                    y: 960,
+                    old: None,
+                    new: Some(150.0),
+                },
+                TimingDiff::TimeSignature {
+                    measure: 1,
+                    old: None,
+                    new: Some((3, 4)),
+                },
+            ]
+        );
+        assert_eq!(
+            diff.notes,
+            [
+                NoteDiff {
+                    lane: NoteLane::Bt(1),
+                    change: NoteChange::Moved {
+                        from: Interval { y: 240, l: 0 },
+                        to: Interval { y: 480, l: 0 },
+                    },
+                },
+                NoteDiff {
+                    lane: NoteLane::Fx(0),
+                    change: NoteChange::Moved {
+                        from: Interval { y: 480, l: 480 },
+                        to: Interval { y: 480, l: 240 },
+                    },
+                },
+                NoteDiff {
+                    lane: NoteLane::Bt(3),
+                    change: NoteChange::Removed(Interval { y: 960, l: 0 }),
+                },
+                NoteDiff {
+                    lane: NoteLane::Bt(0),
+                    change: NoteChange::Added(Interval { y: 1200, l: 0 }),
+                },
+            ]
+        );
+        assert_eq!(
+            diff.lasers,
+            [LaserDiff {
+                side: 0,
+                start: 480,
+                end: 1200,
+            }]
+        );
+
+        assert_eq!(
+            diff.to_string(),
+            "level: \"15\" -> \"16\"\n\
+             BPM at 960: none -> 150\n\
+             Time signature in measure 2: none -> 3/4\n\
+             BT-B: moved chip at 240 to chip at 480\n\
+             FX-L: moved hold at 480 (480 long) to hold at 480 (240 long)\n\
+             BT-D: removed chip at 960\n\
+             BT-A: added chip at 1200\n\
+             Left laser changed from 480 to 1200\n"
+        );
+    }
+
+    #[test]
+    fn same_chart() {
+        let diff = diff(&chart(OLD), &chart(OLD));
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No changes\n");
+    }
+
+    #[test]
+    fn first_note_tick() {
+        let mut old = Chart::new();
+        old.note.bt[0] = vec![Interval { y: 0, l: 0 }, Interval { y: 480, l: 0 }];
+        let mut new = old.clone();
+        assert_eq!(diff(&old, &new).first_note_tick(), None);
+
+        new.note.bt[0][1].y = 720;
+        new.note.fx[1].push(Interval { y: 960, l: 240 });
+        assert_eq!(diff(&old, &new).first_note_tick(), Some(480));
+
+        new.note.bt[0] = old.note.bt[0].clone();
+        assert_eq!(diff(&old, &new).first_note_tick(), Some(960));
+    }
+}
@@ -0,0 +1,361 @@
+use thiserror::Error;
+
+use crate::{GraphSectionPoint, Interval, LaserSection, NoteInfo};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EditError {
+    #[error("Note at {new} overlaps the note at {existing}")]
+    Overlap { new: u32, existing: u32 },
+    #[error("No note at tick {0}")]
+    NotFound(u32),
+    #[error("Tick {0} is not inside a hold")]
+    NotInsideHold(u32),
+    #[error("Laser point already exists at tick {0}")]
+    PointExists(u32),
+    #[error("Tick {0} is not inside a laser segment")]
+    NotInsideSegment(u32),
+    #[error("Shifting by {0} ticks moves notes out of the chart")]
+    ShiftOutOfRange(i64),
+}
+
+/// How `insert_interval` handles notes overlapping the inserted one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverlapMode {
+    #[default]
+    Reject,
+    /// Replace the inserted note and all notes it overlaps with one note covering all of them
+    Merge,
+}
+
+/// Ticks covered by a note, chips cover their own tick so they can't share it with other notes
+fn span(interval: &Interval) -> std::ops::Range<u32> {
+    interval.y..interval.y + interval.l.max(1)
+}
+
+fn intersects(a: &Interval, b: &Interval) -> bool {
+    let (a, b) = (span(a), span(b));
+    a.start < b.end && b.start < a.end
+}
+
+fn section_end(section: &LaserSection) -> u32 {
+    section.tick() + section.last().map_or(0, |p| p.ry)
+}
+
+/// Editing for the notes of a BT or FX lane.
+///
+/// Lanes are kept sorted by `y` with no two notes covering the same tick, a hold may end on the
+/// tick the next note starts on.
+pub trait IntervalLane {
+    fn insert_interval(&mut self, interval: Interval, mode: OverlapMode) -> Result<(), EditError>;
+    /// Removes the note covering `tick`
+    fn remove_at(&mut self, tick: u32) -> Result<Interval, EditError>;
+    /// Splits the hold covering `tick` into two holds meeting at `tick`
+    fn split_hold(&mut self, tick: u32) -> Result<(), EditError>;
+    fn is_valid(&self) -> bool;
+}
+
+impl IntervalLane for Vec<Interval> {
+    fn insert_interval(&mut self, interval: Interval, mode: OverlapMode) -> Result<(), EditError> {
+        let overlapping = self
+            .iter()
+            .enumerate()
+            .filter(|(_, existing)| intersects(existing, &interval))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        let interval = match (mode, overlapping.first().zip(overlapping.last())) {
+            (_, None) => interval,
+            (OverlapMode::Reject, Some((&first, _))) => {
+                return Err(EditError::Overlap {
+                    new: interval.y,
+                    existing: self[first].y,
+                })
+            }
+            (OverlapMode::Merge, Some((&first, &last))) => {
+                let removed = self.drain(first..=last).collect::<Vec<_>>();
+                let start = removed.iter().map(|n| n.y).fold(interval.y, u32::min);
+                let end = removed
+                    .iter()
+                    .map(|n| n.y + n.l)
+                    .fold(interval.y + interval.l, u32::max);
+                Interval {
+                    y: start,
+                    l: end - start,
+                }
+            }
+        };
+
+        let index = self.partition_point(|n| n.y < interval.y);
+        self.insert(index, interval);
+        Ok(())
+    }
+
+    fn remove_at(&mut self, tick: u32) -> Result<Interval, EditError> {
+        let index = self
+            .iter()
+            .position(|n| span(n).contains(&tick))
+            .ok_or(EditError::NotFound(tick))?;
+        Ok(self.remove(index))
+    }
+
+    fn split_hold(&mut self, tick: u32) -> Result<(), EditError> {
+        let index = self
+            .iter()
+            .position(|n| n.y < tick && tick < n.y + n.l)
+            .ok_or(EditError::NotInsideHold(tick))?;
+        let hold = self[index];
+        self[index].l = tick - hold.y;
+        self.insert(
+            index + 1,
+            Interval {
+                y: tick,
+                l: hold.y + hold.l - tick,
+            },
+        );
+        Ok(())
+    }
+
+    fn is_valid(&self) -> bool {
+        self.windows(2).all(|w| span(&w[0]).end <= w[1].y)
+    }
+}
+
+/// Editing for the sections of a laser.
+///
+/// Sections are kept sorted by tick without overlapping, each has at least one point with the
+/// first at `ry` 0 and `ry` strictly increasing.
+pub trait LaserLane {
+    /// Adds a point at `tick` to the section covering it, or starts a new section there
+    fn insert_laser_point(
+        &mut self,
+        tick: u32,
+        point: GraphSectionPoint,
+        wide: u8,
+    ) -> Result<(), EditError>;
+    /// Removes the section covering `tick`
+    fn remove_at(&mut self, tick: u32) -> Result<LaserSection, EditError>;
+    /// Removes the segment covering `tick`, splitting its section in two around the gap
+    fn remove_segment(&mut self, tick: u32) -> Result<(), EditError>;
+    fn is_valid(&self) -> bool;
+}
+
+impl LaserLane for Vec<LaserSection> {
+    fn insert_laser_point(
+        &mut self,
+        tick: u32,
+        point: GraphSectionPoint,
+        wide: u8,
+    ) -> Result<(), EditError> {
+        if let Some(section) = self
+            .iter_mut()
+            .find(|s| (s.tick()..=section_end(s)).contains(&tick))
+        {
+            let ry = tick - section.tick();
+            return match section.1.binary_search_by_key(&ry, |p| p.ry) {
+                Ok(_) => Err(EditError::PointExists(tick)),
+                Err(index) => {
+                    section.1.insert(index, GraphSectionPoint { ry, ..point });
+                    Ok(())
+                }
+            };
+        }
+
+        let index = self.partition_point(|s| s.tick() < tick);
+        self.insert(
+            index,
+            LaserSection(tick, vec![GraphSectionPoint { ry: 0, ..point }], wide),
+        );
+        Ok(())
+    }
+
+    fn remove_at(&mut self, tick: u32) -> Result<LaserSection, EditError> {
+        let index = self
+            .iter()
+            .position(|s| (s.tick()..=section_end(s)).contains(&tick))
+            .ok_or(EditError::NotFound(tick))?;
+        Ok(self.remove(index))
+    }
+
+    fn remove_segment(&mut self, tick: u32) -> Result<(), EditError> {
+        let (index, point) = self
+            .iter()
+            .enumerate()
+            .find_map(|(i, s)| {
+                let ry = tick.checked_sub(s.tick())?;
+                s.segments()
+                    .position(|w| w[0].ry <= ry && ry < w[1].ry)
+                    .map(|p| (i, p))
+            })
+            .ok_or(EditError::NotInsideSegment(tick))?;
+
+        let section = &mut self[index];
+        let mut tail = section.1.split_off(point + 1);
+        let tail_start = tail[0].ry;
+        for p in &mut tail {
+            p.ry -= tail_start;
+        }
+        let tail = LaserSection(section.tick() + tail_start, tail, section.wide());
+        self.insert(index + 1, tail);
+        Ok(())
+    }
+
+    fn is_valid(&self) -> bool {
+        self.iter().all(|s| {
+            s.first().is_some_and(|p| p.ry == 0) && s.segments().all(|w| w[0].ry < w[1].ry)
+        }) && self.windows(2).all(|w| section_end(&w[0]) < w[1].tick())
+    }
+}
+
+impl NoteInfo {
+    /// Moves every note by `ticks`, nothing is changed if a note would end up before tick 0.
+    /// Timing, camera and effect data stay where they are
+    pub fn shift_notes(&mut self, ticks: i64) -> Result<(), EditError> {
+        let shift =
+            |y: u32| u32::try_from(y as i64 + ticks).map_err(|_| EditError::ShiftOutOfRange(ticks));
+
+        let start = self
+            .bt
+            .iter()
+            .chain(&self.fx)
+            .filter_map(|lane| lane.first().map(|n| n.y))
+            .chain(
+                self.laser
+                    .iter()
+                    .filter_map(|l| l.first().map(|s| s.tick())),
+            )
+            .min();
+        let end = self
+            .bt
+            .iter()
+            .chain(&self.fx)
+            .filter_map(|lane| lane.last().map(|n| n.y + n.l))
+            .chain(self.laser.iter().filter_map(|l| l.last().map(section_end)))
+            .max();
+        for tick in start.into_iter().chain(end) {
+            shift(tick)?;
+        }
+
+        // Every tick lies between the checked ones so shifting can't fail from here on
+        for note in self.bt.iter_mut().chain(&mut self.fx).flatten() {
+            note.y = shift(note.y)?;
+        }
+        for section in self.laser.iter_mut().flatten() {
+            section.0 = shift(section.0)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{EditError, IntervalLane, LaserLane, OverlapMode};
+    use crate::{GraphSectionPoint, Interval, LaserSection, NoteInfo};
+
+    fn interval() -> impl Strategy<Value = Interval> {
+        (0u32..2000, prop_oneof![Just(0u32), 1u32..300]).prop_map(|(y, l)| Interval { y, l })
+    }
+
+    proptest! {
+        #[test]
+        fn intervals_stay_sorted(
+            notes in prop::collection::vec((interval(), any::<bool>()), 0..40),
+            cuts in prop::collection::vec(0u32..2300, 0..20),
+        ) {
+            let mut lane = Vec::new();
+            for (note, merge) in notes {
+                let mode = if merge { OverlapMode::Merge } else { OverlapMode::Reject };
+                let before = lane.len();
+                match lane.insert_interval(note, mode) {
+                    Ok(()) => prop_assert!(lane.len() <= before + 1),
+                    Err(EditError::Overlap { .. }) => prop_assert_eq!(lane.len(), before),
+                    Err(e) => prop_assert!(false, "{}", e),
+                }
+                prop_assert!(lane.is_valid());
+            }
+
+            for cut in cuts {
+                let before = lane.len();
+                if lane.split_hold(cut).is_ok() {
+                    prop_assert_eq!(lane.len(), before + 1);
+                }
+                prop_assert!(lane.is_valid());
+                _ = lane.remove_at(cut / 2);
+                prop_assert!(lane.is_valid());
+            }
+        }
+
+        #[test]
+        fn laser_points_stay_ordered(
+            points in prop::collection::vec((0u32..2000, 0.0f64..=1.0), 0..40),
+            gaps in prop::collection::vec(0u32..2000, 0..10),
+        ) {
+            let mut lane: Vec<LaserSection> = Vec::new();
+            for (tick, v) in points {
+                let points_before: usize = lane.iter().map(|s| s.1.len()).sum();
+                match lane.insert_laser_point(tick, GraphSectionPoint::new(0, v), 1) {
+                    Ok(()) => {
+                        let points_after: usize = lane.iter().map(|s| s.1.len()).sum();
+                        prop_assert_eq!(points_after, points_before + 1);
+                    }
+                    Err(EditError::PointExists(_)) => {}
+                    Err(e) => prop_assert!(false, "{}", e),
+                }
+                prop_assert!(lane.is_valid());
+            }
+
+            for gap in gaps {
+                _ = lane.remove_segment(gap);
+                prop_assert!(lane.is_valid());
+            }
+        }
+
+        #[test]
+        fn shift_keeps_order(notes in prop::collection::vec(interval(), 0..20), ticks in -3000i64..3000) {
+            let mut chart = NoteInfo::new();
+            for note in notes {
+                _ = chart.bt[0].insert_interval(note, OverlapMode::Merge);
+            }
+            let original = chart.bt[0].clone();
+            match chart.shift_notes(ticks) {
+                Ok(()) => {
+                    prop_assert!(chart.bt[0].is_valid());
+                    for (a, b) in original.iter().zip(&chart.bt[0]) {
+                        prop_assert_eq!(a.y as i64 + ticks, b.y as i64);
+                    }
+                }
+                Err(_) => prop_assert_eq!(chart.bt[0].clone(), original),
+            }
+        }
+    }
+
+    #[test]
+    fn split_and_merge() {
+        let mut lane = vec![Interval { y: 0, l: 100 }];
+        lane.split_hold(40).unwrap();
+        assert_eq!(
+            lane,
+            vec![Interval { y: 0, l: 40 }, Interval { y: 40, l: 60 }]
+        );
+        assert_eq!(lane.split_hold(40), Err(EditError::NotInsideHold(40)));
+
+        lane.insert_interval(Interval { y: 30, l: 20 }, OverlapMode::Merge)
+            .unwrap();
+        assert_eq!(lane, vec![Interval { y: 0, l: 100 }]);
+
+        let mut laser = vec![LaserSection(
+            10,
+            vec![
+                GraphSectionPoint::new(0, 0.0),
+                GraphSectionPoint::new(10, 1.0),
+                GraphSectionPoint::new(20, 0.0),
+            ],
+            1,
+        )];
+        laser.remove_segment(25).unwrap();
+        assert_eq!(laser.len(), 2);
+        assert_eq!(laser[1].tick(), 30);
+        assert!(laser.is_valid());
+    }
+}
@@ -19,6 +19,7 @@ pub(crate) trait Effect {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[serde(tag = "type", content = "v")]
 #[serde(rename_all = "snake_case")]
 pub enum AudioEffect {
@@ -101,6 +102,7 @@ impl Effect for String {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct ReTrigger {
     pub update_period: EffectParameter<f32>,
     pub wave_length: EffectParameter<f32>,
@@ -110,6 +112,7 @@ pub struct ReTrigger {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Gate {
     pub wave_length: EffectParameter<f32>,
     pub rate: EffectParameter<f32>,
@@ -117,6 +120,7 @@ pub struct Gate {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Flanger {
     pub period: EffectParameter<f32>,
     pub delay: EffectParameter<i64>,
@@ -128,6 +132,7 @@ pub struct Flanger {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct PitchShift {
     pub pitch: EffectParameter<f32>,
     pub pitch_quantize: BoolParameter,
@@ -137,12 +142,14 @@ pub struct PitchShift {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct BitCrusher {
     pub reduction: EffectParameter<i64>,
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Phaser {
     pub period: EffectParameter<f32>,
     pub stage: EffectParameter<i64>,
@@ -155,6 +162,7 @@ pub struct Phaser {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Wobble {
     pub wave_length: EffectParameter<f32>,
     pub lo_freq: EffectParameter<f32>,
@@ -164,6 +172,7 @@ pub struct Wobble {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct TapeStop {
     pub speed: EffectParameter<f32>,
     pub trigger: BoolParameter,
@@ -171,6 +180,7 @@ pub struct TapeStop {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Echo {
     pub update_period: EffectParameter<f32>,
     pub wave_length: EffectParameter<f32>,
@@ -180,6 +190,7 @@ pub struct Echo {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct SideChain {
     pub period: EffectParameter<f32>,
     pub hold_time: EffectParameter<f32>,
@@ -189,6 +200,7 @@ pub struct SideChain {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct HighPassFilter {
     pub v: EffectParameter<f32>,
     pub freq: EffectParameter<f32>,
@@ -198,6 +210,7 @@ pub struct HighPassFilter {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct LowPassFilter {
     pub v: EffectParameter<f32>,
     pub freq: EffectParameter<f32>,
@@ -207,6 +220,7 @@ pub struct LowPassFilter {
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct PeakingFilter {
     pub v: EffectParameter<f32>,
     pub freq: EffectParameter<f32>,
@@ -508,3 +522,73 @@ impl Chart {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AudioEffect, LowPassFilter};
+    use crate::{
+        parameter::{EffectFreq, EffectParameterValue},
+        ByPulseOption, Chart, Interval,
+    };
+
+    #[test]
+    fn param_changes_applied() {
+        let mut chart = Chart::new();
+        chart.note.fx[0] = vec![Interval { y: 0, l: 240 }, Interval { y: 960, l: 240 }];
+        let fx = &mut chart.audio.audio_effect.fx;
+        fx.def.insert(
+            "filter".into(),
+            AudioEffect::LowPassFilter(LowPassFilter::default()),
+        );
+        fx.param_change.insert(
+            "filter".into(),
+            [
+                (
+                    "freq".to_string(),
+                    vec![(0, "1khz-4khz".to_string()), (480, "1/0".to_string())],
+                ),
+                ("mix".to_string(), vec![(480, "0%>100%".to_string())]),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        fx.long_event.insert(
+            "filter".into(),
+            [
+                vec![
+                    ByPulseOption(0, None),
+                    ByPulseOption(
+                        960,
+                        Some(
+                            [("q".to_string(), "0.5-2".to_string())]
+                                .into_iter()
+                                .collect(),
+                        ),
+                    ),
+                ],
+                vec![],
+            ],
+        );
+
+        let tracks = chart.get_effect_tracks();
+        let [first, second] = &tracks[..] else {
+            panic!("Expected two effect intervals, got {}", tracks.len());
+        };
+        let (AudioEffect::LowPassFilter(first), AudioEffect::LowPassFilter(second)) =
+            (&first.effect, &second.effect)
+        else {
+            panic!("Effect type changed");
+        };
+
+        let freq = EffectParameterValue::Freq(EffectFreq::Khz(1.0)..=EffectFreq::Khz(4.0));
+        assert_eq!(first.freq.off, freq);
+        assert_eq!(first.mix, LowPassFilter::default().mix);
+        assert_eq!(first.q, LowPassFilter::default().q);
+
+        // The invalid change keeps the previous value
+        assert_eq!(second.freq.off, freq);
+        assert_eq!(second.mix.value_at(0.0, 120.0, true), 1.0);
+        assert_eq!(second.q.off, EffectParameterValue::Float(0.5..=2.0));
+        assert!((second.freq.value_at(0.5, 120.0, true) - 2000.0).abs() < 0.01);
+    }
+}
@@ -201,17 +201,17 @@ impl Ksh for crate::Chart {
         let mut file_line = 0;
         for (line_idx, line) in meta.enumerate() {
             file_line = line_idx + 1;
-            let line_data: Vec<&str> = line.split('=').collect();
-            if line_data.len() < 2 {
+            let Some((key, value)) = line.split_once('=') else {
                 continue;
-            }
-            let value = String::from(line_data[1].trim());
-            match line_data[0] {
+            };
+            let value = String::from(value.trim());
+            match key {
                 "title" => new_chart.meta.title = value,
                 "artist" => new_chart.meta.artist = value,
                 "effect" => new_chart.meta.chart_author = value,
                 "jacket" => new_chart.meta.jacket_filename = value,
                 "illustrator" => new_chart.meta.jacket_author = value,
+                "information" => new_chart.meta.information = Some(value),
                 "t" => {
                     if let Ok(v) = value.parse::<f64>() {
                         new_chart.beat.bpm.push((0, v))
@@ -219,6 +219,18 @@ impl Ksh for crate::Chart {
                     new_chart.meta.disp_bpm.clone_from(&value);
                 }
                 "beat" => {}
+                "ver" => new_chart.compat.ksh_version = value,
+                "chokkakuvol" => new_chart
+                    .audio
+                    .key_sound
+                    .laser
+                    .vol
+                    .push((0, value.parse::<f64>().with_line(file_line)? / 100.0)),
+                "pfilterdelay" => {
+                    new_chart.audio.audio_effect.laser.peaking_filter_delay =
+                        value.parse().with_line(file_line)?
+                }
+                "filtertype" => set_laser_filter(&mut new_chart, 0, value),
                 "o" => bgm.offset = value.parse::<i32>().with_line(file_line)?,
                 "m" => {
                     let mut filenames = value.split(';').map(String::from);
@@ -244,17 +256,39 @@ impl Ksh for crate::Chart {
                 "mvol" => bgm.vol = value.parse::<f64>().with_line(file_line)? / 100.0,
                 "layer" => {
                     //TODO: parse properly
-                    legacy_bg = Some(LegacyBgInfo {
-                        bg: None,
-                        layer: Some(KshLayerInfo {
-                            filename: Some(value),
-                            duration: 0,
-                            rotation: None,
-                        }),
-                        movie: None,
+                    legacy_bg.get_or_insert_with(LegacyBgInfo::empty).layer = Some(KshLayerInfo {
+                        filename: Some(value),
+                        duration: 0,
+                        rotation: None,
                     })
                 }
-                _ => (),
+                "v" => {
+                    legacy_bg
+                        .get_or_insert_with(LegacyBgInfo::empty)
+                        .movie
+                        .get_or_insert(KshMovieInfo {
+                            filename: None,
+                            offset: 0,
+                        })
+                        .filename = Some(value)
+                }
+                "vo" => {
+                    legacy_bg
+                        .get_or_insert_with(LegacyBgInfo::empty)
+                        .movie
+                        .get_or_insert(KshMovieInfo {
+                            filename: None,
+                            offset: 0,
+                        })
+                        .offset = value.parse().with_line(file_line)?
+                }
+                _ => {
+                    new_chart
+                        .compat
+                        .ksh_unknown
+                        .meta
+                        .insert(key.to_owned(), value);
+                }
             }
         }
 
@@ -447,6 +481,7 @@ impl Ksh for crate::Chart {
                     // Parse custom effect definitions
                     let data = line.splitn(3, ' ').collect::<Vec<_>>();
                     if data.len() != 3 {
+                        new_chart.compat.ksh_unknown.line.push((y, line.to_owned()));
                         continue;
                     }
 
@@ -479,9 +514,11 @@ impl Ksh for crate::Chart {
                                 .insert(name.to_owned(), t),
                             _ => None,
                         };
+                    } else {
+                        new_chart.compat.ksh_unknown.line.push((y, line.to_owned()));
                     }
                 } else if line.contains('=') {
-                    let mut line_data = line.split('=');
+                    let mut line_data = line.splitn(2, '=');
 
                     let line_prop = String::from(line_data.next().unwrap_or(""));
                     let mut line_value = String::from(line_data.next().unwrap_or(""));
@@ -544,6 +581,37 @@ impl Ksh for crate::Chart {
                                 ..Default::default()
                             })
                         }
+                        "center_split" => {
+                            let (v, vf) =
+                                parse_ksh_zoom_values(&line_value).with_line(file_line)?;
+                            new_chart.camera.cam.body.split.push(GraphPoint {
+                                y,
+                                v,
+                                vf,
+                                ..Default::default()
+                            })
+                        }
+                        "lane_toggle" => {
+                            let length = line_value.parse::<u32>().with_line(file_line)?;
+                            new_chart
+                                .camera
+                                .lane_toggle
+                                .push((y, (length * 4 * KSON_RESOLUTION) / 192));
+                        }
+                        "chokkakuvol" => new_chart
+                            .audio
+                            .key_sound
+                            .laser
+                            .vol
+                            .push((y, line_value.parse::<f64>().with_line(file_line)? / 100.0)),
+                        "chokkakuse" => new_chart
+                            .audio
+                            .key_sound
+                            .laser
+                            .slam_event
+                            .entry(line_value)
+                            .or_default()
+                            .push(y),
                         "fx-l" => {
                             fx_string[0] = Some(line_value);
                         }
@@ -554,19 +622,17 @@ impl Ksh for crate::Chart {
                             parse_tilt(&mut new_chart.camera.tilt, y, &line_value, &mut manual_tilt)
                                 .with_line(file_line)?
                         }
-                        "filtertype" => {
-                            let laser = &mut new_chart.audio.audio_effect.laser;
-                            if let Ok(e) = AudioEffect::try_from(line_value.as_ref()) {
-                                laser.def.entry(line_value.clone()).or_insert(e);
-                            }
-                            laser
-                                .pulse_event
-                                .entry(line_value)
-                                .or_default()
-                                .push((y, ()));
-                        }
-                        _ => (),
+                        "filtertype" => set_laser_filter(&mut new_chart, y, line_value),
+                        _ => new_chart
+                            .compat
+                            .ksh_unknown
+                            .option
+                            .entry(line_prop)
+                            .or_default()
+                            .push((y, line_value)),
                     }
+                } else if !line.is_empty() {
+                    new_chart.compat.ksh_unknown.line.push((y, line.to_owned()));
                 }
             }
             measure_index += 1;
@@ -697,7 +763,14 @@ impl Ksh for crate::Chart {
                 "information={}\r",
                 self.meta.information.clone().unwrap_or_default()
             )?;
-            writeln!(&mut w, "ver=171\r")?;
+            for (key, value) in &self.compat.ksh_unknown.meta {
+                writeln!(&mut w, "{}={}\r", key, value)?;
+            }
+            let version = match self.compat.ksh_version.as_str() {
+                "" => "171",
+                version => version,
+            };
+            writeln!(&mut w, "ver={}\r", version)?;
             writeln!(&mut w, "--\r")?;
         }
 
@@ -731,6 +804,17 @@ impl Ksh for crate::Chart {
                         }
                     }
 
+                    //Unknown options and lines
+                    let unknown = &self.compat.ksh_unknown;
+                    for (key, values) in &unknown.option {
+                        for (_, value) in at_pulse(values, y) {
+                            writeln!(&mut w, "{}={}\r", key, value)?;
+                        }
+                    }
+                    for (_, line) in at_pulse(&unknown.line, y) {
+                        writeln!(&mut w, "{}\r", line)?;
+                    }
+
                     //Laser width
                     if let Ok(b) = self.note.laser[0].binary_search_by(|f| f.0.cmp(&y)) {
                         let l = &self.note.laser[0][b];
@@ -943,6 +1027,20 @@ impl Ksh for crate::Chart {
     }
 }
 
+fn at_pulse<T>(values: &ByPulse<T>, y: u32) -> impl Iterator<Item = &(u32, T)> {
+    values[values.partition_point(|v| v.0 < y)..]
+        .iter()
+        .take_while(move |v| v.0 == y)
+}
+
+fn set_laser_filter(chart: &mut Chart, y: u32, filter: String) {
+    let laser = &mut chart.audio.audio_effect.laser;
+    if let Ok(e) = AudioEffect::try_from(filter.as_ref()) {
+        laser.def.entry(filter.clone()).or_insert(e);
+    }
+    laser.pulse_event.entry(filter).or_default().push((y, ()));
+}
+
 fn parse_tilt(
     tilt: &mut camera::TiltInfo,
     y: u32,
@@ -1059,3 +1157,163 @@ fn convert_params(effect: &AudioEffect, params: &mut Dict<String>) {
         _ => {}
     }
 }
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// Replaces the values of header fields in raw ksh data, every other byte of the file is kept
+/// as is. Fields that are not in the header yet are added at the end of it. The values have to
+/// be encoded the same way as the rest of the file.
+pub fn replace_ksh_header_values(data: &[u8], values: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut written = vec![false; values.len()];
+    let mut newline: &[u8] = b"\r\n";
+    let mut lines = data.split_inclusive(|b| *b == b'\n');
+
+    for line in lines.by_ref() {
+        let content = line.strip_suffix(b"\n").unwrap_or(line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        let line_end = &line[content.len()..];
+
+        if content.starts_with(b"--") {
+            for ((key, value), _) in values.iter().zip(&written).filter(|(_, w)| !**w) {
+                out.extend_from_slice(key.as_bytes());
+                out.push(b'=');
+                out.extend_from_slice(value);
+                out.extend_from_slice(newline);
+            }
+            out.extend_from_slice(line);
+            break;
+        }
+
+        if !line_end.is_empty() {
+            newline = line_end;
+        }
+
+        let (bom, field) = match content.strip_prefix(UTF8_BOM) {
+            Some(field) => (UTF8_BOM, field),
+            None => (&[][..], content),
+        };
+        let key = field.split(|b| *b == b'=').next().unwrap_or_default();
+        let replacement = values
+            .iter()
+            .position(|(k, _)| field.len() > key.len() && k.as_bytes() == key);
+
+        match replacement {
+            Some(i) => {
+                out.extend_from_slice(bom);
+                out.extend_from_slice(key);
+                out.push(b'=');
+                out.extend_from_slice(values[i].1);
+                out.extend_from_slice(line_end);
+                written[i] = true;
+            }
+            None => out.extend_from_slice(line),
+        }
+    }
+
+    for line in lines {
+        out.extend_from_slice(line);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replace_ksh_header_values, Ksh};
+    use crate::Chart;
+
+    #[test]
+    fn header_values_replaced() {
+        let data =
+            b"\xEF\xBB\xBFtitle=Old\r\nartist=Someone\r\no=0\r\n--\r\n0000|00|--\r\ntitle=x\r\n";
+        let result =
+            replace_ksh_header_values(data, &[("title", b"New"), ("o", b"-25"), ("level", b"17")]);
+        assert_eq!(
+            result,
+            b"\xEF\xBB\xBFtitle=New\r\nartist=Someone\r\no=-25\r\nlevel=17\r\n--\r\n0000|00|--\r\ntitle=x\r\n"
+        );
+    }
+
+    #[test]
+    fn movie_header() {
+        let chart = Chart::from_ksh("title=A\r\nv=bg.mp4\r\nvo=-120\r\n--\r\n0000|00|--\r\n--")
+            .expect("Failed to parse chart");
+        let movie = chart
+            .bg
+            .legacy
+            .and_then(|bg| bg.movie)
+            .expect("No movie info");
+        assert_eq!(movie.filename.as_deref(), Some("bg.mp4"));
+        assert_eq!(movie.offset, -120);
+    }
+
+    #[test]
+    fn camera_and_lane_options() {
+        let chart = Chart::from_ksh(
+            "title=A\r\n--\r\nzoom_top=10\r\nzoom_bottom=-20\r\ncenter_split=50;0\r\nlane_toggle=192\r\ntilt=keep_bigger\r\n0000|00|--\r\n--",
+        )
+        .expect("Failed to parse chart");
+        let body = &chart.camera.cam.body;
+        assert_eq!(body.rotation_x[0].v, 10.0);
+        assert_eq!(body.zoom[0].v, -20.0);
+        assert_eq!((body.split[0].v, body.split[0].vf), (50.0, Some(0.0)));
+        assert_eq!(
+            chart.camera.lane_toggle,
+            vec![(0, 4 * crate::KSON_RESOLUTION)]
+        );
+        assert_eq!(chart.camera.tilt.keep, vec![(0, true)]);
+    }
+
+    #[test]
+    fn laser_audio_options() {
+        let chart = Chart::from_ksh(
+            "title=A\r\nchokkakuvol=50\r\npfilterdelay=40\r\n--\r\nchokkakuse=down\r\nfiltertype=hpf1\r\n#define_filter myfilter type=BitCrusher\r\n0000|00|--\r\n--",
+        )
+        .expect("Failed to parse chart");
+        let laser = &chart.audio.key_sound.laser;
+        assert_eq!(laser.vol, vec![(0, 0.5)]);
+        assert_eq!(laser.slam_event.get("down"), Some(&vec![0]));
+
+        let effect = &chart.audio.audio_effect.laser;
+        assert_eq!(effect.peaking_filter_delay, 40);
+        assert_eq!(effect.pulse_event.get("hpf1"), Some(&vec![(0, ())]));
+        assert!(effect.def.contains_key("hpf1"));
+        assert!(effect.def.contains_key("myfilter"));
+    }
+
+    #[test]
+    fn unknown_options_round_trip() {
+        let chart = Chart::from_ksh(
+            "title=A\r\nt=120\r\nver=173\r\nicon=x.png\r\n--\r\nbeat=4/4\r\n#define_fx custom type=Unknown\r\n0000|00|--\r\nfuture_option=a=b\r\n0000|00|--\r\n--",
+        )
+        .expect("Failed to parse chart");
+        let compat = &chart.compat;
+        assert_eq!(compat.ksh_version, "173");
+        assert_eq!(
+            compat.ksh_unknown.meta.get("icon").map(String::as_str),
+            Some("x.png")
+        );
+        assert_eq!(
+            compat.ksh_unknown.option.get("future_option"),
+            Some(&vec![(crate::KSON_RESOLUTION * 2, "a=b".to_string())])
+        );
+        assert_eq!(
+            compat.ksh_unknown.line,
+            vec![(0, "#define_fx custom type=Unknown".to_string())]
+        );
+
+        let mut out = vec![];
+        chart.to_ksh(&mut out).expect("Failed to write chart");
+        let written =
+            Chart::from_ksh(&String::from_utf8_lossy(&out)).expect("Failed to parse chart");
+        assert!(written.compat.ksh_unknown == compat.ksh_unknown);
+        assert_eq!(written.compat.ksh_version, "173");
+    }
+
+    #[test]
+    fn header_unchanged_without_values() {
+        let data = b"title=A\nartist=B\n--\n0000|00|--\n--";
+        assert_eq!(replace_ksh_header_values(data, &[]), data);
+    }
+}
@@ -1,16 +1,26 @@
 pub mod camera;
+pub mod diff;
+pub mod editing;
 pub mod effects;
 mod graph;
 mod ksh;
+mod modifiers;
 pub mod overlaps;
 pub mod parameter;
+pub mod radar;
+#[cfg(feature = "schema")]
+pub mod schema;
 pub mod score_ticks;
+pub mod validate;
 mod vox;
 
 use camera::CameraInfo;
 use effects::AudioEffect;
 pub use graph::*;
 pub use ksh::*;
+pub use modifiers::{random_bt_order, LaneModifiers, LaneRng, RandomMode};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 use serde::de::Visitor;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -44,6 +54,7 @@ pub fn ms_from_ticks(ticks: i64, bpm: f64, tpqn: u32) -> f64 {
 
 #[repr(usize)]
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub enum Side {
     Left = 0,
     Right,
@@ -63,6 +74,7 @@ impl Side {
 
 #[repr(usize)]
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub enum BtLane {
     A = 0,
     B,
@@ -251,7 +263,7 @@ impl GraphSectionPoint {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Interval {
     pub y: u32,
     pub l: u32,
@@ -394,6 +406,7 @@ fn default_one<T: From<u8>>() -> T {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct NoteInfo {
     pub bt: [Vec<Interval>; 4],
     pub fx: [Vec<Interval>; 2],
@@ -411,6 +424,7 @@ impl NoteInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct DifficultyInfo {
     pub name: Option<String>,
     pub short_name: Option<String>,
@@ -418,6 +432,7 @@ pub struct DifficultyInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct MetaInfo {
     pub title: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -441,6 +456,7 @@ pub struct MetaInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct GaugeInfo {
     pub total: u32,
 }
@@ -544,6 +560,7 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for ByPulseOption<T> {
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct ByNote<T> {
     pub y: u32,
     pub v: Option<T>,
@@ -552,6 +569,7 @@ pub struct ByNote<T> {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct ByNotes<T> {
     pub bt: Option<[Vec<ByNote<T>>; 4]>,
     pub fx: Option<[Vec<ByNote<T>>; 2]>,
@@ -643,6 +661,7 @@ impl<'a, T> Iterator for ByNotesIter<'a, T> {
 
 /// (Numerator, Denominator)
 #[derive(Serialize, Deserialize, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct TimeSignature(pub u32, pub u32);
 
 impl TimeSignature {
@@ -657,6 +676,7 @@ impl TimeSignature {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct BeatInfo {
     pub bpm: ByPulse<f64>,
     pub time_sig: ByMeasureIdx<TimeSignature>,
@@ -676,6 +696,7 @@ impl BeatInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct BgmInfo {
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub filename: String,
@@ -688,11 +709,13 @@ pub struct BgmInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct LegacyBgmInfo {
     pub fp_filenames: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct PreviewInfo {
     #[serde(default = "default_zero::<u32>")]
     pub offset: u32,
@@ -715,22 +738,29 @@ impl BgmInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct KeySoundInfo {
     pub fx: KeySoundFXInfo,
     pub laser: KeySoundLaserInfo,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct KeySoundLaserInfo {
     pub vol: ByPulse<f64>,
+    /// Pulses where each slam sound gets selected
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub slam_event: Dict<Vec<u32>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct KeySoundFXInfo {
     pub chip_event: HashMap<String, [Vec<ByPulse<KeySoundInvokeFX>>; 2]>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct KeySoundInvokeFX {
     pub vol: f64,
 }
@@ -738,6 +768,7 @@ pub struct KeySoundInvokeFX {
 type NoteParamChange = ByPulseOption<Dict<String>>;
 
 #[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct AudioEffectFXInfo {
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub def: Dict<AudioEffect>,
@@ -748,6 +779,7 @@ pub struct AudioEffectFXInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct AudioEffectLaserInfo {
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     def: Dict<AudioEffect>,
@@ -760,12 +792,14 @@ pub struct AudioEffectLaserInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct AudioEffectInfo {
     pub fx: AudioEffectFXInfo,
     pub laser: AudioEffectLaserInfo,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[serde(default)]
 pub struct AudioInfo {
     pub bgm: BgmInfo,
@@ -782,6 +816,7 @@ impl AudioInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Chart {
     pub meta: MetaInfo,
     pub note: NoteInfo,
@@ -791,9 +826,38 @@ pub struct Chart {
     pub camera: camera::CameraInfo,
     pub version: String,
     pub bg: BgInfo,
+    #[serde(default, skip_serializing_if = "crate::IsDefault::is_default")]
+    pub compat: CompatInfo,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(default)]
+pub struct CompatInfo {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub ksh_version: String,
+    #[serde(skip_serializing_if = "crate::IsDefault::is_default")]
+    pub ksh_unknown: KshUnknownInfo,
+}
+
+/// Ksh content without a kson equivalent, kept so it can be written back out
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(default)]
+pub struct KshUnknownInfo {
+    /// Header options
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub meta: Dict<String>,
+    /// Options set in the chart body, by option name
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub option: Dict<ByPulse<String>>,
+    /// Any other lines in the chart body
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub line: ByPulse<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct BgInfo {
     pub filename: Option<String>,
     #[serde(default)]
@@ -818,13 +882,25 @@ impl Default for BgInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct LegacyBgInfo {
     pub bg: Option<Vec<KshBgInfo>>,
     pub layer: Option<KshLayerInfo>,
     pub movie: Option<KshMovieInfo>,
 }
 
+impl LegacyBgInfo {
+    pub(crate) fn empty() -> Self {
+        Self {
+            bg: None,
+            layer: None,
+            movie: None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct KshLayerInfo {
     pub filename: Option<String>, // self-explanatory (can be KSM default animation layer such as "arrow")
     /// one-loop duration in milliseconds.
@@ -838,17 +914,20 @@ pub struct KshLayerInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct KshLayerRotationInfo {
     pub tilt: bool, // whether lane tilts affect rotation of BG/layer
     pub spin: bool, // whether lane spins affect rotation of BG/layer
 }
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct KshMovieInfo {
     pub filename: Option<String>, // self-explanatory
     pub offset: i32,              // movie offset in millisecond
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct KshBgInfo {
     pub filename: String,
 }
@@ -899,6 +978,7 @@ impl Chart {
             camera: CameraInfo::default(),
             version: "0.7.0".to_string(),
             bg: BgInfo::new(),
+            compat: CompatInfo::default(),
         }
     }
 
@@ -1018,6 +1098,18 @@ impl Chart {
         }
     }
 
+    /// Tempo changes as `(ms, bpm)`, starting with the initial tempo at 0ms. Repeated values of
+    /// the same tempo aren't changes and are skipped.
+    pub fn bpm_changes(&self) -> Vec<(f64, f64)> {
+        let mut changes: Vec<(f64, f64)> = vec![];
+        for &(tick, bpm) in &self.beat.bpm {
+            if changes.last().is_none_or(|&(_, last)| last != bpm) {
+                changes.push((self.tick_to_ms(tick), bpm));
+            }
+        }
+        changes
+    }
+
     pub fn tick_duration_ms_at(&self, tick: u32) -> f64 {
         let bpm = self.bpm_at_tick(tick);
         beat_in_ms(bpm) / KSON_RESOLUTION as f64
@@ -1107,7 +1199,29 @@ where
 mod tests {
     use serde_test::Token;
 
-    use crate::parameter::{self, EffectFloat, EffectFreq, EffectParameterValue};
+    use crate::{
+        ksh::Ksh,
+        parameter::{self, EffectFloat, EffectFreq, EffectParameterValue},
+        Chart,
+    };
+
+    #[test]
+    fn bpm_changes() {
+        let chart = Chart::from_ksh(
+            "title=A\r\nt=120\r\n--\r\n0000|00|--\r\n--\r\nt=240\r\n0000|00|--\r\n--\r\nt=240\r\n0000|00|--\r\n--\r\nt=60\r\n0000|00|--\r\n0000|00|--\r\n--",
+        )
+        .expect("Failed to parse chart");
+
+        let changes = chart.bpm_changes();
+        let expected = [(0.0, 120.0), (2000.0, 240.0), (4000.0, 60.0)];
+        assert_eq!(changes.len(), expected.len(), "{changes:?}");
+        for ((ms, bpm), (expected_ms, expected_bpm)) in changes.into_iter().zip(expected) {
+            assert!((ms - expected_ms).abs() < 1e-6, "{ms} != {expected_ms}");
+            assert_eq!(bpm, expected_bpm);
+        }
+
+        assert!(Chart::new().bpm_changes().is_empty());
+    }
 
     #[test]
     fn effect_param() {
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Chart, Interval};
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RandomMode {
+    #[default]
+    Off,
+    /// The BT lanes are swapped around, patterns stay the same
+    Random,
+    /// Every BT note gets a lane of its own, can create patterns the chart doesn't have
+    HardRandom,
+}
+
+/// Lane modifiers of a single play, with everything needed to apply them to the chart again
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct LaneModifiers {
+    pub mirror: bool,
+    pub random: RandomMode,
+    /// Seed of the [`LaneRng`] used for random, unused when random is off
+    pub seed: u32,
+}
+
+/// Random numbers for the lane modifiers.
+///
+/// Recorded seeds have to give the same lanes on every version, so this is a fixed SplitMix64
+/// instead of a generator from a crate that may change its output.
+#[derive(Debug, Clone)]
+pub struct LaneRng(u64);
+
+impl LaneRng {
+    /// The chart hash is mixed in so one seed doesn't give every chart the same lanes
+    pub fn new(chart_hash: &str, seed: u32) -> Self {
+        // FNV-1a
+        let hash = chart_hash.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        Self(hash ^ seed as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Index below `n`
+    pub fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+/// BT lane order random gives a chart, lane `i` gets the notes of lane `order[i]`
+pub fn random_bt_order(chart_hash: &str, seed: u32) -> [usize; 4] {
+    let mut rng = LaneRng::new(chart_hash, seed);
+    let mut order = [0, 1, 2, 3];
+    for i in (1..order.len()).rev() {
+        order.swap(i, rng.below(i + 1));
+    }
+    order
+}
+
+impl Chart {
+    /// Applies the modifiers of a play, `chart_hash` is the hash of the chart as it was loaded.
+    /// Applying the same modifiers to the same chart always gives the same notes.
+    pub fn apply_modifiers(&mut self, chart_hash: &str, modifiers: &LaneModifiers) {
+        if modifiers.mirror {
+            self.mirror();
+        }
+
+        match modifiers.random {
+            RandomMode::Off => {}
+            RandomMode::Random => self.permute_bt(random_bt_order(chart_hash, modifiers.seed)),
+            RandomMode::HardRandom => {
+                let mut rng = LaneRng::new(chart_hash, modifiers.seed);
+                self.shuffle_bt(|n| rng.below(n));
+            }
+        }
+    }
+
+    /// Reverses the BT lanes, swaps the FX lanes and flips the lasers, the left laser becomes the
+    /// right one with every position inverted
+    pub fn mirror(&mut self) {
+        self.note.bt.reverse();
+        self.note.fx.swap(0, 1);
+        self.note.laser.swap(0, 1);
+        for point in self
+            .note
+            .laser
+            .iter_mut()
+            .flatten()
+            .flat_map(|section| section.1.iter_mut())
+        {
+            point.v = 1.0 - point.v;
+            point.vf = point.vf.map(|vf| 1.0 - vf);
+        }
+
+        for lanes in self.audio.audio_effect.fx.long_event.values_mut() {
+            lanes.swap(0, 1);
+        }
+        for lanes in self.audio.key_sound.fx.chip_event.values_mut() {
+            lanes.swap(0, 1);
+        }
+    }
+
+    /// Moves the notes of BT lane `order[i]` to lane `i`, `order` has to be a permutation of the
+    /// four lanes
+    pub fn permute_bt(&mut self, order: [usize; 4]) {
+        let mut lanes = std::mem::take(&mut self.note.bt).map(Some);
+        self.note.bt = order.map(|i| lanes[i].take().expect("Not a permutation of the BT lanes"));
+    }
+
+    /// Moves every BT note to its own random lane, `pick(n)` returns a random index below `n`.
+    ///
+    /// Notes are placed from the start of the chart into lanes that are free for their whole
+    /// length, so holds never overlap other notes. Notes that can't be placed in a free lane
+    /// keep their own.
+    pub fn shuffle_bt(&mut self, mut pick: impl FnMut(usize) -> usize) {
+        let mut notes: Vec<(usize, Interval)> = std::mem::take(&mut self.note.bt)
+            .into_iter()
+            .enumerate()
+            .flat_map(|(lane, notes)| notes.into_iter().map(move |n| (lane, n)))
+            .collect();
+        notes.sort_by_key(|(_, n)| n.y);
+
+        // First tick each lane is free from, a hold may end on the tick the next note starts on
+        let mut free_from = [0u32; 4];
+        for (lane, note) in notes {
+            let free: Vec<usize> = (0..4).filter(|&l| free_from[l] <= note.y).collect();
+            let lane = if free.is_empty() {
+                lane
+            } else {
+                free[pick(free.len()).min(free.len() - 1)]
+            };
+            free_from[lane] = free_from[lane].max(note.y + note.l.max(1));
+            self.note.bt[lane].push(note);
+        }
+
+        for lane in &mut self.note.bt {
+            lane.sort_by_key(|n| n.y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{random_bt_order, LaneModifiers, RandomMode};
+    use crate::{ksh::Ksh, overlaps::Overlaps, Chart};
+
+    /// Chips in every BT lane, a hold in BT B, an FX chip on the left and both lasers moving
+    fn fixture() -> Chart {
+        Chart::from_ksh(
+            "title=Fixture\r\nt=120\r\n--\r\n1000|20|0o\r\n0200|00|::\r\n0210|00|o:\r\n0001|02|-0\r\n0000|00|--\r\n--",
+        )
+        .expect("Failed to parse chart")
+    }
+
+    #[test]
+    fn mirror_lanes() {
+        let original = fixture();
+        let mut chart = original.clone();
+        chart.mirror();
+
+        for lane in 0..4 {
+            assert_eq!(chart.note.bt[lane], original.note.bt[3 - lane]);
+        }
+        assert_eq!(chart.note.fx[1], original.note.fx[0]);
+        assert_eq!(chart.note.fx[0], original.note.fx[1]);
+
+        for side in 0..2 {
+            let mirrored = &chart.note.laser[side];
+            let original = &original.note.laser[1 - side];
+            assert!(!original.is_empty());
+            assert_eq!(mirrored.len(), original.len());
+            for (a, b) in mirrored.iter().zip(original) {
+                assert_eq!(a.tick(), b.tick());
+                for (a, b) in a.1.iter().zip(&b.1) {
+                    assert_eq!(a.ry, b.ry);
+                    assert!((a.v - (1.0 - b.v)).abs() < 1e-9, "{} {}", a.v, b.v);
+                }
+            }
+        }
+
+        chart.mirror();
+        assert_eq!(chart.note.bt, original.note.bt);
+    }
+
+    #[test]
+    fn permute_lanes() {
+        let original = fixture();
+        let mut chart = original.clone();
+        chart.permute_bt([2, 0, 3, 1]);
+        assert_eq!(chart.note.bt[0], original.note.bt[2]);
+        assert_eq!(chart.note.bt[1], original.note.bt[0]);
+        assert_eq!(chart.note.bt[2], original.note.bt[3]);
+        assert_eq!(chart.note.bt[3], original.note.bt[1]);
+        assert_eq!(chart.note.fx, original.note.fx);
+    }
+
+    #[test]
+    fn recorded_seed_reproduces_chart() {
+        let original = fixture();
+        for random in [RandomMode::Random, RandomMode::HardRandom] {
+            let modifiers = LaneModifiers {
+                mirror: true,
+                random,
+                seed: 0xdead_beef,
+            };
+            let mut played = original.clone();
+            played.apply_modifiers("0123abcd", &modifiers);
+            let mut verified = original.clone();
+            verified.apply_modifiers("0123abcd", &modifiers);
+            assert_eq!(played.note.bt, verified.note.bt);
+            assert_eq!(played.note.fx, verified.note.fx);
+        }
+
+        let orders: Vec<_> = (0..64)
+            .map(|seed| random_bt_order("0123abcd", seed))
+            .collect();
+        for order in &orders {
+            let mut sorted = *order;
+            sorted.sort();
+            assert_eq!(sorted, [0, 1, 2, 3]);
+        }
+        assert!(orders.iter().any(|o| *o != orders[0]));
+        // Recorded seeds have to give the same lanes on later versions
+        assert_eq!(random_bt_order("0123abcd", 42), [1, 2, 0, 3]);
+        assert_ne!(
+            (0..8).map(|s| random_bt_order("a", s)).collect::<Vec<_>>(),
+            (0..8).map(|s| random_bt_order("b", s)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn shuffle_keeps_notes_apart() {
+        let original = fixture();
+        let mut all_notes: Vec<_> = original.note.bt.iter().flatten().copied().collect();
+        all_notes.sort_by_key(|n| (n.y, n.l));
+
+        for seed in 0..16usize {
+            let mut chart = original.clone();
+            let mut state = seed;
+            chart.shuffle_bt(|n| {
+                state = state.wrapping_mul(31).wrapping_add(7);
+                state % n
+            });
+
+            let mut notes: Vec<_> = chart.note.bt.iter().flatten().copied().collect();
+            notes.sort_by_key(|n| (n.y, n.l));
+            assert_eq!(notes, all_notes);
+
+            for lane in &chart.note.bt {
+                for pair in lane.windows(2) {
+                    assert!(pair[0].y < pair[1].y);
+                    assert!(
+                        !pair[0].overlaps(&pair[1]) || pair[0].y + pair[0].l == pair[1].y,
+                        "{:?} overlaps {:?}",
+                        (pair[0].y, pair[0].l),
+                        (pair[1].y, pair[1].l)
+                    );
+                }
+            }
+        }
+    }
+}
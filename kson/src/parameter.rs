@@ -4,11 +4,13 @@ use std::{
 
 use num_traits::{NumCast, NumOps};
 use serde::{de::Visitor, Deserialize, Serialize};
+use thiserror::Error;
 
 #[cfg(feature = "schema")]
 use schemars::JsonSchema;
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub enum InterpolationShape {
     #[default]
     Linear,
@@ -81,6 +83,57 @@ pub enum EffectParameterValue {
     Undefined,
 }
 
+/// What the numbers of an [`EffectParameterValue`] are measured in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParameterUnit {
+    /// Fractions of a measure, synced to the tempo
+    Measure,
+    Seconds,
+    Samples,
+    Switch,
+    Percent,
+    Hertz,
+    Semitones,
+    Number,
+    Filename,
+    Undefined,
+}
+
+impl Display for ParameterUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ParameterUnit::Measure => "fractions of a measure",
+            ParameterUnit::Seconds => "seconds",
+            ParameterUnit::Samples => "samples",
+            ParameterUnit::Switch => "on/off",
+            ParameterUnit::Percent => "percent",
+            ParameterUnit::Hertz => "hertz",
+            ParameterUnit::Semitones => "semitones",
+            ParameterUnit::Number => "plain numbers",
+            ParameterUnit::Filename => "filenames",
+            ParameterUnit::Undefined => "nothing",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ParameterError {
+    #[error("Missing value")]
+    Empty,
+    #[error("\"{0}\" has more than one '>', expected \"off>on\"")]
+    TooManyStates(String),
+    #[error("\"{value}\" is not a valid number of {unit}")]
+    InvalidNumber { value: String, unit: ParameterUnit },
+    #[error("\"{0}\" divides by zero")]
+    ZeroDenominator(String),
+    #[error("Range \"{range}\" goes from {start} to {end}, both ends need the same unit")]
+    MixedUnits {
+        range: String,
+        start: ParameterUnit,
+        end: ParameterUnit,
+    },
+}
+
 trait EffectParam {
     fn interpolate(&self, v: f32, shape: InterpolationShape) -> f32;
 }
@@ -92,6 +145,36 @@ impl EffectParameterValue {
             _ => InterpolationShape::Linear,
         }
     }
+    pub fn unit(&self) -> ParameterUnit {
+        match self {
+            EffectParameterValue::Length(_, true) => ParameterUnit::Measure,
+            EffectParameterValue::Length(_, false) => ParameterUnit::Seconds,
+            EffectParameterValue::Sample(_) => ParameterUnit::Samples,
+            EffectParameterValue::Switch(_) => ParameterUnit::Switch,
+            EffectParameterValue::Rate(_) => ParameterUnit::Percent,
+            EffectParameterValue::Freq(_) => ParameterUnit::Hertz,
+            EffectParameterValue::Pitch(_) => ParameterUnit::Semitones,
+            EffectParameterValue::Int(_) | EffectParameterValue::Float(_) => ParameterUnit::Number,
+            EffectParameterValue::Filename(_) => ParameterUnit::Filename,
+            EffectParameterValue::Undefined => ParameterUnit::Undefined,
+        }
+    }
+
+    /// Value at `progress` between the min and max of the range. Lengths are in seconds with
+    /// tempo synced ones resolved at `bpm`, rates are ratios, frequencies are in Hz and switches
+    /// are 0 or 1. Filenames have no value and return NaN.
+    pub fn value_at(&self, progress: f32, bpm: f32) -> f32 {
+        self.value_with_shape(progress, bpm, self.default_shape())
+    }
+
+    fn value_with_shape(&self, progress: f32, bpm: f32, shape: InterpolationShape) -> f32 {
+        let progress = progress.clamp(0.0, 1.0);
+        match self {
+            EffectParameterValue::Length(l, true) => l.interpolate(progress, shape) * 240.0 / bpm,
+            v => v.interpolate(progress, shape),
+        }
+    }
+
     pub fn to_duration(&self, bpm: f32, v: f32) -> Duration {
         match self {
             EffectParameterValue::Length(l, tempo) => {
@@ -242,22 +325,19 @@ impl<'de, T: Default> Deserialize<'de> for EffectParameter<T> {
 }
 
 impl<T: Default> FromStr for EffectParameter<T> {
-    type Err = &'static str;
+    type Err = ParameterError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (a, b) = {
-            let mut split = s.split('>');
-            if let Some(a) = split.next() {
-                Ok((a.to_string(), split.next().map(str::to_string)))
-            } else {
-                Err("Missing value")
-            }
-        }?;
-        let off: EffectParameterValue = a.parse()?;
+        let mut states = s.split('>');
+        let off: EffectParameterValue = states.next().unwrap_or_default().parse()?;
+        let on = states.next().map(str::parse).transpose()?;
+        if states.next().is_some() {
+            return Err(ParameterError::TooManyStates(s.to_string()));
+        }
 
         Ok(Self {
             v: T::default(),
-            on: b.and_then(|o| EffectParameterValue::from_str(&o).ok()),
+            on,
             shape: off.default_shape(),
             off,
         })
@@ -311,6 +391,19 @@ impl Display for EffectParameterValue {
     }
 }
 
+impl<T> EffectParameter<T> {
+    /// [`EffectParameterValue::value_at`] of the on or off value, using the shape of the
+    /// parameter
+    pub fn value_at(&self, progress: f32, bpm: f32, on: bool) -> f32 {
+        let value = if on {
+            self.on.as_ref().unwrap_or(&self.off)
+        } else {
+            &self.off
+        };
+        value.value_with_shape(progress, bpm, self.shape)
+    }
+}
+
 impl<T> EffectParameter<T>
 where
     T: NumCast + Copy + NumOps + Default,
@@ -341,121 +434,143 @@ where
 }
 
 impl FromStr for EffectParameterValue {
-    type Err = &'static str;
+    type Err = ParameterError;
 
     fn from_str(v: &str) -> Result<Self, Self::Err> {
-        let parse_part = |v: &str| {
-            if v.contains('/') {
-                if let Some((Ok(a), Ok(b))) = v
-                    .split_once('/')
-                    .map(|v| (v.0.parse::<i32>(), v.1.parse::<i32>()))
-                {
-                    let v = EffectFloat::Fraction(a, b);
-                    return EffectParameterValue::Length(v..=v, true);
-                }
-            }
+        let v = v.trim();
+        if v.is_empty() {
+            return Err(ParameterError::Empty);
+        }
 
-            if v.ends_with("ms") {
-                if let Ok(r) = v.trim_end_matches("ms").parse::<f32>() {
-                    let r = EffectFloat::Float(r / 1000.0);
-                    return EffectParameterValue::Length(r..=r, false);
-                }
+        let Some((a, b)) = split_range(v) else {
+            return parse_part(v);
+        };
+
+        let parsed = match (parse_part(a)?, parse_part(b)?) {
+            (EffectParameterValue::Filename(_), _) | (_, EffectParameterValue::Filename(_)) => {
+                EffectParameterValue::Filename(v.to_string())
             }
-            if v.ends_with('s') {
-                if let Ok(r) = v.trim_end_matches('s').parse::<f32>() {
-                    let r = EffectFloat::Float(r);
-                    return EffectParameterValue::Length(r..=r, false);
-                }
+            (EffectParameterValue::Length(a, ab), EffectParameterValue::Length(b, bb))
+                if ab == bb =>
+            {
+                EffectParameterValue::Length(*a.start()..=*b.end(), ab)
             }
-
-            if v.ends_with('%') {
-                if let Ok(r) = v.trim_end_matches('%').parse::<f32>() {
-                    let r = r / 100.0;
-                    return EffectParameterValue::Rate(r..=r);
-                }
+            (EffectParameterValue::Sample(a), EffectParameterValue::Sample(b)) => {
+                EffectParameterValue::Sample(*a.start()..=*b.end())
             }
-
-            if v.ends_with("kHz") || v.ends_with("khz") {
-                if let Ok(r) = v
-                    .trim_end_matches("kHz")
-                    .trim_end_matches("khz")
-                    .parse::<f32>()
-                {
-                    let r = EffectFreq::Khz(r);
-                    return EffectParameterValue::Freq(r..=r);
-                }
+            (EffectParameterValue::Switch(a), EffectParameterValue::Switch(b)) => {
+                EffectParameterValue::Switch(*a.start()..=*b.end())
             }
-
-            if v.ends_with("Hz") || v.ends_with("hz") {
-                if let Ok(r) = v
-                    .trim_end_matches("Hz")
-                    .trim_end_matches("hz")
-                    .parse::<i32>()
-                {
-                    let r = EffectFreq::Hz(r);
-                    return EffectParameterValue::Freq(r..=r);
-                }
+            (EffectParameterValue::Rate(a), EffectParameterValue::Rate(b)) => {
+                EffectParameterValue::Rate(*a.start()..=*b.end())
             }
-            if v.ends_with("samples") {
-                if let Ok(r) = v.trim_end_matches("samples").parse::<i32>() {
-                    return EffectParameterValue::Sample(r..=r);
-                }
+            (EffectParameterValue::Freq(a), EffectParameterValue::Freq(b)) => {
+                EffectParameterValue::Freq(*a.start()..=*b.end())
             }
-
-            if let Ok(v) = v.parse::<f32>() {
-                return EffectParameterValue::Float(v..=v);
+            (EffectParameterValue::Pitch(a), EffectParameterValue::Pitch(b)) => {
+                EffectParameterValue::Pitch(*a.start()..=*b.end())
             }
-
-            if let Ok(v) = v.parse::<i32>() {
-                return EffectParameterValue::Float(v as f32..=v as f32);
+            (EffectParameterValue::Int(a), EffectParameterValue::Int(b)) => {
+                EffectParameterValue::Int(*a.start()..=*b.end())
             }
-
-            if v.eq("on") {
-                EffectParameterValue::Switch(true..=true)
-            } else if v.eq("off") {
-                EffectParameterValue::Switch(false..=false)
-            } else {
-                EffectParameterValue::Filename(v.to_string())
+            (EffectParameterValue::Float(a), EffectParameterValue::Float(b)) => {
+                EffectParameterValue::Float(*a.start()..=*b.end())
+            }
+            (a, b) => {
+                return Err(ParameterError::MixedUnits {
+                    range: v.to_string(),
+                    start: a.unit(),
+                    end: b.unit(),
+                })
             }
         };
 
-        if v.contains('-') {
-            // Range
-            if let Some((a, b)) = v.split_once('-') {
-                let parsed = match (parse_part(a), parse_part(b)) {
-                    (EffectParameterValue::Length(a, ab), EffectParameterValue::Length(b, bb)) => {
-                        EffectParameterValue::Length(*a.start()..=*b.end(), ab || bb)
-                    }
-                    (EffectParameterValue::Sample(a), EffectParameterValue::Sample(b)) => {
-                        EffectParameterValue::Sample(*a.start()..=*b.end())
-                    }
-
-                    (EffectParameterValue::Switch(a), EffectParameterValue::Switch(b)) => {
-                        EffectParameterValue::Switch(*a.start()..=*b.end())
-                    }
-                    (EffectParameterValue::Rate(a), EffectParameterValue::Rate(b)) => {
-                        EffectParameterValue::Rate(*a.start()..=*b.end())
-                    }
-                    (EffectParameterValue::Freq(a), EffectParameterValue::Freq(b)) => {
-                        EffectParameterValue::Freq(*a.start()..=*b.end())
-                    }
-                    (EffectParameterValue::Pitch(a), EffectParameterValue::Pitch(b)) => {
-                        EffectParameterValue::Pitch(*a.start()..=*b.end())
-                    }
-                    (EffectParameterValue::Int(a), EffectParameterValue::Int(b)) => {
-                        EffectParameterValue::Int(*a.start()..=*b.end())
-                    }
-                    (EffectParameterValue::Float(a), EffectParameterValue::Float(b)) => {
-                        EffectParameterValue::Float(*a.start()..=*b.end())
-                    }
-                    _ => EffectParameterValue::Filename(v.to_string()),
-                };
-                return Ok(parsed);
+        Ok(parsed)
+    }
+}
+
+/// Splits `min-max` ranges, a `-` at the start or after another `-` or an exponent is a sign
+fn split_range(v: &str) -> Option<(&str, &str)> {
+    let bytes = v.as_bytes();
+    let i = (1..bytes.len())
+        .find(|&i| bytes[i] == b'-' && !matches!(bytes[i - 1], b'-' | b'e' | b'E'))?;
+    Some((&v[..i], &v[i + 1..]))
+}
+
+/// Whether `v` is meant as a number, other values that don't parse are taken as filenames
+fn looks_numeric(v: &str) -> bool {
+    v.chars().any(|c| c.is_ascii_digit())
+        && v.chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'))
+}
+
+fn parse_number<T: FromStr>(v: &str, unit: ParameterUnit) -> Result<T, ParameterError> {
+    v.parse().map_err(|_| ParameterError::InvalidNumber {
+        value: v.to_string(),
+        unit,
+    })
+}
+
+/// Strips a unit suffix, `None` if `v` doesn't end with it or the rest isn't a number
+fn number_with_suffix<'a>(v: &'a str, suffixes: &[&str]) -> Option<&'a str> {
+    suffixes
+        .iter()
+        .find_map(|suffix| v.strip_suffix(suffix))
+        .filter(|n| looks_numeric(n))
+}
+
+/// Parses a single value of a range
+fn parse_part(v: &str) -> Result<EffectParameterValue, ParameterError> {
+    if let Some((a, b)) = v.split_once('/') {
+        if looks_numeric(a) && looks_numeric(b) {
+            let a = parse_number(a, ParameterUnit::Measure)?;
+            let b: i32 = parse_number(b, ParameterUnit::Measure)?;
+            if b == 0 {
+                return Err(ParameterError::ZeroDenominator(v.to_string()));
             }
+            let v = EffectFloat::Fraction(a, b);
+            return Ok(EffectParameterValue::Length(v..=v, true));
         }
+    }
+
+    if let Some(r) = number_with_suffix(v, &["ms"]) {
+        let r = EffectFloat::Float(parse_number::<f32>(r, ParameterUnit::Seconds)? / 1000.0);
+        return Ok(EffectParameterValue::Length(r..=r, false));
+    }
+    if let Some(r) = number_with_suffix(v, &["s"]) {
+        let r = EffectFloat::Float(parse_number(r, ParameterUnit::Seconds)?);
+        return Ok(EffectParameterValue::Length(r..=r, false));
+    }
 
-        Ok(parse_part(v))
+    if let Some(r) = number_with_suffix(v, &["%"]) {
+        let r = parse_number::<f32>(r, ParameterUnit::Percent)? / 100.0;
+        return Ok(EffectParameterValue::Rate(r..=r));
     }
+
+    if let Some(r) = number_with_suffix(v, &["kHz", "khz"]) {
+        let r = EffectFreq::Khz(parse_number(r, ParameterUnit::Hertz)?);
+        return Ok(EffectParameterValue::Freq(r..=r));
+    }
+    if let Some(r) = number_with_suffix(v, &["Hz", "hz"]) {
+        let r = EffectFreq::Hz(parse_number(r, ParameterUnit::Hertz)?);
+        return Ok(EffectParameterValue::Freq(r..=r));
+    }
+
+    if let Some(r) = number_with_suffix(v, &["samples"]) {
+        let r = parse_number(r, ParameterUnit::Samples)?;
+        return Ok(EffectParameterValue::Sample(r..=r));
+    }
+
+    if looks_numeric(v) {
+        let v = parse_number(v, ParameterUnit::Number)?;
+        return Ok(EffectParameterValue::Float(v..=v));
+    }
+
+    Ok(match v {
+        "on" => EffectParameterValue::Switch(true..=true),
+        "off" => EffectParameterValue::Switch(false..=false),
+        _ => EffectParameterValue::Filename(v.to_string()),
+    })
 }
 
 fn serialize_range<T: PartialOrd, F>(r: &RangeInclusive<T>, ser: F) -> String
@@ -468,3 +583,168 @@ where
         format!("{}-{}", ser(r.start()), ser(r.end()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        EffectFloat, EffectFreq, EffectParameter, EffectParameterValue as V, ParameterError,
+        ParameterUnit,
+    };
+
+    fn fraction(a: i32, b: i32) -> V {
+        let f = EffectFloat::Fraction(a, b);
+        V::Length(f..=f, true)
+    }
+
+    fn seconds(a: f32, b: f32) -> V {
+        V::Length(EffectFloat::Float(a)..=EffectFloat::Float(b), false)
+    }
+
+    #[test]
+    fn parse_syntaxes() {
+        let cases = [
+            ("1/8", fraction(1, 8), None),
+            ("3/16", fraction(3, 16), None),
+            (
+                "1/4-1/16",
+                V::Length(
+                    EffectFloat::Fraction(1, 4)..=EffectFloat::Fraction(1, 16),
+                    true,
+                ),
+                None,
+            ),
+            ("100ms", seconds(0.1, 0.1), None),
+            ("100ms-800ms", seconds(0.1, 0.8), None),
+            ("0.5s", seconds(0.5, 0.5), None),
+            ("50%", V::Rate(0.5..=0.5), None),
+            ("0%>100%", V::Rate(0.0..=0.0), Some(V::Rate(1.0..=1.0))),
+            ("0%-100%", V::Rate(0.0..=1.0), None),
+            (
+                "500Hz",
+                V::Freq(EffectFreq::Hz(500)..=EffectFreq::Hz(500)),
+                None,
+            ),
+            (
+                "80hz-2khz",
+                V::Freq(EffectFreq::Hz(80)..=EffectFreq::Khz(2.0)),
+                None,
+            ),
+            (
+                "10kHz",
+                V::Freq(EffectFreq::Khz(10.0)..=EffectFreq::Khz(10.0)),
+                None,
+            ),
+            ("30samples", V::Sample(30..=30), None),
+            ("0samples-30samples", V::Sample(0..=30), None),
+            ("on", V::Switch(true..=true), None),
+            (
+                "off>on",
+                V::Switch(false..=false),
+                Some(V::Switch(true..=true)),
+            ),
+            ("off-on", V::Switch(false..=true), None),
+            ("1.414", V::Float(1.414..=1.414), None),
+            ("0.5-0.8", V::Float(0.5..=0.8), None),
+            ("1>5", V::Float(1.0..=1.0), Some(V::Float(5.0..=5.0))),
+            ("-12", V::Float(-12.0..=-12.0), None),
+            ("-12--6", V::Float(-12.0..=-6.0), None),
+            ("se.wav", V::Filename("se.wav".into()), None),
+            (
+                "e9fda14b-d635-4cd8-8c7a-ca12f8d9b78a",
+                V::Filename("e9fda14b-d635-4cd8-8c7a-ca12f8d9b78a".into()),
+                None,
+            ),
+        ];
+
+        for (input, off, on) in cases {
+            let param: EffectParameter<f32> =
+                input.parse().unwrap_or_else(|e| panic!("{input}: {e}"));
+            assert_eq!(param.off, off, "{input}");
+            assert_eq!(param.on, on, "{input}");
+            assert_eq!(param.to_string().parse::<EffectParameter<f32>>(), Ok(param));
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases = [
+            ("", ParameterError::Empty),
+            ("1/0", ParameterError::ZeroDenominator("1/0".into())),
+            (
+                "1.5/4",
+                ParameterError::InvalidNumber {
+                    value: "1.5".into(),
+                    unit: ParameterUnit::Measure,
+                },
+            ),
+            (
+                "1.5samples",
+                ParameterError::InvalidNumber {
+                    value: "1.5".into(),
+                    unit: ParameterUnit::Samples,
+                },
+            ),
+            (
+                "12.5Hz",
+                ParameterError::InvalidNumber {
+                    value: "12.5".into(),
+                    unit: ParameterUnit::Hertz,
+                },
+            ),
+            (
+                "1e",
+                ParameterError::InvalidNumber {
+                    value: "1e".into(),
+                    unit: ParameterUnit::Number,
+                },
+            ),
+            (
+                "1/4-100ms",
+                ParameterError::MixedUnits {
+                    range: "1/4-100ms".into(),
+                    start: ParameterUnit::Measure,
+                    end: ParameterUnit::Seconds,
+                },
+            ),
+            (
+                "50%-0.5",
+                ParameterError::MixedUnits {
+                    range: "50%-0.5".into(),
+                    start: ParameterUnit::Percent,
+                    end: ParameterUnit::Number,
+                },
+            ),
+            (
+                "0%>50%>100%",
+                ParameterError::TooManyStates("0%>50%>100%".into()),
+            ),
+            ("0%>1/0", ParameterError::ZeroDenominator("1/0".into())),
+        ];
+
+        for (input, error) in cases {
+            assert_eq!(input.parse::<EffectParameter<f32>>(), Err(error), "{input}");
+        }
+    }
+
+    #[test]
+    fn values() {
+        let value = |s: &str, progress, on| {
+            s.parse::<EffectParameter<f32>>()
+                .unwrap()
+                .value_at(progress, 120.0, on)
+        };
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+
+        assert!(close(value("1/4", 0.0, false), 0.5));
+        assert!(close(value("1/4-1/8", 1.0, false), 0.25));
+        assert!(close(value("100ms-800ms", 0.5, false), 0.45));
+        assert!(close(value("0.5-0.8", 1.0, false), 0.8));
+        assert!(close(value("0.5-0.8", 2.0, false), 0.8));
+        assert!(close(value("80hz-2khz", 0.5, false), 400.0));
+        assert!(close(value("0%>100%", 0.5, false), 0.0));
+        assert!(close(value("0%>100%", 0.5, true), 1.0));
+        assert!(close(value("30samples", 0.0, true), 30.0));
+        assert!(close(value("off>on", 0.0, true), 1.0));
+        assert!(value("se.wav", 0.0, false).is_nan());
+    }
+}
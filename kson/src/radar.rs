@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    score_ticks::{generate_score_ticks, ScoreTick},
+    Chart,
+};
+
+/// Notes per second that give a full `notes` score
+const NOTES_MAX_NPS: f64 = 12.0;
+/// Notes per second in the densest window that give a full `peak` score
+const PEAK_MAX_NPS: f64 = 24.0;
+/// Length of the window used for the peak density
+const PEAK_WINDOW_MS: f64 = 2000.0;
+/// Slams per second that give a full `slams` score
+const SLAMS_MAX_PER_SECOND: f64 = 1.0;
+/// Gimmicks (bpm changes, scroll speed changes, spins) per minute that give a full `tricky` score
+const TRICKY_MAX_PER_MINUTE: f64 = 30.0;
+
+/// Normalized difficulty attributes of a chart, every value is in the range `0.0..=1.0`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Radar {
+    /// Average note density
+    pub notes: f64,
+    /// Note density of the densest part of the chart
+    pub peak: f64,
+    /// Fraction of the chart where a laser is active
+    pub lasers: f64,
+    /// Slam frequency
+    pub slams: f64,
+    /// Fraction of notes that have to be hit while one hand is on a knob
+    pub one_hand: f64,
+    /// Fraction of notes that have to be hit by crossing over a hand that is on a knob
+    pub hand_trip: f64,
+    /// Frequency of bpm changes, scroll speed changes and camera spins
+    pub tricky: f64,
+}
+
+fn lane_side(lane: usize) -> Option<usize> {
+    match lane {
+        0 | 1 | 4 => Some(0),
+        2 | 3 | 5 => Some(1),
+        _ => None,
+    }
+}
+
+impl Chart {
+    /// Calculates the difficulty radar of the chart
+    pub fn radar(&self) -> Radar {
+        let last_tick = self.get_last_tick();
+        let duration_ms = self.tick_to_ms(last_tick);
+        if last_tick == 0 || duration_ms <= 0.0 {
+            return Radar::default();
+        }
+        let duration_s = duration_ms / 1000.0;
+
+        let laser_intervals: [Vec<(u32, u32)>; 2] = [0, 1].map(|side| {
+            self.note.laser[side]
+                .iter()
+                .map(|s| (s.tick(), s.tick() + s.last().map(|p| p.ry).unwrap_or(0)))
+                .collect()
+        });
+        let laser_active = |side: usize, y: u32| {
+            laser_intervals[side]
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&y))
+        };
+
+        let slams = generate_score_ticks(self)
+            .iter()
+            .filter(|t| matches!(t.tick, ScoreTick::Slam { .. }))
+            .count();
+
+        let mut notes: Vec<(u32, usize)> = self
+            .note
+            .bt
+            .iter()
+            .chain(self.note.fx.iter())
+            .enumerate()
+            .flat_map(|(lane, l)| l.iter().map(move |i| (i.y, lane)))
+            .collect();
+        notes.sort_by_key(|(y, _)| *y);
+
+        let mut one_hand = 0;
+        let mut hand_trip = 0;
+        for (y, lane) in &notes {
+            let active = [laser_active(0, *y), laser_active(1, *y)];
+            if active[0] != active[1] {
+                one_hand += 1;
+                let knob_side = if active[0] { 0 } else { 1 };
+                if lane_side(*lane) == Some(knob_side) {
+                    hand_trip += 1;
+                }
+            }
+        }
+
+        let note_times: Vec<f64> = notes.iter().map(|(y, _)| self.tick_to_ms(*y)).collect();
+        let note_count = note_times.len();
+        let mut peak_notes = 0;
+        let mut window_start = 0;
+        for (i, t) in note_times.iter().enumerate() {
+            while note_times[window_start] < t - PEAK_WINDOW_MS {
+                window_start += 1;
+            }
+            peak_notes = peak_notes.max(i + 1 - window_start);
+        }
+
+        let laser_ms: f64 = laser_intervals
+            .iter()
+            .flatten()
+            .map(|(start, end)| self.tick_to_ms(*end) - self.tick_to_ms(*start))
+            .sum();
+
+        let pattern = &self.camera.cam.pattern.laser.slam_event;
+        let gimmicks = self.beat.bpm.len().saturating_sub(1)
+            + self.beat.scroll_speed.len().saturating_sub(1)
+            + pattern.spin.len()
+            + pattern.half_spin.len()
+            + pattern.swing.len();
+
+        let ratio = |count: usize| {
+            if note_count == 0 {
+                0.0
+            } else {
+                count as f64 / note_count as f64
+            }
+        };
+
+        Radar {
+            notes: (note_count as f64 / duration_s / NOTES_MAX_NPS).clamp(0.0, 1.0),
+            peak: (peak_notes as f64 / (PEAK_WINDOW_MS / 1000.0) / PEAK_MAX_NPS).clamp(0.0, 1.0),
+            lasers: (laser_ms / (duration_ms * 2.0)).clamp(0.0, 1.0),
+            slams: (slams as f64 / duration_s / SLAMS_MAX_PER_SECOND).clamp(0.0, 1.0),
+            one_hand: ratio(one_hand),
+            hand_trip: ratio(hand_trip),
+            tricky: (gimmicks as f64 / (duration_s / 60.0) / TRICKY_MAX_PER_MINUTE).clamp(0.0, 1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Radar;
+    use crate::{Chart, GraphSectionPoint, Interval, LaserSection, TimeSignature, KSON_RESOLUTION};
+
+    fn base_chart() -> Chart {
+        let mut chart = Chart::new();
+        chart.beat.bpm = vec![(0, 120.0)];
+        chart.beat.time_sig = vec![(0, TimeSignature(4, 4))];
+        chart
+    }
+
+    /// 8 measures of quarter note chips alternating between all BT lanes
+    fn stream_chart() -> Chart {
+        let mut chart = base_chart();
+        for i in 0..32 {
+            chart.note.bt[i % 4].push(Interval {
+                y: i as u32 * KSON_RESOLUTION,
+                l: 0,
+            });
+        }
+        chart
+    }
+
+    /// A left laser with a slam over 4 measures with chips on both sides
+    fn laser_chart() -> Chart {
+        let mut chart = base_chart();
+        let mut slam = GraphSectionPoint::new(0, 0.0);
+        slam.vf = Some(1.0);
+        chart.note.laser[0].push(LaserSection(
+            0,
+            vec![slam, GraphSectionPoint::new(KSON_RESOLUTION * 16, 0.0)],
+            1,
+        ));
+        for i in 0..16 {
+            chart.note.bt[if i % 2 == 0 { 0 } else { 3 }].push(Interval {
+                y: i * KSON_RESOLUTION * 2,
+                l: 0,
+            });
+        }
+        chart.beat.bpm.push((KSON_RESOLUTION * 16, 180.0));
+        chart
+    }
+
+    fn assert_radar(actual: Radar, expected: Radar) {
+        let pairs = [
+            (actual.notes, expected.notes),
+            (actual.peak, expected.peak),
+            (actual.lasers, expected.lasers),
+            (actual.slams, expected.slams),
+            (actual.one_hand, expected.one_hand),
+            (actual.hand_trip, expected.hand_trip),
+            (actual.tricky, expected.tricky),
+        ];
+        assert!(
+            pairs.iter().all(|(a, e)| (a - e).abs() < 1e-9),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn radar_stream() {
+        assert_radar(
+            stream_chart().radar(),
+            Radar {
+                notes: 0.17204301075268819,
+                peak: 0.10416666666666667,
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn radar_lasers() {
+        assert_radar(
+            laser_chart().radar(),
+            Radar {
+                notes: 0.10526315789473684,
+                peak: 0.08333333333333333,
+                lasers: 0.3157894736842105,
+                slams: 0.07894736842105263,
+                one_hand: 0.5625,
+                hand_trip: 0.3125,
+                tricky: 0.15789473684210525,
+            },
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! JSON Schema of the kson format, for types with custom serde implementations the schemas are
+//! written by hand to match their serialized forms.
+
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{
+        ArrayValidation, InstanceType, Metadata, RootSchema, Schema, SchemaObject, SingleOrVec,
+        SubschemaValidation,
+    },
+    JsonSchema,
+};
+
+use crate::{
+    camera::{CamPatternInvokeSwing, CamPatternInvokeSwingValue},
+    parameter::EffectParameter,
+    ByPulseOption, Chart, GraphPoint, GraphSectionPoint, Interval, LaserSection,
+};
+
+/// Schema of a complete kson chart
+pub fn chart_schema() -> RootSchema {
+    schemars::schema_for!(Chart)
+}
+
+fn any_of(schemas: Vec<Schema>) -> Schema {
+    SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(schemas),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Array where only the first `required` items have to be present
+fn tuple(items: Vec<Schema>, required: u32) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Array.into()),
+        array: Some(Box::new(ArrayValidation {
+            max_items: Some(items.len() as u32),
+            min_items: Some(required),
+            items: Some(SingleOrVec::Vec(items)),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// `[y, v | [v, vf], [a, b]?]`
+fn graph_point_schema(gen: &mut SchemaGenerator) -> Schema {
+    let value = any_of(vec![
+        gen.subschema_for::<f64>(),
+        gen.subschema_for::<(f64, f64)>(),
+    ]);
+    tuple(
+        vec![
+            gen.subschema_for::<u32>(),
+            value,
+            gen.subschema_for::<(f64, f64)>(),
+        ],
+        2,
+    )
+}
+
+impl JsonSchema for GraphPoint {
+    fn schema_name() -> String {
+        "GraphPoint".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        graph_point_schema(gen)
+    }
+}
+
+impl JsonSchema for GraphSectionPoint {
+    fn schema_name() -> String {
+        "GraphSectionPoint".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        graph_point_schema(gen)
+    }
+}
+
+impl JsonSchema for Interval {
+    fn schema_name() -> String {
+        "Interval".to_string()
+    }
+
+    /// `y` for chips, `[y, l]` for longs
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let y = gen.subschema_for::<u32>();
+        any_of(vec![
+            y.clone(),
+            tuple(vec![y, gen.subschema_for::<u32>()], 1),
+        ])
+    }
+}
+
+impl JsonSchema for LaserSection {
+    fn schema_name() -> String {
+        "LaserSection".to_string()
+    }
+
+    /// `[y, points, wide?]`
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        tuple(
+            vec![
+                gen.subschema_for::<u32>(),
+                gen.subschema_for::<Vec<GraphSectionPoint>>(),
+                gen.subschema_for::<u8>(),
+            ],
+            2,
+        )
+    }
+}
+
+impl JsonSchema for CamPatternInvokeSwing {
+    fn schema_name() -> String {
+        "CamPatternInvokeSwing".to_string()
+    }
+
+    /// `[y, direction, length, value?]`
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        tuple(
+            vec![
+                gen.subschema_for::<u32>(),
+                gen.subschema_for::<i32>(),
+                gen.subschema_for::<u32>(),
+                gen.subschema_for::<CamPatternInvokeSwingValue>(),
+            ],
+            3,
+        )
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for ByPulseOption<T> {
+    fn schema_name() -> String {
+        format!("ByPulseOption_for_{}", T::schema_name())
+    }
+
+    /// `y` without a value, `[y, v]` with one
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let y = gen.subschema_for::<u32>();
+        any_of(vec![y.clone(), tuple(vec![y, gen.subschema_for::<T>()], 1)])
+    }
+}
+
+impl<T> JsonSchema for EffectParameter<T> {
+    fn schema_name() -> String {
+        "EffectParameter".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "Parameter value such as `1/4`, `70%`, `30samples` or `10kHz-20kHz`, \
+                     optionally followed by `>` and the value used while the effect is active"
+                        .to_string(),
+                ),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::chart_schema;
+    use crate::{
+        camera::{CamPatternInvokeSwing, CamPatternInvokeSwingValue},
+        Chart, Ksh,
+    };
+
+    fn assert_valid(chart: &Chart) {
+        let schema = serde_json::to_value(chart_schema()).unwrap();
+        let schema = jsonschema::JSONSchema::compile(&schema).unwrap();
+        let chart = serde_json::to_value(chart).unwrap();
+        let errors: Vec<_> = match schema.validate(&chart) {
+            Ok(()) => vec![],
+            Err(errors) => errors
+                .map(|e| format!("{}: {}", e.instance_path, e))
+                .collect(),
+        };
+        assert!(errors.is_empty(), "{}", errors.join("\n"));
+    }
+
+    #[test]
+    fn empty_chart_validates() {
+        assert_valid(&Chart::new());
+    }
+
+    #[test]
+    fn converted_chart_validates() {
+        let ksh = "\
+title=Schema
+artist=Someone
+effect=Someone else
+jacket=jacket.png
+illustrator=
+difficulty=infinite
+level=17
+t=120-180
+m=song.ogg
+o=25
+po=5000
+plength=15000
+total=200
+filtertype=peak
+--
+beat=4/4
+t=120
+zoom_top=50
+1000|00|0-
+0100|00|--
+0010|10|:-
+0001|10|o-
+fx-l=Retrigger;8
+0000|21|--
+0000|11|-0
+laserrange_r=2x
+0000|00|-:@(192
+0000|00|-o
+--
+beat=3/4
+t=180
+1111|00|0-
+0000|00|o-S)192
+0000|00|--
+0000|00|--
+0000|00|--
+--
+";
+        let mut chart = Chart::from_ksh(ksh).unwrap();
+        chart
+            .camera
+            .cam
+            .pattern
+            .laser
+            .slam_event
+            .swing
+            .push(CamPatternInvokeSwing(
+                1200,
+                1,
+                192,
+                CamPatternInvokeSwingValue {
+                    scale: 2.0,
+                    repeat: 2,
+                    decay_order: 1,
+                },
+            ));
+        assert_valid(&chart);
+
+        let json = serde_json::to_string(&chart).unwrap();
+        let round_trip: Chart = serde_json::from_str(&json).unwrap();
+        assert_valid(&round_trip);
+    }
+}
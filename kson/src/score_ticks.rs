@@ -2,10 +2,23 @@ use crate::*;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum ScoreTick {
-    Laser { lane: usize, pos: f64 },
-    Slam { lane: usize, start: f64, end: f64 },
-    Chip { lane: usize },
-    Hold { lane: usize, start_tick: u32 },
+    Laser {
+        lane: usize,
+        pos: f64,
+    },
+    Slam {
+        lane: usize,
+        start: f64,
+        end: f64,
+    },
+    Chip {
+        lane: usize,
+    },
+    Hold {
+        lane: usize,
+        start_tick: u32,
+        end_tick: u32,
+    },
 }
 
 impl ScoreTick {
@@ -47,7 +60,12 @@ pub struct ScoreTickSummary {
 
 pub trait ScoreTicker {
     fn summary(&self) -> ScoreTickSummary;
+    /// Number of ticks at or before `y`
     fn get_combo_at(&self, y: u32) -> u32;
+    /// Ticks from `y0` up to but not including `y1`
+    fn ticks_between(&self, y0: u32, y1: u32) -> &[PlacedScoreTick];
+    /// Ticks of a lane as numbered by [`ScoreTick::global_lane`], BT 0-3, FX 4-5 and lasers 6-7
+    fn ticks_in_lane(&self, lane: usize) -> Vec<PlacedScoreTick>;
 }
 
 fn get_hold_step_at(y: u32, chart: &Chart) -> u32 {
@@ -77,6 +95,7 @@ fn ticks_from_interval(interval: &Interval, lane: usize, chart: &Chart) -> Vec<P
                 tick: ScoreTick::Hold {
                     lane,
                     start_tick: interval.y,
+                    end_tick: interval.y + interval.l,
                 },
             });
             step = get_hold_step_at(y, chart);
@@ -90,6 +109,7 @@ fn ticks_from_interval(interval: &Interval, lane: usize, chart: &Chart) -> Vec<P
                 tick: ScoreTick::Hold {
                     lane,
                     start_tick: interval.y,
+                    end_tick: interval.y + interval.l,
                 },
             })
         }
@@ -220,7 +240,8 @@ pub fn generate_score_ticks(chart: &Chart) -> ScoreTicks {
             .collect(),
     );
 
-    res.sort_by(|pa, pb| pa.y.cmp(&pb.y));
+    // Stable so ticks sharing a lane and y keep the order they were generated in
+    res.sort_by_key(|t| (t.y, t.tick.global_lane()));
 
     res
 }
@@ -243,9 +264,68 @@ impl ScoreTicker for ScoreTicks {
     }
 
     fn get_combo_at(&self, y: u32) -> u32 {
-        match self.binary_search_by(|f| f.y.cmp(&y)) {
-            Ok(c) => c as u32 + 1,
-            Err(c) => c as u32,
+        self.partition_point(|t| t.y <= y) as u32
+    }
+
+    fn ticks_between(&self, y0: u32, y1: u32) -> &[PlacedScoreTick] {
+        let start = self.partition_point(|t| t.y < y0);
+        let end = self.partition_point(|t| t.y < y1).max(start);
+        &self[start..end]
+    }
+
+    fn ticks_in_lane(&self, lane: usize) -> Vec<PlacedScoreTick> {
+        self.iter()
+            .filter(|t| t.tick.global_lane() == lane)
+            .copied()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_score_ticks, ScoreTick, ScoreTicker};
+    use crate::{Chart, Interval};
+
+    #[test]
+    fn combo_queries() {
+        let empty = generate_score_ticks(&Chart::new());
+        assert_eq!(empty.get_combo_at(0), 0);
+        assert_eq!(empty.get_combo_at(u32::MAX), 0);
+        assert!(empty.ticks_between(0, 1000).is_empty());
+        assert!(empty.ticks_in_lane(0).is_empty());
+
+        let mut chart = Chart::new();
+        // Chips on every lane at the same time, then one more on BT-A
+        for lane in chart.note.bt.iter_mut() {
+            lane.push(Interval { y: 480, l: 0 });
         }
+        chart.note.fx[1].push(Interval { y: 480, l: 0 });
+        chart.note.bt[0].push(Interval { y: 960, l: 0 });
+        let ticks = generate_score_ticks(&chart);
+
+        assert_eq!(
+            ticks
+                .iter()
+                .map(|t| t.tick.global_lane())
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 5, 0]
+        );
+        assert_eq!(ticks.get_combo_at(0), 0);
+        assert_eq!(ticks.get_combo_at(479), 0);
+        assert_eq!(ticks.get_combo_at(480), 5);
+        assert_eq!(ticks.get_combo_at(959), 5);
+        assert_eq!(ticks.get_combo_at(960), 6);
+        assert_eq!(ticks.get_combo_at(u32::MAX), 6);
+
+        assert_eq!(ticks.ticks_between(480, 960).len(), 5);
+        assert_eq!(ticks.ticks_between(481, 961).len(), 1);
+        assert!(ticks.ticks_between(960, 480).is_empty());
+
+        let bt_a = ticks.ticks_in_lane(0);
+        assert_eq!(bt_a.len(), 2);
+        assert!(bt_a
+            .iter()
+            .all(|t| matches!(t.tick, ScoreTick::Chip { lane: 0 })));
+        assert!(ticks.ticks_in_lane(4).is_empty());
     }
 }
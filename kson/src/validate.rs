@@ -0,0 +1,150 @@
+use crate::{Chart, Interval};
+
+/// Problem found in a chart, located at tick `y`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChartIssue {
+    pub y: u32,
+    pub message: String,
+}
+
+impl ChartIssue {
+    fn new(y: u32, message: impl Into<String>) -> Self {
+        Self {
+            y,
+            message: message.into(),
+        }
+    }
+}
+
+fn lane_issues(notes: &[Interval], lane: &str, issues: &mut Vec<ChartIssue>) {
+    for pair in notes.windows(2) {
+        if pair[1].y < pair[0].y {
+            issues.push(ChartIssue::new(
+                pair[1].y,
+                format!("{lane} notes out of order"),
+            ));
+        } else if pair[1].y < pair[0].y + pair[0].l || pair[1].y == pair[0].y {
+            issues.push(ChartIssue::new(
+                pair[1].y,
+                format!("Overlapping {lane} notes"),
+            ));
+        }
+    }
+}
+
+/// Finds problems in a chart that would make it play differently than intended
+pub fn validate(chart: &Chart) -> Vec<ChartIssue> {
+    let mut issues = vec![];
+
+    match chart.beat.bpm.first() {
+        None => issues.push(ChartIssue::new(0, "No BPM set")),
+        Some((y, _)) if *y > 0 => issues.push(ChartIssue::new(*y, "No BPM set at the start")),
+        _ => {}
+    }
+    if chart.beat.time_sig.is_empty() {
+        issues.push(ChartIssue::new(0, "No time signature set"));
+    }
+    for (y, bpm) in &chart.beat.bpm {
+        if !bpm.is_finite() || *bpm <= 0.0 {
+            issues.push(ChartIssue::new(*y, format!("Invalid BPM {bpm}")));
+        }
+    }
+    for (measure, sig) in &chart.beat.time_sig {
+        // Measure positions can't be calculated with a broken time signature
+        if sig.0 == 0 || sig.1 == 0 {
+            issues.push(ChartIssue::new(
+                0,
+                format!(
+                    "Invalid time signature {}/{} in measure {}",
+                    sig.0,
+                    sig.1,
+                    measure + 1
+                ),
+            ));
+        }
+    }
+
+    for (lane, notes) in ["A", "B", "C", "D"].iter().zip(&chart.note.bt) {
+        lane_issues(notes, &format!("BT-{lane}"), &mut issues);
+    }
+    for (lane, notes) in ["L", "R"].iter().zip(&chart.note.fx) {
+        lane_issues(notes, &format!("FX-{lane}"), &mut issues);
+    }
+
+    for (side, sections) in ["Left", "Right"].iter().zip(&chart.note.laser) {
+        for section in sections {
+            let Some(first) = section.1.first() else {
+                issues.push(ChartIssue::new(
+                    section.tick(),
+                    format!("Empty {side} laser"),
+                ));
+                continue;
+            };
+            if section.1.len() < 2 && first.vf.is_none() {
+                issues.push(ChartIssue::new(
+                    section.tick(),
+                    format!("{side} laser with a single point"),
+                ));
+            }
+            if section.1.windows(2).any(|p| p[1].ry < p[0].ry) {
+                issues.push(ChartIssue::new(
+                    section.tick(),
+                    format!("{side} laser points out of order"),
+                ));
+            }
+            let out_of_range = |v: f64| !(0.0..=1.0).contains(&v);
+            if let Some(p) = section
+                .1
+                .iter()
+                .find(|p| out_of_range(p.v) || p.vf.is_some_and(out_of_range))
+            {
+                issues.push(ChartIssue::new(
+                    section.tick() + p.ry,
+                    format!("{side} laser outside of the track"),
+                ));
+            }
+        }
+        for pair in sections.windows(2) {
+            let end = pair[0].tick() + pair[0].last().map(|p| p.ry).unwrap_or(0);
+            if pair[1].tick() < end {
+                issues.push(ChartIssue::new(
+                    pair[1].tick(),
+                    format!("Overlapping {side} lasers"),
+                ));
+            }
+        }
+    }
+
+    issues.sort_by_key(|i| i.y);
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate;
+    use crate::{Chart, Interval, TimeSignature};
+
+    #[test]
+    fn finds_overlaps() {
+        let mut chart = Chart::new();
+        chart.beat.bpm.push((0, 120.0));
+        chart.beat.time_sig.push((0, TimeSignature(4, 4)));
+        assert!(validate(&chart).is_empty());
+
+        chart.note.bt[1] = vec![Interval { y: 0, l: 480 }, Interval { y: 240, l: 0 }];
+        chart.note.fx[0] = vec![Interval { y: 960, l: 0 }, Interval { y: 960, l: 0 }];
+        let issues = validate(&chart);
+        assert_eq!(issues.len(), 2);
+        assert_eq!(
+            (issues[0].y, issues[0].message.as_str()),
+            (240, "Overlapping BT-B notes")
+        );
+        assert_eq!(
+            (issues[1].y, issues[1].message.as_str()),
+            (960, "Overlapping FX-L notes")
+        );
+
+        chart.beat.time_sig.clear();
+        assert_eq!(validate(&chart)[0].message, "No time signature set");
+    }
+}